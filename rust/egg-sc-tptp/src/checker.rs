@@ -0,0 +1,619 @@
+use std::collections::HashMap;
+
+use crate::error::EggError;
+use crate::fol::{Formula, Sequent, Term};
+use crate::printer::{equals, Goal, RewriteRule, SCTPTPRule, TPTPProblem};
+
+// replays the SC-TPTP steps produced by the printer and checks that each one is locally valid
+
+/// Check every step of `steps` against its premises and the final sequent against `goal`
+pub fn check_proof(
+    problem: &TPTPProblem,
+    goal: &Goal,
+    steps: &[SCTPTPRule],
+) -> Result<(), EggError> {
+    let mut known: HashMap<String, Sequent> = HashMap::new();
+    for (name, rule) in &problem.axioms {
+        // local rules are hypotheses of the conjecture, not steps
+        if !name.starts_with('$') {
+            known.insert(
+                name.clone(),
+                Sequent {
+                    left: vec![],
+                    right: vec![rule_formula(rule)],
+                },
+            );
+        }
+    }
+
+    for step in steps {
+        let (name, bot) = name_and_bot(step);
+        check_step(step, &known)
+            .map_err(|e| EggError::InvalidProof(format!("step {} ({}): {}", name, bot, e)))?;
+        known.insert(name.clone(), bot.clone());
+    }
+
+    let last = match steps.last() {
+        Some(step) => name_and_bot(step).1,
+        None => return Err(EggError::InvalidProof("the proof is empty".to_string())),
+    };
+    let stated = goal.stated();
+    if !subset(&last.left, &problem.left, &[&stated]) {
+        return Err(EggError::InvalidProof(format!(
+            "the last step {} depends on formulas that are not hypotheses",
+            last
+        )));
+    }
+    if goal.negation.is_some() && !(last.left.contains(&stated) && last.right.is_empty()) {
+        return Err(EggError::InvalidProof(format!(
+            "the last step {} does not refute {}",
+            last, stated
+        )));
+    }
+    if !goal.simplify && goal.negation.is_none() && !last.right.contains(&goal.formula) {
+        return Err(EggError::InvalidProof(format!(
+            "the last step {} does not prove the conjecture {}",
+            last, goal.formula
+        )));
+    }
+    Ok(())
+}
+
+/// the formula an axiom of the problem stands for
+fn rule_formula(rule: &RewriteRule) -> Formula {
+    let (vars, body) = match rule {
+        RewriteRule::FormulaRule(vars, l, r) => {
+            (vars, Formula::Iff(Box::new(l.clone()), Box::new(r.clone())))
+        }
+        RewriteRule::TermRule(vars, l, r) => (vars, equals(l, r)),
+        RewriteRule::Conditional(condition, inner) => {
+            let formula = rule_formula(inner);
+            let (vars, body) = strip_forall(&formula);
+            let body = Formula::Implies(Box::new(condition.clone()), Box::new(body.clone()));
+            return if vars.is_empty() {
+                body
+            } else {
+                Formula::Forall(vars, Box::new(body))
+            };
+        }
+    };
+    if vars.is_empty() {
+        body
+    } else {
+        Formula::Forall(vars.clone(), Box::new(body))
+    }
+}
+
+fn name_and_bot(step: &SCTPTPRule) -> (&String, &Sequent) {
+    use SCTPTPRule::*;
+    match step {
+        RightTrue { name, bot }
+        | RightRefl { name, bot, .. }
+        | RightReflIff { name, bot, .. }
+        | RightSubst { name, bot, .. }
+        | RightSubstIff { name, bot, .. }
+        | LeftForall { name, bot, .. }
+        | Cut { name, bot, .. }
+        | RightSubstEqForallLocal { name, bot, .. }
+        | RightSubstEqForall { name, bot, .. }
+        | RightSubstIffForallLocal { name, bot, .. }
+        | RightSubstIffForall { name, bot, .. }
+        | Hypothesis { name, bot, .. }
+        | RightImplies { name, bot, .. }
+        | RightIff { name, bot, .. }
+        | LeftImplies { name, bot, .. }
+        | LeftNot { name, bot, .. } => (name, bot),
+    }
+}
+
+fn check_step(step: &SCTPTPRule, known: &HashMap<String, Sequent>) -> Result<(), String> {
+    use SCTPTPRule::*;
+    let premise = |name: &String| {
+        known
+            .get(name)
+            .ok_or_else(|| format!("unknown premise {}", name))
+    };
+    match step {
+        RightTrue { bot, .. } => ensure(
+            bot.right.contains(&Formula::True),
+            "$true is not on the right",
+        ),
+        RightRefl { bot, i, .. } => match at(&bot.right, *i)? {
+            Formula::Predicate(op, args) if op == "=" && args.len() == 2 && args[0] == args[1] => {
+                Ok(())
+            }
+            f => Err(format!("{} is not a reflexive equality", f)),
+        },
+        RightReflIff { bot, i, .. } => match at(&bot.right, *i)? {
+            Formula::Iff(a, b) if a == b => Ok(()),
+            f => Err(format!("{} is not a reflexive biimplication", f)),
+        },
+        Hypothesis { bot, i, .. } => {
+            let f = at(&bot.left, *i)?;
+            ensure(bot.right.contains(f), "the hypothesis is not on the right")
+        }
+        RightImplies {
+            bot, premise: p, i, ..
+        } => {
+            let p = premise(p)?;
+            match at(&bot.right, *i)? {
+                Formula::Implies(a, b) => ensure(
+                    p.right.contains(b.as_ref())
+                        && subset(&p.left, &bot.left, &[a.as_ref()])
+                        && subset(&p.right, &bot.right, &[b.as_ref()]),
+                    "the premise does not match the implication",
+                ),
+                f => Err(format!("{} is not an implication", f)),
+            }
+        }
+        RightIff {
+            bot,
+            premise1,
+            premise2,
+            i,
+            ..
+        } => {
+            let (p1, p2) = (premise(premise1)?, premise(premise2)?);
+            match at(&bot.right, *i)? {
+                Formula::Iff(a, b) => {
+                    let forward = Formula::Implies(a.clone(), b.clone());
+                    let backward = Formula::Implies(b.clone(), a.clone());
+                    ensure(
+                        p1.right.contains(&forward)
+                            && p2.right.contains(&backward)
+                            && subset(&p1.left, &bot.left, &[])
+                            && subset(&p2.left, &bot.left, &[])
+                            && subset(&p1.right, &bot.right, &[&forward])
+                            && subset(&p2.right, &bot.right, &[&backward]),
+                        "the premises do not prove both implications",
+                    )
+                }
+                f => Err(format!("{} is not a biimplication", f)),
+            }
+        }
+        LeftImplies {
+            bot,
+            premise1,
+            premise2,
+            i,
+            ..
+        } => {
+            let (p1, p2) = (premise(premise1)?, premise(premise2)?);
+            match at(&bot.left, *i)? {
+                Formula::Implies(a, b) => ensure(
+                    p1.right.contains(a.as_ref())
+                        && p2.left.contains(b.as_ref())
+                        && subset(&p1.left, &bot.left, &[])
+                        && subset(&p1.right, &bot.right, &[a.as_ref()])
+                        && subset(&p2.left, &bot.left, &[b.as_ref()])
+                        && subset(&p2.right, &bot.right, &[]),
+                    "the premises do not match the implication",
+                ),
+                f => Err(format!("{} is not an implication", f)),
+            }
+        }
+        LeftNot {
+            bot, premise: p, i, ..
+        } => {
+            let p = premise(p)?;
+            match at(&bot.left, *i)? {
+                Formula::Not(a) => ensure(
+                    p.right.contains(a.as_ref())
+                        && subset(&p.left, &bot.left, &[])
+                        && subset(&p.right, &bot.right, &[a.as_ref()]),
+                    "the premise does not prove the negated formula",
+                ),
+                f => Err(format!("{} is not a negation", f)),
+            }
+        }
+        LeftForall {
+            bot,
+            premise: p,
+            i,
+            t,
+            ..
+        } => {
+            let p = premise(p)?;
+            match at(&bot.left, *i)? {
+                Formula::Forall(vars, body) if !vars.is_empty() => {
+                    let mut map = HashMap::new();
+                    map.insert(vars[0].clone(), t.clone());
+                    let body = substitute(body, &map, &HashMap::new());
+                    let inst = if vars.len() > 1 {
+                        Formula::Forall(vars[1..].to_vec(), Box::new(body))
+                    } else {
+                        body
+                    };
+                    ensure(
+                        p.left.contains(&inst)
+                            && subset(&p.left, &bot.left, &[&inst])
+                            && subset(&p.right, &bot.right, &[]),
+                        &format!("the premise does not contain the instance {}", inst),
+                    )
+                }
+                f => Err(format!("{} is not universally quantified", f)),
+            }
+        }
+        Cut {
+            bot,
+            premise1,
+            premise2,
+            i,
+            ..
+        } => {
+            let (p1, p2) = (premise(premise1)?, premise(premise2)?);
+            let a = at(&p1.right, *i)?;
+            ensure(
+                p2.left.contains(a)
+                    && subset(&p1.left, &bot.left, &[])
+                    && subset(&p1.right, &bot.right, &[a])
+                    && subset(&p2.left, &bot.left, &[a])
+                    && subset(&p2.right, &bot.right, &[]),
+                &format!("the premises do not agree on the cut formula {}", a),
+            )
+        }
+        RightSubst {
+            bot,
+            premise: p,
+            i,
+            flip,
+            phi,
+            v,
+            ..
+        } => {
+            let p = premise(p)?;
+            let (s, t) = match at(&bot.left, *i)? {
+                Formula::Predicate(op, args) if op == "=" && args.len() == 2 => {
+                    if *flip {
+                        (&args[1], &args[0])
+                    } else {
+                        (&args[0], &args[1])
+                    }
+                }
+                f => return Err(format!("{} is not an equality", f)),
+            };
+            let before = fill_term(phi, v, s);
+            let after = fill_term(phi, v, t);
+            check_substitution(p, bot, &before, &after)
+        }
+        RightSubstIff {
+            bot,
+            premise: p,
+            i,
+            flip,
+            phi,
+            v,
+            ..
+        } => {
+            let p = premise(p)?;
+            let (s, t) = match at(&bot.left, *i)? {
+                Formula::Iff(a, b) => {
+                    if *flip {
+                        (b, a)
+                    } else {
+                        (a, b)
+                    }
+                }
+                f => return Err(format!("{} is not a biimplication", f)),
+            };
+            let before = fill_formula(phi, v, s);
+            let after = fill_formula(phi, v, t);
+            check_substitution(p, bot, &before, &after)
+        }
+        RightSubstEqForall {
+            bot,
+            premise1,
+            premise2,
+            phi,
+            v,
+            ..
+        }
+        | RightSubstIffForall {
+            bot,
+            premise1,
+            premise2,
+            phi,
+            v,
+            ..
+        } => {
+            let (p1, p2) = (premise(premise1)?, premise(premise2)?);
+            let source = p1
+                .right
+                .first()
+                .ok_or_else(|| format!("{} proves nothing", premise1))?;
+            ensure(
+                subset(&p2.left, &bot.left, &[]),
+                "the premise has extra hypotheses",
+            )?;
+            check_instance(source, phi, v, &p2.right, &bot.right)
+        }
+        RightSubstEqForallLocal {
+            bot,
+            premise: p,
+            i,
+            phi,
+            v,
+            ..
+        }
+        | RightSubstIffForallLocal {
+            bot,
+            premise: p,
+            i,
+            phi,
+            v,
+            ..
+        } => {
+            let p = premise(p)?;
+            let source = at(&bot.left, *i)?;
+            ensure(
+                subset(&p.left, &bot.left, &[]),
+                "the premise has extra hypotheses",
+            )?;
+            check_instance(source, phi, v, &p.right, &bot.right)
+        }
+    }
+}
+
+fn ensure(condition: bool, message: &str) -> Result<(), String> {
+    if condition {
+        Ok(())
+    } else {
+        Err(message.to_string())
+    }
+}
+
+fn at(formulas: &[Formula], i: i32) -> Result<&Formula, String> {
+    usize::try_from(i)
+        .ok()
+        .and_then(|i| formulas.get(i))
+        .ok_or_else(|| format!("index {} out of bounds", i))
+}
+
+// every formula of `a` is in `b` or in `extra`
+fn subset(a: &[Formula], b: &[Formula], extra: &[&Formula]) -> bool {
+    a.iter().all(|f| b.contains(f) || extra.contains(&f))
+}
+
+// `premise` proves `before`, the conclusion proves `after` in the same context
+fn check_substitution(
+    premise: &Sequent,
+    bot: &Sequent,
+    before: &Formula,
+    after: &Formula,
+) -> Result<(), String> {
+    ensure(
+        premise.right.contains(before),
+        &format!("the premise does not prove {}", before),
+    )?;
+    ensure(
+        bot.right.contains(after),
+        &format!("the conclusion does not contain {}", after),
+    )?;
+    ensure(
+        subset(&premise.left, &bot.left, &[]) && subset(&premise.right, &bot.right, &[before]),
+        "the premise has extra formulas",
+    )
+}
+
+// some formula of `before` and some formula of `after` are `phi` with the two sides of an
+// instance of `source` (a quantified equality or biimplication) in place of `v`
+fn check_instance(
+    source: &Formula,
+    phi: &Formula,
+    v: &str,
+    before: &[Formula],
+    after: &[Formula],
+) -> Result<(), String> {
+    let (vars, body) = strip_forall(source);
+    let (l, r) = match body {
+        Formula::Predicate(op, args) if op == "=" && args.len() == 2 => {
+            (fill_term(phi, v, &args[0]), fill_term(phi, v, &args[1]))
+        }
+        Formula::Iff(a, b) => (fill_formula(phi, v, a), fill_formula(phi, v, b)),
+        f => return Err(format!("{} is not an equality or a biimplication", f)),
+    };
+    for b in before {
+        for a in after {
+            for (from, to) in [(&l, &r), (&r, &l)] {
+                let mut map = HashMap::new();
+                if match_formula(from, b, &vars, &mut map) && match_formula(to, a, &vars, &mut map)
+                {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    Err(format!(
+        "no instance of {} rewrites the premise into the conclusion",
+        source
+    ))
+}
+
+fn strip_forall(formula: &Formula) -> (Vec<String>, &Formula) {
+    match formula {
+        Formula::Forall(vars, body) => {
+            let (mut inner, body) = strip_forall(body);
+            let mut vars = vars.clone();
+            vars.append(&mut inner);
+            (vars, body)
+        }
+        _ => (Vec::new(), formula),
+    }
+}
+
+fn fill_term(phi: &Formula, v: &str, t: &Term) -> Formula {
+    let mut map = HashMap::new();
+    map.insert(v.to_string(), t.clone());
+    substitute(phi, &map, &HashMap::new())
+}
+
+fn fill_formula(phi: &Formula, v: &str, f: &Formula) -> Formula {
+    let mut map = HashMap::new();
+    map.insert(v.to_string(), f.clone());
+    substitute(phi, &HashMap::new(), &map)
+}
+
+// replaces free term variables and nullary predicates, leaving bound names alone
+fn substitute(
+    formula: &Formula,
+    terms: &HashMap<String, Term>,
+    formulas: &HashMap<String, Formula>,
+) -> Formula {
+    let sub = |f: &Formula| Box::new(substitute(f, terms, formulas));
+    match formula {
+        Formula::True | Formula::False => formula.clone(),
+        Formula::Predicate(name, args) if args.is_empty() && formulas.contains_key(name) => {
+            formulas[name].clone()
+        }
+        Formula::Predicate(name, args) => Formula::Predicate(
+            name.clone(),
+            args.iter()
+                .map(|a| Box::new(substitute_term(a, terms)))
+                .collect(),
+        ),
+        Formula::Not(f) => Formula::Not(sub(f)),
+        Formula::And(fs) => Formula::And(fs.iter().map(|f| sub(f)).collect()),
+        Formula::Or(fs) => Formula::Or(fs.iter().map(|f| sub(f)).collect()),
+        Formula::Implies(a, b) => Formula::Implies(sub(a), sub(b)),
+        Formula::Iff(a, b) => Formula::Iff(sub(a), sub(b)),
+        Formula::Forall(vars, f) | Formula::Exists(vars, f) => {
+            let mut terms = terms.clone();
+            vars.iter().for_each(|v| {
+                terms.remove(v);
+            });
+            let body = Box::new(substitute(f, &terms, formulas));
+            match formula {
+                Formula::Forall(..) => Formula::Forall(vars.clone(), body),
+                _ => Formula::Exists(vars.clone(), body),
+            }
+        }
+    }
+}
+
+fn substitute_term(term: &Term, terms: &HashMap<String, Term>) -> Term {
+    let Term::Function(name, args) = term;
+    match terms.get(name) {
+        Some(t) if args.is_empty() => t.clone(),
+        _ => Term::Function(
+            name.clone(),
+            args.iter()
+                .map(|a| Box::new(substitute_term(a, terms)))
+                .collect(),
+        ),
+    }
+}
+
+// matches `pattern` against `formula`, binding only the names in `vars`
+fn match_formula(
+    pattern: &Formula,
+    formula: &Formula,
+    vars: &[String],
+    map: &mut HashMap<String, Term>,
+) -> bool {
+    match (pattern, formula) {
+        (Formula::True, Formula::True) | (Formula::False, Formula::False) => true,
+        (Formula::Predicate(n1, a1), Formula::Predicate(n2, a2)) => {
+            n1 == n2
+                && a1.len() == a2.len()
+                && a1
+                    .iter()
+                    .zip(a2.iter())
+                    .all(|(p, t)| match_term(p, t, vars, map))
+        }
+        (Formula::Not(p), Formula::Not(f)) => match_formula(p, f, vars, map),
+        (Formula::And(ps), Formula::And(fs)) | (Formula::Or(ps), Formula::Or(fs)) => {
+            ps.len() == fs.len()
+                && ps
+                    .iter()
+                    .zip(fs.iter())
+                    .all(|(p, f)| match_formula(p, f, vars, map))
+        }
+        (Formula::Implies(p1, p2), Formula::Implies(f1, f2))
+        | (Formula::Iff(p1, p2), Formula::Iff(f1, f2)) => {
+            match_formula(p1, f1, vars, map) && match_formula(p2, f2, vars, map)
+        }
+        (Formula::Forall(v1, p), Formula::Forall(v2, f))
+        | (Formula::Exists(v1, p), Formula::Exists(v2, f)) => {
+            let free: Vec<String> = vars.iter().filter(|v| !v1.contains(v)).cloned().collect();
+            v1 == v2 && match_formula(p, f, &free, map)
+        }
+        _ => false,
+    }
+}
+
+fn match_term(
+    pattern: &Term,
+    term: &Term,
+    vars: &[String],
+    map: &mut HashMap<String, Term>,
+) -> bool {
+    let (Term::Function(n1, a1), Term::Function(n2, a2)) = (pattern, term);
+    if a1.is_empty() && vars.contains(n1) {
+        match map.get(n1) {
+            Some(bound) => bound == term,
+            None => {
+                map.insert(n1.clone(), term.clone());
+                true
+            }
+        }
+    } else {
+        n1 == n2
+            && a1.len() == a2.len()
+            && a1
+                .iter()
+                .zip(a2.iter())
+                .all(|(p, t)| match_term(p, t, vars, map))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ScratchDir;
+    use crate::translator::{parse_tptp_problem, tptp_problem_to_tptp_solution, RunnerOptions};
+
+    fn apply(name: &str, args: Vec<Term>) -> Term {
+        Term::Function(name.to_string(), args.into_iter().map(Box::new).collect())
+    }
+
+    fn refl(formula: Formula) -> SCTPTPRule {
+        SCTPTPRule::RightRefl {
+            name: "f0".to_string(),
+            bot: Sequent {
+                left: vec![],
+                right: vec![formula],
+            },
+            i: 0,
+        }
+    }
+
+    #[test]
+    fn emitted_proofs_pass_and_invalid_steps_are_reported() {
+        let dir = ScratchDir::new("checker");
+        let input = dir.write(
+            "problem.p",
+            "fof(a, axiom, ![X] : f(X) = X).\nfof(c, conjecture, f(f(b)) = b).\n",
+        );
+        let output = input.with_file_name("solution.p");
+        let options = RunnerOptions::default();
+        // both levels of steps are replayed
+        for level1 in [false, true] {
+            assert_eq!(
+                tptp_problem_to_tptp_solution(&input, &output, level1, &options, true, &[]),
+                Ok("Theorem")
+            );
+        }
+
+        let problem = parse_tptp_problem(&input).unwrap();
+        let goal = &problem.goals[0];
+        let b = apply("b", vec![]);
+        let ffb = apply("f", vec![apply("f", vec![b.clone()])]);
+        let err = check_proof(&problem, goal, &[refl(equals(&ffb, &b))]).unwrap_err();
+        assert!(matches!(err, EggError::InvalidProof(_)));
+        assert!(err.to_string().contains("is not a reflexive equality"));
+        // a valid step, but not a proof of the conjecture
+        let err = check_proof(&problem, goal, &[refl(equals(&b, &b))]).unwrap_err();
+        assert!(err.to_string().contains("does not prove the conjecture"));
+        assert!(check_proof(&problem, goal, &[]).is_err());
+    }
+}
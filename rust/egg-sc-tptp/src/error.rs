@@ -0,0 +1,65 @@
+use std::fmt;
+
+// errors reported by egg-sc-tptp instead of panicking
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EggError {
+    /// the input could not be read
+    Io(String),
+    /// the input is not valid TPTP, with the line of the failure if known
+    Parse {
+        line: Option<usize>,
+        message: String,
+    },
+    /// the problem uses a construct outside the supported fragment
+    Unsupported(String),
+    /// the conjecture was not proved within the limits
+    NotProved(String),
+    /// the emitted proof failed the internal check
+    InvalidProof(String),
+}
+
+impl EggError {
+    /// SZS status reported for this error
+    pub fn szs_status(&self) -> &'static str {
+        match self {
+            EggError::Io(_) => "OSError",
+            EggError::Parse { .. } => "SyntaxError",
+            EggError::Unsupported(_) => "Inappropriate",
+            EggError::NotProved(_) => "GaveUp",
+            EggError::InvalidProof(_) => "Error",
+        }
+    }
+
+    /// process exit code for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            EggError::Io(_) => 5,
+            EggError::Parse { .. } => 2,
+            EggError::Unsupported(_) => 3,
+            EggError::NotProved(_) => 4,
+            EggError::InvalidProof(_) => 6,
+        }
+    }
+}
+
+impl fmt::Display for EggError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EggError::Io(msg) => write!(f, "I/O error: {}", msg),
+            EggError::Parse {
+                line: Some(line),
+                message,
+            } => write!(f, "parse error at line {}: {}", line, message),
+            EggError::Parse {
+                line: None,
+                message,
+            } => write!(f, "parse error: {}", message),
+            EggError::Unsupported(msg) => write!(f, "unsupported: {}", msg),
+            EggError::NotProved(msg) => write!(f, "not proved: {}", msg),
+            EggError::InvalidProof(msg) => write!(f, "invalid proof: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EggError {}
@@ -298,6 +298,10 @@ pub fn matching_formula(
             matching_formula(formula1, formula1_2, map)
                 && matching_formula(formula2, formula2_2, map)
         }
+        (Formula::Forall(vars, formula), Formula::Forall(vars2, formula2))
+        | (Formula::Exists(vars, formula), Formula::Exists(vars2, formula2)) => {
+            vars == vars2 && matching_formula(formula, formula2, map)
+        }
         _ => false,
     }
 }
@@ -315,6 +319,9 @@ define_language! {
     "or" = Or([Id; 2]),
     "=>" = Implies([Id; 2]),
     "<=>" = Iff([Id; 2]),
+    // binders: the first child is the bound variable as a constant leaf, the second the body
+    "!" = Forall([Id; 2]),
+    "?" = Exists([Id; 2]),
     Function(Symbol, Vec<Id>),
     Predicate(Symbol, Vec<Id>),
   }
@@ -371,23 +378,48 @@ pub fn formula_to_recexpr(formula: &Formula, expr: &mut RecExpr<FOLLang>) -> Id
             let formula2_id = formula_to_recexpr(formula2, expr);
             expr.add(FOLLang::Iff([formula1_id, formula2_id]))
         }
-        Formula::Forall(_vars, _formula) => {
-            panic!("Forall not implemented yet")
+        Formula::Forall(vars, formula) => {
+            let body_id = formula_to_recexpr(formula, expr);
+            bind_vars(vars, body_id, expr, |v, b| FOLLang::Forall([v, b]))
         }
-        Formula::Exists(_vars, _formula) => {
-            panic!("Exists not implemented yet")
+        Formula::Exists(vars, formula) => {
+            let body_id = formula_to_recexpr(formula, expr);
+            bind_vars(vars, body_id, expr, |v, b| FOLLang::Exists([v, b]))
         }
     }
 }
 
+// wrap `body_id` in one binder node per variable, innermost binder for the last variable
+fn bind_vars(
+    vars: &[String],
+    body_id: Id,
+    expr: &mut RecExpr<FOLLang>,
+    binder: impl Fn(Id, Id) -> FOLLang,
+) -> Id {
+    vars.iter().rev().fold(body_id, |body, var| {
+        let var_id = expr.add(FOLLang::Function(Symbol::from(var.clone()), Vec::new()));
+        expr.add(binder(var_id, body))
+    })
+}
+
 pub fn term_to_recexpr_pattern(
     term: &Term,
     vars: &Vec<String>,
     expr: &mut RecExpr<ENodeOrVar<FOLLang>>,
+) -> Id {
+    term_to_recexpr_pattern_bound(term, vars, &[], expr)
+}
+
+// variables in `bound` are bound by a quantifier inside the pattern and stay literal
+fn term_to_recexpr_pattern_bound(
+    term: &Term,
+    vars: &Vec<String>,
+    bound: &[String],
+    expr: &mut RecExpr<ENodeOrVar<FOLLang>>,
 ) -> Id {
     match term {
         Term::Function(name, args) => {
-            if is_variable(name) && args.is_empty() {
+            if is_variable(name) && args.is_empty() && !bound.contains(name) {
                 expr.add(ENodeOrVar::Var(
                     egg::Var::from_str(&format!("?{}", name))
                         .expect(&format!("incorrect variable name: {}", name)),
@@ -395,7 +427,7 @@ pub fn term_to_recexpr_pattern(
             } else {
                 let args_ids = args
                     .iter()
-                    .map(|x| term_to_recexpr_pattern(x, vars, expr))
+                    .map(|x| term_to_recexpr_pattern_bound(x, vars, bound, expr))
                     .collect::<Vec<Id>>();
                 expr.add(ENodeOrVar::ENode(FOLLang::Function(
                     Symbol::from(name.clone()),
@@ -410,6 +442,15 @@ pub fn formula_to_recexpr_pattern(
     formula: &Formula,
     vars: &Vec<String>,
     expr: &mut RecExpr<ENodeOrVar<FOLLang>>,
+) -> Id {
+    formula_to_recexpr_pattern_bound(formula, vars, &[], expr)
+}
+
+fn formula_to_recexpr_pattern_bound(
+    formula: &Formula,
+    vars: &Vec<String>,
+    bound: &[String],
+    expr: &mut RecExpr<ENodeOrVar<FOLLang>>,
 ) -> Id {
     match formula {
         Formula::True => expr.add(ENodeOrVar::ENode(FOLLang::True)),
@@ -417,7 +458,7 @@ pub fn formula_to_recexpr_pattern(
         Formula::Predicate(name, args) => {
             let args_ids = args
                 .iter()
-                .map(|x| term_to_recexpr_pattern(x, vars, expr))
+                .map(|x| term_to_recexpr_pattern_bound(x, vars, bound, expr))
                 .collect::<Vec<Id>>();
             expr.add(ENodeOrVar::ENode(FOLLang::Predicate(
                 Symbol::from(name.clone()),
@@ -425,13 +466,13 @@ pub fn formula_to_recexpr_pattern(
             )))
         }
         Formula::Not(formula) => {
-            let inner_id = formula_to_recexpr_pattern(formula, vars, expr);
+            let inner_id = formula_to_recexpr_pattern_bound(formula, vars, bound, expr);
             expr.add(ENodeOrVar::ENode(FOLLang::Not(inner_id)))
         }
         Formula::And(formulas) => {
             let formulas_ids = formulas
                 .iter()
-                .map(|x| formula_to_recexpr_pattern(x, vars, expr))
+                .map(|x| formula_to_recexpr_pattern_bound(x, vars, bound, expr))
                 .collect::<Vec<Id>>();
             expr.add(ENodeOrVar::ENode(FOLLang::And([
                 formulas_ids[0],
@@ -441,7 +482,7 @@ pub fn formula_to_recexpr_pattern(
         Formula::Or(formulas) => {
             let formulas_ids = formulas
                 .iter()
-                .map(|x| formula_to_recexpr_pattern(x, vars, expr))
+                .map(|x| formula_to_recexpr_pattern_bound(x, vars, bound, expr))
                 .collect::<Vec<Id>>();
             expr.add(ENodeOrVar::ENode(FOLLang::Or([
                 formulas_ids[0],
@@ -449,27 +490,46 @@ pub fn formula_to_recexpr_pattern(
             ])))
         }
         Formula::Implies(formula1, formula2) => {
-            let formula1_id = formula_to_recexpr_pattern(formula1, vars, expr);
-            let formula2_id = formula_to_recexpr_pattern(formula2, vars, expr);
+            let formula1_id = formula_to_recexpr_pattern_bound(formula1, vars, bound, expr);
+            let formula2_id = formula_to_recexpr_pattern_bound(formula2, vars, bound, expr);
             expr.add(ENodeOrVar::ENode(FOLLang::Implies([
                 formula1_id,
                 formula2_id,
             ])))
         }
         Formula::Iff(formula1, formula2) => {
-            let formula1_id = formula_to_recexpr_pattern(formula1, vars, expr);
-            let formula2_id = formula_to_recexpr_pattern(formula2, vars, expr);
+            let formula1_id = formula_to_recexpr_pattern_bound(formula1, vars, bound, expr);
+            let formula2_id = formula_to_recexpr_pattern_bound(formula2, vars, bound, expr);
             expr.add(ENodeOrVar::ENode(FOLLang::Iff([formula1_id, formula2_id])))
         }
-        Formula::Forall(_vars, _formula) => {
-            panic!("Forall not implemented yet")
+        Formula::Forall(bvars, body) => {
+            let inner_bound = [bound, bvars.as_slice()].concat();
+            let body_id = formula_to_recexpr_pattern_bound(body, vars, &inner_bound, expr);
+            bind_vars_pattern(bvars, body_id, expr, |v, b| FOLLang::Forall([v, b]))
         }
-        Formula::Exists(_vars, _formula) => {
-            panic!("Exists not implemented yet")
+        Formula::Exists(bvars, body) => {
+            let inner_bound = [bound, bvars.as_slice()].concat();
+            let body_id = formula_to_recexpr_pattern_bound(body, vars, &inner_bound, expr);
+            bind_vars_pattern(bvars, body_id, expr, |v, b| FOLLang::Exists([v, b]))
         }
     }
 }
 
+fn bind_vars_pattern(
+    vars: &[String],
+    body_id: Id,
+    expr: &mut RecExpr<ENodeOrVar<FOLLang>>,
+    binder: impl Fn(Id, Id) -> FOLLang,
+) -> Id {
+    vars.iter().rev().fold(body_id, |body, var| {
+        let var_id = expr.add(ENodeOrVar::ENode(FOLLang::Function(
+            Symbol::from(var.clone()),
+            Vec::new(),
+        )));
+        expr.add(ENodeOrVar::ENode(binder(var_id, body)))
+    })
+}
+
 // Translator from tptp parser
 
 pub mod tptp_fol_translator {
@@ -771,3 +831,54 @@ pub mod tptp_fol_translator {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // p(x, y) for two constants or variables
+    fn p(x: &str, y: &str) -> Formula {
+        let leaf = |name: &str| Box::new(Term::Function(name.to_string(), Vec::new()));
+        Formula::Predicate("p".to_string(), vec![leaf(x), leaf(y)])
+    }
+
+    fn vars(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn quantifiers_become_one_binder_per_variable() {
+        let formula = Formula::Forall(vars(&["X", "Y"]), Box::new(p("X", "Y")));
+        let mut expr = RecExpr::default();
+        formula_to_recexpr(&formula, &mut expr);
+        assert_eq!(expr.to_string(), "(! X (! Y (p X Y)))");
+
+        // in a pattern the bound variable stays literal, the free one does not
+        let body = Formula::Exists(vars(&["Y"]), Box::new(p("X", "Y")));
+        let mut pattern = RecExpr::default();
+        formula_to_recexpr_pattern(&body, &vars(&["X"]), &mut pattern);
+        assert_eq!(pattern.to_string(), "(? Y (p ?X Y))");
+    }
+
+    #[test]
+    fn quantified_formulas_match_with_the_same_bound_variables() {
+        let pattern = Formula::Forall(vars(&["Y"]), Box::new(p("X", "Y")));
+        let mut map = HashMap::new();
+        assert!(matching_formula(
+            &pattern,
+            &Formula::Forall(vars(&["Y"]), Box::new(p("c", "Y"))),
+            &mut map
+        ));
+        assert_eq!(map["X"], Term::Function("c".to_string(), Vec::new()));
+        assert!(!matching_formula(
+            &pattern,
+            &Formula::Forall(vars(&["Z"]), Box::new(p("c", "Z"))),
+            &mut HashMap::new()
+        ));
+        assert!(!matching_formula(
+            &pattern,
+            &Formula::Exists(vars(&["Y"]), Box::new(p("c", "Y"))),
+            &mut HashMap::new()
+        ));
+    }
+}
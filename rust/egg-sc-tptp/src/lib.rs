@@ -1,3 +1,49 @@
+//! Equational proofs in SC-TPTP format with egg.
+//!
+//! The axioms of a TPTP problem become rewrite rules, the goals are proved by equality
+//! saturation, and egg's explanations are printed as SC-TPTP steps:
+//!
+//! ```no_run
+//! use egg_sc_tptp::{parse_tptp_problem, proof_to_steps, proof_to_tptp, solve_tptp_problem};
+//! use egg_sc_tptp::{solution_header, RunnerOptions};
+//!
+//! let path = std::path::PathBuf::from("problem.p");
+//! let problem = parse_tptp_problem(&path)?;
+//! let mut solution = solve_tptp_problem(&problem, &RunnerOptions::default())?;
+//! let header = format!("{}", solution_header(&problem.header, problem.szs_status()));
+//! // conditions of conditional axioms are not explained here
+//! let mut no_conditions = |_: &egg_sc_tptp::fol::Formula| Vec::new();
+//! for (goal, explanation) in problem.goals.iter().zip(solution.explanations.iter_mut()) {
+//!     let proof = explanation.as_mut().unwrap().make_flat_explanation().clone();
+//!     let steps = proof_to_steps(&proof, &problem, goal, false, &mut no_conditions);
+//!     println!("{}", proof_to_tptp(&header, &steps));
+//! }
+//! # Ok::<(), egg_sc_tptp::EggError>(())
+//! ```
+//!
+//! `solve_to_tptp` and `tptp_problem_to_tptp_solution` do all of this, including the
+//! conditions, status lines and multiple goals.
+//!
+//! Rewrite chains found by other provers, such as the proofs of Twee, are printed the same way
+//! with `chain_to_steps`; `TPTPProblem::add_lemma` makes a proved lemma usable by later chains.
+
+pub mod checker;
+pub mod error;
 pub mod fol;
 pub mod printer;
 pub mod translator;
+
+pub use checker::check_proof;
+pub use error::EggError;
+pub use printer::{
+    chain_to_flat_explanation, chain_to_steps, proof_to_steps, proof_to_tptp, remove_detours,
+    steps_to_tptp, ChainRewrite, Goal, Negation, TPTPProblem,
+};
+pub use translator::{
+    parse_tptp_formula, parse_tptp_problem, parse_tptp_problem_with_includes, solution_header,
+    solve_to_tptp, solve_tptp_problem, tptp_problem_to_tptp_solution, ExtractionCost, RuleMode,
+    RunnerOptions, Solution,
+};
+
+#[cfg(test)]
+mod test_support;
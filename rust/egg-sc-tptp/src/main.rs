@@ -1,21 +1,7 @@
-#![allow(unused_imports)]
-#![allow(dead_code)]
-
-mod fol;
-mod printer;
-mod translator;
-use printer::*;
-use translator::*;
-
-use egg::{rewrite as rw, *};
-use tptp::common::*;
-use tptp::fof;
-use tptp::top;
-use tptp::TPTPIterator;
+use egg_sc_tptp::translator::parse_rule_mode;
+use egg_sc_tptp::{tptp_problem_to_tptp_solution, ExtractionCost, RuleMode, RunnerOptions};
 
 use std::env;
-use std::io::Read;
-use std::ops::Index;
 
 use clap::Parser;
 
@@ -25,10 +11,65 @@ struct Cli {
     output_path: std::path::PathBuf,
     #[clap(long = "level1", short, action)]
     level1: bool,
+    /// time limit for saturation in seconds
+    #[clap(long = "time-limit")]
+    time_limit: Option<u64>,
+    /// maximum number of e-nodes
+    #[clap(long = "node-limit")]
+    node_limit: Option<usize>,
+    /// maximum number of iterations
+    #[clap(long = "iter-limit")]
+    iter_limit: Option<usize>,
+    /// maximum number of e-classes
+    #[clap(long = "class-limit")]
+    class_limit: Option<usize>,
+    /// search for shorter explanations
+    #[clap(long = "optimize-explanations", action)]
+    optimize_explanations: bool,
+    /// cost function for extraction when simplifying: size or depth
+    #[clap(long = "cost")]
+    cost: Option<ExtractionCost>,
+    /// replay the emitted proof with the internal checker before writing it
+    #[clap(long = "check", action)]
+    check: bool,
+    /// use the axiom NAME as MODE: ltr (the default), both, off or priority
+    #[clap(long = "rule", value_parser = parse_rule_mode)]
+    rules: Vec<(String, RuleMode)>,
+    /// directory to search for included files, after the directory of the problem
+    #[clap(long = "include-dir")]
+    include_dirs: Vec<std::path::PathBuf>,
 }
 
 fn main() {
     env::set_var("RUST_BACKTRACE", "1");
     let cli = Cli::parse();
-    tptp_problem_to_tptp_solution(&cli.input_path, &cli.output_path, cli.level1);
+    // command-line options override those in the EggOptions header
+    let runner_options = RunnerOptions {
+        time_limit: cli.time_limit,
+        node_limit: cli.node_limit,
+        iter_limit: cli.iter_limit,
+        class_limit: cli.class_limit,
+        optimize_explanations: cli.optimize_explanations,
+        cost: cli.cost,
+        rules: cli.rules,
+    };
+    match tptp_problem_to_tptp_solution(
+        &cli.input_path,
+        &cli.output_path,
+        cli.level1,
+        &runner_options,
+        cli.check,
+        &cli.include_dirs,
+    ) {
+        Ok(status) => println!("% SZS status {} for {}", status, cli.input_path.display()),
+        Err(e) => {
+            println!(
+                "% SZS status {} for {}",
+                e.szs_status(),
+                cli.input_path.display()
+            );
+            eprintln!("[ERROR] {}", e);
+            std::process::exit(e.exit_code());
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use crate::error::EggError;
 use crate::fol;
 use crate::fol::instantiate_formula;
 use crate::fol::FOLLang;
@@ -63,10 +64,38 @@ pub fn flat_term_to_formula(expr: &FlatTerm<FOLLang>) -> fol::Formula {
             Box::new(flat_term_to_formula(&expr.children[0])),
             Box::new(flat_term_to_formula(&expr.children[1])),
         ),
+        FOLLang::Forall(_) => fol::Formula::Forall(
+            vec![bound_var_name(&expr.children[0])],
+            Box::new(flat_term_to_formula(&expr.children[1])),
+        ),
+        FOLLang::Exists(_) => fol::Formula::Exists(
+            vec![bound_var_name(&expr.children[0])],
+            Box::new(flat_term_to_formula(&expr.children[1])),
+        ),
         _ => panic!("{} is not a formula", expr.to_string()),
     }
 }
 
+fn bound_var_name(expr: &FlatTerm<FOLLang>) -> String {
+    match expr.node {
+        FOLLang::Function(op, _) => op.to_string(),
+        _ => panic!("{} is not a bound variable", expr.to_string()),
+    }
+}
+
+/// suffix of the rules added right to left for axioms used in both directions
+pub const REVERSED_RULE: &str = "~rev";
+
+// the axiom behind a rewrite and the direction it is applied in, a reversed rule is its axiom
+// applied the other way
+fn applied_rule<T>(rewritten: T, backward: bool, rule: Symbol) -> Option<(T, bool, String)> {
+    let name = rule.to_string();
+    match name.strip_suffix(REVERSED_RULE) {
+        Some(axiom) => Some((rewritten, !backward, axiom.to_string())),
+        None => Some((rewritten, backward, name)),
+    }
+}
+
 pub fn flat_term_to_term_hole(
     expr: &FlatTerm<FOLLang>,
     hole: &String,
@@ -74,20 +103,20 @@ pub fn flat_term_to_term_hole(
     if expr.backward_rule.is_some() {
         (
             fol::Term::Function(hole.to_owned(), Vec::new()),
-            Some((
+            applied_rule(
                 flat_term_to_term(&expr.remove_rewrites()),
                 true,
-                expr.backward_rule.unwrap().to_string().to_owned(),
-            )),
+                expr.backward_rule.unwrap(),
+            ),
         )
     } else if expr.forward_rule.is_some() {
         (
             fol::Term::Function(hole.to_owned(), Vec::new()),
-            Some((
+            applied_rule(
                 flat_term_to_term(&expr.remove_rewrites()),
                 false,
-                expr.forward_rule.unwrap().to_string().to_owned(),
-            )),
+                expr.forward_rule.unwrap(),
+            ),
         )
     } else {
         match expr.node {
@@ -122,20 +151,20 @@ pub fn flat_term_to_formula_hole(
     if expr.backward_rule.is_some() {
         (
             fol::Formula::Predicate(hole.to_owned(), vec![]),
-            Some((
+            applied_rule(
                 TermOrFormula::Formula(flat_term_to_formula(&expr.remove_rewrites())),
                 true,
-                expr.backward_rule.unwrap().to_string().to_owned(),
-            )),
+                expr.backward_rule.unwrap(),
+            ),
         )
     } else if expr.forward_rule.is_some() {
         (
             fol::Formula::Predicate(hole.to_owned(), vec![]),
-            Some((
+            applied_rule(
                 TermOrFormula::Formula(flat_term_to_formula(&expr.remove_rewrites())),
                 false,
-                expr.forward_rule.unwrap().to_string().to_owned(),
-            )),
+                expr.forward_rule.unwrap(),
+            ),
         )
     } else {
         match expr.node {
@@ -206,6 +235,20 @@ pub fn flat_term_to_formula_hole(
                     left.1.or(right.1),
                 )
             }
+            FOLLang::Forall(_) => {
+                let body = flat_term_to_formula_hole(&expr.children[1], hole);
+                (
+                    fol::Formula::Forall(vec![bound_var_name(&expr.children[0])], Box::new(body.0)),
+                    body.1,
+                )
+            }
+            FOLLang::Exists(_) => {
+                let body = flat_term_to_formula_hole(&expr.children[1], hole);
+                (
+                    fol::Formula::Exists(vec![bound_var_name(&expr.children[0])], Box::new(body.0)),
+                    body.1,
+                )
+            }
             _ => panic!("{} is not a formula", expr.to_string()),
         }
     }
@@ -309,6 +352,19 @@ pub enum SCTPTPRule {
         premise2: String,
         i: i32,
     },
+    LeftImplies {
+        name: String,
+        bot: fol::Sequent,
+        premise1: String,
+        premise2: String,
+        i: i32,
+    },
+    LeftNot {
+        name: String,
+        bot: fol::Sequent,
+        premise: String,
+        i: i32,
+    },
 }
 
 impl std::fmt::Display for SCTPTPRule {
@@ -342,7 +398,11 @@ impl std::fmt::Display for SCTPTPRule {
       SCTPTPRule::RightImplies {name, bot, premise, i} =>
         write!(f, "fof({}, plain, {}, inference(rightImplies, [status(thm), {}], [{}])).", name, bot, i, premise),
       SCTPTPRule::RightIff {name, bot, premise1, premise2, i} =>
-        write!(f, "fof({}, plain, {}, inference(rightIff, [status(thm), {}], [{}, {}])).", name, bot, i, premise1, premise2)
+        write!(f, "fof({}, plain, {}, inference(rightIff, [status(thm), {}], [{}, {}])).", name, bot, i, premise1, premise2),
+      SCTPTPRule::LeftImplies {name, bot, premise1, premise2, i} =>
+        write!(f, "fof({}, plain, {}, inference(leftImplies, [status(thm), {}], [{}, {}])).", name, bot, i, premise1, premise2),
+      SCTPTPRule::LeftNot {name, bot, premise, i} =>
+        write!(f, "fof({}, plain, {}, inference(leftNot, [status(thm), {}], [{}])).", name, bot, i, premise)
     }
     }
 }
@@ -351,6 +411,8 @@ impl std::fmt::Display for SCTPTPRule {
 pub enum RewriteRule {
     FormulaRule(Vec<String>, fol::Formula, fol::Formula),
     TermRule(Vec<String>, fol::Term, fol::Term),
+    /// rule that only applies when its condition can be rewritten to `$true`
+    Conditional(fol::Formula, Box<RewriteRule>),
 }
 
 pub fn equals(a: &fol::Term, b: &fol::Term) -> fol::Formula {
@@ -360,28 +422,96 @@ pub fn equals(a: &fol::Term, b: &fol::Term) -> fol::Formula {
     )
 }
 
-pub fn line_to_tptp_level1<F>(
+// puts the condition of a rule, instantiated as far as `map` goes, in front of `formula`
+fn guarded(
+    condition: &Option<fol::Formula>,
+    formula: fol::Formula,
+    map: &HashMap<String, fol::Term>,
+) -> fol::Formula {
+    match condition {
+        Some(c) => fol::Formula::Implies(
+            Box::new(instantiate_formula(c, map, &HashMap::new())),
+            Box::new(formula),
+        ),
+        None => formula,
+    }
+}
+
+// proves `left |- condition` by rewriting `$true` into the condition, returns the last step
+fn condition_to_tptp(
+    condition: &fol::Formula,
+    i: &mut i32,
+    left: &Vec<fol::Formula>,
+    map_rule: &dyn Fn(String) -> RewriteRule,
+    explain_condition: &mut dyn FnMut(&fol::Formula) -> Vec<FlatTerm<FOLLang>>,
+    proof: &mut Vec<SCTPTPRule>,
+) -> String {
+    let chain = explain_condition(condition);
+    *i += 1;
+    proof.push(SCTPTPRule::RightTrue {
+        name: format!("f{}", *i),
+        bot: fol::Sequent {
+            left: left.clone(),
+            right: vec![fol::Formula::True],
+        },
+    });
+    let mut last = fol::Formula::True;
+    for line in chain.iter().skip(1) {
+        last = line_to_tptp_level1(line, i, left, map_rule, explain_condition, proof, last);
+    }
+    format!("f{}", *i)
+}
+
+// `condition => conclusion, left |- res` from the proofs of the condition and of the previous step
+fn left_implies(
+    i: &mut i32,
+    left: &Vec<fol::Formula>,
+    condition: fol::Formula,
+    conclusion: fol::Formula,
+    condition_step: String,
+    res: &fol::Formula,
+) -> SCTPTPRule {
+    *i += 1;
+    let mut newleft = vec![fol::Formula::Implies(
+        Box::new(condition),
+        Box::new(conclusion),
+    )];
+    newleft.append(&mut left.clone());
+    SCTPTPRule::LeftImplies {
+        name: format!("f{}", *i),
+        bot: fol::Sequent {
+            left: newleft,
+            right: vec![res.clone()],
+        },
+        premise1: condition_step,
+        premise2: format!("f{}", *i - 1),
+        i: 0,
+    }
+}
+
+pub fn line_to_tptp_level1(
     line: &FlatTerm<FOLLang>,
     i: &mut i32,
     left: &Vec<fol::Formula>,
-    map_rule: F,
+    map_rule: &dyn Fn(String) -> RewriteRule,
+    explain_condition: &mut dyn FnMut(&fol::Formula) -> Vec<FlatTerm<FOLLang>>,
     proof: &mut Vec<SCTPTPRule>,
     prev: fol::Formula,
-) -> fol::Formula
-where
-    F: Fn(String) -> RewriteRule,
-{
+) -> fol::Formula {
     let line_to_holes = flat_term_to_formula_hole(line, &"HOLE".into());
     let with_hole = line_to_holes.0;
     let _rule = line_to_holes.1;
     let (inner, backward, rule_name) = _rule.unwrap();
     let is_local_rule: bool = rule_name.starts_with("$");
     let res = flat_term_to_formula(&line.clone());
-    let rew_rule = map_rule(rule_name.clone());
+    let (condition, rew_rule) = match map_rule(rule_name.clone()) {
+        RewriteRule::Conditional(condition, inner) => (Some(condition), *inner),
+        rule => (None, rule),
+    };
     //let (variables, rule_left, rule_right) = map_rule(rule_name.clone())
+    let premise = format!("f{}", *i);
 
     let mut match_map = HashMap::new();
-    *i += 1;
     match (rew_rule, inner) {
         (
             RewriteRule::FormulaRule(variables, rule_left, rule_right),
@@ -407,11 +537,35 @@ where
                 }
             };
             //let has_matched: bool = if backward { fol::matching_formula(&rule_left, &inner, &mut match_map) } else { fol::matching_formula(&rule_right, &inner, &mut match_map) };
+            // variables that only occur on the other side of the rule are bound by `res`
+            let mut holemap = HashMap::new();
+            holemap.insert(
+                "HOLE".to_owned(),
+                if backward {
+                    rule_left.clone()
+                } else {
+                    rule_right.clone()
+                },
+            );
+            let subst_form_2 = fol::instantiate_formula(&with_hole, &emptymap_t, &holemap);
+            let mut other_map = match_map.clone();
+            if fol::matching_formula(&subst_form_2, &res, &mut other_map) {
+                match_map = other_map;
+            }
             let subst_form = fol::Formula::Iff(
                 Box::new(instantiate_formula(&rule_left, &match_map, &emptymap_f)),
                 Box::new(instantiate_formula(&rule_right, &match_map, &emptymap_f)),
             );
-            let mut newleft = vec![subst_form];
+            let discharged = match &condition {
+                Some(c) => {
+                    let c = instantiate_formula(c, &match_map, &emptymap_f);
+                    let step = condition_to_tptp(&c, i, left, map_rule, explain_condition, proof);
+                    Some((c, step))
+                }
+                None => None,
+            };
+            *i += 1;
+            let mut newleft = vec![subst_form.clone()];
             newleft.append(&mut left.clone());
             use SCTPTPRule::*;
             let subst_step = RightSubstIff {
@@ -420,7 +574,7 @@ where
                     left: newleft,
                     right: vec![res.clone()],
                 },
-                premise: format!("f{}", *i - 1),
+                premise: premise.clone(),
                 i: 0,
                 flip: backward,
                 phi: with_hole,
@@ -428,6 +582,9 @@ where
             };
             let mut vars: Vec<String> = Vec::new();
             proof.push(subst_step);
+            if let Some((c, step)) = discharged {
+                proof.push(left_implies(i, left, c, subst_form, step, &res));
+            }
             variables.iter().enumerate().rev().for_each(|(nth, v)| {
                 let v_var = fol::Term::Function(v.to_owned(), Vec::new());
                 let inst_term: fol::Term = match_map.get(v as &str).unwrap_or(&v_var).clone();
@@ -437,6 +594,7 @@ where
                     Box::new(instantiate_formula(&rule_left, &match_map, &emptymap_f)),
                     Box::new(instantiate_formula(&rule_right, &match_map, &emptymap_f)),
                 );
+                let new_inner = guarded(&condition, new_inner, &match_map);
                 *i += 1;
                 let new_quant_formula = fol::Formula::Forall(vars.clone(), Box::new(new_inner));
                 let forall_no = if is_local_rule && nth == 0 {
@@ -501,11 +659,35 @@ where
                     panic!("Error: forward {} did not match {}", subst_form_1, &prev);
                 }
             };
+            // variables that only occur on the other side of the rule are bound by `res`
+            let mut holemap = HashMap::new();
+            holemap.insert(
+                "HOLE".to_owned(),
+                if backward {
+                    rule_left.clone()
+                } else {
+                    rule_right.clone()
+                },
+            );
+            let subst_form_2 = fol::instantiate_formula(&with_hole, &holemap, &emptymap_f);
+            let mut other_map = match_map.clone();
+            if fol::matching_formula(&subst_form_2, &res, &mut other_map) {
+                match_map = other_map;
+            }
             let subst_form = equals(
                 &fol::instantiate_term(&rule_left, &match_map),
                 &fol::instantiate_term(&rule_right, &match_map),
             );
-            let mut newleft = vec![subst_form];
+            let discharged = match &condition {
+                Some(c) => {
+                    let c = instantiate_formula(c, &match_map, &emptymap_f);
+                    let step = condition_to_tptp(&c, i, left, map_rule, explain_condition, proof);
+                    Some((c, step))
+                }
+                None => None,
+            };
+            *i += 1;
+            let mut newleft = vec![subst_form.clone()];
             newleft.append(&mut left.clone());
             use SCTPTPRule::*;
             let subst_step = RightSubst {
@@ -514,13 +696,16 @@ where
                     left: newleft,
                     right: vec![res.clone()],
                 },
-                premise: format!("f{}", *i - 1),
+                premise: premise.clone(),
                 i: 0,
                 flip: backward,
                 phi: with_hole,
                 v: "HOLE".to_owned(),
             };
             proof.push(subst_step);
+            if let Some((c, step)) = discharged {
+                proof.push(left_implies(i, left, c, subst_form, step, &res));
+            }
             let mut vars: Vec<String> = Vec::new();
             variables.iter().enumerate().rev().for_each(|(nth, v)| {
                 let v_var = &fol::Term::Function(v.to_owned(), Vec::new());
@@ -531,6 +716,7 @@ where
                     &fol::instantiate_term(&rule_left, &match_map),
                     &fol::instantiate_term(&rule_right, &match_map),
                 );
+                let new_inner = guarded(&condition, new_inner, &match_map);
                 *i += 1;
                 let new_quant_formula = fol::Formula::Forall(vars.clone(), Box::new(new_inner));
                 let forall_no = if is_local_rule && nth == 0 {
@@ -673,12 +859,37 @@ pub fn line_to_tptp_level2(
     };
 }
 
-pub fn proof_to_tptp(
-    header: &String,
+/// Drop the rewrites between two occurrences of the same expression in a flat explanation, such as
+/// a rewrite immediately undone by the next one. Every remaining term still carries the rewrite
+/// that leads to it from the previous one.
+pub fn remove_detours(proof: &[FlatTerm<FOLLang>]) -> Vec<FlatTerm<FOLLang>> {
+    let mut states: Vec<FlatTerm<FOLLang>> = Vec::new();
+    let mut shortened: Vec<FlatTerm<FOLLang>> = Vec::new();
+    for line in proof {
+        let state = line.remove_rewrites();
+        match states.iter().position(|s| *s == state) {
+            // back at an earlier expression, forget the loop in between
+            Some(k) => {
+                states.truncate(k + 1);
+                shortened.truncate(k + 1);
+            }
+            None => {
+                states.push(state);
+                shortened.push(line.clone());
+            }
+        }
+    }
+    shortened
+}
+
+/// Turn the flat explanation of `goal` into SC-TPTP inference steps
+pub fn proof_to_steps(
     proof: &Vec<FlatTerm<FOLLang>>,
     problem: &TPTPProblem,
+    goal: &Goal,
     level1: bool,
-) -> String {
+    explain_condition: &mut dyn FnMut(&fol::Formula) -> Vec<FlatTerm<FOLLang>>,
+) -> Vec<SCTPTPRule> {
     let map_rule = |s: String| {
         problem
             .axioms
@@ -744,7 +955,7 @@ pub fn proof_to_tptp(
                 }]
             }
         }
-        _ if problem.simplify => {
+        _ if goal.simplify => {
             let first_seq = fol::Sequent {
                 left: problem.left.clone(),
                 right: vec![fol::Formula::Iff(
@@ -795,35 +1006,202 @@ pub fn proof_to_tptp(
     let mut i = 0;
 
     let mut proof_vec = Vec::<SCTPTPRule>::new();
-    proof.iter().skip(1).for_each(|line| {
-        let res = if level1 {
+    for line in proof.iter().skip(1) {
+        // discharging the condition of a conditional rule needs level 1 steps
+        let conditional = match flat_term_to_formula_hole(line, &"HOLE".into()).1 {
+            Some((_, _, rule_name)) => {
+                matches!(map_rule(rule_name), RewriteRule::Conditional(..))
+            }
+            None => false,
+        };
+        if level1 || conditional {
             last_formula = line_to_tptp_level1(
                 line,
                 &mut i,
                 &problem.left,
                 &map_rule,
+                explain_condition,
                 &mut proof_vec,
                 last_formula.clone(),
             );
         } else {
-            line_to_tptp_level2(line, &mut i, &problem.left, &mut proof_vec)
+            line_to_tptp_level2(line, &mut i, &problem.left, &mut proof_vec);
+            last_formula = flat_term_to_formula(line);
+        }
+    }
+    let mut steps = first_steps;
+    steps.append(&mut proof_vec);
+    // `left |- s = t` contradicts the disequality `s != t` the problem states
+    if goal.negation.is_some() {
+        let mut newleft = vec![goal.stated()];
+        newleft.append(&mut problem.left.clone());
+        steps.push(SCTPTPRule::LeftNot {
+            name: format!("f{}", i + 1),
+            bot: fol::Sequent {
+                left: newleft,
+                right: vec![],
+            },
+            premise: format!("f{}", i),
+            i: 0,
+        });
+    }
+    steps
+}
+
+/// One rewrite of a chain found by another prover, such as a `= { by ... }` line of a Twee proof:
+/// the axiom `rule` rewrites a single subterm of the previous term into `term`
+#[derive(Clone, Debug)]
+pub struct ChainRewrite {
+    pub term: fol::Term,
+    pub rule: String,
+}
+
+// the flat term of `term`, without rewrites
+fn term_to_flat_term(term: &fol::Term) -> FlatTerm<FOLLang> {
+    match term {
+        fol::Term::Function(name, args) => FlatTerm::new(
+            FOLLang::Function(
+                Symbol::from(name.clone()),
+                (0..args.len()).map(Id::from).collect(),
+            ),
+            args.iter().map(|a| term_to_flat_term(a)).collect(),
+        ),
+    }
+}
+
+// whether `rule` rewrites `before` into `after` right to left, if it rewrites it at all
+fn rule_direction(rule: &RewriteRule, before: &fol::Term, after: &fol::Term) -> Option<bool> {
+    let RewriteRule::TermRule(_, l, r) = rule else {
+        return None;
+    };
+    [(l, r, false), (r, l, true)]
+        .into_iter()
+        .find(|(from, to, _)| {
+            let mut map = HashMap::new();
+            fol::matching_term(from, before, &mut map) && fol::matching_term(to, after, &mut map)
+        })
+        .map(|(_, _, backward)| backward)
+}
+
+// `after` with the rewrite by `rule` that leads to it from `before` marked at the innermost
+// subterm the rule applies to
+fn mark_rewrite(
+    before: &fol::Term,
+    after: &fol::Term,
+    rule: &RewriteRule,
+    rule_name: &str,
+) -> Option<FlatTerm<FOLLang>> {
+    let (fol::Term::Function(f, xs), fol::Term::Function(g, ys)) = (before, after);
+    if f == g && xs.len() == ys.len() {
+        // a rewrite below this node changes exactly one argument
+        let changed: Vec<usize> = (0..xs.len()).filter(|&k| xs[k] != ys[k]).collect();
+        if let [k] = changed[..] {
+            if let Some(child) = mark_rewrite(&xs[k], &ys[k], rule, rule_name) {
+                let mut flat = term_to_flat_term(after);
+                flat.children[k] = child;
+                return Some(flat);
+            }
+        }
+    }
+    let mut flat = term_to_flat_term(after);
+    if rule_direction(rule, before, after)? {
+        flat.backward_rule = Some(Symbol::from(rule_name));
+    } else {
+        flat.forward_rule = Some(Symbol::from(rule_name));
+    }
+    Some(flat)
+}
+
+/// The flat explanation of a chain of rewrites from `start`, in the shape of egg's explanations:
+/// it starts from `start = start` and every line rewrites the right-hand side. The position and
+/// direction of each rewrite are found by matching its rule.
+pub fn chain_to_flat_explanation(
+    problem: &TPTPProblem,
+    start: &fol::Term,
+    chain: &[ChainRewrite],
+) -> Result<Vec<FlatTerm<FOLLang>>, EggError> {
+    let equation = |right: FlatTerm<FOLLang>| {
+        FlatTerm::new(
+            FOLLang::Predicate(Symbol::from("="), vec![Id::from(0), Id::from(1)]),
+            vec![term_to_flat_term(start), right],
+        )
+    };
+    let mut lines = vec![equation(term_to_flat_term(start))];
+    let mut before = start;
+    for (n, rewrite) in chain.iter().enumerate() {
+        let rule = match problem.axioms.iter().find(|axiom| axiom.0 == rewrite.rule) {
+            Some((_, rule @ RewriteRule::TermRule(..))) => rule,
+            Some(_) => {
+                return Err(EggError::Unsupported(format!(
+                    "rewrite {} uses {}, which is not an unconditional equation",
+                    n + 1,
+                    rewrite.rule
+                )))
+            }
+            None => {
+                return Err(EggError::InvalidProof(format!(
+                    "rewrite {} uses {}, which is not an axiom of the problem",
+                    n + 1,
+                    rewrite.rule
+                )))
+            }
         };
-        res
-    });
-    format!(
-        "{}\n{}\n{}",
-        header,
-        first_steps
-            .iter()
-            .map(|step| step.to_string())
-            .collect::<Vec<String>>()
-            .join("\n"),
-        proof_vec
-            .iter()
-            .map(|step| step.to_string())
-            .collect::<Vec<String>>()
-            .join("\n")
-    )
+        let marked = mark_rewrite(before, &rewrite.term, rule, &rewrite.rule).ok_or_else(|| {
+            EggError::InvalidProof(format!(
+                "rewrite {}: {} does not rewrite {} into {}",
+                n + 1,
+                rewrite.rule,
+                before,
+                rewrite.term
+            ))
+        })?;
+        lines.push(equation(marked));
+        before = &rewrite.term;
+    }
+    Ok(lines)
+}
+
+/// Turn a chain of rewrites from `start` that proves `goal` into SC-TPTP inference steps, see
+/// `chain_to_flat_explanation`
+pub fn chain_to_steps(
+    problem: &TPTPProblem,
+    goal: &Goal,
+    start: &fol::Term,
+    chain: &[ChainRewrite],
+    level1: bool,
+) -> Result<Vec<SCTPTPRule>, EggError> {
+    let proof = chain_to_flat_explanation(problem, start, chain)?;
+    // only unconditional rules are accepted, there are no conditions to explain
+    let mut no_conditions = |_: &fol::Formula| Vec::new();
+    Ok(proof_to_steps(
+        &proof,
+        problem,
+        goal,
+        level1,
+        &mut no_conditions,
+    ))
+}
+
+pub fn steps_to_tptp(steps: &[SCTPTPRule]) -> String {
+    steps
+        .iter()
+        .map(|step| step.to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Print `steps` as a TPTP proof file below `header`
+pub fn proof_to_tptp(header: &String, steps: &[SCTPTPRule]) -> String {
+    format!("{}\n{}\n", header, steps_to_tptp(steps))
+}
+
+// the variables of `term` in order of first occurrence
+fn free_variables(term: &fol::Term, vars: &mut Vec<String>) {
+    let fol::Term::Function(name, args) = term;
+    if args.is_empty() && fol::is_variable(name) && !vars.contains(name) {
+        vars.push(name.clone());
+    }
+    args.iter().for_each(|a| free_variables(a, vars));
 }
 
 pub struct TPTPProblem {
@@ -831,7 +1209,134 @@ pub struct TPTPProblem {
     pub header: Header,
     pub axioms: Vec<(String, RewriteRule)>,
     pub left: Vec<fol::Formula>,
-    pub conjecture: (String, fol::Formula),
+    pub goals: Vec<Goal>,
     pub options: Vec<String>,
+}
+
+impl TPTPProblem {
+    /// Add the equation `formula`, proved elsewhere, as the rule `name` with its free variables
+    /// universally quantified, so that later chains can rewrite with it
+    pub fn add_lemma(&mut self, name: &str, formula: &fol::Formula) -> Result<(), EggError> {
+        match formula {
+            fol::Formula::Predicate(op, args) if op == "=" && args.len() == 2 => {
+                let mut vars = Vec::new();
+                args.iter().for_each(|a| free_variables(a, &mut vars));
+                self.axioms.push((
+                    name.to_string(),
+                    RewriteRule::TermRule(vars, *args[0].clone(), *args[1].clone()),
+                ));
+                Ok(())
+            }
+            _ => Err(EggError::Unsupported(format!(
+                "lemma {} must be an equality",
+                name
+            ))),
+        }
+    }
+
+    /// SZS status of the problem once its goals are proved
+    pub fn szs_status(&self) -> &'static str {
+        match self.goals.first() {
+            Some(goal) => goal.szs_status(),
+            None => "Theorem",
+        }
+    }
+}
+
+/// a conjecture to prove, or a formula to simplify
+#[derive(Clone, Debug)]
+pub struct Goal {
+    pub name: String,
+    pub formula: fol::Formula,
     pub simplify: bool,
+    /// set when the problem states the disequality `~formula` instead
+    pub negation: Option<Negation>,
+}
+
+/// how a disequality `s != t` is refuted by proving `s = t`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Negation {
+    /// a negated conjecture, the problem is unsatisfiable
+    NegatedConjecture,
+    /// a conjecture, its negation is a theorem
+    Disequality,
+}
+
+impl Goal {
+    /// the goal as stated in the problem
+    pub fn stated(&self) -> fol::Formula {
+        match self.negation {
+            Some(_) => fol::Formula::Not(Box::new(self.formula.clone())),
+            None => self.formula.clone(),
+        }
+    }
+
+    /// SZS status of the problem once the goal is proved
+    pub fn szs_status(&self) -> &'static str {
+        match self.negation {
+            None => "Theorem",
+            Some(Negation::NegatedConjecture) => "Unsatisfiable",
+            Some(Negation::Disequality) => "CounterTheorem",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(name: &str) -> FlatTerm<FOLLang> {
+        FlatTerm::new(
+            FOLLang::Function(Symbol::from(name), Vec::new()),
+            Vec::new(),
+        )
+    }
+
+    fn rewritten(name: &str, rule: &str) -> FlatTerm<FOLLang> {
+        let mut term = leaf(name);
+        term.forward_rule = Some(Symbol::from(rule));
+        term
+    }
+
+    #[test]
+    fn binder_nodes_convert_back_to_quantifiers() {
+        let body = FlatTerm::new(
+            FOLLang::Predicate(Symbol::from("p"), vec![Id::from(0)]),
+            vec![leaf("X")],
+        );
+        let p_x = fol::Formula::Predicate(
+            "p".to_string(),
+            vec![Box::new(fol::Term::Function("X".to_string(), Vec::new()))],
+        );
+
+        let forall = FlatTerm::new(
+            FOLLang::Forall([Id::from(0), Id::from(1)]),
+            vec![leaf("X"), body.clone()],
+        );
+        assert_eq!(
+            flat_term_to_formula(&forall),
+            fol::Formula::Forall(vec!["X".to_string()], Box::new(p_x.clone()))
+        );
+        let exists = FlatTerm::new(
+            FOLLang::Exists([Id::from(0), Id::from(1)]),
+            vec![leaf("X"), body],
+        );
+        assert_eq!(
+            flat_term_to_formula_hole(&exists, &"HOLE".to_string()).0,
+            fol::Formula::Exists(vec!["X".to_string()], Box::new(p_x))
+        );
+    }
+
+    #[test]
+    fn detours_back_to_an_earlier_term_are_removed() {
+        let proof = vec![
+            leaf("a"),
+            rewritten("b", "ab"),
+            rewritten("a", "ba"),
+            rewritten("c", "ac"),
+        ];
+        assert!(remove_detours(&proof) == vec![leaf("a"), rewritten("c", "ac")]);
+        let direct = vec![leaf("a"), rewritten("b", "ab")];
+        assert!(remove_detours(&direct) == direct);
+    }
 }
@@ -0,0 +1,46 @@
+use crate::printer::TPTPProblem;
+use crate::translator::parse_tptp_problem;
+use std::fs;
+use std::path::PathBuf;
+
+// Fixtures shared by the unit tests: problems are read from files, so a test
+// writes its problem to a scratch directory.
+
+/// A fresh directory under the system temp directory, named after the test
+/// and this process, removed again when dropped
+pub struct ScratchDir {
+    path: PathBuf,
+}
+
+impl ScratchDir {
+    pub fn new(name: &str) -> ScratchDir {
+        let path =
+            std::env::temp_dir().join(format!("egg-sc-tptp-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        ScratchDir { path }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Write `contents` to `relative`, creating its directories, and return its path
+    pub fn write(&self, relative: &str, contents: &str) -> PathBuf {
+        let path = self.path.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Parse `text` as the problem `problem.p` of this directory
+    pub fn problem(&self, text: &str) -> TPTPProblem {
+        parse_tptp_problem(&self.write("problem.p", text)).unwrap()
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
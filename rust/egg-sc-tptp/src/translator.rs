@@ -6,17 +6,21 @@ use tptp::common::*;
 use tptp::top;
 use tptp::TPTPIterator;
 
+use crate::checker::check_proof;
+use crate::error::EggError;
 use crate::fol;
 use fol::FOLLang;
 
 use crate::printer::*;
 
 //function that ready translate a file with path 'path' and then calls TPTPIterator::<()>::new(bytes) on it
-pub fn take_input(path: &std::path::PathBuf) -> Vec<u8> {
-    let mut file = std::fs::File::open(path).unwrap();
+pub fn take_input(path: &std::path::PathBuf) -> std::result::Result<Vec<u8>, EggError> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| EggError::Io(format!("{}: {}", path.display(), e)))?;
     let mut bytes = Vec::new();
-    file.read_to_end(&mut bytes).unwrap();
-    bytes
+    file.read_to_end(&mut bytes)
+        .map_err(|e| EggError::Io(format!("{}: {}", path.display(), e)))?;
+    Ok(bytes)
 }
 
 use nom::branch::alt;
@@ -158,7 +162,12 @@ pub fn comment_line<'a, E: Error<'a>>(x: &'a [u8]) -> Result<'a, HeaderLine, E>
     ))(x)
 }
 
-pub fn parse_header(mut bytes: &[u8]) -> Header {
+pub fn parse_header(mut bytes: &[u8]) -> std::result::Result<Header, EggError> {
+    let input = bytes;
+    let header_error = |remaining: &[u8]| EggError::Parse {
+        line: Some(line_of(input, remaining)),
+        message: "parsing header failed".to_string(),
+    };
     let mut header: Vec<HeaderLine> = Vec::new();
     loop {
         let r = comment_line::<'_, ()>(bytes);
@@ -170,9 +179,9 @@ pub fn parse_header(mut bytes: &[u8]) -> Header {
                         if header.is_empty() || !(tag.is_empty()) {
                             header.push(comment);
                         } else {
-                            match header.last_mut().unwrap() {
-                                HeaderLine::Comment(_, v) => v.push(values[0].clone()),
-                                _ => panic!("Error: parsing header failed"),
+                            match header.last_mut() {
+                                Some(HeaderLine::Comment(_, v)) => v.push(values[0].clone()),
+                                _ => return Err(header_error(bytes)),
                             }
                         }
                     }
@@ -183,23 +192,118 @@ pub fn parse_header(mut bytes: &[u8]) -> Header {
                     break;
                 }
             }
-            Err(_) => panic!("Error: parsing header failed"),
+            Err(_) => return Err(header_error(bytes)),
         }
     }
     let header2 = Header { comments: header };
-    header2
+    Ok(header2)
+}
+
+// 1-based line at which `remaining` starts within `input`
+fn line_of(input: &[u8], remaining: &[u8]) -> usize {
+    let consumed = input.len() - remaining.len();
+    input[..consumed].iter().filter(|&&b| b == b'\n').count() + 1
+}
+
+/// Parse the TPTP problem at `path` into rewrite rules and goals
+pub fn parse_tptp_problem(path: &std::path::PathBuf) -> std::result::Result<TPTPProblem, EggError> {
+    parse_tptp_problem_with_includes(path, &[])
+}
+
+/// Parse the TPTP problem at `path`, inlining the files it includes. Included files are looked
+/// up next to the including file, then in `include_dirs` and in the directory named by $TPTP.
+pub fn parse_tptp_problem_with_includes(
+    path: &std::path::PathBuf,
+    include_dirs: &[std::path::PathBuf],
+) -> std::result::Result<TPTPProblem, EggError> {
+    let bytes = take_input(path)?;
+    let header = parse_header(&bytes.clone())?;
+    let mut statements = Statements::default();
+    read_statements(path, include_dirs, None, &mut statements, &mut Vec::new())?;
+    let Statements { rules, goals, left } = statements;
+
+    // hypotheses become local rules, which would leak into the other goals
+    if goals.len() > 1 && !left.is_empty() {
+        return Err(EggError::Unsupported(
+            "a conjecture with hypotheses must be the only goal".to_string(),
+        ));
+    }
+
+    // the SZS status depends on how the goal is stated
+    if goals.len() > 1 && goals.iter().any(|g| g.negation.is_some()) {
+        return Err(EggError::Unsupported(
+            "a disequality goal must be the only goal".to_string(),
+        ));
+    }
+
+    // options for the runner given in the header
+    let options = header
+        .comments
+        .iter()
+        .filter_map(|l| match l {
+            HeaderLine::Comment(tag, value) if tag == "EggOptions" => Some(value.clone()),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+
+    return Ok(TPTPProblem {
+        path: path.clone(),
+        header: header,
+        axioms: rules,
+        left: left,
+        goals: goals,
+        options: options,
+    });
+}
+
+/// Parse one formula in TPTP syntax, such as an equation printed by another prover
+pub fn parse_tptp_formula(text: &str) -> std::result::Result<fol::Formula, EggError> {
+    let input = format!("fof(formula, plain, {}).\n", text);
+    let invalid = || EggError::Parse {
+        line: None,
+        message: format!("invalid formula {}", text),
+    };
+    match TPTPIterator::<()>::new(input.as_bytes()).next() {
+        Some(Ok(top::TPTPInput::Annotated(annotated))) => {
+            use crate::fol::tptp_fol_translator::*;
+            match fol::AnnotatedStatement::translate(&*annotated).statement {
+                fol::Statement::Formula(formula) => Ok(formula),
+                fol::Statement::Sequent(_) => Err(invalid()),
+            }
+        }
+        _ => Err(invalid()),
+    }
+}
+
+// formulas collected from a problem and the files it includes
+#[derive(Default)]
+struct Statements {
+    rules: Vec<(String, RewriteRule)>,
+    goals: Vec<Goal>,
+    left: Vec<fol::Formula>,
 }
 
-pub fn parse_tptp_problem(path: &std::path::PathBuf) -> TPTPProblem {
-    let bytes = take_input(path);
-    let header = parse_header(&bytes.clone());
+// read the formulas of `path` into `statements`, following includes; if `selection` is given,
+// only the formulas it names are kept
+fn read_statements(
+    path: &std::path::PathBuf,
+    include_dirs: &[std::path::PathBuf],
+    selection: Option<&[String]>,
+    statements: &mut Statements,
+    open: &mut Vec<std::path::PathBuf>,
+) -> std::result::Result<(), EggError> {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+    if open.contains(&canonical) {
+        return Err(EggError::Unsupported(format!(
+            "{} includes itself",
+            path.display()
+        )));
+    }
+    open.push(canonical);
+    let bytes = take_input(path)?;
     let mut parser = TPTPIterator::<()>::new(bytes.as_slice());
-    let mut rules: Vec<(String, RewriteRule)> = Vec::new();
-    let mut conjecture: (String, fol::Formula) = ("".to_string(), fol::Formula::True);
-    let mut left: Vec<fol::Formula> = Vec::new();
-    let mut simplify = false;
-    let mut number_of_questions = 0;
-    for result in &mut parser {
+    while let Some(result) = parser.next() {
         match result {
             Ok(r) => {
                 match r {
@@ -207,6 +311,9 @@ pub fn parse_tptp_problem(path: &std::path::PathBuf) -> TPTPProblem {
                         use crate::fol::tptp_fol_translator::*;
                         let anot_form = fol::AnnotatedStatement::translate(&*annotated);
                         let name = anot_form.name;
+                        if selection.map_or(false, |names| !names.contains(&name)) {
+                            continue;
+                        }
                         let role = anot_form.role;
                         let (conditions, main_formula) = match anot_form.statement {
                             fol::Statement::Formula(f) => (Vec::<fol::Formula>::new(), f),
@@ -214,7 +321,10 @@ pub fn parse_tptp_problem(path: &std::path::PathBuf) -> TPTPProblem {
                                 let left = &sequent.left;
                                 let right = &sequent.right;
                                 if right.len() != 1 {
-                                    panic!("Axioms and Conjectures must have exactly one formula on the right hand side")
+                                    return Err(EggError::Unsupported(format!(
+                                        "{} must have exactly one formula on the right hand side",
+                                        name
+                                    )));
                                 }
                                 let f = &right[0];
                                 (left.clone(), f.clone())
@@ -222,14 +332,10 @@ pub fn parse_tptp_problem(path: &std::path::PathBuf) -> TPTPProblem {
                         };
                         //let annotations = &anot_form.0.annotations;
                         match role.as_str() {
-                            "conjecture" => {
-                                if number_of_questions > 0 {
-                                    panic!("Error: only one conjecture or simplification at a time is allowed")
-                                }
-                                number_of_questions += 1;
+                            "conjecture" | "negated_conjecture" => {
                                 //Handles rewrite rules on the left
                                 conditions.iter().enumerate().for_each(|(no, c)| {
-                                    left.push(c.clone());
+                                    statements.left.push(c.clone());
                                     let formula = &mut c.clone();
                                     let mut vars = Vec::<String>::new();
                                     get_head_vars_logic(&c, formula, &mut vars);
@@ -237,7 +343,7 @@ pub fn parse_tptp_problem(path: &std::path::PathBuf) -> TPTPProblem {
                                         fol::Formula::Predicate(op, args)
                                             if op == "=" && args.len() == 2 =>
                                         {
-                                            rules.push((
+                                            statements.rules.push((
                                                 format!("${no}"),
                                                 RewriteRule::TermRule(
                                                     vars,
@@ -246,7 +352,7 @@ pub fn parse_tptp_problem(path: &std::path::PathBuf) -> TPTPProblem {
                                                 ),
                                             ))
                                         }
-                                        fol::Formula::Iff(l, r) => rules.push((
+                                        fol::Formula::Iff(l, r) => statements.rules.push((
                                             format!("${no}"),
                                             RewriteRule::FormulaRule(vars, *l.clone(), *r.clone()),
                                         )),
@@ -256,40 +362,65 @@ pub fn parse_tptp_problem(path: &std::path::PathBuf) -> TPTPProblem {
                                 //Handles the conjecture
                                 let mut formula = main_formula.clone();
                                 get_head_logic(&main_formula, &mut formula);
-                                conjecture = (name, formula);
+                                let negation = match disequality(&formula) {
+                                    Some(_) if formula != main_formula => {
+                                        return Err(EggError::Unsupported(format!(
+                                            "disequality {} must not be quantified",
+                                            name
+                                        )))
+                                    }
+                                    Some(equality) => {
+                                        formula = equality;
+                                        Some(if role == "conjecture" {
+                                            Negation::Disequality
+                                        } else {
+                                            Negation::NegatedConjecture
+                                        })
+                                    }
+                                    None if role == "conjecture" => None,
+                                    None => {
+                                        return Err(EggError::Unsupported(format!(
+                                            "negated conjecture {} must be a disequality",
+                                            name
+                                        )))
+                                    }
+                                };
+                                statements.goals.push(Goal {
+                                    name,
+                                    formula,
+                                    simplify: false,
+                                    negation,
+                                });
                             }
                             "axiom" => {
                                 let formula = &mut main_formula.clone();
                                 let mut vars = Vec::<String>::new();
                                 get_head_vars_logic(&main_formula, formula, &mut vars);
-                                match formula {
-                                    fol::Formula::Predicate(op, args)
-                                        if op == "=" && args.len() == 2 =>
-                                    {
-                                        rules.push((
-                                            name,
-                                            RewriteRule::TermRule(
-                                                vars,
-                                                *args[0].clone(),
-                                                *args[1].clone(),
-                                            ),
-                                        ))
+                                let rule = match &*formula {
+                                    fol::Formula::Implies(condition, body) => {
+                                        axiom_rule(vars, body).map(|rule| {
+                                            RewriteRule::Conditional(
+                                                *condition.clone(),
+                                                Box::new(rule),
+                                            )
+                                        })
+                                    }
+                                    _ => axiom_rule(vars, formula),
+                                };
+                                match rule {
+                                    Some(rule) => statements.rules.push((name, rule)),
+                                    None => {
+                                        return Err(EggError::Unsupported(format!(
+                                            "axiom {} must be an equality or a biimplication, possibly under a condition",
+                                            name
+                                        )))
                                     }
-                                    fol::Formula::Iff(l, r) => rules.push((
-                                        name,
-                                        RewriteRule::FormulaRule(vars, *l.clone(), *r.clone()),
-                                    )),
-                                    _ => panic!("formulas must be equalities or biimplications"),
                                 }
                             }
                             "simplify" => {
-                                if number_of_questions > 0 {
-                                    panic!("Error: only one conjecture or simplification at a time is allowed")
-                                }
-                                number_of_questions += 1;
                                 //Handles rewrite rules on the left
                                 conditions.iter().enumerate().for_each(|(no, c)| {
-                                    left.push(c.clone());
+                                    statements.left.push(c.clone());
                                     let formula = &mut c.clone();
                                     let mut vars = Vec::<String>::new();
                                     get_head_vars_logic(&c, formula, &mut vars);
@@ -297,7 +428,7 @@ pub fn parse_tptp_problem(path: &std::path::PathBuf) -> TPTPProblem {
                                         fol::Formula::Predicate(op, args)
                                             if op == "=" && args.len() == 2 =>
                                         {
-                                            rules.push((
+                                            statements.rules.push((
                                                 format!("${no}"),
                                                 RewriteRule::TermRule(
                                                     vars,
@@ -306,7 +437,7 @@ pub fn parse_tptp_problem(path: &std::path::PathBuf) -> TPTPProblem {
                                                 ),
                                             ))
                                         }
-                                        fol::Formula::Iff(l, r) => rules.push((
+                                        fol::Formula::Iff(l, r) => statements.rules.push((
                                             format!("${no}"),
                                             RewriteRule::FormulaRule(vars, *l.clone(), *r.clone()),
                                         )),
@@ -316,172 +447,596 @@ pub fn parse_tptp_problem(path: &std::path::PathBuf) -> TPTPProblem {
                                 //Handles the conjecture
                                 let mut formula = main_formula.clone();
                                 get_head_logic(&main_formula, &mut formula);
-                                conjecture = (name, formula);
-                                simplify = true;
+                                statements.goals.push(Goal {
+                                    name,
+                                    formula,
+                                    simplify: true,
+                                    negation: None,
+                                });
                             }
                             _ => (),
                         }
                     }
-                    _ => (),
+                    top::TPTPInput::Include(include) => {
+                        let file_name = include.file_name.to_string();
+                        let included =
+                            resolve_include(path, file_name.trim_matches('\''), include_dirs)?;
+                        let selection: Option<Vec<String>> = include
+                            .selection
+                            .0
+                            .as_ref()
+                            .map(|names| names.0.iter().map(|n| n.to_string()).collect());
+                        read_statements(
+                            &included,
+                            include_dirs,
+                            selection.as_deref(),
+                            statements,
+                            open,
+                        )?;
+                    }
                 }
             }
             Err(_) => {
-                panic!("Error: parsing failed")
+                return Err(EggError::Parse {
+                    line: Some(line_of(&bytes, parser.remaining)),
+                    message: format!("invalid TPTP input in {}", path.display()),
+                })
             }
         }
     }
+    open.pop();
+    Ok(())
+}
 
-    return TPTPProblem {
-        path: path.clone(),
-        header: header,
-        axioms: rules,
-        left: left,
-        conjecture: conjecture,
-        options: Vec::new(),
-        simplify: simplify,
-    };
+// included files are looked up next to the including file, then in `include_dirs` and in $TPTP
+fn resolve_include(
+    path: &std::path::PathBuf,
+    file_name: &str,
+    include_dirs: &[std::path::PathBuf],
+) -> std::result::Result<std::path::PathBuf, EggError> {
+    let mut dirs: Vec<std::path::PathBuf> = path
+        .parent()
+        .map(|dir| match dir.as_os_str().is_empty() {
+            true => std::path::PathBuf::from("."),
+            false => dir.to_path_buf(),
+        })
+        .into_iter()
+        .collect();
+    dirs.extend(include_dirs.iter().cloned());
+    if let Some(tptp) = std::env::var_os("TPTP") {
+        dirs.push(std::path::PathBuf::from(tptp));
+    }
+    dirs.iter()
+        .map(|dir| dir.join(file_name))
+        .find(|file| file.is_file())
+        .ok_or_else(|| {
+            let searched: Vec<String> = dirs.iter().map(|d| d.display().to_string()).collect();
+            EggError::Io(format!(
+                "{}: included file '{}' not found in {}",
+                path.display(),
+                file_name,
+                searched.join(", ")
+            ))
+        })
 }
 
-pub fn solve_tptp_problem(problem: &TPTPProblem) -> Explanation<FOLLang> {
-    let rules: Vec<Rewrite<FOLLang, ()>> = problem
-        .axioms
-        .iter()
-        .map(|(name, rew)| match rew {
-            RewriteRule::FormulaRule(vars, l, r) => {
-                let mut expr_left: RecExpr<ENodeOrVar<fol::FOLLang>> = RecExpr::default();
-                let mut expr_right: RecExpr<ENodeOrVar<fol::FOLLang>> = RecExpr::default();
-                fol::formula_to_recexpr_pattern(l, &vars, &mut expr_left);
-                fol::formula_to_recexpr_pattern(r, &vars, &mut expr_right);
-                Rewrite::<FOLLang, ()>::new(
-                    name,
-                    egg::Pattern::new(expr_left),
-                    egg::Pattern::new(expr_right),
-                )
-                .expect("failed to create rewrite rule")
-            }
-            RewriteRule::TermRule(vars, l, r) => {
-                let mut expr_left: RecExpr<ENodeOrVar<fol::FOLLang>> = RecExpr::default();
-                let mut expr_right: RecExpr<ENodeOrVar<fol::FOLLang>> = RecExpr::default();
-                fol::term_to_recexpr_pattern(l, &vars, &mut expr_left);
-                fol::term_to_recexpr_pattern(r, &vars, &mut expr_right);
-                Rewrite::<FOLLang, ()>::new(
-                    name,
-                    egg::Pattern::new(expr_left),
-                    egg::Pattern::new(expr_right),
-                )
-                .expect("failed to create rewrite rule")
+// `s = t` for a disequality `s != t` or `~(s = t)`
+fn disequality(formula: &fol::Formula) -> Option<fol::Formula> {
+    match formula {
+        fol::Formula::Predicate(op, args) if op == "!=" && args.len() == 2 => {
+            Some(fol::Formula::Predicate("=".to_owned(), args.clone()))
+        }
+        fol::Formula::Not(inner) => match inner.as_ref() {
+            fol::Formula::Predicate(op, args) if op == "=" && args.len() == 2 => {
+                Some(*inner.clone())
             }
-        })
-        .collect::<Vec<_>>();
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// rewrite rule for an equality or a biimplication
+fn axiom_rule(vars: Vec<String>, formula: &fol::Formula) -> Option<RewriteRule> {
+    match formula {
+        fol::Formula::Predicate(op, args) if op == "=" && args.len() == 2 => Some(
+            RewriteRule::TermRule(vars, *args[0].clone(), *args[1].clone()),
+        ),
+        fol::Formula::Iff(l, r) => Some(RewriteRule::FormulaRule(vars, *l.clone(), *r.clone())),
+        _ => None,
+    }
+}
 
-    let mut top_expr: RecExpr<FOLLang> = RecExpr::default();
-    fol::formula_to_recexpr(&fol::Formula::True, &mut top_expr);
+// left and right hand side of a rule as patterns
+fn rule_patterns(rule: &RewriteRule) -> (Pattern<FOLLang>, Pattern<FOLLang>) {
+    let mut expr_left: RecExpr<ENodeOrVar<fol::FOLLang>> = RecExpr::default();
+    let mut expr_right: RecExpr<ENodeOrVar<fol::FOLLang>> = RecExpr::default();
+    match rule {
+        RewriteRule::FormulaRule(vars, l, r) => {
+            fol::formula_to_recexpr_pattern(l, &vars, &mut expr_left);
+            fol::formula_to_recexpr_pattern(r, &vars, &mut expr_right);
+        }
+        RewriteRule::TermRule(vars, l, r) => {
+            fol::term_to_recexpr_pattern(l, &vars, &mut expr_left);
+            fol::term_to_recexpr_pattern(r, &vars, &mut expr_right);
+        }
+        RewriteRule::Conditional(_, inner) => return rule_patterns(inner),
+    }
+    (Pattern::new(expr_left), Pattern::new(expr_right))
+}
 
-    let mut runner: Runner<FOLLang, ()> = Runner::default().with_explanations_enabled();
-    if problem.options.len() >= 2 && problem.options[0] == "--time-limit" {
-        let time_limit = problem.options[1]
-            .parse::<u64>()
-            .expect("time limit must be a number");
-        runner = runner.with_time_limit(std::time::Duration::from_secs(time_limit));
-        println!("Time limit set to {} seconds", time_limit);
+// adds both sides of a rule, variables included, to the e-graph
+fn add_rule_exprs(runner: Runner<FOLLang, ()>, rule: &RewriteRule) -> Runner<FOLLang, ()> {
+    match rule {
+        RewriteRule::FormulaRule(_vars, l, r) => {
+            let mut expr_left: RecExpr<fol::FOLLang> = RecExpr::default();
+            let mut expr_right: RecExpr<fol::FOLLang> = RecExpr::default();
+            fol::formula_to_recexpr(l, &mut expr_left);
+            fol::formula_to_recexpr(r, &mut expr_right);
+            runner.with_expr(&expr_left).with_expr(&expr_right)
+        }
+        RewriteRule::TermRule(_vars, l, r) => {
+            let mut expr_left: RecExpr<fol::FOLLang> = RecExpr::default();
+            let mut expr_right: RecExpr<fol::FOLLang> = RecExpr::default();
+            fol::term_to_recexpr(l, &mut expr_left);
+            fol::term_to_recexpr(r, &mut expr_right);
+            runner.with_expr(&expr_left).with_expr(&expr_right)
+        }
+        RewriteRule::Conditional(_, inner) => add_rule_exprs(runner, inner),
     }
-    runner = problem
-        .axioms
-        .iter()
-        .fold(runner, |runner, (_name, rw)| match rw {
-            RewriteRule::FormulaRule(_vars, l, r) => {
-                let mut expr_left: RecExpr<fol::FOLLang> = RecExpr::default();
-                let mut expr_right: RecExpr<fol::FOLLang> = RecExpr::default();
-                fol::formula_to_recexpr(l, &mut expr_left);
-                fol::formula_to_recexpr(r, &mut expr_right);
-                runner.with_expr(&expr_left).with_expr(&expr_right)
+}
+
+/// cost function used to extract the simplest term in simplification mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExtractionCost {
+    AstSize,
+    AstDepth,
+}
+
+impl std::str::FromStr for ExtractionCost {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        match s {
+            "size" => Ok(ExtractionCost::AstSize),
+            "depth" => Ok(ExtractionCost::AstDepth),
+            _ => Err(format!(
+                "unknown cost function {} (expected size or depth)",
+                s
+            )),
+        }
+    }
+}
+
+/// how an axiom is used as a rewrite rule, set with `--rule NAME=MODE`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RuleMode {
+    /// rewrite left to right only, the default
+    LeftToRight,
+    /// rewrite in both directions
+    Both,
+    /// leave the axiom out
+    Disabled,
+    /// never ban the rule, however often it matches
+    Priority,
+}
+
+impl std::str::FromStr for RuleMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        match s {
+            "ltr" => Ok(RuleMode::LeftToRight),
+            "both" => Ok(RuleMode::Both),
+            "off" => Ok(RuleMode::Disabled),
+            "priority" => Ok(RuleMode::Priority),
+            _ => Err(format!(
+                "unknown rule mode {} (expected ltr, both, off or priority)",
+                s
+            )),
+        }
+    }
+}
+
+/// parse the `NAME=MODE` argument of `--rule`
+pub fn parse_rule_mode(s: &str) -> std::result::Result<(String, RuleMode), String> {
+    match s.rsplit_once('=') {
+        Some((name, mode)) if !name.is_empty() => Ok((name.to_string(), mode.parse()?)),
+        _ => Err(format!("--rule expects NAME=MODE, got {}", s)),
+    }
+}
+
+/// resource limits and proof options for the egg runner, set on the command line or in the
+/// EggOptions header
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RunnerOptions {
+    pub time_limit: Option<u64>,
+    pub node_limit: Option<usize>,
+    pub iter_limit: Option<usize>,
+    pub class_limit: Option<usize>,
+    /// let egg search for shorter explanations, at some cost in time
+    pub optimize_explanations: bool,
+    pub cost: Option<ExtractionCost>,
+    /// modes of the axioms by name, several modes can be given for one axiom
+    pub rules: Vec<(String, RuleMode)>,
+}
+
+impl RunnerOptions {
+    /// parse `--time-limit N`, `--node-limit N`, `--iter-limit N`, `--class-limit N`,
+    /// `--optimize-explanations`, `--cost size|depth` and `--rule NAME=MODE`
+    pub fn from_options(options: &[String]) -> std::result::Result<Self, EggError> {
+        fn number<T: std::str::FromStr>(
+            flag: &str,
+            value: Option<&str>,
+        ) -> std::result::Result<Option<T>, EggError> {
+            match value.map(|v| v.parse::<T>()) {
+                Some(Ok(n)) => Ok(Some(n)),
+                _ => Err(EggError::Parse {
+                    line: None,
+                    message: format!("{} expects a number, got {:?}", flag, value),
+                }),
             }
-            RewriteRule::TermRule(_vars, l, r) => {
-                let mut expr_left: RecExpr<fol::FOLLang> = RecExpr::default();
-                let mut expr_right: RecExpr<fol::FOLLang> = RecExpr::default();
-                fol::term_to_recexpr(l, &mut expr_left);
-                fol::term_to_recexpr(r, &mut expr_right);
-                runner.with_expr(&expr_left).with_expr(&expr_right)
+        }
+
+        let mut runner_options = RunnerOptions::default();
+        let mut tokens = options.iter().flat_map(|o| o.split_whitespace());
+        while let Some(flag) = tokens.next() {
+            match flag {
+                "--time-limit" => runner_options.time_limit = number(flag, tokens.next())?,
+                "--node-limit" => runner_options.node_limit = number(flag, tokens.next())?,
+                "--iter-limit" => runner_options.iter_limit = number(flag, tokens.next())?,
+                "--class-limit" => runner_options.class_limit = number(flag, tokens.next())?,
+                "--optimize-explanations" => runner_options.optimize_explanations = true,
+                "--cost" => {
+                    let cost = tokens.next().unwrap_or_default();
+                    runner_options.cost = Some(cost.parse().map_err(|message| EggError::Parse {
+                        line: None,
+                        message,
+                    })?)
+                }
+                "--rule" => {
+                    let rule = tokens.next().unwrap_or_default();
+                    runner_options
+                        .rules
+                        .push(parse_rule_mode(rule).map_err(|message| EggError::Parse {
+                            line: None,
+                            message,
+                        })?)
+                }
+                other => eprintln!("[WARN] ignoring unknown egg option {}", other),
             }
-        });
+        }
+        Ok(runner_options)
+    }
 
-    let (start, end, mut runner) = if problem.simplify == true {
-        let mut expr_start: RecExpr<fol::FOLLang> = RecExpr::default();
-        let start_id = fol::formula_to_recexpr(&problem.conjecture.1, &mut expr_start);
-        runner = runner.with_expr(&expr_start);
-        runner = runner.run(&rules);
-        let root = *runner.roots.last().unwrap();
-        let extractor = Extractor::new(&runner.egraph, AstSize);
-        let (_, best) = extractor.find_best(root);
-        let mut start_iff_expr = expr_start.clone();
-        start_iff_expr.add(fol::FOLLang::Iff([start_id, start_id]));
-        let iff_enode = fol::FOLLang::Iff([Id::from(0), Id::from(1)]);
-        let start_best_expr = iff_enode.join_recexprs(|_id| {
-            if _id == Id::from(0) {
-                &expr_start
-            } else {
-                &best
+    /// options set in `self` take precedence over those in `other`
+    pub fn or(&self, other: &RunnerOptions) -> RunnerOptions {
+        RunnerOptions {
+            time_limit: self.time_limit.or(other.time_limit),
+            node_limit: self.node_limit.or(other.node_limit),
+            iter_limit: self.iter_limit.or(other.iter_limit),
+            class_limit: self.class_limit.or(other.class_limit),
+            optimize_explanations: self.optimize_explanations || other.optimize_explanations,
+            cost: self.cost.or(other.cost),
+            rules: self.rules.iter().chain(&other.rules).cloned().collect(),
+        }
+    }
+
+    /// direction of the rule for the axiom `name`, the first one given wins, and whether it has
+    /// priority
+    fn rule_mode(&self, name: &str) -> (RuleMode, bool) {
+        let modes = || {
+            self.rules
+                .iter()
+                .filter(move |(n, _)| n == name)
+                .map(|(_, mode)| *mode)
+        };
+        let direction = modes()
+            .find(|mode| *mode != RuleMode::Priority)
+            .unwrap_or(RuleMode::LeftToRight);
+        (direction, modes().any(|mode| mode == RuleMode::Priority))
+    }
+
+    pub fn apply(&self, mut runner: Runner<FOLLang, ()>) -> Runner<FOLLang, ()> {
+        if let Some(secs) = self.time_limit {
+            runner = runner.with_time_limit(std::time::Duration::from_secs(secs));
+            println!("Time limit set to {} seconds", secs);
+        }
+        if let Some(n) = self.node_limit {
+            runner = runner.with_node_limit(n);
+            println!("Node limit set to {}", n);
+        }
+        if let Some(n) = self.iter_limit {
+            runner = runner.with_iter_limit(n);
+            println!("Iteration limit set to {}", n);
+        }
+        if let Some(n) = self.class_limit {
+            // egg has no built-in limit on e-classes, stop from a hook instead
+            runner = runner.with_hook(move |r| {
+                if r.egraph.number_of_classes() > n {
+                    Err(format!("class limit {} exceeded", n))
+                } else {
+                    Ok(())
+                }
+            });
+            println!("E-class limit set to {}", n);
+        }
+        if self.optimize_explanations {
+            runner = runner.with_explanation_length_optimization();
+            println!("Explanation length optimization enabled");
+        }
+        runner
+    }
+}
+
+/// size of the saturated e-graph and how the runner got there
+#[derive(Clone, Debug)]
+pub struct SaturationStats {
+    pub stop_reason: String,
+    pub iterations: usize,
+    pub nodes: usize,
+    pub classes: usize,
+    pub applications: usize,
+}
+
+impl SaturationStats {
+    fn from_runner(runner: &Runner<FOLLang, ()>) -> Self {
+        SaturationStats {
+            stop_reason: format!("{:?}", runner.stop_reason),
+            iterations: runner.iterations.len(),
+            nodes: runner.egraph.total_number_of_nodes(),
+            classes: runner.egraph.number_of_classes(),
+            applications: runner
+                .iterations
+                .iter()
+                .map(|it| it.applied.values().sum::<usize>())
+                .sum(),
+        }
+    }
+}
+
+// print why the runner stopped and how large the e-graph got
+fn report_saturation(stats: &SaturationStats) {
+    println!(
+        "Stop reason: {} after {} iterations ({} nodes, {} classes)",
+        stats.stop_reason, stats.iterations, stats.nodes, stats.classes
+    );
+}
+
+/// explanations of the goals, with the saturated runner to explain rule conditions
+pub struct Solution {
+    /// one explanation per goal, in the order of `TPTPProblem::goals`
+    pub explanations: Vec<std::result::Result<Explanation<FOLLang>, EggError>>,
+    pub stats: SaturationStats,
+    pub runner: Runner<FOLLang, ()>,
+}
+
+// egg rewrite for the axiom `name` from `lhs` to `rhs`, guarded by its condition if it has one
+fn rewrite(
+    name: &str,
+    rew: &RewriteRule,
+    lhs: Pattern<FOLLang>,
+    rhs: Pattern<FOLLang>,
+) -> std::result::Result<Rewrite<FOLLang, ()>, EggError> {
+    let rule = match rew {
+        RewriteRule::Conditional(condition, _) => {
+            let mut expr_cond: RecExpr<ENodeOrVar<fol::FOLLang>> = RecExpr::default();
+            let mut expr_true: RecExpr<ENodeOrVar<fol::FOLLang>> = RecExpr::default();
+            fol::formula_to_recexpr_pattern(condition, &Vec::new(), &mut expr_cond);
+            fol::formula_to_recexpr_pattern(&fol::Formula::True, &Vec::new(), &mut expr_true);
+            let condition = Pattern::new(expr_cond);
+            let lhs_vars = lhs.vars();
+            if let Some(v) = condition.vars().iter().find(|v| !lhs_vars.contains(v)) {
+                return Err(EggError::Unsupported(format!(
+                    "condition of {} uses {} which does not occur on the left hand side",
+                    name, v
+                )));
             }
-        });
-        (start_iff_expr, start_best_expr, runner)
-    } else {
-        let (start, end) = match &problem.conjecture.1 {
-            fol::Formula::Predicate(op, args) if op == "=" && args.len() == 2 => {
-                let mut expr_start: RecExpr<fol::FOLLang> = RecExpr::default();
-                fol::formula_to_recexpr(
-                    &fol::Formula::Predicate(
-                        "=".to_owned(),
-                        vec![args[0].clone(), args[0].clone()],
-                    ),
-                    &mut expr_start,
-                );
-                let mut expr_end: RecExpr<fol::FOLLang> = RecExpr::default();
-                fol::formula_to_recexpr(&problem.conjecture.1, &mut expr_end);
-                (expr_start, expr_end)
+            // the rule fires once the instantiated condition is known to be $true
+            Rewrite::<FOLLang, ()>::new(
+                name,
+                lhs,
+                ConditionalApplier {
+                    condition: ConditionEqual::new(condition, Pattern::new(expr_true)),
+                    applier: rhs,
+                },
+            )
+        }
+        _ => Rewrite::<FOLLang, ()>::new(name, lhs, rhs),
+    };
+    rule.map_err(|e| EggError::Unsupported(format!("rewrite rule {}: {}", name, e)))
+}
+
+/// Saturate an e-graph with the axioms of `problem` and explain each of its goals
+pub fn solve_tptp_problem(
+    problem: &TPTPProblem,
+    runner_options: &RunnerOptions,
+) -> std::result::Result<Solution, EggError> {
+    let runner_options = runner_options.or(&RunnerOptions::from_options(&problem.options)?);
+    for (name, _) in &runner_options.rules {
+        if !problem.axioms.iter().any(|(axiom, _)| axiom == name) {
+            eprintln!("[WARN] --rule names unknown axiom {}", name);
+        }
+    }
+
+    let mut rules: Vec<Rewrite<FOLLang, ()>> = Vec::new();
+    let mut scheduler = BackoffScheduler::default();
+    let mut runner: Runner<FOLLang, ()> =
+        runner_options.apply(Runner::default().with_explanations_enabled());
+    for (name, rew) in &problem.axioms {
+        let (direction, priority) = runner_options.rule_mode(name);
+        if direction == RuleMode::Disabled {
+            continue;
+        }
+        let (lhs, rhs) = rule_patterns(rew);
+        rules.push(rewrite(name, rew, lhs.clone(), rhs.clone())?);
+        if priority {
+            scheduler = scheduler.do_not_ban(name.as_str());
+        }
+        if direction == RuleMode::Both {
+            let reversed = format!("{}{}", name, REVERSED_RULE);
+            rules.push(rewrite(&reversed, rew, rhs, lhs)?);
+            if priority {
+                scheduler = scheduler.do_not_ban(reversed.as_str());
             }
-            fol::Formula::Iff(l, _) => {
-                let mut expr_start: RecExpr<fol::FOLLang> = RecExpr::default();
-                fol::formula_to_recexpr(&fol::Formula::Iff(l.clone(), l.clone()), &mut expr_start);
-                let mut expr_end: RecExpr<fol::FOLLang> = RecExpr::default();
-                fol::formula_to_recexpr(&problem.conjecture.1, &mut expr_end);
-                (expr_start, expr_end)
+        }
+        runner = add_rule_exprs(runner, rew);
+    }
+    runner = runner.with_scheduler(scheduler);
+
+    if problem.goals.is_empty() {
+        return Err(EggError::Unsupported(
+            "the problem has no conjecture".to_string(),
+        ));
+    }
+    // every goal is added before saturating so that they share one e-graph
+    let mut goal_exprs: Vec<(RecExpr<FOLLang>, Option<RecExpr<FOLLang>>)> = Vec::new();
+    for goal in &problem.goals {
+        if goal.simplify {
+            let mut expr_start: RecExpr<fol::FOLLang> = RecExpr::default();
+            fol::formula_to_recexpr(&goal.formula, &mut expr_start);
+            runner = runner.with_expr(&expr_start);
+            goal_exprs.push((expr_start, None));
+        } else {
+            let (start, end) = conjecture_exprs(goal)?;
+            runner = runner.with_expr(&start).with_expr(&end);
+            goal_exprs.push((start, Some(end)));
+        }
+    }
+    runner = runner.run(&rules);
+    let stats = SaturationStats::from_runner(&runner);
+    report_saturation(&stats);
+
+    let cost = runner_options.cost.unwrap_or(ExtractionCost::AstSize);
+    let mut explanations = Vec::new();
+    for (goal, (start, end)) in problem.goals.iter().zip(goal_exprs) {
+        let (start, end, proved) = match end {
+            Some(end) => {
+                // an explanation only exists if both sides ended up in the same e-class
+                let start_id = runner.egraph.lookup_expr(&start);
+                let end_id = runner.egraph.lookup_expr(&end);
+                let proved = match (start_id, end_id) {
+                    (Some(s), Some(e)) => runner.egraph.find(s) == runner.egraph.find(e),
+                    _ => false,
+                };
+                (start, end, proved)
+            }
+            None => {
+                let (start, end) = simplification_exprs(&runner, &start, cost);
+                (start, end, true)
             }
-            _ => panic!("conjecture must be an equality"),
         };
-        runner = runner.with_expr(&start).with_expr(&end);
-        runner = runner.run(&rules);
-        (start, end, runner)
+        explanations.push(if proved {
+            Ok(runner.explain_equivalence(&start, &end))
+        } else {
+            Err(EggError::NotProved(format!(
+                "{} (stop reason: {})",
+                goal.name, stats.stop_reason
+            )))
+        });
+    }
+    Ok(Solution {
+        explanations,
+        stats,
+        runner,
+    })
+}
+
+// `start = start` (or `start <=> start`) and the conjecture itself
+fn conjecture_exprs(
+    goal: &Goal,
+) -> std::result::Result<(RecExpr<FOLLang>, RecExpr<FOLLang>), EggError> {
+    let mut expr_start: RecExpr<fol::FOLLang> = RecExpr::default();
+    match &goal.formula {
+        fol::Formula::Predicate(op, args) if op == "=" && args.len() == 2 => {
+            fol::formula_to_recexpr(
+                &fol::Formula::Predicate("=".to_owned(), vec![args[0].clone(), args[0].clone()]),
+                &mut expr_start,
+            );
+        }
+        fol::Formula::Iff(l, _) => {
+            fol::formula_to_recexpr(&fol::Formula::Iff(l.clone(), l.clone()), &mut expr_start);
+        }
+        _ => {
+            return Err(EggError::Unsupported(format!(
+                "conjecture {} must be an equality or a biimplication",
+                goal.name
+            )))
+        }
+    };
+    let mut expr_end: RecExpr<fol::FOLLang> = RecExpr::default();
+    fol::formula_to_recexpr(&goal.formula, &mut expr_end);
+    Ok((expr_start, expr_end))
+}
+
+// `start <=> start` and `start <=> best` for the cheapest expression `best` equivalent to `start`
+fn simplification_exprs(
+    runner: &Runner<FOLLang, ()>,
+    expr_start: &RecExpr<FOLLang>,
+    cost: ExtractionCost,
+) -> (RecExpr<FOLLang>, RecExpr<FOLLang>) {
+    let start_id = Id::from(expr_start.as_ref().len() - 1);
+    let root = runner
+        .egraph
+        .lookup_expr(expr_start)
+        .expect("the expression to simplify is in the e-graph");
+    let best = match cost {
+        ExtractionCost::AstSize => Extractor::new(&runner.egraph, AstSize).find_best(root).1,
+        ExtractionCost::AstDepth => Extractor::new(&runner.egraph, AstDepth).find_best(root).1,
     };
-    let e = runner.explain_equivalence(&start, &end);
-    e
+    let mut start_iff_expr = expr_start.clone();
+    start_iff_expr.add(fol::FOLLang::Iff([start_id, start_id]));
+    let iff_enode = fol::FOLLang::Iff([Id::from(0), Id::from(1)]);
+    let start_best_expr = iff_enode.join_recexprs(|_id| {
+        if _id == Id::from(0) {
+            expr_start
+        } else {
+            &best
+        }
+    });
+    (start_iff_expr, start_best_expr)
 }
 
+/// Parse the problem at `path`, solve it and write the solution to `output`, returns the SZS status
 pub fn tptp_problem_to_tptp_solution(
     path: &std::path::PathBuf,
     output: &std::path::PathBuf,
     level1: bool,
-) -> () {
-    let mut problem: TPTPProblem = parse_tptp_problem(path);
+    runner_options: &RunnerOptions,
+    check: bool,
+    include_dirs: &[std::path::PathBuf],
+) -> std::result::Result<&'static str, EggError> {
+    let problem: TPTPProblem = parse_tptp_problem_with_includes(path, include_dirs)?;
+    let (res, unproved) = solve_to_tptp(&problem, runner_options, level1, check)?;
+    let io_error = |e: std::io::Error| EggError::Io(format!("{}: {}", output.display(), e));
+    let mut file = std::fs::File::create(output).map_err(io_error)?;
+    use std::io::Write;
+    file.write_all(res.as_bytes()).map_err(io_error)?;
+
+    if !unproved.is_empty() {
+        let messages: Vec<String> = unproved.iter().map(|e| e.to_string()).collect();
+        return Err(EggError::NotProved(messages.join("; ")));
+    }
+    Ok(problem.szs_status())
+}
+
+/// Header of the solution file: the problem's header with the status, solver and logic updated
+pub fn solution_header(header: &Header, status: &str) -> Header {
     let mut newcomments = Vec::<HeaderLine>::new();
-    let contains_solver = problem.header.comments.iter().any(|l| match l {
+    let contains_solver = header.comments.iter().any(|l| match l {
         HeaderLine::Comment(tag, _) => tag == "Solver",
         _ => false,
     });
-    let contains_logic = problem.header.comments.iter().any(|l| match l {
+    let contains_logic = header.comments.iter().any(|l| match l {
         HeaderLine::Comment(tag, _) => tag == "Logic",
         _ => false,
     });
-    problem.header.comments.iter().for_each(|l| match l {
+    header.comments.iter().for_each(|l| match l {
         HeaderLine::Comment(tag, value) => {
             if tag == "EggOptions" {
                 newcomments.push(l.clone());
-                let mut opts: Vec<String> = value.iter().map(|v| v.to_string()).collect();
-                problem.options.append(&mut opts);
             } else if tag == "Status" {
-                newcomments.push(HeaderLine::Comment(
-                    tag.clone(),
-                    vec!["Theorem".to_string()],
-                ));
+                newcomments.push(HeaderLine::Comment(tag.clone(), vec![status.to_string()]));
             } else if tag == "Solver" {
                 newcomments.push(HeaderLine::Comment(
                     "Solver".to_string(),
@@ -519,18 +1074,103 @@ pub fn tptp_problem_to_tptp_solution(
         _ => newcomments.push(l.clone()),
     });
 
-    let newheader = Header {
+    Header {
         comments: newcomments,
+    }
+}
+
+/// Solve the goals of `problem` and print the SC-TPTP solution, with the goals that could not be
+/// proved. Fails if no goal was proved or, with `check`, if a proof does not pass the checker.
+pub fn solve_to_tptp(
+    problem: &TPTPProblem,
+    runner_options: &RunnerOptions,
+    level1: bool,
+    check: bool,
+) -> std::result::Result<(String, Vec<EggError>), EggError> {
+    let init = format!("{}", solution_header(&problem.header, problem.szs_status()));
+    let mut solution = solve_tptp_problem(&problem, runner_options)?;
+    let runner = &mut solution.runner;
+    let mut explain_condition = |condition: &fol::Formula| {
+        let mut expr_true: RecExpr<FOLLang> = RecExpr::default();
+        let mut expr_cond: RecExpr<FOLLang> = RecExpr::default();
+        fol::formula_to_recexpr(&fol::Formula::True, &mut expr_true);
+        fol::formula_to_recexpr(condition, &mut expr_cond);
+        remove_detours(
+            runner
+                .explain_equivalence(&expr_true, &expr_cond)
+                .make_flat_explanation(),
+        )
     };
 
-    let init = format!("{}", newheader);
-    let mut proof = solve_tptp_problem(&problem);
-    let expl = proof.make_flat_explanation();
+    let mut blocks = Vec::new();
+    let mut unproved = Vec::new();
+    let mut proof_steps = 0;
+    for (goal, explanation) in problem.goals.iter().zip(solution.explanations.iter_mut()) {
+        match explanation {
+            Ok(explanation) => {
+                let flat = explanation.make_flat_explanation();
+                let expl = remove_detours(flat);
+                if expl.len() < flat.len() {
+                    println!(
+                        "Removed {} redundant rewrites from the proof of {}",
+                        flat.len() - expl.len(),
+                        goal.name
+                    );
+                }
+                proof_steps += expl.len().saturating_sub(1);
+                let steps = proof_to_steps(&expl, &problem, goal, level1, &mut explain_condition);
+                if check {
+                    check_proof(&problem, goal, &steps)?;
+                    println!("Proof of {} checked: {} steps", goal.name, steps.len());
+                }
+                blocks.push((goal, steps));
+            }
+            Err(e) => unproved.push(e.clone()),
+        }
+    }
+    if blocks.is_empty() {
+        return Err(unproved.remove(0));
+    }
 
-    let res = proof_to_tptp(&init, expl, &problem, level1);
-    let mut file = std::fs::File::create(output).unwrap();
-    use std::io::Write;
-    file.write_all(res.as_bytes()).unwrap();
+    // status and statistics first, so callers can classify the output like Vampire's
+    let stats = &solution.stats;
+    let problem_name = problem
+        .path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let summary = format!(
+        "% SZS status {} for {}\n\
+         % Statistics : {} e-nodes, {} e-classes, {} iterations, \
+         {} rewrite applications, {} proof steps\n",
+        if unproved.is_empty() {
+            problem.szs_status()
+        } else {
+            "GaveUp"
+        },
+        problem_name,
+        stats.nodes,
+        stats.classes,
+        stats.iterations,
+        stats.applications,
+        proof_steps
+    );
+    let res = if problem.goals.len() == 1 {
+        summary + &proof_to_tptp(&init, &blocks[0].1)
+    } else {
+        // one delimited block per goal, step names restart in every block
+        blocks
+            .iter()
+            .fold(summary + &init + "\n", |res, (goal, steps)| {
+                res + &format!(
+                    "% SZS output start Proof for {}\n{}\n% SZS output end Proof for {}\n",
+                    goal.name,
+                    steps_to_tptp(steps),
+                    goal.name
+                )
+            })
+    };
+    Ok((res, unproved))
 }
 
 fn get_head_logic<'a>(frm: &fol::Formula, res: &mut fol::Formula) -> () {
@@ -555,3 +1195,303 @@ fn get_head_vars_logic<'a>(
         _ => *res_f = frm.clone(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ScratchDir;
+
+    #[test]
+    fn failures_are_typed_errors_with_their_szs_status() {
+        let dir = ScratchDir::new("errors");
+        let invalid = dir.write(
+            "invalid.p",
+            "fof(a, axiom, f(X) = X).\nfof(b, axiom, = ).\n",
+        );
+        let err = parse_tptp_problem(&invalid).err().unwrap();
+        assert!(matches!(err, EggError::Parse { line: Some(_), .. }));
+        assert_eq!((err.szs_status(), err.exit_code()), ("SyntaxError", 2));
+
+        let missing = invalid.with_file_name("missing.p");
+        let err = parse_tptp_problem(&missing).err().unwrap();
+        assert_eq!((err.szs_status(), err.exit_code()), ("OSError", 5));
+
+        let unsupported = dir.write("unsupported.p", "fof(a, axiom, p(a)).\n");
+        let err = parse_tptp_problem(&unsupported).err().unwrap();
+        assert!(matches!(err, EggError::Unsupported(_)));
+        assert_eq!((err.szs_status(), err.exit_code()), ("Inappropriate", 3));
+
+        let problem = dir.problem("fof(a, axiom, ![X] : f(X) = X).\nfof(c, conjecture, a = b).\n");
+        let solution = solve_tptp_problem(&problem, &RunnerOptions::default()).unwrap();
+        let err = solution.explanations[0].as_ref().err().unwrap();
+        assert!(matches!(err, EggError::NotProved(_)));
+        assert_eq!((err.szs_status(), err.exit_code()), ("GaveUp", 4));
+    }
+
+    #[test]
+    fn limits_from_the_command_line_take_precedence_over_the_header() {
+        let header =
+            RunnerOptions::from_options(&["--iter-limit 1 --node-limit 1000".to_string()]).unwrap();
+        assert_eq!(
+            (header.iter_limit, header.node_limit),
+            (Some(1), Some(1000))
+        );
+        let command_line = RunnerOptions {
+            iter_limit: Some(3),
+            ..RunnerOptions::default()
+        };
+        let limits = command_line.or(&header);
+        assert_eq!(
+            (limits.iter_limit, limits.node_limit),
+            (Some(3), Some(1000))
+        );
+        assert!(RunnerOptions::from_options(&["--node-limit many".to_string()]).is_err());
+        assert!(RunnerOptions::from_options(&["--node-limit".to_string()]).is_err());
+
+        // the axiom keeps adding terms, so only the limit stops saturation
+        let dir = ScratchDir::new("limits");
+        let problem =
+            dir.problem("fof(a, axiom, ![X] : f(X) = f(s(X))).\nfof(c, conjecture, f(a) = b).\n");
+        let solution = solve_tptp_problem(&problem, &header).unwrap();
+        assert!(solution.stats.stop_reason.contains("IterationLimit"));
+        let err = solution.explanations[0].as_ref().err().unwrap();
+        assert!(matches!(err, EggError::NotProved(_)));
+        assert!(err.to_string().contains("IterationLimit"));
+    }
+
+    #[test]
+    fn solutions_start_with_the_status_and_statistics() {
+        let dir = ScratchDir::new("statistics");
+        let input = dir.write(
+            "problem.p",
+            "fof(a, axiom, ![X] : f(X) = X).\nfof(c, conjecture, f(f(b)) = b).\n",
+        );
+        let output = input.with_file_name("solution.p");
+        let options = RunnerOptions::default();
+        assert_eq!(
+            tptp_problem_to_tptp_solution(&input, &output, false, &options, false, &[]),
+            Ok("Theorem")
+        );
+        let solution = std::fs::read_to_string(&output).unwrap();
+        let mut lines = solution.lines();
+        assert_eq!(lines.next(), Some("% SZS status Theorem for problem"));
+        let statistics = lines.next().unwrap();
+        assert!(statistics.starts_with("% Statistics : "));
+        assert!(statistics.ends_with(" proof steps"));
+    }
+
+    #[test]
+    fn conditional_rules_fire_only_once_their_condition_holds() {
+        let dir = ScratchDir::new("conditional");
+        let axioms = "fof(cond, axiom, ![X] : (p(X) => f(X) = g(X))).\n\
+                      fof(pa, axiom, p(a) <=> $true).\n";
+        let options = RunnerOptions::default();
+        let input = dir.write(
+            "proved.p",
+            &format!("{}fof(c, conjecture, f(a) = g(a)).\n", axioms),
+        );
+        let output = input.with_file_name("solution.p");
+        assert_eq!(
+            tptp_problem_to_tptp_solution(&input, &output, false, &options, false, &[]),
+            Ok("Theorem")
+        );
+        let solution = std::fs::read_to_string(&output).unwrap();
+        assert!(solution.contains("inference(leftImplies"));
+
+        let problem = dir.problem(&format!("{}fof(c, conjecture, f(b) = g(b)).\n", axioms));
+        let solution = solve_tptp_problem(&problem, &options).unwrap();
+        assert!(matches!(
+            solution.explanations[0],
+            Err(EggError::NotProved(_))
+        ));
+
+        // the condition cannot be instantiated from the left hand side
+        let problem = dir.problem(
+            "fof(cond, axiom, ![X, Y] : (q(Y) => f(X) = X)).\nfof(c, conjecture, f(a) = a).\n",
+        );
+        let err = solve_tptp_problem(&problem, &options).err().unwrap();
+        assert!(matches!(err, EggError::Unsupported(_)));
+    }
+
+    #[test]
+    fn the_cost_function_picks_the_simplified_formula() {
+        let dir = ScratchDir::new("cost");
+        let input = dir.write(
+            "problem.p",
+            "fof(e, axiom, g(a, a, a, a) = h(h(h(a)))).\nfof(s, simplify, p(g(a, a, a, a))).\n",
+        );
+        let output = input.with_file_name("solution.p");
+        let simplify = |options: &str| {
+            let options = RunnerOptions::from_options(&[options.to_string()]).unwrap();
+            tptp_problem_to_tptp_solution(&input, &output, false, &options, false, &[]).unwrap();
+            std::fs::read_to_string(&output).unwrap()
+        };
+        // the smaller term is the deeper one
+        assert!(simplify("").contains("p(h(h(h(a))))"));
+        assert!(!simplify("--cost depth").contains("p(h(h(h(a))))"));
+        assert!(simplify("--cost size --optimize-explanations").contains("p(h(h(h(a))))"));
+
+        assert!(RunnerOptions::from_options(&["--cost height".to_string()]).is_err());
+    }
+
+    #[test]
+    fn several_goals_get_one_proof_block_each() {
+        let dir = ScratchDir::new("goals");
+        let input = dir.write(
+            "problem.p",
+            "fof(a, axiom, ![X] : f(X) = X).\n\
+             fof(c1, conjecture, f(b) = b).\n\
+             fof(c2, conjecture, f(f(c)) = c).\n\
+             fof(c3, conjecture, b = c).\n",
+        );
+        let output = input.with_file_name("solution.p");
+        let options = RunnerOptions::default();
+        // the unproved goal is reported, the proofs of the others are still written
+        let err =
+            tptp_problem_to_tptp_solution(&input, &output, false, &options, true, &[]).unwrap_err();
+        assert!(matches!(err, EggError::NotProved(_)));
+        assert!(err.to_string().contains("c3"));
+        let solution = std::fs::read_to_string(&output).unwrap();
+        assert!(solution.starts_with("% SZS status GaveUp for problem\n"));
+        for goal in ["c1", "c2"] {
+            assert!(solution.contains(&format!("% SZS output start Proof for {}\n", goal)));
+            assert!(solution.contains(&format!("% SZS output end Proof for {}\n", goal)));
+        }
+        assert!(!solution.contains("Proof for c3"));
+
+        let hypotheses = dir.write(
+            "hypotheses.p",
+            "fof(c1, conjecture, [a = b] --> [f(a) = f(b)]).\nfof(c2, conjecture, b = b).\n",
+        );
+        let err = parse_tptp_problem(&hypotheses).err().unwrap();
+        assert!(matches!(err, EggError::Unsupported(_)));
+    }
+
+    #[test]
+    fn the_library_returns_the_solution_with_the_unproved_goals() {
+        let dir = ScratchDir::new("library");
+        let problem = dir.problem(
+            "fof(a, axiom, ![X] : f(X) = X).\n\
+             fof(c1, conjecture, f(b) = b).\n\
+             fof(c2, conjecture, b = c).\n",
+        );
+        let options = RunnerOptions::default();
+        let (solution, unproved) = solve_to_tptp(&problem, &options, false, true).unwrap();
+        assert!(solution.contains("% SZS output start Proof for c1\n"));
+        assert_eq!(unproved.len(), 1);
+        assert!(unproved[0].to_string().contains("c2"));
+
+        // nothing to print if no goal was proved
+        let problem = dir.problem("fof(c, conjecture, b = c).\n");
+        let err = solve_to_tptp(&problem, &options, false, false).unwrap_err();
+        assert!(matches!(err, EggError::NotProved(_)));
+    }
+
+    #[test]
+    fn includes_are_inlined_with_their_selection() {
+        let dir = ScratchDir::new("includes");
+        dir.write(
+            "axioms.ax",
+            "fof(fx, axiom, ![X] : f(X) = X).\nfof(gx, axiom, ![X] : g(X) = X).\n",
+        );
+        let problem = dir.problem("include('axioms.ax', [fx]).\nfof(c, conjecture, f(b) = b).\n");
+        let names: Vec<&str> = problem
+            .axioms
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, vec!["fx"]);
+
+        // files outside the problem's directory are found through the include directories
+        let lib = dir.path().join("lib");
+        dir.write("lib/more.ax", "fof(hx, axiom, ![X] : h(X) = X).\n");
+        let input = dir.write(
+            "more.p",
+            "include('more.ax').\nfof(c, conjecture, h(b) = b).\n",
+        );
+        let err = parse_tptp_problem(&input).err().unwrap();
+        assert!(matches!(err, EggError::Io(_)));
+        let problem = parse_tptp_problem_with_includes(&input, &[lib]).unwrap();
+        assert_eq!(problem.axioms[0].0, "hx");
+
+        let input = dir.write("cycle.p", "include('cycle.p').\n");
+        let err = parse_tptp_problem(&input).err().unwrap();
+        assert!(matches!(err, EggError::Unsupported(_)));
+    }
+
+    #[test]
+    fn disequalities_are_refuted_with_their_own_status() {
+        let dir = ScratchDir::new("disequality");
+        let axiom = "fof(a, axiom, ![X] : f(X) = X).\n";
+        let output = dir.path().join("solution.p");
+        let options = RunnerOptions::default();
+        for (goal, status) in [
+            ("fof(c, conjecture, f(b) != b).\n", "CounterTheorem"),
+            (
+                "fof(c, negated_conjecture, ~ (f(b) = b)).\n",
+                "Unsatisfiable",
+            ),
+        ] {
+            let input = dir.write("problem.p", &format!("{}{}", axiom, goal));
+            assert_eq!(
+                tptp_problem_to_tptp_solution(&input, &output, false, &options, true, &[]),
+                Ok(status)
+            );
+            let solution = std::fs::read_to_string(&output).unwrap();
+            assert!(solution.starts_with(&format!("% SZS status {} for problem\n", status)));
+            assert!(solution.contains("inference(leftNot"));
+        }
+
+        let input = dir.write(
+            "problem.p",
+            &format!("{}fof(c, conjecture, ![X] : f(X) != X).\n", axiom),
+        );
+        let err = parse_tptp_problem(&input).err().unwrap();
+        assert!(matches!(err, EggError::Unsupported(_)));
+        let input = dir.write(
+            "problem.p",
+            &format!("{}fof(c, negated_conjecture, f(b) = b).\n", axiom),
+        );
+        let err = parse_tptp_problem(&input).err().unwrap();
+        assert!(matches!(err, EggError::Unsupported(_)));
+    }
+
+    #[test]
+    fn rule_modes_select_the_directions_of_the_axioms() {
+        assert_eq!(
+            parse_rule_mode("comm=off"),
+            Ok(("comm".to_string(), RuleMode::Disabled))
+        );
+        assert!(parse_rule_mode("comm").is_err());
+        assert!(parse_rule_mode("=off").is_err());
+        assert!(parse_rule_mode("comm=rtl").is_err());
+
+        let dir = ScratchDir::new("rules");
+        let input = dir.write(
+            "problem.p",
+            "fof(fg, axiom, ![X] : f(X) = g(X)).\n\
+             fof(fh, axiom, ![X] : f(X) = h(X)).\n\
+             fof(c, conjecture, g(b) = h(b)).\n",
+        );
+        let problem = parse_tptp_problem(&input).unwrap();
+        let proved = |options: &str| {
+            let options = RunnerOptions::from_options(&[options.to_string()]).unwrap();
+            solve_tptp_problem(&problem, &options).unwrap().explanations[0].is_ok()
+        };
+        // left to right, the rules need an f term and the goal has none
+        assert!(!proved(""));
+        assert!(proved("--rule fg=both"));
+        assert!(!proved("--rule fg=both --rule fh=off"));
+        assert!(proved("--rule fg=priority --rule fg=both"));
+
+        // the reversed rule is printed as its axiom applied right to left
+        let output = input.with_file_name("solution.p");
+        let options = RunnerOptions::from_options(&["--rule fg=both".to_string()]).unwrap();
+        assert_eq!(
+            tptp_problem_to_tptp_solution(&input, &output, false, &options, true, &[]),
+            Ok("Theorem")
+        );
+        let solution = std::fs::read_to_string(&output).unwrap();
+        assert!(!solution.contains(REVERSED_RULE));
+    }
+}
@@ -1,7 +1,7 @@
-use itertools::Itertools;
 use regex::Regex;
 use std::collections::BTreeMap;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum Term {
@@ -56,30 +56,149 @@ pub fn normalize_formula_alpha(formula: &str) -> String {
         .collect()
 }
 
+/// Substitution produced by matching: variable of the matched formula -> term it was bound to
+pub type Substitution = BTreeMap<String, String>;
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Term::Var(v) => write!(f, "{}", v),
+            Term::Fun(op, args) if op == "|" => write!(
+                f,
+                "{}",
+                args.iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            ),
+            Term::Fun(op, args) if (op == "=" || op == "!=") && args.len() == 2 => {
+                write!(f, "{} {} {}", args[0], op, args[1])
+            }
+            Term::Fun(name, args) if args.is_empty() => write!(f, "{}", name),
+            Term::Fun(name, args) => write!(
+                f,
+                "{}({})",
+                name,
+                args.iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        }
+    }
+}
+
+/// Split off a leading quantifier: "! [X,Y] : body" -> (["X", "Y"], "body")
+fn split_quantifier(formula: &str) -> (Vec<String>, String) {
+    let quant_re = Regex::new(r"!\s*\[([^\]]*)\]\s*:\s*(.*)").unwrap();
+
+    if let Some(cap) = quant_re.captures(formula) {
+        let vars = cap[1].split(',').map(|v| v.trim().to_string()).collect();
+        (vars, cap[2].trim().to_string())
+    } else {
+        (Vec::new(), formula.trim().to_string())
+    }
+}
+
 /// Parse a formula like "(op(V0,op(V1,V0))=X3)" into Term::Fun("=", [...]).
 fn parse_formula(s: &str) -> Term {
     parse_term(s.trim())
 }
 
 fn parse_term(s: &str) -> Term {
-    let s = s.trim();
+    let s = strip_outer_parens(s.trim());
+
+    // Clause: l1 | l2 | ...
+    let literals = split_top_level_on(s, '|');
+    if literals.len() > 1 {
+        return Term::Fun(
+            "|".to_string(),
+            literals.iter().map(|l| parse_term(l)).collect(),
+        );
+    }
+
+    // (Dis)equality: lhs = rhs, lhs != rhs
+    if let Some((lhs, op, rhs)) = split_equality(s) {
+        return Term::Fun(op.to_string(), vec![parse_term(lhs), parse_term(rhs)]);
+    }
+
+    // Function application: name(arg1,arg2,...)
+    if let Some(open) = s.find('(') {
+        if s.ends_with(')') {
+            let fun_name = s[..open].trim().to_string();
+            let args = split_top_level(&s[open + 1..s.len() - 1])
+                .iter()
+                .map(|p| parse_term(p))
+                .collect();
+            return Term::Fun(fun_name, args);
+        }
+    }
+
+    // Variables start with an uppercase letter, everything else is a constant
+    if s.starts_with(|c: char| c.is_ascii_uppercase()) {
+        Term::Var(s.to_string())
+    } else {
+        Term::Fun(s.to_string(), Vec::new())
+    }
+}
 
-    // Variable (no parentheses)
-    if !s.starts_with('(') {
-        return Term::Var(s.to_string());
+/// Remove parentheses enclosing the whole string: "((a = b))" -> "a = b"
+fn strip_outer_parens(mut s: &str) -> &str {
+    while s.starts_with('(') && s.ends_with(')') {
+        let mut depth = 0;
+        let mut closes_at_end = true;
+        for (i, c) in s.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 && i != s.len() - 1 {
+                        closes_at_end = false;
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !closes_at_end {
+            break;
+        }
+        s = s[1..s.len() - 1].trim();
     }
+    s
+}
 
-    // Function application: (name,arg1,arg2,...)
-    let inside = &s[1..s.len() - 1];
-    let mut parts = split_top_level(inside);
+/// Find a top-level "=" or "!=" and split around it
+fn split_equality(s: &str) -> Option<(&str, &'static str, &str)> {
+    let bytes = s.as_bytes();
+    let mut depth = 0;
 
-    let fun_name = parts.remove(0).to_string();
-    let args = parts.into_iter().map(|p| parse_term(&p)).collect();
-    Term::Fun(fun_name, args)
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '=' if depth == 0 => {
+                // skip "=>" and "<=>"
+                if bytes.get(i + 1) == Some(&b'>') {
+                    continue;
+                }
+                if i > 0 && bytes[i - 1] == b'!' {
+                    return Some((s[..i - 1].trim(), "!=", s[i + 1..].trim()));
+                }
+                return Some((s[..i].trim(), "=", s[i + 1..].trim()));
+            }
+            _ => {}
+        }
+    }
+    None
 }
 
 /// Split arguments at top-level commas: op(V0,op(X1,V0)) -> ["op", "V0", "op(X1,V0)"]
 fn split_top_level(s: &str) -> Vec<String> {
+    split_top_level_on(s, ',')
+}
+
+fn split_top_level_on(s: &str, sep: char) -> Vec<String> {
     let mut result = Vec::new();
     let mut depth = 0;
     let mut start = 0;
@@ -88,9 +207,9 @@ fn split_top_level(s: &str) -> Vec<String> {
         match c {
             '(' => depth += 1,
             ')' => depth -= 1,
-            ',' if depth == 0 => {
+            c if c == sep && depth == 0 => {
                 result.push(s[start..i].trim().to_string());
-                start = i + 1;
+                start = i + c.len_utf8();
             }
             _ => {}
         }
@@ -100,85 +219,374 @@ fn split_top_level(s: &str) -> Vec<String> {
     result
 }
 
+/// Checks whether two formulas match modulo variable renaming (alpha-equivalence),
+/// including quantified variables at the top level. Equalities and disequalities
+/// match in either orientation, so `s = t` matches `t = s`.
+///
+/// Only a renaming is accepted: if `other_formula` is a proper instance of
+/// `formula` (a variable bound to a compound term or constant, or two variables
+/// to the same one) this is false, use `match_with_substitution` or `subsumes`
+/// for that. Apart from the leading `!` quantifier and enclosing parentheses the
+/// formulas are not normalized, so clauses with their literals in another order
+/// do not match.
 pub fn formulas_match(formula: &str, other_formula: &str) -> bool {
-    formulas_match_with_permutations(formula, other_formula)
+    formulas_match_with_orientation(formula, other_formula, false)
 }
 
-/// Pattern match formula onto other_formula with variable map
-fn match_terms(formula: &Term, other_formula: &Term, map: &mut HashMap<String, Term>) -> bool {
+/// Like `formulas_match`, but if `preserve_orientation` is set, `s = t` only
+/// matches `s = t` and not `t = s`.
+pub fn formulas_match_with_orientation(
+    formula: &str,
+    other_formula: &str,
+    preserve_orientation: bool,
+) -> bool {
+    match_bodies(formula, other_formula, !preserve_orientation)
+        .iter()
+        .any(|map| onto_distinct_variables(map.values().cloned()))
+}
+
+fn is_equality(op: &str) -> bool {
+    op == "=" || op == "!="
+}
+
+/// Pattern match formula onto other_formula, extending `map`, and return every
+/// variable map under which they match. If `symmetric` is set, the sides of
+/// (dis)equalities may be swapped, so there is one map per orientation that
+/// works; later arguments are matched against each of them in turn.
+fn match_terms(
+    formula: &Term,
+    other_formula: &Term,
+    map: HashMap<String, Term>,
+    symmetric: bool,
+) -> Vec<HashMap<String, Term>> {
     match formula {
-        Term::Var(v) => {
-            if let Some(existing) = map.get(v) {
-                existing == other_formula
-            } else {
+        Term::Var(v) => match map.get(v) {
+            Some(existing) if existing != other_formula => Vec::new(),
+            Some(_) => vec![map],
+            None => {
+                let mut map = map;
                 map.insert(v.clone(), other_formula.clone());
-                true
+                vec![map]
             }
+        },
+
+        Term::Fun(f1, a1) => match other_formula {
+            Term::Fun(f2, a2) if f1 == f2 && a1.len() == a2.len() => {
+                if symmetric && is_equality(f1) && a1.len() == 2 {
+                    // the given orientation first, then the flipped one
+                    let a2_flipped = [a2[1].clone(), a2[0].clone()];
+                    let mut maps = match_args(a1, a2, map.clone(), symmetric);
+                    maps.extend(match_args(a1, &a2_flipped, map, symmetric));
+                    maps
+                } else {
+                    match_args(a1, a2, map, symmetric)
+                }
+            }
+            _ => Vec::new(),
+        },
+    }
+}
+
+fn match_args(
+    args: &[Term],
+    other_args: &[Term],
+    map: HashMap<String, Term>,
+    symmetric: bool,
+) -> Vec<HashMap<String, Term>> {
+    args.iter()
+        .zip(other_args.iter())
+        .fold(vec![map], |maps, (sub1, sub2)| {
+            maps.into_iter()
+                .flat_map(|map| match_terms(sub1, sub2, map, symmetric))
+                .collect()
+        })
+}
+
+/// Match the bodies of both formulas (leading quantifiers removed) and return
+/// every variable map under which they match
+fn match_bodies(formula: &str, other_formula: &str, symmetric: bool) -> Vec<HashMap<String, Term>> {
+    let (_, body) = split_quantifier(formula);
+    let (_, other_body) = split_quantifier(other_formula);
+
+    match_terms(
+        &parse_formula(&body),
+        &parse_formula(&other_body),
+        HashMap::new(),
+        symmetric,
+    )
+}
+
+/// Pattern match `formula` onto `other_formula` and return the substitution used,
+/// i.e. for every variable of `formula` the subterm of `other_formula` it stands for.
+/// Returns `None` if `other_formula` is not an instance of `formula` (up to the
+/// orientation of equalities).
+pub fn match_with_substitution(formula: &str, other_formula: &str) -> Option<Substitution> {
+    match_bodies(formula, other_formula, true)
+        .into_iter()
+        .next()
+        .map(|map| {
+            map.into_iter()
+                .map(|(var, term)| (var, term.to_string()))
+                .collect()
+        })
+}
+
+/// Unit-equality subsumption: `general` subsumes `specific` if `specific` is an
+/// instance of `general` (up to the orientation of equalities)
+pub fn subsumes(general: &str, specific: &str) -> bool {
+    !match_bodies(general, specific, true).is_empty()
+}
+
+/// `general` subsumes `specific` but is not alpha-equivalent to it
+pub fn strictly_subsumes(general: &str, specific: &str) -> bool {
+    subsumes(general, specific) && !subsumes(specific, general)
+}
+
+/// A substitution is a renaming if it maps variables injectively onto variables
+pub fn is_renaming(subst: &Substitution) -> bool {
+    onto_distinct_variables(subst.values().map(|t| parse_term(t)))
+}
+
+fn onto_distinct_variables(images: impl IntoIterator<Item = Term>) -> bool {
+    let mut seen: HashSet<String> = HashSet::new();
+    images.into_iter().all(|t| match t {
+        Term::Var(v) => seen.insert(v),
+        Term::Fun(_, _) => false,
+    })
+}
+
+/// Canonical representative of the alpha-equivalence class of a formula:
+/// leading quantifiers are dropped and variables are renamed V0, V1, ... in
+/// order of first occurrence. Two formulas are alpha-equivalent iff their
+/// canonical forms are equal. A top-level (dis)equality is oriented so that
+/// `s = t` and `t = s` share the same canonical form.
+pub fn canonical_form(formula: &str) -> String {
+    let (_, body) = split_quantifier(formula);
+    let term = parse_formula(&body);
+    let canonical = |t: &Term| rename_vars(t, &mut HashMap::new()).to_string();
+
+    match &term {
+        Term::Fun(op, args) if is_equality(op) && args.len() == 2 => {
+            let flipped = Term::Fun(op.clone(), vec![args[1].clone(), args[0].clone()]);
+            std::cmp::min(canonical(&term), canonical(&flipped))
         }
+        _ => canonical(&term),
+    }
+}
 
-        Term::Fun(f1, a1) => {
-            if let Term::Fun(f2, a2) = other_formula {
-                if f1 != f2 || a1.len() != a2.len() {
-                    return false;
-                }
-                for (sub1, sub2) in a1.iter().zip(a2.iter()) {
-                    if !match_terms(sub1, sub2, map) {
-                        return false;
-                    }
-                }
-                true
+fn rename_vars(term: &Term, names: &mut HashMap<String, String>) -> Term {
+    match term {
+        Term::Var(v) => {
+            let next = format!("V{}", names.len());
+            Term::Var(names.entry(v.clone()).or_insert(next).clone())
+        }
+        Term::Fun(f, args) => Term::Fun(
+            f.clone(),
+            args.iter().map(|a| rename_vars(a, names)).collect(),
+        ),
+    }
+}
+
+/// Bring a formula into the shape used to match proof steps against lemmas:
+/// enclosing parentheses and leading `!` quantifier prefixes are dropped and a
+/// top-level equation is oriented canonically. With `strip_negation`, as for
+/// steps derived from the negated conjecture, an outer `~` is dropped and a
+/// disequation `s != t` becomes `s = t`.
+pub fn normalize_for_matching(formula: &str, strip_negation: bool) -> String {
+    let prefix_re = Regex::new(r"^!\s*\[[^\]]*\]\s*:").unwrap();
+    let mut s = strip_outer_parens(formula.trim());
+    while let Some(prefix) = prefix_re.find(s) {
+        s = strip_outer_parens(s[prefix.end()..].trim());
+    }
+    if strip_negation {
+        if let Some(rest) = s.strip_prefix('~') {
+            s = strip_outer_parens(rest.trim());
+        }
+    }
+
+    match split_equality(s) {
+        Some((lhs, op, rhs)) => {
+            let op = if strip_negation { "=" } else { op };
+            let (lhs, rhs) = (strip_outer_parens(lhs), strip_outer_parens(rhs));
+            let forward = format!("{} {} {}", lhs, op, rhs);
+            let backward = format!("{} {} {}", rhs, op, lhs);
+            if canonical_form_oriented(&backward) < canonical_form_oriented(&forward) {
+                backward
             } else {
-                false
+                forward
             }
         }
+        None => s.to_string(),
     }
 }
 
-/// Checks whether two formulas match modulo variable renaming (alpha-equivalence),
-/// including quantified variables at the top level.
-pub fn formulas_match_with_permutations(formula: &str, other_formula: &str) -> bool {
-    let quant_re = Regex::new(r"!\s*\[([^\]]*)\]\s*:\s*(.*)").unwrap();
+/// Canonical form without trying the other orientation of an equation
+fn canonical_form_oriented(formula: &str) -> String {
+    rename_vars(&parse_formula(formula), &mut HashMap::new()).to_string()
+}
 
-    let (vars, body) = if let Some(cap) = quant_re.captures(formula) {
-        let vars: Vec<String> = cap[1].split(',').map(|v| v.trim().to_string()).collect();
-        (vars, cap[2].trim().to_string())
-    } else {
-        (Vec::new(), formula.to_string())
-    };
+/// Index over formulas keyed by their canonical form, so alpha-equivalent
+/// formulas can be looked up directly instead of matching against every entry.
+#[derive(Debug, Clone)]
+pub struct FormulaIndex<T> {
+    buckets: HashMap<String, Vec<T>>,
+}
 
-    // normalize other_formula once
-    let other_norm = normalize_formula_alpha(other_formula);
-    let parsed_other = parse_formula(&other_norm);
-
-    if vars.len() <= 3 {
-        // try all permutations for small number of quantified variables
-        for perm in vars.iter().permutations(vars.len()).unique() {
-            let mut body_perm = body.clone();
-            for (i, var) in perm.iter().enumerate() {
-                let canon_var = format!("V{}", i);
-                let var_re = Regex::new(&format!(r"\b{}\b", regex::escape(var))).unwrap();
-                body_perm = var_re
-                    .replace_all(&body_perm, canon_var.as_str())
-                    .to_string();
-            }
+impl<T> Default for FormulaIndex<T> {
+    fn default() -> Self {
+        FormulaIndex {
+            buckets: HashMap::new(),
+        }
+    }
+}
 
-            let norm_body = normalize_formula_alpha(&body_perm);
-            let parsed_formula = parse_formula(&norm_body);
+impl<T> FormulaIndex<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-            let mut map: HashMap<String, Term> = HashMap::new();
-            if match_terms(&parsed_formula, &parsed_other, &mut map) {
-                return true;
-            }
+    /// Add a value under the canonical form of `formula`
+    pub fn insert(&mut self, formula: &str, value: T) {
+        self.buckets
+            .entry(canonical_form(formula))
+            .or_default()
+            .push(value);
+    }
+
+    /// All values whose formula is alpha-equivalent to `formula`, in insertion order
+    pub fn lookup_alpha_equivalent(&self, formula: &str) -> &[T] {
+        self.buckets
+            .get(&canonical_form(formula))
+            .map(|v| v.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+/// Index over formulas by their head symbols, so the formulas that may
+/// subsume a given one are found without matching against every entry. A
+/// formula can only be an instance of one whose equation sides have the same
+/// head symbols, or are variables, and otherwise of one with the same
+/// predicate or number of literals.
+#[derive(Debug, Clone)]
+pub struct SubsumptionIndex<T> {
+    buckets: HashMap<String, Vec<(String, T)>>,
+}
+
+impl<T> Default for SubsumptionIndex<T> {
+    fn default() -> Self {
+        SubsumptionIndex {
+            buckets: HashMap::new(),
         }
-        false
-    } else {
-        // for larger formulas, just normalize in order without permutations
-        let norm_body = normalize_formula_alpha(&body);
-        let parsed_formula = parse_formula(&norm_body);
+    }
+}
 
-        let mut map: HashMap<String, Term> = HashMap::new();
-        match_terms(&parsed_formula, &parsed_other, &mut map)
+impl<T> SubsumptionIndex<T> {
+    pub fn new() -> Self {
+        Self::default()
     }
+
+    /// Add a value under the head symbols of `formula`
+    pub fn insert(&mut self, formula: &str, value: T) {
+        let key = shape_key(&body_term(formula));
+        self.buckets
+            .entry(key)
+            .or_default()
+            .push((formula.to_string(), value));
+    }
+
+    /// All values whose formula strictly subsumes `formula`
+    pub fn strict_generalizations(&self, formula: &str) -> Vec<&T> {
+        generalized_keys(&body_term(formula))
+            .iter()
+            .filter_map(|key| self.buckets.get(key))
+            .flatten()
+            .filter(|(general, _)| strictly_subsumes(general, formula))
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    /// All values whose formula is an instance of `formula`, alpha-equivalent
+    /// ones included, in no particular order
+    pub fn instances(&self, formula: &str) -> Vec<&T> {
+        let key = shape_key(&body_term(formula));
+        self.buckets
+            .iter()
+            .filter(|(other, _)| key_generalizes(&key, other))
+            .flat_map(|(_, entries)| entries)
+            .filter(|(specific, _)| subsumes(formula, specific))
+            .map(|(_, value)| value)
+            .collect()
+    }
+}
+
+fn body_term(formula: &str) -> Term {
+    parse_formula(&split_quantifier(formula).1)
+}
+
+/// `f/2` for a term `f(s, t)`, `*` for a variable
+fn head(term: &Term) -> String {
+    match term {
+        Term::Var(_) => "*".to_string(),
+        Term::Fun(f, args) => format!("{}/{}", f, args.len()),
+    }
+}
+
+/// The head symbols of a formula, with the sides of an equation in sorted
+/// order since subsumption ignores the orientation
+fn shape_key(term: &Term) -> String {
+    match term {
+        Term::Fun(op, args) if is_equality(op) && args.len() == 2 => {
+            equation_key(op, head(&args[0]), head(&args[1]))
+        }
+        _ => head(term),
+    }
+}
+
+fn equation_key(op: &str, lhs: String, rhs: String) -> String {
+    let mut sides = [lhs, rhs];
+    sides.sort();
+    format!("{} {} {}", sides[0], op, sides[1])
+}
+
+/// Whether a formula with key `specific` may be an instance of one with key
+/// `general`, the inverse of `generalized_keys`
+fn key_generalizes(general: &str, specific: &str) -> bool {
+    if general == "*" || general == specific {
+        return true;
+    }
+    let sides = |key: &str| -> Option<(String, String, String)> {
+        let mut parts = key.split(' ');
+        let lhs = parts.next()?.to_string();
+        let op = parts.next()?.to_string();
+        let rhs = parts.next()?.to_string();
+        Some((lhs, op, rhs))
+    };
+    let (Some((gl, gop, gr)), Some((sl, sop, sr))) = (sides(general), sides(specific)) else {
+        return false;
+    };
+    let side = |g: &str, s: &str| g == "*" || g == s;
+    gop == sop && ((side(&gl, &sl) && side(&gr, &sr)) || (side(&gl, &sr) && side(&gr, &sl)))
+}
+
+/// The keys of the formulas `term` may be an instance of: either side of an
+/// equation may have been a variable, and so may the whole formula
+fn generalized_keys(term: &Term) -> Vec<String> {
+    let mut keys = match term {
+        Term::Fun(op, args) if is_equality(op) && args.len() == 2 => {
+            let mut keys = Vec::new();
+            for lhs in [head(&args[0]), "*".to_string()] {
+                for rhs in [head(&args[1]), "*".to_string()] {
+                    keys.push(equation_key(op, lhs.clone(), rhs));
+                }
+            }
+            keys
+        }
+        _ => vec![head(term)],
+    };
+    keys.push("*".to_string());
+    keys.sort();
+    keys.dedup();
+    keys
 }
 
 #[cfg(test)]
@@ -250,6 +658,115 @@ mod tests {
         assert!(formulas_match(twee, vamp));
     }
 
+    #[test]
+    fn test_substitution() {
+        let lemma = "! [X, Y] : (op(X, Y) = X)";
+        let step = "(op(sK0,op(X1,X1)) = sK0)";
+        let subst = match_with_substitution(lemma, step).unwrap();
+        assert_eq!(subst["X"], "sK0");
+        assert_eq!(subst["Y"], "op(X1,X1)");
+    }
+
+    #[test]
+    fn test_substitution_non_instance() {
+        let lemma = "! [X, Y] : (op(X, Y) = X)";
+        let step = "(op(sK0,sK1) = sK1)";
+        assert!(match_with_substitution(lemma, step).is_none());
+    }
+
+    #[test]
+    fn test_subsumes() {
+        let general = "! [X, Y] : (op(X, Y) = X)";
+        let instance = "! [X0] : (X0 = op(X0, op(X0, X0)))";
+        assert!(strictly_subsumes(general, instance));
+        assert!(!subsumes(instance, general));
+        assert!(!strictly_subsumes(general, "! [Z, W] : (op(Z, W) = Z)"));
+    }
+
+    #[test]
+    fn test_index_lookup() {
+        let mut index = FormulaIndex::new();
+        index.insert("! [X0, X1] : (op(X1,X0) = X1)", 1);
+        index.insert("(op(X0,X0) = X0)", 2);
+        assert_eq!(index.lookup_alpha_equivalent("! [X, Y] : (op(Y, X) = Y)"), &[1]);
+        assert_eq!(index.lookup_alpha_equivalent("(op(X5,X5) = X5)"), &[2]);
+        assert!(index.lookup_alpha_equivalent("(op(X0,X1) = X0)").len() == 1);
+        assert!(index.lookup_alpha_equivalent("(op(X0,X1) = X1)").is_empty());
+    }
+
+    #[test]
+    fn test_match_flipped() {
+        let twee = "! [X, Y] : (X = op(X, Y))";
+        let vamp = "(op(X0,X1) = X0)";
+        assert!(formulas_match(twee, vamp));
+        assert!(!formulas_match_with_orientation(twee, vamp, true));
+    }
+
+    #[test]
+    fn test_match_flipped_in_clause() {
+        // the first literal matches in both orientations, only the flipped one
+        // is consistent with the second literal
+        let lemma = "(X = Y | p(Y))";
+        let clause = "(a = b | p(a))";
+        assert!(subsumes(lemma, clause));
+        assert_eq!(match_with_substitution(lemma, clause).unwrap()["Y"], "a");
+        assert!(formulas_match("(X = Y | p(Y))", "(Z = W | p(Z))"));
+        assert!(!formulas_match_with_orientation(
+            "(X = Y | p(Y))",
+            "(Z = W | p(Z))",
+            true
+        ));
+    }
+
+    #[test]
+    fn test_subsumption_index() {
+        let mut index = SubsumptionIndex::new();
+        index.insert("! [X, Y] : (op(X, Y) = X)", 1);
+        index.insert("! [X] : (X = inv(X))", 2);
+        index.insert("! [X, Y] : (op(X, Y) = op(Y, X))", 3);
+        index.insert("(op(e, e) = e)", 4);
+        assert_eq!(
+            index.strict_generalizations("! [X0] : (X0 = op(X0, op(X0, X0)))"),
+            vec![&1]
+        );
+        assert_eq!(
+            index.strict_generalizations("(inv(op(e, e)) = op(e, e))"),
+            vec![&2]
+        );
+        assert_eq!(
+            index.strict_generalizations("(op(e, e) = op(e, e))"),
+            vec![&3]
+        );
+        // alpha-equivalent formulas do not strictly subsume each other
+        assert!(index
+            .strict_generalizations("! [Z, W] : (op(Z, W) = Z)")
+            .is_empty());
+        assert!(index
+            .strict_generalizations("(inv(e) = op(e, e))")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_subsumption_index_instances() {
+        let mut index = SubsumptionIndex::new();
+        index.insert("(op(sK0,op(X1,X1)) = sK0)", 1);
+        index.insert("! [X0] : (X0 = op(X0,X0))", 2);
+        index.insert("(inv(e) = e)", 3);
+        index.insert("! [X0, X1] : (op(X0,X1) = X0)", 4);
+        let mut instances = index.instances("! [X, Y] : (op(X, Y) = X)");
+        instances.sort();
+        assert_eq!(instances, vec![&1, &2, &4]);
+        assert_eq!(index.instances("! [X] : (inv(X) = X)"), vec![&3]);
+        assert!(index.instances("(op(e, e) = e)").is_empty());
+    }
+
+    #[test]
+    fn test_index_flipped() {
+        let mut index = FormulaIndex::new();
+        index.insert("(op(X0,X1) = X0)", 1);
+        assert_eq!(index.lookup_alpha_equivalent("(X3 = op(X3,X4))"), &[1]);
+    }
+
     #[test]
     fn test_norm1() {
         let form = "! [X, Y] : (op(X, Y) = X)";
@@ -298,4 +815,22 @@ mod tests {
 
         assert!(formulas_match(formula1, formula2));
     }
+
+    #[test]
+    fn normalize_for_matching_drops_prefixes_and_negation() {
+        let step = "(! [X0,X1] : mult(inv(X0),mult(X0,X1)) = X1)";
+        let lemma = "! [X, Y] : (mult(inv(X), mult(X, Y)) = Y)";
+        assert_eq!(
+            canonical_form(&normalize_for_matching(step, false)),
+            canonical_form(&normalize_for_matching(lemma, false))
+        );
+
+        let negated = "b != mult(inv(a),mult(a,b))";
+        assert_eq!(
+            normalize_for_matching(negated, true),
+            normalize_for_matching("mult(inv(a),mult(a,b)) = b", false)
+        );
+        assert!(normalize_for_matching(negated, false).contains("!="));
+        assert_eq!(normalize_for_matching("~(p(a))", true), "p(a)");
+    }
 }
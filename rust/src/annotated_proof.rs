@@ -0,0 +1,702 @@
+use crate::alpha_match::canonical_form;
+use crate::naming::NameAllocator;
+use crate::proof_step::{detect_prover, proof_steps, rule_breakdown, ProofRole, RuleKind};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// A minimized proof put together from the proofs of its parts: the input
+// problem, then the start (superposition or dependency) proofs, the history
+// lemma, the root lemma and the conjecture, always in this order. A lemma
+// stated in several parts goes by one name in all of them.
+
+const INPUT_HEADER: &str = "% === Input Problem ===";
+
+/// First line of a start proof made of superposition steps taken from the Vampire proof
+pub const SUPERPOSITION_HEADER: &str = "% === Superposition Steps ===";
+
+/// Start of the line after a superposition step naming the Vampire rule that derived it
+pub const SUPERPOSITION_RULE: &str = "%   by ";
+
+/// Start of the line marking the output of a lemma no prover proved, kept
+/// with `--assume-unproved`, which a minimized proof assumes instead
+const HYPOTHESIS_MARK: &str = "% HYPOTHESIS: ";
+
+/// The prover `output` for `lemma` marked as a hypothesis the proof assumes
+pub fn hypothesis(lemma: &str, output: &str) -> String {
+    format!("{}{} is not proved\n{}", HYPOTHESIS_MARK, lemma, output)
+}
+
+/// What a section of a minimized proof proves, in the order sections are printed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum SectionKind {
+    /// superposition steps or the proofs of the dependencies
+    Start,
+    History,
+    Root,
+    Conjecture,
+}
+
+impl SectionKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            SectionKind::Start => "start",
+            SectionKind::History => "history",
+            SectionKind::Root => "root",
+            SectionKind::Conjecture => "conjecture",
+        }
+    }
+}
+
+/// One proof of a minimized proof and where it comes from
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProofSection {
+    pub kind: SectionKind,
+    /// the lemma proved, none for start proofs and the conjecture
+    pub lemma: Option<String>,
+    /// prover that wrote the proof, judged by its format
+    pub prover: String,
+    pub steps: usize,
+    pub proof: String,
+}
+
+/// Sections of a minimized proof with their step counts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnotatedProofBuilder {
+    input: String,
+    sections: Vec<ProofSection>,
+}
+
+impl AnnotatedProofBuilder {
+    pub fn new(input: &str) -> AnnotatedProofBuilder {
+        AnnotatedProofBuilder {
+            input: input.to_string(),
+            sections: Vec::new(),
+        }
+    }
+
+    /// Add the proof of `lemma` (none for start proofs and the conjecture) taking `steps` steps
+    pub fn add(
+        &mut self,
+        kind: SectionKind,
+        lemma: Option<&str>,
+        proof: &str,
+        steps: usize,
+    ) -> &mut AnnotatedProofBuilder {
+        self.sections.push(ProofSection {
+            kind,
+            lemma: lemma.map(str::to_string),
+            prover: detect_prover(proof).to_string(),
+            steps,
+            proof: proof.to_string(),
+        });
+        self
+    }
+
+    /// Steps of all sections together
+    pub fn total_steps(&self) -> usize {
+        self.sections.iter().map(|s| s.steps).sum()
+    }
+
+    /// Lemmas the sections assume without a proof, see `hypothesis`. A proof
+    /// with any is only conditional.
+    pub fn hypotheses(&self) -> Vec<String> {
+        let assumed: BTreeSet<&str> = self
+            .sections
+            .iter()
+            .flat_map(|s| s.proof.lines())
+            .filter_map(|line| line.strip_prefix(HYPOTHESIS_MARK))
+            .filter_map(|rest| rest.split_whitespace().next())
+            .collect();
+        assumed.into_iter().map(str::to_string).collect()
+    }
+
+    /// Steps of all sections by rule kind, plus a cut for every root or
+    /// history lemma, which the sections after it use as an axiom. Steps that
+    /// cannot be read from a section's proof count as `other`.
+    pub fn rule_breakdown(&self) -> BTreeMap<RuleKind, usize> {
+        let mut rules = BTreeMap::new();
+        for section in &self.sections {
+            let mut section_rules = if section.proof.starts_with(SUPERPOSITION_HEADER) {
+                superposition_rules(&section.proof, section.steps)
+            } else {
+                proof_steps(&section.prover, &section.proof)
+                    .map(|steps| rule_breakdown(&steps))
+                    .unwrap_or_default()
+            };
+            let counted: usize = section_rules.values().sum();
+            if counted < section.steps {
+                *section_rules.entry(RuleKind::Other).or_default() += section.steps - counted;
+            }
+            for (kind, n) in section_rules {
+                *rules.entry(kind).or_default() += n;
+            }
+            if section.lemma.is_some() {
+                *rules.entry(RuleKind::Cut).or_default() += 1;
+            }
+        }
+        rules
+    }
+
+    /// Sections in print order; sections of the same kind keep the order they were added in
+    fn ordered(&self) -> Vec<&ProofSection> {
+        let mut sections: Vec<&ProofSection> = self.sections.iter().collect();
+        sections.sort_by_key(|s| s.kind);
+        sections
+    }
+
+    /// Sections in print order, with every lemma that more than one of them
+    /// states named alike in all of them: by the first name the document gives
+    /// it (an axiom, lemma or superposition step name), otherwise by a fresh
+    /// `shared_lemma_<n>`. Twee statements and the rewrites using them are
+    /// relabeled; Vampire and egg steps, whose names are their numbers, get a
+    /// `% <step> is <name>` line before them.
+    fn unified(&self) -> Vec<ProofSection> {
+        let sections = self.ordered();
+        let statements: Vec<Vec<Statement>> = sections.iter().map(|s| statements(s)).collect();
+
+        let mut found_in: BTreeMap<&str, BTreeSet<usize>> = BTreeMap::new();
+        for (i, section) in statements.iter().enumerate() {
+            for statement in section {
+                found_in.entry(&statement.key).or_default().insert(i);
+            }
+        }
+        let mut names = NameAllocator::for_problem(&self.input).unwrap_or_default();
+        let given: Vec<&String> = statements
+            .iter()
+            .flatten()
+            .filter_map(|s| s.given.as_ref())
+            .collect();
+        names.reserve(&given);
+        // names of axioms and lemmas before those of goals, which are often just `goal`
+        let mut shared: BTreeMap<&str, String> = BTreeMap::new();
+        for goals in [false, true] {
+            for statement in statements.iter().flatten() {
+                if found_in[statement.key.as_str()].len() > 1
+                    && statement.given.is_some()
+                    && statement.goal == goals
+                {
+                    shared
+                        .entry(&statement.key)
+                        .or_insert_with(|| statement.local.clone());
+                }
+            }
+        }
+        let mut unnamed = 0;
+        for statement in statements.iter().flatten() {
+            if found_in[statement.key.as_str()].len() > 1
+                && !shared.contains_key(statement.key.as_str())
+            {
+                unnamed += 1;
+                let name = names.fresh(&format!("shared_lemma_{}", unnamed));
+                shared.insert(&statement.key, name);
+            }
+        }
+
+        sections
+            .into_iter()
+            .zip(&statements)
+            .map(|(section, statements)| {
+                let renamed: BTreeMap<&str, &str> = statements
+                    .iter()
+                    .filter_map(|s| {
+                        let name = shared.get(s.key.as_str())?;
+                        (name != &s.local).then_some((s.local.as_str(), name.as_str()))
+                    })
+                    .collect();
+                let mut section = section.clone();
+                if !renamed.is_empty() {
+                    section.proof = rename(&section, &renamed);
+                }
+                section
+            })
+            .collect()
+    }
+
+    /// The input problem under its header, followed by the proofs of the
+    /// sections. A conditional proof says so first.
+    pub fn render(&self) -> String {
+        let hypotheses = self.hypotheses();
+        let mut text = if hypotheses.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "% CONDITIONAL: this proof assumes the unproved lemmas {}\n",
+                hypotheses.join(", ")
+            )
+        };
+        text.push_str(&format!("{}\n{}\n\n", INPUT_HEADER, self.input));
+        for section in self.unified() {
+            text.push_str(&section.proof);
+        }
+        text
+    }
+
+    /// The derivation as a DOT graph: the input axioms and the proof of every
+    /// section in print order, labelled with its steps. There is an edge into a
+    /// section from every earlier section it uses, see `premises`; a section
+    /// that uses none is built on the axioms alone, the others may use them too.
+    pub fn to_dot(&self) -> String {
+        let mut dot = format!(
+            "digraph proof {{\n    label=\"{} steps\";\n    node [shape=box];\n    axioms [label=\"input axioms\", shape=ellipse];\n",
+            self.total_steps()
+        );
+        let sections = self.ordered();
+        let statements: Vec<Vec<Statement>> = sections.iter().map(|s| statements(s)).collect();
+        for (i, section) in sections.iter().enumerate() {
+            let node = format!("s{}", i);
+            let what = match (&section.lemma, section.kind) {
+                (Some(lemma), _) => lemma.clone(),
+                (None, SectionKind::Start) if section.proof.starts_with(SUPERPOSITION_HEADER) => {
+                    "superposition steps".to_string()
+                }
+                (None, SectionKind::Start) => "dependencies".to_string(),
+                (None, kind) => kind.name().to_string(),
+            };
+            let mut attrs = vec![format!(
+                "label=\"{}: {}\\n{} steps ({})\"",
+                section.kind.name(),
+                what,
+                section.steps,
+                section.prover
+            )];
+            if section.kind == SectionKind::Conjecture {
+                attrs.push("style=filled".to_string());
+                attrs.push("fillcolor=lightyellow".to_string());
+            }
+            dot.push_str(&format!("    {} [{}];\n", node, attrs.join(", ")));
+            let premises = premises(&statements, sections.as_slice(), i);
+            if premises.is_empty() {
+                dot.push_str(&format!("    axioms -> {};\n", node));
+            } else {
+                for premise in premises {
+                    dot.push_str(&format!("    s{} -> {};\n", premise, node));
+                }
+                dot.push_str(&format!("    axioms -> {} [style=dashed];\n", node));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The sections, total steps and steps by rule as JSON, for tools reading the result
+    pub fn to_json(&self) -> Result<String, String> {
+        let rules: BTreeMap<&str, usize> = self
+            .rule_breakdown()
+            .into_iter()
+            .map(|(kind, n)| (kind.name(), n))
+            .collect();
+        serde_json::to_string_pretty(&serde_json::json!({
+            "total_steps": self.total_steps(),
+            "assumes": self.hypotheses(),
+            "rules": rules,
+            "sections": self.unified(),
+        }))
+        .map_err(|e| format!("Failed to serialize the proof sections: {}", e))
+    }
+}
+
+/// A formula a section states under `local`, its name there. `given` is
+/// that name if it means something outside the section, unlike Twee's
+/// `lemma_3` or Vampire's `f12`.
+/// The rules of the steps of a superposition section, by the rule line after
+/// each step; steps written before the rules were recorded count as superposition
+fn superposition_rules(proof: &str, steps: usize) -> BTreeMap<RuleKind, usize> {
+    let mut rules = BTreeMap::new();
+    for rule in proof
+        .lines()
+        .filter_map(|line| line.strip_prefix(SUPERPOSITION_RULE))
+    {
+        *rules.entry(RuleKind::of(rule.trim())).or_default() += 1;
+    }
+    let counted: usize = rules.values().sum();
+    if counted == 0 {
+        rules.insert(RuleKind::Superposition, steps);
+    }
+    rules
+}
+
+struct Statement {
+    local: String,
+    given: Option<String>,
+    /// canonical form of the formula
+    key: String,
+    goal: bool,
+    /// an axiom of the section's problem, given rather than derived
+    axiom: bool,
+}
+
+fn statements(section: &ProofSection) -> Vec<Statement> {
+    if section.proof.starts_with(SUPERPOSITION_HEADER) {
+        // "% <name>: <formula> | deps: ..."
+        return section
+            .proof
+            .lines()
+            .filter_map(|line| line.strip_prefix("% ")?.split_once(": "))
+            .filter_map(|(name, rest)| Some((name, rest.split_once(" | deps:")?.0)))
+            .map(|(name, formula)| Statement {
+                local: name.to_string(),
+                given: Some(name.to_string()),
+                key: canonical_form(formula),
+                goal: false,
+                axiom: false,
+            })
+            .collect();
+    }
+    let Ok(steps) = proof_steps(&section.prover, &section.proof) else {
+        return Vec::new();
+    };
+    let twee = section.prover == "twee";
+    steps
+        .into_iter()
+        .filter(|step| match step.role {
+            // Twee's inferences are the single rewrites of a statement
+            ProofRole::Inference => !twee,
+            ProofRole::NegatedConjecture | ProofRole::Preprocessing => false,
+            _ => true,
+        })
+        .map(|step| Statement {
+            given: (twee && !is_twee_number(&step.name)).then(|| step.name.clone()),
+            key: canonical_form(&step.formula.to_string()),
+            goal: step.role == ProofRole::Conjecture,
+            axiom: step.role == ProofRole::Axiom,
+            local: step.name,
+        })
+        .collect()
+}
+
+/// The sections before section `i` that it uses: those proving a formula it
+/// is given as an axiom, by canonical form. A superposition section offers
+/// every step it derives, any other section the goal it proves.
+fn premises(statements: &[Vec<Statement>], sections: &[&ProofSection], i: usize) -> Vec<usize> {
+    let given: BTreeSet<&str> = statements[i]
+        .iter()
+        .filter(|statement| statement.axiom)
+        .map(|statement| statement.key.as_str())
+        .collect();
+    (0..i)
+        .filter(|&j| {
+            let superposition = sections[j].proof.starts_with(SUPERPOSITION_HEADER);
+            statements[j]
+                .iter()
+                .filter(|statement| superposition || statement.goal)
+                .any(|statement| given.contains(statement.key.as_str()))
+        })
+        .collect()
+}
+
+/// `lemma_3`, `axiom_1` or `goal_1`: a statement Twee numbered but nobody named
+fn is_twee_number(name: &str) -> bool {
+    name.split_once('_').is_some_and(|(kind, number)| {
+        ["axiom", "lemma", "goal"].contains(&kind) && number.parse::<usize>().is_ok()
+    })
+}
+
+/// The proof of `section` with the statements in `renamed` (local name ->
+/// shared name) renamed
+fn rename(section: &ProofSection, renamed: &BTreeMap<&str, &str>) -> String {
+    let superposition = section.proof.starts_with(SUPERPOSITION_HEADER);
+    let mut text = String::new();
+    for line in section.proof.lines() {
+        let line = if superposition {
+            rename_superposition_line(line, renamed)
+        } else if section.prover == "twee" {
+            rename_twee_line(line, renamed)
+        } else {
+            // Vampire's `12. ...` is step f12, egg's `fof(s3, ...` step s3
+            let trimmed = line.trim_start();
+            let local = match trimmed.split_once('.') {
+                Some((n, _)) if n.parse::<usize>().is_ok() => Some((n, format!("f{}", n))),
+                _ => trimmed
+                    .strip_prefix("fof(")
+                    .and_then(|rest| rest.split_once(','))
+                    .map(|(name, _)| (name.trim(), name.trim().to_string())),
+            };
+            if let Some(name) = local.and_then(|(shown, local)| {
+                renamed
+                    .get(local.as_str())
+                    .map(|name| format!("% {} is {}\n", shown, name))
+            }) {
+                text.push_str(&name);
+            }
+            line.to_string()
+        };
+        text.push_str(&line);
+        text.push('\n');
+    }
+    if !section.proof.ends_with('\n') {
+        text.pop();
+    }
+    text
+}
+
+/// `Lemma 3: f` and `= { by lemma 3 }` as `Lemma 3 (name): f` and
+/// `= { by lemma 3 (name) }`, which Twee's own format allows
+fn rename_twee_line(line: &str, renamed: &BTreeMap<&str, &str>) -> String {
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let trimmed = line.trim_start();
+    if let Some((head, rest)) = trimmed.split_once(": ") {
+        if let Some(head) = rename_twee_reference(head, renamed) {
+            return format!("{}{}: {}", indent, head, rest);
+        }
+    }
+    if let Some(by) = trimmed
+        .strip_prefix("= { by ")
+        .and_then(|rest| rest.strip_suffix(" }"))
+    {
+        let (by, direction) = match by.strip_suffix(" R->L") {
+            Some(by) => (by, " R->L"),
+            None => (by, ""),
+        };
+        if let Some(by) = rename_twee_reference(by, renamed) {
+            return format!("{}= {{ by {}{} }}", indent, by, direction);
+        }
+    }
+    line.to_string()
+}
+
+/// `Lemma 3`, `axiom 1 (a1)` and the like with the name given in `renamed`,
+/// if it has one there
+fn rename_twee_reference(reference: &str, renamed: &BTreeMap<&str, &str>) -> Option<String> {
+    let mut words = reference.splitn(3, ' ');
+    let kind = words.next()?;
+    if !["axiom", "lemma", "goal"].contains(&kind.to_lowercase().as_str()) {
+        return None;
+    }
+    let number = words.next()?;
+    number.parse::<usize>().ok()?;
+    let local = match words.next() {
+        Some(label) => label.strip_prefix('(')?.strip_suffix(')')?.to_string(),
+        None => format!("{}_{}", kind.to_lowercase(), number),
+    };
+    let name = renamed.get(local.as_str())?;
+    Some(format!("{} {} ({})", kind, number, name))
+}
+
+/// `% name: f | deps: a->3, b->4` with the step and its deps renamed
+fn rename_superposition_line(line: &str, renamed: &BTreeMap<&str, &str>) -> String {
+    let Some((name, rest)) = line.strip_prefix("% ").and_then(|l| l.split_once(": ")) else {
+        return line.to_string();
+    };
+    let Some((formula, deps)) = rest.split_once(" | deps: ") else {
+        return line.to_string();
+    };
+    let deps: Vec<String> = deps
+        .split(", ")
+        .map(|dep| match dep.split_once("->") {
+            Some((name, n)) => format!("{}->{}", renamed.get(name).unwrap_or(&name), n),
+            None => dep.to_string(),
+        })
+        .collect();
+    format!(
+        "% {}: {} | deps: {}",
+        renamed.get(name).unwrap_or(&name),
+        formula,
+        deps.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_render_in_order_whatever_the_order_added() {
+        let mut proof = AnnotatedProofBuilder::new("fof(a1, axiom, f(X) = X).");
+        proof
+            .add(SectionKind::Conjecture, None, "conjecture proof\n", 2)
+            .add(
+                SectionKind::Root,
+                Some("single_lemma_0002"),
+                "root proof\n",
+                3,
+            )
+            .add(SectionKind::Start, None, "start proof\n", 1);
+        assert_eq!(proof.total_steps(), 6);
+        assert_eq!(
+            proof.render(),
+            "% === Input Problem ===\nfof(a1, axiom, f(X) = X).\n\nstart proof\nroot proof\nconjecture proof\n"
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&proof.to_json().unwrap()).unwrap();
+        assert_eq!(json["total_steps"], 6);
+        assert_eq!(json["sections"][1]["kind"], "root");
+        assert_eq!(json["sections"][1]["lemma"], "single_lemma_0002");
+        // none of the test proofs can be read; the root lemma is cut into the conjecture proof
+        assert_eq!(json["rules"]["other"], 6);
+        assert_eq!(json["rules"]["cut"], 1);
+
+        let dot = proof.to_dot();
+        assert!(
+            dot.contains("s1 [label=\"root: single_lemma_0002\\n3 steps (vampire)\"];"),
+            "{}",
+            dot
+        );
+        // proofs that cannot be read use nothing but the axioms
+        assert!(dot.contains("    axioms -> s2;\n"), "{}", dot);
+        assert!(!dot.contains("s1 -> s2"), "{}", dot);
+    }
+
+    #[test]
+    fn superposition_steps_count_by_their_rules() {
+        let steps = "% === Superposition Steps ===\n\
+                     % sp_1: mult(inv(X0),X0) = mult(e,e) | deps: a2->0, a1->0\n\
+                     %   by superposition\n\
+                     % sp_2: mult(e,e) = e | deps: a1->0\n\
+                     %   by forward demodulation\n\
+                     % sp_3: e = e | deps: sp_2->2\n\
+                     %   by trivial inequality removal\n\n";
+        let mut proof = AnnotatedProofBuilder::new("fof(a1, axiom, f(X) = X).");
+        proof.add(SectionKind::Start, None, steps, 3);
+        let rules = proof.rule_breakdown();
+        assert_eq!(
+            rules,
+            BTreeMap::from([
+                (RuleKind::Superposition, 1),
+                (RuleKind::Demodulation, 1),
+                (RuleKind::Other, 1),
+            ])
+        );
+        assert_eq!(rules.values().sum::<usize>(), proof.total_steps());
+
+        // sections written without the rules
+        let old = "% === Superposition Steps ===\n% sp_1: f(e) = e | deps: a1->0\n\n";
+        let mut proof = AnnotatedProofBuilder::new("fof(a1, axiom, f(X) = X).");
+        proof.add(SectionKind::Start, None, old, 1);
+        assert_eq!(
+            proof.rule_breakdown(),
+            BTreeMap::from([(RuleKind::Superposition, 1)])
+        );
+    }
+
+    #[test]
+    fn lemmas_shared_by_sections_go_by_one_name() {
+        let mut proof = AnnotatedProofBuilder::new("fof(a1, axiom, ! [X] : mult(e, X) = X).");
+        proof
+            .add(
+                SectionKind::Root,
+                Some("single_lemma_0004"),
+                "Axiom 1 (a1): mult(e, X) = X.\n\
+                 Axiom 2 (sp_5): mult(e, e) = e.\n\n\
+                 Goal 1 (single_lemma_0004): mult(e, mult(e, e)) = e.\n\
+                 Proof:\n  mult(e, mult(e, e))\n= { by axiom 2 (sp_5) }\n  mult(e, e)\n\
+                 = { by axiom 2 (sp_5) }\n  e\n\n",
+                2,
+            )
+            .add(
+                SectionKind::Start,
+                None,
+                "Axiom 1 (a1): mult(e, X) = X.\n\n\
+                 Lemma 2: mult(e, e) = e.\n\
+                 Proof:\n  mult(e, e)\n= { by axiom 1 (a1) }\n  e\n\n\
+                 Goal 1 (goal): mult(e, e) = e.\n\
+                 Proof:\n  mult(e, e)\n= { by lemma 2 }\n  e\n\n",
+                1,
+            )
+            .add(
+                SectionKind::Conjecture,
+                None,
+                "1. mult(e,X0) = X0 [input]\n\
+                 2. mult(e,mult(e,e)) = e [input]\n\
+                 3. mult(e,e) = e [superposition 1,2]\n",
+                1,
+            );
+        let text = proof.render();
+        // Twee's lemma 2 of the start proof is the superposition step sp_5 of the root proof
+        assert!(text.contains("Lemma 2 (sp_5): mult(e, e) = e."), "{}", text);
+        assert!(text.contains("= { by lemma 2 (sp_5) }"), "{}", text);
+        assert!(text.contains("Axiom 2 (sp_5): mult(e, e) = e."), "{}", text);
+        // the root lemma has its own name in the conjecture proof, Vampire's step 3 is sp_5
+        assert!(text.contains("% 2 is single_lemma_0004\n2. "), "{}", text);
+        assert!(text.contains("% 3 is sp_5\n3. "), "{}", text);
+        // the input axiom keeps its name from the problem
+        assert!(text.contains("% 1 is a1\n1. "), "{}", text);
+        assert!(text.contains("Goal 1 (sp_5): mult(e, e) = e."), "{}", text);
+        // the renamed Twee proof still reads the same
+        let sections = proof.unified();
+        let start = proof_steps("twee", &sections[0].proof).unwrap();
+        assert!(start.iter().any(|s| s.name == "sp_5"));
+        assert_eq!(proof.total_steps(), 4);
+        // the root uses the start, the conjecture the root, each by the formula it proves
+        let dot = proof.to_dot();
+        let edges: Vec<&str> = dot.lines().filter(|l| l.contains("->")).collect();
+        assert_eq!(
+            edges,
+            vec![
+                "    axioms -> s0;",
+                "    s0 -> s1;",
+                "    axioms -> s1 [style=dashed];",
+                "    s1 -> s2;",
+                "    axioms -> s2 [style=dashed];",
+            ]
+        );
+
+        let mut unnamed = AnnotatedProofBuilder::new("fof(a1, axiom, ! [X] : mult(e, X) = X).");
+        unnamed
+            .add(
+                SectionKind::History,
+                Some("history_lemma_0001"),
+                "Axiom 1 (a1): mult(e, X) = X.\n\n\
+                 Lemma 4: mult(e, e) = e.\n\
+                 Proof:\n  mult(e, e)\n= { by axiom 1 (a1) }\n  e\n\n",
+                1,
+            )
+            .add(
+                SectionKind::Root,
+                Some("single_lemma_0002"),
+                "Axiom 1 (a1): mult(e, X) = X.\n\n\
+                 Lemma 4: mult(e, e) = e.\n\
+                 Proof:\n  mult(e, e)\n= { by axiom 1 (a1) }\n  e\n\n\
+                 Lemma 5: mult(e, mult(e, e)) = e.\n\
+                 Proof:\n  mult(e, mult(e, e))\n= { by lemma 4 }\n  e\n\n",
+                2,
+            );
+        let text = unnamed.render();
+        assert_eq!(
+            text.matches("Lemma 4 (shared_lemma_1)").count(),
+            2,
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("= { by lemma 4 (shared_lemma_1) }"),
+            "{}",
+            text
+        );
+        // statements only one section has keep Twee's numbering
+        assert!(
+            text.contains("Lemma 5: mult(e, mult(e, e)) = e."),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn proofs_assuming_unproved_lemmas_say_so() {
+        let mut proof = AnnotatedProofBuilder::new("fof(a1, axiom, f(X) = X).");
+        proof.add(SectionKind::Conjecture, None, "conjecture proof\n", 2);
+        assert!(proof.hypotheses().is_empty());
+        assert!(proof.render().starts_with(INPUT_HEADER));
+
+        let output = "% SZS status CounterSatisfiable for single_lemma_0003\n";
+        proof.add(
+            SectionKind::Root,
+            Some("single_lemma_0003"),
+            &hypothesis("single_lemma_0003", output),
+            1,
+        );
+        assert_eq!(proof.hypotheses(), vec!["single_lemma_0003".to_string()]);
+        // the assumption is not free
+        assert_eq!(proof.total_steps(), 3);
+        let text = proof.render();
+        assert!(
+            text.starts_with(
+                "% CONDITIONAL: this proof assumes the unproved lemmas single_lemma_0003\n"
+            ),
+            "{}",
+            text
+        );
+        let json: serde_json::Value = serde_json::from_str(&proof.to_json().unwrap()).unwrap();
+        assert_eq!(json["assumes"], serde_json::json!(["single_lemma_0003"]));
+    }
+}
@@ -0,0 +1,150 @@
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Output artifacts (summaries, reports, DAGs, lemma and proof files) are
+// written to a temporary file next to their final path and renamed into
+// place, so a crash mid-write leaves the previous file or none, never a
+// truncated one. JSON artifacts cut off by an older writer are reported as
+// such when they are read. Their names embed the problem name, made safe
+// for any file system by `safe_name`.
+
+/// Temporary files started by this process, so that threads writing at the
+/// same time never share one
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Like `fs::write`, but readers of `path` see either its old or its new
+/// contents: the contents go to a temporary file in the same directory,
+/// which replaces `path` once it is complete.
+pub fn write_atomic<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> io::Result<()> {
+    let path = path.as_ref();
+    let temp = temp_path(path);
+    let written = File::create(&temp).and_then(|mut file| {
+        file.write_all(contents.as_ref())?;
+        file.sync_all()
+    });
+    let result = written.and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// `.<name>.<pid>-<n>.tmp` next to `path`; the leading dot and the extension
+/// keep it out of the directory scans for lemma and proof files
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map_or("artifact".to_string(), |name| {
+        name.to_string_lossy().to_string()
+    });
+    path.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        name,
+        std::process::id(),
+        TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Longest problem name used as is in file names. With prefixes such as
+/// `vampire_proof_` and their extensions, file names stay well below the
+/// 255 bytes most file systems allow.
+const MAX_NAME_LEN: usize = 96;
+
+/// `name`, e.g. the stem of a problem file, as it can be part of a file name
+/// on every platform: characters other than ASCII letters, digits, `-`, `_`
+/// and `.` become `_`, and names longer than `MAX_NAME_LEN` are cut. A name
+/// that had to change ends in `-` and 12 hex digits of the SHA-256 of the
+/// original, so that different names do not end up in the same files.
+pub fn safe_name(name: &str) -> String {
+    let safe: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if safe == name && !name.is_empty() && name.len() <= MAX_NAME_LEN {
+        return safe;
+    }
+    let hash = format!("{:x}", Sha256::digest(name.as_bytes()));
+    let kept: String = safe.chars().take(MAX_NAME_LEN - 13).collect();
+    format!("{}-{}", kept, &hash[..12])
+}
+
+/// Parse a JSON artifact. A document that ends early was cut off while it was
+/// written, which the error says instead of pointing at its last line.
+pub fn parse_json<T: DeserializeOwned>(text: &str) -> Result<T, String> {
+    serde_json::from_str(text).map_err(|e| {
+        if e.is_eof() {
+            format!(
+                "the file is truncated ({}), it was cut off while it was written; \
+                 run the phase that writes it again",
+                e
+            )
+        } else {
+            e.to_string()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ScratchDir;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn atomic_writes_replace_the_whole_file() {
+        let scratch = ScratchDir::new("artifact");
+        let dir = scratch.path();
+        let path = dir.join("summary.json");
+        write_atomic(&path, "{\"1\": \"first\"}").unwrap();
+        write_atomic(&path, "{\"1\": \"second\"}").unwrap();
+        let read: BTreeMap<u32, String> = parse_json(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(read[&1], "second");
+        // no temporary file is left behind
+        assert_eq!(fs::read_dir(dir).unwrap().count(), 1);
+
+        // a directory that does not exist fails like fs::write
+        assert!(write_atomic(dir.join("missing").join("x.json"), "{}").is_err());
+    }
+
+    #[test]
+    fn names_are_made_safe_for_file_names() {
+        assert_eq!(
+            safe_name("Equation650_implies_Equation448"),
+            "Equation650_implies_Equation448"
+        );
+        assert_eq!(safe_name("GRP001-1.003"), "GRP001-1.003");
+
+        let spaced = safe_name("x ◇ y = y ◇ x #2");
+        assert!(spaced.starts_with("x___y___y___x__2-"), "{}", spaced);
+        // the same characters replaced differently
+        assert_ne!(spaced, safe_name("x ◇ y = y ◇ x  2"));
+
+        let long = "Equation".repeat(40);
+        let cut = safe_name(&long);
+        assert_eq!(cut.len(), MAX_NAME_LEN);
+        assert!(cut.starts_with("EquationEquation"));
+        assert_ne!(cut, safe_name(&format!("{}x", long)));
+        // names that are safe already stay as they are
+        assert_eq!(safe_name(&cut), cut);
+        assert_eq!(safe_name(&spaced), spaced);
+    }
+
+    #[test]
+    fn truncated_json_is_told_from_invalid_json() {
+        let truncated = parse_json::<BTreeMap<u32, String>>("{\"1\": \"fir").unwrap_err();
+        assert!(truncated.contains("truncated"), "{}", truncated);
+        assert!(parse_json::<BTreeMap<u32, String>>("")
+            .unwrap_err()
+            .contains("truncated"));
+        let invalid = parse_json::<BTreeMap<u32, String>>("{\"1\": 2}").unwrap_err();
+        assert!(!invalid.contains("truncated"), "{}", invalid);
+    }
+}
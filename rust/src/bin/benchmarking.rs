@@ -1,13 +1,137 @@
-use frankenstein::run;
+use frankenstein::events::EventLog;
+use frankenstein::manifest::{load_manifest, print_regressions, regressions};
+use frankenstein::process::DryRunRunner;
+use frankenstein::remote::{run_remote, SshWorker};
+use frankenstein::results_db::ResultsDb;
+use frankenstein::run_config::{Backend, RunConfig};
+use frankenstein::{run, run_with};
 use std::env;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    // --dry-run lists the frankenstein commands without running them
+    let dry_run = args.iter().any(|a| a == "--dry-run");
+    args.retain(|a| a != "--dry-run");
+    let mut config = RunConfig::default();
+    // --deterministic is passed on to every frankenstein command
+    if args.iter().any(|a| a == "--deterministic") {
+        args.retain(|a| a != "--deterministic");
+        config.deterministic = true;
+    }
+    // --keep-original keeps the Vampire proof of problems minimizing makes longer
+    if args.iter().any(|a| a == "--keep-original") {
+        args.retain(|a| a != "--keep-original");
+        config.keep_original = true;
+    }
+    // --assume-unproved lets minimize use lemmas no prover proved as hypotheses
+    if args.iter().any(|a| a == "--assume-unproved") {
+        args.retain(|a| a != "--assume-unproved");
+        config.assume_unproved = true;
+    }
+    // --cap-steps lets minimize cap the provers at the steps that could beat the best candidate
+    if args.iter().any(|a| a == "--cap-steps") {
+        args.retain(|a| a != "--cap-steps");
+        config.cap_steps = true;
+    }
+    // --force-backend <backend> makes minimize build on one backend only
+    if let Some(i) = args.iter().position(|a| a == "--force-backend") {
+        let backend = args
+            .get(i + 1)
+            .ok_or_else(|| "--force-backend expects vampire or twee".to_string())
+            .and_then(|name| Backend::parse(name));
+        match backend {
+            Ok(backend) => config.force_backend = Some(backend),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        args.drain(i..i + 2);
+    }
+    // --hosts host[:checkout],... runs the problems on these machines over SSH
+    let hosts = match args.iter().position(|a| a == "--hosts") {
+        Some(i) if i + 1 < args.len() => Some(args.drain(i..i + 2).nth(1).unwrap()),
+        Some(_) => {
+            eprintln!("--hosts expects a comma-separated list of host[:checkout]");
+            std::process::exit(1);
+        }
+        None => None,
+    };
+    // --manifest <file> checks the results against the expected ones
+    let manifest_file = match args.iter().position(|a| a == "--manifest") {
+        Some(i) if i + 1 < args.len() => Some(args.drain(i..i + 2).nth(1).unwrap()),
+        Some(_) => {
+            eprintln!("--manifest expects a manifest file");
+            std::process::exit(1);
+        }
+        None => None,
+    };
+    // --results-db <file> logs every outcome (and, through frankenstein, every prover run)
+    let results_db = match args.iter().position(|a| a == "--results-db") {
+        Some(i) if i + 1 < args.len() => Some(args.drain(i..i + 2).nth(1).unwrap()),
+        Some(_) => {
+            eprintln!("--results-db expects a database file");
+            std::process::exit(1);
+        }
+        None => None,
+    };
+    // --lemma-library <file> lets every problem use the lemmas proved for the others
+    match args.iter().position(|a| a == "--lemma-library") {
+        Some(i) if i + 1 < args.len() => {
+            let path = args.drain(i..i + 2).nth(1).unwrap();
+            config.lemma_library = Some(path);
+        }
+        Some(_) => {
+            eprintln!("--lemma-library expects a file");
+            std::process::exit(1);
+        }
+        None => {}
+    }
+    // --events <path> makes every frankenstein command append its progress events there
+    match args.iter().position(|a| a == "--events") {
+        Some(i) if i + 1 < args.len() => {
+            let path = args.drain(i..i + 2).nth(1).unwrap();
+            match EventLog::open(&path, "") {
+                Ok(events) => config.events = events,
+                Err(err) => {
+                    eprintln!("Error: {}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(_) => {
+            eprintln!("--events expects a file");
+            std::process::exit(1);
+        }
+        None => {}
+    }
+    // opened once every flag is set, since the database records the configuration
+    if let Some(path) = results_db {
+        match ResultsDb::open(&path, "", &config) {
+            Ok(db) => config.results_db = db,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
     if args.len() < 2 {
-        eprintln!("Usage: {} <input_folder> [frankenstein_binary]", args[0]);
+        eprintln!(
+            "Usage: {} <input_folder> [frankenstein_binary] [--hosts host[:checkout],...] [--manifest <file>] [--results-db <file>] [--lemma-library <file>] [--events <path>] [--dry-run] [--deterministic] [--keep-original] [--assume-unproved] [--cap-steps] [--force-backend vampire|twee]",
+            args[0]
+        );
         std::process::exit(1);
     }
 
+    // read the manifest before spending hours on the benchmark
+    let manifest = match manifest_file.as_deref().map(load_manifest).transpose() {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+
     let input_folder = &args[1];
     let frankenstein_bin = if args.len() >= 3 {
         &args[2]
@@ -15,5 +139,40 @@ fn main() {
         "./frankenstein"
     };
 
-    run(input_folder, frankenstein_bin);
+    let results = if let Some(hosts) = hosts {
+        let workers: Vec<SshWorker> = hosts
+            .split(',')
+            .filter(|h| !h.is_empty())
+            .map(|spec| {
+                let worker = SshWorker::parse(spec);
+                if dry_run {
+                    SshWorker::with_runner(
+                        &worker.host,
+                        &worker.root,
+                        Box::new(DryRunRunner::new()),
+                    )
+                } else {
+                    worker
+                }
+            })
+            .collect();
+        run_remote(&config, input_folder, frankenstein_bin, &workers)
+    } else if dry_run {
+        run_with(
+            &DryRunRunner::new(),
+            &config,
+            input_folder,
+            frankenstein_bin,
+        )
+    } else {
+        run(&config, input_folder, frankenstein_bin)
+    };
+
+    if let (Some(manifest), Some(manifest_file)) = (manifest, manifest_file) {
+        let found = regressions(&manifest, &results);
+        print_regressions(&manifest_file, &found);
+        if !found.is_empty() {
+            std::process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,285 @@
+use crate::artifact::{self, write_atomic};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// What Phase 1 (`collect`) did with a problem, written next to its summary as
+// `collect_<suffix>.json` so the benchmark driver, which runs `collect` as a
+// separate process, can read it back.
+
+/// The proof `collect` picked for a lemma
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProvedLemma {
+    pub number: u32,
+    /// lemma problem the proof is for, e.g. "single_lemma_0003"
+    pub file: String,
+    pub prover: String,
+    pub steps: usize,
+}
+
+/// A lemma no prover proved in any mode
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FailedLemma {
+    pub number: u32,
+    /// the lemma problems tried, one per mode the lemma was extracted in
+    pub files: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CollectReport {
+    /// lemma problems extracted per mode ("single", "history", "abstract")
+    pub extracted: BTreeMap<String, usize>,
+    /// proved lemmas by number
+    pub proved: Vec<ProvedLemma>,
+    pub failed: Vec<FailedLemma>,
+    /// lemma dropped as a repetition of a lemma of a lower number -> that lemma
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    /// runs of each prover on the lemma problems of each mode
+    #[serde(default)]
+    pub prover_runs: Vec<ProverRuns>,
+}
+
+/// Why a prover run on a lemma problem gave no proof
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    Timeout,
+    Memout,
+    /// the proof was printed but its steps could not be read
+    ParseError,
+    GaveUp,
+    CounterSatisfiable,
+    /// any other failure, e.g. a crash or a prover that could not be started
+    Error,
+}
+
+impl FailureKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            FailureKind::Timeout => "timeout",
+            FailureKind::Memout => "memout",
+            FailureKind::ParseError => "parse error",
+            FailureKind::GaveUp => "gave up",
+            FailureKind::CounterSatisfiable => "countersat",
+            FailureKind::Error => "error",
+        }
+    }
+}
+
+/// How often one prover was run on the lemma problems of one mode, and how
+/// often it failed, by kind of failure
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProverRuns {
+    pub prover: String,
+    /// "single", "history" or "abstract"
+    pub mode: String,
+    pub attempts: usize,
+    pub failures: BTreeMap<FailureKind, usize>,
+}
+
+impl ProverRuns {
+    pub fn failed(&self) -> usize {
+        self.failures.values().sum()
+    }
+
+    /// `timeout 40%, gave up 10%`
+    pub fn failure_rates(&self) -> String {
+        self.failures
+            .iter()
+            .map(|(kind, n)| {
+                format!(
+                    "{} {:.0}%",
+                    kind.name(),
+                    100.0 * *n as f64 / self.attempts.max(1) as f64
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Count a run of `prover` on a lemma problem of `mode` in `runs`
+pub fn add_run(runs: &mut Vec<ProverRuns>, prover: &str, mode: &str, failure: Option<FailureKind>) {
+    add_runs(
+        runs,
+        &ProverRuns {
+            prover: prover.to_string(),
+            mode: mode.to_string(),
+            attempts: 1,
+            failures: failure.into_iter().map(|kind| (kind, 1)).collect(),
+        },
+    );
+}
+
+/// Add the counts of `more` to those of the same prover and mode in `runs`,
+/// keeping `runs` sorted by prover, then mode in the order `collect` runs them
+pub fn add_runs(runs: &mut Vec<ProverRuns>, more: &ProverRuns) {
+    match runs
+        .iter_mut()
+        .find(|r| r.prover == more.prover && r.mode == more.mode)
+    {
+        Some(known) => {
+            known.attempts += more.attempts;
+            for (kind, n) in &more.failures {
+                *known.failures.entry(*kind).or_default() += n;
+            }
+        }
+        None => {
+            runs.push(more.clone());
+            runs.sort_by(|a, b| {
+                (&a.prover, mode_order(&a.mode), &a.mode).cmp(&(
+                    &b.prover,
+                    mode_order(&b.mode),
+                    &b.mode,
+                ))
+            });
+        }
+    }
+}
+
+impl CollectReport {
+    /// Where `collect` writes the report of the problem with `suffix`
+    pub fn path(output_dir: &Path, suffix: &str) -> PathBuf {
+        output_dir.join(format!("collect_{}.json", suffix))
+    }
+
+    pub fn load(path: &Path) -> Result<CollectReport, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        artifact::parse_json(&text)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize the collect report: {}", e))?;
+        write_atomic(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Lemma problems extracted over all modes
+    pub fn extracted_total(&self) -> usize {
+        self.extracted.values().sum()
+    }
+
+    /// `single 4, history 3, abstract 2` in mode order
+    pub fn extracted_by_mode(&self) -> String {
+        let mut modes: Vec<(&String, &usize)> = self.extracted.iter().collect();
+        modes.sort_by_key(|(mode, _)| mode_order(mode));
+        modes
+            .iter()
+            .map(|(mode, n)| format!("{} {}", mode, n))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Proved lemmas per prover
+    pub fn wins(&self) -> BTreeMap<&str, usize> {
+        let mut wins = BTreeMap::new();
+        for lemma in &self.proved {
+            *wins.entry(lemma.prover.as_str()).or_default() += 1;
+        }
+        wins
+    }
+}
+
+/// Modes in the order `collect` runs them, unknown ones last
+fn mode_order(mode: &str) -> usize {
+    ["single", "history", "abstract"]
+        .iter()
+        .position(|m| *m == mode)
+        .unwrap_or(usize::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_survives_the_round_trip_through_its_file() {
+        let report = CollectReport {
+            extracted: BTreeMap::from([
+                ("abstract".to_string(), 1),
+                ("history".to_string(), 2),
+                ("single".to_string(), 3),
+            ]),
+            proved: vec![
+                ProvedLemma {
+                    number: 1,
+                    file: "single_lemma_0001".to_string(),
+                    prover: "twee".to_string(),
+                    steps: 2,
+                },
+                ProvedLemma {
+                    number: 2,
+                    file: "history_lemma_0002".to_string(),
+                    prover: "vampire".to_string(),
+                    steps: 5,
+                },
+            ],
+            failed: vec![FailedLemma {
+                number: 3,
+                files: vec!["single_lemma_0003".to_string()],
+            }],
+            aliases: BTreeMap::from([(
+                "history_lemma_0004".to_string(),
+                "single_lemma_0001".to_string(),
+            )]),
+            prover_runs: vec![ProverRuns {
+                prover: "twee".to_string(),
+                mode: "abstract".to_string(),
+                attempts: 5,
+                failures: BTreeMap::from([(FailureKind::Timeout, 2)]),
+            }],
+        };
+        assert_eq!(report.extracted_total(), 6);
+        assert_eq!(
+            report.extracted_by_mode(),
+            "single 3, history 2, abstract 1"
+        );
+        assert_eq!(report.wins(), BTreeMap::from([("twee", 1), ("vampire", 1)]));
+
+        let path = std::env::temp_dir().join(format!("collect_report_{}.json", std::process::id()));
+        report.write(&path).unwrap();
+        assert_eq!(CollectReport::load(&path).unwrap(), report);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn prover_runs_add_up_per_prover_and_mode() {
+        let mut runs = Vec::new();
+        add_run(&mut runs, "twee", "abstract", Some(FailureKind::Timeout));
+        add_run(&mut runs, "vampire", "single", None);
+        add_run(&mut runs, "twee", "single", Some(FailureKind::GaveUp));
+        add_run(&mut runs, "twee", "abstract", None);
+        add_run(&mut runs, "twee", "abstract", Some(FailureKind::Timeout));
+        add_run(&mut runs, "twee", "abstract", Some(FailureKind::ParseError));
+        add_run(&mut runs, "twee", "abstract", None);
+
+        let order: Vec<(&str, &str)> = runs
+            .iter()
+            .map(|r| (r.prover.as_str(), r.mode.as_str()))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                ("twee", "single"),
+                ("twee", "abstract"),
+                ("vampire", "single")
+            ]
+        );
+        assert_eq!(runs[1].attempts, 5);
+        assert_eq!(runs[1].failed(), 3);
+        assert_eq!(runs[1].failure_rates(), "timeout 40%, parse error 20%");
+        assert_eq!(runs[2].failed(), 0);
+        assert_eq!(runs[2].failure_rates(), "");
+
+        // the runs of another problem
+        let twee = runs[1].clone();
+        add_runs(&mut runs, &twee);
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[1].attempts, 10);
+        assert_eq!(runs[1].failure_rates(), "timeout 40%, parse error 20%");
+    }
+}
@@ -1,4 +1,3 @@
-use crate::alpha_match::*;
 use crate::utils::*;
 use regex::Regex;
 use std::collections::VecDeque;
@@ -26,11 +25,8 @@ pub fn load_dag(dag_file: &str) -> BTreeMap<String, BTreeSet<String>> {
     dag
 }
 
-/// Write DAG to file
-pub fn write_dag(
-    dag_file: &str,
-    dag: &BTreeMap<String, BTreeSet<String>>,
-) -> Result<(), std::io::Error> {
+/// Print a DAG in the format `load_dag` reads
+pub fn format_dag(dag: &BTreeMap<String, BTreeSet<String>>) -> String {
     let mut output = String::new();
     for (parent, children) in dag.iter() {
         let children_str = children
@@ -41,7 +37,55 @@ pub fn write_dag(
 
         output.push_str(&format!("{parent} -> {{{children_str}}}\n"));
     }
-    fs::write(dag_file, output)
+    output
+}
+
+/// Fewest edges from `root` to each lemma it reaches in `dag`, `root` itself at 0
+pub fn distances(dag: &BTreeMap<String, BTreeSet<String>>, root: &str) -> BTreeMap<String, usize> {
+    let mut distances = BTreeMap::from([(root.to_string(), 0)]);
+    let mut queue = VecDeque::from([root.to_string()]);
+    while let Some(lemma) = queue.pop_front() {
+        let distance = distances[&lemma];
+        for child in dag.get(&lemma).into_iter().flatten() {
+            if !distances.contains_key(child) {
+                distances.insert(child.clone(), distance + 1);
+                queue.push_back(child.clone());
+            }
+        }
+    }
+    distances
+}
+
+/// Sort `candidates` nearest to `root` in `dag` first, ties in name order.
+/// Lemmas `root` does not reach, e.g. parents of redirected duplicates, come last.
+pub fn sort_by_proximity(
+    candidates: &mut [String],
+    dag: &BTreeMap<String, BTreeSet<String>>,
+    root: &str,
+) {
+    let distances = distances(dag, root);
+    candidates.sort_by(|a, b| {
+        let distance = |lemma: &String| distances.get(lemma).copied().unwrap_or(usize::MAX);
+        distance(a).cmp(&distance(b)).then_with(|| a.cmp(b))
+    });
+}
+
+/// The most general lemma `lemma` is an instance of, following `subsumed_by`
+/// until it ends or leads back to a lemma on the way; `None` if `lemma` is
+/// not subsumed
+fn strongest_lemma<'a>(
+    lemma: &'a str,
+    subsumed_by: &'a BTreeMap<String, String>,
+) -> Option<&'a str> {
+    let mut on_the_way = BTreeSet::from([lemma]);
+    let mut current = lemma;
+    while let Some(next) = subsumed_by.get(current) {
+        if !on_the_way.insert(next.as_str()) {
+            break;
+        }
+        current = next;
+    }
+    (current != lemma).then_some(current)
 }
 
 /// Build DAG from precomputed lemmas
@@ -52,6 +96,8 @@ pub fn build_dag(
     let PrecomputedLemmas {
         all_lemmas,
         all_twee,
+        twee_index,
+        subsumed_by,
         lemmas,
     } = precomputed;
 
@@ -83,16 +129,58 @@ pub fn build_dag(
 
         // check if the lemma itself is a duplicate of a TWEE lemma
         let mut redirected = false;
-        for twee_dep in all_twee {
+        if let Some(&twee_idx) = twee_index
+            .lookup_alpha_equivalent(&lemma_info.formula)
+            .first()
+        {
+            let twee_dep = &all_twee[twee_idx];
             let twee_name = &twee_dep.name;
-            let twee_formula = &twee_dep.formula;
-            if formulas_match(&lemma_info.formula, twee_formula)
-                && formulas_match(twee_formula, &lemma_info.formula)
-            {
-                println!("[DUPLICATE] lemma {} duplicates {}", lemma, twee_name);
-                duplicates.push((lemma.clone(), twee_name.clone()));
-
-                // redirect to smallest parent
+            println!("[DUPLICATE] lemma {} duplicates {}", lemma, twee_name);
+            duplicates.push((lemma.clone(), twee_name.clone()));
+
+            // redirect to smallest parent
+            let smallest_parent = twee_dep
+                .parents
+                .iter()
+                .min_by_key(|p| {
+                    p.chars()
+                        .filter(|c| c.is_ascii_digit())
+                        .collect::<String>()
+                        .parse::<u32>()
+                        .unwrap_or(u32::MAX)
+                })
+                .expect("Duplicate TWEE lemma has no parents");
+
+            // add DAG edges
+            // get the dependencies of the smallest parent
+            if let Some(parent_info) = all_lemmas.get(smallest_parent) {
+                dag.entry(smallest_parent.clone()).or_default().extend(
+                    parent_info
+                        .dependencies
+                        .iter()
+                        .map(|(dep_name, _)| dep_name.clone()),
+                );
+            }
+
+            queue.push_back(smallest_parent.clone());
+            redirected = true;
+        }
+
+        // handle dependencies
+        for (dep_name, dep_formula) in &lemma_info.dependencies {
+            if dep_name.starts_with("twee_") {
+                continue;
+            } // skip twee dependencies
+
+            let mut is_duplicate = false;
+            if let Some(&twee_idx) = twee_index.lookup_alpha_equivalent(dep_formula).first() {
+                let twee_dep = &all_twee[twee_idx];
+                let twee_name = &twee_dep.name;
+                println!("[DUPLICATE] dep {} duplicates {}", dep_name, twee_name);
+                duplicates.push((dep_name.clone(), twee_name.clone()));
+                is_duplicate = true;
+
+                // redirect DAG edges to the smallest parent
                 let smallest_parent = twee_dep
                     .parents
                     .iter()
@@ -103,9 +191,8 @@ pub fn build_dag(
                             .parse::<u32>()
                             .unwrap_or(u32::MAX)
                     })
-                    .expect("Duplicate TWEE lemma has no parents");
+                    .expect("TWEE dep has no parents");
 
-                // add DAG edges
                 // get the dependencies of the smallest parent
                 if let Some(parent_info) = all_lemmas.get(smallest_parent) {
                     dag.entry(smallest_parent.clone()).or_default().extend(
@@ -116,61 +203,28 @@ pub fn build_dag(
                     );
                 }
 
-                queue.push_back(smallest_parent.clone());
-                redirected = true;
-                break;
+                // continue traversal from parent
+                if !seen.contains(smallest_parent) {
+                    queue.push_back(smallest_parent.clone());
+                }
             }
-        }
-
-        // handle dependencies
-        for (dep_name, dep_formula) in &lemma_info.dependencies {
-            if dep_name.starts_with("twee_") {
-                continue;
-            } // skip twee dependencies
 
-            let mut is_duplicate = false;
-            for twee_dep in all_twee {
-                let twee_name = &twee_dep.name;
-                let twee_formula = &twee_dep.formula;
-                if formulas_match(dep_formula, twee_formula)
-                    && formulas_match(twee_formula, dep_formula)
+            if !is_duplicate && !redirected {
+                // an instance of a more general lemma is replaced by that lemma
+                // (unless that is the lemma that depends on it)
+                if let Some(stronger) =
+                    strongest_lemma(dep_name, subsumed_by).filter(|s| *s != lemma.as_str())
                 {
-                    println!("[DUPLICATE] dep {} duplicates {}", dep_name, twee_name);
-                    duplicates.push((dep_name.clone(), twee_name.clone()));
-                    is_duplicate = true;
-
-                    // redirect DAG edges to the smallest parent
-                    let smallest_parent = twee_dep
-                        .parents
-                        .iter()
-                        .min_by_key(|p| {
-                            p.chars()
-                                .filter(|c| c.is_ascii_digit())
-                                .collect::<String>()
-                                .parse::<u32>()
-                                .unwrap_or(u32::MAX)
-                        })
-                        .expect("TWEE dep has no parents");
-
-                    // get the dependencies of the smallest parent
-                    if let Some(parent_info) = all_lemmas.get(smallest_parent) {
-                        dag.entry(smallest_parent.clone()).or_default().extend(
-                            parent_info
-                                .dependencies
-                                .iter()
-                                .map(|(dep_name, _)| dep_name.clone()),
-                        );
-                    }
-
-                    // continue traversal from parent
-                    if !seen.contains(smallest_parent) {
-                        queue.push_back(smallest_parent.clone());
+                    println!("[SUBSUMED] dep {} is an instance of {}", dep_name, stronger);
+                    dag.entry(lemma.clone())
+                        .or_default()
+                        .insert(stronger.to_string());
+                    if !seen.contains(stronger) {
+                        queue.push_back(stronger.to_string());
                     }
-                    break;
+                    continue;
                 }
-            }
 
-            if !is_duplicate && !redirected {
                 // normal DAG entry
                 dag.entry(lemma.clone())
                     .or_default()
@@ -192,3 +246,103 @@ pub fn build_dag(
 
     Ok((dag, lemmas.clone()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ScratchDir;
+
+    #[test]
+    fn candidates_nearest_to_the_root_come_first() {
+        let dag: BTreeMap<String, BTreeSet<String>> = [
+            (
+                "history_lemma_0009",
+                vec!["history_lemma_0007", "single_lemma_0002"],
+            ),
+            ("history_lemma_0007", vec!["history_lemma_0001"]),
+            ("history_lemma_0001", vec!["a1"]),
+            ("history_lemma_0005", vec!["single_lemma_0002"]),
+        ]
+        .into_iter()
+        .map(|(parent, children)| {
+            let children = children.into_iter().map(String::from).collect();
+            (parent.to_string(), children)
+        })
+        .collect();
+
+        let distances = distances(&dag, "history_lemma_0009");
+        assert_eq!(distances["history_lemma_0009"], 0);
+        assert_eq!(distances["history_lemma_0007"], 1);
+        assert_eq!(distances["history_lemma_0001"], 2);
+        assert_eq!(distances["a1"], 3);
+        assert!(!distances.contains_key("history_lemma_0005"));
+
+        let mut candidates: Vec<String> = [
+            "history_lemma_0001",
+            "history_lemma_0005",
+            "history_lemma_0007",
+            "single_lemma_0002",
+        ]
+        .map(String::from)
+        .to_vec();
+        sort_by_proximity(&mut candidates, &dag, "history_lemma_0009");
+        assert_eq!(
+            candidates,
+            [
+                "history_lemma_0007",
+                "single_lemma_0002",
+                "history_lemma_0001",
+                "history_lemma_0005"
+            ]
+        );
+    }
+
+    #[test]
+    fn subsumed_lemmas_lead_to_the_strongest_without_looping() {
+        let subsumed_by = BTreeMap::from([
+            (
+                "single_lemma_0001".to_string(),
+                "history_lemma_0002".to_string(),
+            ),
+            (
+                "history_lemma_0002".to_string(),
+                "single_lemma_0003".to_string(),
+            ),
+            (
+                "abstract_lemma_0004".to_string(),
+                "abstract_lemma_0005".to_string(),
+            ),
+            (
+                "abstract_lemma_0005".to_string(),
+                "abstract_lemma_0004".to_string(),
+            ),
+        ]);
+        assert_eq!(
+            strongest_lemma("single_lemma_0001", &subsumed_by),
+            Some("single_lemma_0003")
+        );
+        assert_eq!(strongest_lemma("single_lemma_0003", &subsumed_by), None);
+        assert_eq!(
+            strongest_lemma("abstract_lemma_0004", &subsumed_by),
+            Some("abstract_lemma_0005")
+        );
+    }
+
+    #[test]
+    fn formatted_dags_read_back_unchanged() {
+        let dag: BTreeMap<String, BTreeSet<String>> = BTreeMap::from([
+            (
+                "history_lemma_0003".to_string(),
+                BTreeSet::from(["a1".to_string(), "single_lemma_0001".to_string()]),
+            ),
+            ("single_lemma_0001".to_string(), BTreeSet::new()),
+        ]);
+        assert_eq!(
+            format_dag(&dag),
+            "history_lemma_0003 -> {\"a1\", \"single_lemma_0001\"}\nsingle_lemma_0001 -> {}\n"
+        );
+
+        let dir = ScratchDir::new("format_dag");
+        assert_eq!(load_dag(&dir.write("dag.txt", format_dag(&dag))), dag);
+    }
+}
@@ -0,0 +1,219 @@
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Optional progress events for dashboards (`--events <path>`): one JSON object
+// per line, appended to a file or written to a descriptor such as
+// `/dev/fd/3`, next to the human log on stdout. Without `--events` every
+// call does nothing.
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// a command of the pipeline (collect, shorten, group, minimize, ...) started
+    PhaseStarted { phase: String },
+    PhaseFinished {
+        phase: String,
+        ok: bool,
+        seconds: f64,
+    },
+    /// a root/history combination of minimize got a total
+    CandidateEvaluated {
+        root: String,
+        history: Option<String>,
+        steps: usize,
+    },
+    /// a prover run with its SZS status ("Timeout", "Error", ...)
+    ProverInvoked {
+        prover: String,
+        status: String,
+        steps: Option<usize>,
+        seconds: f64,
+    },
+}
+
+/// What every line carries besides the event
+#[derive(Serialize)]
+struct Line<'a> {
+    /// seconds since the Unix epoch
+    time: f64,
+    problem: &'a str,
+    pid: u32,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+#[derive(Debug)]
+struct Sink {
+    out: File,
+    path: String,
+    problem: String,
+    /// the phase started last and not finished yet
+    phase: Option<(String, Instant)>,
+}
+
+/// Where the events of a run go; the default log writes none
+#[derive(Debug, Default)]
+pub struct EventLog {
+    sink: Option<Mutex<Sink>>,
+}
+
+impl EventLog {
+    /// Write events to `path`, appended to so several processes can share
+    /// it. `problem` names the problem they are about.
+    pub fn open(path: &str, problem: &str) -> Result<EventLog, String> {
+        let out = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open event log {}: {}", path, e))?;
+        Ok(EventLog {
+            sink: Some(Mutex::new(Sink {
+                out,
+                path: path.to_string(),
+                problem: problem.to_string(),
+                phase: None,
+            })),
+        })
+    }
+
+    /// `--events <path>` for the frankenstein commands if this log writes events
+    pub fn forwarded_flag(&self) -> Vec<String> {
+        match &self.sink {
+            Some(sink) => vec!["--events".to_string(), sink.lock().unwrap().path.clone()],
+            None => Vec::new(),
+        }
+    }
+
+    pub fn emit(&self, event: Event) {
+        if let Some(sink) = &self.sink {
+            emit_to(&mut sink.lock().unwrap(), &event);
+        }
+    }
+
+    /// Start `phase`, finished by `finish_phase`
+    pub fn start_phase(&self, phase: &str) {
+        if let Some(sink) = &self.sink {
+            let mut sink = sink.lock().unwrap();
+            sink.phase = Some((phase.to_string(), Instant::now()));
+            emit_to(
+                &mut sink,
+                &Event::PhaseStarted {
+                    phase: phase.to_string(),
+                },
+            );
+        }
+    }
+
+    /// Finish the phase started last, if any
+    pub fn finish_phase(&self, ok: bool) {
+        if let Some(sink) = &self.sink {
+            let mut sink = sink.lock().unwrap();
+            if let Some((phase, started)) = sink.phase.take() {
+                let seconds = started.elapsed().as_secs_f64();
+                emit_to(&mut sink, &Event::PhaseFinished { phase, ok, seconds });
+            }
+        }
+    }
+
+    pub fn prover_invoked(
+        &self,
+        prover: &str,
+        status: &str,
+        steps: Option<usize>,
+        elapsed: Duration,
+    ) {
+        self.emit(Event::ProverInvoked {
+            prover: prover.to_string(),
+            status: status.to_string(),
+            steps,
+            seconds: elapsed.as_secs_f64(),
+        });
+    }
+
+    pub fn candidate_evaluated(&self, root: &str, history: Option<&str>, steps: usize) {
+        self.emit(Event::CandidateEvaluated {
+            root: root.to_string(),
+            history: history.map(str::to_string),
+            steps,
+        });
+    }
+}
+
+/// The line written for `event`
+fn render(event: &Event, problem: &str, time: SystemTime) -> String {
+    let line = Line {
+        time: time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64(),
+        problem,
+        pid: std::process::id(),
+        event,
+    };
+    serde_json::to_string(&line).expect("events serialize to JSON")
+}
+
+fn emit_to(sink: &mut Sink, event: &Event) {
+    // one write per line, so lines of processes sharing the file do not mix
+    let line = render(event, &sink.problem, SystemTime::now()) + "\n";
+    if let Err(e) = sink.out.write_all(line.as_bytes()) {
+        eprintln!("[WARN] Failed to write event: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ScratchDir;
+    use std::fs;
+
+    #[test]
+    fn events_are_one_json_object_per_line() {
+        let event = Event::ProverInvoked {
+            prover: "twee".to_string(),
+            status: "Theorem".to_string(),
+            steps: Some(4),
+            seconds: 0.5,
+        };
+        let line = render(&event, "Equation650", UNIX_EPOCH + Duration::from_secs(10));
+        assert!(!line.contains('\n'));
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["event"], "prover_invoked");
+        assert_eq!(json["problem"], "Equation650");
+        assert_eq!(json["time"], 10.0);
+        assert_eq!(json["prover"], "twee");
+        assert_eq!(json["steps"], 4);
+        assert_eq!(json["seconds"], 0.5);
+    }
+
+    #[test]
+    fn phases_are_finished_once() {
+        let dir = ScratchDir::new("events");
+        let path = dir
+            .path()
+            .join("events.jsonl")
+            .to_string_lossy()
+            .to_string();
+        let log = EventLog::open(&path, "Equation650").unwrap();
+        assert_eq!(log.forwarded_flag(), ["--events", path.as_str()]);
+        log.start_phase("collect");
+        log.finish_phase(false);
+        log.finish_phase(true);
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["event"], "phase_finished");
+        assert_eq!(lines[1]["ok"], false);
+
+        // without --events nothing is written or forwarded
+        let log = EventLog::default();
+        log.start_phase("collect");
+        assert!(log.forwarded_flag().is_empty());
+    }
+}
@@ -0,0 +1,907 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// First-order term. Variables start with an uppercase letter,
+/// everything else is a function symbol or a constant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    Var(String),
+    Fun(String, Vec<Term>),
+}
+
+/// First-order formula as it appears in TPTP `fof` statements
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Formula {
+    Atom(Term),
+    Eq(Term, Term),
+    Neq(Term, Term),
+    Not(Box<Formula>),
+    And(Vec<Formula>),
+    Or(Vec<Formula>),
+    Implies(Box<Formula>, Box<Formula>),
+    Iff(Box<Formula>, Box<Formula>),
+    Forall(Vec<String>, Box<Formula>),
+    Exists(Vec<String>, Box<Formula>),
+}
+
+fn is_variable(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase())
+}
+
+impl Term {
+    fn collect_vars(&self, bound: &[String], out: &mut Vec<String>) {
+        match self {
+            Term::Var(v) => {
+                if !bound.contains(v) && !out.contains(v) {
+                    out.push(v.clone());
+                }
+            }
+            Term::Fun(_, args) => {
+                for arg in args {
+                    arg.collect_vars(bound, out);
+                }
+            }
+        }
+    }
+}
+
+impl Formula {
+    /// A single positive equation, possibly universally quantified
+    pub fn is_unit_equality(&self) -> bool {
+        match self {
+            Formula::Eq(_, _) => true,
+            Formula::Forall(_, body) => body.is_unit_equality(),
+            _ => false,
+        }
+    }
+
+    /// Free variables in order of first occurrence
+    pub fn free_vars(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        self.collect_free_vars(&mut Vec::new(), &mut out);
+        out
+    }
+
+    fn collect_free_vars(&self, bound: &mut Vec<String>, out: &mut Vec<String>) {
+        match self {
+            Formula::Atom(t) => t.collect_vars(bound, out),
+            Formula::Eq(l, r) | Formula::Neq(l, r) => {
+                l.collect_vars(bound, out);
+                r.collect_vars(bound, out);
+            }
+            Formula::Not(f) => f.collect_free_vars(bound, out),
+            Formula::And(fs) | Formula::Or(fs) => {
+                for f in fs {
+                    f.collect_free_vars(bound, out);
+                }
+            }
+            Formula::Implies(a, b) | Formula::Iff(a, b) => {
+                a.collect_free_vars(bound, out);
+                b.collect_free_vars(bound, out);
+            }
+            Formula::Forall(vars, body) | Formula::Exists(vars, body) => {
+                let len = bound.len();
+                bound.extend(vars.iter().cloned());
+                body.collect_free_vars(bound, out);
+                bound.truncate(len);
+            }
+        }
+    }
+
+    /// Universally quantify all free variables
+    pub fn universal_closure(self) -> Formula {
+        let free = self.free_vars();
+        if free.is_empty() {
+            return self;
+        }
+        match self {
+            // extend an existing leading quantifier instead of nesting a new one
+            Formula::Forall(mut vars, body) => {
+                vars.extend(free);
+                Formula::Forall(vars, body)
+            }
+            other => Formula::Forall(free, Box::new(other)),
+        }
+    }
+
+    /// Render as a TPTP unitary formula (operand of `~` or body of a quantifier)
+    fn unitary(&self) -> String {
+        match self {
+            Formula::Atom(_) | Formula::Not(_) | Formula::Forall(..) | Formula::Exists(..) => {
+                self.to_string()
+            }
+            _ => format!("({})", self),
+        }
+    }
+
+    /// Render as an operand of a binary connective
+    fn unit(&self) -> String {
+        match self {
+            Formula::And(_) | Formula::Or(_) | Formula::Implies(..) | Formula::Iff(..) => {
+                format!("({})", self)
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
+/// Skolem constants introduced by Vampire, e.g. `sK0`
+fn is_skolem(name: &str) -> bool {
+    name.strip_prefix("sK")
+        .is_some_and(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit()))
+}
+
+impl Term {
+    fn skolems_to_variables(
+        &self,
+        mapping: &mut BTreeMap<String, String>,
+        taken: &mut BTreeSet<String>,
+    ) -> Result<Term, String> {
+        match self {
+            Term::Var(v) => Ok(Term::Var(v.clone())),
+            Term::Fun(name, args) if is_skolem(name) => {
+                if !args.is_empty() {
+                    return Err(format!("Skolem function {} has arguments", name));
+                }
+                let var = mapping
+                    .entry(name.clone())
+                    .or_insert_with(|| {
+                        // `sK0` becomes `SK0`, or `SK0_1`, ... if the chain has an `SK0`
+                        let preferred = name.to_uppercase();
+                        let var = std::iter::once(preferred.clone())
+                            .chain((1..).map(|n| format!("{}_{}", preferred, n)))
+                            .find(|v| !taken.contains(v))
+                            .unwrap_or_default();
+                        taken.insert(var.clone());
+                        var
+                    })
+                    .clone();
+                Ok(Term::Var(var))
+            }
+            Term::Fun(name, args) => Ok(Term::Fun(
+                name.clone(),
+                args.iter()
+                    .map(|a| a.skolems_to_variables(mapping, taken))
+                    .collect::<Result<_, _>>()?,
+            )),
+        }
+    }
+}
+
+impl Formula {
+    /// Logical negation of the universal closure, pushed inwards to the
+    /// literals: the negation of a clause is an existentially quantified
+    /// conjunction, not a conjunction with the same free variables.
+    pub fn negate(self) -> Formula {
+        self.universal_closure().negated()
+    }
+
+    /// Negation pushed inwards, with free variables left as they are
+    fn negated(self) -> Formula {
+        match self {
+            Formula::Atom(t) => Formula::Not(Box::new(Formula::Atom(t))),
+            Formula::Eq(l, r) => Formula::Neq(l, r),
+            Formula::Neq(l, r) => Formula::Eq(l, r),
+            Formula::Not(f) => *f,
+            Formula::And(fs) => Formula::Or(fs.into_iter().map(Formula::negated).collect()),
+            Formula::Or(fs) => Formula::And(fs.into_iter().map(Formula::negated).collect()),
+            Formula::Implies(a, b) => Formula::And(vec![*a, b.negated()]),
+            Formula::Iff(a, b) => Formula::Not(Box::new(Formula::Iff(a, b))),
+            Formula::Forall(vars, body) => Formula::Exists(vars, Box::new(body.negated())),
+            Formula::Exists(vars, body) => Formula::Forall(vars, Box::new(body.negated())),
+        }
+    }
+
+    /// Rebuild the formula with `f` applied to every top-level term
+    fn map_terms(
+        &self,
+        f: &mut dyn FnMut(&Term) -> Result<Term, String>,
+    ) -> Result<Formula, String> {
+        let all = |fs: &[Formula], f: &mut dyn FnMut(&Term) -> Result<Term, String>| {
+            fs.iter()
+                .map(|x| x.map_terms(f))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(match self {
+            Formula::Atom(t) => Formula::Atom(f(t)?),
+            Formula::Eq(l, r) => Formula::Eq(f(l)?, f(r)?),
+            Formula::Neq(l, r) => Formula::Neq(f(l)?, f(r)?),
+            Formula::Not(x) => Formula::Not(Box::new(x.map_terms(f)?)),
+            Formula::And(fs) => Formula::And(all(fs, f)?),
+            Formula::Or(fs) => Formula::Or(all(fs, f)?),
+            Formula::Implies(a, b) => {
+                Formula::Implies(Box::new(a.map_terms(f)?), Box::new(b.map_terms(f)?))
+            }
+            Formula::Iff(a, b) => {
+                Formula::Iff(Box::new(a.map_terms(f)?), Box::new(b.map_terms(f)?))
+            }
+            Formula::Forall(vars, body) => {
+                Formula::Forall(vars.clone(), Box::new(body.map_terms(f)?))
+            }
+            Formula::Exists(vars, body) => {
+                Formula::Exists(vars.clone(), Box::new(body.map_terms(f)?))
+            }
+        })
+    }
+}
+
+impl Formula {
+    /// Every variable name of the formula, free, bound or only quantified
+    fn variable_names(&self, out: &mut BTreeSet<String>) {
+        let _ = self.map_terms(&mut |t| {
+            let mut vars = Vec::new();
+            t.collect_vars(&[], &mut vars);
+            out.extend(vars);
+            Ok(t.clone())
+        });
+        self.quantified_variables(out);
+    }
+
+    fn quantified_variables(&self, out: &mut BTreeSet<String>) {
+        match self {
+            Formula::Atom(_) | Formula::Eq(..) | Formula::Neq(..) => {}
+            Formula::Not(f) => f.quantified_variables(out),
+            Formula::And(fs) | Formula::Or(fs) => {
+                for f in fs {
+                    f.quantified_variables(out);
+                }
+            }
+            Formula::Implies(a, b) | Formula::Iff(a, b) => {
+                a.quantified_variables(out);
+                b.quantified_variables(out);
+            }
+            Formula::Forall(vars, body) | Formula::Exists(vars, body) => {
+                out.extend(vars.iter().cloned());
+                body.quantified_variables(out);
+            }
+        }
+    }
+}
+
+/// Replace Skolem constants by variables, using one mapping for the whole chain
+/// so that a constant shared between steps becomes the same variable everywhere.
+/// The variables are fresh: none of them occurs in the chain already.
+/// Skolem functions with arguments cannot be generalized and are rejected.
+pub fn skolems_to_variables(
+    chain: &[Formula],
+) -> Result<(Vec<Formula>, BTreeMap<String, String>), String> {
+    let mut taken = BTreeSet::new();
+    for formula in chain {
+        formula.variable_names(&mut taken);
+    }
+    let mut mapping = BTreeMap::new();
+    let turned = chain
+        .iter()
+        .map(|f| f.map_terms(&mut |t| t.skolems_to_variables(&mut mapping, &mut taken)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((turned, mapping))
+}
+
+impl Term {
+    /// Number of symbols in the term
+    fn size(&self) -> usize {
+        match self {
+            Term::Var(_) => 1,
+            Term::Fun(_, args) => 1 + args.iter().map(Term::size).sum::<usize>(),
+        }
+    }
+
+    /// Every subterm that is not a variable, with repetitions
+    fn collect_subterms(&self, out: &mut Vec<Term>) {
+        if let Term::Fun(_, args) = self {
+            out.push(self.clone());
+            for arg in args {
+                arg.collect_subterms(out);
+            }
+        }
+    }
+
+    fn replace(&self, from: &Term, to: &Term) -> Term {
+        match self {
+            _ if self == from => to.clone(),
+            Term::Var(_) => self.clone(),
+            Term::Fun(name, args) => Term::Fun(
+                name.clone(),
+                args.iter().map(|a| a.replace(from, to)).collect(),
+            ),
+        }
+    }
+}
+
+impl Term {
+    fn collect_symbols(&self, out: &mut BTreeSet<(String, usize)>) {
+        if let Term::Fun(name, args) = self {
+            out.insert((name.clone(), args.len()));
+            for arg in args {
+                arg.collect_symbols(out);
+            }
+        }
+    }
+}
+
+impl Formula {
+    /// Function symbols and constants with their arities
+    pub fn symbols(&self) -> BTreeSet<(String, usize)> {
+        let mut symbols = BTreeSet::new();
+        let _ = self.map_terms(&mut |t| {
+            t.collect_symbols(&mut symbols);
+            Ok(t.clone())
+        });
+        symbols
+    }
+}
+
+/// Generalizations of `formula`, each replacing all occurrences of one
+/// repeated subterm by a fresh variable, larger subterms first. The results
+/// are universally closed; whether they still hold is up to the caller.
+pub fn generalizations(formula: &Formula) -> Vec<Formula> {
+    let body = match formula {
+        Formula::Forall(_, body) => body.as_ref(),
+        other => other,
+    };
+    let mut subterms = Vec::new();
+    let _ = body.map_terms(&mut |t| {
+        t.collect_subterms(&mut subterms);
+        Ok(t.clone())
+    });
+    let mut repeated: Vec<Term> = Vec::new();
+    for term in &subterms {
+        if subterms.iter().filter(|t| *t == term).count() > 1 && !repeated.contains(term) {
+            repeated.push(term.clone());
+        }
+    }
+    // stable, so subterms of the same size keep their order of appearance
+    repeated.sort_by_key(|t| std::cmp::Reverse(t.size()));
+
+    let vars = body.free_vars();
+    let fresh = (0..)
+        .map(|n| format!("G{}", n))
+        .find(|v| !vars.contains(v))
+        .unwrap_or_default();
+    repeated
+        .iter()
+        .filter_map(|term| {
+            body.map_terms(&mut |t| Ok(t.replace(term, &Term::Var(fresh.clone()))))
+                .ok()
+        })
+        .map(Formula::universal_closure)
+        .collect()
+}
+
+impl Term {
+    /// Least general generalization of `self` and `other`: their common
+    /// shape, with a variable wherever they differ. `pairs` holds the pairs
+    /// of differing subterms seen so far, so a pair that occurs twice gets
+    /// the same variable both times.
+    fn anti_unify(&self, other: &Term, pairs: &mut Vec<(Term, Term)>) -> Term {
+        match (self, other) {
+            (Term::Fun(f, xs), Term::Fun(g, ys)) if f == g && xs.len() == ys.len() => Term::Fun(
+                f.clone(),
+                xs.iter()
+                    .zip(ys)
+                    .map(|(x, y)| x.anti_unify(y, pairs))
+                    .collect(),
+            ),
+            _ => {
+                let pair = (self.clone(), other.clone());
+                let index = match pairs.iter().position(|p| *p == pair) {
+                    Some(index) => index,
+                    None => {
+                        pairs.push(pair);
+                        pairs.len() - 1
+                    }
+                };
+                Term::Var(format!("X{}", index))
+            }
+        }
+    }
+}
+
+/// The sides of a unit equality, under its quantifier
+fn equation_sides(formula: &Formula) -> Option<(&Term, &Term)> {
+    match formula {
+        Formula::Eq(l, r) => Some((l, r)),
+        Formula::Forall(_, body) => equation_sides(body),
+        _ => None,
+    }
+}
+
+/// The most specific unit equality that `a` and `b` are both instances of,
+/// universally closed. `b` is flipped if that keeps more of the shape in
+/// common. `None` unless both are unit equalities with some shape in common:
+/// generalizations such as `X0 = X1`, `X0 = e` or `X0 = X0` say nothing.
+pub fn anti_unify(a: &Formula, b: &Formula) -> Option<Formula> {
+    let (al, ar) = equation_sides(a)?;
+    let (bl, br) = equation_sides(b)?;
+    let generalize = |bl: &Term, br: &Term| {
+        let mut pairs = Vec::new();
+        let l = al.anti_unify(bl, &mut pairs);
+        let r = ar.anti_unify(br, &mut pairs);
+        (l, r)
+    };
+    let says_something = |(l, r): &(Term, Term)| {
+        let bare_variable = |side: &Term, other: &Term| {
+            let mut vars = Vec::new();
+            other.collect_vars(&[], &mut vars);
+            matches!(side, Term::Var(v) if !vars.contains(v))
+        };
+        l != r && !bare_variable(l, r) && !bare_variable(r, l)
+    };
+    let size = |(l, r): &(Term, Term)| l.size() + r.size();
+
+    let straight = Some(generalize(bl, br)).filter(says_something);
+    let flipped = Some(generalize(br, bl)).filter(says_something);
+    let (l, r) = match (straight, flipped) {
+        (Some(s), Some(f)) if size(&f) > size(&s) => f,
+        (Some(s), _) => s,
+        (None, f) => f?,
+    };
+    Some(Formula::Eq(l, r).universal_closure())
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Term::Var(v) => write!(f, "{}", v),
+            Term::Fun(name, args) if args.is_empty() => write!(f, "{}", name),
+            Term::Fun(name, args) => write!(
+                f,
+                "{}({})",
+                name,
+                args.iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |fs: &[Formula], op: &str| {
+            fs.iter()
+                .map(|x| x.unit())
+                .collect::<Vec<_>>()
+                .join(op)
+        };
+        match self {
+            Formula::Atom(t) => write!(f, "{}", t),
+            Formula::Eq(l, r) => write!(f, "{} = {}", l, r),
+            Formula::Neq(l, r) => write!(f, "{} != {}", l, r),
+            Formula::Not(inner) => write!(f, "~ {}", inner.unitary()),
+            Formula::And(fs) => write!(f, "{}", join(fs, " & ")),
+            Formula::Or(fs) => write!(f, "{}", join(fs, " | ")),
+            Formula::Implies(a, b) => write!(f, "{} => {}", a.unit(), b.unit()),
+            Formula::Iff(a, b) => write!(f, "{} <=> {}", a.unit(), b.unit()),
+            Formula::Forall(vars, body) => {
+                write!(f, "! [{}] : {}", vars.join(", "), body.unitary())
+            }
+            Formula::Exists(vars, body) => {
+                write!(f, "? [{}] : {}", vars.join(", "), body.unitary())
+            }
+        }
+    }
+}
+
+/// Print an annotated TPTP formula
+pub fn fof(name: &str, role: &str, formula: &Formula) -> String {
+    format!("fof({}, {},\n    {}\n).\n", name, role, formula)
+}
+
+/// Parse a formula, universally close it and print it as `fof(name, role, ...)`
+pub fn format_fof(name: &str, role: &str, formula: &str) -> Result<String, String> {
+    let parsed = parse_formula(formula)
+        .map_err(|e| format!("Cannot parse formula of {}: {}", name, e))?;
+    Ok(fof(name, role, &parsed.universal_closure()))
+}
+
+// --- Parsing ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Colon,
+    Forall,
+    Exists,
+    Not,
+    And,
+    Or,
+    Eq,
+    Neq,
+    Implies,
+    Iff,
+    /// `<=`
+    RevImplies,
+    /// `<~>`
+    Xor,
+    /// `~|`
+    Nor,
+    /// `~&`
+    Nand,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (token, width) = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => (Token::LParen, 1),
+            ')' => (Token::RParen, 1),
+            '[' => (Token::LBracket, 1),
+            ']' => (Token::RBracket, 1),
+            ',' => (Token::Comma, 1),
+            ':' => (Token::Colon, 1),
+            '~' if next == Some('|') => (Token::Nor, 2),
+            '~' if next == Some('&') => (Token::Nand, 2),
+            '~' => (Token::Not, 1),
+            '&' => (Token::And, 1),
+            '|' => (Token::Or, 1),
+            '?' => (Token::Exists, 1),
+            '!' if next == Some('=') => (Token::Neq, 2),
+            '!' => (Token::Forall, 1),
+            '=' if next == Some('>') => (Token::Implies, 2),
+            '=' => (Token::Eq, 1),
+            '<' if next == Some('=') && chars.get(i + 2) == Some(&'>') => (Token::Iff, 3),
+            '<' if next == Some('~') && chars.get(i + 2) == Some(&'>') => (Token::Xor, 3),
+            '<' if next == Some('=') => (Token::RevImplies, 2),
+            // 'quoted atoms' and "distinct objects", kept with their quotes
+            '\'' | '"' => {
+                let mut end = i + 1;
+                while end < chars.len() && chars[end] != c {
+                    end += if chars[end] == '\\' { 2 } else { 1 };
+                }
+                if end >= chars.len() {
+                    return Err(format!("unterminated {}", c));
+                }
+                let ident: String = chars[i..=end].iter().collect();
+                (Token::Ident(ident), end + 1 - i)
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '$' => {
+                let start = i;
+                let mut end = i + 1;
+                while end < chars.len() && (chars[end].is_alphanumeric() || chars[end] == '_') {
+                    end += 1;
+                }
+                let ident: String = chars[start..end].iter().collect();
+                (Token::Ident(ident), end - start)
+            }
+            c => return Err(format!("unexpected character '{}'", c)),
+        };
+        tokens.push(token);
+        i += width;
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.advance() {
+            Some(t) if t == expected => Ok(()),
+            other => Err(format!("expected {:?}, found {:?}", expected, other)),
+        }
+    }
+
+    fn formula(&mut self) -> Result<Formula, String> {
+        let first = self.unary()?;
+        match self.peek() {
+            Some(Token::And) | Some(Token::Or) => {
+                let op = self.peek().cloned();
+                let mut parts = vec![first];
+                while self.peek() == op.as_ref() {
+                    self.pos += 1;
+                    parts.push(self.unary()?);
+                }
+                if op == Some(Token::And) {
+                    Ok(Formula::And(parts))
+                } else {
+                    Ok(Formula::Or(parts))
+                }
+            }
+            Some(Token::Implies) => {
+                self.pos += 1;
+                let rhs = self.unary()?;
+                Ok(Formula::Implies(Box::new(first), Box::new(rhs)))
+            }
+            Some(Token::Iff) => {
+                self.pos += 1;
+                let rhs = self.unary()?;
+                Ok(Formula::Iff(Box::new(first), Box::new(rhs)))
+            }
+            // the other connectives in terms of those above
+            Some(Token::RevImplies) => {
+                self.pos += 1;
+                let rhs = self.unary()?;
+                Ok(Formula::Implies(Box::new(rhs), Box::new(first)))
+            }
+            Some(Token::Xor) => {
+                self.pos += 1;
+                let rhs = self.unary()?;
+                let iff = Formula::Iff(Box::new(first), Box::new(rhs));
+                Ok(Formula::Not(Box::new(iff)))
+            }
+            Some(Token::Nor) => {
+                self.pos += 1;
+                let rhs = self.unary()?;
+                Ok(Formula::Not(Box::new(Formula::Or(vec![first, rhs]))))
+            }
+            Some(Token::Nand) => {
+                self.pos += 1;
+                let rhs = self.unary()?;
+                Ok(Formula::Not(Box::new(Formula::And(vec![first, rhs]))))
+            }
+            _ => Ok(first),
+        }
+    }
+
+    fn unary(&mut self) -> Result<Formula, String> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.pos += 1;
+                Ok(Formula::Not(Box::new(self.unary()?)))
+            }
+            Some(Token::Forall) | Some(Token::Exists) => {
+                let universal = self.advance() == Some(Token::Forall);
+                self.expect(Token::LBracket)?;
+                let mut vars = Vec::new();
+                loop {
+                    match self.advance() {
+                        Some(Token::Ident(v)) => vars.push(v),
+                        other => return Err(format!("expected a variable, found {:?}", other)),
+                    }
+                    match self.advance() {
+                        Some(Token::Comma) => continue,
+                        Some(Token::RBracket) => break,
+                        other => return Err(format!("expected ',' or ']', found {:?}", other)),
+                    }
+                }
+                self.expect(Token::Colon)?;
+                let body = Box::new(self.unary()?);
+                if universal {
+                    Ok(Formula::Forall(vars, body))
+                } else {
+                    Ok(Formula::Exists(vars, body))
+                }
+            }
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let inner = self.formula()?;
+                self.expect(Token::RParen)?;
+                Ok(inner)
+            }
+            _ => self.atom(),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Formula, String> {
+        let lhs = self.term()?;
+        match self.peek() {
+            Some(Token::Eq) => {
+                self.pos += 1;
+                Ok(Formula::Eq(lhs, self.term()?))
+            }
+            Some(Token::Neq) => {
+                self.pos += 1;
+                Ok(Formula::Neq(lhs, self.term()?))
+            }
+            _ => Ok(Formula::Atom(lhs)),
+        }
+    }
+
+    fn term(&mut self) -> Result<Term, String> {
+        match self.advance() {
+            Some(Token::Ident(name)) => {
+                if self.peek() == Some(&Token::LParen) {
+                    self.pos += 1;
+                    let mut args = vec![self.term()?];
+                    while self.peek() == Some(&Token::Comma) {
+                        self.pos += 1;
+                        args.push(self.term()?);
+                    }
+                    self.expect(Token::RParen)?;
+                    Ok(Term::Fun(name, args))
+                } else if is_variable(&name) {
+                    Ok(Term::Var(name))
+                } else {
+                    Ok(Term::Fun(name, Vec::new()))
+                }
+            }
+            other => Err(format!("expected a term, found {:?}", other)),
+        }
+    }
+}
+
+/// Parse a TPTP `fof` formula body, e.g. "! [X, Y] : (op(X, Y) = X)"
+pub fn parse_formula(s: &str) -> Result<Formula, String> {
+    let mut parser = Parser {
+        tokens: tokenize(s)?,
+        pos: 0,
+    };
+    let formula = parser.formula()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(format!(
+            "unexpected {:?} after end of formula",
+            parser.tokens[parser.pos]
+        ));
+    }
+    Ok(formula)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_quantified() {
+        let f = parse_formula("! [X0, X1] :\n      (op(X1,X0) = X1)").unwrap();
+        assert_eq!(f.to_string(), "! [X0, X1] : (op(X1,X0) = X1)");
+    }
+
+    #[test]
+    fn test_closure_twee_vars() {
+        let f = parse_formula("op(X, op(Y, Z)) = Y").unwrap();
+        assert_eq!(
+            f.universal_closure().to_string(),
+            "! [X, Y, Z] : (op(X,op(Y,Z)) = Y)"
+        );
+    }
+
+    #[test]
+    fn test_closure_keeps_skolems() {
+        let f = parse_formula("sK0 != op(sK0,X0)").unwrap();
+        assert_eq!(
+            f.universal_closure().to_string(),
+            "! [X0] : (sK0 != op(sK0,X0))"
+        );
+    }
+
+    #[test]
+    fn test_nested_quantifier() {
+        let f = parse_formula("! [X] : ? [Y] : op(X, Y) = X").unwrap();
+        assert!(f.free_vars().is_empty());
+        assert_eq!(f.to_string(), "! [X] : ? [Y] : (op(X,Y) = X)");
+    }
+
+    #[test]
+    fn test_negate_clause() {
+        let f = parse_formula("sK0 != op(sK0,X0) | X0 = sK1").unwrap();
+        // not (for all X0: C) is (exists X0: not C)
+        assert_eq!(
+            f.negate().to_string(),
+            "? [X0] : (sK0 = op(sK0,X0) & X0 != sK1)"
+        );
+        let closed = parse_formula("! [X] : ? [Y] : op(X, Y) = X").unwrap();
+        assert_eq!(
+            closed.negate().to_string(),
+            "? [X] : ! [Y] : (op(X,Y) != X)"
+        );
+    }
+
+    #[test]
+    fn test_skolems_consistent() {
+        let chain = vec![
+            parse_formula("op(sK0,sK1) = sK0").unwrap(),
+            parse_formula("sK1 != op(X0,sK0)").unwrap(),
+        ];
+        let (turned, mapping) = skolems_to_variables(&chain).unwrap();
+        assert_eq!(turned[0].to_string(), "op(SK0,SK1) = SK0");
+        assert_eq!(turned[1].to_string(), "SK1 != op(X0,SK0)");
+        assert_eq!(mapping.len(), 2);
+        assert!(skolems_to_variables(&[parse_formula("sK2(X0) = X0").unwrap()]).is_err());
+
+        // a variable SK0, free or bound, is not captured
+        let chain = vec![
+            parse_formula("op(sK0,SK0) = sK0").unwrap(),
+            parse_formula("! [SK1] : op(SK1,sK1) = SK1").unwrap(),
+        ];
+        let (turned, _) = skolems_to_variables(&chain).unwrap();
+        assert_eq!(turned[0].to_string(), "op(SK0_1,SK0) = SK0_1");
+        assert_eq!(turned[1].to_string(), "! [SK1] : (op(SK1,SK1_1) = SK1)");
+    }
+
+    #[test]
+    fn test_all_binary_connectives() {
+        let f = |s: &str| parse_formula(s).unwrap().to_string();
+        assert_eq!(f("p(X) <= q(X)"), "q(X) => p(X)");
+        assert_eq!(f("p <~> q"), "~ (p <=> q)");
+        assert_eq!(f("p ~| q"), "~ (p | q)");
+        assert_eq!(f("p ~& q"), "~ (p & q)");
+        assert_eq!(f("~ p | q"), "~ p | q");
+    }
+
+    #[test]
+    fn test_quoted_atoms() {
+        let f = parse_formula("'a b'(X) = \"c\" & 'it\\'s'").unwrap();
+        assert_eq!(f.to_string(), "'a b'(X) = \"c\" & 'it\\'s'");
+        assert_eq!(f.free_vars(), ["X"]);
+        assert!(parse_formula("'open").is_err());
+    }
+
+    #[test]
+    fn test_clause() {
+        let f = parse_formula("X0 = X1 | op(X0,X1) != X0").unwrap();
+        assert_eq!(
+            f.universal_closure().to_string(),
+            "! [X0, X1] : (X0 = X1 | op(X0,X1) != X0)"
+        );
+    }
+
+    #[test]
+    fn test_symbols() {
+        let f = parse_formula("! [X] : mult(e, inv(X)) != f(X, e)").unwrap();
+        let symbols: Vec<_> = f.symbols().into_iter().collect();
+        assert_eq!(
+            symbols,
+            [
+                ("e".to_string(), 0),
+                ("f".to_string(), 2),
+                ("inv".to_string(), 1),
+                ("mult".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_anti_unify() {
+        let f = |s: &str| parse_formula(s).unwrap();
+        let shared = anti_unify(
+            &f("! [X] : mult(X, mult(e, X)) = X"),
+            &f("! [Y] : mult(inv(Y), mult(e, inv(Y))) = inv(Y)"),
+        );
+        assert_eq!(
+            shared.unwrap().to_string(),
+            "! [X0] : (mult(X0,mult(e,X0)) = X0)"
+        );
+        // the second equation is lined up with the first by flipping it
+        let shared = anti_unify(&f("mult(e, a) = a"), &f("b = mult(e, b)"));
+        assert_eq!(shared.unwrap().to_string(), "! [X0] : (mult(e,X0) = X0)");
+        // nothing in common but being an equation
+        assert!(anti_unify(&f("mult(e, a) = a"), &f("inv(e) = e")).is_none());
+        assert!(anti_unify(&f("mult(e, e) = e"), &f("inv(e) = e")).is_none());
+        assert!(anti_unify(&f("a = a"), &f("b = b")).is_none());
+        assert!(anti_unify(&f("p(a)"), &f("p(b)")).is_none());
+    }
+
+    #[test]
+    fn test_generalizations() {
+        let f = parse_formula("! [X] : (op(op(e,e),X) = op(op(e,e),e))").unwrap();
+        let general: Vec<_> = generalizations(&f).iter().map(|g| g.to_string()).collect();
+        assert_eq!(
+            general,
+            vec![
+                "! [G0, X] : (op(G0,X) = op(G0,e))",
+                "! [G0, X] : (op(op(G0,G0),X) = op(op(G0,G0),G0))",
+            ]
+        );
+        assert!(generalizations(&parse_formula("op(X,Y) = X").unwrap()).is_empty());
+    }
+}
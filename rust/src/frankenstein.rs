@@ -1,16 +1,43 @@
-use crate::prover_wrapper::{proof_length, prove_lemmas};
-use crate::utils::*;
+use crate::alpha_match::FormulaIndex;
+use crate::artifact::{safe_name, write_atomic};
+use crate::collect_report::{CollectReport, FailedLemma, ProvedLemma};
+use crate::formula::{fof, parse_formula, Formula};
+use crate::integrity;
+use crate::lemma_library;
+use crate::lemma_store::{self, LemmaCache, LemmaKind, LemmaStore};
+use crate::process::decode_output;
+use crate::provenance::{keep_version, write_with_notes, write_with_provenance};
+use crate::prover_wrapper::{lemma_number, proof_length, prove_lemmas};
+use crate::ranking;
+use crate::run_config::RunConfig;
+use crate::summary::{self, Summary, SummaryEntry};
+use crate::tptp_file::TptpFile;
+use crate::vampire_proof::{parse_vampire_steps, StepRole, VampireStep};
+use crate::workspace;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Phase 1: extract lemmas, and run provers on them.
-/// Produces `summary.json` for use in Phase 2.
-pub fn collect(input_file: &str, proof_file: &str, suffix: String) {
+/// Produces `summary.json` for use in Phase 2, and returns what was extracted
+/// and proved, which is also saved as `collect_<suffix>.json`. Only the
+/// `modes` given are extracted; the lemmas of the others are removed. With
+/// `vampire_steps` the single lemmas are read from the Vampire proof steps
+/// instead of by the OCaml parser. `config` holds the settings of the run.
+pub fn collect(
+    config: &RunConfig,
+    input_file: &str,
+    proof_file: &str,
+    suffix: String,
+    modes: &[LemmaKind],
+    vampire_steps: bool,
+) -> CollectReport {
     println!("=== Phase 1: Collection ===");
     println!("[INFO] Input:  {}", input_file);
     println!("[INFO] Output: {}", proof_file);
+    // the suffix names the summary and report files
+    let suffix = safe_name(&suffix);
 
     let lemmas_dir = "../lemmas".to_string();
 
@@ -24,84 +51,344 @@ pub fn collect(input_file: &str, proof_file: &str, suffix: String) {
         println!("[INFO] Cleaned lemmas directory.");
     }
 
-    let modes = ["single", "history", "abstract"];
     let mut all_lemma_files = Vec::new();
+    let mut report = CollectReport::default();
 
-    for mode in &modes {
-        let mode_dir = format!("{}/{}", lemmas_dir, mode);
+    for kind in LemmaKind::ALL {
+        let mode = kind.dir_name();
+        let mode_dir = Path::new(&lemmas_dir).join(mode);
         if Path::new(&mode_dir).exists() {
             fs::remove_dir_all(&mode_dir).expect("Failed to clean mode directory");
         }
+        if !modes.contains(&kind) {
+            // no lemmas of an earlier run may stand in for a skipped mode
+            println!("[INFO] Skipping {} lemmas.", mode);
+            continue;
+        }
         fs::create_dir_all(&mode_dir).expect("Failed to create mode directory");
 
-        // run OCaml parser to extract lemmas for the given mode
-        run_ocaml_parser(&proof_file, mode)
-            .expect(&format!("Failed to extract lemmas for mode '{}'", mode));
+        let mut extracted = Vec::new();
+        if vampire_steps && kind == LemmaKind::Single {
+            // equational proofs do without the OCaml parser
+            match write_step_lemmas(proof_file, &lemmas_dir) {
+                Ok(0) => println!(
+                    "[WARN] No unit equality among the steps of {}, using the OCaml parser",
+                    proof_file
+                ),
+                Ok(n) => println!(
+                    "[INFO] Extracted {} single lemmas from the Vampire proof steps",
+                    n
+                ),
+                Err(err) => println!("[WARN] {}, using the OCaml parser", err),
+            }
+            extracted = extracted_lemma_files(&lemmas_dir);
+        }
 
-        // move extracted lemma files to mode directory
-        for entry in fs::read_dir(&lemmas_dir).expect("Failed to read lemmas directory") {
-            let path = entry.expect("Failed to read entry").path();
-            if path.extension().map(|ext| ext == "p").unwrap_or(false) {
-                let filename = path.file_name().unwrap();
-                let new_path = Path::new(&mode_dir).join(filename);
-                fs::rename(&path, &new_path).expect("Failed to move lemma file");
-                all_lemma_files.push(new_path.to_string_lossy().to_string());
+        if extracted.is_empty() {
+            // run OCaml parser to extract lemmas for the given mode
+            run_ocaml_parser(&proof_file, mode)
+                .expect(&format!("Failed to extract lemmas for mode '{}'", mode));
+            extracted = extracted_lemma_files(&lemmas_dir);
+        }
+        if extracted.is_empty() {
+            println!(
+                "[WARN] The OCaml parser extracted no {} lemmas from {}",
+                mode, proof_file
+            );
+            if kind == LemmaKind::Single && !vampire_steps {
+                match write_step_lemmas(proof_file, &lemmas_dir) {
+                    Ok(0) => println!("[WARN] The Rust fallback found no single lemmas either"),
+                    Ok(n) => println!(
+                        "[INFO] The Rust fallback extracted {} single lemmas from the proof",
+                        n
+                    ),
+                    Err(err) => println!("[WARN] Single lemma fallback failed: {}", err),
+                }
+                extracted = extracted_lemma_files(&lemmas_dir);
             }
         }
+
+        // move extracted lemma files to mode directory, in name order
+        report.extracted.insert(mode.to_string(), extracted.len());
+        for path in extracted {
+            let filename = path.file_name().unwrap();
+            let new_path = Path::new(&mode_dir).join(filename);
+            fs::rename(&path, &new_path).expect("Failed to move lemma file");
+            all_lemma_files.push(new_path.to_string_lossy().to_string());
+        }
     }
 
+    let (all_lemma_files, aliases) = drop_redundant_lemmas(input_file, all_lemma_files);
+    // the lemmas later phases may find under another name
+    let mut handed_over = vec![PathBuf::from(proof_file)];
+    if !aliases.is_empty() {
+        match lemma_store::write_aliases(&lemmas_dir, &aliases) {
+            Ok(path) => {
+                println!(
+                    "[INFO] {} lemmas are aliases of earlier lemmas, see '{}'.",
+                    aliases.len(),
+                    path.display()
+                );
+                handed_over.push(path);
+            }
+            Err(err) => println!("[WARN] {}", err),
+        }
+    }
+    report.aliases = aliases;
+
     // run provers on all lemma files
     let provers = ["vampire", "twee"];
-    let results = prove_lemmas(&all_lemma_files, &provers, "../proofs");
+    let (mut results, prover_runs) = prove_lemmas(config, &all_lemma_files, &provers, "../proofs");
+    report.prover_runs = prover_runs;
+    // ranking and the lemma library read the same lemma files
+    let lemma_cache = LemmaCache::default();
+    rank_lemmas(config, &mut results, proof_file, &lemmas_dir, &lemma_cache);
 
     println!("\n=== Phase 1 Summary ===");
-    let mut lemma_nums: Vec<u32> = results.keys().cloned().collect();
-    lemma_nums.sort();
-    for n in lemma_nums {
-        let (mode, prover, proof) = &results[&n];
+    for (&n, entry) in summary::proved(&results) {
+        // the proofs were only kept if their length could be told
+        let steps = proof_length(&entry.prover, &entry.proof).unwrap_or_default();
         println!(
             "- lemma_{:04} (mode: {}): proved by '{}' with {} steps",
-            n,
-            mode,
-            prover,
-            proof_length(prover, proof)
+            n, entry.file, entry.prover, steps
         );
+        report.proved.push(ProvedLemma {
+            number: n,
+            file: entry.file.clone(),
+            prover: entry.prover.clone(),
+            steps,
+        });
+    }
+
+    let mut unproved: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    for file in &all_lemma_files {
+        let n = lemma_number(file);
+        if !results.get(&n).is_some_and(|entry| entry.is_proved()) {
+            let stem = Path::new(file).file_stem().unwrap().to_string_lossy();
+            unproved.entry(n).or_default().push(stem.to_string());
+        }
+    }
+    for (number, files) in unproved {
+        println!("- lemma_{:04}: no proof ({})", number, files.join(", "));
+        report.failed.push(FailedLemma { number, files });
     }
 
     // save summary for Phase 2
     let summary_file = format!("../output/summary_{}.json", suffix);
-    let summary_json = serde_json::to_string_pretty(&results).expect("Failed to serialize results");
-    fs::write(&summary_file, summary_json).expect("Failed to save summary.json");
+    summary::write(&summary_file, &results).expect("Failed to save summary.json");
     println!(
         "\n[INFO] Phase 1 complete. Summary saved to '{}'.",
         summary_file
     );
+    // what the later phases read, so they can tell it was not replaced since
+    handed_over.push(PathBuf::from(&summary_file));
+    let record_file = integrity::record_file(&summary_file);
+    if let Err(err) = integrity::record(&record_file, "collect", &handed_over, &lemmas_dir) {
+        eprintln!("[WARN] Artifact hashes not recorded: {}", err);
+    }
+
+    // lemmas for the other problems of the benchmark
+    if let Some(library) = &config.lemma_library {
+        let added = fs::read_to_string(input_file)
+            .map_err(|e| format!("Failed to read {}: {}", input_file, e))
+            .and_then(|problem| {
+                let lemmas = LemmaStore::load_cached(&lemmas_dir, &lemma_cache)?;
+                lemma_library::add_problem(library, &suffix, &problem, &results, &lemmas)
+            });
+        match added {
+            Ok(n) => println!(
+                "[INFO] {} new lemmas added to the lemma library '{}'.",
+                n, library
+            ),
+            Err(err) => println!("[WARN] Lemma library not updated: {}", err),
+        }
+    }
+
+    let report_file = CollectReport::path(Path::new("../output"), &suffix);
+    match report.write(&report_file) {
+        Ok(()) => println!(
+            "[INFO] Collect report saved to '{}'.",
+            report_file.display()
+        ),
+        Err(err) => eprintln!("[WARN] {}", err),
+    }
+    report
+}
+
+/// Rank the lemmas of `results` against the Vampire refutation in `proof_file`,
+/// for the order in which `minimize` tries them
+fn rank_lemmas(
+    config: &RunConfig,
+    results: &mut Summary,
+    proof_file: &str,
+    lemmas_dir: &str,
+    lemma_cache: &LemmaCache,
+) {
+    let ranked = fs::read_to_string(proof_file)
+        .map_err(|e| format!("Failed to read {}: {}", proof_file, e))
+        .and_then(|proof| {
+            let lemmas = LemmaStore::load_cached(lemmas_dir, lemma_cache)?;
+            ranking::rank_summary(config, results, &proof, &lemmas);
+            Ok(())
+        });
+    if let Err(err) = ranked {
+        println!("[WARN] Lemmas not ranked: {}", err);
+        return;
+    }
+    let top: Vec<&str> = summary::by_usefulness(results)
+        .into_iter()
+        .filter(|n| results[n].usefulness.is_some())
+        .take(5)
+        .map(|n| results[&n].file.as_str())
+        .collect();
+    if !top.is_empty() {
+        println!("[INFO] Most useful lemmas: {}", top.join(", "));
+    }
+}
+
+/// What a lemma dropped before proving repeats, up to variable renaming
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Repeats {
+    Axiom(String),
+    /// an earlier lemma of any mode, which the dropped one becomes an alias of
+    Lemma(String),
+}
+
+impl std::fmt::Display for Repeats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Repeats::Axiom(name) => write!(f, "alpha-equivalent to axiom {}", name),
+            Repeats::Lemma(name) => write!(f, "duplicate of {}", name),
+        }
+    }
+}
+
+/// `lemma_files` without the lemmas not worth prover time: those whose
+/// conjecture is alpha-equivalent to an axiom of `input_file` or to a lemma of
+/// a lower number. The files of repeated lemmas are removed, and each becomes
+/// an alias of the lemma it repeats; the aliases are returned with the kept
+/// files. Files that cannot be read are kept.
+fn drop_redundant_lemmas(
+    input_file: &str,
+    lemma_files: Vec<String>,
+) -> (Vec<String>, BTreeMap<String, String>) {
+    let read = |path: &str| {
+        fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))
+            .and_then(|text| TptpFile::parse(&text))
+    };
+    let axioms: Vec<(String, String)> = match read(input_file) {
+        Ok(file) => file
+            .formulas()
+            .filter(|f| !f.is_conjecture())
+            .map(|f| (f.name.clone(), f.body_line()))
+            .collect(),
+        Err(err) => {
+            println!("[WARN] Not checking the lemmas against the axioms: {}", err);
+            Vec::new()
+        }
+    };
+    let lemmas: Vec<(String, String)> = lemma_files
+        .iter()
+        .filter_map(|path| {
+            let file = read(path).ok()?;
+            let conjecture = file.formulas().find(|f| f.is_conjecture())?;
+            let name = Path::new(path).file_stem()?.to_string_lossy().to_string();
+            Some((name, conjecture.body_line()))
+        })
+        .collect();
+
+    let redundant = redundant_lemmas(&axioms, &lemmas);
+    let mut aliases = BTreeMap::new();
+    for (name, repeats) in &redundant {
+        println!("[INFO] Not proving {}: {}", name, repeats);
+        if let Repeats::Lemma(canonical) = repeats {
+            aliases.insert(name.clone(), canonical.clone());
+        }
+    }
+    if !redundant.is_empty() {
+        println!(
+            "[INFO] Dropped {} of {} lemma problems before proving.",
+            redundant.len(),
+            lemma_files.len()
+        );
+    }
+    let mut kept = Vec::new();
+    for path in lemma_files {
+        let name = Path::new(&path).file_stem().unwrap().to_string_lossy();
+        if aliases.contains_key(name.as_ref()) {
+            if let Err(err) = fs::remove_file(&path) {
+                println!("[WARN] Failed to remove {}: {}", path, err);
+            }
+        } else if !redundant.contains_key(name.as_ref()) {
+            kept.push(path);
+        }
+    }
+    (kept, aliases)
+}
+
+/// Lemmas (name, conjecture) alpha-equivalent to one of the `axioms` or to a
+/// lemma of a lower number, of any mode, with what they repeat. The modes of
+/// one number state the same formula from different axioms, and stay as the
+/// alternatives Phase 1 picks the shortest proof of.
+fn redundant_lemmas(
+    axioms: &[(String, String)],
+    lemmas: &[(String, String)],
+) -> BTreeMap<String, Repeats> {
+    let mut known: FormulaIndex<(u32, Repeats)> = FormulaIndex::new();
+    for (name, formula) in axioms {
+        known.insert(formula, (0, Repeats::Axiom(name.clone())));
+    }
+    // a repeated formula is kept under its lowest number
+    let mut by_number: Vec<&(String, String)> = lemmas.iter().collect();
+    by_number.sort_by_key(|(name, _)| lemma_number(name));
+    let mut redundant = BTreeMap::new();
+    for (name, formula) in by_number {
+        let number = lemma_number(name);
+        let matches = known.lookup_alpha_equivalent(formula);
+        let repeated = matches
+            .iter()
+            .find(|(other, repeats)| matches!(repeats, Repeats::Axiom(_)) || *other != number);
+        match repeated {
+            Some((_, repeats)) => {
+                redundant.insert(name.clone(), repeats.clone());
+            }
+            None if matches.is_empty() => {
+                known.insert(formula, (number, Repeats::Lemma(name.clone())))
+            }
+            None => {}
+        }
+    }
+    redundant
 }
 
 /// Phase 2: Shorten history proofs by replacing history lemmas with abstract lemmas
 /// and rerunning provers on updated files.
-pub fn shorten_proofs(summary_file: &str) {
+pub fn shorten_proofs(config: &RunConfig, summary_file: &str) {
     println!("=== Phase 2: Shorten History Proofs ===");
 
     let lemmas_dir = "../lemmas".to_string();
     let proofs_dir = "../proofs".to_string();
     let tmp_dirs = vec![
-        ("vampire", "../proofs/vampire_tmp".to_string()),
-        ("twee", "../proofs/twee_tmp".to_string()),
-        ("egg", "../proofs/egg_tmp".to_string()),
+        ("vampire", Path::new(&proofs_dir).join("vampire_tmp")),
+        ("twee", Path::new(&proofs_dir).join("twee_tmp")),
+        ("egg", Path::new(&proofs_dir).join("egg_tmp")),
     ];
+    let record_file = integrity::record_file(summary_file);
+    if let Err(err) = integrity::verify(&record_file, &lemmas_dir) {
+        println!("[ERROR] {}", err);
+        return;
+    }
 
-    let summary_data: HashMap<u32, (String, String, String)> = serde_json::from_str(
-        &fs::read_to_string(&summary_file).expect("Failed to read summary.json"),
-    )
-    .expect("Failed to parse summary.json");
+    let summary_data = summary::load(summary_file).expect("Failed to read summary.json");
 
-    // map abstract lemma number -> formula
+    // map abstract lemma number -> formula, for the abstract lemmas to build on
+    let lemmas = LemmaStore::load(&lemmas_dir).expect("Failed to read lemmas directory");
     let mut abstract_map: HashMap<u32, String> = HashMap::new();
-    for (&n, (mode, _, _)) in &summary_data {
-        if mode.starts_with("abstract") {
+    for (&n, entry) in &summary_data {
+        if entry.file.starts_with("abstract") && entry.is_usable(config) {
             let lemma_name = format!("abstract_lemma_{:04}", n);
-            let formula = match load_lemma(&lemmas_dir, &lemma_name) {
+            let formula = match lemmas.formula(&lemma_name) {
                 Ok(f) => f,
                 Err(err) => {
                     eprintln!("[WARN] Missing lemma {}: {}", lemma_name, err);
@@ -113,66 +400,117 @@ pub fn shorten_proofs(summary_file: &str) {
         }
     }
 
-    let history_to_update: Vec<u32> = summary_data
+    let mut history_to_update: Vec<u32> = summary_data
         .iter()
-        .filter(|(_, (mode, _, _))| mode.starts_with("history"))
+        .filter(|(_, entry)| entry.file.starts_with("history"))
         .map(|(n, _)| *n)
         .collect();
+    history_to_update.sort();
 
+    if history_to_update.is_empty() {
+        // e.g. collect ran without the history mode
+        println!("[INFO] No history lemmas in the summary, nothing to shorten.");
+        return;
+    }
     println!("[INFO] History files to update: {:?}", history_to_update);
 
+    // lemma number of every single and history lemma, for premises that
+    // are neither named nor roled as lemmas
+    let mut lemma_numbers = FormulaIndex::new();
+    for kind in [LemmaKind::Single, LemmaKind::History] {
+        for (name, formula) in lemmas.iter_by_kind(kind) {
+            lemma_numbers.insert(&formula.to_string(), lemma_number(name));
+        }
+    }
+
     // replace history lemmas with abstract formulas
+    let mut report = Vec::new();
     for &history_file_num in &history_to_update {
-        let history_file = format!(
-            "{}/history/history_lemma_{:04}.p",
-            lemmas_dir, history_file_num
-        );
-        let mut content = fs::read_to_string(&history_file)
+        let history_file = history_lemma_file(&lemmas_dir, history_file_num);
+        let content = fs::read_to_string(&history_file)
             .unwrap_or_else(|_| panic!("Failed to read {}", history_file));
+        let mut file = match TptpFile::parse(&content) {
+            Ok(file) => file,
+            Err(err) => {
+                println!("[ERROR] Cannot read {}: {}", history_file, err);
+                continue;
+            }
+        };
 
-        let block_re = Regex::new(r"(?s)(fof\(lemma_(\d{4}),\s*lemma\s*,.*?\)\s*\.)").unwrap();
-        let mut replaced_any = false;
-
-        content = block_re
-            .replace_all(&content, |caps: &regex::Captures| {
-                let lemma_num: u32 = caps[2].parse().unwrap();
-                if let Some(formula) = abstract_map.get(&lemma_num) {
-                    println!(
-                        "[INFO] Replacing lemma_{:04} in history file {}",
-                        lemma_num, history_file_num
-                    );
-                    replaced_any = true;
-                    format!("fof(lemma_{:04}, lemma,\n    {}\n).", lemma_num, formula)
-                } else {
-                    caps[1].to_string()
-                }
-            })
-            .to_string();
+        let substitution =
+            substitute_abstract_lemmas(&mut file, history_file_num, &abstract_map, &lemma_numbers);
+        for (name, n, how) in &substitution.matched {
+            println!(
+                "[INFO] Replacing {} in history file {} by abstract_lemma_{:04} (matched {})",
+                name, history_file_num, n, how
+            );
+        }
+        for (name, n) in &substitution.unmatched {
+            println!(
+                "[INFO] Keeping {} in history file {}: no usable abstract_lemma_{:04}",
+                name, history_file_num, n
+            );
+        }
+        report.push((
+            history_file_num,
+            substitution.matched.len(),
+            substitution.unmatched.len(),
+        ));
 
-        if replaced_any {
-            fs::write(&history_file, content)
+        let mut notes: Vec<String> = substitution
+            .matched
+            .iter()
+            .map(|(name, n, _)| format!("{} replaced by abstract_lemma_{:04}", name, n))
+            .collect();
+        if !notes.is_empty() {
+            // keep the version being replaced, for rollback and audit
+            let previous = keep_version(Path::new(&history_file))
+                .unwrap_or_else(|_| panic!("Failed to keep a copy of {}", history_file))
+                .expect("history file was just read");
+            let previous = previous.to_string_lossy().to_string();
+            println!("[INFO] Previous version kept as {}", previous);
+            notes.push(format!("previous version: {}", previous));
+            write_with_notes(config, &history_file, &file.to_string(), &previous, &notes)
                 .unwrap_or_else(|_| panic!("Failed to write {}", history_file));
         }
     }
 
+    println!("\n=== Abstract Lemma Substitution ===");
+    for (n, matched, unmatched) in &report {
+        println!(
+            "- history_lemma_{:04}: {} replaced, {} without abstract lemma",
+            n, matched, unmatched
+        );
+    }
+
     // rerun provers on updated history files
     let updated_files: Vec<String> = history_to_update
         .iter()
-        .map(|n| format!("{}/history/history_lemma_{:04}.p", lemmas_dir, n))
+        .map(|n| history_lemma_file(&lemmas_dir, *n))
         .collect();
 
     let provers = ["vampire", "twee", "egg"];
     fs::create_dir_all("../tmp").expect("Failed to create ../tmp directory");
-    let updated_results = prove_lemmas(&updated_files, &provers, "../tmp"); // tmp root
+    let (updated_results, _) = prove_lemmas(config, &updated_files, &provers, "../tmp"); // tmp root
 
     println!("\n=== Updated History Proofs ===");
-    for (n, (mode, prover, proof)) in &updated_results {
+    for (&n, entry) in &updated_results {
+        let SummaryEntry {
+            file: mode,
+            prover,
+            proof,
+            ..
+        } = entry;
+        if !entry.is_usable(config) {
+            println!("- history_lemma_{:04}: no proof", n);
+            continue;
+        }
         println!(
             "- history_lemma_{:04} (mode: {}): proved by '{}' with {} steps",
             n,
             mode,
             prover,
-            proof_length(prover, proof)
+            proof_length(prover, proof).unwrap_or_default()
         );
 
         // find prover-specific tmp dir
@@ -185,34 +523,39 @@ pub fn shorten_proofs(summary_file: &str) {
         // tmp folder filename
         let proof_file_tmp =
             Path::new(tmp_dir).join(format!("history_lemma_{:04}_{}.proof", n, prover));
-        fs::write(&proof_file_tmp, proof)
-            .unwrap_or_else(|_| panic!("Failed to write proof file {}", proof_file_tmp.display()));
-
+        let history_file = history_lemma_file(&lemmas_dir, n);
         // main proofs folder filename (same naming convention)
         let proof_file_main =
             Path::new(&proofs_dir).join(format!("history_lemma_{:04}_{}.proof", n, prover));
-        fs::write(&proof_file_main, proof)
-            .unwrap_or_else(|_| panic!("Failed to write proof file {}", proof_file_main.display()));
+        for proof_file in [&proof_file_tmp, &proof_file_main] {
+            write_versioned_proof(config, proof_file, proof, &history_file)
+                .unwrap_or_else(|_| panic!("Failed to write proof file {}", proof_file.display()));
+        }
+    }
+
+    // the history lemmas changed
+    if let Err(err) = integrity::rerecord(&record_file, "shorten", &lemmas_dir) {
+        eprintln!("[WARN] Artifact hashes not recorded: {}", err);
     }
 }
 
 /// Phase 3: Structural analysis of proofs. Groups lemmas by shared axioms
 /// and saves results in a text file.
-pub fn structural_groups(summary_file: &str) {
-    use std::{collections::HashMap, fs, path::Path};
-
+pub fn structural_groups(config: &RunConfig, summary_file: &str) {
     println!("=== Phase 3: Structural Analysis of Proofs ===");
 
     let proofs_dir = "../proofs".to_string();
     let output_groups_file = "../output/structural_groups.txt".to_string();
 
+    if let Err(err) = integrity::verify(&integrity::record_file(summary_file), "../lemmas") {
+        println!("[ERROR] {}", err);
+        return;
+    }
+
     // load summary.json
-    let summary_data: HashMap<u32, (String, String, String)> = serde_json::from_str(
-        &fs::read_to_string(&summary_file).expect("Failed to read summary.json"),
-    )
-    .expect("Failed to parse summary.json");
+    let summary_data = summary::load(summary_file).expect("Failed to read summary.json");
 
-    if summary_data.is_empty() {
+    if !summary_data.values().any(|entry| entry.is_proved()) {
         println!("[INFO] No proofs found in summary.json. Run Phase 1 first.");
         return;
     }
@@ -220,13 +563,57 @@ pub fn structural_groups(summary_file: &str) {
     let mut groups_output = String::new();
     groups_output.push_str("=== Structural Groups ===\n");
 
-    // maps: key -> {lemma numbers}, key → {axioms}
-    let mut groups: HashMap<String, Vec<u32>> = HashMap::new();
-    let mut key_to_axioms: HashMap<String, Vec<String>> = HashMap::new();
+    let (groups, without_axioms) = find_structural_groups(&summary_data, &proofs_dir);
+    for lemma_num in without_axioms {
+        groups_output.push_str(&format!(
+            "[WARN] lemma_{:04} has no recognizable axioms.\n",
+            lemma_num
+        ));
+    }
+    for group in &groups {
+        groups_output.push_str(&format!("\n[GROUP] Lemmas {:?}\n", group.lemmas));
+        groups_output.push_str("  Shared axioms:\n");
+        for ax in &group.axioms {
+            groups_output.push_str(&format!("    - {}\n", ax));
+        }
+    }
+
+    // save the output to structural_groups.txt
+    write_with_provenance(config, &output_groups_file, &groups_output, summary_file)
+        .expect("Failed to save structural groups to file");
+    println!(
+        "\n[INFO] Structural analysis complete. Groups saved to '{}'.",
+        output_groups_file
+    );
+}
 
-    for (&lemma_num, (mode, prover, proof_text)) in &summary_data {
+/// Proved lemmas whose proofs start from the same axioms
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructuralGroup {
+    pub lemmas: Vec<u32>,
+    /// the shared axioms, normalized and sorted
+    pub axioms: Vec<String>,
+}
+
+/// The groups of more than one proved lemma in `summary_data`, and the
+/// lemmas whose proofs show no recognizable axioms
+pub fn find_structural_groups(
+    summary_data: &Summary,
+    proofs_dir: &str,
+) -> (Vec<StructuralGroup>, Vec<u32>) {
+    // sorted axiom list -> lemma numbers
+    let mut groups: BTreeMap<Vec<String>, Vec<u32>> = BTreeMap::new();
+    let mut without_axioms = Vec::new();
+
+    for (&lemma_num, entry) in summary::proved(summary_data) {
+        let SummaryEntry {
+            file: mode,
+            prover,
+            proof: proof_text,
+            ..
+        } = entry;
         // construct proof path: <proofs_dir>/<mode>_<prover>.proof
-        let proof_path = format!("{}/{}_{}.proof", proofs_dir, mode, prover);
+        let proof_path = Path::new(proofs_dir).join(format!("{}_{}.proof", mode, prover));
 
         let proof_content = if Path::new(&proof_path).exists() {
             fs::read_to_string(&proof_path).unwrap_or_else(|_| proof_text.clone())
@@ -238,48 +625,197 @@ pub fn structural_groups(summary_file: &str) {
         let axioms = extract_axioms(&proof_content);
 
         if axioms.is_empty() {
-            groups_output.push_str(&format!(
-                "[WARN] lemma_{:04} has no recognizable axioms.\n",
-                lemma_num
-            ));
+            without_axioms.push(lemma_num);
             continue;
         }
 
         // normalize: sorted axiom list becomes the key
-        let mut key_vec: Vec<String> = axioms.iter().cloned().collect();
-        key_vec.sort();
-        let key = key_vec.join("|");
-
-        key_to_axioms.insert(key.clone(), key_vec);
+        let mut key: Vec<String> = axioms.into_iter().collect();
+        key.sort();
         groups.entry(key).or_default().push(lemma_num);
     }
 
-    // print only real groups (with more than 1 lemma)
-    for (key, lemmas) in &groups {
-        if lemmas.len() > 1 {
-            groups_output.push_str(&format!("\n[GROUP] Lemmas {:?}\n", lemmas));
-            if let Some(axioms) = key_to_axioms.get(key) {
-                groups_output.push_str("  Shared axioms:\n");
-                for ax in axioms {
-                    groups_output.push_str(&format!("    - {}\n", ax));
-                }
+    // only real groups (with more than 1 lemma)
+    let groups = groups
+        .into_iter()
+        .filter(|(_, lemmas)| lemmas.len() > 1)
+        .map(|(axioms, lemmas)| StructuralGroup { lemmas, axioms })
+        .collect();
+    (groups, without_axioms)
+}
+
+/// --- Helper Functions ---
+
+/// How a premise of a history lemma was found to refer to a lemma
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Match {
+    /// named `lemma_NNNN`, whatever its role
+    Name,
+    /// alpha-equivalent to a single or history lemma
+    Formula,
+}
+
+impl std::fmt::Display for Match {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Match::Name => write!(f, "by name"),
+            Match::Formula => write!(f, "by formula"),
+        }
+    }
+}
+
+/// The lemma references of one history lemma file
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Substitution {
+    /// (premise, lemma number, match) of the premises replaced by an abstract lemma
+    matched: Vec<(String, u32, Match)>,
+    /// (premise, lemma number) of the premises without a usable abstract lemma
+    unmatched: Vec<(String, u32)>,
+}
+
+/// Replace the premises of a history lemma that refer to lemma N by
+/// abstract lemma N. A premise refers to lemma N if it is named `lemma_N`,
+/// whatever its role, or else if it has the `lemma` role and its formula is
+/// alpha-equivalent to lemma N (`lemma_numbers`) for an N before the history
+/// lemma's own number. Other premises, such as an axiom of the problem that
+/// happens to state a lemma, are left alone.
+fn substitute_abstract_lemmas(
+    file: &mut TptpFile,
+    history_num: u32,
+    abstract_map: &HashMap<u32, String>,
+    lemma_numbers: &FormulaIndex<u32>,
+) -> Substitution {
+    let mut substitution = Substitution::default();
+    for premise in file.formulas_mut().filter(|f| !f.is_conjecture()) {
+        let by_name = premise
+            .name
+            .strip_prefix("lemma_")
+            .and_then(|n| n.parse::<u32>().ok());
+        let reference = match by_name {
+            Some(n) => Some((n, Match::Name)),
+            None if premise.role != "lemma" => None,
+            None => lemma_numbers
+                .lookup_alpha_equivalent(&premise.body_line())
+                .iter()
+                .copied()
+                .filter(|&n| n < history_num)
+                .min()
+                .map(|n| (n, Match::Formula)),
+        };
+        let Some((n, how)) = reference else {
+            continue;
+        };
+        match abstract_map.get(&n) {
+            Some(formula) => {
+                premise.body = formula.clone();
+                substitution.matched.push((premise.name.clone(), n, how));
             }
+            None => substitution.unmatched.push((premise.name.clone(), n)),
         }
     }
+    substitution
+}
 
-    // save the output to structural_groups.txt
-    fs::write(&output_groups_file, groups_output)
-        .expect("Failed to save structural groups to file");
-    println!(
-        "\n[INFO] Structural analysis complete. Groups saved to '{}'.",
-        output_groups_file
-    );
+/// Write a shortened proof to `path`, keeping the proof it replaces as a numbered version
+fn write_versioned_proof(
+    config: &RunConfig,
+    path: &Path,
+    proof: &str,
+    history_file: &str,
+) -> std::io::Result<()> {
+    let notes: Vec<String> = keep_version(path)?
+        .map(|previous| format!("previous version: {}", previous.display()))
+        .into_iter()
+        .collect();
+    write_with_notes(config, path, proof, history_file, &notes)
 }
 
-/// --- Helper Functions ---
+fn history_lemma_file(lemmas_dir: &str, n: u32) -> String {
+    Path::new(lemmas_dir)
+        .join("history")
+        .join(format!("history_lemma_{:04}.p", n))
+        .to_string_lossy()
+        .to_string()
+}
+
+/// The lemma problems the extractor left in `lemmas_dir`, in name order
+fn extracted_lemma_files(lemmas_dir: &str) -> Vec<PathBuf> {
+    let mut extracted: Vec<_> = fs::read_dir(lemmas_dir)
+        .expect("Failed to read lemmas directory")
+        .map(|entry| entry.expect("Failed to read entry").path())
+        .filter(|path| path.extension().map(|ext| ext == "p").unwrap_or(false))
+        .collect();
+    extracted.sort();
+    extracted
+}
+
+/// Single lemma problems read from the Vampire proof `proof` in Rust, for the
+/// `vampire-steps` mode or when the OCaml parser extracts none. The lemmas are
+/// numbered like those of the OCaml parser, over the superposition,
+/// demodulation and resolution steps in proof order, and every unconditional
+/// unit equality among them becomes `single_lemma_<n>.p`: the axioms of the
+/// proof, universally closed, and the lemma as conjecture. A step that cannot
+/// be read is an error: an axiom would be left out of every lemma, a lemma
+/// would be missing from the numbering the OCaml parser would give.
+fn step_lemmas(proof: &str) -> Result<Vec<(String, String)>, String> {
+    let steps = parse_vampire_steps(proof);
+    // the conjecture is an input too, and the negated conjecture points at it
+    let conjectures: HashSet<usize> = steps
+        .iter()
+        .filter(|step| step.role == StepRole::NegatedConjecture)
+        .flat_map(|step| step.premises.iter().copied())
+        .collect();
+    let closed = |step: &VampireStep| {
+        parse_formula(&step.formula)
+            .map(Formula::universal_closure)
+            .map_err(|e| format!("Cannot read step {} of the proof: {}", step.index, e))
+    };
+    let axioms: String = steps
+        .iter()
+        .filter(|step| step.role == StepRole::Input && !conjectures.contains(&step.index))
+        .map(closed)
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .enumerate()
+        .map(|(i, formula)| fof(&format!("a{}", i + 1), "axiom", formula) + "\n")
+        .collect();
+    let extracted = ["superposition", "demodulation", "resolution"];
+    let derived = steps.iter().filter(|step| {
+        step.role == StepRole::Inference && extracted.iter().any(|r| step.rule.contains(r))
+    });
+    let mut lemmas = Vec::new();
+    for (i, step) in derived.enumerate() {
+        if step.formula.contains(" <- ") {
+            continue;
+        }
+        let formula = closed(step)?;
+        if formula.is_unit_equality() {
+            let n = i + 1;
+            let conjecture = fof(&format!("conjecture_{:04}", n), "conjecture", &formula);
+            lemmas.push((
+                format!("single_lemma_{:04}.p", n),
+                axioms.clone() + &conjecture,
+            ));
+        }
+    }
+    Ok(lemmas)
+}
+
+/// Write the single lemmas of the steps of `proof_file` to `lemmas_dir`, returning how many
+fn write_step_lemmas(proof_file: &str, lemmas_dir: &str) -> Result<usize, String> {
+    let proof = fs::read_to_string(proof_file)
+        .map_err(|e| format!("Failed to read {}: {}", proof_file, e))?;
+    let lemmas = step_lemmas(&proof)?;
+    for (name, content) in &lemmas {
+        let path = Path::new(lemmas_dir).join(name);
+        write_atomic(&path, content)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(lemmas.len())
+}
 
 fn run_ocaml_parser(proof_file: &str, mode: &str) -> Result<(), String> {
-    let parser_path = "ocaml_install/tptp_parser".to_string();
+    let parser_path = workspace::ocaml_parser(Path::new(".."));
     let output = std::process::Command::new(parser_path)
         .arg(proof_file)
         .arg(mode)
@@ -289,10 +825,10 @@ fn run_ocaml_parser(proof_file: &str, mode: &str) -> Result<(), String> {
     if !output.status.success() {
         return Err(format!(
             "OCaml parser failed: {}",
-            String::from_utf8_lossy(&output.stderr)
+            decode_output(&output.stderr).0
         ));
     }
-    println!("{}", String::from_utf8_lossy(&output.stdout));
+    println!("{}", decode_output(&output.stdout).0);
     Ok(())
 }
 
@@ -327,3 +863,180 @@ fn extract_axioms(proof_text: &str) -> HashSet<String> {
     }
     set
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ScratchDir;
+
+    #[test]
+    fn step_lemmas_are_the_derived_unit_equalities() {
+        let proof = "1. ! [X0] : mult(e,X0) = X0 [input]\n\
+                     2. ! [X0] : e = mult(inv(X0),X0) [input]\n\
+                     4. b = mult(inv(a),b) [input]\n\
+                     5. b != mult(inv(a),b) [negated conjecture 4]\n\
+                     6. ! [X0] : mult(inv(X0),X0) = mult(e,e) [superposition 2,1]\n\
+                     7. mult(X0,X1) = X1 <- (1) [superposition 6,1]\n\
+                     8. b != b [superposition 5,6]\n\
+                     9. $false [trivial inequality removal 8]\n\
+                     10. ! [X0] : X0 = mult(e,X0) [forward demodulation 6,1]\n";
+        let lemmas = step_lemmas(proof).unwrap();
+        // numbered like the OCaml parser does, over steps 6, 7, 8 and 10
+        assert_eq!(lemmas.len(), 2);
+        assert_eq!(lemmas[1].0, "single_lemma_0004.p");
+        let (name, content) = &lemmas[0];
+        assert_eq!(name, "single_lemma_0001.p");
+        let file = TptpFile::parse(content).unwrap();
+        let names: Vec<&str> = file.formulas().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["a1", "a2", "conjecture_0001"]);
+        assert!(content.contains("mult(inv(X0),X0) = mult(e,e)"));
+    }
+
+    #[test]
+    fn step_lemmas_need_every_axiom() {
+        let proof = "1. ! [X0] : mult(e,X0) = X0 [input]\n\
+                     2. ! [X0] : e = mult(inv(X0,X0) [input]\n\
+                     3. ! [X0] : mult(inv(X0),X0) = mult(e,e) [superposition 2,1]\n";
+        let err = step_lemmas(proof).unwrap_err();
+        assert!(err.contains("step 2"), "{}", err);
+
+        // nor is a derived step dropped, it would be missing from the numbering
+        let proof = "1. ! [X0] : mult(e,X0) = X0 [input]\n\
+                     2. ! [X0] : mult(X0,e = X0 [superposition 1,1]\n\
+                     3. ! [X0] : X0 = mult(e,X0) [forward demodulation 1,1]\n";
+        let err = step_lemmas(proof).unwrap_err();
+        assert!(err.contains("step 2"), "{}", err);
+    }
+
+    #[test]
+    fn lemma_premises_are_matched_by_name_or_formula() {
+        let mut file = TptpFile::parse(
+            "fof(a1, axiom, ! [X] : mult(e, X) = X).\n\
+             fof(lemma_0001, lemma, ! [X] : mult(X, e) = X).\n\
+             fof(a2, axiom, ! [A, B] : mult(A, B) = mult(B, A)).\n\
+             fof(l2, lemma, ! [A, B] : mult(A, B) = mult(B, A)).\n\
+             fof(lemma_0003, axiom, ! [X] : mult(X, X) = e).\n\
+             fof(conjecture_0004, conjecture, ! [X] : mult(X, X) = e).\n",
+        )
+        .unwrap();
+        let abstract_map = HashMap::from([
+            (1, "! [X, Y] : mult(X, Y) = X".to_string()),
+            (2, "! [X, Y, Z] : mult(X, Y) = mult(Y, Z)".to_string()),
+        ]);
+        let mut lemma_numbers = FormulaIndex::new();
+        lemma_numbers.insert("! [X, Y] : mult(X, Y) = mult(Y, X)", 2);
+        lemma_numbers.insert("! [X] : mult(X, X) = e", 4);
+
+        let substitution = substitute_abstract_lemmas(&mut file, 4, &abstract_map, &lemma_numbers);
+
+        assert_eq!(
+            substitution,
+            Substitution {
+                matched: vec![
+                    ("lemma_0001".to_string(), 1, Match::Name),
+                    ("l2".to_string(), 2, Match::Formula),
+                ],
+                unmatched: vec![("lemma_0003".to_string(), 3)],
+            }
+        );
+        let bodies: Vec<&str> = file.formulas().map(|f| f.body.as_str()).collect();
+        assert_eq!(
+            bodies,
+            [
+                "! [X] : mult(e, X) = X",
+                "! [X, Y] : mult(X, Y) = X",
+                "! [A, B] : mult(A, B) = mult(B, A)",
+                "! [X, Y, Z] : mult(X, Y) = mult(Y, Z)",
+                "! [X] : mult(X, X) = e",
+                "! [X] : mult(X, X) = e",
+            ]
+        );
+    }
+
+    #[test]
+    fn lemmas_repeating_an_axiom_or_an_earlier_lemma_are_dropped() {
+        let pairs = |items: &[(&str, &str)]| -> Vec<(String, String)> {
+            items
+                .iter()
+                .map(|(name, formula)| (name.to_string(), formula.to_string()))
+                .collect()
+        };
+        let axioms = pairs(&[("a1", "! [X] : mult(e, X) = X")]);
+        let lemmas = pairs(&[
+            ("single_lemma_0001", "! [Y] : Y = mult(e, Y)"),
+            ("single_lemma_0002", "! [X, Y] : mult(X, Y) = mult(Y, X)"),
+            ("single_lemma_0003", "! [A, B] : mult(B, A) = mult(A, B)"),
+            ("single_lemma_0004", "! [A] : mult(A, A) = A"),
+            // the same lemma from other axioms is an alternative, a repetition
+            // of a lower number in another mode is proved once too
+            ("history_lemma_0002", "! [A, B] : mult(A, B) = mult(B, A)"),
+            ("history_lemma_0003", "! [A, B] : mult(A, B) = mult(B, A)"),
+            ("history_lemma_0005", "! [A] : A = mult(A, A)"),
+        ]);
+        let redundant = redundant_lemmas(&axioms, &lemmas);
+        assert_eq!(
+            redundant,
+            BTreeMap::from([
+                (
+                    "history_lemma_0003".to_string(),
+                    Repeats::Lemma("single_lemma_0002".to_string())
+                ),
+                (
+                    "history_lemma_0005".to_string(),
+                    Repeats::Lemma("single_lemma_0004".to_string())
+                ),
+                (
+                    "single_lemma_0001".to_string(),
+                    Repeats::Axiom("a1".to_string())
+                ),
+                (
+                    "single_lemma_0003".to_string(),
+                    Repeats::Lemma("single_lemma_0002".to_string())
+                ),
+            ])
+        );
+        assert_eq!(
+            redundant["single_lemma_0001"].to_string(),
+            "alpha-equivalent to axiom a1"
+        );
+    }
+
+    #[test]
+    fn repeated_lemma_files_are_removed_and_become_aliases() {
+        let scratch = ScratchDir::new("drop_redundant");
+        let input = scratch.write("problem.p", "fof(a1, axiom, ! [X] : mult(e, X) = X).\n");
+        let lemma = |name: &str, conjecture: &str| {
+            scratch.write(
+                &format!("lemmas/{}.p", name),
+                format!(
+                    "fof(a1, axiom, ! [X] : mult(e, X) = X).\n\nfof(c, conjecture, {}).\n",
+                    conjecture
+                ),
+            )
+        };
+        let axiom = lemma("single_lemma_0001", "! [Y] : mult(e, Y) = Y");
+        let kept = lemma("single_lemma_0002", "! [X, Y] : mult(X, Y) = mult(Y, X)");
+        let alias = lemma("history_lemma_0003", "! [A, B] : mult(B, A) = mult(A, B)");
+        // unreadable lemma files are left to the provers to report
+        let missing = scratch
+            .path()
+            .join("lemmas/single_lemma_0004.p")
+            .to_string_lossy()
+            .to_string();
+
+        let (files, aliases) = drop_redundant_lemmas(
+            &input,
+            vec![axiom.clone(), kept.clone(), alias.clone(), missing.clone()],
+        );
+        assert_eq!(files, vec![kept, missing]);
+        assert_eq!(
+            aliases,
+            BTreeMap::from([(
+                "history_lemma_0003".to_string(),
+                "single_lemma_0002".to_string()
+            )])
+        );
+        assert!(!Path::new(&alias).exists());
+        assert!(Path::new(&axiom).exists());
+    }
+}
@@ -0,0 +1,298 @@
+use crate::artifact::write_atomic;
+use crate::formula::Term;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// Benchmark problems of the equational theories project: "Equation<n> implies
+// Equation<m>" over a magma with one binary operation `◇`, written `op` in
+// TPTP. The equations are numbered in the Lean files of `benchmarks/Equations`
+// (`equation 3957 := x ◇ y = (y ◇ (x ◇ z)) ◇ x`). `generate` writes the
+// problem for two of them in the layout of `python/generate_input.py`, so the
+// new file looks like the ones in `benchmarks/input*`.
+
+pub const EQUATIONS_DIR: &str = "../benchmarks/Equations";
+
+/// An equation with its variables renamed to X0, X1, ... by first occurrence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Equation {
+    pub lhs: Term,
+    pub rhs: Term,
+    pub vars: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Var(String),
+    Op,
+    Eq,
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '◇' | '*' => {
+                chars.next();
+                tokens.push(Token::Op);
+            }
+            '=' => {
+                chars.next();
+                tokens.push(Token::Eq);
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            c if c.is_alphabetic() => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() || c == '_' {
+                        name.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Var(name));
+            }
+            c => return Err(format!("unexpected '{}'", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// variables of the equation in order of first occurrence
+    vars: Vec<String>,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// `◇` associates to the left, as `infixl` in Lean
+    fn term(&mut self) -> Result<Term, String> {
+        let mut term = self.atom()?;
+        while self.tokens.get(self.pos) == Some(&Token::Op) {
+            self.pos += 1;
+            let right = self.atom()?;
+            term = Term::Fun("op".to_string(), vec![term, right]);
+        }
+        Ok(term)
+    }
+
+    fn atom(&mut self) -> Result<Term, String> {
+        match self.next() {
+            Some(Token::Var(name)) => {
+                let index = match self.vars.iter().position(|v| *v == name) {
+                    Some(index) => index,
+                    None => {
+                        self.vars.push(name);
+                        self.vars.len() - 1
+                    }
+                };
+                Ok(Term::Var(format!("X{}", index)))
+            }
+            Some(Token::Open) => {
+                let term = self.term()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(term),
+                    _ => Err("missing ')'".to_string()),
+                }
+            }
+            _ => Err("expected a variable or '('".to_string()),
+        }
+    }
+}
+
+impl Equation {
+    /// Parse an equation in the notation of the Lean files, with `◇` or `*`
+    pub fn parse(text: &str) -> Result<Equation, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text).map_err(|e| format!("Cannot parse '{}': {}", text, e))?,
+            pos: 0,
+            vars: Vec::new(),
+        };
+        let sides = parser.term().and_then(|lhs| match parser.next() {
+            Some(Token::Eq) => Ok((lhs, parser.term()?)),
+            _ => Err("expected '='".to_string()),
+        });
+        let (lhs, rhs) = sides.map_err(|e| format!("Cannot parse '{}': {}", text, e))?;
+        if parser.pos < parser.tokens.len() {
+            return Err(format!(
+                "Cannot parse '{}': unexpected input after the equation",
+                text
+            ));
+        }
+        Ok(Equation {
+            lhs,
+            rhs,
+            vars: parser.vars.len(),
+        })
+    }
+
+    /// `(lhs = rhs)` as in the generated problems
+    fn tptp(&self) -> String {
+        format!("({} = {})", self.lhs, self.rhs)
+    }
+}
+
+/// The numbered equations of the `Eqns*.lean` files in `dir`, also those
+/// commented out there (`-- equation 2 := x = y`)
+pub fn load_equations(dir: &Path) -> Result<BTreeMap<usize, String>, String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut equations = BTreeMap::new();
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().is_none_or(|ext| ext != "lean") {
+            continue;
+        }
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        for line in content.lines() {
+            let line = line.trim().trim_start_matches("--").trim();
+            let Some(rest) = line.strip_prefix("equation ") else {
+                continue;
+            };
+            if let Some((number, text)) = rest.split_once(":=") {
+                if let Ok(number) = number.trim().parse::<usize>() {
+                    equations.insert(number, text.trim().to_string());
+                }
+            }
+        }
+    }
+    Ok(equations)
+}
+
+/// The number of an equation given as `3957` or `Equation3957`
+fn equation_number(arg: &str) -> Option<usize> {
+    arg.strip_prefix("Equation").unwrap_or(arg).parse().ok()
+}
+
+/// The equation an argument stands for, with its number if it has one. A raw
+/// equation gets the number of the listed equation it is, up to renaming.
+fn resolve(
+    arg: &str,
+    equations: &BTreeMap<usize, String>,
+) -> Result<(Option<usize>, Equation), String> {
+    if let Some(number) = equation_number(arg) {
+        let text = equations
+            .get(&number)
+            .ok_or_else(|| format!("There is no equation {}", number))?;
+        return Ok((Some(number), Equation::parse(text)?));
+    }
+    let equation = Equation::parse(arg)?;
+    let number = equations
+        .iter()
+        .find(|(_, text)| Equation::parse(text).is_ok_and(|listed| listed == equation))
+        .map(|(number, _)| *number);
+    Ok((number, equation))
+}
+
+/// The TPTP problem "hypothesis implies goal", both quantified over the
+/// variables of the larger of the two
+pub fn problem(hypothesis: &Equation, goal: &Equation) -> String {
+    let vars = (0..hypothesis.vars.max(goal.vars))
+        .map(|i| format!("X{}", i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "fof(a1, axiom,\n    ! [{}] :\n        {}\n).\n\nfof(conjecture0, conjecture,\n    ! [{}] :\n        {}\n).\n",
+        vars,
+        hypothesis.tptp(),
+        vars,
+        goal.tptp()
+    )
+}
+
+/// Write the problem "hypothesis implies goal" to `output_file`, or to
+/// `Equation<n>_implies_Equation<m>.p` in `output_dir` if both equations are
+/// numbered. Each equation is a number (`3957`, `Equation3957`) or an equation
+/// in Lean notation (`"x ◇ y = (y ◇ (x ◇ z)) ◇ x"`).
+pub fn generate(
+    hypothesis: &str,
+    goal: &str,
+    equations_dir: &str,
+    output_file: Option<&str>,
+    output_dir: &str,
+) -> Result<String, String> {
+    // raw equations do without the list, they are just not numbered then
+    let equations = match load_equations(Path::new(equations_dir)) {
+        Ok(equations) => equations,
+        Err(err) if equation_number(hypothesis).is_none() && equation_number(goal).is_none() => {
+            println!("[WARN] {}", err);
+            BTreeMap::new()
+        }
+        Err(err) => return Err(err),
+    };
+    let (hypothesis_number, hypothesis) = resolve(hypothesis, &equations)?;
+    let (goal_number, goal) = resolve(goal, &equations)?;
+
+    let output_file = match (output_file, hypothesis_number, goal_number) {
+        (Some(file), _, _) => file.to_string(),
+        (None, Some(h), Some(g)) => {
+            fs::create_dir_all(output_dir)
+                .map_err(|e| format!("Failed to create {}: {}", output_dir, e))?;
+            Path::new(output_dir)
+                .join(format!("Equation{}_implies_Equation{}.p", h, g))
+                .to_string_lossy()
+                .to_string()
+        }
+        _ => return Err("Give an output file for equations not in the list".to_string()),
+    };
+    write_atomic(&output_file, problem(&hypothesis, &goal))
+        .map_err(|e| format!("Failed to write {}: {}", output_file, e))?;
+    Ok(format!("[RESULT] Wrote {}", output_file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbered_equations_become_implication_problems() {
+        let dir =
+            std::env::temp_dir().join(format!("frankenstein-generate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("Eqns3000_3999.lean"),
+            "/-! Equations list -/\n\n-- equation 3 := x = x ◇ x\nequation 3957 := x ◇ y = (y ◇ (x ◇ z)) ◇ x\nequation 3971 := x ◇ y = (y ◇ x) ◇ x\n",
+        )
+        .unwrap();
+        let dir_str = dir.to_string_lossy().to_string();
+
+        let msg = generate("3957", "Equation3971", &dir_str, None, &dir_str).unwrap();
+        assert!(msg.contains("Equation3957_implies_Equation3971.p"));
+        let written = fs::read_to_string(dir.join("Equation3957_implies_Equation3971.p")).unwrap();
+        assert_eq!(
+            written,
+            "fof(a1, axiom,\n    ! [X0, X1, X2] :\n        (op(X0,X1) = op(op(X1,op(X0,X2)),X0))\n).\n\nfof(conjecture0, conjecture,\n    ! [X0, X1, X2] :\n        (op(X0,X1) = op(op(X1,X0),X0))\n).\n"
+        );
+
+        // a raw equation is numbered if it is in the list up to renaming, and
+        // `*` and chains without parentheses read as in Lean
+        generate("a = a * a", "u * v = v * u * u", &dir_str, None, &dir_str).unwrap();
+        assert!(dir.join("Equation3_implies_Equation3971.p").is_file());
+        assert!(generate("a = a * b", "3", &dir_str, None, &dir_str).is_err());
+        assert!(Equation::parse("x ◇ = y").is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::alpha_match::FormulaIndex;
+use crate::artifact::write_atomic;
+use crate::formula::{anti_unify, Formula};
+use crate::frankenstein::find_structural_groups;
+use crate::integrity;
+use crate::lemma_store::LemmaStore;
+use crate::proof_step::ProofRole;
+use crate::provenance::write_with_provenance;
+use crate::prover_wrapper::{backend, proof_length, prove_lemmas};
+use crate::run_config::RunConfig;
+use crate::summary::{self, Summary, SummaryEntry};
+use crate::tptp_file::{AnnotatedFormula, TptpFile};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Phase 4: one shared lemma per structural group. The members of a group
+// are proved from the same axioms; the antiunification of their formulas is
+// proved once and given to every member as an axiom, so that their proofs
+// can build on it instead of each repeating the common part.
+
+const PROVERS: [&str; 3] = ["vampire", "twee", "egg"];
+
+/// Where Phase 4 reads and writes its files
+struct Dirs {
+    lemmas: String,
+    /// problems and prover runs
+    work: PathBuf,
+    /// proofs of the shared lemmas and the rewritten member proofs
+    proofs: PathBuf,
+}
+
+/// Phase 4: synthesize, prove and use a shared lemma for each structural group
+pub fn group_minimize(config: &RunConfig, summary_file: &str) {
+    println!("=== Phase 4: Group Minimization ===");
+
+    let proofs_dir = "../proofs".to_string();
+    let report_file = "../output/group_minimization.txt".to_string();
+    let dirs = Dirs {
+        lemmas: "../lemmas".to_string(),
+        work: Path::new("../tmp").join("group"),
+        proofs: Path::new(&proofs_dir).join("group"),
+    };
+
+    if let Err(err) = integrity::verify(&integrity::record_file(summary_file), &dirs.lemmas) {
+        println!("[ERROR] {}", err);
+        return;
+    }
+
+    let summary_data = summary::load(summary_file).expect("Failed to read summary.json");
+    let lemmas = LemmaStore::load(&dirs.lemmas).expect("Failed to read lemmas directory");
+
+    let (groups, _) = find_structural_groups(&summary_data, &proofs_dir);
+    if groups.is_empty() {
+        println!("[INFO] No structural groups, nothing to minimize. Run Phase 1 first.");
+        return;
+    }
+    fs::create_dir_all(&dirs.work).expect("Failed to create ../tmp/group directory");
+    fs::create_dir_all(&dirs.proofs).expect("Failed to create ../proofs/group directory");
+
+    let mut report = String::new();
+    report.push_str("=== Group Minimization ===\n");
+    for (i, group) in groups.iter().enumerate() {
+        let name = format!("group_lemma_{:04}", i + 1);
+        println!("\n[INFO] {}: lemmas {:?}", name, group.lemmas);
+        match minimize_group(config, &name, &group.lemmas, &summary_data, &lemmas, &dirs) {
+            Ok(lines) => report.push_str(&lines),
+            Err(err) => {
+                println!("[WARN] {} skipped: {}", name, err);
+                report.push_str(&format!("\n[SKIPPED] Lemmas {:?}: {}\n", group.lemmas, err));
+            }
+        }
+    }
+
+    write_with_provenance(config, &report_file, &report, summary_file)
+        .expect("Failed to save group minimization report");
+    println!(
+        "\n[INFO] Group minimization complete. Report saved to '{}'.",
+        report_file
+    );
+}
+
+/// Prove the shared lemma `name` of the group `members` and reprove every
+/// member with it. Returns the report lines of the group.
+fn minimize_group(
+    config: &RunConfig,
+    name: &str,
+    members: &[u32],
+    summary_data: &Summary,
+    lemmas: &LemmaStore,
+    dirs: &Dirs,
+) -> Result<String, String> {
+    let entries: Vec<&SummaryEntry> = members.iter().map(|n| &summary_data[n]).collect();
+    let formulas = entries
+        .iter()
+        .map(|entry| {
+            lemmas
+                .get(&entry.file)
+                .cloned()
+                .ok_or_else(|| format!("Missing lemma {}", entry.file))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let shared = shared_lemma(&formulas).ok_or("the members have no common shape")?;
+    println!("[INFO] Shared lemma: {}", shared);
+
+    // the shared lemma follows from the axioms of any member
+    let mut problem = member_problem(lemmas, &entries[0].file)?;
+    problem.retain(|f| !f.is_conjecture());
+    problem.push(AnnotatedFormula::new(
+        "fof",
+        name,
+        "conjecture",
+        &shared.to_string(),
+    ));
+    let problem_file = dirs.work.join(format!("{}.p", name));
+    write_problem(&problem_file, &problem)?;
+    let out_dir = dirs.work.join(format!("{}_proof", name));
+    let (proved, _) = prove_lemmas(
+        config,
+        &[problem_file.to_string_lossy().to_string()],
+        &PROVERS,
+        &out_dir.to_string_lossy(),
+    );
+    let shared_proof = proved
+        .into_values()
+        .find(SummaryEntry::is_proved)
+        .ok_or("the shared lemma was not proved")?;
+    let shared_steps = proof_length(&shared_proof.prover, &shared_proof.proof)?;
+    save_proof(config, dirs, name, &shared_proof, &problem_file)?;
+
+    // every member with the shared lemma as an axiom
+    let member_dir = dirs.work.join(name);
+    fs::create_dir_all(&member_dir)
+        .map_err(|e| format!("Failed to create {}: {}", member_dir.display(), e))?;
+    let mut member_files = Vec::new();
+    for entry in &entries {
+        let mut problem = member_problem(lemmas, &entry.file)?;
+        problem.push(AnnotatedFormula::new(
+            "fof",
+            name,
+            "axiom",
+            &shared.to_string(),
+        ));
+        let file = member_dir.join(format!("{}.p", entry.file));
+        write_problem(&file, &problem)?;
+        member_files.push(file.to_string_lossy().to_string());
+    }
+    let out_dir = dirs.work.join(format!("{}_members", name));
+    let (reproved, _) = prove_lemmas(config, &member_files, &PROVERS, &out_dir.to_string_lossy());
+
+    let mut lines = format!(
+        "\n[GROUP] Lemmas {:?}\n  {}: {}\n  proved by '{}' with {} steps\n",
+        members, name, shared, shared_proof.prover, shared_steps
+    );
+    let (mut before, mut after) = (0, shared_steps);
+    for (&n, entry) in members.iter().zip(&entries) {
+        let old_steps = proof_length(&entry.prover, &entry.proof)?;
+        before += old_steps;
+        let rewritten = reproved
+            .get(&n)
+            .filter(|new| new.is_proved() && uses_lemma(new, name, &shared));
+        match rewritten {
+            Some(new) => {
+                let new_steps = proof_length(&new.prover, &new.proof)?;
+                after += new_steps;
+                let member_file = member_dir.join(format!("{}.p", entry.file));
+                save_proof(config, dirs, &entry.file, new, &member_file)?;
+                lines.push_str(&format!(
+                    "  - {}: {} -> {} steps using {}\n",
+                    entry.file, old_steps, new_steps, name
+                ));
+            }
+            None => {
+                after += old_steps;
+                lines.push_str(&format!(
+                    "  - {}: {} steps, kept (no proof using {})\n",
+                    entry.file, old_steps, name
+                ));
+            }
+        }
+    }
+    println!(
+        "[RESULT] {} for lemmas {:?}: {} -> {} steps in total",
+        name, members, before, after
+    );
+    lines.push_str(&format!("  total: {} -> {} steps\n", before, after));
+    Ok(lines)
+}
+
+/// The most specific lemma all `formulas` are instances of, if they share
+/// more than being equations
+fn shared_lemma(formulas: &[Formula]) -> Option<Formula> {
+    let (first, rest) = formulas.split_first()?;
+    rest.iter().try_fold(first.clone(), |shared, formula| {
+        anti_unify(&shared, formula)
+    })
+}
+
+/// Whether `entry`'s proof starts from the axiom `name` with formula `lemma`
+fn uses_lemma(entry: &SummaryEntry, name: &str, lemma: &Formula) -> bool {
+    let mut index = FormulaIndex::new();
+    index.insert(&lemma.to_string(), ());
+    let Ok(steps) = backend(&entry.prover).and_then(|b| b.steps(&entry.proof)) else {
+        return false;
+    };
+    steps.iter().any(|step| {
+        step.role == ProofRole::Axiom
+            && (step.name == name
+                || !index
+                    .lookup_alpha_equivalent(&step.formula.to_string())
+                    .is_empty())
+    })
+}
+
+/// The problem of lemma `file` as collect wrote it
+fn member_problem(lemmas: &LemmaStore, file: &str) -> Result<TptpFile, String> {
+    let path = lemmas.path(file)?;
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    TptpFile::parse(&content)
+}
+
+fn write_problem(path: &Path, problem: &TptpFile) -> Result<(), String> {
+    write_atomic(path, problem.to_string())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Save the proof of `name` as `<proofs>/<name>_<prover>.proof`
+fn save_proof(
+    config: &RunConfig,
+    dirs: &Dirs,
+    name: &str,
+    entry: &SummaryEntry,
+    problem: &Path,
+) -> Result<(), String> {
+    let path = dirs.proofs.join(format!("{}_{}.proof", name, entry.prover));
+    write_with_provenance(config, &path, &entry.proof, &problem.to_string_lossy())
+        .map_err(|e| format!("Failed to write proof file {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula::parse_formula;
+
+    #[test]
+    fn group_members_share_their_antiunification() {
+        let formulas: Vec<Formula> = [
+            "! [X] : mult(X, mult(inv(X), e)) = e",
+            "! [Y] : mult(e, mult(inv(e), e)) = e",
+            "! [Z] : mult(inv(Z), mult(inv(inv(Z)), e)) = e",
+        ]
+        .iter()
+        .map(|s| parse_formula(s).unwrap())
+        .collect();
+        assert_eq!(
+            shared_lemma(&formulas).unwrap().to_string(),
+            "! [X0] : (mult(X0,mult(inv(X0),e)) = e)"
+        );
+
+        let unrelated = [formulas[0].clone(), parse_formula("inv(e) = e").unwrap()];
+        assert!(shared_lemma(&unrelated).is_none());
+        assert!(shared_lemma(&[]).is_none());
+    }
+}
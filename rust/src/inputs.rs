@@ -0,0 +1,412 @@
+use crate::artifact::{safe_name, write_atomic};
+use flate2::read::MultiGzDecoder;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+// Problem files as benchmarks ship them: plain, compressed with gzip, or
+// bundled in a `.tar`, `.tar.gz`, `.tgz` or `.zip` archive (with the
+// `archives` feature). Archives are not extracted: listing them reads only
+// the member names, and a member is read when the pipeline gets to it. The
+// provers need a file, so a compressed problem or an archive member is
+// written to `output/inputs` then, a member under its path in the archive.
+
+/// Where decompressed problems and archive members are written
+pub const INPUTS_DIR: &str = "../output/inputs";
+
+/// A problem of a benchmark run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputProblem {
+    /// a problem file, possibly compressed with gzip
+    File(PathBuf),
+    /// a problem file `name` inside `archive`, read when it is materialized
+    Member { archive: PathBuf, name: String },
+}
+
+impl InputProblem {
+    /// The problem as the user named it, e.g. `TPTP.tgz:Problems/GRP001-1.p`
+    pub fn label(&self) -> String {
+        match self {
+            InputProblem::File(path) => path.display().to_string(),
+            InputProblem::Member { archive, name, .. } => {
+                format!("{}:{}", archive.display(), name)
+            }
+        }
+    }
+
+    /// A plain problem file the pipeline can run on: the file itself, or its
+    /// decompressed contents written to `inputs_dir`, or the archive member
+    /// written to `inputs_dir/<archive>/<path in the archive>`
+    pub fn materialize(&self, inputs_dir: &Path) -> Result<PathBuf, String> {
+        match self {
+            InputProblem::File(path) if !is_gzip(path) => Ok(path.clone()),
+            InputProblem::File(path) => {
+                let compressed = fs::read(path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                let file_name = path
+                    .file_name()
+                    .ok_or_else(|| format!("{} is not a file", path.display()))?;
+                write_problem(inputs_dir, Path::new(file_name), &compressed)
+            }
+            InputProblem::Member { archive, name } => {
+                let contents = read_member(archive, name).map_err(|e| {
+                    format!("Failed to read {} from {}: {}", name, archive.display(), e)
+                })?;
+                let dir = inputs_dir.join(safe_name(&archive_stem(archive)));
+                write_problem(&dir, &member_path(name)?, &contents)
+            }
+        }
+    }
+}
+
+/// The problems of `input`, in name order: the files of a folder, with the
+/// problems inside its archives in place of the archives, or the problems of
+/// an archive. An archive in the folder that cannot be read, e.g. without the
+/// `archives` feature, is skipped with a warning.
+pub fn problems(input: &Path) -> Result<Vec<InputProblem>, String> {
+    if !input.is_dir() {
+        if input.is_file() && is_archive(input) {
+            return archive_problems(input);
+        }
+        return Err(format!(
+            "Input folder '{}' does not exist or is not a directory or an archive.",
+            input.display()
+        ));
+    }
+    let mut files: Vec<PathBuf> = fs::read_dir(input)
+        .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            path.is_file().then_some(path)
+        })
+        .collect();
+    files.sort();
+    let mut problems = Vec::new();
+    for file in files {
+        if is_archive(&file) {
+            match archive_problems(&file) {
+                Ok(members) => problems.extend(members),
+                Err(err) => println!("[WARN] Skipping {}", err),
+            }
+        } else {
+            problems.push(InputProblem::File(file));
+        }
+    }
+    Ok(problems)
+}
+
+/// The decompressed copy of `input_file` in `output_dir` if it is compressed
+/// with gzip, for the commands that read a single problem
+pub fn decompressed(input_file: &str, output_dir: &str) -> Result<Option<String>, String> {
+    let path = Path::new(input_file);
+    if !is_gzip(path) || !path.is_file() {
+        return Ok(None);
+    }
+    let problem = InputProblem::File(path.to_path_buf()).materialize(Path::new(output_dir))?;
+    println!(
+        "[INFO] Decompressed {} to {}",
+        input_file,
+        problem.display()
+    );
+    Ok(Some(problem.to_string_lossy().to_string()))
+}
+
+fn is_gzip(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(".gz") && !is_archive(path)
+}
+
+fn is_archive(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    [".tar", ".tar.gz", ".tgz", ".zip"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+}
+
+/// `path` without its archive extension, e.g. `TPTP` for `TPTP.tar.gz`
+fn archive_stem(path: &Path) -> String {
+    let name = path.file_name().map_or_else(
+        || "archive".to_string(),
+        |n| n.to_string_lossy().to_string(),
+    );
+    [".tar.gz", ".tgz", ".tar", ".zip"]
+        .iter()
+        .find_map(|ext| name.strip_suffix(ext))
+        .unwrap_or(&name)
+        .to_string()
+}
+
+/// The relative path a member `name` is written to: its path in the archive,
+/// without the parts that would leave the directory (`..`, a leading `/`) and
+/// with every part made safe for a file name
+fn member_path(name: &str) -> Result<PathBuf, String> {
+    let path: PathBuf = Path::new(name)
+        .components()
+        .filter_map(|part| match part {
+            Component::Normal(part) => Some(safe_name(&part.to_string_lossy())),
+            _ => None,
+        })
+        .collect();
+    if path.as_os_str().is_empty() {
+        return Err(format!("Archive member '{}' has no file name", name));
+    }
+    Ok(path)
+}
+
+/// Write `contents`, decompressed if `relative` ends in `.gz`, to `dir`
+/// under `relative` without that extension
+fn write_problem(dir: &Path, relative: &Path, contents: &[u8]) -> Result<PathBuf, String> {
+    let name = relative.to_string_lossy();
+    let (relative, text) = match name.strip_suffix(".gz") {
+        Some(stripped) => {
+            let mut text = Vec::new();
+            MultiGzDecoder::new(contents)
+                .read_to_end(&mut text)
+                .map_err(|e| format!("Failed to decompress {}: {}", name, e))?;
+            (PathBuf::from(stripped), text)
+        }
+        None => (relative.to_path_buf(), contents.to_vec()),
+    };
+    let path = dir.join(relative);
+    let parent = path.parent().unwrap_or(dir);
+    fs::create_dir_all(parent)
+        .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    write_atomic(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Members of an archive taken as problems: `.p` files, possibly compressed,
+/// but no hidden files such as the `._` files macOS adds to archives
+fn is_problem_member(name: &str) -> bool {
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    !file_name.starts_with('.') && (file_name.ends_with(".p") || file_name.ends_with(".p.gz"))
+}
+
+/// The problem members of `archive`, sorted by name. Only the names are read.
+fn archive_problems(archive: &Path) -> Result<Vec<InputProblem>, String> {
+    let mut names: Vec<String> = member_names(archive)
+        .map_err(|e| format!("archive {}: {}", archive.display(), e))?
+        .into_iter()
+        .filter(|name| is_problem_member(name))
+        .collect();
+    names.sort();
+    Ok(names
+        .into_iter()
+        .map(|name| InputProblem::Member {
+            archive: archive.to_path_buf(),
+            name,
+        })
+        .collect())
+}
+
+/// The files of an archive in the order they are stored, `.zip` by its
+/// extension, a tar archive otherwise, compressed with gzip unless `.tar`
+#[cfg(feature = "archives")]
+enum Archive {
+    Tar(tar::Archive<Box<dyn Read>>),
+    Zip(zip::ZipArchive<fs::File>),
+}
+
+#[cfg(feature = "archives")]
+impl Archive {
+    fn open(path: &Path) -> Result<Archive, String> {
+        let file = fs::File::open(path).map_err(|e| e.to_string())?;
+        let name = path.to_string_lossy();
+        if name.ends_with(".zip") {
+            return zip::ZipArchive::new(file)
+                .map(Archive::Zip)
+                .map_err(|e| e.to_string());
+        }
+        let reader: Box<dyn Read> = if name.ends_with(".tar") {
+            Box::new(file)
+        } else {
+            Box::new(MultiGzDecoder::new(file))
+        };
+        Ok(Archive::Tar(tar::Archive::new(reader)))
+    }
+}
+
+/// The names of the files in `archive`, read one entry at a time
+#[cfg(feature = "archives")]
+fn member_names(archive: &Path) -> Result<Vec<String>, String> {
+    match Archive::open(archive)? {
+        Archive::Zip(zip) => Ok(zip
+            .file_names()
+            .filter(|name| !name.ends_with('/'))
+            .map(str::to_string)
+            .collect()),
+        Archive::Tar(mut tar) => {
+            let mut names = Vec::new();
+            for entry in tar.entries().map_err(|e| e.to_string())? {
+                let entry = entry.map_err(|e| e.to_string())?;
+                if entry.header().entry_type().is_file() {
+                    let path = entry.path().map_err(|e| e.to_string())?;
+                    names.push(path.to_string_lossy().to_string());
+                }
+            }
+            Ok(names)
+        }
+    }
+}
+
+/// The contents of the file `name` in `archive`; a tar archive is read up to it
+#[cfg(feature = "archives")]
+fn read_member(archive: &Path, name: &str) -> Result<Vec<u8>, String> {
+    let mut contents = Vec::new();
+    match Archive::open(archive)? {
+        Archive::Zip(mut zip) => {
+            let mut member = zip.by_name(name).map_err(|e| e.to_string())?;
+            member
+                .read_to_end(&mut contents)
+                .map_err(|e| e.to_string())?;
+        }
+        Archive::Tar(mut tar) => {
+            let mut found = false;
+            for entry in tar.entries().map_err(|e| e.to_string())? {
+                let mut entry = entry.map_err(|e| e.to_string())?;
+                if entry.path().map_err(|e| e.to_string())?.to_string_lossy() == name {
+                    entry
+                        .read_to_end(&mut contents)
+                        .map_err(|e| e.to_string())?;
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                return Err("no such member".to_string());
+            }
+        }
+    }
+    Ok(contents)
+}
+
+#[cfg(not(feature = "archives"))]
+fn member_names(_archive: &Path) -> Result<Vec<String>, String> {
+    Err("this build reads no archives; build with `--features archives`".to_string())
+}
+
+#[cfg(not(feature = "archives"))]
+fn read_member(_archive: &Path, _name: &str) -> Result<Vec<u8>, String> {
+    Err("this build reads no archives; build with `--features archives`".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ScratchDir;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    const PROBLEM: &str = "fof(c, conjecture, a = a).\n";
+
+    fn gzip(text: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn gzip_problems_are_decompressed() {
+        let scratch = ScratchDir::new("inputs_gzip");
+        let dir = scratch.path();
+        fs::write(dir.join("b.p"), PROBLEM).unwrap();
+        fs::write(dir.join("a.p.gz"), gzip(PROBLEM)).unwrap();
+
+        let found = problems(dir).unwrap();
+        assert_eq!(
+            found,
+            [
+                InputProblem::File(dir.join("a.p.gz")),
+                InputProblem::File(dir.join("b.p"))
+            ]
+        );
+        let inputs = dir.join("inputs");
+        let a = found[0].materialize(&inputs).unwrap();
+        assert_eq!(a, inputs.join("a.p"));
+        assert_eq!(fs::read_to_string(&a).unwrap(), PROBLEM);
+        // plain files are used where they are
+        assert_eq!(found[1].materialize(&inputs).unwrap(), dir.join("b.p"));
+
+        let input = dir.join("a.p.gz").to_string_lossy().to_string();
+        let out = dir.join("single").to_string_lossy().to_string();
+        let single = decompressed(&input, &out).unwrap().unwrap();
+        assert_eq!(fs::read_to_string(single).unwrap(), PROBLEM);
+        let plain = dir.join("b.p").to_string_lossy().to_string();
+        assert_eq!(decompressed(&plain, &out).unwrap(), None);
+
+        fs::write(dir.join("broken.p.gz"), "not gzip").unwrap();
+        let broken = dir.join("broken.p.gz").to_string_lossy().to_string();
+        assert!(decompressed(&broken, &out).is_err());
+        assert!(problems(&dir.join("missing")).is_err());
+    }
+
+    #[cfg(feature = "archives")]
+    #[test]
+    fn archive_members_are_problems() {
+        let scratch = ScratchDir::new("inputs_tar");
+        let dir = scratch.path();
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, contents) in [
+            ("Problems/GRP002-1.p", PROBLEM.as_bytes().to_vec()),
+            ("Problems/GRP001-1.p.gz", gzip(PROBLEM)),
+            ("Problems/._GRP001-1.p", Vec::new()),
+            ("Axioms/GRP004-0.ax", PROBLEM.as_bytes().to_vec()),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            builder
+                .append_data(&mut header, name, contents.as_slice())
+                .unwrap();
+        }
+        fs::write(dir.join("TPTP.tar"), builder.into_inner().unwrap()).unwrap();
+        fs::write(dir.join("c.p"), PROBLEM).unwrap();
+
+        let found = problems(dir).unwrap();
+        let labels: Vec<String> = found.iter().map(InputProblem::label).collect();
+        assert_eq!(labels.len(), 3);
+        assert!(labels[0].ends_with("TPTP.tar:Problems/GRP001-1.p.gz"));
+        assert!(labels[1].ends_with("TPTP.tar:Problems/GRP002-1.p"));
+        assert!(labels[2].ends_with("c.p"));
+        // nothing is extracted until a member is run
+        assert!(!dir.join("inputs").exists());
+        let first = found[0].materialize(&dir.join("inputs")).unwrap();
+        // members keep their path in the archive
+        assert_eq!(first, dir.join("inputs/TPTP/Problems").join("GRP001-1.p"));
+        assert_eq!(fs::read_to_string(first).unwrap(), PROBLEM);
+        let second = found[1].materialize(&dir.join("inputs")).unwrap();
+        assert_eq!(fs::read_to_string(second).unwrap(), PROBLEM);
+
+        // an archive can be the input itself
+        assert_eq!(problems(&dir.join("TPTP.tar")).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn members_stay_below_their_archive_directory() {
+        assert_eq!(
+            member_path("Problems/GRP/GRP001-1.p").unwrap(),
+            Path::new("Problems/GRP/GRP001-1.p")
+        );
+        assert_eq!(
+            member_path("/../../etc/x y.p").unwrap(),
+            Path::new("etc").join(safe_name("x y.p"))
+        );
+        assert!(member_path("../").is_err());
+    }
+
+    #[cfg(not(feature = "archives"))]
+    #[test]
+    fn archives_need_the_feature() {
+        let scratch = ScratchDir::new("inputs_no_archives");
+        let dir = scratch.path();
+        fs::write(dir.join("TPTP.zip"), "").unwrap();
+        fs::write(dir.join("a.p"), PROBLEM).unwrap();
+        // archives in a folder are skipped, the other problems are run
+        assert_eq!(
+            problems(dir).unwrap(),
+            [InputProblem::File(dir.join("a.p"))]
+        );
+        let err = problems(&dir.join("TPTP.zip")).unwrap_err();
+        assert!(err.contains("--features archives"), "{}", err);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::artifact::{self, write_atomic};
+use crate::lemma_store::LemmaKind;
+use crate::workspace::sha256_file;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// The phases of a problem run as separate processes and hand their results
+// over through the workspace: `collect` leaves the Vampire proof, the summary
+// and the lemma files for `shorten`, `group` and `minimize`. `collect` records
+// the SHA-256 of each of them in `artifacts_<suffix>.json`, next to the
+// summary, and the later phases check them before reading anything, so a
+// file left over from another problem or another run is reported instead of
+// being mixed in.
+
+/// Content hashes of the artifacts of a problem
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Artifacts {
+    /// the phase that recorded the hashes last, e.g. "collect"
+    pub phase: String,
+    /// path -> hex SHA-256
+    pub files: BTreeMap<String, String>,
+    /// lemma file relative to the lemmas directory -> hex SHA-256, so the
+    /// directory may be given another way later
+    pub lemmas: BTreeMap<String, String>,
+}
+
+/// Where the hashes of the problem with summary `summary_file` are kept:
+/// `artifacts_<suffix>.json` for `summary_<suffix>.json`
+pub fn record_file(summary_file: &str) -> PathBuf {
+    let path = Path::new(summary_file);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let suffix = name.strip_prefix("summary_").unwrap_or(&name);
+    path.with_file_name(format!("artifacts_{}", suffix))
+}
+
+/// The lemma files below `lemmas_dir`, in name order
+fn lemma_files(lemmas_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for kind in LemmaKind::ALL {
+        let dir = lemmas_dir.join(kind.dir_name());
+        if !dir.is_dir() {
+            continue;
+        }
+        let entries =
+            fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path.extension().is_some_and(|ext| ext == "p") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+impl Artifacts {
+    /// Hash `files` and every lemma file below `lemmas_dir`
+    fn hash(phase: &str, files: &[PathBuf], lemmas_dir: &str) -> Result<Artifacts, String> {
+        let mut artifacts = Artifacts {
+            phase: phase.to_string(),
+            ..Artifacts::default()
+        };
+        for file in files {
+            let hash = sha256_file(file)?;
+            artifacts
+                .files
+                .insert(file.to_string_lossy().to_string(), hash);
+        }
+        let lemmas_dir = Path::new(lemmas_dir);
+        for file in lemma_files(lemmas_dir)? {
+            let name = file.strip_prefix(lemmas_dir).unwrap_or(&file);
+            let hash = sha256_file(&file)?;
+            artifacts
+                .lemmas
+                .insert(name.to_string_lossy().to_string(), hash);
+        }
+        Ok(artifacts)
+    }
+
+    pub fn load(path: &Path) -> Result<Artifacts, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        artifact::parse_json(&text)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize the artifact hashes: {}", e))?;
+        write_atomic(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// The first artifact that differs between `self` and `now`, and how
+    fn changed(&self, now: &Artifacts) -> Option<(String, &'static str)> {
+        changed(&self.files, &now.files).or_else(|| changed(&self.lemmas, &now.lemmas))
+    }
+}
+
+/// The first file of `recorded` that is missing from or differs in `now`, or
+/// the first file only in `now`
+fn changed(
+    recorded: &BTreeMap<String, String>,
+    now: &BTreeMap<String, String>,
+) -> Option<(String, &'static str)> {
+    for (path, hash) in recorded {
+        match now.get(path) {
+            None => return Some((path.clone(), "was removed")),
+            Some(current) if current != hash => return Some((path.clone(), "was changed")),
+            Some(_) => {}
+        }
+    }
+    now.keys()
+        .find(|path| !recorded.contains_key(*path))
+        .map(|path| (path.clone(), "was added"))
+}
+
+/// Record the hashes of `files` and of the lemma files below `lemmas_dir`,
+/// as handed over by `phase`
+pub fn record(
+    record_file: &Path,
+    phase: &str,
+    files: &[PathBuf],
+    lemmas_dir: &str,
+) -> Result<(), String> {
+    Artifacts::hash(phase, files, lemmas_dir)?.write(record_file)
+}
+
+/// Record the hashes again after `phase` rewrote some of the recorded files
+pub fn rerecord(record_file: &Path, phase: &str, lemmas_dir: &str) -> Result<(), String> {
+    let recorded = Artifacts::load(record_file)?;
+    let files: Vec<PathBuf> = recorded.files.keys().map(PathBuf::from).collect();
+    record(record_file, phase, &files, lemmas_dir)
+}
+
+/// Check the artifacts recorded in `record_file` against the workspace. A
+/// problem without hashes, collected before they were recorded, is not checked.
+pub fn verify(record_file: &Path, lemmas_dir: &str) -> Result<(), String> {
+    if !record_file.exists() {
+        println!(
+            "[WARN] No artifact hashes in {}, not checking the artifacts of the problem",
+            record_file.display()
+        );
+        return Ok(());
+    }
+    let recorded = Artifacts::load(record_file)?;
+    let files: Vec<PathBuf> = recorded
+        .files
+        .keys()
+        .map(PathBuf::from)
+        .filter(|path| path.exists())
+        .collect();
+    let now = Artifacts::hash(&recorded.phase, &files, lemmas_dir)?;
+    match recorded.changed(&now) {
+        Some((path, how)) => Err(format!(
+            "Stale artifact {}: it {} after `{}` ran, re-run collect",
+            path, how, recorded.phase
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn artifacts_changed_after_collect_are_stale() {
+        let root =
+            std::env::temp_dir().join(format!("frankenstein-integrity-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let lemmas_dir = root.join("lemmas").to_string_lossy().to_string();
+        fs::create_dir_all(root.join("lemmas").join("single")).unwrap();
+        let lemma = root
+            .join("lemmas")
+            .join("single")
+            .join("single_lemma_0001.p");
+        fs::write(&lemma, "fof(conjecture_0001, conjecture, a = b).\n").unwrap();
+        let summary = root.join("summary_p1.json");
+        fs::write(&summary, "{}").unwrap();
+        let record_file = record_file(&summary.to_string_lossy());
+        assert_eq!(record_file, root.join("artifacts_p1.json"));
+
+        // nothing recorded yet, as for problems collected before the hashes
+        assert!(verify(&record_file, &lemmas_dir).is_ok());
+
+        record(&record_file, "collect", &[summary.clone()], &lemmas_dir).unwrap();
+        assert!(verify(&record_file, &lemmas_dir).is_ok());
+
+        fs::write(&summary, "{\"1\": {}}").unwrap();
+        let err = verify(&record_file, &lemmas_dir).unwrap_err();
+        assert!(err.contains("summary_p1.json"));
+        assert!(err.contains("re-run collect"));
+
+        // a phase that rewrites artifacts records them again
+        rerecord(&record_file, "shorten", &lemmas_dir).unwrap();
+        assert!(verify(&record_file, &lemmas_dir).is_ok());
+
+        let other = root
+            .join("lemmas")
+            .join("single")
+            .join("single_lemma_0002.p");
+        fs::write(&other, "fof(conjecture_0002, conjecture, a = c).\n").unwrap();
+        let err = verify(&record_file, &lemmas_dir).unwrap_err();
+        assert!(err.contains("single_lemma_0002.p") && err.contains("was added"));
+        fs::remove_file(&other).unwrap();
+
+        fs::remove_file(&summary).unwrap();
+        let err = verify(&record_file, &lemmas_dir).unwrap_err();
+        assert!(err.contains("was removed"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
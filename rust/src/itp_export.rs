@@ -0,0 +1,1070 @@
+use crate::annotated_proof::ProofSection;
+use crate::artifact::{self, write_atomic};
+use crate::formula::{parse_formula, Formula, Term};
+use crate::lemma_store::LemmaStore;
+use crate::naming::NameAllocator;
+use crate::tptp_file::TptpFile;
+use crate::twee_proof::{parse_twee_proof, Direction, TweeKind};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+// Export of a minimized proof to a proof assistant: every Twee rewrite chain
+// becomes a Lean 4 `calc` block or an Isabelle `also ... finally` chain, each
+// step justified by the axiom or lemma it rewrote with, instantiated and put
+// into its context by congruence. Sections no Twee chain proves (Vampire and
+// egg proofs, axioms Twee was given) are stated with `sorry`.
+
+/// Proof assistant an exported proof is written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Lean,
+    Isabelle,
+}
+
+impl Target {
+    pub fn parse(name: &str) -> Result<Target, String> {
+        match name {
+            "lean" => Ok(Target::Lean),
+            "isabelle" => Ok(Target::Isabelle),
+            _ => Err(format!(
+                "Unknown export target '{}', expected lean or isabelle",
+                name
+            )),
+        }
+    }
+
+    fn keywords(self) -> &'static [&'static str] {
+        match self {
+            Target::Lean => &[
+                "at", "by", "calc", "do", "else", "end", "exact", "from", "fun", "have", "if",
+                "in", "intro", "let", "match", "open", "show", "sorry", "then", "theorem", "with",
+                "Prop", "Type",
+            ],
+            Target::Isabelle => &[
+                "ALL", "EX", "O", "also", "and", "assumes", "begin", "by", "case", "end",
+                "finally", "fix", "fixes", "for", "from", "have", "if", "in", "is", "let", "o",
+                "obtains", "of", "proof", "qed", "show", "shows", "sorry", "then", "using",
+                "where", "with",
+            ],
+        }
+    }
+}
+
+/// Variable standing for the rewritten position in the context of a step
+const HOLE: &str = "hole'";
+
+/// An equation the export can rewrite with: `! [vars] : lhs = rhs`
+#[derive(Debug, Clone)]
+struct Equation {
+    name: String,
+    vars: Vec<String>,
+    lhs: Term,
+    rhs: Term,
+}
+
+/// One application of an equation: `args` instantiate its variables (none
+/// for a variable the step does not fix), and `context` is the term around
+/// the rewritten position, with `HOLE` at it
+#[derive(Debug, Clone)]
+struct Rewrite {
+    equation: String,
+    args: Vec<Option<Term>>,
+    /// used right to left
+    symmetric: bool,
+    context: Option<Term>,
+}
+
+#[derive(Debug, Clone)]
+struct Step {
+    to: Term,
+    /// none if no position of the step is an instance of the equation Twee named
+    by: Option<Rewrite>,
+}
+
+#[derive(Debug, Clone)]
+enum Proof {
+    /// rewrite chain from the left-hand side of the statement
+    Chain(Vec<Step>),
+    /// not reconstructed, with the reason
+    Sorry(String),
+}
+
+#[derive(Debug, Clone)]
+struct Statement {
+    name: String,
+    vars: Vec<String>,
+    body: Formula,
+    proof: Proof,
+}
+
+/// How the conjecture follows from the last statements
+#[derive(Debug, Clone)]
+enum Conclusion {
+    /// the conjecture is an instance of an equation
+    Instance(Rewrite),
+    Sorry(String),
+}
+
+/// A minimized proof ready to print: the axioms of the problem, the statements
+/// proved on the way in order, and the conjecture
+#[derive(Debug, Clone)]
+pub struct Export {
+    name: String,
+    hypotheses: Vec<(String, Formula)>,
+    conjecture: Formula,
+    statements: Vec<Statement>,
+    conclusion: Conclusion,
+}
+
+#[derive(Deserialize)]
+struct SectionsFile {
+    sections: Vec<ProofSection>,
+}
+
+/// Leading universal quantifiers of a formula and the formula under them
+fn strip_forall(formula: &Formula) -> (Vec<String>, &Formula) {
+    let mut vars = Vec::new();
+    let mut body = formula;
+    while let Formula::Forall(vs, inner) = body {
+        vars.extend(vs.iter().cloned());
+        body = inner;
+    }
+    (vars, body)
+}
+
+fn term_vars(term: &Term, out: &mut Vec<String>) {
+    match term {
+        Term::Var(v) => {
+            if !out.contains(v) {
+                out.push(v.clone());
+            }
+        }
+        Term::Fun(_, args) => args.iter().for_each(|a| term_vars(a, out)),
+    }
+}
+
+/// `lhs = rhs` with variables renamed in order of occurrence, to find an
+/// equation up to renaming
+fn shape(lhs: &Term, rhs: &Term) -> String {
+    fn rename(term: &Term, names: &mut Vec<String>) -> String {
+        match term {
+            Term::Var(v) => {
+                let i = names.iter().position(|n| n == v).unwrap_or_else(|| {
+                    names.push(v.clone());
+                    names.len() - 1
+                });
+                format!("V{}", i)
+            }
+            Term::Fun(f, args) if args.is_empty() => f.clone(),
+            Term::Fun(f, args) => format!(
+                "{}({})",
+                f,
+                args.iter()
+                    .map(|a| rename(a, names))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        }
+    }
+    let mut names = Vec::new();
+    let lhs = rename(lhs, &mut names);
+    format!("{} = {}", lhs, rename(rhs, &mut names))
+}
+
+/// Extend `subst` so that `pattern` instantiated by it is `term`
+fn matches(pattern: &Term, term: &Term, subst: &mut HashMap<String, Term>) -> bool {
+    match (pattern, term) {
+        (Term::Var(v), _) => match subst.get(v) {
+            Some(bound) => bound == term,
+            None => {
+                subst.insert(v.clone(), term.clone());
+                true
+            }
+        },
+        (Term::Fun(f, fargs), Term::Fun(g, gargs)) => {
+            f == g
+                && fargs.len() == gargs.len()
+                && fargs.iter().zip(gargs).all(|(p, t)| matches(p, t, subst))
+        }
+        _ => false,
+    }
+}
+
+fn subterm<'a>(term: &'a Term, path: &[usize]) -> &'a Term {
+    match (term, path.split_first()) {
+        (Term::Fun(_, args), Some((&i, rest))) => subterm(&args[i], rest),
+        _ => term,
+    }
+}
+
+fn replace(term: &Term, path: &[usize], with: &Term) -> Term {
+    match (term, path.split_first()) {
+        (Term::Fun(f, args), Some((&i, rest))) => {
+            let mut args = args.clone();
+            args[i] = replace(&args[i], rest, with);
+            Term::Fun(f.clone(), args)
+        }
+        _ => with.clone(),
+    }
+}
+
+/// Path to the innermost subterm outside of which `from` and `to` agree
+fn differing_path(from: &Term, to: &Term) -> Vec<usize> {
+    let mut path = Vec::new();
+    let (mut from, mut to) = (from, to);
+    while let (Term::Fun(f, fargs), Term::Fun(g, gargs)) = (from, to) {
+        if f != g || fargs.len() != gargs.len() {
+            break;
+        }
+        let mut differing = (0..fargs.len()).filter(|&i| fargs[i] != gargs[i]);
+        match (differing.next(), differing.next()) {
+            (Some(i), None) => {
+                path.push(i);
+                from = &fargs[i];
+                to = &gargs[i];
+            }
+            _ => break,
+        }
+    }
+    path
+}
+
+/// `from = to` as an instance of `equation`
+fn instance(from: &Term, to: &Term, equation: &Equation, symmetric: bool) -> Option<Rewrite> {
+    let (source, target) = if symmetric {
+        (&equation.rhs, &equation.lhs)
+    } else {
+        (&equation.lhs, &equation.rhs)
+    };
+    let mut subst = HashMap::new();
+    let fits = matches(source, from, &mut subst) && matches(target, to, &mut subst);
+    fits.then(|| Rewrite {
+        equation: equation.name.clone(),
+        args: equation
+            .vars
+            .iter()
+            .map(|v| subst.get(v).cloned())
+            .collect(),
+        symmetric,
+        context: None,
+    })
+}
+
+/// The rewrite turning `from` into `to` with `equation`, at the innermost
+/// position where that works
+fn rewrite(from: &Term, to: &Term, equation: &Equation, symmetric: bool) -> Option<Rewrite> {
+    let path = differing_path(from, to);
+    (0..=path.len()).rev().find_map(|len| {
+        let position = &path[..len];
+        let rewrite = instance(
+            subterm(from, position),
+            subterm(to, position),
+            equation,
+            symmetric,
+        )?;
+        Some(Rewrite {
+            context: (len > 0).then(|| replace(from, position, &Term::Var(HOLE.to_string()))),
+            ..rewrite
+        })
+    })
+}
+
+/// What the export knows so far: the equations it may rewrite with, newest last
+#[derive(Default)]
+struct Known {
+    equations: Vec<Equation>,
+}
+
+impl Known {
+    /// Add `formula` under `name` if it is an equation; free variables count as quantified
+    fn add(&mut self, name: &str, formula: &Formula) {
+        let (mut vars, body) = strip_forall(formula);
+        if let Formula::Eq(lhs, rhs) = body {
+            term_vars(lhs, &mut vars);
+            term_vars(rhs, &mut vars);
+            self.equations.push(Equation {
+                name: name.to_string(),
+                vars,
+                lhs: lhs.clone(),
+                rhs: rhs.clone(),
+            });
+        }
+    }
+
+    /// The newest equation that is `lhs = rhs` up to renaming, and whether it
+    /// states it the other way round
+    fn find(&self, lhs: &Term, rhs: &Term) -> Option<(&Equation, bool)> {
+        let (wanted, flipped) = (shape(lhs, rhs), shape(rhs, lhs));
+        self.equations.iter().rev().find_map(|eq| {
+            let known = shape(&eq.lhs, &eq.rhs);
+            if known == wanted {
+                Some((eq, false))
+            } else if known == flipped {
+                Some((eq, true))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl Export {
+    /// Build the export of the minimized proof with `sections` of the problem
+    /// `problem` (TPTP text). Lemmas proved by other provers are read from `lemmas`.
+    pub fn build(
+        name: &str,
+        problem: &str,
+        sections: &[ProofSection],
+        lemmas: &LemmaStore,
+    ) -> Result<Export, String> {
+        let mut names = NameAllocator::for_problem(problem)?;
+        let mut known = Known::default();
+        let mut hypotheses = Vec::new();
+        let mut conjecture = None;
+        for formula in TptpFile::parse(problem)?.formulas() {
+            let parsed = parse_formula(&formula.body_line())
+                .map_err(|e| format!("Cannot parse {}: {}", formula.name, e))?
+                .universal_closure();
+            if formula.is_conjecture() {
+                conjecture = Some(parsed);
+            } else {
+                known.add(&formula.name, &parsed);
+                hypotheses.push((formula.name.clone(), parsed));
+            }
+        }
+        let conjecture = conjecture.ok_or("The problem has no conjecture")?;
+
+        let mut statements = Vec::new();
+        for section in sections {
+            let prefix = section
+                .lemma
+                .clone()
+                .unwrap_or_else(|| section.kind.name().to_string());
+            if section.prover == "twee" {
+                twee_statements(section, &prefix, &mut names, &mut known, &mut statements)?;
+            } else if let Some(lemma) = &section.lemma {
+                let formula = lemmas.get(lemma).cloned().ok_or_else(|| {
+                    format!("Missing lemma {}, proved by {}", lemma, section.prover)
+                })?;
+                let name = names.fresh(lemma);
+                let (vars, body) = strip_forall(&formula);
+                known.add(&name, &formula);
+                statements.push(Statement {
+                    name,
+                    vars,
+                    body: body.clone(),
+                    proof: Proof::Sorry(format!(
+                        "proved by {} in {} steps",
+                        section.prover, section.steps
+                    )),
+                });
+            }
+        }
+
+        let conclusion = match strip_forall(&conjecture) {
+            (_, Formula::Eq(lhs, rhs)) => known
+                .find(lhs, rhs)
+                .and_then(|(eq, flipped)| instance(lhs, rhs, eq, flipped))
+                .map(Conclusion::Instance)
+                .unwrap_or_else(|| Conclusion::Sorry("no statement is the conjecture".to_string())),
+            _ => Conclusion::Sorry("the conjecture is not an equation".to_string()),
+        };
+        Ok(Export {
+            name: name.to_string(),
+            hypotheses,
+            conjecture,
+            statements,
+            conclusion,
+        })
+    }
+
+    pub fn render(&self, target: Target) -> String {
+        match target {
+            Target::Lean => self.render_lean(),
+            Target::Isabelle => self.render_isabelle(),
+        }
+    }
+
+    /// Function and predicate symbols with their arities
+    fn signature(&self) -> (BTreeMap<String, usize>, BTreeMap<String, usize>) {
+        fn term(t: &Term, functions: &mut BTreeMap<String, usize>) {
+            if let Term::Fun(f, args) = t {
+                functions.entry(f.clone()).or_insert(args.len());
+                args.iter().for_each(|a| term(a, functions));
+            }
+        }
+        fn formula(
+            f: &Formula,
+            functions: &mut BTreeMap<String, usize>,
+            predicates: &mut BTreeMap<String, usize>,
+        ) {
+            match f {
+                Formula::Atom(Term::Fun(p, args)) => {
+                    predicates.entry(p.clone()).or_insert(args.len());
+                    args.iter().for_each(|a| term(a, functions));
+                }
+                Formula::Atom(t) => term(t, functions),
+                Formula::Eq(l, r) | Formula::Neq(l, r) => {
+                    term(l, functions);
+                    term(r, functions);
+                }
+                Formula::Not(x) => formula(x, functions, predicates),
+                Formula::And(fs) | Formula::Or(fs) => {
+                    fs.iter().for_each(|x| formula(x, functions, predicates))
+                }
+                Formula::Implies(a, b) | Formula::Iff(a, b) => {
+                    formula(a, functions, predicates);
+                    formula(b, functions, predicates);
+                }
+                Formula::Forall(_, body) | Formula::Exists(_, body) => {
+                    formula(body, functions, predicates)
+                }
+            }
+        }
+        let (mut functions, mut predicates) = (BTreeMap::new(), BTreeMap::new());
+        let formulas = self
+            .hypotheses
+            .iter()
+            .map(|(_, f)| f)
+            .chain([&self.conjecture])
+            .chain(self.statements.iter().map(|s| &s.body));
+        for f in formulas {
+            formula(f, &mut functions, &mut predicates);
+        }
+        // a step may pass through a constant the statements do not mention
+        for statement in &self.statements {
+            if let Proof::Chain(steps) = &statement.proof {
+                steps.iter().for_each(|s| term(&s.to, &mut functions));
+            }
+        }
+        (functions, predicates)
+    }
+
+    fn render_lean(&self) -> String {
+        let id = |name: &str| ident(name, Target::Lean);
+        let (functions, predicates) = self.signature();
+        let mut binders: Vec<String> = functions
+            .iter()
+            .map(|(f, &arity)| format!("({} : {})", id(f), lean_type(arity, "α")))
+            .collect();
+        binders.extend(
+            predicates
+                .iter()
+                .map(|(p, &arity)| format!("({} : {})", id(p), lean_type(arity, "Prop"))),
+        );
+
+        let mut text = format!(
+            "-- Minimized proof of {}, exported by frankenstein\n\ntheorem {} {{α : Type _}}\n",
+            self.name,
+            id(&format!("proof_{}", self.name))
+        );
+        if !binders.is_empty() {
+            text.push_str(&format!("    {}\n", binders.join(" ")));
+        }
+        for (name, formula) in &self.hypotheses {
+            text.push_str(&format!("    ({} : {})\n", id(name), lean_formula(formula)));
+        }
+        text.push_str(&format!("    : {} := by\n", lean_formula(&self.conjecture)));
+
+        for statement in &self.statements {
+            let vars: Vec<String> = statement.vars.iter().map(|v| id(v)).collect();
+            let stated = Formula::Forall(statement.vars.clone(), Box::new(statement.body.clone()));
+            let stated = if vars.is_empty() {
+                &statement.body
+            } else {
+                &stated
+            };
+            text.push_str(&format!(
+                "  have {} : {} := ",
+                id(&statement.name),
+                lean_formula(stated)
+            ));
+            match (&statement.proof, &statement.body) {
+                (Proof::Chain(steps), Formula::Eq(lhs, _)) => {
+                    text.push_str("by\n");
+                    if !vars.is_empty() {
+                        text.push_str(&format!("    intro {}\n", vars.join(" ")));
+                    }
+                    if steps.is_empty() {
+                        text.push_str("    rfl\n");
+                    }
+                    for (i, step) in steps.iter().enumerate() {
+                        let proof = step.by.as_ref().map_or("sorry".to_string(), lean_rewrite);
+                        if i == 0 {
+                            text.push_str(&format!(
+                                "    calc {} = {} := {}\n",
+                                lean_term(lhs),
+                                lean_term(&step.to),
+                                proof
+                            ));
+                        } else {
+                            text.push_str(&format!(
+                                "      _ = {} := {}\n",
+                                lean_term(&step.to),
+                                proof
+                            ));
+                        }
+                    }
+                }
+                (Proof::Sorry(reason), _) => {
+                    text.push_str(&format!("sorry -- {}\n", reason));
+                }
+                (Proof::Chain(_), _) => text.push_str("sorry\n"),
+            }
+        }
+
+        let (vars, _) = strip_forall(&self.conjecture);
+        if !vars.is_empty() {
+            let vars: Vec<String> = vars.iter().map(|v| id(v)).collect();
+            text.push_str(&format!("  intro {}\n", vars.join(" ")));
+        }
+        match &self.conclusion {
+            Conclusion::Instance(r) => text.push_str(&format!("  exact {}\n", lean_rewrite(r))),
+            Conclusion::Sorry(reason) => text.push_str(&format!("  sorry -- {}\n", reason)),
+        }
+        text
+    }
+
+    fn render_isabelle(&self) -> String {
+        let id = |name: &str| ident(name, Target::Isabelle);
+        let (functions, predicates) = self.signature();
+        let mut fixes: Vec<String> = functions
+            .iter()
+            .map(|(f, &arity)| format!("{} :: \"{}\"", id(f), isabelle_type(arity, "'a")))
+            .collect();
+        fixes.extend(
+            predicates
+                .iter()
+                .map(|(p, &arity)| format!("{} :: \"{}\"", id(p), isabelle_type(arity, "bool"))),
+        );
+
+        let mut text = format!(
+            "theory {}\n  imports Main\nbegin\n\n(* Minimized proof of {}, exported by frankenstein *)\n\ntheorem {}:\n",
+            theory_name(&self.name),
+            self.name,
+            id(&format!("proof_{}", self.name))
+        );
+        if !fixes.is_empty() {
+            text.push_str(&format!("  fixes {}\n", fixes.join(" and ")));
+        }
+        let assumptions: Vec<String> = self
+            .hypotheses
+            .iter()
+            .map(|(name, formula)| format!("{}: \"{}\"", id(name), isabelle_formula(formula)))
+            .collect();
+        if !assumptions.is_empty() {
+            text.push_str(&format!("  assumes {}\n", assumptions.join("\n    and ")));
+        }
+        text.push_str(&format!(
+            "  shows \"{}\"\nproof -\n",
+            isabelle_formula(&self.conjecture)
+        ));
+
+        for statement in &self.statements {
+            let vars: Vec<String> = statement.vars.iter().map(|v| id(v)).collect();
+            let stated = Formula::Forall(statement.vars.clone(), Box::new(statement.body.clone()));
+            let stated = if vars.is_empty() {
+                &statement.body
+            } else {
+                &stated
+            };
+            text.push_str(&format!(
+                "  have {}: \"{}\"",
+                id(&statement.name),
+                isabelle_formula(stated)
+            ));
+            match (&statement.proof, &statement.body) {
+                (Proof::Chain(steps), Formula::Eq(lhs, rhs)) if !steps.is_empty() => {
+                    if vars.is_empty() {
+                        text.push_str("\n  proof -\n");
+                    } else {
+                        text.push_str(&format!(
+                            "\n  proof (intro allI)\n    fix {}\n",
+                            vars.join(" ")
+                        ));
+                    }
+                    for (i, step) in steps.iter().enumerate() {
+                        let from = if i == 0 {
+                            isabelle_term(lhs)
+                        } else {
+                            "...".to_string()
+                        };
+                        let proof = step
+                            .by
+                            .as_ref()
+                            .map_or("sorry".to_string(), isabelle_rewrite);
+                        text.push_str(&format!(
+                            "    {}have \"{} = {}\" {}\n",
+                            if i == 0 { "" } else { "also " },
+                            from,
+                            isabelle_term(&step.to),
+                            proof
+                        ));
+                    }
+                    text.push_str(&format!(
+                        "    finally show \"{} = {}\" .\n  qed\n",
+                        isabelle_term(lhs),
+                        isabelle_term(rhs)
+                    ));
+                }
+                (Proof::Chain(_), _) => text.push_str(" by simp\n"),
+                (Proof::Sorry(reason), _) => {
+                    text.push_str(&format!(" sorry (* {} *)\n", reason));
+                }
+            }
+        }
+
+        match &self.conclusion {
+            Conclusion::Instance(r) => {
+                let intro = if strip_forall(&self.conjecture).0.is_empty() {
+                    ""
+                } else {
+                    "intro allI, "
+                };
+                text.push_str(&format!(
+                    "  show ?thesis by ({}{})\n",
+                    intro,
+                    isabelle_rule(r)
+                ));
+            }
+            Conclusion::Sorry(reason) => {
+                text.push_str(&format!("  show ?thesis sorry (* {} *)\n", reason));
+            }
+        }
+        text.push_str("qed\n\nend\n");
+        text
+    }
+}
+
+/// Statements of the Twee proof of `section`: the lemmas and goals with
+/// their rewrite chains, and the axioms Twee was given that are not known yet
+fn twee_statements(
+    section: &ProofSection,
+    prefix: &str,
+    names: &mut NameAllocator,
+    known: &mut Known,
+    statements: &mut Vec<Statement>,
+) -> Result<(), String> {
+    // Twee's name -> known equation, and whether Twee states it the other way round
+    let mut local: HashMap<String, (Equation, bool)> = HashMap::new();
+    for twee in parse_twee_proof(&section.proof)? {
+        let Formula::Eq(lhs, rhs) = &twee.formula else {
+            return Err(format!(
+                "{} of the {} proof is not an equation",
+                twee.name, prefix
+            ));
+        };
+        if twee.kind == TweeKind::Axiom {
+            if known.find(lhs, rhs).is_none() {
+                let name = names.fresh(&format!("{}_{}", prefix, twee.name));
+                let mut vars = Vec::new();
+                term_vars(lhs, &mut vars);
+                term_vars(rhs, &mut vars);
+                known.add(&name, &twee.formula);
+                statements.push(Statement {
+                    name,
+                    vars,
+                    body: twee.formula.clone(),
+                    proof: Proof::Sorry(format!("given to Twee in the {} proof", prefix)),
+                });
+            }
+            let (equation, flipped) = known.find(lhs, rhs).expect("the axiom was just added");
+            local.insert(twee.name.clone(), (equation.clone(), flipped));
+            continue;
+        }
+
+        let start = twee
+            .rewrites
+            .first()
+            .map_or(lhs, |r| match &r.equation {
+                Formula::Eq(from, _) => from,
+                _ => lhs,
+            })
+            .clone();
+        let mut from = start.clone();
+        let mut steps = Vec::new();
+        for r in &twee.rewrites {
+            let Formula::Eq(_, to) = &r.equation else {
+                continue;
+            };
+            let by = local.get(&r.used).and_then(|(equation, flipped)| {
+                let symmetric = *flipped != (r.direction == Direction::RightToLeft);
+                rewrite(&from, to, equation, symmetric)
+            });
+            if by.is_none() {
+                println!(
+                    "[WARN] Step to {} in {} of the {} proof is not a rewrite with {}",
+                    to, twee.name, prefix, r.used
+                );
+            }
+            steps.push(Step { to: to.clone(), by });
+            from = to.clone();
+        }
+        let end = if steps.is_empty() { rhs.clone() } else { from };
+
+        let preferred = match twee.kind {
+            TweeKind::Goal => prefix.to_string(),
+            _ => format!("{}_{}", prefix, twee.name),
+        };
+        let mut vars = Vec::new();
+        term_vars(&start, &mut vars);
+        term_vars(&end, &mut vars);
+        for step in &steps {
+            term_vars(&step.to, &mut vars);
+        }
+        let equation = Equation {
+            name: names.fresh(&preferred),
+            vars: vars.clone(),
+            lhs: start,
+            rhs: end,
+        };
+        statements.push(Statement {
+            name: equation.name.clone(),
+            vars,
+            body: Formula::Eq(equation.lhs.clone(), equation.rhs.clone()),
+            proof: Proof::Chain(steps),
+        });
+        known.equations.push(equation.clone());
+        local.insert(twee.name.clone(), (equation, false));
+    }
+    Ok(())
+}
+
+/// A name the target accepts: other characters become `_`, a leading digit
+/// gets a letter and keywords a prime
+fn ident(name: &str, target: Target) -> String {
+    if name == HOLE {
+        return name.to_string();
+    }
+    let mut id: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !id.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        id.insert(0, 'h');
+    }
+    if target.keywords().contains(&id.as_str()) {
+        id.push('\'');
+    }
+    id
+}
+
+/// `f a (g b)` in both Lean and Isabelle
+fn application(term: &Term, target: Target) -> String {
+    match term {
+        Term::Var(v) => ident(v, target),
+        Term::Fun(f, args) if args.is_empty() => ident(f, target),
+        Term::Fun(f, args) => {
+            let mut text = ident(f, target);
+            for arg in args {
+                text.push(' ');
+                text.push_str(&argument(arg, target));
+            }
+            text
+        }
+    }
+}
+
+fn argument(term: &Term, target: Target) -> String {
+    match term {
+        Term::Fun(_, args) if !args.is_empty() => format!("({})", application(term, target)),
+        _ => application(term, target),
+    }
+}
+
+fn lean_term(term: &Term) -> String {
+    application(term, Target::Lean)
+}
+
+fn isabelle_term(term: &Term) -> String {
+    application(term, Target::Isabelle)
+}
+
+fn lean_type(arity: usize, result: &str) -> String {
+    let mut parts = vec!["α"; arity];
+    parts.push(result);
+    parts.join(" → ")
+}
+
+fn isabelle_type(arity: usize, result: &str) -> String {
+    let mut parts = vec!["'a"; arity];
+    parts.push(result);
+    parts.join(" => ")
+}
+
+/// Connectives of a target
+struct Syntax {
+    not: &'static str,
+    and: &'static str,
+    or: &'static str,
+    implies: &'static str,
+    iff: &'static str,
+    neq: &'static str,
+}
+
+fn formula_text(f: &Formula, target: Target) -> String {
+    let syntax = match target {
+        Target::Lean => Syntax {
+            not: "¬",
+            and: " ∧ ",
+            or: " ∨ ",
+            implies: " → ",
+            iff: " ↔ ",
+            neq: " ≠ ",
+        },
+        Target::Isabelle => Syntax {
+            not: "~ ",
+            and: " & ",
+            or: " | ",
+            implies: " --> ",
+            iff: " <-> ",
+            neq: " ~= ",
+        },
+    };
+    let operand = |f: &Formula| match f {
+        Formula::Atom(_) | Formula::Eq(..) | Formula::Neq(..) => formula_text(f, target),
+        _ => format!("({})", formula_text(f, target)),
+    };
+    let quantifier = |symbol: &str, vars: &[String], body: &Formula| {
+        let vars: Vec<String> = vars.iter().map(|v| ident(v, target)).collect();
+        match target {
+            Target::Lean => format!(
+                "{} {} : α, {}",
+                symbol,
+                vars.join(" "),
+                formula_text(body, target)
+            ),
+            Target::Isabelle => format!(
+                "{} {}. {}",
+                symbol,
+                vars.join(" "),
+                formula_text(body, target)
+            ),
+        }
+    };
+    let join = |fs: &[Formula], op: &str| fs.iter().map(operand).collect::<Vec<_>>().join(op);
+    match f {
+        Formula::Atom(t) => application(t, target),
+        Formula::Eq(l, r) => format!("{} = {}", application(l, target), application(r, target)),
+        Formula::Neq(l, r) => format!(
+            "{}{}{}",
+            application(l, target),
+            syntax.neq,
+            application(r, target)
+        ),
+        Formula::Not(x) => match x.as_ref() {
+            Formula::Atom(_) => format!("{}{}", syntax.not, formula_text(x, target)),
+            _ => format!("{}({})", syntax.not, formula_text(x, target)),
+        },
+        Formula::And(fs) => join(fs, syntax.and),
+        Formula::Or(fs) => join(fs, syntax.or),
+        Formula::Implies(a, b) => format!("{}{}{}", operand(a), syntax.implies, operand(b)),
+        Formula::Iff(a, b) => format!("{}{}{}", operand(a), syntax.iff, operand(b)),
+        Formula::Forall(vars, body) => match target {
+            Target::Lean => quantifier("∀", vars, body),
+            Target::Isabelle => quantifier("ALL", vars, body),
+        },
+        Formula::Exists(vars, body) => match target {
+            Target::Lean => quantifier("∃", vars, body),
+            Target::Isabelle => quantifier("EX", vars, body),
+        },
+    }
+}
+
+fn lean_formula(f: &Formula) -> String {
+    formula_text(f, Target::Lean)
+}
+
+fn isabelle_formula(f: &Formula) -> String {
+    formula_text(f, Target::Isabelle)
+}
+
+/// Proof term of a rewrite step: the instantiated equation, turned around
+/// if used right to left, under `congrArg` if the step rewrites inside a term
+fn lean_rewrite(r: &Rewrite) -> String {
+    let mut instance = ident(&r.equation, Target::Lean);
+    for arg in &r.args {
+        instance.push(' ');
+        match arg {
+            Some(t) => instance.push_str(&argument(t, Target::Lean)),
+            None => instance.push('_'),
+        }
+    }
+    let parenthesized = |text: String| {
+        if text.contains(' ') {
+            format!("({})", text)
+        } else {
+            text
+        }
+    };
+    if r.symmetric {
+        instance = format!("{}.symm", parenthesized(instance));
+    }
+    match &r.context {
+        Some(context) => format!(
+            "congrArg (fun {} => {}) {}",
+            HOLE,
+            lean_term(context),
+            parenthesized(instance)
+        ),
+        None => instance,
+    }
+}
+
+/// Isabelle methods proving a rewrite step
+fn isabelle_rule(r: &Rewrite) -> String {
+    let mut attributes = "rule_format".to_string();
+    if r.symmetric {
+        attributes.push_str(", symmetric");
+    }
+    let rule = format!(
+        "rule {}[{}]",
+        ident(&r.equation, Target::Isabelle),
+        attributes
+    );
+    match &r.context {
+        Some(context) => format!(
+            "rule arg_cong[where f = \"%{}. {}\"], {}",
+            HOLE,
+            isabelle_term(context),
+            rule
+        ),
+        None => rule,
+    }
+}
+
+fn isabelle_rewrite(r: &Rewrite) -> String {
+    format!("by ({})", isabelle_rule(r))
+}
+
+/// File name of the export of problem `name`; Isabelle wants the theory named after it
+fn file_name(name: &str, target: Target) -> String {
+    match target {
+        Target::Lean => format!("proof_{}.lean", name),
+        Target::Isabelle => format!("{}.thy", theory_name(name)),
+    }
+}
+
+fn theory_name(name: &str) -> String {
+    ident(&format!("Proof_{}", name), Target::Isabelle)
+}
+
+/// Write the minimized proof of `input_file`, described by `sections_file`
+/// (`output/proof_<problem>.json`), for `target` to `output_dir`
+pub fn export_proof(
+    input_file: &str,
+    sections_file: &str,
+    lemmas_dir: &str,
+    target: Target,
+    output_dir: &str,
+) -> Result<String, String> {
+    let problem = fs::read_to_string(input_file)
+        .map_err(|e| format!("Failed to read {}: {}", input_file, e))?;
+    let json = fs::read_to_string(sections_file).map_err(|e| {
+        format!(
+            "Failed to read {} (run minimize first): {}",
+            sections_file, e
+        )
+    })?;
+    let sections = artifact::parse_json::<SectionsFile>(&json)
+        .map_err(|e| format!("Failed to parse {}: {}", sections_file, e))?
+        .sections;
+    let lemmas = LemmaStore::load(lemmas_dir)?;
+
+    let name = crate::extract_suffix(input_file);
+    let export = Export::build(&name, &problem, &sections, &lemmas)?;
+    let output_file = Path::new(output_dir).join(file_name(&name, target));
+    write_atomic(&output_file, export.render(target))
+        .map_err(|e| format!("Failed to write {}: {}", output_file.display(), e))?;
+
+    let sorries = export
+        .statements
+        .iter()
+        .filter(|s| matches!(s.proof, Proof::Sorry(_)))
+        .count();
+    Ok(format!(
+        "[RESULT] Exported {} statement(s) to {} ({} left as sorry)",
+        export.statements.len(),
+        output_file.display(),
+        sorries
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotated_proof::SectionKind;
+
+    const PROBLEM: &str = "fof(a1, axiom, ! [X] : mult(e, X) = X).\nfof(a2, axiom, ! [X] : mult(X, e) = X).\nfof(goal, conjecture, mult(mult(e, e), b) = b).\n";
+
+    const TWEE_PROOF: &str = "\
+Axiom 1 (a1): mult(e, X) = X.
+
+Lemma 2: e = mult(e, e).
+Proof:
+  e
+= { by axiom 1 (a1) R->L }
+  mult(e, e)
+
+Goal 1 (goal): mult(mult(e, e), b) = b.
+Proof:
+  mult(mult(e, e), b)
+= { by lemma 2 R->L }
+  mult(e, b)
+= { by axiom 1 (a1) }
+  b
+
+RESULT: Theorem (the conjecture is true).
+";
+
+    fn export() -> Export {
+        let sections = vec![ProofSection {
+            kind: SectionKind::Conjecture,
+            lemma: None,
+            prover: "twee".to_string(),
+            steps: 3,
+            proof: TWEE_PROOF.to_string(),
+        }];
+        Export::build("test", PROBLEM, &sections, &LemmaStore::default()).unwrap()
+    }
+
+    #[test]
+    fn rewrite_chains_become_calc_blocks() {
+        let lean = export().render(Target::Lean);
+        assert!(
+            lean.contains("    (a1 : ∀ X : α, mult e X = X)\n"),
+            "{}",
+            lean
+        );
+        assert!(
+            lean.contains("  have conjecture_lemma_2 : e = mult e e := by\n    calc e = mult e e := (a1 e).symm\n"),
+            "{}",
+            lean
+        );
+        // the lemma is used inside the term, right to left
+        assert!(
+            lean.contains("    calc mult (mult e e) b = mult e b := congrArg (fun hole' => mult hole' b) conjecture_lemma_2.symm\n      _ = b := a1 b\n"),
+            "{}",
+            lean
+        );
+        assert!(lean.ends_with("  exact conjecture\n"), "{}", lean);
+    }
+
+    #[test]
+    fn rewrite_chains_become_isar_chains() {
+        let isabelle = export().render(Target::Isabelle);
+        assert!(isabelle.starts_with("theory Proof_test\n"), "{}", isabelle);
+        assert!(
+            isabelle.contains("    have \"mult (mult e e) b = mult e b\" by (rule arg_cong[where f = \"%hole'. mult hole' b\"], rule conjecture_lemma_2[rule_format, symmetric])\n    also have \"... = b\" by (rule a1[rule_format])\n    finally show \"mult (mult e e) b = b\" .\n"),
+            "{}",
+            isabelle
+        );
+        assert!(
+            isabelle.ends_with("  show ?thesis by (rule conjecture[rule_format])\nqed\n\nend\n"),
+            "{}",
+            isabelle
+        );
+    }
+}
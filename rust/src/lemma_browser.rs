@@ -0,0 +1,224 @@
+use crate::dag::load_dag;
+use crate::lemma_store::LemmaStore;
+use crate::prover_wrapper::proof_length;
+use crate::summary::{self, LemmaStatus, Summary, SummaryEntry};
+use crate::utils::{lemma_index, select_actual_lemma};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+
+type Dag = BTreeMap<String, BTreeSet<String>>;
+
+fn load_summary(summary_file: &str) -> Result<Summary, String> {
+    let text = fs::read_to_string(summary_file)
+        .map_err(|e| format!("Failed to read {} (run collect first): {}", summary_file, e))?;
+    summary::parse(&text).map_err(|e| format!("Failed to parse {}: {}", summary_file, e))
+}
+
+/// `12 steps`, or what the prover said if the lemma is not proved
+fn describe_length(entry: &SummaryEntry) -> String {
+    if entry.is_proved() {
+        match proof_length(&entry.prover, &entry.proof) {
+            Ok(steps) => format!("{} steps", steps),
+            Err(e) => e,
+        }
+    } else {
+        "not proved".to_string()
+    }
+}
+
+/// The DAG of the minimized proof, empty if minimize has not run
+fn load_dag_if_present(dag_file: &str) -> Dag {
+    if Path::new(dag_file).exists() {
+        load_dag(dag_file)
+    } else {
+        Dag::new()
+    }
+}
+
+/// Lemma file name for `name`: a bare number or `lemma_NNNN` is the lemma that
+/// collect kept for that index, anything else is taken as is
+fn resolve_name(name: &str, summary: &Summary) -> String {
+    let index = name
+        .strip_prefix("lemma_")
+        .unwrap_or(name)
+        .parse::<u32>()
+        .ok();
+    match index.and_then(|n| summary.get(&n)) {
+        Some(entry) => entry.file.clone(),
+        None => name.to_string(),
+    }
+}
+
+/// `lemmas list`: every lemma collect kept, with its prover and proof length.
+/// Lemmas used by the minimized proof are marked with `*`.
+pub fn list_lemmas(summary_file: &str, dag_file: &str, lemmas_dir: &str) -> Result<(), String> {
+    let summary = load_summary(summary_file)?;
+    let dag = load_dag_if_present(dag_file);
+    let lemmas = LemmaStore::load(lemmas_dir)?;
+    let in_dag = |name: &str| dag.contains_key(name) || dag.values().any(|c| c.contains(name));
+
+    for entry in summary.values() {
+        let file = &entry.file;
+        let formula = lemmas.formula(file).unwrap_or_else(|_| "?".to_string());
+        println!(
+            "{} {:<22} {:<8} {:>10}  {}",
+            if in_dag(file.as_str()) { "*" } else { " " },
+            file,
+            entry.prover,
+            describe_length(entry),
+            formula
+        );
+    }
+    Ok(())
+}
+
+/// `lemmas show <name>`: formula, prover, proof length and proof of a lemma
+pub fn show_lemma(
+    name: &str,
+    summary_file: &str,
+    lemmas_dir: &str,
+    proofs_dir: &str,
+) -> Result<(), String> {
+    let summary = load_summary(summary_file)?;
+    let lemmas = LemmaStore::load(lemmas_dir)?;
+    let name = lemmas.canonical(&resolve_name(name, &summary));
+
+    let formula = lemmas.formula(&name)?;
+    let entry = match summary.values().find(|entry| entry.file == name) {
+        Some(entry) => entry.clone(),
+        None => {
+            // a lemma collect did not keep: use whatever proof the proofs directory has
+            let actual = select_actual_lemma(proofs_dir, &name)
+                .filter(|actual| actual != &name)
+                .ok_or_else(|| format!("No proof found for {}", name))?;
+            let prover = actual.rsplit('_').next().unwrap_or("").to_string();
+            let path = Path::new(proofs_dir).join(format!("{}.proof", actual));
+            let proof = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            SummaryEntry {
+                file: name.clone(),
+                prover,
+                proof,
+                status: LemmaStatus::Proved,
+                usefulness: None,
+            }
+        }
+    };
+
+    println!("Lemma:   {}", name);
+    println!("Formula: {}", formula);
+    println!("Prover:  {}", entry.prover);
+    println!("Length:  {}", describe_length(&entry));
+    println!("\n{}", entry.proof.trim_end());
+    Ok(())
+}
+
+/// `lemmas why <name>`: how a lemma is reached from the root of the minimized proof
+pub fn trace_lemma(
+    name: &str,
+    summary_file: &str,
+    dag_file: &str,
+    lemmas_dir: &str,
+) -> Result<(), String> {
+    let summary = load_summary(summary_file)?;
+    let name = LemmaStore::load(lemmas_dir)?.canonical(&resolve_name(name, &summary));
+    let dag = load_dag_if_present(dag_file);
+    if dag.is_empty() {
+        return Err(format!("No DAG at {} (run minimize first)", dag_file));
+    }
+
+    match dependency_chain(&dag, &name) {
+        Some(chain) => {
+            println!("{}", chain.join(" -> "));
+            Ok(())
+        }
+        None => Err(format!("{} is not used by the minimized proof", name)),
+    }
+}
+
+/// Shortest chain from a root of `dag` (a node that is nobody's child) down to `name`
+fn dependency_chain(dag: &Dag, name: &str) -> Option<Vec<String>> {
+    let children: BTreeSet<&String> = dag.values().flatten().collect();
+    let mut roots: Vec<&String> = dag.keys().filter(|k| !children.contains(k)).collect();
+    roots.sort_by_key(|r| lemma_index(r));
+
+    let mut parent: HashMap<&str, &str> = HashMap::new();
+    let mut queue: VecDeque<&str> = roots.iter().map(|r| r.as_str()).collect();
+    let mut seen: BTreeSet<&str> = queue.iter().copied().collect();
+    while let Some(node) = queue.pop_front() {
+        if node == name {
+            let mut chain = vec![node.to_string()];
+            let mut current = node;
+            while let Some(&p) = parent.get(current) {
+                chain.push(p.to_string());
+                current = p;
+            }
+            chain.reverse();
+            return Some(chain);
+        }
+        for child in dag.get(node).into_iter().flatten() {
+            if seen.insert(child.as_str()) {
+                parent.insert(child.as_str(), node);
+                queue.push_back(child.as_str());
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dag(edges: &[(&str, &[&str])]) -> Dag {
+        edges
+            .iter()
+            .map(|(p, cs)| (p.to_string(), cs.iter().map(|c| c.to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn chain_from_root() {
+        let dag = dag(&[
+            ("single_lemma_0005", &["history_lemma_0003", "a1"]),
+            ("history_lemma_0003", &["single_lemma_0001"]),
+            ("single_lemma_0001", &["a2"]),
+        ]);
+        assert_eq!(
+            dependency_chain(&dag, "single_lemma_0001").unwrap(),
+            vec![
+                "single_lemma_0005",
+                "history_lemma_0003",
+                "single_lemma_0001"
+            ]
+        );
+        assert_eq!(
+            dependency_chain(&dag, "single_lemma_0005").unwrap(),
+            vec!["single_lemma_0005"]
+        );
+        assert!(dependency_chain(&dag, "single_lemma_0002").is_none());
+    }
+
+    #[test]
+    fn numbers_resolve_through_the_summary() {
+        let mut summary = Summary::new();
+        summary.insert(
+            3,
+            SummaryEntry {
+                file: "abstract_lemma_0003".to_string(),
+                prover: "twee".to_string(),
+                proof: String::new(),
+                status: LemmaStatus::Proved,
+                usefulness: None,
+            },
+        );
+        assert_eq!(resolve_name("3", &summary), "abstract_lemma_0003");
+        assert_eq!(resolve_name("lemma_0003", &summary), "abstract_lemma_0003");
+        assert_eq!(
+            resolve_name("single_lemma_0003", &summary),
+            "single_lemma_0003"
+        );
+        assert_eq!(resolve_name("7", &summary), "7");
+    }
+}
@@ -0,0 +1,293 @@
+use crate::alpha_match::canonical_form;
+use crate::artifact::{self, write_atomic};
+use crate::formula::parse_formula;
+use crate::lemma_store::LemmaStore;
+use crate::proof_step::{proof_steps, ProofRole};
+use crate::prover_wrapper::proof_length;
+use crate::summary::{self, Summary};
+use crate::tptp_file::TptpFile;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+// Optional library of proved lemmas shared by the problems of a benchmark
+// run (`--lemma-library <file>`). `collect` adds the lemmas it proved with
+// the signature and axioms of their problem; `minimize` offers the lemmas of
+// other problems with the same signature as candidate axioms, as long as
+// they follow from its own axioms, and a proof that uses one says which
+// problem it comes from. Only lemmas proved from the axioms alone are kept,
+// so that the proof of a lemma is all a borrowing problem needs.
+
+/// Function symbols and constants of a problem with their arities, sorted
+pub type Signature = Vec<(String, usize)>;
+
+/// A lemma proved for one problem of the run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LibraryLemma {
+    /// problem the lemma was proved for, e.g. "Equation650_implies_Equation448"
+    pub problem: String,
+    /// lemma problem of the proof, e.g. "single_lemma_0003"
+    pub lemma: String,
+    /// canonical form: no quantifier, variables renamed V0, V1, ...
+    pub formula: String,
+    pub signature: Signature,
+    /// canonical forms of the axioms the lemma was proved from
+    pub axioms: Vec<String>,
+    pub prover: String,
+    pub steps: usize,
+    pub proof: String,
+}
+
+impl LibraryLemma {
+    /// Name of the lemma as an axiom of another problem
+    pub fn axiom_name(&self) -> String {
+        let problem: String = self
+            .problem
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!("library_{}_{}", problem, self.lemma)
+    }
+
+    /// The proof of the lemma, headed by where it comes from
+    pub fn attributed_proof(&self) -> String {
+        format!(
+            "% cross-problem dependency: {} is {} of problem {}\n{}",
+            self.axiom_name(),
+            self.lemma,
+            self.problem,
+            self.proof
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct LemmaLibrary {
+    pub lemmas: Vec<LibraryLemma>,
+}
+
+impl LemmaLibrary {
+    /// The library at `path`, empty if there is no file yet
+    pub fn load(path: &str) -> Result<LemmaLibrary, String> {
+        if !Path::new(path).exists() {
+            return Ok(LemmaLibrary::default());
+        }
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        artifact::parse_json(&text).map_err(|e| format!("Failed to parse {}: {}", path, e))
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize the lemma library: {}", e))?;
+        write_atomic(path, json).map_err(|e| format!("Failed to write {}: {}", path, e))
+    }
+
+    /// Add `lemma` unless the library has a lemma with the same formula,
+    /// signature and axioms already. Returns whether it was added.
+    pub fn add(&mut self, lemma: LibraryLemma) -> bool {
+        let known = self.lemmas.iter().any(|l| {
+            l.formula == lemma.formula && l.signature == lemma.signature && l.axioms == lemma.axioms
+        });
+        if !known {
+            self.lemmas.push(lemma);
+        }
+        !known
+    }
+
+    /// The lemmas of other problems than `problem` with the same signature,
+    /// proved from a subset of `axioms`, so that they hold in `problem` too
+    pub fn offered(
+        &self,
+        problem: &str,
+        signature: &Signature,
+        axioms: &[String],
+    ) -> Vec<&LibraryLemma> {
+        self.lemmas
+            .iter()
+            .filter(|l| l.problem != problem && &l.signature == signature)
+            .filter(|l| l.axioms.iter().all(|axiom| axioms.contains(axiom)))
+            .collect()
+    }
+}
+
+/// The signature of the TPTP problem `problem`, and the canonical forms of its axioms
+pub fn theory(problem: &str) -> Result<(Signature, Vec<String>), String> {
+    let mut symbols = BTreeSet::new();
+    let mut axioms = Vec::new();
+    for formula in TptpFile::parse(problem)?.formulas() {
+        let body = formula.body_line();
+        symbols.extend(parse_formula(&body)?.symbols());
+        if !formula.is_conjecture() {
+            axioms.push(canonical_form(&body));
+        }
+    }
+    axioms.sort();
+    Ok((symbols.into_iter().collect(), axioms))
+}
+
+/// Whether every axiom the proof of a lemma uses is one of `axioms` (canonical
+/// forms), rather than another lemma of its problem whose proof it would need.
+/// Proofs whose steps cannot be read are not known to be.
+fn proved_from(prover: &str, proof: &str, axioms: &[String]) -> bool {
+    proof_steps(prover, proof).is_ok_and(|steps| {
+        steps
+            .iter()
+            .filter(|step| step.role == ProofRole::Axiom)
+            .all(|step| axioms.contains(&canonical_form(&step.formula.to_string())))
+    })
+}
+
+/// Add the lemmas proved for `problem` (in `summary_data`, with their formulas
+/// in `lemmas`) to the library at `path`. Lemmas with symbols outside the
+/// problem's signature, e.g. Skolem constants, mean nothing to other problems
+/// and are left out, and so are lemmas proved with the help of other lemmas.
+/// Returns how many lemmas were new.
+pub fn add_problem(
+    path: &str,
+    problem: &str,
+    problem_text: &str,
+    summary_data: &Summary,
+    lemmas: &LemmaStore,
+) -> Result<usize, String> {
+    let (signature, axioms) = theory(problem_text)?;
+    let mut library = LemmaLibrary::load(path)?;
+    let mut added = 0;
+    for (_, entry) in summary::proved(summary_data) {
+        let Some(formula) = lemmas.get(&entry.file) else {
+            continue;
+        };
+        if !formula
+            .symbols()
+            .iter()
+            .all(|symbol| signature.contains(symbol))
+        {
+            continue;
+        }
+        if !proved_from(&entry.prover, &entry.proof, &axioms) {
+            continue;
+        }
+        let lemma = LibraryLemma {
+            problem: problem.to_string(),
+            lemma: entry.file.clone(),
+            formula: canonical_form(&formula.to_string()),
+            signature: signature.clone(),
+            axioms: axioms.clone(),
+            prover: entry.prover.clone(),
+            steps: proof_length(&entry.prover, &entry.proof)?,
+            proof: entry.proof.clone(),
+        };
+        if library.add(lemma) {
+            added += 1;
+        }
+    }
+    library.write(path)?;
+    Ok(added)
+}
+
+/// The library lemmas offered to `problem`, none without `--lemma-library`
+pub fn offered_for(
+    path: Option<&str>,
+    problem: &str,
+    problem_text: &str,
+) -> Result<Vec<LibraryLemma>, String> {
+    let Some(path) = path else {
+        return Ok(Vec::new());
+    };
+    let library = LemmaLibrary::load(path)?;
+    let (signature, axioms) = theory(problem_text)?;
+    Ok(library
+        .offered(problem, &signature, &axioms)
+        .into_iter()
+        .cloned()
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lemma(problem: &str, formula: &str, theory: &(Signature, Vec<String>)) -> LibraryLemma {
+        LibraryLemma {
+            problem: problem.to_string(),
+            lemma: "single_lemma_0003".to_string(),
+            formula: canonical_form(formula),
+            signature: theory.0.clone(),
+            axioms: theory.1.clone(),
+            prover: "twee".to_string(),
+            steps: 2,
+            proof: String::new(),
+        }
+    }
+
+    #[test]
+    fn lemmas_are_offered_to_problems_with_the_same_signature_and_axioms() {
+        let group = "fof(a1, axiom, ! [X] : mult(e, X) = X).\n\
+                     fof(a2, axiom, ! [X] : mult(inv(X), X) = e).\n";
+        let p1 = theory(&format!(
+            "{}fof(c, conjecture, ! [X] : mult(X, e) = X).",
+            group
+        ))
+        .unwrap();
+        assert_eq!(
+            p1.0,
+            [
+                ("e".to_string(), 0),
+                ("inv".to_string(), 1),
+                ("mult".to_string(), 2)
+            ]
+        );
+        assert_eq!(p1.1.len(), 2);
+        // more axioms, same signature
+        let p2 = theory(&format!(
+            "{}fof(a3, axiom, ! [X] : inv(inv(X)) = X).\n\
+             fof(c, conjecture, ! [X] : mult(X, inv(X)) = e).",
+            group
+        ))
+        .unwrap();
+        // one of the axioms of p1 is missing
+        let p3 = theory(
+            "fof(a1, axiom, ! [Y] : mult(e, Y) = Y).\n\
+             fof(c, conjecture, ! [X] : mult(inv(X), X) = e).",
+        )
+        .unwrap();
+        assert_eq!(p3.0, p1.0);
+
+        let mut library = LemmaLibrary::default();
+        assert!(library.add(lemma("p1", "! [X] : mult(X, e) = X", &p1)));
+        // alpha-equivalent to the first lemma
+        assert!(!library.add(lemma("p1b", "! [Y] : Y = mult(Y, e)", &p1)));
+
+        let offered = library.offered("p2", &p2.0, &p2.1);
+        assert_eq!(offered.len(), 1);
+        assert_eq!(offered[0].axiom_name(), "library_p1_single_lemma_0003");
+        assert!(library.offered("p1", &p1.0, &p1.1).is_empty());
+        assert!(library.offered("p3", &p3.0, &p3.1).is_empty());
+        assert!(library
+            .offered("p4", &vec![("f".to_string(), 2)], &p2.1)
+            .is_empty());
+    }
+
+    #[test]
+    fn only_lemmas_proved_from_the_axioms_are_kept() {
+        let (_, axioms) = theory(
+            "fof(a1, axiom, ! [X] : mult(e, X) = X).\n\
+             fof(c, conjecture, mult(e, e) = e).",
+        )
+        .unwrap();
+        let from_axioms = "1. mult(e,X0) = X0 [input]\n2. mult(e,e) = e [superposition 1,1]\n";
+        assert!(proved_from("vampire", from_axioms, &axioms));
+        // the second input is an earlier lemma of the problem, not an axiom
+        let from_lemma = "1. mult(e,X0) = X0 [input]\n2. mult(X0,e) = X0 [input]\n\
+                          3. mult(e,e) = e [superposition 1,2]\n";
+        assert!(!proved_from("vampire", from_lemma, &axioms));
+        assert!(!proved_from("vampire", "1. @@@ [input]\n", &axioms));
+    }
+}
@@ -0,0 +1,405 @@
+use crate::alpha_match::FormulaIndex;
+use crate::artifact::{self, write_atomic};
+use crate::formula::{parse_formula, Formula};
+use crate::tptp_file::TptpFile;
+use crate::utils::strip_prover_suffix;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// The lemma variants collect extracts, each in its own subdirectory of the lemmas directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LemmaKind {
+    Single,
+    History,
+    Abstract,
+}
+
+/// File of the lemmas directory mapping each lemma `collect` dropped as a
+/// repetition of a lemma of a lower number to that lemma
+pub const ALIASES_FILE: &str = "aliases.json";
+
+/// Mode of `--modes` for single lemmas read from the Vampire proof by
+/// `collect` itself instead of by the OCaml extractor
+pub const VAMPIRE_STEPS_MODE: &str = "vampire-steps";
+
+impl LemmaKind {
+    pub const ALL: [LemmaKind; 3] = [LemmaKind::Single, LemmaKind::History, LemmaKind::Abstract];
+
+    /// Subdirectory of the lemmas directory, also the mode passed to the extractor
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            LemmaKind::Single => "single",
+            LemmaKind::History => "history",
+            LemmaKind::Abstract => "abstract",
+        }
+    }
+
+    /// Prefix of the lemma names, e.g. `single_lemma_0003`
+    pub fn prefix(self) -> &'static str {
+        match self {
+            LemmaKind::Single => "single_lemma_",
+            LemmaKind::History => "history_lemma_",
+            LemmaKind::Abstract => "abstract_lemma_",
+        }
+    }
+
+    pub fn of(name: &str) -> Option<LemmaKind> {
+        LemmaKind::ALL
+            .into_iter()
+            .find(|kind| name.starts_with(kind.prefix()))
+    }
+
+    /// The kind of mode `name`; `vampire-steps` lemmas are single lemmas
+    pub fn parse(name: &str) -> Result<LemmaKind, String> {
+        if name == VAMPIRE_STEPS_MODE {
+            return Ok(LemmaKind::Single);
+        }
+        LemmaKind::ALL
+            .into_iter()
+            .find(|kind| kind.dir_name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown lemma mode '{}', expected single, history, abstract or {}",
+                    name, VAMPIRE_STEPS_MODE
+                )
+            })
+    }
+
+    /// Whether the comma-separated `only` list asks for the single lemmas to
+    /// be read from the Vampire proof steps
+    pub fn vampire_steps_selected(only: Option<&str>) -> bool {
+        only.is_some_and(|list| {
+            list.split(',')
+                .any(|name| name.trim() == VAMPIRE_STEPS_MODE)
+        })
+    }
+
+    /// The modes `collect` extracts: those in the comma-separated `only` list
+    /// (all if none) minus those in `skip`, in the order of `ALL`
+    pub fn selected(only: Option<&str>, skip: Option<&str>) -> Result<Vec<LemmaKind>, String> {
+        let parse_list = |list: &str| -> Result<Vec<LemmaKind>, String> {
+            list.split(',')
+                .map(|name| LemmaKind::parse(name.trim()))
+                .collect()
+        };
+        let only = only.map(parse_list).transpose()?;
+        let skip = skip.map(parse_list).transpose()?.unwrap_or_default();
+        let modes: Vec<LemmaKind> = LemmaKind::ALL
+            .into_iter()
+            .filter(|kind| only.as_ref().is_none_or(|only| only.contains(kind)))
+            .filter(|kind| !skip.contains(kind))
+            .collect();
+        if modes.is_empty() {
+            return Err("No lemma mode left to extract".to_string());
+        }
+        Ok(modes)
+    }
+}
+
+/// Name of the lemma inside its file: `single_lemma_0003` states it as `conjecture_0003`
+fn internal_name(kind: LemmaKind, name: &str) -> String {
+    format!("conjecture_{}", &name[kind.prefix().len()..])
+}
+
+/// The lemmas of the lemmas directory, read once. Names may carry a prover
+/// suffix (`single_lemma_0003_twee`), which is ignored, and may be aliases of
+/// the lemma they repeat.
+#[derive(Debug, Clone, Default)]
+pub struct LemmaStore {
+    dir: PathBuf,
+    lemmas: BTreeMap<String, Formula>,
+    /// alias -> canonical lemma, from `aliases.json`
+    aliases: BTreeMap<String, String>,
+    /// lemma names by formula, in name order
+    by_formula: FormulaIndex<String>,
+}
+
+type FileStamp = (SystemTime, u64);
+
+/// Lemma files parsed so far, with the modification time (to the nanosecond)
+/// and length they had, for a command that loads the lemmas directory more
+/// than once: a file is only read and parsed again once it changed
+#[derive(Debug, Default)]
+pub struct LemmaCache {
+    files: Mutex<BTreeMap<PathBuf, (FileStamp, Formula)>>,
+}
+
+/// Write the alias map of `lemmas_dir`, returning its path
+pub fn write_aliases(
+    lemmas_dir: &str,
+    aliases: &BTreeMap<String, String>,
+) -> Result<PathBuf, String> {
+    let path = Path::new(lemmas_dir).join(ALIASES_FILE);
+    let json = serde_json::to_string_pretty(aliases)
+        .map_err(|e| format!("Failed to serialize the lemma aliases: {}", e))?;
+    write_atomic(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+fn read_aliases(lemmas_dir: &Path) -> Result<BTreeMap<String, String>, String> {
+    let path = lemmas_dir.join(ALIASES_FILE);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let text = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    artifact::parse_json(&text).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+impl LemmaStore {
+    /// Read the single, history and abstract lemmas below `lemmas_dir`, and
+    /// their aliases. Missing subdirectories are empty; lemma files that
+    /// cannot be read are skipped with a warning, so asking for them later
+    /// reports them missing.
+    pub fn load(lemmas_dir: &str) -> Result<LemmaStore, String> {
+        LemmaStore::load_cached(lemmas_dir, &LemmaCache::default())
+    }
+
+    /// `load`, taking the files that did not change since from `cache`
+    pub fn load_cached(lemmas_dir: &str, cache: &LemmaCache) -> Result<LemmaStore, String> {
+        let mut store = LemmaStore {
+            dir: PathBuf::from(lemmas_dir),
+            lemmas: BTreeMap::new(),
+            aliases: read_aliases(Path::new(lemmas_dir))?,
+            by_formula: FormulaIndex::new(),
+        };
+        for kind in LemmaKind::ALL {
+            let kind_dir = store.dir.join(kind.dir_name());
+            if !kind_dir.is_dir() {
+                continue;
+            }
+            let entries = fs::read_dir(&kind_dir)
+                .map_err(|e| format!("Failed to read {}: {}", kind_dir.display(), e))?;
+            for entry in entries {
+                let path = entry.map_err(|e| e.to_string())?.path();
+                let name = match path.file_stem().and_then(|s| s.to_str()) {
+                    Some(name) if name.starts_with(kind.prefix()) => name.to_string(),
+                    _ => continue,
+                };
+                if path.extension().is_none_or(|ext| ext != "p") {
+                    continue;
+                }
+                match cache.read(&path, &internal_name(kind, &name)) {
+                    Ok(formula) => {
+                        store.lemmas.insert(name, formula);
+                    }
+                    Err(err) => eprintln!("[WARN] Skipping lemma {}: {}", name, err),
+                }
+            }
+        }
+        store.index_formulas();
+        Ok(store)
+    }
+
+    fn index_formulas(&mut self) {
+        self.by_formula = FormulaIndex::new();
+        for (name, formula) in &self.lemmas {
+            self.by_formula.insert(&formula.to_string(), name.clone());
+        }
+    }
+
+    /// The lemma `name` stands for: itself without a prover suffix, or the
+    /// lemma it is an alias of
+    pub fn canonical(&self, name: &str) -> String {
+        let name = strip_prover_suffix(name);
+        match self.aliases.get(&name) {
+            Some(canonical) => canonical.clone(),
+            None => name,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Formula> {
+        self.lemmas.get(&self.canonical(name))
+    }
+
+    /// The lemmas whose formula is alpha-equivalent to `formula`, in name
+    /// order, for when a proof gives the formula but not the lemma name
+    pub fn find_alpha_equivalent(&self, formula: &str) -> Vec<&str> {
+        self.by_formula
+            .lookup_alpha_equivalent(formula)
+            .iter()
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// The formula of lemma `name` as text, for building problems
+    pub fn formula(&self, name: &str) -> Result<String, String> {
+        self.get(name)
+            .map(|f| f.to_string())
+            .ok_or_else(|| format!("Missing lemma {}", name))
+    }
+
+    /// The problem file of lemma `name`
+    pub fn path(&self, name: &str) -> Result<PathBuf, String> {
+        let name = self.canonical(name);
+        let kind =
+            LemmaKind::of(&name).ok_or_else(|| format!("Unknown lemma type for {}", name))?;
+        Ok(self.dir.join(kind.dir_name()).join(format!("{}.p", name)))
+    }
+
+    /// The lemmas of one kind in name order
+    pub fn iter_by_kind(&self, kind: LemmaKind) -> impl Iterator<Item = (&str, &Formula)> {
+        self.lemmas
+            .iter()
+            .filter(move |(name, _)| LemmaKind::of(name) == Some(kind))
+            .map(|(name, formula)| (name.as_str(), formula))
+    }
+}
+
+impl LemmaCache {
+    /// `read_lemma`, unless the file is cached with its current stamp
+    fn read(&self, path: &Path, internal: &str) -> Result<Formula, String> {
+        let metadata = fs::metadata(path).map_err(|e| e.to_string())?;
+        let stamp = (
+            metadata.modified().map_err(|e| e.to_string())?,
+            metadata.len(),
+        );
+        if let Some((cached_stamp, formula)) = self.files.lock().unwrap().get(path) {
+            if *cached_stamp == stamp {
+                return Ok(formula.clone());
+            }
+        }
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let formula = read_lemma(path, &content, internal)?;
+        self.files
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), (stamp, formula.clone()));
+        Ok(formula)
+    }
+}
+
+fn read_lemma(path: &Path, content: &str, internal: &str) -> Result<Formula, String> {
+    let body = TptpFile::parse(content)?
+        .formula(internal)
+        .map(|f| f.body_line())
+        .ok_or_else(|| format!("no formula {} in {}", internal, path.display()))?;
+    parse_formula(&body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_and_lists_lemmas() {
+        let dir = std::env::temp_dir().join(format!("frankenstein-lemmas-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("single")).unwrap();
+        fs::write(
+            dir.join("single").join("single_lemma_0002.p"),
+            "fof(a1, axiom, ! [X] : mult(e, X) = X).\n\nfof(conjecture_0002, conjecture,\n    ! [X] :\n      (mult(X, e) = X)\n).\n",
+        )
+        .unwrap();
+        let lemmas_dir = dir.to_string_lossy().to_string();
+
+        let cache = LemmaCache::default();
+        let store = LemmaStore::load_cached(&lemmas_dir, &cache).unwrap();
+        assert_eq!(cache.files.lock().unwrap().len(), 1);
+        assert_eq!(
+            store.formula("single_lemma_0002_twee").unwrap(),
+            "! [X] : (mult(X,e) = X)"
+        );
+        assert!(store.formula("history_lemma_0001").is_err());
+
+        let singles: Vec<_> = store
+            .iter_by_kind(LemmaKind::Single)
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(singles, vec!["single_lemma_0002"]);
+
+        // an alias reads as the lemma it repeats
+        let aliases = BTreeMap::from([(
+            "history_lemma_0005".to_string(),
+            "single_lemma_0002".to_string(),
+        )]);
+        write_aliases(&lemmas_dir, &aliases).unwrap();
+        let aliased = LemmaStore::load_cached(&lemmas_dir, &cache).unwrap();
+        assert_eq!(
+            aliased.canonical("history_lemma_0005_twee"),
+            "single_lemma_0002"
+        );
+        assert_eq!(
+            aliased.formula("history_lemma_0005").unwrap(),
+            "! [X] : (mult(X,e) = X)"
+        );
+        assert_eq!(
+            aliased.canonical("abstract_lemma_0001"),
+            "abstract_lemma_0001"
+        );
+
+        // looked up by formula, up to renaming and orientation
+        assert_eq!(
+            aliased.find_alpha_equivalent("! [Y] : Y = mult(Y, e)"),
+            vec!["single_lemma_0002"]
+        );
+        assert!(aliased.find_alpha_equivalent("mult(e, e) = f").is_empty());
+
+        // a changed file is read again, not taken from the cache
+        fs::write(
+            dir.join("single").join("single_lemma_0002.p"),
+            "fof(conjecture_0002, conjecture,\n    ! [X, Y] :\n      (mult(X, Y) = mult(Y, X))\n).\n",
+        )
+        .unwrap();
+        assert_eq!(
+            LemmaStore::load_cached(&lemmas_dir, &cache)
+                .unwrap()
+                .formula("single_lemma_0002")
+                .unwrap(),
+            "! [X, Y] : (mult(X,Y) = mult(Y,X))"
+        );
+        // or rewritten to the same length, once its modification time moved on
+        let path = dir.join("single").join("single_lemma_0002.p");
+        fs::write(
+            &path,
+            "fof(conjecture_0002, conjecture,\n    ! [X, Y] :\n      (mult(Y, X) = mult(X, Y))\n).\n",
+        )
+        .unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + std::time::Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(
+            LemmaStore::load_cached(&lemmas_dir, &cache)
+                .unwrap()
+                .formula("single_lemma_0002")
+                .unwrap(),
+            "! [X, Y] : (mult(Y,X) = mult(X,Y))"
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn modes_are_selected_in_extraction_order() {
+        assert_eq!(
+            LemmaKind::selected(None, None).unwrap(),
+            LemmaKind::ALL.to_vec()
+        );
+        assert_eq!(
+            LemmaKind::selected(Some("abstract, history"), None).unwrap(),
+            vec![LemmaKind::History, LemmaKind::Abstract]
+        );
+        assert_eq!(
+            LemmaKind::selected(None, Some("single")).unwrap(),
+            vec![LemmaKind::History, LemmaKind::Abstract]
+        );
+        assert!(LemmaKind::selected(Some("history"), Some("history")).is_err());
+        assert!(LemmaKind::selected(Some("histroy"), None).is_err());
+        // vampire-steps lemmas are single lemmas read another way
+        assert_eq!(
+            LemmaKind::selected(Some("vampire-steps,history"), None).unwrap(),
+            vec![LemmaKind::Single, LemmaKind::History]
+        );
+        assert!(LemmaKind::vampire_steps_selected(Some(
+            "history, vampire-steps"
+        )));
+        assert!(!LemmaKind::vampire_steps_selected(Some("single")));
+        assert!(!LemmaKind::vampire_steps_selected(None));
+    }
+}
@@ -1,160 +1,488 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::Read;
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use wait_timeout::ChildExt;
 
+pub mod alpha_match;
+pub mod annotated_proof;
+pub mod artifact;
+pub mod collect_report;
+pub mod events;
+pub mod formula;
+pub mod inputs;
+pub mod manifest;
+pub mod naming;
+pub mod process;
+pub mod proof_step;
 pub mod prover_wrapper;
+pub mod remote;
+pub mod results_db;
+pub mod run_config;
+pub mod stats;
+pub mod summary;
+#[cfg(test)]
+mod test_support;
+pub mod tptp_file;
+pub mod twee_proof;
+pub mod vampire_proof;
+use crate::collect_report::CollectReport;
+use crate::inputs::InputProblem;
+use crate::process::{LocalRunner, ProcessRunner, ProcessSpec};
+use crate::proof_step::{parse_breakdown, rule_breakdown, vampire_steps, RuleKind};
 use crate::prover_wrapper::proof_length;
+use crate::run_config::RunConfig;
 
 #[derive(Debug)]
 pub struct BenchmarkResult {
     pub file: String,
+    /// SZS status of the Vampire run, e.g. "Theorem"
+    pub szs_status: Option<String>,
     pub vampire_steps: Option<usize>,
+    /// length of the minimized proof, even if longer than the Vampire proof
     pub minimized_steps: Option<usize>,
+    /// the minimized proof was longer and `--keep-original` kept the Vampire proof as the output
+    pub kept_original: bool,
+    /// inferences of the Vampire proof by rule kind
+    pub vampire_rules: BTreeMap<RuleKind, usize>,
+    /// steps of the minimized proof by rule kind, as reported by `minimize`
+    pub minimized_rules: BTreeMap<RuleKind, usize>,
+    /// unproved lemmas the minimized proof assumes, kept with `--assume-unproved`;
+    /// with any, the minimized proof is only conditional
+    pub assumed: Vec<String>,
+    /// lemmas extracted and proved by `collect`, if it got that far
+    pub collect: Option<CollectReport>,
 }
 
-/// Run the benchmarking.
+impl BenchmarkResult {
+    /// The result of a problem the pipeline could not run on
+    pub(crate) fn failed(file: String) -> BenchmarkResult {
+        BenchmarkResult {
+            file,
+            szs_status: None,
+            vampire_steps: None,
+            minimized_steps: None,
+            kept_original: false,
+            vampire_rules: BTreeMap::new(),
+            minimized_rules: BTreeMap::new(),
+            assumed: Vec::new(),
+            collect: None,
+        }
+    }
+
+    /// Whether minimizing made the proof longer than the Vampire proof
+    pub fn no_improvement(&self) -> bool {
+        matches!((self.vampire_steps, self.minimized_steps), (Some(v), Some(m)) if m > v)
+    }
+
+    /// Length of the proof the problem ended up with: the minimized one,
+    /// unless the Vampire proof was kept
+    pub fn final_steps(&self) -> Option<usize> {
+        if self.kept_original {
+            self.vampire_steps
+        } else {
+            self.minimized_steps
+        }
+    }
+}
+
+/// Pipeline run on every problem, in order
+pub const COMMANDS: [&str; 4] = ["run_vampire", "collect", "shorten", "minimize"];
+
+/// Run the benchmarking and return the result of every problem.
 /// `input_folder`: folder with input files
 /// `frankenstein_bin`: path to prebuilt frankenstein binary
-pub fn run(input_folder: &str, frankenstein_bin: &str) {
-    let input_dir = Path::new(input_folder);
-    if !input_dir.is_dir() {
-        eprintln!(
-            "Input folder '{}' does not exist or is not a directory.",
-            input_dir.display()
-        );
-        return;
-    }
-    let output_dir = Path::new("../output");
-    fs::create_dir_all(output_dir).expect("Failed to create output folder");
+/// `config`: settings passed on to every command
+pub fn run(config: &RunConfig, input_folder: &str, frankenstein_bin: &str) -> Vec<BenchmarkResult> {
+    run_with(&LocalRunner, config, input_folder, frankenstein_bin)
+}
 
-    let input_files: Vec<_> = fs::read_dir(input_dir)
-        .expect("Failed to read input directory")
-        .filter_map(|entry| {
-            let entry = entry.ok()?;
-            let path = entry.path();
-            path.is_file().then_some(path)
-        })
-        .collect();
+/// `run` with the frankenstein commands started through `runner`
+pub fn run_with(
+    runner: &dyn ProcessRunner,
+    config: &RunConfig,
+    input_folder: &str,
+    frankenstein_bin: &str,
+) -> Vec<BenchmarkResult> {
+    let problems = match BenchmarkRunner::new(runner, config, input_folder, frankenstein_bin) {
+        Ok(problems) => problems,
+        Err(err) => {
+            eprintln!("{}", err);
+            return Vec::new();
+        }
+    };
+    let all_results: Vec<BenchmarkResult> = problems.collect();
+    print_global_summary(config, &all_results);
+    all_results
+}
+
+/// The benchmark run of a folder or archive, one problem at a time: every
+/// `next` runs the pipeline on the next problem, prints and records its outcome and
+/// returns its result. Stopping early leaves the other problems untouched;
+/// the global summary is up to the caller (`print_global_summary` in `run`).
+pub struct BenchmarkRunner<'a> {
+    runner: &'a dyn ProcessRunner,
+    config: &'a RunConfig,
+    frankenstein_bin: String,
+    output_dir: PathBuf,
+    inputs_dir: PathBuf,
+    problems: std::vec::IntoIter<InputProblem>,
+}
 
-    let commands = ["run_vampire", "collect", "shorten", "minimize"];
-    let mut all_results: Vec<BenchmarkResult> = Vec::new();
+impl<'a> BenchmarkRunner<'a> {
+    /// A run over the problems of `input_folder` in name order, with the
+    /// frankenstein commands started through `runner` with the settings of
+    /// `config`. `input_folder` may be
+    /// an archive, and the folder may hold archives and gzip problems; see
+    /// `inputs::problems`.
+    pub fn new(
+        runner: &'a dyn ProcessRunner,
+        config: &'a RunConfig,
+        input_folder: &str,
+        frankenstein_bin: &str,
+    ) -> Result<BenchmarkRunner<'a>, String> {
+        let input_dir = Path::new(input_folder);
+        let problems = inputs::problems(input_dir)?;
+        let output_dir = PathBuf::from("../output");
+        fs::create_dir_all(&output_dir)
+            .map_err(|e| format!("Failed to create output folder: {}", e))?;
 
-    println!("Starting benchmarking in folder: {}\n", input_dir.display());
-    println!("Output folder: {}\n", output_dir.display());
+        println!("Starting benchmarking in folder: {}\n", input_dir.display());
+        println!("Output folder: {}\n", output_dir.display());
 
-    'file_loop: for input_file in input_files {
+        Ok(BenchmarkRunner {
+            runner,
+            config,
+            frankenstein_bin: frankenstein_bin.to_string(),
+            output_dir,
+            inputs_dir: PathBuf::from(inputs::INPUTS_DIR),
+            problems: problems.into_iter(),
+        })
+    }
+
+    /// Write decompressed problems and archive members to `dir` instead of
+    /// `inputs::INPUTS_DIR`
+    pub fn inputs_dir(mut self, dir: &Path) -> BenchmarkRunner<'a> {
+        self.inputs_dir = dir.to_path_buf();
+        self
+    }
+
+    /// Run every command of the pipeline on `input_file`
+    fn run_problem(&self, input_file: &Path) -> BenchmarkResult {
+        let output_dir = self.output_dir.as_path();
         let input_str = input_file.to_string_lossy().to_string();
         println!("=== Processing file: {} ===", input_str);
 
+        let mut szs_status: Option<String> = None;
         let mut vampire_steps: Option<usize> = None;
         let mut minimized_steps: Option<usize> = None;
+        let mut kept_original = false;
+        let mut vampire_rules = BTreeMap::new();
+        let mut minimized_rules = BTreeMap::new();
+        let mut assumed = Vec::new();
+        let mut collect = None;
 
-        for cmd in &commands {
+        for cmd in &COMMANDS {
             println!("Running '{} {}' ...", cmd, input_str);
 
-            let mut child = match Command::new(frankenstein_bin)
-                .args([cmd, input_str.as_str()])
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()
-            {
-                Ok(c) => c,
-                Err(e) => {
-                    eprintln!("Failed to start '{} {}': {}", cmd, input_str, e);
-                    continue;
-                }
-            };
-
             let timeout = Duration::from_secs(3600); // 1 hour
+            let spec = ProcessSpec::new(&self.frankenstein_bin, timeout)
+                .args(&[*cmd, input_str.as_str()])
+                .args(&config_flags(self.config))
+                .args(&self.config.results_db.forwarded_flag())
+                .args(&self.config.forwarded_lemma_library())
+                .args(&self.config.events.forwarded_flag());
 
-            let status = match child.wait_timeout(timeout) {
-                Ok(Some(status)) => status,
-                Ok(None) => {
-                    eprintln!(
-                        "[TIMEOUT] '{}' exceeded {:?} on {} — recording as failed",
-                        cmd, timeout, input_str
-                    );
-                    let _ = child.kill();
-                    all_results.push(BenchmarkResult {
-                        file: input_str.clone(),
-                        vampire_steps: None,
-                        minimized_steps: None,
-                    });
-                    continue 'file_loop;
-                }
+            let output = match self.runner.run(&spec) {
+                Ok(output) => output,
                 Err(e) => {
-                    eprintln!("Failed waiting for '{}': {}", cmd, e);
+                    eprintln!("Failed to run '{} {}': {}", cmd, input_str, e);
                     continue;
                 }
             };
 
-            let output = child
-                .wait_with_output()
-                .expect("Failed to collect process output");
-
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
+            if output.timed_out {
+                eprintln!(
+                    "[TIMEOUT] '{}' exceeded {:?} on {} — recording as failed",
+                    cmd, timeout, input_str
+                );
+                let result = BenchmarkResult {
+                    file: input_str.clone(),
+                    szs_status,
+                    vampire_steps: None,
+                    minimized_steps: None,
+                    kept_original: false,
+                    vampire_rules: BTreeMap::new(),
+                    minimized_rules: BTreeMap::new(),
+                    assumed: Vec::new(),
+                    collect,
+                };
+                record_outcome(self.config, &result);
+                return result;
+            }
 
-            if !status.success() {
-                eprintln!("Command '{}' failed on {}\n{}", cmd, input_str, stderr);
+            let stdout = &output.stdout;
+            if !output.succeeded() {
+                eprintln!(
+                    "Command '{}' failed on {}\n{}",
+                    cmd, input_str, output.stderr
+                );
             }
 
             // --- Vampire proof length ---
             if *cmd == "run_vampire" {
                 let suffix = extract_suffix(&input_str);
-                let vampire_file = output_dir.join(format!("vampire_proof_{}.out", suffix));
-
-                if vampire_file.exists() {
-                    let mut content = String::new();
-                    if let Ok(mut file) = fs::File::open(&vampire_file) {
-                        if file.read_to_string(&mut content).is_ok() {
-                            vampire_steps = Some(proof_length("vampire", &content));
-                        }
-                    }
-                }
+                szs_status = vampire_szs_status(stdout);
+                vampire_steps = vampire_proof_steps(output_dir, &suffix);
+                vampire_rules = vampire_rule_steps(output_dir, &suffix);
+            }
+
+            // --- Lemmas of Phase 1 ---
+            if *cmd == "collect" {
+                let suffix = extract_suffix(&input_str);
+                collect = CollectReport::load(&CollectReport::path(output_dir, &suffix)).ok();
             }
 
             // --- Minimized proof length ---
             if *cmd == "minimize" {
-                for line in stdout.lines() {
-                    if let Some(rest) = line.strip_prefix("[RESULT] Total steps:") {
-                        if let Ok(n) = rest.trim().parse::<usize>() {
-                            minimized_steps = Some(match vampire_steps {
-                                Some(v) if n > v => v,
-                                _ => n,
-                            });
-                            break; // we found the number, no need to keep scanning
-                        }
-                    }
-                }
+                minimized_steps = minimized_proof_steps(stdout);
+                kept_original = kept_original_proof(stdout);
+                minimized_rules = minimized_rule_steps(stdout);
+                assumed = minimized_hypotheses(stdout);
             }
         }
 
-        println!("--- Summary for {} ---", input_str);
-        println!(
-            "Vampire proof steps: {}",
-            vampire_steps
-                .map(|s| s.to_string())
-                .unwrap_or_else(|| "N/A".to_string())
-        );
-        println!(
-            "Minimized proof steps: {}",
-            minimized_steps
-                .map(|s| s.to_string())
-                .unwrap_or_else(|| "N/A".to_string())
-        );
-        println!("===========================\n");
-
-        all_results.push(BenchmarkResult {
+        let result = BenchmarkResult {
             file: input_str,
+            szs_status,
             vampire_steps,
             minimized_steps,
-        });
+            kept_original,
+            vampire_rules,
+            minimized_rules,
+            assumed,
+            collect,
+        };
+        print_file_summary(&result);
+        record_outcome(self.config, &result);
+        result
+    }
+}
+
+impl Iterator for BenchmarkRunner<'_> {
+    type Item = BenchmarkResult;
+
+    fn next(&mut self) -> Option<BenchmarkResult> {
+        let problem = self.problems.next()?;
+        match problem.materialize(&self.inputs_dir) {
+            Ok(input_file) => Some(self.run_problem(&input_file)),
+            Err(err) => {
+                eprintln!("Skipping {}: {}", problem.label(), err);
+                let result = BenchmarkResult::failed(problem.label());
+                record_outcome(self.config, &result);
+                Some(result)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.problems.size_hint()
+    }
+}
+
+impl ExactSizeIterator for BenchmarkRunner<'_> {}
+
+/// `--deterministic`, `--keep-original`, `--assume-unproved`, `--cap-steps`
+/// and `--force-backend` for the frankenstein commands, if set for this run
+pub(crate) fn config_flags(config: &RunConfig) -> Vec<&'static str> {
+    let mut flags = Vec::new();
+    if config.deterministic {
+        flags.push("--deterministic");
     }
+    if config.keep_original {
+        flags.push("--keep-original");
+    }
+    if config.assume_unproved {
+        flags.push("--assume-unproved");
+    }
+    if config.cap_steps {
+        flags.push("--cap-steps");
+    }
+    if let Some(backend) = config.force_backend {
+        flags.extend(["--force-backend", backend.name()]);
+    }
+    flags
+}
+
+/// Length of the Vampire proof that `run_vampire` saved in `output_dir`
+pub(crate) fn vampire_proof_steps(output_dir: &Path, suffix: &str) -> Option<usize> {
+    let vampire_file = output_dir.join(format!("vampire_proof_{}.out", suffix));
+    let mut content = String::new();
+    let mut file = fs::File::open(vampire_file).ok()?;
+    file.read_to_string(&mut content).ok()?;
+    proof_length("vampire", &content).ok()
+}
+
+/// Inferences of the Vampire proof that `run_vampire` saved in `output_dir`, by rule kind
+pub(crate) fn vampire_rule_steps(output_dir: &Path, suffix: &str) -> BTreeMap<RuleKind, usize> {
+    let vampire_file = output_dir.join(format!("vampire_proof_{}.out", suffix));
+    fs::read_to_string(vampire_file)
+        .ok()
+        .and_then(|content| vampire_steps(&content).ok())
+        .map(|steps| rule_breakdown(&steps))
+        .unwrap_or_default()
+}
+
+/// SZS status of the Vampire run as `run_vampire` reports it, also when
+/// Vampire found no proof and saved none. With `--portfolio` the last
+/// strategy run counts.
+pub(crate) fn vampire_szs_status(stdout: &str) -> Option<String> {
+    stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("[RESULT] SZS status:"))
+        .filter_map(|rest| rest.split_whitespace().next())
+        .next_back()
+        .map(str::to_string)
+}
+
+/// Length reported by `minimize`, even if longer than the Vampire proof
+pub(crate) fn minimized_proof_steps(stdout: &str) -> Option<usize> {
+    stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("[RESULT] Total steps:"))
+        .find_map(|rest| rest.split_whitespace().next()?.parse::<usize>().ok())
+}
+
+/// Unproved lemmas the proof reported by `minimize` is conditional on
+pub(crate) fn minimized_hypotheses(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("[RESULT] Total steps:"))
+        .find_map(|rest| rest.split("(conditional on the unproved lemmas ").nth(1))
+        .map(|lemmas| {
+            lemmas
+                .trim_end_matches(')')
+                .split(", ")
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Steps of the minimized proof by rule kind, as reported by `minimize`
+pub(crate) fn minimized_rule_steps(stdout: &str) -> BTreeMap<RuleKind, usize> {
+    stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("[RESULT] Steps by rule:"))
+        .map(parse_breakdown)
+        .unwrap_or_default()
+}
 
+/// Whether `minimize` kept the Vampire proof as its output
+pub(crate) fn kept_original_proof(stdout: &str) -> bool {
+    stdout
+        .lines()
+        .any(|line| line.starts_with("[RESULT] Kept the original proof"))
+}
+
+/// Log the result of a problem to the results database of `config`, if there is one
+pub(crate) fn record_outcome(config: &RunConfig, result: &BenchmarkResult) {
+    config.results_db.record_outcome(
+        &result.file,
+        result.szs_status.as_deref(),
+        result.vampire_steps,
+        result.minimized_steps,
+    );
+}
+
+pub(crate) fn print_file_summary(result: &BenchmarkResult) {
+    println!("--- Summary for {} ---", result.file);
+    println!(
+        "Vampire proof steps: {}",
+        result
+            .vampire_steps
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "N/A".to_string())
+    );
+    println!(
+        "Minimized proof steps: {}{}",
+        result
+            .minimized_steps
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "N/A".to_string()),
+        conditional_note(result)
+    );
+    if let Some(collect) = &result.collect {
+        println!("Lemmas: {}", lemma_counts(collect));
+    }
+    if !result.minimized_rules.is_empty() {
+        println!(
+            "Steps by rule: {}",
+            rule_changes(&result.vampire_rules, &result.minimized_rules)
+        );
+    }
+    if let Some(note) = improvement_note(result) {
+        println!("{}", note);
+    }
+    println!("===========================\n");
+}
+
+/// `9 extracted (single 4, history 3, abstract 2), 7 proved (twee 5, vampire 2), 2 failed`
+fn lemma_counts(collect: &CollectReport) -> String {
+    let wins: Vec<String> = collect
+        .wins()
+        .iter()
+        .map(|(prover, n)| format!("{} {}", prover, n))
+        .collect();
+    format!(
+        "{} extracted ({}), {} proved ({}), {} failed",
+        collect.extracted_total(),
+        collect.extracted_by_mode(),
+        collect.proved.len(),
+        wins.join(", "),
+        collect.failed.len()
+    )
+}
+
+/// `superposition 12 -> 3, rewrite 0 -> 5` for every kind of rule in either proof
+fn rule_changes(
+    vampire: &BTreeMap<RuleKind, usize>,
+    minimized: &BTreeMap<RuleKind, usize>,
+) -> String {
+    RuleKind::ALL
+        .into_iter()
+        .filter(|kind| vampire.contains_key(kind) || minimized.contains_key(kind))
+        .map(|kind| {
+            format!(
+                "{} {} -> {}",
+                kind.name(),
+                vampire.get(&kind).unwrap_or(&0),
+                minimized.get(&kind).unwrap_or(&0)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// "No improvement" if minimizing made the proof longer, and what was kept
+fn improvement_note(result: &BenchmarkResult) -> Option<&'static str> {
+    match (result.no_improvement(), result.kept_original) {
+        (false, _) => None,
+        (true, false) => Some("No improvement: the minimized proof is longer"),
+        (true, true) => Some("No improvement: kept the Vampire proof"),
+    }
+}
+
+/// ` (conditional on a, b)` if the minimized proof assumes unproved lemmas
+fn conditional_note(result: &BenchmarkResult) -> String {
+    if result.assumed.is_empty() {
+        String::new()
+    } else {
+        format!(" (conditional on {})", result.assumed.join(", "))
+    }
+}
+
+pub fn print_global_summary(config: &RunConfig, all_results: &[BenchmarkResult]) {
     // --- Global summary ---
     println!("\n========== GLOBAL SUMMARY ==========");
 
@@ -163,9 +491,9 @@ pub fn run(input_folder: &str, frankenstein_bin: &str) {
     let mut count_vampire = 0usize;
     let mut count_minimized = 0usize;
 
-    for r in &all_results {
+    for r in all_results {
         println!(
-            "{:<45}  Vampire: {:>6}  Minimized: {:>6}",
+            "{:<45}  Vampire: {:>6}  Minimized: {:>6}{}{}",
             r.file,
             r.vampire_steps
                 .map(|v| {
@@ -175,13 +503,15 @@ pub fn run(input_folder: &str, frankenstein_bin: &str) {
                 })
                 .unwrap_or_else(|| "N/A".to_string()),
             r.minimized_steps
-                .map(|m| {
-                    total_minimized += m;
-                    count_minimized += 1;
-                    m.to_string()
-                })
+                .map(|m| m.to_string())
                 .unwrap_or_else(|| "N/A".to_string()),
+            conditional_note(r),
+            improvement_note(r).map_or(String::new(), |note| format!("  {}", note)),
         );
+        if let Some(m) = r.final_steps() {
+            total_minimized += m;
+            count_minimized += 1;
+        }
     }
 
     println!("------------------------------------");
@@ -200,14 +530,188 @@ pub fn run(input_folder: &str, frankenstein_bin: &str) {
         );
     }
 
+    // rule kinds over the problems minimization reported them for, to compare like with like
+    let mut vampire_rules = BTreeMap::new();
+    let mut minimized_rules = BTreeMap::new();
+    for r in all_results.iter().filter(|r| !r.minimized_rules.is_empty()) {
+        for (kind, n) in &r.vampire_rules {
+            *vampire_rules.entry(*kind).or_default() += n;
+        }
+        for (kind, n) in &r.minimized_rules {
+            *minimized_rules.entry(*kind).or_default() += n;
+        }
+    }
+    if !minimized_rules.is_empty() {
+        println!(
+            "Steps by rule (Vampire -> minimized): {}",
+            rule_changes(&vampire_rules, &minimized_rules)
+        );
+    }
+
+    let reports: Vec<&CollectReport> = all_results
+        .iter()
+        .filter_map(|r| r.collect.as_ref())
+        .collect();
+    if !reports.is_empty() {
+        println!(
+            "Lemmas proved: {} of {} ({} failed)",
+            reports.iter().map(|c| c.proved.len()).sum::<usize>(),
+            reports
+                .iter()
+                .map(|c| c.proved.len() + c.failed.len())
+                .sum::<usize>(),
+            reports.iter().map(|c| c.failed.len()).sum::<usize>()
+        );
+    }
+
+    let mut prover_runs = Vec::new();
+    for runs in reports.iter().flat_map(|c| &c.prover_runs) {
+        collect_report::add_runs(&mut prover_runs, runs);
+    }
+    if !prover_runs.is_empty() {
+        println!("Prover failures on lemma problems:");
+    }
+    for runs in &prover_runs {
+        println!(
+            "  {} on {} lemmas: {} of {} runs failed{}",
+            runs.prover,
+            runs.mode,
+            runs.failed(),
+            runs.attempts,
+            match runs.failed() {
+                0 => String::new(),
+                _ => format!(" ({})", runs.failure_rates()),
+            }
+        );
+    }
+
+    let no_improvement = all_results.iter().filter(|r| r.no_improvement()).count();
+    if no_improvement > 0 {
+        println!(
+            "No improvement on {} of {} problems",
+            no_improvement,
+            all_results.len()
+        );
+    }
+
+    println!("Configuration: {}", config.fingerprint());
     println!("====================================");
     println!("All benchmarking runs completed.");
 }
 
-fn extract_suffix(path: &str) -> String {
-    Path::new(path)
+/// Suffix of the files frankenstein writes for `path`, as computed by its `extract_suffix`
+pub(crate) fn extract_suffix(path: &str) -> String {
+    let path = path.strip_suffix(".gz").unwrap_or(path);
+    let stem = Path::new(path)
         .file_stem()
         .unwrap()
         .to_string_lossy()
-        .to_string()
+        .to_string();
+
+    match stem.strip_prefix("input_problem_") {
+        Some(stripped) => artifact::safe_name(stripped),
+        None => artifact::safe_name(&stem),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{DryRunRunner, ProcessOutput};
+    use crate::test_support::ScratchDir;
+
+    #[test]
+    fn problems_are_run_one_at_a_time() {
+        let input_dir = ScratchDir::new("benchmark_runner");
+        for name in ["c.p", "a.p", "b.p"] {
+            input_dir.write(name, "fof(c, conjecture, a = a).");
+        }
+        let runner = DryRunRunner::replying(|spec| {
+            if spec.args[0] == "minimize" {
+                ProcessOutput::success("[RESULT] Total steps: 7\n")
+            } else {
+                ProcessOutput::success("")
+            }
+        });
+
+        let folder = input_dir.path().to_string_lossy();
+        let config = RunConfig::default();
+        let mut problems =
+            BenchmarkRunner::new(&runner, &config, &folder, "./frankenstein").unwrap();
+        assert_eq!(problems.len(), 3);
+        let first = problems.next().unwrap();
+        assert!(first.file.ends_with("a.p"));
+        assert_eq!(first.minimized_steps, Some(7));
+        // nothing is run for the problems not asked for yet
+        assert_eq!(runner.calls().len(), COMMANDS.len());
+        assert_eq!(problems.len(), 2);
+        let rest: Vec<String> = problems.map(|r| r.file).collect();
+        assert!(rest[0].ends_with("b.p") && rest[1].ends_with("c.p"));
+
+        assert!(
+            BenchmarkRunner::new(&runner, &config, "no/such/folder", "./frankenstein").is_err()
+        );
+    }
+
+    #[test]
+    fn gzip_problems_are_run_decompressed() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let input_dir = ScratchDir::new("benchmark_gz");
+        let inputs_dir = ScratchDir::new("benchmark_gz_inputs");
+        let name = "gz.p";
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"fof(c, conjecture, a = a).").unwrap();
+        input_dir.write(&format!("{}.gz", name), encoder.finish().unwrap());
+        input_dir.write("broken.p.gz", "not gzip");
+        let runner = DryRunRunner::replying(|_| ProcessOutput::success(""));
+
+        let folder = input_dir.path().to_string_lossy();
+        let config = RunConfig::default();
+        let results: Vec<BenchmarkResult> =
+            BenchmarkRunner::new(&runner, &config, &folder, "./frankenstein")
+                .unwrap()
+                .inputs_dir(inputs_dir.path())
+                .collect();
+        // the broken problem is recorded as failed without running anything
+        assert!(results[0].file.ends_with("broken.p.gz"));
+        assert_eq!(runner.calls().len(), COMMANDS.len());
+        let decompressed = inputs_dir.path().join(name);
+        assert_eq!(results[1].file, decompressed.to_string_lossy());
+        assert_eq!(runner.calls()[0].args[1], results[1].file);
+        assert_eq!(
+            fs::read_to_string(&decompressed).unwrap(),
+            "fof(c, conjecture, a = a)."
+        );
+        assert_eq!(
+            extract_suffix(&format!("{}.gz", name)),
+            extract_suffix(name)
+        );
+    }
+
+    #[test]
+    fn the_szs_status_is_read_from_the_run() {
+        let stdout = "[INFO] Running Vampire strategy 'default' for 12s...\n\
+                      [RESULT] SZS status: Timeout\n\
+                      [INFO] Running Vampire strategy 'avatar_off' for 12s...\n\
+                      [RESULT] SZS status: CounterSatisfiable\n";
+        assert_eq!(
+            vampire_szs_status(stdout).as_deref(),
+            Some("CounterSatisfiable")
+        );
+        assert_eq!(vampire_szs_status("[INFO] nothing ran\n"), None);
+    }
+
+    #[test]
+    fn conditional_results_keep_their_steps_and_hypotheses() {
+        let stdout = "[RESULT] Total steps: 9 (conditional on the unproved lemmas \
+                      single_lemma_0002, history_lemma_0005)\n";
+        assert_eq!(minimized_proof_steps(stdout), Some(9));
+        assert_eq!(
+            minimized_hypotheses(stdout),
+            vec!["single_lemma_0002", "history_lemma_0005"]
+        );
+        assert!(minimized_hypotheses("[RESULT] Total steps: 9\n").is_empty());
+    }
 }
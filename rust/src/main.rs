@@ -1,59 +1,297 @@
-mod alpha_match;
 mod dag;
 mod frankenstein;
+mod generate;
+mod group_minimize;
+mod integrity;
+mod itp_export;
+mod lemma_browser;
+mod lemma_library;
+mod lemma_store;
 mod minimize;
-mod prover_wrapper;
+mod preprocess;
+mod proof_diff;
+mod provenance;
+mod prover_stats;
+mod ranking;
 mod run_vamp;
 mod superpose;
+#[cfg(test)]
+mod test_support;
+mod typed;
 mod utils;
+mod workspace;
 
+// the modules of the benchmark library are used from it, not compiled again
+use ::frankenstein::{
+    alpha_match, annotated_proof, artifact, collect_report, events, formula, inputs, naming,
+    process, proof_step, prover_wrapper, results_db, run_config, stats, summary, tptp_file,
+    twee_proof, vampire_proof,
+};
+use events::EventLog;
+use process::{DryRunRunner, LocalRunner};
+use prover_wrapper::RecordingRunner;
+use results_db::ResultsDb;
+use run_config::{Backend, RunConfig, SkolemPolicy};
 use std::env;
 use std::path::Path;
+use std::time::Duration;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    let mut config = RunConfig::default();
+    // --deterministic, --equational-only, --recheck-steps, --twee-steps, --generalize-lemmas,
+    // --adaptive-provers, --keep-original, --assume-unproved and --cap-steps are accepted
+    // by every command
+    if args.iter().any(|a| a == "--equational-only") {
+        args.retain(|a| a != "--equational-only");
+        config.equational_only = true;
+    }
+    if args.iter().any(|a| a == "--recheck-steps") {
+        args.retain(|a| a != "--recheck-steps");
+        config.recheck_steps = true;
+    }
+    if args.iter().any(|a| a == "--twee-steps") {
+        args.retain(|a| a != "--twee-steps");
+        config.twee_steps = true;
+    }
+    if args.iter().any(|a| a == "--generalize-lemmas") {
+        args.retain(|a| a != "--generalize-lemmas");
+        config.generalize_lemmas = true;
+    }
+    if args.iter().any(|a| a == "--adaptive-provers") {
+        args.retain(|a| a != "--adaptive-provers");
+        config.adaptive_provers = true;
+    }
+    if args.iter().any(|a| a == "--keep-original") {
+        args.retain(|a| a != "--keep-original");
+        config.keep_original = true;
+    }
+    if args.iter().any(|a| a == "--assume-unproved") {
+        args.retain(|a| a != "--assume-unproved");
+        config.assume_unproved = true;
+    }
+    if args.iter().any(|a| a == "--cap-steps") {
+        args.retain(|a| a != "--cap-steps");
+        config.cap_steps = true;
+    }
+    // --skolems <policy> decides what happens to root lemmas with Skolem constants
+    if let Some(i) = args.iter().position(|a| a == "--skolems") {
+        let policy = args
+            .get(i + 1)
+            .ok_or_else(|| "--skolems expects skip, generalize or keep".to_string())
+            .and_then(|name| SkolemPolicy::parse(name));
+        match policy {
+            Ok(policy) => config.skolems = policy,
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(&config, 1);
+            }
+        }
+        args.drain(i..i + 2);
+    }
+    // --force-backend <backend> makes minimize build on one backend only
+    if let Some(i) = args.iter().position(|a| a == "--force-backend") {
+        let backend = args
+            .get(i + 1)
+            .ok_or_else(|| "--force-backend expects vampire or twee".to_string())
+            .and_then(|name| Backend::parse(name));
+        match backend {
+            Ok(backend) => config.force_backend = Some(backend),
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(&config, 1);
+            }
+        }
+        args.drain(i..i + 2);
+    }
+    // --jobs <n> proves up to n lemma groups at the same time in Phase 1
+    if let Some(i) = args.iter().position(|a| a == "--jobs") {
+        match args.get(i + 1).map(|n| n.parse::<usize>()) {
+            Some(Ok(jobs)) if jobs > 0 => config.jobs = jobs,
+            _ => {
+                eprintln!("--jobs expects a positive number");
+                exit(&config, 1);
+            }
+        }
+        args.drain(i..i + 2);
+    }
+    if args.iter().any(|a| a == "--deterministic") {
+        args.retain(|a| a != "--deterministic");
+        config.deterministic = true;
+    }
+    // --results-db <file> logs prover runs and candidates to an SQLite database
+    let mut results_db = None;
+    if let Some(i) = args.iter().position(|a| a == "--results-db") {
+        let Some(path) = args.get(i + 1).cloned() else {
+            eprintln!("--results-db expects a database file");
+            exit(&config, 1);
+        };
+        args.drain(i..i + 2);
+        results_db = Some(path);
+    }
+    // --lemma-library <file> shares proved lemmas with the other problems of a benchmark
+    if let Some(i) = args.iter().position(|a| a == "--lemma-library") {
+        let Some(path) = args.get(i + 1).cloned() else {
+            eprintln!("--lemma-library expects a file");
+            exit(&config, 1);
+        };
+        args.drain(i..i + 2);
+        config.lemma_library = Some(path);
+    }
+    // --events <path> appends progress events as JSON lines to a file or descriptor
+    if let Some(i) = args.iter().position(|a| a == "--events") {
+        let Some(path) = args.get(i + 1).cloned() else {
+            eprintln!("--events expects a file");
+            exit(&config, 1);
+        };
+        args.drain(i..i + 2);
+        let problem = args
+            .get(2)
+            .map(|input| extract_suffix(input))
+            .unwrap_or_default();
+        match EventLog::open(&path, &problem) {
+            Ok(events) => config.events = events,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                exit(&config, 1);
+            }
+        }
+    }
+    // printed once every flag is set
+    if config.deterministic {
+        println!("[INFO] Configuration: {}", config.fingerprint());
+    }
+    // opened once every flag is set, since the database records the configuration
+    if let Some(path) = results_db {
+        let problem = args
+            .get(2)
+            .map(|input| extract_suffix(input))
+            .unwrap_or_default();
+        match ResultsDb::open(&path, &problem, &config) {
+            Ok(db) => config.results_db = db,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                exit(&config, 1);
+            }
+        }
+    }
     if args.len() < 2 {
-        eprintln!("Usage: cargo run -- [collect|shorten|group|minimize|run_vampire] <input_file>");
+        eprintln!(
+            "Usage: cargo run -- [init|collect|shorten|group|group-minimize|minimize|preprocess|run_vampire|turnaround|proof_graph|proof-diff|extract-steps|twee-to-sctptp|export|generate|lemmas|stats] <input_file> [--deterministic] [--equational-only] [--recheck-steps] [--twee-steps] [--generalize-lemmas] [--adaptive-provers] [--keep-original] [--assume-unproved] [--cap-steps] [--skolems skip|generalize|keep] [--force-backend vampire|twee] [--jobs <n>] [--results-db <file>] [--lemma-library <file>] [--events <path>]"
+        );
         eprintln!("Usage for benchmarking: cargo run -- benchmarking");
         return;
     }
+    // gzip problems are decompressed, and typed (TFF/THF) problems translated to FOF or
+    // rejected, before any command reads them
+    let reads_problem = matches!(
+        args[1].as_str(),
+        "collect" | "minimize" | "run_vampire" | "preprocess" | "export"
+    );
+    if reads_problem && args.len() >= 3 {
+        let input = inputs::decompressed(&args[2], inputs::INPUTS_DIR).and_then(|plain| {
+            let plain = plain.unwrap_or_else(|| args[2].clone());
+            Ok(typed::untyped_input(&config, &plain, "../output/untyped")?.unwrap_or(plain))
+        });
+        match input {
+            Ok(input) => args[2] = input,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                exit(&config, 1);
+            }
+        }
+    }
+    config.events.start_phase(&args[1]);
     match args[1].as_str() {
+        "init" => {
+            // the workspace is the directory around rust/, as for every other command
+            let root = args.get(2).map(String::as_str).unwrap_or("..");
+            match workspace::init(Path::new(root), &LocalRunner) {
+                Ok(msg) => println!("{}", msg),
+                Err(err) => {
+                    eprintln!("Error: {}", err);
+                    exit(&config, 1);
+                }
+            }
+        }
         "collect" => {
             if args.len() < 3 {
-                eprintln!("Usage: cargo run -- collect <input_file>");
+                usage(
+                    &config,
+                    "Usage: cargo run -- collect <input_file> [--use-existing-proof <file>] [--modes single,history,abstract,vampire-steps] [--skip-modes <modes>]"
+                );
             } else {
                 let input_file = &args[2];
+                let modes = match lemma_store::LemmaKind::selected(
+                    flag_value(&args, "--modes"),
+                    flag_value(&args, "--skip-modes"),
+                ) {
+                    Ok(modes) => modes,
+                    Err(err) => {
+                        eprintln!("Error: {}", err);
+                        exit(&config, 1);
+                    }
+                };
                 // extract suffix from input file
                 let suffix = extract_suffix(input_file);
                 let output_file = format!("../output/vampire_proof_{}.out", suffix);
-                frankenstein::collect(&input_file, &output_file, suffix);
+                if let Some(proof) = flag_value(&args, "--use-existing-proof") {
+                    if let Err(err) = run_vamp::import_existing_proof(&config, proof, &output_file) {
+                        eprintln!("Error: {}", err);
+                        exit(&config, 1);
+                    }
+                }
+                let vampire_steps =
+                    lemma_store::LemmaKind::vampire_steps_selected(flag_value(&args, "--modes"));
+                frankenstein::collect(
+                    &config,
+                    &input_file,
+                    &output_file,
+                    suffix,
+                    &modes,
+                    vampire_steps,
+                );
             }
         }
         "shorten" => {
             if args.len() < 3 {
-                eprintln!("Usage: cargo run -- collect <input_file>");
+                usage(&config, "Usage: cargo run -- collect <input_file>");
             } else {
                 let input_file = &args[2];
                 // extract suffix from input file
                 let suffix = extract_suffix(input_file);
                 let summary_file = format!("../output/summary_{}.json", suffix);
-                frankenstein::shorten_proofs(&summary_file)
+                frankenstein::shorten_proofs(&config, &summary_file)
             }
         }
         "group" => {
             if args.len() < 3 {
-                eprintln!("Usage: cargo run -- collect <input_file>");
+                usage(&config, "Usage: cargo run -- collect <input_file>");
+            } else {
+                let input_file = &args[2];
+                // extract suffix from input file
+                let suffix = extract_suffix(input_file);
+                let summary_file = format!("../output/summary_{}.json", suffix);
+                frankenstein::structural_groups(&config, &summary_file)
+            }
+        }
+        "group-minimize" => {
+            if args.len() < 3 {
+                usage(&config, "Usage: cargo run -- group-minimize <input_file>");
             } else {
                 let input_file = &args[2];
                 // extract suffix from input file
                 let suffix = extract_suffix(input_file);
                 let summary_file = format!("../output/summary_{}.json", suffix);
-                frankenstein::structural_groups(&summary_file)
+                group_minimize::group_minimize(&config, &summary_file)
             }
         }
         "minimize" => {
             if args.len() < 3 {
-                eprintln!("Usage: cargo run -- minimize <input_file>");
+                usage(
+                    &config,
+                    "Usage: cargo run -- minimize <input_file> [--use-existing-proof <file>] [--emit graph] [--assert-max-steps <n>] [--replay <trace.json> [--candidate <n>]] [--lemmas-dir <dir>] [--proofs-dir <dir>]"
+                );
             } else {
                 let input_file = &args[2];
 
@@ -63,34 +301,416 @@ fn main() {
                 // construct summary and output files with suffix
                 let summary_file = format!("../output/summary_{}.json", suffix);
                 let output_file = format!("../output/vampire_proof_{}.out", suffix);
+                if let Some(proof) = flag_value(&args, "--use-existing-proof") {
+                    if let Err(err) = run_vamp::import_existing_proof(&config, proof, &output_file) {
+                        eprintln!("Error: {}", err);
+                        exit(&config, 1);
+                    }
+                }
+
+                // lemmas and Phase 1 proofs of this workspace unless given
+                let defaults = minimize::Dirs::default();
+                let dirs = minimize::Dirs::new(
+                    flag_value(&args, "--lemmas-dir").unwrap_or(&defaults.lemmas),
+                    flag_value(&args, "--proofs-dir").unwrap_or(&defaults.proofs),
+                    &defaults.output,
+                );
 
-                // call minimize with input file and suffixed summary
-                match minimize::try_minimize(&input_file, &output_file, &summary_file) {
+                // call minimize with input file and suffixed summary, or replay
+                // one candidate of a previous search
+                let result = match flag_value(&args, "--replay") {
+                    Some(trace_file) => {
+                        let candidate = match flag_value(&args, "--candidate")
+                            .map(|n| n.parse::<usize>())
+                            .transpose()
+                        {
+                            Ok(candidate) => candidate,
+                            Err(_) => {
+                                eprintln!("Invalid value for --candidate");
+                                exit(&config, 1);
+                            }
+                        };
+                        minimize::replay_candidate(
+                            &config,
+                            &input_file,
+                            &output_file,
+                            &summary_file,
+                            &dirs,
+                            trace_file,
+                            candidate,
+                        )
+                    }
+                    None => {
+                        let emit = match flag_value(&args, "--emit").map(minimize::Emit::parse) {
+                            Some(Ok(emit)) => vec![emit],
+                            Some(Err(err)) => {
+                                eprintln!("{}", err);
+                                exit(&config, 1);
+                            }
+                            None => Vec::new(),
+                        };
+                        let max_steps = match flag_value(&args, "--assert-max-steps")
+                            .map(|n| n.parse::<usize>())
+                            .transpose()
+                        {
+                            Ok(max_steps) => max_steps,
+                            Err(_) => {
+                                eprintln!("Invalid value for --assert-max-steps");
+                                exit(&config, 1);
+                            }
+                        };
+                        minimize::try_minimize(
+                            &config,
+                            &input_file,
+                            &output_file,
+                            &summary_file,
+                            &dirs,
+                            &emit,
+                            max_steps,
+                        )
+                    }
+                };
+                match result {
                     Ok(msg) => println!("{}", msg),
-                    Err(err) => eprintln!("Error: {}", err),
+                    Err(err) => {
+                        eprintln!("Error: {}", err);
+                        if let Some(advice) = err.advice() {
+                            eprintln!("{}", advice);
+                        }
+                        exit(&config, err.exit_code());
+                    }
                 }
             }
         }
         "run_vampire" => {
             if args.len() < 3 {
-                eprintln!("Usage: cargo run -- run_vampire <input_file>");
+                usage(
+                    &config,
+                    "Usage: cargo run -- run_vampire <input_file> [time_limit_secs] [--portfolio] [--dry-run] [--use-existing-proof <file>]"
+                );
             } else {
                 let input_file = &args[2];
+                // optional time limit in seconds and portfolio flag
+                let mut time_limit = run_vamp::DEFAULT_TIME_LIMIT;
+                let mut portfolio = false;
+                let mut dry_run = false;
+                let existing_proof = flag_value(&args, "--use-existing-proof");
+                for arg in &args[3..] {
+                    if Some(arg.as_str()) == existing_proof || arg == "--use-existing-proof" {
+                        continue;
+                    } else if arg == "--portfolio" {
+                        portfolio = true;
+                    } else if arg == "--dry-run" {
+                        dry_run = true;
+                    } else if let Ok(secs) = arg.parse::<u64>() {
+                        time_limit = Duration::from_secs(secs);
+                    } else {
+                        eprintln!("Invalid argument '{}'", arg);
+                        exit(&config, 1);
+                    }
+                }
                 // extract suffix from input file
                 let suffix = extract_suffix(input_file);
                 let output_file = format!("../output/vampire_proof_{}.out", suffix);
 
-                run_vamp::run_vampire_only(input_file, &output_file);
+                let result = match existing_proof {
+                    Some(proof) => run_vamp::import_existing_proof(&config, proof, &output_file),
+                    // print the Vampire commands instead of running them
+                    None if dry_run => {
+                        let runner = DryRunRunner::new();
+                        let _ = run_vamp::run_vampire_only(
+                            &runner,
+                            &config,
+                            input_file,
+                            &output_file,
+                            time_limit,
+                            portfolio,
+                        );
+                        println!(
+                            "[INFO] Dry run: {} Vampire command(s) not executed",
+                            runner.calls().len()
+                        );
+                        Ok(())
+                    }
+                    None => run_vamp::run_vampire_only(
+                        &RecordingRunner::local(&config),
+                        &config,
+                        input_file,
+                        &output_file,
+                        time_limit,
+                        portfolio,
+                    ),
+                };
+                if let Err(err) = result {
+                    eprintln!("Error: {}", err);
+                    exit(&config, 1);
+                }
             }
         }
-        _ => eprintln!(
-            "Unknown command '{}'. Use 'collect', 'shorten', 'group', or 'minimize'",
+        "turnaround" => {
+            if args.len() < 3 {
+                usage(&config, "Usage: cargo run -- turnaround <vampire_proof.out> [output_file]");
+            } else {
+                let proof_file = &args[2];
+                let output_file = match args.get(3) {
+                    Some(out) => out.clone(),
+                    None => match Path::new(proof_file).file_stem().and_then(|s| s.to_str()) {
+                        Some(stem) => format!("../output/forward_{}.p", artifact::safe_name(stem)),
+                        None => {
+                            eprintln!("No output name for {}, give an output_file", proof_file);
+                            exit(&config, 1);
+                        }
+                    },
+                };
+
+                match vampire_proof::turnaround(&config, proof_file, &output_file) {
+                    Ok(msg) => println!("{}", msg),
+                    Err(err) => {
+                        eprintln!("Error: {}", err);
+                        exit(&config, 1);
+                    }
+                }
+            }
+        }
+        "proof_graph" => {
+            if args.len() < 3 {
+                usage(&config, "Usage: cargo run -- proof_graph <vampire_proof.out> [output_file]");
+            } else {
+                let proof_file = &args[2];
+                let output_file = match args.get(3) {
+                    Some(out) => out.clone(),
+                    None => match Path::new(proof_file).file_stem().and_then(|s| s.to_str()) {
+                        Some(stem) => format!("../output/{}.dot", artifact::safe_name(stem)),
+                        None => {
+                            eprintln!("No output name for {}, give an output_file", proof_file);
+                            exit(&config, 1);
+                        }
+                    },
+                };
+
+                match vampire_proof::write_refutation_dot(proof_file, &output_file) {
+                    Ok(msg) => println!("{}", msg),
+                    Err(err) => {
+                        eprintln!("Error: {}", err);
+                        exit(&config, 1);
+                    }
+                }
+            }
+        }
+        "proof-diff" => {
+            if args.len() < 4 {
+                usage(&config, "Usage: cargo run -- proof-diff <a.out> <b.out>");
+            } else if let Err(err) = proof_diff::proof_diff(&args[2], &args[3]) {
+                eprintln!("Error: {}", err);
+                exit(&config, 1);
+            }
+        }
+        "stats" => {
+            if args.len() < 3 {
+                usage(&config, "Usage: cargo run -- stats <proof_file> [--prover vampire|twee|egg]");
+            } else {
+                match stats::proof_stats(&args[2], flag_value(&args, "--prover")) {
+                    Ok(stats) => println!("{}", stats),
+                    Err(err) => {
+                        eprintln!("Error: {}", err);
+                        exit(&config, 1);
+                    }
+                }
+            }
+        }
+        "extract-steps" => {
+            if args.len() < 4 {
+                usage(
+                    &config,
+                    "Usage: cargo run -- extract-steps <vampire_proof.out> <lemma_formula_or_file> [--write <axioms.p>]"
+                );
+            } else {
+                let output = flag_value(&args, "--write");
+                match superpose::extract_steps(&config, &args[2], &args[3], output) {
+                    Ok(msg) => println!("{}", msg),
+                    Err(err) => {
+                        eprintln!("Error: {}", err);
+                        exit(&config, 1);
+                    }
+                }
+            }
+        }
+        "twee-to-sctptp" => {
+            if args.len() < 4 {
+                usage(
+                    &config,
+                    "Usage: cargo run -- twee-to-sctptp <problem.p> <twee_proof.out> [output_dir]"
+                );
+            } else {
+                let output_dir = match args.get(4) {
+                    Some(dir) => dir.clone(),
+                    None => format!("../proofs/sctptp_{}", extract_suffix(&args[2])),
+                };
+                match twee_proof::write_sctptp_proofs(&args[2], &args[3], &output_dir) {
+                    Ok(msg) => println!("{}", msg),
+                    Err(err) => {
+                        eprintln!("Error: {}", err);
+                        exit(&config, 1);
+                    }
+                }
+            }
+        }
+        "export" => {
+            if args.len() < 3 {
+                usage(&config, "Usage: cargo run -- export <input_file> [--to lean|isabelle]");
+            } else {
+                let suffix = extract_suffix(&args[2]);
+                let sections_file = format!("../output/proof_{}.json", suffix);
+                let result = itp_export::Target::parse(flag_value(&args, "--to").unwrap_or("lean"))
+                    .and_then(|target| {
+                        itp_export::export_proof(
+                            &args[2],
+                            &sections_file,
+                            "../lemmas",
+                            target,
+                            "../output",
+                        )
+                    });
+                match result {
+                    Ok(msg) => println!("{}", msg),
+                    Err(err) => {
+                        eprintln!("Error: {}", err);
+                        exit(&config, 1);
+                    }
+                }
+            }
+        }
+        "generate" => {
+            if args.len() < 4 {
+                usage(
+                    &config,
+                    "Usage: cargo run -- generate <hypothesis> <goal> [output_file]  (equation numbers such as 3957, or equations such as \"x ◇ y = y ◇ x\")"
+                );
+            } else {
+                match generate::generate(
+                    &args[2],
+                    &args[3],
+                    generate::EQUATIONS_DIR,
+                    args.get(4).map(String::as_str),
+                    "../benchmarks/generated",
+                ) {
+                    Ok(msg) => println!("{}", msg),
+                    Err(err) => {
+                        eprintln!("Error: {}", err);
+                        exit(&config, 1);
+                    }
+                }
+            }
+        }
+        "lemmas" => {
+            let lemmas_usage =
+                "Usage: cargo run -- lemmas <input_file> [list | show <name> | why <name>]";
+            if args.len() < 3 {
+                usage(&config, lemmas_usage);
+            } else {
+                let suffix = extract_suffix(&args[2]);
+                let summary_file = format!("../output/summary_{}.json", suffix);
+                let dag_file = format!("../output/dag_{}.txt", suffix);
+                let result = match (args.get(3).map(String::as_str), args.get(4)) {
+                    (None, _) | (Some("list"), _) => {
+                        lemma_browser::list_lemmas(&summary_file, &dag_file, "../lemmas")
+                    }
+                    (Some("show"), Some(name)) => {
+                        lemma_browser::show_lemma(name, &summary_file, "../lemmas", "../proofs")
+                    }
+                    (Some("why"), Some(name)) => {
+                        lemma_browser::trace_lemma(name, &summary_file, &dag_file, "../lemmas")
+                    }
+                    _ => Err(lemmas_usage.to_string()),
+                };
+                if let Err(err) = result {
+                    eprintln!("Error: {}", err);
+                    exit(&config, 1);
+                }
+            }
+        }
+        "preprocess" => {
+            if args.len() < 3 {
+                usage(
+                    &config,
+                    "Usage: cargo run -- preprocess <input_file> [output_file] [--tolerance <t>] [--depth <d>]"
+                );
+            } else {
+                let input_file = &args[2];
+                // same file name, so the reduced problem keeps the suffix of the input
+                let output_file = match args.get(3).filter(|a| !a.starts_with("--")) {
+                    Some(out) => out.clone(),
+                    None => match Path::new(input_file).file_name() {
+                        Some(name) => {
+                            format!("../output/preprocessed/{}", name.to_string_lossy())
+                        }
+                        None => {
+                            eprintln!("Error: {} does not name a problem file", input_file);
+                            exit(&config, 1);
+                        }
+                    },
+                };
+                let mut params = preprocess::SineParams::default();
+                if let Some(t) = flag_value(&args, "--tolerance") {
+                    match t.parse::<f64>() {
+                        Ok(t) if t >= 1.0 => params.tolerance = t,
+                        _ => {
+                            eprintln!("--tolerance expects a number of at least 1");
+                            exit(&config, 1);
+                        }
+                    }
+                }
+                if let Some(d) = flag_value(&args, "--depth") {
+                    match d.parse::<usize>() {
+                        Ok(d) => params.depth = Some(d),
+                        Err(_) => {
+                            eprintln!("--depth expects a number of rounds");
+                            exit(&config, 1);
+                        }
+                    }
+                }
+                match preprocess::preprocess(&config, input_file, &output_file, params) {
+                    Ok(msg) => println!("{}", msg),
+                    Err(err) => {
+                        eprintln!("Error: {}", err);
+                        exit(&config, 1);
+                    }
+                }
+            }
+        }
+        _ => usage(&config, &format!(
+            "Unknown command '{}'. Use 'init', 'collect', 'shorten', 'group', 'minimize', 'turnaround', 'proof_graph', or 'lemmas'",
             args[1]
-        ),
+        )),
     }
+    config.events.finish_phase(true);
+}
+
+/// Print `message` for a command given the wrong arguments, reporting the
+/// command as failed in the event log. The `finish_phase(true)` at the end of
+/// `main` then has no phase left to finish.
+fn usage(config: &RunConfig, message: &str) {
+    eprintln!("{}", message);
+    config.events.finish_phase(false);
+}
+
+/// Exit with `code`, reporting the command as failed in the event log
+fn exit(config: &RunConfig, code: i32) -> ! {
+    config.events.finish_phase(false);
+    std::process::exit(code)
+}
+
+/// Value following `flag` on the command line, e.g. `--use-existing-proof <file>`
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(|s| s.as_str())
 }
 
+/// Problem name the files of `path` are named after, safe for any file system
 pub fn extract_suffix(path: &str) -> String {
+    let path = path.strip_suffix(".gz").unwrap_or(path);
     let stem = Path::new(path)
         .file_stem()
         .unwrap()
@@ -98,8 +718,8 @@ pub fn extract_suffix(path: &str) -> String {
         .to_string();
 
     if let Some(stripped) = stem.strip_prefix("input_problem_") {
-        stripped.to_string()
+        artifact::safe_name(stripped)
     } else {
-        stem // fallback: whole stem
+        artifact::safe_name(&stem) // fallback: whole stem
     }
 }
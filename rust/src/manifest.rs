@@ -0,0 +1,150 @@
+use crate::BenchmarkResult;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// Expected outcome of one problem of a benchmark suite
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// file name of the problem in the input folder
+    pub problem: String,
+    /// SZS status of the Vampire run, e.g. "Theorem"
+    pub status: String,
+    /// largest acceptable length of the minimized proof
+    pub max_steps: usize,
+}
+
+/// A problem of the manifest that did worse than expected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Regression {
+    pub problem: String,
+    pub reason: String,
+}
+
+/// Read a manifest: a JSON array of `{"problem", "status", "max_steps"}` objects
+pub fn load_manifest(path: &str) -> Result<Vec<ManifestEntry>, String> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read manifest {}: {}", path, e))?;
+    serde_json::from_str(&text).map_err(|e| format!("Failed to parse manifest {}: {}", path, e))
+}
+
+/// Compare a run with the manifest. Problems of the run the manifest does not
+/// list are not checked; listed problems the run did not get to are regressions.
+pub fn regressions(manifest: &[ManifestEntry], results: &[BenchmarkResult]) -> Vec<Regression> {
+    let mut found = Vec::new();
+    for entry in manifest {
+        let result = results.iter().find(|r| {
+            Path::new(&r.file)
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy() == entry.problem)
+        });
+        let reason = match result {
+            None => Some("not run".to_string()),
+            Some(r) if r.szs_status.as_deref() != Some(entry.status.as_str()) => Some(format!(
+                "status {}, expected {}",
+                r.szs_status.as_deref().unwrap_or("unknown"),
+                entry.status
+            )),
+            Some(r) => match r.final_steps() {
+                None => Some(format!(
+                    "no minimized proof, expected at most {} steps",
+                    entry.max_steps
+                )),
+                Some(steps) if steps > entry.max_steps => Some(format!(
+                    "{} steps, expected at most {}",
+                    steps, entry.max_steps
+                )),
+                Some(_) => None,
+            },
+        };
+        if let Some(reason) = reason {
+            found.push(Regression {
+                problem: entry.problem.clone(),
+                reason,
+            });
+        }
+    }
+    found
+}
+
+pub fn print_regressions(manifest_file: &str, regressions: &[Regression]) {
+    println!("\n========== REGRESSIONS ==========");
+    for r in regressions {
+        println!("{:<45}  {}", r.problem, r.reason);
+    }
+    println!(
+        "{} regression(s) against {}",
+        regressions.len(),
+        manifest_file
+    );
+    println!("=================================");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(file: &str, status: Option<&str>, minimized: Option<usize>) -> BenchmarkResult {
+        BenchmarkResult {
+            file: file.to_string(),
+            szs_status: status.map(str::to_string),
+            vampire_steps: Some(20),
+            minimized_steps: minimized,
+            kept_original: false,
+            vampire_rules: Default::default(),
+            minimized_rules: Default::default(),
+            assumed: Vec::new(),
+            collect: None,
+        }
+    }
+
+    #[test]
+    fn reports_problems_worse_than_the_manifest() {
+        let manifest: Vec<ManifestEntry> = serde_json::from_str(
+            r#"[
+                {"problem": "a.p", "status": "Theorem", "max_steps": 10},
+                {"problem": "b.p", "status": "Theorem", "max_steps": 10},
+                {"problem": "c.p", "status": "Theorem", "max_steps": 10},
+                {"problem": "d.p", "status": "Theorem", "max_steps": 10}
+            ]"#,
+        )
+        .unwrap();
+        let results = [
+            result("../benchmarks/input1/a.p", Some("Theorem"), Some(10)),
+            result("../benchmarks/input1/b.p", Some("Theorem"), Some(12)),
+            result("../benchmarks/input1/c.p", Some("Timeout"), None),
+            result("../benchmarks/input1/e.p", Some("Theorem"), Some(99)),
+        ];
+
+        let found = regressions(&manifest, &results);
+        let reasons: Vec<_> = found
+            .iter()
+            .map(|r| (r.problem.as_str(), r.reason.as_str()))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                ("b.p", "12 steps, expected at most 10"),
+                ("c.p", "status Timeout, expected Theorem"),
+                ("d.p", "not run"),
+            ]
+        );
+    }
+
+    #[test]
+    fn kept_original_proofs_count_with_their_own_length() {
+        let manifest: Vec<ManifestEntry> =
+            serde_json::from_str(r#"[{"problem": "a.p", "status": "Theorem", "max_steps": 20}]"#)
+                .unwrap();
+        let mut longer = result("a.p", Some("Theorem"), Some(25));
+        assert!(longer.no_improvement());
+        assert_eq!(
+            regressions(&manifest, std::slice::from_ref(&longer)).len(),
+            1
+        );
+
+        longer.kept_original = true;
+        assert_eq!(longer.final_steps(), Some(20));
+        assert!(regressions(&manifest, &[longer]).is_empty());
+    }
+}
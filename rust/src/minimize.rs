@@ -1,118 +1,1133 @@
+use crate::alpha_match::canonical_form;
+use crate::annotated_proof::{AnnotatedProofBuilder, SectionKind};
+use crate::artifact::{self, write_atomic};
 use crate::dag::*;
 use crate::extract_suffix;
+use crate::formula::{format_fof, generalizations, parse_formula, skolems_to_variables, Formula};
+use crate::integrity;
+use crate::lemma_library::{self, LibraryLemma};
+use crate::lemma_store::{LemmaKind, LemmaStore};
+use crate::naming::NameAllocator;
+use crate::process::ProcessRunner;
+use crate::proof_step::{detect_prover, format_breakdown, proof_steps, ProofRole};
+use crate::provenance::{strip, write_with_provenance};
+use crate::prover_stats::{LemmaShape, ProverStats};
 use crate::prover_wrapper::*;
+use crate::run_config::{Backend, RunConfig, SkolemPolicy};
+use crate::summary;
 use crate::superpose::*;
+use crate::tptp_file::{AnnotatedFormula, TptpFile};
 use crate::utils::*;
 use regex::Regex;
-use std::collections::{BTreeMap, BTreeSet};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+/// One candidate evaluated by `minimize`: a lemma the conjecture is proved
+/// from directly, or a root/history combination of the search. `history` is
+/// `None` for direct proofs and the root-only fallback, `steps` is `None` if
+/// Twee found no proof.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TraceCandidate {
+    /// traces written before direct proofs were tried hold only search candidates
+    #[serde(default)]
+    pub kind: CandidateKind,
+    pub root: String,
+    pub history: Option<String>,
+    pub steps: Option<usize>,
+    /// where the parts of the candidate proof were taken from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub choices: Vec<SourceChoice>,
+}
+
+impl TraceCandidate {
+    fn new(kind: CandidateKind, root: &str, history: Option<&str>) -> TraceCandidate {
+        TraceCandidate {
+            kind,
+            root: root.to_string(),
+            history: history.map(str::to_string),
+            steps: None,
+            choices: Vec::new(),
+        }
+    }
+}
+
+/// How a candidate proves the conjecture
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CandidateKind {
+    /// the conjecture proved with `root` appended on its own, before the search
+    Direct,
+    /// the conjecture proved from `root`, which the search proves with its
+    /// history lemma or on its own
+    #[default]
+    Search,
+}
+
+impl CandidateKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            CandidateKind::Direct => "direct",
+            CandidateKind::Search => "search",
+        }
+    }
+}
+
+/// Which backend a part of a candidate proof was built on, with the length
+/// of the proof each backend offered and why one was chosen
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SourceChoice {
+    /// "start" for the steps the candidate starts from, "history" for the
+    /// proof of a history lemma that the Vampire proof derives as well
+    pub part: String,
+    /// superposition steps taken from the Vampire proof
+    pub vampire_steps: usize,
+    /// steps of the prover proofs: of the dependencies for "start", of the
+    /// history lemma for "history"
+    pub twee_steps: usize,
+    pub chosen: Backend,
+    pub reason: String,
+}
+
+impl SourceChoice {
+    /// The steps a candidate starts from: the superposition steps, unless
+    /// the dependency proofs are shorter or there are no superposition steps,
+    /// or the backend `forced`
+    fn start(forced: Option<Backend>, vampire_steps: usize, twee_steps: usize) -> SourceChoice {
+        let (chosen, reason) = match forced {
+            Some(backend) => (backend, "forced by --force-backend"),
+            None if twee_steps == 0 => (Backend::Vampire, "no dependency proofs"),
+            None if vampire_steps == 0 => (Backend::Twee, "no superposition steps"),
+            None if vampire_steps <= twee_steps => (Backend::Vampire, "not longer"),
+            None => (Backend::Twee, "shorter"),
+        };
+        SourceChoice::new("start", vampire_steps, twee_steps, chosen, reason)
+    }
+
+    /// The proof of a history lemma the superposition steps derive as well:
+    /// the prover proof, unless it is longer than the steps, or the backend `forced`
+    fn history(forced: Option<Backend>, vampire_steps: usize, twee_steps: usize) -> SourceChoice {
+        let (chosen, reason) = match forced {
+            Some(backend) => (backend, "forced by --force-backend"),
+            None if twee_steps <= vampire_steps => (Backend::Twee, "not longer"),
+            None => (Backend::Vampire, "shorter"),
+        };
+        SourceChoice::new("history", vampire_steps, twee_steps, chosen, reason)
+    }
+
+    fn new(
+        part: &str,
+        vampire_steps: usize,
+        twee_steps: usize,
+        chosen: Backend,
+        reason: &str,
+    ) -> SourceChoice {
+        SourceChoice {
+            part: part.to_string(),
+            vampire_steps,
+            twee_steps,
+            chosen,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Every candidate of a minimization run, in evaluation order, and the one picked
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CandidateTrace {
+    pub input: String,
+    pub candidates: Vec<TraceCandidate>,
+    pub best: Option<usize>,
+}
 
-/// Tries several candidate root lemmas and picks the best
+/// How `prove_lemma` runs the provers for one search: which provers to ask,
+/// which axiom sets were screened for consistency, and during a replay, where
+/// to keep every problem given to them
+pub struct ProverRuns<'a> {
+    /// the settings of the run
+    config: &'a RunConfig,
+    /// Phase 1 winners by lemma shape, with `--adaptive-provers`
+    stats: Option<ProverStats>,
+    /// the appended lemmas screened so far, see `inconsistent_lemma`
+    screen: ConsistencyScreen<'a>,
+    /// where a replay keeps every problem given to a prover and its output
+    replay_dir: Option<PathBuf>,
+    /// prover runs kept so far
+    kept: Cell<usize>,
+}
+
+impl<'a> ProverRuns<'a> {
+    /// Runs for a search, screening the appended lemmas through `runner`
+    fn new(
+        runner: &'a dyn ProcessRunner,
+        config: &'a RunConfig,
+        stats: Option<ProverStats>,
+        replay_dir: Option<PathBuf>,
+    ) -> ProverRuns<'a> {
+        ProverRuns {
+            config,
+            stats,
+            screen: ConsistencyScreen::new(runner, config),
+            replay_dir,
+            kept: Cell::new(0),
+        }
+    }
+
+    /// The provers to ask for a proof of `problem`, in order
+    fn provers_for(&self, problem: &str) -> Result<Vec<&'static str>, String> {
+        match &self.stats {
+            Some(stats) => Ok(stats.provers_for(LemmaShape::of(problem)?)),
+            None => Ok(vec!["twee"]),
+        }
+    }
+
+    /// During a replay, keep a prover's problem and its output as numbered files
+    fn keep(&self, prover: &str, conjecture: Option<&str>, problem: &str, proof: Option<&str>) {
+        let Some(dir) = &self.replay_dir else {
+            return;
+        };
+        let n = self.kept.get() + 1;
+        self.kept.set(n);
+        let name = format!("{:02}_{}", n, conjecture.unwrap_or("conjecture"));
+        let _ = write_atomic(dir.join(format!("{}.p", name)), problem);
+        let no_proof = format!("% {} found no proof\n", prover);
+        let _ = write_atomic(
+            dir.join(format!("{}.out", name)),
+            proof.unwrap_or(&no_proof),
+        );
+    }
+}
+
+/// How one `prove_lemma` call asks the provers: the runs of the search, and
+/// the most steps a proof may take to still be of use
+#[derive(Clone, Copy)]
+pub struct ProverOptions<'a> {
+    pub runs: &'a ProverRuns<'a>,
+    pub max_steps: Option<usize>,
+}
+
+/// Exit code of `minimize` when Phase 1 left nothing to minimize
+pub const EXIT_NO_LEMMAS: i32 = 2;
+
+/// Exit code of `minimize --assert-max-steps` when the final proof is longer
+pub const EXIT_TOO_MANY_STEPS: i32 = 3;
+
+/// Why `minimize` stopped. The first variants are the usual ways Phase 1
+/// leaves nothing to work with; the CLI prints advice for them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MinimizeError {
+    /// there is no summary file, Phase 1 did not run
+    MissingSummary(String),
+    /// the summary is not a Phase 1 summary
+    BadSummary(String, String),
+    /// Phase 1 extracted no lemmas from the Vampire proof
+    NoLemmas(String),
+    /// Phase 1 extracted lemmas, but no prover proved any of them
+    NoProvedLemmas {
+        summary: String,
+        lemmas: usize,
+    },
+    /// an artifact of Phase 1 changed after `collect` recorded it
+    StaleArtifact(String),
+    /// the final proof is longer than `--assert-max-steps` allows
+    TooManySteps {
+        steps: usize,
+        max: usize,
+    },
+    /// `--assert-max-steps` was given, but the final proof assumes lemmas no
+    /// prover proved (`--assume-unproved`), so it proves nothing on its own
+    ConditionalProof {
+        steps: usize,
+        hypotheses: Vec<String>,
+    },
+    Failed(String),
+}
+
+impl MinimizeError {
+    /// What to do about it, if there is a usual fix
+    pub fn advice(&self) -> Option<&'static str> {
+        match self {
+            MinimizeError::MissingSummary(_) => {
+                Some("Run `collect <input_file>` (after `run_vampire`) before `minimize`.")
+            }
+            MinimizeError::BadSummary(..) => {
+                Some("The summary was not written by `collect`; run `collect <input_file>` again.")
+            }
+            MinimizeError::NoLemmas(_) => Some(
+                "Check that `run_vampire` found a proof and that the OCaml lemma extractor is built.",
+            ),
+            MinimizeError::NoProvedLemmas { .. } => Some(
+                "Check that the provers in ../bin run, or give them more time; the lemmas are in ../lemmas.",
+            ),
+            MinimizeError::StaleArtifact(_) => Some(
+                "Run `collect <input_file>` again so the summary, the Vampire proof and the lemmas belong together.",
+            ),
+            MinimizeError::ConditionalProof { .. } => Some(
+                "Run without `--assume-unproved`, or prove the assumed lemmas, to assert the length of a complete proof.",
+            ),
+            MinimizeError::TooManySteps { .. } | MinimizeError::Failed(_) => None,
+        }
+    }
+
+    /// `EXIT_NO_LEMMAS` when Phase 1 left nothing to minimize,
+    /// `EXIT_TOO_MANY_STEPS` when the proof fails `--assert-max-steps`, 1 otherwise
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            MinimizeError::TooManySteps { .. } | MinimizeError::ConditionalProof { .. } => {
+                EXIT_TOO_MANY_STEPS
+            }
+            MinimizeError::StaleArtifact(_) | MinimizeError::Failed(_) => 1,
+            _ => EXIT_NO_LEMMAS,
+        }
+    }
+}
+
+impl fmt::Display for MinimizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MinimizeError::MissingSummary(file) => {
+                write!(f, "No Phase 1 summary {}", file)
+            }
+            MinimizeError::BadSummary(file, err) => {
+                write!(f, "Cannot read the Phase 1 summary {}: {}", file, err)
+            }
+            MinimizeError::NoLemmas(file) => {
+                write!(f, "Phase 1 extracted no lemmas, {} is empty", file)
+            }
+            MinimizeError::NoProvedLemmas { summary, lemmas } => write!(
+                f,
+                "No prover proved any of the {} lemmas of Phase 1, {} is empty",
+                lemmas, summary
+            ),
+            MinimizeError::TooManySteps { steps, max } => write!(
+                f,
+                "The final proof has {} steps, more than the asserted {}",
+                steps, max
+            ),
+            MinimizeError::ConditionalProof { steps, hypotheses } => write!(
+                f,
+                "The final proof has {} steps, but only under the unproved lemmas {}",
+                steps,
+                hypotheses.join(", ")
+            ),
+            MinimizeError::StaleArtifact(err) | MinimizeError::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<String> for MinimizeError {
+    fn from(err: String) -> Self {
+        MinimizeError::Failed(err)
+    }
+}
+
+impl From<&str> for MinimizeError {
+    fn from(err: &str) -> Self {
+        MinimizeError::Failed(err.to_string())
+    }
+}
+
+/// Whether the Phase 1 summary has lemmas to build on, and if not, why. The
+/// artifacts `collect` handed over must not have changed since.
+fn check_summary(
+    config: &RunConfig,
+    summary_file: &str,
+    lemmas_dir: &str,
+) -> Result<(), MinimizeError> {
+    let text = fs::read_to_string(summary_file)
+        .map_err(|_| MinimizeError::MissingSummary(summary_file.to_string()))?;
+    let summary = summary::parse(&text)
+        .map_err(|e| MinimizeError::BadSummary(summary_file.to_string(), e))?;
+    if summary.values().any(|entry| entry.is_usable(config)) {
+        return integrity::verify(&integrity::record_file(summary_file), lemmas_dir)
+            .map_err(MinimizeError::StaleArtifact);
+    }
+    let lemmas = LemmaStore::load(lemmas_dir)?;
+    let extracted: usize = LemmaKind::ALL
+        .into_iter()
+        .map(|kind| lemmas.iter_by_kind(kind).count())
+        .sum();
+    if extracted == 0 {
+        Err(MinimizeError::NoLemmas(summary_file.to_string()))
+    } else {
+        Err(MinimizeError::NoProvedLemmas {
+            summary: summary_file.to_string(),
+            lemmas: extracted,
+        })
+    }
+}
+
+/// Renderings of the minimized proof written next to the text proof, asked for with `--emit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    /// the derivation as a DOT graph, `<output>/proof_<suffix>.dot`
+    Graph,
+}
+
+impl Emit {
+    pub fn parse(name: &str) -> Result<Emit, String> {
+        match name {
+            "graph" => Ok(Emit::Graph),
+            _ => Err(format!("Unknown --emit value '{}', expected graph", name)),
+        }
+    }
+}
+
+/// Where `minimize` reads the lemmas and the Phase 1 proofs of a problem and
+/// writes its artifacts, relative to the working directory unless absolute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dirs {
+    pub lemmas: String,
+    pub proofs: String,
+    /// the Twee proofs of Phase 1, whose lemmas are reused
+    pub twee_proofs: String,
+    /// the DAG, lemmas, proof, trace and replays of the search
+    pub output: String,
+}
+
+impl Dirs {
+    /// `lemmas/`, `proofs/`, `proofs/twee_tmp/` and `output/` of the workspace at `root`
+    pub fn in_workspace(root: &str) -> Dirs {
+        Dirs::new(
+            &Path::new(root).join("lemmas").to_string_lossy(),
+            &Path::new(root).join("proofs").to_string_lossy(),
+            &Path::new(root).join("output").to_string_lossy(),
+        )
+    }
+
+    /// The Twee proofs are in `twee_tmp/` below `proofs`
+    pub fn new(lemmas: &str, proofs: &str, output: &str) -> Dirs {
+        Dirs {
+            lemmas: lemmas.to_string(),
+            proofs: proofs.to_string(),
+            twee_proofs: Path::new(proofs)
+                .join("twee_tmp")
+                .to_string_lossy()
+                .to_string(),
+            output: output.to_string(),
+        }
+    }
+
+    /// `<output>/<kind>_<suffix>.<extension>`, the name of every artifact of a problem
+    fn artifact(&self, kind: &str, suffix: &str, extension: &str) -> String {
+        Path::new(&self.output)
+            .join(format!("{}_{}.{}", kind, suffix, extension))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Where a replay of a candidate of `input_file` keeps its files
+    fn replay(&self, input_file: &str) -> PathBuf {
+        Path::new(&self.output).join(format!("replay_{}", extract_suffix(input_file)))
+    }
+}
+
+impl Default for Dirs {
+    /// the workspace around `rust/`
+    fn default() -> Dirs {
+        Dirs::in_workspace("..")
+    }
+}
+
+/// Tries several candidate root lemmas and picks the best.
+/// The evaluated candidates are written to `<output>/trace_<suffix>.json`.
+/// With `max_steps`, a final proof longer than that is an error, after the
+/// proof is written, and so is one that assumes unproved lemmas.
 pub fn try_minimize(
+    config: &RunConfig,
     input_file: &str,
     vampire_file: &str,
     summary_file: &str,
+    dirs: &Dirs,
+    emit: &[Emit],
+    max_steps: Option<usize>,
+) -> Result<String, MinimizeError> {
+    check_summary(config, summary_file, &dirs.lemmas)?;
+    let (message, steps) = search(
+        config,
+        input_file,
+        vampire_file,
+        summary_file,
+        dirs,
+        None,
+        emit,
+    )?;
+    match (steps, max_steps) {
+        (Some((steps, _)), Some(max)) if steps > max => {
+            Err(MinimizeError::TooManySteps { steps, max })
+        }
+        (Some((steps, hypotheses)), Some(_)) if !hypotheses.is_empty() => {
+            Err(MinimizeError::ConditionalProof { steps, hypotheses })
+        }
+        _ => Ok(message),
+    }
+}
+
+/// Re-evaluates candidate `index` of `trace_file` (the picked one by default),
+/// keeping the DAG, the lemmas and every Twee problem and proof in `<output>/replay_<suffix>`
+pub fn replay_candidate(
+    config: &RunConfig,
+    input_file: &str,
+    vampire_file: &str,
+    summary_file: &str,
+    dirs: &Dirs,
+    trace_file: &str,
+    index: Option<usize>,
+) -> Result<String, MinimizeError> {
+    check_summary(config, summary_file, &dirs.lemmas)?;
+    let text = fs::read_to_string(trace_file)
+        .map_err(|e| format!("Failed to read trace {}: {}", trace_file, e))?;
+    let trace: CandidateTrace = artifact::parse_json(&text)
+        .map_err(|e| format!("Failed to parse trace {}: {}", trace_file, e))?;
+    let index = index
+        .or(trace.best)
+        .ok_or("Trace has no picked candidate, choose one with --candidate <n>")?;
+    let candidate = trace.candidates.get(index).ok_or_else(|| {
+        format!(
+            "Trace has {} candidates, no candidate {}",
+            trace.candidates.len(),
+            index
+        )
+    })?;
+
+    let replay_dir = dirs.replay(input_file);
+    fs::create_dir_all(&replay_dir)
+        .map_err(|e| format!("Failed to create {}: {}", replay_dir.display(), e))?;
+    println!(
+        "[INFO] Replaying {} candidate {}: root {} with history {}, keeping intermediate files in {}",
+        candidate.kind.name(),
+        index,
+        candidate.root,
+        candidate.history.as_deref().unwrap_or("none"),
+        replay_dir.display()
+    );
+
+    let replay = Replay {
+        candidate,
+        dir: replay_dir,
+    };
+    let result = search(
+        config,
+        input_file,
+        vampire_file,
+        summary_file,
+        dirs,
+        Some(&replay),
+        &[],
+    );
+    Ok(result?.0)
+}
+
+/// A replay in progress: the recorded candidate and where its files go.
+/// Handed to `search` explicitly, so a search that is no replay has no replay
+/// state anywhere.
+struct Replay<'a> {
+    candidate: &'a TraceCandidate,
+    dir: PathBuf,
+}
+
+/// Whether the search evaluates the `kind` candidate `root` with `history`:
+/// always, unless replaying another candidate
+fn evaluates(
+    replay: Option<&TraceCandidate>,
+    kind: CandidateKind,
+    root: &str,
+    history: Option<&str>,
+) -> bool {
+    replay.is_none_or(|c| c.kind == kind && c.root == root && c.history.as_deref() == history)
+}
+
+/// What a history candidate gives the provers, by the canonical forms of the
+/// formulas: root, history lemma and the lemmas and superposition steps the
+/// history lemma is proved from, with the names and proof lengths of the
+/// dependencies. Different roots often come down to the same formulas under
+/// other names, e.g. a single and a history lemma stating the same.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CandidateKey {
+    root: String,
+    history: String,
+    start: Vec<String>,
+    /// dependency names with the steps of their proofs
+    dependencies: Vec<(String, usize)>,
+    superposition_steps: usize,
+}
+
+impl CandidateKey {
+    /// `root` and `history` are formulas, `start` the formulas they are proved from
+    fn new(
+        root: &str,
+        history: &str,
+        start: &[String],
+        dependencies: &[(String, usize)],
+        superposition_steps: usize,
+    ) -> CandidateKey {
+        let mut start: Vec<String> = start.iter().map(|f| canonical_form(f)).collect();
+        start.sort();
+        start.dedup();
+        let mut dependencies = dependencies.to_vec();
+        dependencies.sort();
+        CandidateKey {
+            root: canonical_form(root),
+            history: canonical_form(history),
+            start,
+            dependencies,
+            superposition_steps,
+        }
+    }
+}
+
+/// History candidates a search evaluated. A repeat gives the provers the same
+/// problems up to names, so they are expected to find proofs of the same
+/// length; only candidates that were evaluated to the end are recorded, a
+/// repeat of one that was skipped or found no proof is still tried.
+#[derive(Default)]
+struct EvaluatedCandidates {
+    /// key -> (root, history) evaluated with it
+    seen: HashMap<CandidateKey, (String, String)>,
+    /// candidates skipped as repeats
+    repeats: usize,
+}
+
+impl EvaluatedCandidates {
+    /// The root and history evaluated before with the same `key`, counted as a repeat
+    fn repeat_of(&mut self, key: &CandidateKey) -> Option<(String, String)> {
+        let first = self.seen.get(key).cloned();
+        if first.is_some() {
+            self.repeats += 1;
+        }
+        first
+    }
+
+    /// Record that `root` with `history` was evaluated with `key`
+    fn record(&mut self, key: CandidateKey, root: &str, history: &str) {
+        self.seen
+            .entry(key)
+            .or_insert_with(|| (root.to_string(), history.to_string()));
+    }
+}
+
+/// Record the steps of the candidate evaluated last, in the event log of `config` as well
+fn record_steps(config: &RunConfig, trace: &mut [TraceCandidate], steps: usize) {
+    if let Some(last) = trace.last_mut() {
+        last.steps = Some(steps);
+        config
+            .events
+            .candidate_evaluated(&last.root, last.history.as_deref(), steps);
+    }
+}
+
+/// Record where a part of the candidate evaluated last was taken from
+fn record_choice(trace: &mut [TraceCandidate], choice: SourceChoice) {
+    println!(
+        "   [INFO] {} proof from {}: {} Vampire steps, {} Twee steps ({})",
+        choice.part,
+        choice.chosen.name(),
+        choice.vampire_steps,
+        choice.twee_steps,
+        choice.reason
+    );
+    if let Some(last) = trace.last_mut() {
+        last.choices.push(choice);
+    }
+}
+
+/// Longest proof a part of a candidate may take to beat the shortest total
+/// so far, `best_total`, when `spent` steps of the candidate are counted
+/// already. Without `cap_steps` (`--cap-steps`) there is no cap.
+fn step_cap(cap_steps: bool, best_total: Option<usize>, spent: usize) -> Option<usize> {
+    if !cap_steps {
+        return None;
+    }
+    best_total.map(|best| best.saturating_sub(spent + 1))
+}
+
+/// The lemmas of a DAG as TPTP lemma lines
+fn format_dag_lemmas(dag_lemmas: &BTreeMap<String, String>) -> Result<String, String> {
+    let mut lemmas_text = String::new();
+    for (lemma_name, formula) in dag_lemmas {
+        lemmas_text.push_str(&format_fof(lemma_name, "lemma", formula)?);
+        lemmas_text.push('\n');
+    }
+    Ok(lemmas_text)
+}
+
+/// The Phase 1 proof of `lemma` and its steps
+fn load_lemma_proof(proofs_dir: &str, lemma: &str) -> Result<(String, usize), String> {
+    let actual_file = select_actual_lemma(proofs_dir, lemma)
+        .ok_or_else(|| format!("No proof file found for {}", lemma))?;
+    // try different variants
+    let ext = [
+        format!("{}/{}.proof", proofs_dir, actual_file),
+        format!("{}/{}_twee.proof", proofs_dir, actual_file),
+        format!("{}/{}_vampire.proof", proofs_dir, actual_file),
+    ];
+
+    let path = ext
+        .iter()
+        .find(|p| Path::new(p).exists())
+        .ok_or_else(|| format!("No proof file found for {} in any variant", lemma))?;
+
+    let proof = fs::read_to_string(path).map_err(|_| format!("Cannot read proof file {}", path))?;
+
+    // the prover is the suffix of the file found, `select_actual_lemma`
+    // leaves it off for names starting with `a`, such as abstract lemmas
+    let prover = Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.rsplit('_').next())
+        .ok_or_else(|| format!("Cannot extract prover from filename {}", path))?
+        .to_string();
+
+    lemma_proof(lemma, &prover, proof)
+}
+
+/// The lemmas `best_direct_proof` appends on their own: the precomputed
+/// lemmas, with their proofs in `proofs_dir`, and the lemmas of other problems
+struct DirectLemmas<'a> {
+    precomputed: &'a PrecomputedLemmas,
+    proofs_dir: &'a str,
+    library: &'a [LibraryLemma],
+}
+
+/// Fast path before the candidate search: prove the conjecture with each
+/// precomputed lemma appended on its own. Returns the shortest of these
+/// proofs as (steps, lemma, annotated proof); the lemma's own proof counts
+/// only if the conjecture proof uses it.
+fn best_direct_proof(
+    input_content: &str,
+    lemmas: &LemmaStore,
+    candidates: DirectLemmas,
+    runs: &ProverRuns,
+    replay: Option<&TraceCandidate>,
+    trace: &mut Vec<TraceCandidate>,
+) -> Result<Option<(usize, String, AnnotatedProofBuilder)>, String> {
+    let DirectLemmas {
+        precomputed,
+        proofs_dir,
+        library,
+    } = candidates;
+    // (lemma, formula, proof, steps) of the precomputed lemmas, then of the
+    // lemmas other problems offer
+    let mut direct: Vec<(String, String, String, usize)> = Vec::new();
+    for (lemma, info) in &precomputed.all_lemmas {
+        if !evaluates(replay, CandidateKind::Direct, lemma, None) {
+            continue;
+        }
+        // lemmas containing Skolem constants are handled by the configured policy
+        let Some(formula) =
+            root_formula_for(runs.config, lemma, info.formula.clone(), input_content)?
+        else {
+            continue;
+        };
+        match load_lemma_proof(proofs_dir, lemma) {
+            Ok((proof, steps)) => direct.push((lemma.clone(), formula, proof, steps)),
+            Err(err) => println!("   [WARN] {}, no direct proof with it", err),
+        }
+    }
+    for borrowed in library {
+        let lemma = borrowed.axiom_name();
+        if evaluates(replay, CandidateKind::Direct, &lemma, None) {
+            direct.push((
+                lemma,
+                borrowed.formula.clone(),
+                borrowed.attributed_proof(),
+                borrowed.steps,
+            ));
+        }
+    }
+
+    let mut best: Option<(usize, String, AnnotatedProofBuilder)> = None;
+    let cap_steps = runs.config.cap_steps;
+    for (lemma, formula, lemma_proof, lemma_steps) in direct {
+        trace.push(TraceCandidate::new(CandidateKind::Direct, &lemma, None));
+
+        let Some((proof, proof_steps)) = prove_lemma(
+            input_content,
+            lemmas,
+            None,
+            None,
+            vec![(formula.as_str(), lemma.as_str())],
+            None,
+            ProverOptions {
+                runs,
+                max_steps: step_cap(cap_steps, best.as_ref().map(|(steps, _, _)| *steps), 0),
+            },
+        )?
+        else {
+            continue;
+        };
+        let mut annotated_proof = AnnotatedProofBuilder::new(input_content);
+        if proof_uses_lemma(&proof, &lemma) {
+            annotated_proof.add(
+                SectionKind::Root,
+                Some(lemma.as_str()),
+                &lemma_proof,
+                lemma_steps,
+            );
+        }
+        annotated_proof.add(SectionKind::Conjecture, None, &proof, proof_steps);
+        let steps = annotated_proof.total_steps();
+        record_steps(runs.config, trace, steps);
+        println!(
+            "   [INFO] Direct proof with {} takes {} steps",
+            lemma, steps
+        );
+        if best
+            .as_ref()
+            .is_none_or(|(best_steps, _, _)| steps < *best_steps)
+        {
+            best = Some((steps, lemma, annotated_proof));
+        }
+    }
+    Ok(best)
+}
+
+/// The formula of `root_lemma` to build on, or `None` if the root is skipped.
+/// Roots with Skolem constants are skipped, generalized or kept as they are,
+/// see `SkolemPolicy`.
+fn root_formula_for(
+    config: &RunConfig,
+    root_lemma: &str,
+    formula: String,
+    input_content: &str,
+) -> Result<Option<String>, String> {
+    root_formula_with(
+        &RecordingRunner::local(config),
+        config,
+        root_lemma,
+        formula,
+        input_content,
+    )
+}
+
+/// `root_formula_for` checking generalized roots through `runner`.
+/// A root that cannot be parsed or generalized is skipped with a warning.
+fn root_formula_with(
+    runner: &dyn ProcessRunner,
+    config: &RunConfig,
+    root_lemma: &str,
+    formula: String,
+    input_content: &str,
+) -> Result<Option<String>, String> {
+    let skolem_re = Regex::new(r"\bsK\d+\b").unwrap();
+    if !skolem_re.is_match(&formula) {
+        return Ok(Some(formula));
+    }
+    match config.skolems {
+        SkolemPolicy::Skip => {
+            println!(
+                "[DEBUG] Skipping root lemma {} due to Skolem constants in formula: {}",
+                root_lemma, formula
+            );
+            Ok(None)
+        }
+        SkolemPolicy::Keep => {
+            println!(
+                "[INFO] Keeping the Skolem constants of root lemma {} as fresh constants",
+                root_lemma
+            );
+            Ok(Some(formula))
+        }
+        SkolemPolicy::Generalize => {
+            let generalized = parse_formula(&formula)
+                .and_then(|parsed| skolems_to_variables(&[parsed]))
+                .map(|(mut turned, _)| turned.remove(0).universal_closure().to_string())
+                .and_then(|g| Ok((format_fof(root_lemma, "conjecture", &g)?, g)));
+            let (conjecture, generalized) = match generalized {
+                Ok(conjecture) => conjecture,
+                Err(err) => {
+                    println!(
+                        "[WARN] Skipping root lemma {}, cannot generalize it: {}",
+                        root_lemma, err
+                    );
+                    return Ok(None);
+                }
+            };
+            // the generalized lemma is stronger, it has to follow from the axioms again
+            let problem = format!("{}\n{}", remove_conjectures(input_content)?, conjecture);
+            if proves_quickly(runner, config, &problem) {
+                println!(
+                    "[INFO] Generalized root lemma {} to {}",
+                    root_lemma, generalized
+                );
+                Ok(Some(generalized))
+            } else {
+                println!(
+                    "[DEBUG] Skipping root lemma {}, its generalization {} was not proved",
+                    root_lemma, generalized
+                );
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// `formula` made more general if `--generalize-lemmas` is set: repeated
+/// subterms become variables as long as Vampire still proves the result from
+/// the axioms, so the lemma applies to more sub-proofs. Each formula is
+/// generalized once per search, `generalized` keeps the results.
+fn generalize_lemma(
+    config: &RunConfig,
+    name: &str,
+    formula: String,
+    input_content: &str,
+    generalized: &mut HashMap<String, String>,
+) -> String {
+    if !config.generalize_lemmas {
+        return formula;
+    }
+    generalize_with(
+        &RecordingRunner::local(config),
+        config,
+        name,
+        formula,
+        input_content,
+        generalized,
+    )
+}
+
+/// `generalize_lemma` with the prover runs going to `runner`. A formula
+/// that cannot be generalized is kept as it is.
+fn generalize_with(
+    runner: &dyn ProcessRunner,
+    config: &RunConfig,
+    name: &str,
+    formula: String,
+    input_content: &str,
+    generalized: &mut HashMap<String, String>,
+) -> String {
+    if let Some(general) = generalized.get(&formula) {
+        return general.clone();
+    }
+    let general = match most_general(runner, config, name, &formula, input_content) {
+        Ok(general) => general,
+        Err(err) => {
+            println!(
+                "[WARN] Cannot generalize {}, keeping it as it is: {}",
+                name, err
+            );
+            formula.clone()
+        }
+    };
+    generalized.insert(formula, general.clone());
+    general
+}
+
+/// The most general form of `formula` that Vampire proves from the axioms
+fn most_general(
+    runner: &dyn ProcessRunner,
+    config: &RunConfig,
+    name: &str,
+    formula: &str,
+    input_content: &str,
 ) -> Result<String, String> {
-    let lemmas_dir = "../lemmas".to_string();
-    let proofs_dir = "../proofs".to_string();
-    let twee_proofs_dir = "../proofs/twee_tmp".to_string();
+    let axioms = remove_conjectures(input_content)?;
+    let holds = |candidate: &Formula| {
+        let conjecture = format_fof(name, "conjecture", &candidate.to_string());
+        conjecture.is_ok_and(|c| proves_quickly(runner, config, &format!("{}\n{}", axioms, c)))
+    };
+    let original = parse_formula(formula)?;
+    let mut current = original.clone();
+    // every accepted generalization has fewer symbols, so this ends
+    while let Some(general) = generalizations(&current).into_iter().find(|g| holds(g)) {
+        current = general;
+    }
+    if current != original {
+        let general = current.to_string();
+        println!("[INFO] Generalized {} to {}", name, general);
+        Ok(general)
+    } else {
+        Ok(formula.to_string())
+    }
+}
+
+/// Steps of a final proof and the unproved lemmas it assumes
+type FinalProof = (usize, Vec<String>);
+
+/// The search behind `try_minimize` and `replay_candidate`. Besides the
+/// message, returns the final proof unless replaying.
+fn search(
+    config: &RunConfig,
+    input_file: &str,
+    vampire_file: &str,
+    summary_file: &str,
+    dirs: &Dirs,
+    replaying: Option<&Replay>,
+    emit: &[Emit],
+) -> Result<(String, Option<FinalProof>), String> {
+    let replay = replaying.map(|r| r.candidate);
+    let lemmas = LemmaStore::load(&dirs.lemmas)?;
+    let proofs_dir = dirs.proofs.clone();
+    let twee_proofs_dir = dirs.twee_proofs.clone();
     let input_content = fs::read_to_string(&input_file)
         .map_err(|e| format!("Failed to read input file {}: {}", input_file, e))?;
 
     let suffix = extract_suffix(input_file);
-    let dag_with_suffix = format!("../output/dag_{}.txt", suffix);
-    let lemmas_with_suffix = format!("../output/lemmas_{}.p", suffix);
-    let proof_with_suffix = format!("../output/proof_{}.out", suffix);
-
-    let summary_data: serde_json::Value =
-        serde_json::from_str(&fs::read_to_string(&summary_file).map_err(|e| e.to_string())?)
-            .map_err(|e| e.to_string())?;
-
-    let max_key = summary_data
-        .as_object()
-        .ok_or("summary.json should contain an object")?
-        .keys()
-        .filter_map(|k| k.parse::<u32>().ok())
-        .max()
-        .ok_or("summary.json is empty")?;
+    let dag_with_suffix = dirs.artifact("dag", &suffix, "txt");
+    let lemmas_with_suffix = dirs.artifact("lemmas", &suffix, "p");
+    let proof_with_suffix = dirs.artifact("proof", &suffix, "out");
+    let sections_with_suffix = dirs.artifact("proof", &suffix, "json");
+    let graph_with_suffix = dirs.artifact("proof", &suffix, "dot");
+
+    let summary_data = summary::load(summary_file)?;
+
+    let max_key = *summary_data.keys().max().ok_or("summary.json is empty")?;
+
+    let stats = if config.adaptive_provers {
+        let stats = ProverStats::from_summary(summary_file, &dirs.lemmas).unwrap_or_else(|e| {
+            println!("[WARN] No prover statistics, using the defaults: {}", e);
+            ProverStats::default()
+        });
+        println!(
+            "[INFO] Choosing provers from {} Phase 1 lemmas",
+            stats.samples()
+        );
+        Some(stats)
+    } else {
+        None
+    };
+    // a replay keeps every prover run next to its other files
+    let runner = RecordingRunner::local(config);
+    let runs = ProverRuns::new(&runner, config, stats, replaying.map(|r| r.dir.clone()));
 
     let mut global_best: Option<(
-        usize,  // lemma_count
-        usize,  // steps_total
-        String, // root_lemma
-        String, // best_history
-        String, // annotated_proof
-        String, // dag_text
-        String, // lemmas_text
+        usize,                 // lemma_count
+        usize,                 // steps_total
+        String,                // root_lemma
+        String,                // best_history
+        AnnotatedProofBuilder, // annotated_proof
+        String,                // dag_text
+        String,                // lemmas_text
+        CandidateKind,         // kind
     )> = None;
 
     // precompute lemmas
-    let precomputed = precompute_lemmas(&proofs_dir, &lemmas_dir, &twee_proofs_dir)?;
+    let precomputed = precompute_lemmas(&proofs_dir, &lemmas, &twee_proofs_dir)?;
+    // lemmas of other problems of the benchmark, with `--lemma-library`
+    let library =
+        lemma_library::offered_for(config.lemma_library.as_deref(), &suffix, &input_content)?;
+    if !library.is_empty() {
+        println!(
+            "[INFO] {} lemmas of other problems offered by the lemma library",
+            library.len()
+        );
+    }
 
-    let mut offset = 1;
+    // most useful lemmas first; the last lemma is never a root
+    let mut roots = summary::by_usefulness(&summary_data)
+        .into_iter()
+        .filter(|&n| n < max_key);
     let mut accepted = 0;
     let max_candidates = 4;
-
-    while accepted < max_candidates && offset < max_key {
-        let key = (max_key - offset).to_string();
-        offset += 1;
-
-        // check if key exists in summary_data
-        let entry = match summary_data.get(&key) {
-            Some(e) => e,
-            None => {
-                // key not found in summary, skipping.
-                continue;
-            }
+    let mut trace: Vec<TraceCandidate> = Vec::new();
+    let mut evaluated = EvaluatedCandidates::default();
+    // formula -> its generalization, with `--generalize-lemmas`
+    let mut generalized: HashMap<String, String> = HashMap::new();
+    // shortest total of any candidate so far, caps the conjecture proofs of later ones
+    let mut best_total: Option<usize> = None;
+    let cap_steps = config.cap_steps;
+
+    // the conjecture proved directly with a single lemma is the bound to beat
+    if let Some((steps, lemma, annotated_proof)) = best_direct_proof(
+        &input_content,
+        &lemmas,
+        DirectLemmas {
+            precomputed: &precomputed,
+            proofs_dir: &proofs_dir,
+            library: &library,
+        },
+        &runs,
+        replay,
+        &mut trace,
+    )? {
+        println!(
+            "[INFO] Best direct proof uses {} and takes {} steps",
+            lemma, steps
+        );
+        let (dag, dag_lemmas) = match library.iter().find(|l| l.axiom_name() == lemma) {
+            // a lemma of another problem has no DAG here, only its formula
+            Some(borrowed) => (
+                BTreeMap::from([(lemma.clone(), BTreeSet::new())]),
+                BTreeMap::from([(lemma.clone(), borrowed.formula.clone())]),
+            ),
+            None => build_dag(&lemma, &precomputed)?,
         };
+        best_total = Some(steps);
+        global_best = Some((
+            1,
+            steps,
+            lemma,
+            String::new(),
+            annotated_proof,
+            format_dag(&dag),
+            format_dag_lemmas(&dag_lemmas)?,
+            CandidateKind::Direct,
+        ));
+    }
 
-        let root_lemma = entry[0].as_str().ok_or("Bad summary.json format")?;
+    // a replay looks for its root however far down the summary it is
+    while replay.is_some() || accepted < max_candidates {
+        let Some(key) = roots.next() else {
+            break;
+        };
+        let entry = &summary_data[&key];
 
-        // skip lemmas containing Skolem constants
-        let skolem_re = Regex::new(r"\bsK\d+\b").unwrap();
-        let root_formula = load_lemma(&lemmas_dir, root_lemma)
-            .map_err(|_| format!("Missing lemma {}", root_lemma))?;
-        if skolem_re.is_match(&root_formula) {
+        let root_lemma = entry.file.as_str();
+        if replay.is_some_and(|c| c.root != root_lemma) {
+            continue;
+        }
+        if !entry.is_usable(config) {
             println!(
-                "[DEBUG] Skipping root lemma {} due to Skolem constants in formula: {}",
-                root_lemma, root_formula
+                "[INFO] Skipping root {}: no prover proved it (see --assume-unproved)",
+                root_lemma
             );
-            // skipping lemma because it contains Skolem constants
             continue;
         }
 
+        // lemmas containing Skolem constants are handled by the configured policy
+        let Some(root_formula) = root_formula_for(
+            config,
+            root_lemma,
+            lemmas.formula(root_lemma)?,
+            &input_content,
+        )?
+        else {
+            continue;
+        };
+        let root_formula = generalize_lemma(
+            config,
+            root_lemma,
+            root_formula,
+            &input_content,
+            &mut generalized,
+        );
+
         // valid root lemma
         accepted += 1;
 
         println!("\n[INFO] Root lemma {}", root_lemma);
 
         // build the minimal dag
-        let (dag, lemmas) = build_dag(&root_lemma, &precomputed)?;
-        let dag_file = "../output/tmp_dag.txt";
-        write_dag(dag_file, &dag).map_err(|e| e.to_string())?;
-
-        let lemmas_out_path = "../output/tmp_lemmas.p";
-        let mut lemmas_txt = String::new();
-        for (lemma_name, formula) in &lemmas {
-            lemmas_txt.push_str(&format!(
-                "fof({}, lemma,\n    {}\n).\n\n",
-                lemma_name, formula
-            ));
-        }
-        fs::write(&lemmas_out_path, lemmas_txt)
-            .map_err(|e| format!("Failed to write {}: {}", lemmas_out_path, e))?;
+        // the DAG and its lemmas stay in memory, they are only written for the best root
+        let (dag, dag_lemmas) = build_dag(&root_lemma, &precomputed)?;
+        let dag_text = format_dag(&dag);
+        let lemmas_text = format_dag_lemmas(&dag_lemmas)?;
 
         // collect all history candidates which appear before the root
         let root_index_str = root_lemma.rsplit('_').next().unwrap(); // "0016"
                                                                      // (steps_total, history_lemma, annotated_proof)
-        let mut local_best: Option<(usize, Option<String>, String)> = None;
+        let mut local_best: Option<(usize, Option<String>, AnnotatedProofBuilder)> = None;
         let mut candidates: Vec<String> = dag
             .keys()
             .filter(|k| k.starts_with("history_"))
             .filter(|k| k.rsplit('_').next().unwrap() < root_index_str)
             .cloned()
             .collect();
+        // nearest to the root first, where good cuts are usually found
+        sort_by_proximity(&mut candidates, &dag, root_lemma);
 
         // collect all nodes: keys + all children
         let mut all_nodes: BTreeSet<String> = BTreeSet::new();
@@ -138,6 +1153,7 @@ pub fn try_minimize(
                     })
                     .cloned(),
             );
+            sort_by_proximity(&mut candidates, &dag, root_lemma);
             // if no single or abstract lemmas are present either, fallback to root-only proof
             // this is the second case: the root itself is single/abstract
             if candidates.is_empty() {
@@ -153,59 +1169,81 @@ pub fn try_minimize(
                     );
                     continue; // skipping this now
                 }
+                if !evaluates(replay, CandidateKind::Search, root_lemma, None) {
+                    continue;
+                }
                 println!(
                     "   [INFO] No history or single lemmas found — falling back to root-only proof"
                 );
-
-                // fallback TODO see ../benchmarks/input10/Equation4417_implies_Equation4429.p
-                // this can be made way more elegant to derive the lemma not just append the Vampire proof
-                let actual_file = select_actual_lemma(&proofs_dir, root_lemma)
-                    .ok_or_else(|| format!("No proof file found for root {}", root_lemma))?;
-                // try different variants
-                let ext = [
-                    format!("{}/{}.proof", proofs_dir, actual_file),
-                    format!("{}/{}_twee.proof", proofs_dir, actual_file),
-                    format!("{}/{}_vampire.proof", proofs_dir, actual_file),
-                ];
-
-                let path = ext.iter().find(|p| Path::new(p).exists()).ok_or_else(|| {
-                    format!("No proof file found for root {} in any variant", root_lemma)
-                })?;
-
-                let root_proof = fs::read_to_string(path)
-                    .map_err(|_| format!("Cannot read proof file {}", path))?;
-
-                let prover = actual_file
-                    .rsplit('_')
-                    .next()
-                    .ok_or_else(|| format!("Cannot extract prover from filename {}", actual_file))?
-                    .split('.')
-                    .next()
-                    .ok_or_else(|| format!("Cannot extract prover from filename {}", actual_file))?
-                    .to_string();
-
-                let root_proof_steps = proof_length(&prover, &root_proof);
+                trace.push(TraceCandidate::new(CandidateKind::Search, root_lemma, None));
+
+                // the root from the superposition steps that derive it in the Vampire
+                // proof, or from its Phase 1 proof, whichever is shorter
+                let (phase1_proof, phase1_steps) = load_lemma_proof(&proofs_dir, root_lemma)?;
+                let (_, superposition_steps, instantiations, _) =
+                    superposition_steps(config, &dag, vampire_file, &lemmas, root_lemma)
+                        .unwrap_or_default();
+                let choice = SourceChoice::start(
+                    config.force_backend,
+                    superposition_steps.len(),
+                    phase1_steps,
+                );
+                let use_superposition = choice.chosen == Backend::Vampire;
+                record_choice(&mut trace, choice);
+                if use_superposition && superposition_steps.is_empty() {
+                    println!(
+                        "   [INFO] No superposition steps derive root {}, skipping",
+                        root_lemma
+                    );
+                    continue;
+                }
+                let mut names = NameAllocator::for_problem(&input_content)?;
+                names.reserve(&[root_lemma]);
+                let step_names = superposition_step_names(&superposition_steps, &mut names);
+                let (root_proof, root_proof_steps) = if use_superposition {
+                    (
+                        prepend_superposition_steps(
+                            &superposition_steps,
+                            &instantiations,
+                            &step_names,
+                        ),
+                        superposition_steps.len(),
+                    )
+                } else {
+                    (phase1_proof, phase1_steps)
+                };
                 let Some((sub_proof, sub_proof_steps)) = prove_lemma(
-                    &input_file,
-                    &lemmas_dir,
-                    None,
+                    &input_content,
+                    &lemmas,
+                    use_superposition.then_some((&superposition_steps, &step_names)),
                     None,
                     vec![(&root_formula, root_lemma)],
                     None,
+                    ProverOptions {
+                        runs: &runs,
+                        max_steps: step_cap(cap_steps, best_total, root_proof_steps),
+                    },
                 )?
                 else {
                     // no proof -> skip this candidate
                     continue;
                 };
 
-                let annotated_proof = format!(
-                    "% === Input Problem ===\n{}\n\n{}{}",
-                    input_content, root_proof, sub_proof
-                );
+                let mut annotated_proof = AnnotatedProofBuilder::new(&input_content);
+                annotated_proof
+                    .add(
+                        SectionKind::Root,
+                        Some(root_lemma),
+                        &root_proof,
+                        root_proof_steps,
+                    )
+                    .add(SectionKind::Conjecture, None, &sub_proof, sub_proof_steps);
 
-                let steps_total = root_proof_steps + sub_proof_steps;
+                let steps_total = annotated_proof.total_steps();
 
                 // root-only fallback:
+                record_steps(config, &mut trace, steps_total);
+                best_total = Some(best_total.map_or(steps_total, |best| best.min(steps_total)));
                 local_best = Some((steps_total, None, annotated_proof));
             } else {
                 // basically here we are trying to prove the root from its single or abstract dependecies.
@@ -216,14 +1254,26 @@ pub fn try_minimize(
                 );
 
                 for candidate in &candidates {
+                    if !evaluates(
+                        replay,
+                        CandidateKind::Search,
+                        root_lemma,
+                        Some(candidate.as_str()),
+                    ) {
+                        continue;
+                    }
+                    trace.push(TraceCandidate::new(
+                        CandidateKind::Search,
+                        root_lemma,
+                        Some(candidate.as_str()),
+                    ));
                     println!(
                         "   [INFO] Trying single/abstract candidate {} of {}",
                         candidate,
                         candidates.len()
                     );
 
-                    let mut annotated_proof = String::new();
-                    let mut steps_total = 0;
+                    let mut annotated_proof = AnnotatedProofBuilder::new(&input_content);
 
                     // check whether candidate is single or abstract
                     let is_single = candidate.starts_with("single_lemma_");
@@ -237,14 +1287,17 @@ pub fn try_minimize(
                         // get the lemma derived by superposition directly from Vampire proof
                         // in this case we are just proving the single lemma directly
                         let maybe_superposition =
-                            superposition_steps(dag_file, vampire_file, &lemmas_dir, candidate);
+                            superposition_steps(config, &dag, vampire_file, &lemmas, candidate);
                         // in dependencies we will get itself (the single lemma)
                         // in this case we can ignore proved_history
-                        let (dependencies, superposition_steps, _) = match maybe_superposition {
-                            Some((deps, steps, ph)) => (deps, steps, ph),
-                            None => (vec![], BTreeMap::new(), false),
-                        };
+                        let (dependencies, superposition_steps, instantiations, _) =
+                            maybe_superposition.unwrap_or_default();
                         let superposition_steps_count = superposition_steps.len();
+                        // names of the steps, clear of the input problem and this candidate's lemmas
+                        let mut names = NameAllocator::for_problem(&input_content)?;
+                        names.reserve(&[root_lemma, candidate.as_str()]);
+                        names.reserve(&dependencies);
+                        let step_names = superposition_step_names(&superposition_steps, &mut names);
 
                         // 2. Load dependency proofs
                         // load the proof of the single lemma
@@ -260,32 +1313,32 @@ pub fn try_minimize(
                             .join("\n\n"); // separate proofs by blank lines
 
                         // 3. Decide which source to use
-                        let use_superposition = if total_dep_steps == 0 {
-                            // no DAG dependencies -> must use superposition
-                            true
-                        } else {
-                            // DAG dependencies exist -> use superposition only if it's shorter or equal
-                            superposition_steps_count > 0
-                                && superposition_steps_count <= total_dep_steps
-                        };
+                        let choice = SourceChoice::start(
+                            config.force_backend,
+                            superposition_steps_count,
+                            total_dep_steps,
+                        );
+                        let use_superposition = choice.chosen == Backend::Vampire;
+                        record_choice(&mut trace, choice);
 
                         // start lemmas
-                        let (start_proof, start_proof_steps) = if total_dep_steps
-                            <= superposition_steps_count
-                            && total_dep_steps != 0
-                        {
-                            (combined_dep_proof_text.clone(), total_dep_steps)
-                        } else {
-                            let sp_proof_text = prepend_superposition_steps(&superposition_steps);
+                        let (start_proof, start_proof_steps) = if use_superposition {
+                            let sp_proof_text = prepend_superposition_steps(
+                                &superposition_steps,
+                                &instantiations,
+                                &step_names,
+                            );
                             (sp_proof_text, superposition_steps_count)
+                        } else {
+                            (combined_dep_proof_text, total_dep_steps)
                         };
 
                         // 6. Compute root_proof
                         let Some((root_proof, root_proof_steps)) = prove_lemma(
-                            &input_file,
-                            &lemmas_dir,
+                            &input_content,
+                            &lemmas,
                             if use_superposition {
-                                Some(&superposition_steps)
+                                Some((&superposition_steps, &step_names))
                             } else {
                                 None
                             },
@@ -296,6 +1349,10 @@ pub fn try_minimize(
                             },
                             vec![(&root_formula, root_lemma)],
                             Some(&root_lemma),
+                            ProverOptions {
+                                runs: &runs,
+                                max_steps: None,
+                            },
                         )?
                         else {
                             // no proof -> skip this candidate
@@ -304,10 +1361,10 @@ pub fn try_minimize(
 
                         // 7. Compute sub_proof / conjecture proof
                         let Some((sub_proof, sub_proof_steps)) = prove_lemma(
-                            &input_file,
-                            &lemmas_dir,
+                            &input_content,
+                            &lemmas,
                             if use_superposition {
-                                Some(&superposition_steps)
+                                Some((&superposition_steps, &step_names))
                             } else {
                                 None
                             },
@@ -318,6 +1375,10 @@ pub fn try_minimize(
                             },
                             vec![(&root_formula, root_lemma)],
                             None,
+                            ProverOptions {
+                                runs: &runs,
+                                max_steps: step_cap(cap_steps, best_total, start_proof_steps),
+                            },
                         )?
                         else {
                             // no proof -> skip this candidate
@@ -333,21 +1394,19 @@ pub fn try_minimize(
                                 "   [INFO] Root lemma {} not used in conjecture proof — skipping",
                                 root_lemma
                             );
-                            annotated_proof = format!(
-                                "% === Input Problem ===\n{}\n\n{}{}",
-                                input_content, start_proof, sub_proof
-                            );
-
-                            // 9. Compute total steps
-                            steps_total = start_proof_steps + sub_proof_steps;
+                            annotated_proof
+                                .add(SectionKind::Start, None, &start_proof, start_proof_steps)
+                                .add(SectionKind::Conjecture, None, &sub_proof, sub_proof_steps);
                         } else {
-                            annotated_proof = format!(
-                                "% === Input Problem ===\n{}\n\n{}{}{}",
-                                input_content, start_proof, root_proof, sub_proof
-                            );
-
-                            // 9. Compute total steps
-                            steps_total = start_proof_steps + root_proof_steps + sub_proof_steps;
+                            annotated_proof
+                                .add(SectionKind::Start, None, &start_proof, start_proof_steps)
+                                .add(
+                                    SectionKind::Root,
+                                    Some(root_lemma),
+                                    &root_proof,
+                                    root_proof_steps,
+                                )
+                                .add(SectionKind::Conjecture, None, &sub_proof, sub_proof_steps);
                         }
                     }
                     // if we fall back to an abstract candidate we will have to prove
@@ -365,10 +1424,11 @@ pub fn try_minimize(
 
                             // extract prover
                             let prover = "twee".to_string();
-                            let abstract_proof_steps = proof_length(&prover, &abstract_proof);
+                            let (abstract_proof, abstract_proof_steps) =
+                                lemma_proof(candidate, &prover, abstract_proof)?;
 
                             // load the formula of the abstracted lemma
-                            let abstract_formula = match load_lemma(&lemmas_dir, candidate) {
+                            let abstract_formula = match lemmas.formula(candidate) {
                                 Ok(f) => f,
                                 Err(err) => {
                                     eprintln!(
@@ -379,14 +1439,53 @@ pub fn try_minimize(
                                 }
                             };
 
+                            // 5.1. With --twee-steps the lemmas Twee derived for the
+                            // abstract lemma are given to the next runs as well
+                            let twee_steps = if config.twee_steps {
+                                let mut names = NameAllocator::for_problem(&input_content)?;
+                                names.reserve(&[root_lemma, candidate.as_str()]);
+                                twee_step_lemmas(&abstract_proof, &mut names).unwrap_or_else(
+                                    |err| {
+                                        eprintln!(
+                                            "     [WARN] Cannot read the Twee proof of {}: {}",
+                                            candidate, err
+                                        );
+                                        Vec::new()
+                                    },
+                                )
+                            } else {
+                                Vec::new()
+                            };
+                            let step_axioms: Vec<(String, &str)> = twee_steps
+                                .iter()
+                                .map(|(name, step)| (step.formula.to_string(), name.as_str()))
+                                .collect();
+                            let axioms = || {
+                                let mut axioms = vec![
+                                    (root_formula.as_str(), root_lemma),
+                                    (abstract_formula.as_str(), candidate.as_str()),
+                                ]; // abstract lemma as dependency
+                                axioms.extend(step_axioms.iter().map(|(f, n)| (f.as_str(), *n)));
+                                axioms
+                            };
+                            let abstract_proof = if twee_steps.is_empty() {
+                                abstract_proof
+                            } else {
+                                format!("{}{}", prepend_twee_steps(&twee_steps), abstract_proof)
+                            };
+
                             // 6. Compute root_proof
                             let Some((root_proof, root_proof_steps)) = prove_lemma(
-                                &input_file,
-                                &lemmas_dir,
+                                &input_content,
+                                &lemmas,
                                 None,
                                 None,
-                                vec![(&root_formula, root_lemma), (&abstract_formula, candidate)], // abstract lemma as dependency
+                                axioms(),
                                 Some(&root_lemma),
+                                ProverOptions {
+                                    runs: &runs,
+                                    max_steps: None,
+                                },
                             )?
                             else {
                                 // no proof -> skip this candidate
@@ -395,12 +1494,20 @@ pub fn try_minimize(
 
                             // 7. Compute sub_proof / conjecture proof
                             let Some((sub_proof, sub_proof_steps)) = prove_lemma(
-                                &input_file,
-                                &lemmas_dir,
+                                &input_content,
+                                &lemmas,
                                 None,
                                 None,
-                                vec![(&root_formula, root_lemma), (&abstract_formula, candidate)], // abstract lemma as dependency
+                                axioms(),
                                 None,
+                                ProverOptions {
+                                    runs: &runs,
+                                    max_steps: step_cap(
+                                        cap_steps,
+                                        best_total,
+                                        abstract_proof_steps,
+                                    ),
+                                },
                             )?
                             else {
                                 // no proof -> skip this candidate
@@ -415,22 +1522,39 @@ pub fn try_minimize(
                                     "   [INFO] Root lemma {} not used in conjecture proof — skipping",
                                     root_lemma
                                 );
-                                annotated_proof = format!(
-                                    "% === Input Problem ===\n{}\n\n{}{}",
-                                    input_content, abstract_proof, sub_proof
-                                );
-
-                                // 9. Compute total steps
-                                steps_total = abstract_proof_steps + sub_proof_steps;
+                                annotated_proof
+                                    .add(
+                                        SectionKind::History,
+                                        Some(candidate.as_str()),
+                                        &abstract_proof,
+                                        abstract_proof_steps,
+                                    )
+                                    .add(
+                                        SectionKind::Conjecture,
+                                        None,
+                                        &sub_proof,
+                                        sub_proof_steps,
+                                    );
                             } else {
-                                annotated_proof = format!(
-                                    "% === Input Problem ===\n{}\n\n{}{}{}",
-                                    input_content, abstract_proof, root_proof, sub_proof
-                                );
-
-                                // 9. Compute total steps
-                                steps_total =
-                                    abstract_proof_steps + root_proof_steps + sub_proof_steps;
+                                annotated_proof
+                                    .add(
+                                        SectionKind::History,
+                                        Some(candidate.as_str()),
+                                        &abstract_proof,
+                                        abstract_proof_steps,
+                                    )
+                                    .add(
+                                        SectionKind::Root,
+                                        Some(root_lemma),
+                                        &root_proof,
+                                        root_proof_steps,
+                                    )
+                                    .add(
+                                        SectionKind::Conjecture,
+                                        None,
+                                        &sub_proof,
+                                        sub_proof_steps,
+                                    );
                             }
                         } else {
                             println!(
@@ -442,6 +1566,9 @@ pub fn try_minimize(
                     }
                     // single/history fallback:
                     // update local best
+                    let steps_total = annotated_proof.total_steps();
+                    record_steps(config, &mut trace, steps_total);
+                    best_total = Some(best_total.map_or(steps_total, |best| best.min(steps_total)));
                     local_best = match local_best {
                         None => Some((steps_total, Some(candidate.clone()), annotated_proof)),
                         Some((best_steps, _, _)) => {
@@ -466,6 +1593,14 @@ pub fn try_minimize(
                     );
                     continue;
                 }
+                if !evaluates(
+                    replay,
+                    CandidateKind::Search,
+                    root_lemma,
+                    Some(n_history_lemma.as_str()),
+                ) {
+                    continue;
+                }
                 println!(
                     "   [INFO] Trying history candidate {} of {}",
                     n_history_lemma,
@@ -475,13 +1610,11 @@ pub fn try_minimize(
                 // 1. Get superposition steps
                 // get the lemma derived by superposition directly from Vampire proof
                 let maybe_superposition =
-                    superposition_steps(dag_file, vampire_file, &lemmas_dir, n_history_lemma);
+                    superposition_steps(config, &dag, vampire_file, &lemmas, n_history_lemma);
+
+                let (dependencies, superposition_steps, instantiations, proved_history) =
+                    maybe_superposition.unwrap_or_default();
 
-                let (dependencies, superposition_steps, proved_history) = match maybe_superposition
-                {
-                    Some((deps, steps, ph)) => (deps, steps, ph),
-                    None => (vec![], BTreeMap::new(), false),
-                };
                 let superposition_steps_count = superposition_steps.len();
 
                 // If the history lemma is proved by superposition, the
@@ -509,6 +1642,42 @@ pub fn try_minimize(
                 let dep_proofs = load_all_dependency_proofs(&proofs_dir, &dependencies)?;
                 // count the steps for all the dependencies
                 let total_dep_steps: usize = dep_proofs.iter().map(|(_, _, steps, _)| *steps).sum();
+
+                // another root may have led to the same formulas under other names
+                let start_formulas: Vec<String> = dependencies
+                    .iter()
+                    .map(|dep| lemmas.formula(dep).unwrap_or_else(|_| dep.clone()))
+                    .chain(superposition_steps.values().map(|s| s.formula.clone()))
+                    .collect();
+                let dep_steps: Vec<(String, usize)> = dep_proofs
+                    .iter()
+                    .map(|(dep, _, steps, _)| (dep.clone(), *steps))
+                    .collect();
+                let key = CandidateKey::new(
+                    &root_formula,
+                    &lemmas.formula(n_history_lemma)?,
+                    &start_formulas,
+                    &dep_steps,
+                    superposition_steps_count,
+                );
+                if let Some((root, history)) = evaluated.repeat_of(&key) {
+                    println!(
+                        "   [INFO] Skipping history {}: same formulas as root {} with history {}",
+                        n_history_lemma, root, history
+                    );
+                    continue;
+                }
+                trace.push(TraceCandidate::new(
+                    CandidateKind::Search,
+                    root_lemma,
+                    Some(n_history_lemma.as_str()),
+                ));
+                // names of the steps, clear of the input problem and this candidate's lemmas
+                let mut names = NameAllocator::for_problem(&input_content)?;
+                names.reserve(&[root_lemma, n_history_lemma.as_str()]);
+                names.reserve(&dependencies);
+                let step_names = superposition_step_names(&superposition_steps, &mut names);
+
                 // combine all dependency proofs text
                 let combined_dep_proof_text = dep_proofs
                     .iter()
@@ -517,33 +1686,41 @@ pub fn try_minimize(
                     .join("\n\n"); // separate proofs by blank lines
 
                 // 3. Decide which source to use
-                let use_superposition = if total_dep_steps == 0 {
-                    // no DAG dependencies -> must use superposition
-                    true
-                } else {
-                    // DAG dependencies exist -> use superposition only if it's shorter or equal
-                    superposition_steps_count > 0 && superposition_steps_count <= total_dep_steps
-                };
+                let choice = SourceChoice::start(
+                    config.force_backend,
+                    superposition_steps_count,
+                    total_dep_steps,
+                );
+                let use_superposition = choice.chosen == Backend::Vampire;
+                record_choice(&mut trace, choice);
 
                 // start lemmas
-                let (start_proof, start_proof_steps) =
-                    if total_dep_steps <= superposition_steps_count && total_dep_steps != 0 {
-                        (combined_dep_proof_text.clone(), total_dep_steps)
-                    } else {
-                        let sp_proof_text = prepend_superposition_steps(&superposition_steps);
-                        (sp_proof_text, superposition_steps_count)
-                    };
+                let (start_proof, start_proof_steps) = if use_superposition {
+                    let sp_proof_text = prepend_superposition_steps(
+                        &superposition_steps,
+                        &instantiations,
+                        &step_names,
+                    );
+                    (sp_proof_text, superposition_steps_count)
+                } else {
+                    (combined_dep_proof_text, total_dep_steps)
+                };
 
                 // 4. Load n_history formula
-                let n_formula = load_lemma(&lemmas_dir, &n_history_lemma)
-                    .map_err(|_| format!("Missing lemma {}", n_history_lemma))?;
+                let n_formula = generalize_lemma(
+                    config,
+                    &n_history_lemma,
+                    lemmas.formula(&n_history_lemma)?,
+                    &input_content,
+                    &mut generalized,
+                );
 
                 // 5. Compute n_history_proof
                 let Some((n_history_proof, n_history_proof_steps)) = prove_lemma(
-                    &input_file,
-                    &lemmas_dir,
+                    &input_content,
+                    &lemmas,
                     if use_superposition {
-                        Some(&superposition_steps)
+                        Some((&superposition_steps, &step_names))
                     } else {
                         None
                     },
@@ -554,6 +1731,10 @@ pub fn try_minimize(
                     },
                     vec![(&n_formula, &n_history_lemma)],
                     Some(&n_history_lemma),
+                    ProverOptions {
+                        runs: &runs,
+                        max_steps: None,
+                    },
                 )?
                 else {
                     // no proof -> skip this candidate
@@ -563,19 +1744,21 @@ pub fn try_minimize(
                 // in case this history lemma was already derived by superposition.
                 let mut use_proved_history = false;
                 if proved_history {
-                    if n_history_proof_steps <= superposition_steps_count {
-                        use_proved_history = false;
-                    } else {
-                        use_proved_history = true;
-                    };
+                    let choice = SourceChoice::history(
+                        config.force_backend,
+                        superposition_steps_count,
+                        n_history_proof_steps,
+                    );
+                    use_proved_history = choice.chosen == Backend::Vampire;
+                    record_choice(&mut trace, choice);
                 }
 
                 // 6. Compute root_proof
                 let Some((root_proof, root_proof_steps)) = prove_lemma(
-                    &input_file,
-                    &lemmas_dir,
+                    &input_content,
+                    &lemmas,
                     if use_superposition {
-                        Some(&superposition_steps)
+                        Some((&superposition_steps, &step_names))
                     } else {
                         None
                     },
@@ -586,6 +1769,10 @@ pub fn try_minimize(
                     },
                     vec![(&n_formula, &n_history_lemma), (&root_formula, root_lemma)],
                     Some(&root_lemma),
+                    ProverOptions {
+                        runs: &runs,
+                        max_steps: None,
+                    },
                 )?
                 else {
                     // no proof -> skip this candidate
@@ -594,10 +1781,10 @@ pub fn try_minimize(
 
                 // 7. Compute sub_proof / conjecture proof
                 let Some((sub_proof, sub_proof_steps)) = prove_lemma(
-                    &input_file,
-                    &lemmas_dir,
+                    &input_content,
+                    &lemmas,
                     if use_superposition {
-                        Some(&superposition_steps)
+                        Some((&superposition_steps, &step_names))
                     } else {
                         None
                     },
@@ -608,6 +1795,10 @@ pub fn try_minimize(
                     },
                     vec![(&n_formula, &n_history_lemma), (&root_formula, root_lemma)],
                     None,
+                    ProverOptions {
+                        runs: &runs,
+                        max_steps: step_cap(cap_steps, best_total, start_proof_steps),
+                    },
                 )?
                 else {
                     // no proof -> skip this candidate
@@ -630,61 +1821,48 @@ pub fn try_minimize(
                     history_used = false;
                 }
                 // 9. Annotate all proofs
-                let annotated_proof;
-                let steps_total;
                 if !root_used && !history_used {
                     println!(
                         "   [INFO] Root {} and history lemma {} not used in the proof — skipping",
                         root_lemma, n_history_lemma
                     );
-
-                    annotated_proof = format!(
-                        "% === Input Problem ===\n{}\n\n{}{}",
-                        input_content, start_proof, sub_proof
-                    );
-
-                    // 9. Compute total steps
-                    steps_total = start_proof_steps + sub_proof_steps;
-                } else if !root_used && history_used {
+                } else if !root_used {
                     println!(
                         "   [INFO] Root lemma {} not used in the proof — skipping",
                         root_lemma
                     );
-
-                    annotated_proof = format!(
-                        "% === Input Problem ===\n{}\n\n{}{}{}",
-                        input_content, start_proof, n_history_proof, sub_proof
-                    );
-
-                    // 9. Compute total steps
-                    steps_total = start_proof_steps + n_history_proof_steps + sub_proof_steps;
-                } else if root_used && !history_used {
+                } else if !history_used {
                     println!(
                         "   [INFO] History lemma {} not used in the proof — skipping",
                         n_history_lemma
                     );
-
-                    annotated_proof = format!(
-                        "% === Input Problem ===\n{}\n\n{}{}{}",
-                        input_content, start_proof, root_proof, sub_proof
+                }
+                let mut annotated_proof = AnnotatedProofBuilder::new(&input_content);
+                annotated_proof.add(SectionKind::Start, None, &start_proof, start_proof_steps);
+                if history_used {
+                    annotated_proof.add(
+                        SectionKind::History,
+                        Some(n_history_lemma.as_str()),
+                        &n_history_proof,
+                        n_history_proof_steps,
                     );
-
-                    // 9. Compute total steps
-                    steps_total = start_proof_steps + root_proof_steps + sub_proof_steps;
-                } else {
-                    // root and history were used
-                    annotated_proof = format!(
-                        "% === Input Problem ===\n{}\n\n{}{}{}{}",
-                        input_content, start_proof, n_history_proof, root_proof, sub_proof
+                }
+                if root_used {
+                    annotated_proof.add(
+                        SectionKind::Root,
+                        Some(root_lemma),
+                        &root_proof,
+                        root_proof_steps,
                     );
-
-                    // 9. Compute total steps
-                    steps_total = start_proof_steps
-                        + n_history_proof_steps
-                        + root_proof_steps
-                        + sub_proof_steps;
                 }
+                annotated_proof.add(SectionKind::Conjecture, None, &sub_proof, sub_proof_steps);
+
+                // 9. Compute total steps
+                let steps_total = annotated_proof.total_steps();
                 // update local_best
+                record_steps(config, &mut trace, steps_total);
+                evaluated.record(key, root_lemma, n_history_lemma);
+                best_total = Some(best_total.map_or(steps_total, |best| best.min(steps_total)));
                 local_best = match local_best {
                     None => Some((steps_total, Some(n_history_lemma.clone()), annotated_proof)),
                     Some((best_steps, _, _)) => {
@@ -704,12 +1882,6 @@ pub fn try_minimize(
         }
         // update global_best
         if let Some((steps_total, best_history, annotated_proof)) = local_best {
-            let dag_text = fs::read_to_string("../output/tmp_dag.txt")
-                .map_err(|e| format!("Failed to read tmp_dag.txt: {}", e))?;
-
-            let lemmas_text = fs::read_to_string("../output/tmp_lemmas.p")
-                .map_err(|e| format!("Failed to read tmp_lemmas.p: {}", e))?;
-
             global_best = match global_best {
                 None => Some((
                     lemma_count,
@@ -719,8 +1891,9 @@ pub fn try_minimize(
                     annotated_proof,
                     dag_text,
                     lemmas_text,
+                    CandidateKind::Search,
                 )),
-                Some((b_lemmas, b_steps, _, _, _, _, _)) => {
+                Some((b_lemmas, b_steps, ..)) => {
                     if steps_total < b_steps || (lemma_count == b_lemmas && steps_total < b_steps) {
                         Some((
                             lemma_count,
@@ -730,6 +1903,7 @@ pub fn try_minimize(
                             annotated_proof,
                             dag_text,
                             lemmas_text,
+                            CandidateKind::Search,
                         ))
                     } else {
                         global_best
@@ -738,108 +1912,792 @@ pub fn try_minimize(
             };
         }
     }
-    if let Some((_, steps, root, n_history, annotated_proof, dag_text, lemmas_text)) = &global_best
+    if let Some(replaying) = replaying {
+        return finish_replay(
+            replaying,
+            global_best.map(|best| (best.1, best.4.render(), best.5, best.6)),
+        )
+        .map(|message| (message, None));
+    }
+
+    if evaluated.repeats > 0 {
+        println!(
+            "[INFO] Skipped {} repeated candidate evaluations",
+            evaluated.repeats
+        );
+    }
+
+    let best = global_best
+        .as_ref()
+        .and_then(|(_, steps, root, n_history, .., kind)| {
+            trace.iter().position(|c| {
+                c.kind == *kind
+                    && &c.root == root
+                    && c.history.as_deref().unwrap_or_default() == n_history.as_str()
+                    && c.steps == Some(*steps)
+            })
+        });
+    for candidate in &trace {
+        config.results_db.record_candidate(
+            &candidate.root,
+            candidate.history.as_deref(),
+            candidate.steps,
+        );
+    }
+    let trace = CandidateTrace {
+        input: input_file.to_string(),
+        candidates: trace,
+        best,
+    };
+    let trace_with_suffix = dirs.artifact("trace", &suffix, "json");
+    match serde_json::to_string_pretty(&trace) {
+        Ok(json) => {
+            if let Err(e) = write_atomic(&trace_with_suffix, json) {
+                eprintln!("[WARN] Failed to write {}: {}", trace_with_suffix, e);
+            }
+        }
+        Err(e) => eprintln!("[WARN] Failed to serialize candidate trace: {}", e),
+    }
+
+    if let Some((_, steps, root, n_history, annotated_proof, dag_text, lemmas_text, _)) =
+        &global_best
     {
         println!("\n[RESULT] Best combination found:");
         println!("[RESULT] Root lemma: {}", root);
+        if let Some(borrowed) = library.iter().find(|l| &l.axiom_name() == root) {
+            println!(
+                "[RESULT] Cross-problem dependency: {} is {} of problem {}",
+                root, borrowed.lemma, borrowed.problem
+            );
+        }
         println!("[RESULT] History lemma: {}", n_history);
-        println!("[RESULT] Total steps: {}", steps);
-        let vampire_steps = match fs::read_to_string(&vampire_file) {
-            Ok(content) => proof_length("vampire", &content),
-            Err(_) => 0,
+        let hypotheses = annotated_proof.hypotheses();
+        if hypotheses.is_empty() {
+            println!("[RESULT] Total steps: {}", steps);
+        } else {
+            println!(
+                "[RESULT] Total steps: {} (conditional on the unproved lemmas {})",
+                steps,
+                hypotheses.join(", ")
+            );
+        }
+        println!(
+            "[RESULT] Steps by rule: {}",
+            format_breakdown(&annotated_proof.rule_breakdown())
+        );
+        let vampire_proof = fs::read_to_string(&vampire_file).ok();
+        let vampire_steps = match &vampire_proof {
+            Some(content) => Vampire.proof_length(content).unwrap_or_default(),
+            None => 0,
         };
         println!("[RESULT] Initial proof steps: {}", vampire_steps);
+        // an unreadable Vampire proof counts as 0 steps, which is no reason to keep it
+        let keep_original = match &vampire_proof {
+            Some(content) if *steps > vampire_steps => {
+                println!(
+                    "[RESULT] No improvement: the minimized proof is {} steps longer than the original",
+                    steps - vampire_steps
+                );
+                config.keep_original.then_some(content)
+            }
+            _ => None,
+        };
 
-        fs::write(dag_with_suffix.clone(), dag_text).map_err(|e| e.to_string())?;
-        fs::write(lemmas_with_suffix.clone(), lemmas_text).map_err(|e| e.to_string())?;
-        fs::write(proof_with_suffix.clone(), annotated_proof).map_err(|e| e.to_string())?;
+        write_with_provenance(config, &dag_with_suffix, dag_text, input_file)
+            .map_err(|e| e.to_string())?;
+        write_with_provenance(config, &lemmas_with_suffix, lemmas_text, input_file)
+            .map_err(|e| e.to_string())?;
+        let (final_steps, final_hypotheses) = if keep_original.is_some() {
+            (vampire_steps, Vec::new())
+        } else {
+            (*steps, hypotheses)
+        };
+        let official_proof = match keep_original {
+            Some(content) => {
+                println!("[RESULT] Kept the original proof as {}", proof_with_suffix);
+                strip(content).to_string()
+            }
+            None => annotated_proof.render(),
+        };
+        write_with_provenance(config, &proof_with_suffix, &official_proof, input_file)
+            .map_err(|e| e.to_string())?;
+        write_atomic(&sections_with_suffix, annotated_proof.to_json()?)
+            .map_err(|e| format!("Failed to write {}: {}", sections_with_suffix, e))?;
+        if emit.contains(&Emit::Graph) {
+            write_atomic(&graph_with_suffix, annotated_proof.to_dot())
+                .map_err(|e| format!("Failed to write {}: {}", graph_with_suffix, e))?;
+            println!("[RESULT] Derivation graph written to {}", graph_with_suffix);
+        }
+        Ok((
+            "Minimization complete".into(),
+            Some((final_steps, final_hypotheses)),
+        ))
     } else {
-        return Err("No valid root/history candidate combination found.".into());
+        Err("No valid root/history candidate combination found.".into())
     }
+}
 
-    // cleanup temporary files
-    let _ = fs::remove_file("../output/tmp_dag.txt");
-    let _ = fs::remove_file("../output/tmp_lemmas.p");
-
-    Ok("Minimization complete".into())
+/// Keep the DAG, lemmas and proof of a replayed candidate next to its Twee runs
+/// and compare the result with the recorded one. `result` is (steps, proof, DAG, lemmas).
+fn finish_replay(
+    replay: &Replay,
+    result: Option<(usize, String, String, String)>,
+) -> Result<String, String> {
+    let (candidate, dir) = (replay.candidate, &replay.dir);
+    let steps = match result {
+        Some((steps, proof, dag_text, lemmas_text)) => {
+            for (name, text) in [
+                ("proof.out", proof),
+                ("dag.txt", dag_text),
+                ("lemmas.p", lemmas_text),
+            ] {
+                let path = dir.join(name);
+                write_atomic(&path, text)
+                    .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            }
+            Some(steps)
+        }
+        None => None,
+    };
+    let show = |steps: Option<usize>| steps.map_or("no proof".to_string(), |s| s.to_string());
+    println!("\n[RESULT] Recorded steps: {}", show(candidate.steps));
+    println!("[RESULT] Replayed steps: {}", show(steps));
+    if steps != candidate.steps {
+        println!("[WARN] Replay differs from the recorded search");
+    }
+    Ok(format!("Replay complete, see {}", dir.display()))
 }
 
 /// Generic lemma proving function.
 ///
+/// - `input_problem`: text of the input problem, read once per search
+/// - `superposition_steps`: steps to add as axioms, with their names
 /// - `axioms`: list of (formula, name)
 /// - `conjecture`: lemma to promote to conjecture (optional)
+/// - `prover`: which provers to ask, where a replay keeps their runs, and
+///   the longest proof of any use (optional), passed on to the provers as a
+///   search limit; a longer proof counts as none
 /// - Returns `(proof_text, step_count)`
 pub fn prove_lemma(
-    input_file: &str,
-    lemmas_dir: &str,
-    superposition_steps: Option<&BTreeMap<usize, SuperpositionStep>>,
+    input_problem: &str,
+    lemmas: &LemmaStore,
+    superposition_steps: Option<(&StepMap, &BTreeMap<usize, String>)>,
     dependency_lemmas: Option<&[String]>,
     axioms: Vec<(&str, &str)>,
     conjecture: Option<&str>,
+    prover: ProverOptions,
 ) -> Result<Option<(String, usize)>, String> {
-    let tmp_path = create_tmp_copy(input_file)?;
+    let ProverOptions { runs, max_steps } = prover;
+    // 0. No proof fits a cap of 0 steps, the provers are not asked
+    if max_steps == Some(0) {
+        println!(
+            "   [INFO] No proof of {} could beat the best candidate, not proving it",
+            conjecture.unwrap_or("the conjecture")
+        );
+        return Ok(None);
+    }
+
+    // the problem is built in memory and piped to Twee, no temp files needed
+    let mut problem = input_problem.to_string();
 
     // 1.1. Add superposition steps if provided
-    if let Some(sp_steps) = superposition_steps {
-        append_superposition_steps_as_lemmas(&tmp_path, sp_steps, lemmas_dir)?;
+    if let Some((sp_steps, step_names)) = superposition_steps {
+        append_superposition_steps_as_lemmas(&mut problem, sp_steps, step_names)?;
     }
     // 1.2. Add dependency lemmas if provided
     else if let Some(deps) = dependency_lemmas {
         for dep in deps {
             // load formula for each dependency
-            let formula =
-                load_lemma(lemmas_dir, dep).map_err(|_| format!("Missing lemma {}", dep))?;
-            append_as_axiom(&tmp_path, &formula, dep);
+            let formula = lemmas.formula(dep)?;
+            append_as_axiom(&mut problem, &formula, dep)?;
         }
     }
 
     // 2. Append additional axioms
     for (formula, name) in axioms {
-        append_as_axiom(&tmp_path, formula, name);
+        append_as_axiom(&mut problem, formula, name)?;
+    }
+
+    // 3. Screen the lemmas appended as axioms: one that makes the axioms
+    // inconsistent would make any proof trivial, so there is no proof to use
+    let given = TptpFile::parse(input_problem)?.formulas().count();
+    let appended: Vec<AnnotatedFormula> = TptpFile::parse(&problem)?
+        .formulas()
+        .skip(given)
+        .filter(|f| Some(f.name.as_str()) != conjecture)
+        .cloned()
+        .collect();
+    if let Some(lemma) = inconsistent_lemma(&runs.screen, input_problem, &appended)? {
+        println!(
+            "   [WARN] {} makes the axioms inconsistent, no proof of {} is taken with it",
+            lemma,
+            conjecture.unwrap_or("the conjecture")
+        );
+        return Ok(None);
     }
 
-    // 3. Promote to conjecture if requested
+    // 3.1. Promote to conjecture if requested
     if let Some(c) = conjecture {
-        promote_axiom_to_conjecture(&tmp_path, c)?;
+        problem = promote_axiom_to_conjecture(&problem, c)?;
     }
 
-    // 4. Run Twee
-    let proof = match run_twee(&tmp_path) {
-        Some(p) => p,
+    // 4. Run Twee, or with --adaptive-provers the provers that won lemmas
+    // of this shape in Phase 1, until one finds a proof
+    let mut found = None;
+    for prover in runs.provers_for(&problem)? {
+        let proof = match prover {
+            "vampire" => run_vampire_stdin(runs.config, &problem, max_steps),
+            _ => run_twee_stdin(runs.config, &problem, max_steps),
+        };
+        runs.keep(prover, conjecture, &problem, proof.as_deref());
+        if let Some(proof) = proof {
+            found = Some((prover, proof));
+            break;
+        }
+    }
+    let (prover, proof) = match found {
+        Some(found) => found,
         None => {
-            // Twee failed -> skip this candidate
-            let _ = fs::remove_file(&tmp_path);
+            // no prover succeeded -> skip this candidate
             return Ok(None);
         }
     };
 
     // 5. Count the steps
-    let steps = proof_length_twee(&proof);
-
-    // 6. Cleanup tmp
-    let _ = fs::remove_file(&tmp_path);
+    let steps = proof_length(prover, &proof)?;
+    if let Some(max_steps) = max_steps.filter(|&max| steps > max) {
+        println!(
+            "   [INFO] Proof of {} takes {} steps, more than the {} that could beat the best candidate",
+            conjecture.unwrap_or("the conjecture"),
+            steps,
+            max_steps
+        );
+        return Ok(None);
+    }
 
     Ok(Some((proof, steps)))
 }
 
+/// The lemma of `appended` that makes the axioms of `input_problem`
+/// inconsistent together with the lemmas before it, if the axioms are
+/// consistent on their own. The lemmas are screened all at once, since
+/// several can be inconsistent only together, and if they are, prefixes of
+/// them are bisected for the one to blame. They are screened under names by
+/// position, so the answers `screen` keeps are reused whenever the lemmas
+/// come up again, under whatever names.
+fn inconsistent_lemma(
+    screen: &ConsistencyScreen,
+    input_problem: &str,
+    appended: &[AnnotatedFormula],
+) -> Result<Option<String>, String> {
+    if appended.is_empty() {
+        return Ok(None);
+    }
+    let mut axioms = TptpFile::parse(input_problem)?;
+    axioms.retain(|f| !f.is_conjecture());
+    // inconsistent input axioms are the problem's own, no lemma is to blame
+    if screen.inconsistent(&axioms.to_string()) {
+        return Ok(None);
+    }
+    let with_first = |count: usize| {
+        let mut screened = axioms.clone();
+        for (i, lemma) in appended[..count].iter().enumerate() {
+            screened.push(AnnotatedFormula::new(
+                &lemma.language,
+                &format!("screened_lemma_{}", i + 1),
+                "axiom",
+                &lemma.body,
+            ));
+        }
+        screen.inconsistent(&screened.to_string())
+    };
+    if !with_first(appended.len()) {
+        return Ok(None);
+    }
+    // the first `consistent` lemmas are consistent, the first `inconsistent` not
+    let (mut consistent, mut inconsistent) = (0, appended.len());
+    while inconsistent - consistent > 1 {
+        let middle = (consistent + inconsistent) / 2;
+        if with_first(middle) {
+            inconsistent = middle;
+        } else {
+            consistent = middle;
+        }
+    }
+    Ok(Some(appended[inconsistent - 1].name.clone()))
+}
+
 /// Checks if a proof uses a lemma (Twee or Vampire)
 pub fn proof_uses_lemma(proof: &str, lemma_name: &str) -> bool {
-    proof.lines().any(|line| {
-        let line = line.trim();
+    let prover = detect_prover(proof);
+    let Ok(steps) = proof_steps(prover, proof) else {
+        // unreadable proofs are kept, as if they used it
+        return true;
+    };
+    // Vampire match (we assume its always a match cause of how Vampire works)
+    if prover == "vampire" {
+        return steps.iter().any(|s| s.role == ProofRole::Axiom);
+    }
+    steps
+        .iter()
+        .any(|s| s.name == lemma_name || s.premises.iter().any(|p| p == lemma_name))
+}
 
-        // Twee match
-        if line.contains(lemma_name)
-            || line.contains(&format!("({},", lemma_name))
-            || line.contains(&format!(" {} ", lemma_name))
-        {
-            return true;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{DryRunRunner, ProcessOutput};
+    use crate::test_support::ScratchDir;
+
+    #[test]
+    fn summaries_without_proved_lemmas_say_why() {
+        let scratch = ScratchDir::new("check_summary");
+        let config = RunConfig::default();
+        let lemmas_dir = scratch.path().join("lemmas").to_string_lossy().to_string();
+        fs::create_dir_all(&lemmas_dir).unwrap();
+        let missing = scratch
+            .path()
+            .join("summary.json")
+            .to_string_lossy()
+            .to_string();
+
+        let err = check_summary(&config, &missing, &lemmas_dir).unwrap_err();
+        assert_eq!(err, MinimizeError::MissingSummary(missing.clone()));
+        assert_eq!(err.exit_code(), EXIT_NO_LEMMAS);
+        assert!(err.advice().is_some());
+
+        let garbage = scratch.write("garbage.json", "not a summary");
+        assert!(matches!(
+            check_summary(&config, &garbage, &lemmas_dir),
+            Err(MinimizeError::BadSummary(file, _)) if file == garbage
+        ));
+
+        let empty = scratch.write("summary.json", "{}");
+        let err = check_summary(&config, &empty, &lemmas_dir).unwrap_err();
+        assert_eq!(err, MinimizeError::NoLemmas(empty.clone()));
+        assert_eq!(err.exit_code(), EXIT_NO_LEMMAS);
+        assert!(err.to_string().contains("no lemmas"));
+
+        for n in 1..=2 {
+            scratch.write(
+                &format!("lemmas/single/single_lemma_000{}.p", n),
+                &format!(
+                    "fof(a1, axiom, ! [X] : mult(e, X) = X).\n\nfof(conjecture_000{}, conjecture, mult(e, e) = e).\n",
+                    n
+                ),
+            );
         }
+        let err = check_summary(&config, &empty, &lemmas_dir).unwrap_err();
+        assert_eq!(
+            err,
+            MinimizeError::NoProvedLemmas {
+                summary: empty.clone(),
+                lemmas: 2,
+            }
+        );
+        assert_eq!(err.exit_code(), EXIT_NO_LEMMAS);
+        assert!(err.advice().is_some());
+
+        let failed = MinimizeError::Failed("prover crashed".to_string());
+        assert_eq!(failed.exit_code(), 1);
+        assert!(failed.advice().is_none());
+        let too_long = MinimizeError::TooManySteps { steps: 12, max: 10 };
+        assert_eq!(too_long.exit_code(), EXIT_TOO_MANY_STEPS);
+        assert!(too_long.advice().is_none());
+        let conditional = MinimizeError::ConditionalProof {
+            steps: 4,
+            hypotheses: vec!["single_lemma_0003".to_string()],
+        };
+        assert_eq!(conditional.exit_code(), EXIT_TOO_MANY_STEPS);
+        assert!(conditional.to_string().contains("single_lemma_0003"));
+        assert!(conditional.advice().is_some());
+    }
 
-        // Vampire match (we assume its always a match cause of how Vampire works)
-        if line.contains("[input]") {
-            return true;
+    #[test]
+    fn repeated_candidates_are_told_by_their_formulas() {
+        let mut evaluated = EvaluatedCandidates::default();
+        let deps = [("single_lemma_0001".to_string(), 3)];
+        let first = CandidateKey::new(
+            "mult(X, e) = X",
+            "mult(inv(X), X) = e",
+            &["mult(e, X) = X".to_string()],
+            &deps,
+            0,
+        );
+        assert_eq!(evaluated.repeat_of(&first), None);
+        // the same formulas under other variables
+        let same = CandidateKey::new(
+            "! [Y] : Y = mult(Y, e)",
+            "mult(inv(Z), Z) = e",
+            &["mult(e, Y) = Y".to_string(), "mult(e, X) = X".to_string()],
+            &deps,
+            0,
+        );
+        assert_eq!(same, first);
+        // not a repeat until the first was evaluated to the end
+        assert_eq!(evaluated.repeat_of(&same), None);
+        evaluated.record(first.clone(), "single_lemma_0004", "history_lemma_0002");
+        assert_eq!(
+            evaluated.repeat_of(&same),
+            Some((
+                "single_lemma_0004".to_string(),
+                "history_lemma_0002".to_string()
+            ))
+        );
+        // proved from other formulas, other dependencies or proofs of other lengths
+        let others = [
+            CandidateKey::new("mult(X, e) = X", "mult(inv(X), X) = e", &[], &deps, 0),
+            CandidateKey::new(
+                "mult(X, e) = X",
+                "mult(inv(X), X) = e",
+                &["mult(e, X) = X".to_string()],
+                &[("single_lemma_0003".to_string(), 3)],
+                0,
+            ),
+            CandidateKey::new(
+                "mult(X, e) = X",
+                "mult(inv(X), X) = e",
+                &["mult(e, X) = X".to_string()],
+                &[("single_lemma_0001".to_string(), 5)],
+                0,
+            ),
+            CandidateKey::new(
+                "mult(X, e) = X",
+                "mult(inv(X), X) = e",
+                &["mult(e, X) = X".to_string()],
+                &deps,
+                2,
+            ),
+        ];
+        for other in &others {
+            assert_eq!(evaluated.repeat_of(other), None, "{:?}", other);
         }
+        assert_eq!(evaluated.repeats, 1);
+    }
 
-        false
-    })
+    #[test]
+    fn lemmas_are_generalized_once_and_kept_when_they_cannot_be() {
+        let input = "fof(a1, axiom, ! [X] : op(e, X) = X).\nfof(c, conjecture, op(e, e) = e).";
+        let formula = "! [X] : (op(op(e,e),X) = op(op(e,e),e))".to_string();
+        let mut generalized = HashMap::new();
+        let config = RunConfig::default();
+        let unproved = DryRunRunner::replying(|_| ProcessOutput::success("% SZS status Timeout"));
+        assert_eq!(
+            generalize_with(
+                &unproved,
+                &config,
+                "l1",
+                formula.clone(),
+                input,
+                &mut generalized
+            ),
+            formula
+        );
+        let tried = unproved.calls().len();
+        assert_eq!(tried, 2);
+        // the second time the formula comes up, no prover runs
+        assert_eq!(
+            generalize_with(
+                &unproved,
+                &config,
+                "l2",
+                formula.clone(),
+                input,
+                &mut generalized
+            ),
+            formula
+        );
+        assert_eq!(unproved.calls().len(), tried);
+        // what does not parse is kept as it is
+        assert_eq!(
+            generalize_with(
+                &unproved,
+                &config,
+                "l3",
+                "op(".to_string(),
+                input,
+                &mut generalized
+            ),
+            "op("
+        );
+        assert_eq!(unproved.calls().len(), tried);
+
+        let proved = DryRunRunner::replying(|_| ProcessOutput::success("% SZS status Theorem"));
+        assert_eq!(
+            generalize_with(&proved, &config, "l1", formula, input, &mut HashMap::new()),
+            "! [G0, X] : (op(G0,X) = op(G0,e))"
+        );
+    }
+
+    #[test]
+    fn steps_are_capped_only_when_asked_and_never_run_for_nothing() {
+        assert_eq!(step_cap(false, Some(10), 3), None);
+
+        assert_eq!(step_cap(true, None, 3), None);
+        // a proof of 6 steps after 3 makes 9, still shorter than 10
+        assert_eq!(step_cap(true, Some(10), 3), Some(6));
+        assert_eq!(step_cap(true, Some(4), 3), Some(0));
+        assert_eq!(step_cap(true, Some(4), 7), Some(0));
+
+        // with nothing left to beat, no prover runs: the problem is not even parsed
+        let config = RunConfig::default();
+        let proof = prove_lemma(
+            "not a TPTP problem",
+            &LemmaStore::default(),
+            None,
+            None,
+            vec![],
+            None,
+            ProverOptions {
+                runs: &ProverRuns::new(&DryRunRunner::new(), &config, None, None),
+                max_steps: Some(0),
+            },
+        );
+        assert_eq!(proof, Ok(None));
+    }
+
+    #[test]
+    fn lemmas_making_the_axioms_inconsistent_are_found_once() {
+        let runner = DryRunRunner::replying(|spec| {
+            let input = spec.input.as_deref().unwrap_or("");
+            if input.contains("screen_me_false")
+                || (input.contains("screen_left") && input.contains("screen_right"))
+            {
+                ProcessOutput::success("% SZS status Unsatisfiable for stdin")
+            } else {
+                ProcessOutput::success("% SZS status Satisfiable for stdin")
+            }
+        });
+        let input = "fof(a1, axiom, f(screen_input) = e).\nfof(c, conjecture, e = e).";
+        let lemma = |name: &str, body: &str| AnnotatedFormula::new("fof", name, "axiom", body);
+        let config = RunConfig::default();
+        let screen = ConsistencyScreen::new(&runner, &config);
+        let good = lemma("sp_1", "f(screen_good) = e");
+        let bad = lemma("sp_2", "screen_me_false != screen_me_false");
+        assert_eq!(
+            inconsistent_lemma(&screen, input, &[good.clone(), bad]),
+            Ok(Some("sp_2".to_string()))
+        );
+        let screened = runner.calls().len();
+        assert_eq!(screened, 3);
+        // the same lemma under another name is not screened again
+        let renamed = lemma("sp_7", "screen_me_false != screen_me_false");
+        assert_eq!(
+            inconsistent_lemma(&screen, input, &[good.clone(), renamed]),
+            Ok(Some("sp_7".to_string()))
+        );
+        assert_eq!(runner.calls().len(), screened);
+        assert_eq!(inconsistent_lemma(&screen, input, &[]), Ok(None));
+
+        // lemmas inconsistent only together are found as well
+        let left = lemma("sp_4", "f(screen_left) = e");
+        let right = lemma("sp_5", "f(screen_right) = e");
+        assert_eq!(
+            inconsistent_lemma(&screen, input, &[left, good, right]),
+            Ok(Some("sp_5".to_string()))
+        );
+
+        // inconsistent input axioms are not the lemmas' fault
+        let bad_input = "fof(a1, axiom, screen_me_false = e).";
+        assert_eq!(
+            inconsistent_lemma(&screen, bad_input, &[lemma("sp_3", "f(e) = e")]),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn artifacts_and_replays_go_to_the_output_dir() {
+        let dirs = Dirs::in_workspace("work");
+        assert_eq!(Path::new(&dirs.output), Path::new("work/output"));
+        assert_eq!(
+            Path::new(&dirs.artifact("trace", "grp", "json")),
+            Path::new("work/output/trace_grp.json")
+        );
+        let replay_dir = std::env::temp_dir().join("frankenstein-replay-test");
+        let _ = fs::remove_dir_all(&replay_dir);
+        let dirs = Dirs::new("lemmas", "proofs", &replay_dir.to_string_lossy());
+        assert_eq!(
+            dirs.replay("problems/input_problem_grp.p"),
+            replay_dir.join("replay_grp")
+        );
+
+        // only a replay keeps the prover runs, numbered from 1 for each search
+        let config = RunConfig::default();
+        let runner = DryRunRunner::new();
+        let runs = ProverRuns::new(&runner, &config, None, None);
+        runs.keep("twee", None, "problem", None);
+        assert_eq!(runs.kept.get(), 0);
+        fs::create_dir_all(&replay_dir).unwrap();
+        for _ in 0..2 {
+            let runs = ProverRuns::new(&runner, &config, None, Some(replay_dir.clone()));
+            assert_eq!(runs.provers_for("problem"), Ok(vec!["twee"]));
+            runs.keep("twee", Some("lemma_1"), "problem", Some("proof"));
+            runs.keep("twee", None, "problem", None);
+        }
+        assert_eq!(
+            fs::read_to_string(replay_dir.join("01_lemma_1.out")).unwrap(),
+            "proof"
+        );
+        assert_eq!(
+            fs::read_to_string(replay_dir.join("02_conjecture.out")).unwrap(),
+            "% twee found no proof\n"
+        );
+        assert!(!replay_dir.join("03_conjecture.p").exists());
+        let _ = fs::remove_dir_all(&replay_dir);
+    }
+
+    #[test]
+    fn replays_keep_their_result_in_their_own_dir() {
+        let dir = std::env::temp_dir().join(format!("frankenstein-replay-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let candidate = TraceCandidate::new(CandidateKind::Search, "single_lemma_0001", None);
+        let replay = Replay {
+            candidate: &candidate,
+            dir: dir.clone(),
+        };
+        let result = Some((
+            4,
+            "proof".to_string(),
+            "dag".to_string(),
+            "lemmas".to_string(),
+        ));
+        let message = finish_replay(&replay, result).unwrap();
+        assert!(message.contains(&dir.display().to_string()));
+        assert_eq!(fs::read_to_string(dir.join("proof.out")).unwrap(), "proof");
+        assert_eq!(fs::read_to_string(dir.join("dag.txt")).unwrap(), "dag");
+        assert_eq!(fs::read_to_string(dir.join("lemmas.p")).unwrap(), "lemmas");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn source_choices_say_why() {
+        let start = SourceChoice::start(None, 3, 5);
+        assert_eq!(
+            (start.chosen, start.reason.as_str()),
+            (Backend::Vampire, "not longer")
+        );
+        assert_eq!(SourceChoice::start(None, 5, 3).chosen, Backend::Twee);
+        assert_eq!(
+            SourceChoice::start(None, 0, 3).reason,
+            "no superposition steps"
+        );
+        assert_eq!(
+            SourceChoice::start(None, 4, 0).reason,
+            "no dependency proofs"
+        );
+
+        let history = SourceChoice::history(None, 3, 3);
+        assert_eq!(
+            (history.part.as_str(), history.chosen),
+            ("history", Backend::Twee)
+        );
+        assert_eq!(SourceChoice::history(None, 2, 3).chosen, Backend::Vampire);
+        let forced = SourceChoice::history(Some(Backend::Vampire), 3, 3);
+        assert_eq!(
+            (forced.chosen, forced.reason.as_str()),
+            (Backend::Vampire, "forced by --force-backend")
+        );
+
+        let candidate = TraceCandidate {
+            kind: CandidateKind::Search,
+            root: "single_lemma_0003".to_string(),
+            history: None,
+            steps: Some(4),
+            choices: vec![start],
+        };
+        let json = serde_json::to_string(&candidate).unwrap();
+        assert!(json.contains("\"chosen\":\"vampire\""), "{}", json);
+        let read: TraceCandidate = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, candidate);
+        // traces written before the choices were recorded
+        let old: TraceCandidate =
+            serde_json::from_str(r#"{"root": "r", "history": null, "steps": 2}"#).unwrap();
+        assert!(old.choices.is_empty());
+    }
+
+    #[test]
+    fn roots_that_cannot_be_generalized_are_skipped() {
+        let input = "fof(a1, axiom, f(e) = e).\nfof(goal, conjecture, f(f(e)) = e).";
+        let proved = DryRunRunner::replying(|_| ProcessOutput::success("% SZS status Theorem\n"));
+        let root = |runner: &DryRunRunner, skolems, formula: &str| {
+            let config = RunConfig {
+                skolems,
+                ..RunConfig::default()
+            };
+            root_formula_with(runner, &config, "root", formula.to_string(), input)
+        };
+
+        // without Skolem constants the policy does not matter
+        assert_eq!(
+            root(&proved, SkolemPolicy::Skip, "f(e) = e"),
+            Ok(Some("f(e) = e".to_string()))
+        );
+        assert_eq!(root(&proved, SkolemPolicy::Skip, "f(sK0) = e"), Ok(None));
+        assert_eq!(
+            root(&proved, SkolemPolicy::Keep, "f(sK0) = e"),
+            Ok(Some("f(sK0) = e".to_string()))
+        );
+        assert!(proved.calls().is_empty());
+
+        let generalized = root(&proved, SkolemPolicy::Generalize, "f(sK0) = e")
+            .unwrap()
+            .unwrap();
+        assert!(!generalized.contains("sK0"), "{}", generalized);
+        let calls = proved.calls();
+        assert_eq!(calls.len(), 1);
+        let problem = calls[0].input.as_deref().unwrap();
+        assert!(problem.contains("fof(root, conjecture"), "{}", problem);
+        assert!(!problem.contains("goal"), "{}", problem);
+
+        // an unparsable root or a Skolem function is skipped, not an error
+        assert_eq!(
+            root(&proved, SkolemPolicy::Generalize, "f(sK0 = e"),
+            Ok(None)
+        );
+        assert_eq!(
+            root(&proved, SkolemPolicy::Generalize, "sK1(X) = e"),
+            Ok(None)
+        );
+        assert_eq!(proved.calls().len(), 1);
+
+        // so is a generalization that does not follow from the axioms
+        let unproved = DryRunRunner::replying(|_| ProcessOutput::success(""));
+        assert_eq!(
+            root(&unproved, SkolemPolicy::Generalize, "f(sK0) = e"),
+            Ok(None)
+        );
+        assert_eq!(unproved.calls().len(), 1);
+    }
+
+    #[test]
+    fn direct_candidates_are_told_apart_from_root_only_ones() {
+        let direct = TraceCandidate {
+            steps: Some(3),
+            ..TraceCandidate::new(CandidateKind::Direct, "single_lemma_0003", None)
+        };
+        let json = serde_json::to_string(&direct).unwrap();
+        assert!(json.contains("\"kind\":\"direct\""), "{}", json);
+        let read: TraceCandidate = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, direct);
+        let root_only = TraceCandidate::new(CandidateKind::Search, "single_lemma_0003", None);
+        let read: TraceCandidate =
+            serde_json::from_str(&serde_json::to_string(&root_only).unwrap()).unwrap();
+        assert_eq!(read, root_only);
+
+        // traces written before direct proofs were tried
+        let old: TraceCandidate =
+            serde_json::from_str(r#"{"root": "r", "history": null, "steps": 2}"#).unwrap();
+        assert_eq!(old.kind, CandidateKind::Search);
+
+        // a replay runs only the candidate of its kind
+        let runs = |replay: Option<&TraceCandidate>, kind| {
+            evaluates(replay, kind, "single_lemma_0003", None)
+        };
+        assert!(runs(Some(&direct), CandidateKind::Direct));
+        assert!(!runs(Some(&direct), CandidateKind::Search));
+        assert!(runs(Some(&root_only), CandidateKind::Search));
+        assert!(!runs(Some(&root_only), CandidateKind::Direct));
+        assert!(runs(None, CandidateKind::Direct));
+    }
 }
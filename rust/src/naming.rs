@@ -0,0 +1,64 @@
+use crate::tptp_file::TptpFile;
+use std::collections::BTreeSet;
+
+/// Hands out formula names that are not in use yet: the names of the input
+/// problem and everything reserved so far, e.g. the lemmas a candidate adds.
+/// The same reservations made in the same order give the same names.
+#[derive(Debug, Clone, Default)]
+pub struct NameAllocator {
+    taken: BTreeSet<String>,
+}
+
+impl NameAllocator {
+    pub fn new() -> NameAllocator {
+        NameAllocator::default()
+    }
+
+    /// An allocator for formulas added to `problem`
+    pub fn for_problem(problem: &str) -> Result<NameAllocator, String> {
+        let mut names = NameAllocator::new();
+        for formula in TptpFile::parse(problem)?.formulas() {
+            names.taken.insert(formula.name.clone());
+        }
+        Ok(names)
+    }
+
+    /// Mark names as used
+    pub fn reserve<S: AsRef<str>>(&mut self, names: &[S]) {
+        self.taken
+            .extend(names.iter().map(|n| n.as_ref().to_string()));
+    }
+
+    /// `preferred` if it is still free, otherwise the first free
+    /// `preferred_2`, `preferred_3`, ... The name returned is taken from now on.
+    pub fn fresh(&mut self, preferred: &str) -> String {
+        let name = if self.taken.contains(preferred) {
+            (2..)
+                .map(|n| format!("{}_{}", preferred, n))
+                .find(|name| !self.taken.contains(name))
+                .unwrap()
+        } else {
+            preferred.to_string()
+        };
+        self.taken.insert(name.clone());
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fresh_names_avoid_the_problem_and_reservations() {
+        let problem = "fof(single_lemma_0001, axiom, a = b).\nfof(goal, conjecture, a = c).";
+        let mut names = NameAllocator::for_problem(problem).unwrap();
+        names.reserve(&["single_lemma_0002"]);
+
+        assert_eq!(names.fresh("single_lemma_0001"), "single_lemma_0001_2");
+        assert_eq!(names.fresh("single_lemma_0002"), "single_lemma_0002_2");
+        assert_eq!(names.fresh("single_lemma_0002"), "single_lemma_0002_3");
+        assert_eq!(names.fresh("single_lemma_0003"), "single_lemma_0003");
+        assert_eq!(names.fresh("goal"), "goal_2");
+    }
+}
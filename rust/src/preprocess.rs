@@ -0,0 +1,199 @@
+use crate::provenance::write_with_provenance;
+use crate::run_config::RunConfig;
+use crate::tptp_file::{AnnotatedFormula, TptpFile};
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::Path;
+
+// Reducing a problem before `run_vampire`: SInE-style axiom selection keeps
+// the axioms whose rarest symbols are reachable from the conjecture, and the
+// kept formulas are normalized to one line each with duplicates dropped.
+
+/// SInE defaults: an axiom is triggered by symbols at most this many times as
+/// common as its rarest symbol, with no limit on the depth
+pub const DEFAULT_TOLERANCE: f64 = 1.0;
+
+/// How far relevance spreads from the conjecture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SineParams {
+    pub tolerance: f64,
+    /// rounds of selection after the conjecture's symbols, unlimited if none
+    pub depth: Option<usize>,
+}
+
+impl Default for SineParams {
+    fn default() -> SineParams {
+        SineParams {
+            tolerance: DEFAULT_TOLERANCE,
+            depth: None,
+        }
+    }
+}
+
+/// Function and predicate symbols of a formula body; variables and `$` builtins are left out
+fn symbols(body: &str) -> BTreeSet<String> {
+    let symbol_re = Regex::new(r"(?:^|[^$\w])([a-z]\w*)").unwrap();
+    symbol_re
+        .captures_iter(body)
+        .map(|c| c[1].to_string())
+        .collect()
+}
+
+/// Formulas relevance starts from
+fn is_goal(formula: &AnnotatedFormula) -> bool {
+    formula.is_conjecture() || formula.role == "hypothesis"
+}
+
+/// Names of the axioms of `file` selected for its conjecture
+pub fn select_axioms(file: &TptpFile, params: SineParams) -> Result<BTreeSet<String>, String> {
+    let axioms: Vec<(&str, BTreeSet<String>)> = file
+        .formulas()
+        .filter(|f| !is_goal(f))
+        .map(|f| (f.name.as_str(), symbols(&f.body)))
+        .collect();
+
+    let mut occurrences: BTreeMap<&str, usize> = BTreeMap::new();
+    for (_, syms) in &axioms {
+        for s in syms {
+            *occurrences.entry(s.as_str()).or_default() += 1;
+        }
+    }
+
+    // symbol -> axioms it triggers
+    let mut triggers: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    let mut selected = BTreeSet::new();
+    for (i, (name, syms)) in axioms.iter().enumerate() {
+        let Some(rarest) = syms.iter().map(|s| occurrences[s.as_str()]).min() else {
+            // no symbols, e.g. `X = Y`: nothing can trigger it, and it is cheap to keep
+            selected.insert(name.to_string());
+            continue;
+        };
+        for s in syms {
+            if occurrences[s.as_str()] as f64 <= params.tolerance * rarest as f64 {
+                triggers.entry(s.as_str()).or_default().push(i);
+            }
+        }
+    }
+
+    if !file.formulas().any(is_goal) {
+        return Err("No conjecture to select axioms for".to_string());
+    }
+    let mut reached: HashSet<String> = HashSet::new();
+    let mut frontier: Vec<String> = file
+        .formulas()
+        .filter(|f| is_goal(f))
+        .flat_map(|f| symbols(&f.body))
+        .collect();
+
+    let mut round = 0;
+    while !frontier.is_empty() && params.depth.is_none_or(|d| round < d) {
+        let mut next = Vec::new();
+        for s in frontier {
+            if !reached.insert(s.clone()) {
+                continue;
+            }
+            for &i in triggers.get(s.as_str()).into_iter().flatten() {
+                let (name, syms) = &axioms[i];
+                if selected.insert(name.to_string()) {
+                    next.extend(syms.iter().filter(|s| !reached.contains(*s)).cloned());
+                }
+            }
+        }
+        frontier = next;
+        round += 1;
+    }
+    Ok(selected)
+}
+
+/// `file` with only the selected axioms and the goals, one formula per line,
+/// without comments and without repeated formulas
+pub fn reduce(file: &TptpFile, params: SineParams) -> Result<(String, usize, usize), String> {
+    let selected = select_axioms(file, params)?;
+    let mut seen = HashSet::new();
+    let mut lines = Vec::new();
+    let mut axioms = 0;
+    for f in file.formulas() {
+        if !is_goal(f) {
+            axioms += 1;
+            if !selected.contains(&f.name) {
+                continue;
+            }
+        }
+        let body = f.body_line();
+        if !seen.insert((f.language.clone(), f.role.clone(), body.clone())) {
+            continue;
+        }
+        lines.push(format!("{}({}, {}, {}).", f.language, f.name, f.role, body));
+    }
+    let kept = lines.len() - file.formulas().filter(|f| is_goal(f)).count();
+    Ok((lines.join("\n") + "\n", kept, axioms))
+}
+
+/// Write the reduced `input_file` to `output_file`
+pub fn preprocess(
+    config: &RunConfig,
+    input_file: &str,
+    output_file: &str,
+    params: SineParams,
+) -> Result<String, String> {
+    let text = fs::read_to_string(input_file)
+        .map_err(|e| format!("Failed to read {}: {}", input_file, e))?;
+    if text.lines().any(|l| l.trim_start().starts_with("include(")) {
+        return Err(format!(
+            "{} has include directives; expand them first (e.g. with tptp4X)",
+            input_file
+        ));
+    }
+    let file = TptpFile::parse(&text)?;
+    let (reduced, kept, axioms) = reduce(&file, params)?;
+
+    if let Some(dir) = Path::new(output_file).parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    write_with_provenance(config, output_file, &reduced, input_file)
+        .map_err(|e| format!("Failed to write {}: {}", output_file, e))?;
+    Ok(format!(
+        "[INFO] Kept {} of {} axioms (tolerance {}, depth {}), reduced problem written to {}",
+        kept,
+        axioms,
+        params.tolerance,
+        params
+            .depth
+            .map_or("unlimited".to_string(), |d| d.to_string()),
+        output_file
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn axioms_unrelated_to_the_conjecture_are_dropped() {
+        let problem = "% a comment\n\
+            fof(a1, axiom, ! [X] : mult(e, X) = X).\n\
+            fof(a2, axiom, ! [X] : mult(inv(X), X) = e).\n\
+            fof(a3, axiom, ! [X] : add(zero, X) = X).\n\
+            fof(a4, axiom,\n    ! [X] : mult(e, X) = X).\n\
+            fof(c, conjecture, mult(e, e) = e).\n";
+        let file = TptpFile::parse(problem).unwrap();
+
+        let selected = select_axioms(&file, SineParams::default()).unwrap();
+        // inv is the rarest symbol of a2, and the conjecture does not mention it
+        assert_eq!(selected.into_iter().collect::<Vec<_>>(), vec!["a1", "a4"]);
+        let shallow = SineParams {
+            depth: Some(0),
+            ..SineParams::default()
+        };
+        assert!(select_axioms(&file, shallow).unwrap().is_empty());
+
+        let (reduced, kept, axioms) = reduce(&file, SineParams::default()).unwrap();
+        assert_eq!((kept, axioms), (1, 4));
+        assert_eq!(
+            reduced,
+            "fof(a1, axiom, ! [X] : mult(e, X) = X).\nfof(c, conjecture, mult(e, e) = e).\n"
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::Duration;
+use wait_timeout::ChildExt;
+
+/// An external command: program, arguments, optional stdin and a wall-clock limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessSpec {
+    pub program: String,
+    pub args: Vec<String>,
+    pub input: Option<String>,
+    pub timeout: Duration,
+}
+
+impl ProcessSpec {
+    pub fn new(program: &str, timeout: Duration) -> ProcessSpec {
+        ProcessSpec {
+            program: program.to_string(),
+            args: Vec::new(),
+            input: None,
+            timeout,
+        }
+    }
+
+    pub fn args<S: AsRef<str>>(mut self, args: &[S]) -> ProcessSpec {
+        self.args
+            .extend(args.iter().map(|a| a.as_ref().to_string()));
+        self
+    }
+
+    /// Stream `input` to the command over stdin
+    pub fn input(mut self, input: &str) -> ProcessSpec {
+        self.input = Some(input.to_string());
+        self
+    }
+
+    /// The command line, for logs
+    pub fn command_line(&self) -> String {
+        std::iter::once(self.program.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// What a command produced. `exit_code` is `None` if it was killed, e.g. on timeout.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessOutput {
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub stdout: String,
+    pub stderr: String,
+    /// the bytes of stdout as printed, if they were not plain UTF-8
+    pub raw_stdout: Option<Vec<u8>>,
+}
+
+impl ProcessOutput {
+    /// A successful run printing `stdout`
+    pub fn success(stdout: &str) -> ProcessOutput {
+        ProcessOutput {
+            exit_code: Some(0),
+            stdout: stdout.to_string(),
+            ..ProcessOutput::default()
+        }
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Runs external commands (provers, the frankenstein binary in benchmarks).
+/// Errors are failures to start or wait for the command, not non-zero exits.
+pub trait ProcessRunner: Send + Sync {
+    fn run(&self, spec: &ProcessSpec) -> Result<ProcessOutput, String>;
+}
+
+/// Runs commands on this machine
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalRunner;
+
+impl ProcessRunner for LocalRunner {
+    fn run(&self, spec: &ProcessSpec) -> Result<ProcessOutput, String> {
+        let stdin = if spec.input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::inherit()
+        };
+        let mut child = Command::new(&spec.program)
+            .args(&spec.args)
+            .stdin(stdin)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start process '{}': {}", spec.program, e))?;
+
+        // write from a separate thread so a full stdout pipe cannot block us
+        if let (Some(input), Some(mut pipe)) = (spec.input.clone(), child.stdin.take()) {
+            std::thread::spawn(move || {
+                let _ = pipe.write_all(input.as_bytes());
+            });
+        }
+
+        // read both pipes while waiting: a prover printing more than a pipe
+        // holds (a long proof) would otherwise block until the timeout
+        let stdout = drain(child.stdout.take());
+        let stderr = drain(child.stderr.take());
+
+        let status = child
+            .wait_timeout(spec.timeout)
+            .map_err(|e| format!("Failed waiting for '{}': {}", spec.program, e))?;
+        if status.is_none() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        let stdout = collect(&spec.program, stdout)?;
+        let stderr = collect(&spec.program, stderr)?;
+
+        let (text, converted) = decode_output(&stdout);
+        if converted {
+            eprintln!(
+                "[WARN] Output of '{}' is not UTF-8, converted it and kept the original bytes",
+                spec.program
+            );
+        }
+        Ok(ProcessOutput {
+            exit_code: status.and_then(|s| s.code()),
+            timed_out: status.is_none(),
+            stdout: text,
+            stderr: decode_output(&stderr).0,
+            raw_stdout: converted.then_some(stdout),
+        })
+    }
+}
+
+type Drain = std::thread::JoinHandle<std::io::Result<Vec<u8>>>;
+
+/// Read `pipe` to its end on a separate thread
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> Drain {
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut bytes)?;
+        }
+        Ok(bytes)
+    })
+}
+
+/// What a `drain` thread read
+fn collect(program: &str, reader: Drain) -> Result<Vec<u8>, String> {
+    reader
+        .join()
+        .map_err(|_| format!("Failed to collect output of '{}'", program))?
+        .map_err(|e| format!("Failed to collect output of '{}': {}", program, e))
+}
+
+/// Text of a command's output. UTF-8 is taken as is (without a byte order
+/// mark), UTF-16 with a byte order mark is converted, and bytes that are not
+/// UTF-8 otherwise are read as Latin-1, so none is replaced by U+FFFD and a
+/// formula around them still parses. Also returns whether anything was converted.
+pub fn decode_output(bytes: &[u8]) -> (String, bool) {
+    if let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        let (text, _) = decode_output(rest);
+        return (text, true);
+    }
+    let utf16 = |rest: &[u8], unit: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = rest
+            .chunks_exact(2)
+            .map(|pair| unit([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16(&units).ok()
+    };
+    let decoded = match bytes {
+        [0xFF, 0xFE, rest @ ..] if rest.len() % 2 == 0 => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] if rest.len() % 2 == 0 => utf16(rest, u16::from_be_bytes),
+        _ => None,
+    };
+    if let Some(text) = decoded {
+        return (text, true);
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return (text.to_string(), false);
+    }
+
+    let mut text = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        text.push_str(chunk.valid());
+        text.extend(chunk.invalid().iter().map(|&b| char::from(b)));
+    }
+    (text, true)
+}
+
+type Reply = Box<dyn Fn(&ProcessSpec) -> ProcessOutput + Send + Sync>;
+
+/// Records commands instead of running them, answering each with a canned output.
+/// Used for dry runs and as an in-memory fake prover in tests.
+pub struct DryRunRunner {
+    reply: Reply,
+    calls: Mutex<Vec<ProcessSpec>>,
+}
+
+impl DryRunRunner {
+    /// Every command succeeds without output
+    pub fn new() -> DryRunRunner {
+        DryRunRunner::replying(|_| ProcessOutput::success(""))
+    }
+
+    /// Every command is answered by `reply`
+    pub fn replying<F>(reply: F) -> DryRunRunner
+    where
+        F: Fn(&ProcessSpec) -> ProcessOutput + Send + Sync + 'static,
+    {
+        DryRunRunner {
+            reply: Box::new(reply),
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The commands received so far, in order
+    pub fn calls(&self) -> Vec<ProcessSpec> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl Default for DryRunRunner {
+    fn default() -> DryRunRunner {
+        DryRunRunner::new()
+    }
+}
+
+impl ProcessRunner for DryRunRunner {
+    fn run(&self, spec: &ProcessSpec) -> Result<ProcessOutput, String> {
+        println!("[DRY-RUN] {}", spec.command_line());
+        self.calls.lock().unwrap().push(spec.clone());
+        Ok((self.reply)(spec))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn local_runner_captures_output_and_exit_code() {
+        let spec = ProcessSpec::new("sh", Duration::from_secs(10))
+            .args(&["-c", "cat; echo err >&2; exit 3"])
+            .input("hello");
+        let output = LocalRunner.run(&spec).unwrap();
+        assert_eq!(output.exit_code, Some(3));
+        assert!(!output.succeeded());
+        assert_eq!(output.stdout, "hello");
+        assert_eq!(output.stderr, "err\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn local_runner_reads_output_larger_than_a_pipe() {
+        // 200KB on each pipe, far more than a pipe buffer holds
+        let spec = ProcessSpec::new("sh", Duration::from_secs(10)).args(&[
+            "-c",
+            "head -c 200000 /dev/zero | tr '\\0' a; head -c 200000 /dev/zero | tr '\\0' b >&2",
+        ]);
+        let output = LocalRunner.run(&spec).unwrap();
+        assert!(!output.timed_out);
+        assert!(output.succeeded());
+        assert_eq!(output.stdout.len(), 200000);
+        assert_eq!(output.stderr.len(), 200000);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn local_runner_kills_on_timeout() {
+        let spec = ProcessSpec::new("sleep", Duration::from_millis(100)).args(&["10"]);
+        let output = LocalRunner.run(&spec).unwrap();
+        assert!(output.timed_out);
+        assert_eq!(output.exit_code, None);
+    }
+
+    #[test]
+    fn decoding_keeps_every_byte() {
+        assert_eq!(decode_output(b"a = b"), ("a = b".to_string(), false));
+        // a stray Latin-1 byte inside otherwise UTF-8 output
+        assert_eq!(
+            decode_output(b"f(\xE9, \xC3\xA9) = X"),
+            ("f(\u{e9}, \u{e9}) = X".to_string(), true)
+        );
+        assert_eq!(
+            decode_output(b"\xFF\xFEa\x00=\x00b\x00"),
+            ("a=b".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn local_runner_reports_missing_program() {
+        let spec = ProcessSpec::new("./no-such-prover", Duration::from_secs(1));
+        assert!(LocalRunner.run(&spec).is_err());
+    }
+
+    #[test]
+    fn dry_run_records_commands() {
+        let runner = DryRunRunner::replying(|spec| {
+            ProcessOutput::success(&format!("% SZS status Theorem for {}", spec.args[0]))
+        });
+        let spec = ProcessSpec::new("../bin/twee", Duration::from_secs(1)).args(&["a.p"]);
+        let output = runner.run(&spec).unwrap();
+
+        assert_eq!(output.stdout, "% SZS status Theorem for a.p");
+        assert_eq!(runner.calls(), vec![spec]);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::alpha_match::canonical_form;
+use crate::provenance::strip;
+use crate::vampire_proof::parse_vampire_line;
+use regex::Regex;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+
+/// A derived formula of an annotated proof: a superposition step, a Twee
+/// lemma or goal, or a Vampire inference. Input axioms are not steps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Step {
+    /// where the step comes from, e.g. "single_lemma_0003" or "twee lemma 2"
+    label: String,
+    formula: String,
+    /// how it was derived: dependencies, rewrite count or inference rule
+    detail: String,
+}
+
+/// The steps of an annotated proof in the order they appear
+fn proof_steps(proof: &str) -> Vec<Step> {
+    let superposition_re = Regex::new(r"^% (\S+): (.*) \| deps: (.*)$").unwrap();
+    let twee_re = Regex::new(r"^(Lemma|Goal) (\d+)(?: \(([^)]*)\))?: (.*)\.$").unwrap();
+
+    let mut steps = Vec::new();
+    // index in `steps` and rewrite count of the Twee statement being proved
+    let mut twee_step: Option<(usize, usize)> = None;
+    let finish_twee = |steps: &mut Vec<Step>, twee_step: &mut Option<(usize, usize)>| {
+        if let Some((i, rewrites)) = twee_step.take() {
+            steps[i].detail = format!("{} rewrites", rewrites);
+        }
+    };
+
+    for line in strip(proof).lines() {
+        let trimmed = line.trim();
+        if let Some(cap) = superposition_re.captures(line) {
+            steps.push(Step {
+                label: cap[1].to_string(),
+                formula: cap[2].trim().to_string(),
+                detail: format!("deps: {}", cap[3].trim()),
+            });
+        } else if let Some(cap) = twee_re.captures(trimmed) {
+            finish_twee(&mut steps, &mut twee_step);
+            let label = match cap.get(3) {
+                Some(name) => format!("twee {} {}", cap[1].to_lowercase(), name.as_str()),
+                None => format!("twee {} {}", cap[1].to_lowercase(), &cap[2]),
+            };
+            steps.push(Step {
+                label,
+                formula: cap[4].trim().to_string(),
+                detail: String::new(),
+            });
+            twee_step = Some((steps.len() - 1, 0));
+        } else if trimmed.starts_with("= { by") {
+            if let Some((_, rewrites)) = twee_step.as_mut() {
+                *rewrites += 1;
+            }
+        } else if trimmed.starts_with("RESULT:") || trimmed.starts_with("% SZS") {
+            finish_twee(&mut steps, &mut twee_step);
+        } else if let Some(step) = parse_vampire_line(line).filter(|s| s.is_inference()) {
+            steps.push(Step {
+                label: format!("vampire {}", step.index),
+                formula: step.formula,
+                detail: step.rule,
+            });
+        }
+    }
+    finish_twee(&mut steps, &mut twee_step);
+    steps
+}
+
+/// Differences between two proofs, steps being aligned by alpha-equivalent formulas
+#[derive(Debug, Default)]
+struct ProofDiff {
+    common: usize,
+    changed: Vec<(Step, Step)>,
+    removed: Vec<Step>,
+    added: Vec<Step>,
+}
+
+fn diff_steps(a: &[Step], b: &[Step]) -> ProofDiff {
+    let mut unmatched: BTreeMap<String, VecDeque<&Step>> = BTreeMap::new();
+    for step in b {
+        unmatched
+            .entry(canonical_form(&step.formula))
+            .or_default()
+            .push_back(step);
+    }
+
+    let mut diff = ProofDiff::default();
+    for step in a {
+        match unmatched
+            .get_mut(&canonical_form(&step.formula))
+            .and_then(|same| same.pop_front())
+        {
+            Some(other) if other.detail == step.detail => diff.common += 1,
+            Some(other) => diff.changed.push((step.clone(), other.clone())),
+            None => diff.removed.push(step.clone()),
+        }
+    }
+    // what is left of `b` in proof order
+    diff.added = b
+        .iter()
+        .filter(|step| {
+            unmatched
+                .get(&canonical_form(&step.formula))
+                .is_some_and(|left| left.iter().any(|s| std::ptr::eq(*s, *step)))
+        })
+        .cloned()
+        .collect();
+    diff
+}
+
+/// `proof-diff <a> <b>`: the steps that were removed, added or derived
+/// differently in `b` compared to `a`
+pub fn proof_diff(file_a: &str, file_b: &str) -> Result<(), String> {
+    let read = |file: &str| {
+        fs::read_to_string(file).map_err(|e| format!("Failed to read {}: {}", file, e))
+    };
+    let steps_a = proof_steps(&read(file_a)?);
+    let steps_b = proof_steps(&read(file_b)?);
+    let diff = diff_steps(&steps_a, &steps_b);
+
+    println!("--- {} ({} steps)", file_a, steps_a.len());
+    println!("+++ {} ({} steps)", file_b, steps_b.len());
+    for step in &diff.removed {
+        println!("- {}: {} [{}]", step.label, step.formula, step.detail);
+    }
+    for step in &diff.added {
+        println!("+ {}: {} [{}]", step.label, step.formula, step.detail);
+    }
+    for (old, new) in &diff.changed {
+        println!(
+            "~ {} -> {}: {} [{}] -> [{}]",
+            old.label, new.label, new.formula, old.detail, new.detail
+        );
+    }
+    println!(
+        "[RESULT] {} unchanged, {} changed, {} removed, {} added",
+        diff.common,
+        diff.changed.len(),
+        diff.removed.len(),
+        diff.added.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROOF: &str = "\
+% === Superposition Steps ===
+% single_lemma_0001: mult(e,X0) = X0 | deps: a1->0
+
+Lemma 1: mult(X, e) = X.
+Proof:
+  mult(X, e)
+= { by axiom 1 (a1) }
+  mult(e, X)
+= { by lemma 0 }
+  X
+
+Goal 1 (goal): mult(e, b) = b.
+Proof:
+  mult(e, b)
+= { by lemma 1 }
+  b
+
+RESULT: Theorem (the conjecture is true).
+";
+
+    #[test]
+    fn reads_superposition_and_twee_steps() {
+        let steps = proof_steps(PROOF);
+        let summary: Vec<_> = steps
+            .iter()
+            .map(|s| (s.label.as_str(), s.detail.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("single_lemma_0001", "deps: a1->0"),
+                ("twee lemma 1", "2 rewrites"),
+                ("twee goal goal", "1 rewrites"),
+            ]
+        );
+    }
+
+    #[test]
+    fn aligns_steps_up_to_variable_names() {
+        let other = PROOF
+            .replace("mult(X, e) = X", "mult(Y, e) = Y")
+            .replace(
+                "= { by lemma 1 }\n  b",
+                "= { by axiom 1 (a1) }\n  b\n= { by lemma 1 }\n  b",
+            )
+            .replace("% single_lemma_0001: mult(e,X0) = X0 | deps: a1->0\n", "");
+        let diff = diff_steps(&proof_steps(PROOF), &proof_steps(&other));
+
+        assert_eq!(diff.common, 1);
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].label, "single_lemma_0001");
+        assert!(diff.added.is_empty());
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].1.detail, "2 rewrites");
+    }
+}
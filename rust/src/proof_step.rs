@@ -0,0 +1,418 @@
+use crate::formula::{parse_formula, Formula};
+use crate::prover_wrapper::backend;
+use crate::tptp_file::TptpFile;
+use crate::twee_proof::{parse_twee_proof, Direction, TweeKind};
+use crate::vampire_proof::{inference_name, parse_vampire_steps, StepRole, VampireStep};
+use std::collections::BTreeMap;
+use std::fmt;
+
+// The steps of a proof in one shape for every prover, so lengths, lemma use
+// and printing do not depend on how Vampire, Twee or egg write their proofs.
+
+/// What a step is in its proof
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofRole {
+    Axiom,
+    Conjecture,
+    NegatedConjecture,
+    /// a statement proved by earlier steps, e.g. a Twee lemma
+    Lemma,
+    /// a core inference, counted in the proof length
+    Inference,
+    /// clausification, skolemisation, splitting and other bookkeeping
+    Preprocessing,
+}
+
+impl ProofRole {
+    /// TPTP role of the step
+    pub fn tptp_role(self) -> &'static str {
+        match self {
+            ProofRole::Axiom => "axiom",
+            ProofRole::Conjecture => "conjecture",
+            ProofRole::NegatedConjecture => "negated_conjecture",
+            ProofRole::Lemma => "lemma",
+            ProofRole::Inference | ProofRole::Preprocessing => "plain",
+        }
+    }
+}
+
+/// Class of the rule of an inference, for reports of what proofs are made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RuleKind {
+    Superposition,
+    Demodulation,
+    /// a rewrite of a Twee rewrite chain
+    Rewrite,
+    /// a proved lemma used in a later proof
+    Cut,
+    Other,
+}
+
+impl RuleKind {
+    pub const ALL: [RuleKind; 5] = [
+        RuleKind::Superposition,
+        RuleKind::Demodulation,
+        RuleKind::Rewrite,
+        RuleKind::Cut,
+        RuleKind::Other,
+    ];
+
+    /// The class of `rule`, e.g. "forward demodulation" or "rewrite right to left"
+    pub fn of(rule: &str) -> RuleKind {
+        if rule.contains("superposition") {
+            RuleKind::Superposition
+        } else if rule.contains("demodulation") {
+            RuleKind::Demodulation
+        } else if rule.starts_with("rewrite") {
+            RuleKind::Rewrite
+        } else if rule == "cut" {
+            RuleKind::Cut
+        } else {
+            RuleKind::Other
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RuleKind::Superposition => "superposition",
+            RuleKind::Demodulation => "demodulation",
+            RuleKind::Rewrite => "rewrite",
+            RuleKind::Cut => "cut",
+            RuleKind::Other => "other",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<RuleKind> {
+        RuleKind::ALL.into_iter().find(|k| k.name() == name)
+    }
+}
+
+/// Number of inferences of `steps` of each kind of rule
+pub fn rule_breakdown(steps: &[ProofStep]) -> BTreeMap<RuleKind, usize> {
+    let mut rules = BTreeMap::new();
+    for step in steps.iter().filter(|s| s.is_inference()) {
+        *rules.entry(RuleKind::of(&step.rule)).or_default() += 1;
+    }
+    rules
+}
+
+/// `superposition 3, rewrite 4`, leaving out kinds without steps
+pub fn format_breakdown(rules: &BTreeMap<RuleKind, usize>) -> String {
+    let parts: Vec<String> = rules
+        .iter()
+        .filter(|(_, &n)| n > 0)
+        .map(|(kind, n)| format!("{} {}", kind.name(), n))
+        .collect();
+    if parts.is_empty() {
+        "none".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+/// Read back what `format_breakdown` printed; unknown kinds are skipped
+pub fn parse_breakdown(text: &str) -> BTreeMap<RuleKind, usize> {
+    text.split(',')
+        .filter_map(|part| {
+            let (name, n) = part.trim().rsplit_once(' ')?;
+            Some((RuleKind::parse(name)?, n.parse().ok()?))
+        })
+        .collect()
+}
+
+/// One step of a proof: its formula, the rule that derived it and the names
+/// of the steps it was derived from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofStep {
+    pub name: String,
+    pub formula: Formula,
+    /// e.g. "superposition", "rewrite" or "rightSubst"; "input" for axioms
+    pub rule: String,
+    pub premises: Vec<String>,
+    pub role: ProofRole,
+}
+
+impl ProofStep {
+    /// Whether this step is one of the core inferences counted in the proof length
+    pub fn is_inference(&self) -> bool {
+        self.role == ProofRole::Inference
+    }
+
+    /// A Vampire step named `f<n>` after its number. Split assumptions
+    /// (`C <- (1)`) are dropped from the formula, see `resolve_splitting`.
+    pub fn from_vampire(step: &VampireStep) -> Result<ProofStep, String> {
+        let formula = step.formula.split(" <- ").next().unwrap_or_default();
+        let formula = parse_formula(formula)
+            .map_err(|e| format!("Cannot parse step {}: {}", step.index, e))?;
+        let role = match step.role {
+            StepRole::Input => ProofRole::Axiom,
+            StepRole::NegatedConjecture => ProofRole::NegatedConjecture,
+            StepRole::Inference if step.is_inference() => ProofRole::Inference,
+            StepRole::Inference | StepRole::Splitting => ProofRole::Preprocessing,
+        };
+        Ok(ProofStep {
+            name: format!("f{}", step.index),
+            formula,
+            rule: step.rule.clone(),
+            premises: step.premises.iter().map(|p| format!("f{}", p)).collect(),
+            role,
+        })
+    }
+}
+
+/// Print as `fof(name, role, closed formula, inference(...)).`; axioms and
+/// conjectures have no source
+impl fmt::Display for ProofStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let formula = self.formula.clone().universal_closure();
+        let status = match self.role {
+            ProofRole::Axiom | ProofRole::Conjecture => {
+                return write!(
+                    f,
+                    "fof({}, {},\n    {}).",
+                    self.name,
+                    self.role.tptp_role(),
+                    formula
+                );
+            }
+            ProofRole::NegatedConjecture => "cth",
+            _ => "thm",
+        };
+        write!(
+            f,
+            "fof({}, {},\n    {},\n    inference({},[status({})],[{}])).",
+            self.name,
+            self.role.tptp_role(),
+            formula,
+            inference_name(&self.rule),
+            status,
+            self.premises.join(",")
+        )
+    }
+}
+
+/// The steps of a Vampire proof in the order they appear
+pub fn vampire_steps(proof: &str) -> Result<Vec<ProofStep>, String> {
+    parse_vampire_steps(proof)
+        .iter()
+        .map(ProofStep::from_vampire)
+        .collect()
+}
+
+/// The steps of a Twee proof. Every rewrite of a lemma or goal proof is an
+/// inference (`<statement>_rewrite_<n>`) from the axiom or lemma it used; the
+/// lemma or goal follows its rewrites with them as premises.
+pub fn twee_steps(proof: &str) -> Result<Vec<ProofStep>, String> {
+    let mut steps = Vec::new();
+    for statement in parse_twee_proof(proof)? {
+        let mut premises = Vec::new();
+        for (i, rewrite) in statement.rewrites.into_iter().enumerate() {
+            let name = format!("{}_rewrite_{}", statement.name, i + 1);
+            let rule = match rewrite.direction {
+                Direction::LeftToRight => "rewrite",
+                Direction::RightToLeft => "rewrite right to left",
+            };
+            steps.push(ProofStep {
+                name: name.clone(),
+                formula: rewrite.equation,
+                rule: rule.to_string(),
+                premises: vec![rewrite.used],
+                role: ProofRole::Inference,
+            });
+            premises.push(name);
+        }
+        let (role, rule) = match statement.kind {
+            TweeKind::Axiom => (ProofRole::Axiom, "input"),
+            TweeKind::Lemma => (ProofRole::Lemma, "rewrite chain"),
+            TweeKind::Goal => (ProofRole::Conjecture, "rewrite chain"),
+        };
+        steps.push(ProofStep {
+            name: statement.name,
+            formula: statement.formula,
+            rule: rule.to_string(),
+            premises,
+            role,
+        });
+    }
+    Ok(steps)
+}
+
+/// The steps of an egg SC-TPTP proof. Steps are sequents; the formula of a
+/// step is the disjunction of its right-hand side.
+pub fn egg_steps(proof: &str) -> Result<Vec<ProofStep>, String> {
+    let mut steps = Vec::new();
+    for annotated in TptpFile::parse(proof)?.formulas() {
+        let body = annotated.body_line();
+        let conclusion = match body.split_once("-->") {
+            Some((_, right)) => right.trim().trim_start_matches('[').trim_end_matches(']'),
+            None => body.as_str(),
+        };
+        let formula = if conclusion.trim().is_empty() {
+            parse_formula("$false")
+        } else {
+            parse_formula(conclusion)
+        }
+        .map_err(|e| format!("Cannot parse step {}: {}", annotated.name, e))?;
+
+        let (rule, premises) = match annotated
+            .annotations
+            .as_deref()
+            .and_then(|a| a.trim().strip_prefix("inference("))
+        {
+            Some(source) => {
+                let rule = source.split(',').next().unwrap_or_default().trim();
+                // the parents are the last list of the inference term
+                let premises = source
+                    .rfind('[')
+                    .map(|open| &source[open + 1..])
+                    .and_then(|rest| rest.split(']').next())
+                    .map(|list| {
+                        list.split(',')
+                            .map(str::trim)
+                            .filter(|p| !p.is_empty())
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                (rule.to_string(), premises)
+            }
+            None => ("input".to_string(), Vec::new()),
+        };
+        let role = match annotated.role.as_str() {
+            "axiom" | "hypothesis" => ProofRole::Axiom,
+            "conjecture" => ProofRole::Conjecture,
+            "negated_conjecture" => ProofRole::NegatedConjecture,
+            _ => ProofRole::Inference,
+        };
+        steps.push(ProofStep {
+            name: annotated.name.clone(),
+            formula,
+            rule,
+            premises,
+            role,
+        });
+    }
+    Ok(steps)
+}
+
+/// The steps of a proof by `prover`, read by its backend
+pub fn proof_steps(prover: &str, proof: &str) -> Result<Vec<ProofStep>, String> {
+    backend(prover)?.steps(proof)
+}
+
+/// The prover that wrote `proof`, judged by its format
+pub fn detect_prover(proof: &str) -> &'static str {
+    if proof.lines().any(|l| l.trim() == "Proof:") {
+        "twee"
+    } else if proof
+        .lines()
+        .any(|l| l.trim_start().starts_with("fof(") && l.contains("inference("))
+    {
+        "egg"
+    } else {
+        "vampire"
+    }
+}
+
+/// Print steps as a TPTP derivation, one annotated formula per step
+pub fn print_tptp(steps: &[ProofStep]) -> String {
+    steps.iter().map(|step| format!("{}\n", step)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWEE_PROOF: &str = "\
+Axiom 1 (a1): mult(e, X) = X.
+Axiom 2 (a2): mult(X, e) = X.
+
+Lemma 3: mult(e, e) = e.
+Proof:
+  mult(e, e)
+= { by axiom 1 (a1) }
+  e
+
+Goal 1 (goal): mult(mult(e, e),
+  b) = b.
+Proof:
+  mult(mult(e, e),
+    b)
+= { by lemma 3 }
+  mult(e, b)
+= { by axiom 1 (a1) }
+  b
+
+RESULT: Theorem (the conjecture is true).
+";
+
+    #[test]
+    fn twee_rewrites_are_steps_from_what_they_used() {
+        let steps = twee_steps(TWEE_PROOF).unwrap();
+        let summary: Vec<_> = steps
+            .iter()
+            .map(|s| (s.name.as_str(), s.role, s.premises.join(" ")))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("a1", ProofRole::Axiom, String::new()),
+                ("a2", ProofRole::Axiom, String::new()),
+                ("lemma_3_rewrite_1", ProofRole::Inference, "a1".to_string()),
+                ("lemma_3", ProofRole::Lemma, "lemma_3_rewrite_1".to_string()),
+                (
+                    "goal_rewrite_1",
+                    ProofRole::Inference,
+                    "lemma_3".to_string()
+                ),
+                ("goal_rewrite_2", ProofRole::Inference, "a1".to_string()),
+                (
+                    "goal",
+                    ProofRole::Conjecture,
+                    "goal_rewrite_1 goal_rewrite_2".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            steps[4].formula.to_string(),
+            "mult(mult(e,e),b) = mult(e,b)"
+        );
+        assert_eq!(steps.iter().filter(|s| s.is_inference()).count(), 3);
+    }
+
+    #[test]
+    fn inferences_are_counted_by_rule_kind() {
+        let proof = "1. ! [X0] : mult(e,X0) = X0 [input]\n2. mult(e,sK0) != sK0 [negated conjecture 1]\n3. sK0 != sK0 [forward demodulation 2,1]\n4. $false [trivial inequality removal 3]";
+        let mut rules = rule_breakdown(&vampire_steps(proof).unwrap());
+        for (kind, n) in rule_breakdown(&twee_steps(TWEE_PROOF).unwrap()) {
+            *rules.entry(kind).or_default() += n;
+        }
+        let printed = format_breakdown(&rules);
+        assert_eq!(printed, "demodulation 1, rewrite 3, other 1");
+        assert_eq!(parse_breakdown(&printed), rules);
+        assert_eq!(format_breakdown(&BTreeMap::new()), "none");
+    }
+
+    #[test]
+    fn vampire_steps_print_as_tptp() {
+        let proof = "1. ! [X0] : mult(e,X0) = X0 [input]\n2. mult(e,sK0) != sK0 [negated conjecture 1]\n3. sK0 != sK0 [superposition 2,1]";
+        let steps = vampire_steps(proof).unwrap();
+        assert_eq!(steps.iter().filter(|s| s.is_inference()).count(), 1);
+        let printed = print_tptp(&steps);
+        assert!(printed.contains("fof(f1, axiom,\n    ! [X0] : (mult(e,X0) = X0))."));
+        assert!(printed.contains(
+            "fof(f3, plain,\n    sK0 != sK0,\n    inference(superposition,[status(thm)],[f2,f1]))."
+        ));
+    }
+
+    #[test]
+    fn egg_steps_read_sequent_conclusions() {
+        let proof = "fof(a1, axiom, ! [X] : mult(e, X) = X).\nfof(f1, plain, [] --> [mult(e, b) = b], inference(leftForall, [status(thm), 0, $fot(b)], [a1])).";
+        let steps = egg_steps(proof).unwrap();
+        assert_eq!(steps[1].formula.to_string(), "mult(e,b) = b");
+        assert_eq!(steps[1].rule, "leftForall");
+        assert_eq!(steps[1].premises, vec!["a1".to_string()]);
+        assert!(steps[1].is_inference());
+        assert_eq!(detect_prover(proof), "egg");
+    }
+}
@@ -0,0 +1,267 @@
+use crate::artifact::write_atomic;
+use crate::process::{LocalRunner, ProcessRunner, ProcessSpec};
+use crate::prover_wrapper::prover_binary;
+use crate::run_config::RunConfig;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Provenance headers: `%` comment lines at the top of every generated
+// proof, lemma and DAG file, recording how the file was made.
+
+const BEGIN: &str = "% --- provenance ---";
+const END: &str = "% --- end provenance ---";
+
+/// Provenance header for an artifact generated from `input_file` by this
+/// process. In deterministic mode (`--deterministic`) it has no timestamp, so
+/// reruns write the same files.
+pub fn header(config: &RunConfig, input_file: &str) -> String {
+    header_with_notes(config, input_file, &[])
+}
+
+/// [`header`] with free-form `notes`, e.g. what was changed relative to the input
+pub fn header_with_notes(config: &RunConfig, input_file: &str, notes: &[String]) -> String {
+    header_for(input_file, notes, config.deterministic)
+}
+
+fn header_for(input_file: &str, notes: &[String], deterministic: bool) -> String {
+    let command_line = std::env::args().collect::<Vec<_>>().join(" ");
+    let input = match fs::read(input_file) {
+        Ok(bytes) => format!("{} (hash {})", input_file, content_hash(&bytes)),
+        Err(_) => format!("{} (unreadable)", input_file),
+    };
+
+    let mut lines = vec![
+        BEGIN.to_string(),
+        format!("% generator: frankenstein {}", env!("CARGO_PKG_VERSION")),
+        format!("% command: {}", command_line),
+        format!("% input: {}", input),
+    ];
+    for (prover, version) in prover_versions() {
+        lines.push(format!("% {}: {}", prover, version));
+    }
+    for note in notes {
+        lines.push(format!("% note: {}", note));
+    }
+    if !deterministic {
+        lines.push(format!("% timestamp: {}", utc_timestamp(SystemTime::now())));
+    }
+    lines.push(END.to_string());
+    lines.join("\n") + "\n"
+}
+
+/// `content` without a leading provenance header, e.g. of an imported proof
+pub fn strip(content: &str) -> &str {
+    if !content.starts_with(BEGIN) {
+        return content;
+    }
+    match content.find(END) {
+        Some(end) => content[end + END.len()..].trim_start_matches('\n'),
+        None => content,
+    }
+}
+
+/// Write `content` to `path` behind a fresh provenance header, replacing any header it had
+pub fn write_with_provenance<P: AsRef<Path>>(
+    config: &RunConfig,
+    path: P,
+    content: &str,
+    input_file: &str,
+) -> std::io::Result<()> {
+    write_atomic(path, header(config, input_file) + strip(content))
+}
+
+/// [`write_with_provenance`] with `notes` in the header
+pub fn write_with_notes<P: AsRef<Path>>(
+    config: &RunConfig,
+    path: P,
+    content: &str,
+    input_file: &str,
+    notes: &[String],
+) -> std::io::Result<()> {
+    write_atomic(
+        path,
+        header_with_notes(config, input_file, notes) + strip(content),
+    )
+}
+
+/// Copy `path` aside before it is overwritten: to `<path>.orig` the first
+/// time, then to `<path>.v2`, `<path>.v3`, ... Returns the copy, or `None`
+/// if there was no file to keep.
+pub fn keep_version(path: &Path) -> std::io::Result<Option<PathBuf>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let copy = (1..)
+        .map(|version| version_path(path, version))
+        .find(|copy| !copy.exists())
+        .expect("unbounded version range");
+    fs::copy(path, &copy)?;
+    Ok(Some(copy))
+}
+
+/// Whether `path` is a copy made by [`keep_version`]
+pub fn is_version_copy(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            ext == "orig"
+                || ext
+                    .strip_prefix('v')
+                    .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        })
+}
+
+fn version_path(path: &Path, version: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    match version {
+        1 => name.push(".orig"),
+        v => name.push(format!(".v{}", v)),
+    }
+    PathBuf::from(name)
+}
+
+/// Hex SHA-256 of `bytes`, the same for every build and platform
+fn content_hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Version of each prover, looked up once per process. Provers
+/// without a usable `--version` are identified by the hash of their binary.
+fn prover_versions() -> &'static [(String, String)] {
+    static VERSIONS: OnceLock<Vec<(String, String)>> = OnceLock::new();
+    VERSIONS.get_or_init(|| {
+        ["vampire", "twee"]
+            .iter()
+            .map(|prover| (prover.to_string(), prover_version(&prover_binary(prover))))
+            .collect()
+    })
+}
+
+pub fn prover_version(path: &Path) -> String {
+    let spec =
+        ProcessSpec::new(&path.to_string_lossy(), Duration::from_secs(5)).args(&["--version"]);
+    let reported = LocalRunner
+        .run(&spec)
+        .ok()
+        .filter(|output| output.succeeded())
+        .and_then(|output| output.stdout.lines().next().map(|l| l.trim().to_string()))
+        .filter(|line| line.chars().any(|c| c.is_ascii_digit()) && !line.contains("SZS"));
+    match (reported, fs::read(path)) {
+        (Some(version), _) => version,
+        (None, Ok(bytes)) => format!("binary hash {}", content_hash(&bytes)),
+        (None, Err(_)) => "not found".to_string(),
+    }
+}
+
+/// `time` as `YYYY-MM-DDTHH:MM:SSZ`
+fn utc_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_is_utc_calendar_time() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        assert_eq!(utc_timestamp(time), "2024-02-29T12:34:56Z");
+        assert_eq!(utc_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn deterministic_headers_have_no_timestamp() {
+        let first = header_for("no_such_input.p", &[], true);
+        std::thread::sleep(Duration::from_millis(1100));
+        assert_eq!(header_for("no_such_input.p", &[], true), first);
+        assert!(!first.contains("% timestamp:"));
+        assert!(header_for("no_such_input.p", &[], false).contains("% timestamp:"));
+    }
+
+    #[test]
+    fn strip_removes_only_a_leading_header() {
+        let proof = "1. a = b [input]\n";
+        let with_header = header(&RunConfig::default(), "no_such_input.p") + proof;
+        assert!(with_header.contains("% input: no_such_input.p (unreadable)"));
+        assert_eq!(strip(&with_header), proof);
+        assert_eq!(strip(proof), proof);
+    }
+
+    #[test]
+    fn overwritten_files_keep_numbered_versions() {
+        let dir = std::env::temp_dir().join(format!("provenance_versions_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("history_lemma_0004.p");
+        assert_eq!(keep_version(&file).unwrap(), None);
+
+        fs::write(&file, "first").unwrap();
+        let orig = keep_version(&file).unwrap().unwrap();
+        assert_eq!(orig, dir.join("history_lemma_0004.p.orig"));
+        fs::write(&file, "second").unwrap();
+        let v2 = keep_version(&file).unwrap().unwrap();
+        assert_eq!(v2, dir.join("history_lemma_0004.p.v2"));
+
+        assert_eq!(fs::read_to_string(&orig).unwrap(), "first");
+        assert_eq!(fs::read_to_string(&v2).unwrap(), "second");
+        assert!(is_version_copy(&orig) && is_version_copy(&v2));
+        assert!(!is_version_copy(&file));
+        assert!(!is_version_copy(Path::new(
+            "history_lemma_0004_vampire.proof"
+        )));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn inputs_are_identified_by_their_sha256() {
+        assert_eq!(
+            content_hash(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let dir = std::env::temp_dir().join(format!("provenance_hash_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.p");
+        fs::write(&input, "abc").unwrap();
+        assert!(header(&RunConfig::default(), &input.to_string_lossy())
+            .contains("(hash ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad)"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn notes_are_part_of_the_header() {
+        let notes = vec!["lemma_0003 replaced by abstract_lemma_0003".to_string()];
+        let header = header_with_notes(&RunConfig::default(), "no_such_input.p", &notes);
+        assert!(header.contains("% note: lemma_0003 replaced by abstract_lemma_0003\n"));
+        assert_eq!(
+            strip(&(header + "fof(a, axiom, p).\n")),
+            "fof(a, axiom, p).\n"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use crate::lemma_store::LemmaKind;
+use crate::summary::{self, SummaryEntry};
+use crate::tptp_file::TptpFile;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// Which prover won which kind of lemma in Phase 1, so the minimization can
+// try the likely winner first and leave out provers that never win on a kind
+// of lemma (`--adaptive-provers`).
+
+/// Provers the minimization can run on a lemma
+pub const PROVERS: [&str; 2] = ["twee", "vampire"];
+
+/// Problems with at least this many axioms count as large
+const LARGE_PROBLEM: usize = 20;
+
+/// Phase 1 lemmas of a shape needed before its winners are trusted over the defaults
+const MIN_SAMPLES: usize = 3;
+
+/// The features of a lemma problem the choice of prover depends on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LemmaShape {
+    /// the conjecture contains Skolem constants
+    pub skolems: bool,
+    /// the problem has at least `LARGE_PROBLEM` axioms
+    pub large: bool,
+}
+
+impl LemmaShape {
+    pub fn of(problem: &str) -> Result<LemmaShape, String> {
+        let file = TptpFile::parse(problem)?;
+        let skolem_re = Regex::new(r"\bsK\d+\b").unwrap();
+        let skolems = file
+            .formulas()
+            .any(|f| f.is_conjecture() && skolem_re.is_match(&f.body));
+        let axioms = file.formulas().filter(|f| !f.is_conjecture()).count();
+        Ok(LemmaShape {
+            skolems,
+            large: axioms >= LARGE_PROBLEM,
+        })
+    }
+
+    /// Provers to try without enough statistics: Twee does poorly on Skolem
+    /// constants, Vampire copes better with large clause sets
+    fn default_provers(self) -> Vec<&'static str> {
+        if self.skolems {
+            vec!["vampire"]
+        } else if self.large {
+            vec!["vampire", "twee"]
+        } else {
+            vec!["twee", "vampire"]
+        }
+    }
+}
+
+/// How often each prover gave the shortest proof, per lemma shape
+#[derive(Debug, Clone, Default)]
+pub struct ProverStats {
+    wins: BTreeMap<LemmaShape, BTreeMap<&'static str, usize>>,
+}
+
+impl ProverStats {
+    /// Count the winners in the Phase 1 `summary_file` by the shape of their
+    /// lemma problems in `lemmas_dir`. Lemmas whose problem is gone are left out.
+    pub fn from_summary(summary_file: &str, lemmas_dir: &str) -> Result<ProverStats, String> {
+        let text = fs::read_to_string(summary_file)
+            .map_err(|e| format!("Failed to read {}: {}", summary_file, e))?;
+        let summary = summary::parse(&text)
+            .map_err(|e| format!("Failed to parse {}: {}", summary_file, e))?;
+
+        let mut stats = ProverStats::default();
+        for (_, SummaryEntry { file, prover, .. }) in summary::proved(&summary) {
+            let (Some(prover), Some(kind)) = (
+                PROVERS.into_iter().find(|p| *p == prover.as_str()),
+                LemmaKind::of(file),
+            ) else {
+                continue;
+            };
+            let path = Path::new(lemmas_dir)
+                .join(kind.dir_name())
+                .join(format!("{}.p", file));
+            if let Ok(problem) = fs::read_to_string(&path) {
+                stats.record(LemmaShape::of(&problem)?, prover);
+            }
+        }
+        Ok(stats)
+    }
+
+    pub fn record(&mut self, shape: LemmaShape, prover: &'static str) {
+        *self
+            .wins
+            .entry(shape)
+            .or_default()
+            .entry(prover)
+            .or_default() += 1;
+    }
+
+    /// Number of lemmas counted
+    pub fn samples(&self) -> usize {
+        self.wins.values().flat_map(|w| w.values()).sum()
+    }
+
+    /// Provers to run on a lemma of `shape`, in order. With enough Phase 1
+    /// lemmas of this shape the most frequent winner comes first and provers
+    /// that never won are skipped; otherwise the defaults of the shape apply.
+    pub fn provers_for(&self, shape: LemmaShape) -> Vec<&'static str> {
+        let wins = match self.wins.get(&shape) {
+            Some(wins) if wins.values().sum::<usize>() >= MIN_SAMPLES => wins,
+            _ => return shape.default_provers(),
+        };
+        let mut provers: Vec<&'static str> = PROVERS
+            .into_iter()
+            .filter(|p| wins.get(p).is_some_and(|&n| n > 0))
+            .collect();
+        // stable, so Twee stays first on a tie
+        provers.sort_by_key(|p| std::cmp::Reverse(wins[p]));
+        provers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn winners_order_the_provers_once_there_are_enough() {
+        let problem =
+            "fof(a1, axiom, ! [X] : mult(e, X) = X).\nfof(c, conjecture, mult(e, sK0) = sK0).\n";
+        let shape = LemmaShape::of(problem).unwrap();
+        assert_eq!(
+            shape,
+            LemmaShape {
+                skolems: true,
+                large: false
+            }
+        );
+
+        let mut stats = ProverStats::default();
+        assert_eq!(stats.provers_for(shape), vec!["vampire"]);
+        stats.record(shape, "twee");
+        stats.record(shape, "twee");
+        assert_eq!(stats.provers_for(shape), vec!["vampire"]);
+        stats.record(shape, "vampire");
+        assert_eq!(stats.samples(), 3);
+        assert_eq!(stats.provers_for(shape), vec!["twee", "vampire"]);
+
+        let small = LemmaShape {
+            skolems: false,
+            large: false,
+        };
+        assert_eq!(stats.provers_for(small), vec!["twee", "vampire"]);
+    }
+}
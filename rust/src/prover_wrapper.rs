@@ -1,162 +1,507 @@
+use crate::artifact::write_atomic;
+use crate::collect_report::{self, FailureKind, ProverRuns};
+use crate::process::{LocalRunner, ProcessOutput, ProcessRunner, ProcessSpec};
+use crate::proof_step::{egg_steps, twee_steps, vampire_steps, ProofStep};
+use crate::run_config::{
+    self, RunConfig, PROVER_TIMEOUT, SCREEN_TIME_LIMIT, STEP_CHECK_TIME_LIMIT,
+};
+use crate::summary::{LemmaStatus, Summary, SummaryEntry};
+use crate::tptp_file::TptpFile;
+use crate::vampire_proof::parse_vampire_steps;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::path::Path;
-use std::time::Duration;
-use wait_timeout::ChildExt;
-
-fn run_external_prover(exe_path: &str, args: &[&str]) -> Option<String> {
-    let mut child = match std::process::Command::new(exe_path)
-        .args(args)
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .spawn()
-    {
-        Ok(c) => c,
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Run a prover, optionally streaming the problem to it over stdin
+fn run_external_prover_with_input(
+    runner: &dyn ProcessRunner,
+    exe_path: &str,
+    args: &[&str],
+    input: Option<&str>,
+) -> Option<String> {
+    prover_output(runner, exe_path, args, input).map(|output| output.stdout)
+}
+
+/// Everything a successful prover run printed, including the original bytes
+/// if they were not UTF-8
+fn prover_output(
+    runner: &dyn ProcessRunner,
+    exe_path: &str,
+    args: &[&str],
+    input: Option<&str>,
+) -> Option<ProcessOutput> {
+    prover_run(runner, exe_path, args, input).and_then(|output| successful(exe_path, output))
+}
+
+/// Everything a prover run printed, whether it succeeded or not. None if the
+/// prover could not be started.
+fn prover_run(
+    runner: &dyn ProcessRunner,
+    exe_path: &str,
+    args: &[&str],
+    input: Option<&str>,
+) -> Option<ProcessOutput> {
+    let mut spec = ProcessSpec::new(exe_path, PROVER_TIMEOUT).args(args);
+    if let Some(problem) = input {
+        spec = spec.input(problem);
+    }
+    match runner.run(&spec) {
+        Ok(output) => Some(output),
         Err(e) => {
-            eprintln!("[ERROR] Failed to start process '{}': {}", exe_path, e);
-            return None;
+            eprintln!("[ERROR] {}", e);
+            None
         }
+    }
+}
+
+/// Programs whose runs are prover runs, by file stem
+const RECORDED_PROVERS: [&str; 3] = ["vampire", "twee", "egg-sc-tptp"];
+
+/// The prover `spec` runs, if it is a prover run: asking a prover for its
+/// version is not
+fn recorded_prover(spec: &ProcessSpec) -> Option<String> {
+    let prover = Path::new(&spec.program).file_stem()?.to_string_lossy();
+    (RECORDED_PROVERS.contains(&prover.as_ref()) && spec.args != ["--version"])
+        .then(|| prover.to_string())
+}
+
+/// Runs commands through `runner`, recording every prover run in the results
+/// database and the event log of `config`, whoever started it
+pub struct RecordingRunner<'a> {
+    runner: &'a dyn ProcessRunner,
+    config: &'a RunConfig,
+}
+
+impl<'a> RecordingRunner<'a> {
+    pub fn new(runner: &'a dyn ProcessRunner, config: &'a RunConfig) -> RecordingRunner<'a> {
+        RecordingRunner { runner, config }
+    }
+
+    /// Runs commands on this machine
+    pub fn local(config: &'a RunConfig) -> RecordingRunner<'a> {
+        RecordingRunner::new(&LocalRunner, config)
+    }
+}
+
+impl ProcessRunner for RecordingRunner<'_> {
+    fn run(&self, spec: &ProcessSpec) -> Result<ProcessOutput, String> {
+        let started = Instant::now();
+        let output = self.runner.run(spec);
+        record_run(self.config, spec, output.as_ref().ok(), started.elapsed());
+        output
+    }
+}
+
+/// Record a finished run of `spec` in the results database and the event log
+/// of `config` if it ran a prover; `output` is `None` if it could not be run
+fn record_run(
+    config: &RunConfig,
+    spec: &ProcessSpec,
+    output: Option<&ProcessOutput>,
+    elapsed: Duration,
+) {
+    let Some(prover) = recorded_prover(spec) else {
+        return;
+    };
+    let status = match output {
+        None => "Error",
+        Some(output) if output.timed_out => "Timeout",
+        Some(output) if output.succeeded() => output_status(&output.stdout),
+        Some(_) => "Error",
     };
+    // provers without a backend, such as the egg-sc-tptp binary, are not counted
+    let steps = output
+        .filter(|_| matches!(status, "Theorem" | "Unsatisfiable"))
+        .and_then(|output| proof_length(&prover, &output.stdout).ok());
+    config
+        .results_db
+        .record_prover_run(&prover, status, steps, elapsed);
+    config
+        .events
+        .prover_invoked(&prover, status, steps, elapsed);
+}
 
-    let timeout = Duration::from_secs(10);
-    match child.wait_timeout(timeout).unwrap() {
-        Some(status) => {
-            let output = child.wait_with_output().unwrap();
-            if status.success() {
-                Some(String::from_utf8_lossy(&output.stdout).to_string())
-            } else {
-                eprintln!("[ERROR] Prover exited with error: {:?}", status);
-                None
-            }
-        }
-        None => {
-            eprintln!(
-                "[TIMEOUT] Prover '{}' exceeded {} seconds",
-                exe_path,
-                timeout.as_secs()
-            );
-            let _ = child.kill();
-            None
+/// `output` if its run succeeded, otherwise None after saying why it did not
+fn successful(exe_path: &str, output: ProcessOutput) -> Option<ProcessOutput> {
+    if output.timed_out {
+        eprintln!(
+            "[TIMEOUT] Prover '{}' exceeded {} seconds",
+            exe_path,
+            PROVER_TIMEOUT.as_secs()
+        );
+        None
+    } else if output.succeeded() {
+        Some(output)
+    } else {
+        eprintln!("[ERROR] Prover exited with error: {:?}", output.exit_code);
+        // egg-sc-tptp reports why it failed as an SZS status line
+        if let Some(line) = output.stdout.lines().find(|l| l.contains("SZS status")) {
+            eprintln!("[INFO] {}", line.trim_start_matches('%').trim());
         }
+        None
+    }
+}
+
+/// Status word of a prover's output: its SZS status, or Twee's `RESULT:`
+fn output_status(output: &str) -> &str {
+    output
+        .lines()
+        .find_map(|l| {
+            l.split("SZS status")
+                .nth(1)
+                .or_else(|| l.split("RESULT:").nth(1))
+        })
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap_or("Unknown")
+}
+
+/// Location of an external tool: `../bin/<name>` (with the platform's
+/// executable extension) if it is there, otherwise `<name>` on the PATH
+pub fn prover_binary(name: &str) -> PathBuf {
+    let bundled = Path::new("..")
+        .join("bin")
+        .join(format!("{}{}", name, env::consts::EXE_SUFFIX));
+    if bundled.is_file() {
+        return env::current_dir()
+            .map(|dir| dir.join(&bundled))
+            .unwrap_or(bundled);
     }
+    which::which(name).unwrap_or(bundled)
 }
 
 fn vampire_path() -> String {
-    env::current_dir()
-        .unwrap()
-        .join("../bin/vampire")
-        .to_str()
-        .unwrap()
-        .to_string()
+    prover_binary("vampire").to_string_lossy().to_string()
 }
 
 fn twee_path() -> String {
-    env::current_dir()
-        .unwrap()
-        .join("../bin/twee")
-        .to_str()
-        .unwrap()
-        .to_string()
+    prover_binary("twee").to_string_lossy().to_string()
 }
 
 fn egg_path() -> String {
     env::current_dir()
         .unwrap()
-        .join("target/debug/egg-sc-tptp")
-        .to_str()
-        .unwrap()
+        .join("target")
+        .join("debug")
+        .join(format!("egg-sc-tptp{}", env::consts::EXE_SUFFIX))
+        .to_string_lossy()
         .to_string()
 }
 
-pub fn run_vampire(file: &str) -> Option<String> {
-    run_external_prover(&vampire_path(), &["--input_syntax", "tptp", file])
+/// Vampire's run on the problem `file`, successful or not
+fn vampire_run(
+    runner: &dyn ProcessRunner,
+    config: &RunConfig,
+    file: &str,
+) -> Option<ProcessOutput> {
+    let mut args = vec!["--input_syntax".to_string(), "tptp".to_string()];
+    args.extend(config.vampire_options());
+    args.push(file.to_string());
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    prover_run(runner, &vampire_path(), &args, None)
 }
-pub fn run_twee(file: &str) -> Option<String> {
-    run_external_prover(&twee_path(), &["--quiet", file])
+/// Twee's run on the problem `file`, successful or not
+fn twee_run(runner: &dyn ProcessRunner, file: &str) -> Option<ProcessOutput> {
+    prover_run(runner, &twee_path(), &["--quiet", file], None)
 }
-fn run_egg(input: &str, output: &str) -> Option<String> {
-    run_external_prover(&egg_path(), &[input, output])
+/// Run Twee on an in-memory problem and keep only the SZS block of its output.
+/// With `max_steps` Twee gives up after `CRITICAL_PAIRS_PER_STEP` critical
+/// pairs per step, since a longer proof would be of no use.
+pub fn run_twee_stdin(
+    config: &RunConfig,
+    problem: &str,
+    max_steps: Option<usize>,
+) -> Option<String> {
+    let mut args = vec!["--quiet".to_string()];
+    if let Some(max_steps) = max_steps {
+        args.push("--max-cps".to_string());
+        args.push(((max_steps + 1) * run_config::CRITICAL_PAIRS_PER_STEP).to_string());
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let runner = RecordingRunner::local(config);
+    run_twee_problem(&runner, &args, problem).map(|out| extract_szs_output(&out))
 }
 
-/// Count Vampire proof steps, ignoring input/negated conjecture lines
-/// Count Vampire proof steps based on core inference tags
-pub fn proof_length_vampire(proof: &str) -> usize {
-    let mut count = 0;
+/// Run Vampire on an in-memory problem and keep only the SZS block of a proof.
+/// Output without a proof counts as no proof. With `max_steps` Vampire stops
+/// after `ACTIVATIONS_PER_STEP` activations per step.
+pub fn run_vampire_stdin(
+    config: &RunConfig,
+    problem: &str,
+    max_steps: Option<usize>,
+) -> Option<String> {
+    let mut args = vec!["--input_syntax".to_string(), "tptp".to_string()];
+    args.extend(config.vampire_options());
+    if let Some(max_steps) = max_steps {
+        args.push("--activation_limit".to_string());
+        args.push(((max_steps + 1) * run_config::ACTIVATIONS_PER_STEP).to_string());
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let runner = RecordingRunner::local(config);
+    let output = run_external_prover_with_input(&runner, &vampire_path(), &args, Some(problem))?;
+    matches!(output_status(&output), "Theorem" | "Unsatisfiable")
+        .then(|| extract_szs_output(&output))
+}
 
-    // core inference indicators
-    let proof_keywords = [
-        "demodulation",
-        "superposition",
-        "resolution",
-        "trivial inequality removal",
-    ];
+/// Twee reads the problem from stdin when given `-` as the file, so nothing
+/// goes through the file system
+fn run_twee_problem(runner: &dyn ProcessRunner, args: &[&str], problem: &str) -> Option<String> {
+    let mut args = args.to_vec();
+    args.push("-");
+    run_external_prover_with_input(runner, &twee_path(), &args, Some(problem))
+}
 
-    for line in proof.lines() {
-        let l = line.trim_start();
+/// Consistency screening: whether Vampire refutes an axiom set (a problem
+/// without conjecture) within a short time limit. A lemma axiom mangled into
+/// something false would make every proof from them trivially valid. The
+/// answers are kept per axiom set for the search that owns the screen, since
+/// minimize asks again for every candidate.
+pub struct ConsistencyScreen<'a> {
+    runner: &'a dyn ProcessRunner,
+    config: &'a RunConfig,
+    screened: RefCell<HashMap<String, bool>>,
+}
 
-        // skip empty lines and comments
-        if l.is_empty() || l.starts_with('%') {
-            continue;
+impl<'a> ConsistencyScreen<'a> {
+    /// A screen running Vampire through `runner` with the options of
+    /// `config`, with nothing screened yet
+    pub fn new(runner: &'a dyn ProcessRunner, config: &'a RunConfig) -> ConsistencyScreen<'a> {
+        ConsistencyScreen {
+            runner,
+            config,
+            screened: RefCell::new(HashMap::new()),
         }
+    }
 
-        // remove leading line number (e.g., "23. ...")
-        let l_no_num = if let Some(dot_pos) = l.find('.') {
-            let (_, rest) = l.split_at(dot_pos + 1);
-            rest.trim_start()
-        } else {
-            l
+    /// Whether Vampire refutes `axioms`
+    pub fn inconsistent(&self, axioms: &str) -> bool {
+        if let Some(&known) = self.screened.borrow().get(axioms) {
+            return known;
+        }
+
+        let time_limit = SCREEN_TIME_LIMIT.as_secs().to_string();
+        let options = self.config.vampire_options();
+        let spec = ProcessSpec::new(&vampire_path(), SCREEN_TIME_LIMIT * 2)
+            .args(&["--input_syntax", "tptp", "-t", time_limit.as_str()])
+            .args(options.as_slice())
+            .input(axioms);
+        // a failed or inconclusive run is no evidence of inconsistency
+        let inconsistent = match self.runner.run(&spec) {
+            Ok(output) => output.stdout.contains("SZS status Unsatisfiable"),
+            Err(e) => {
+                eprintln!("[WARN] Consistency screening skipped: {}", e);
+                false
+            }
         };
+        self.screened
+            .borrow_mut()
+            .insert(axioms.to_string(), inconsistent);
+        inconsistent
+    }
+}
 
-        // only count lines whose inference tag contains one of the keywords
-        if l_no_num.contains('[') && proof_keywords.iter().any(|kw| l_no_num.contains(kw)) {
-            count += 1;
+/// Whether Vampire proves the conjecture of `problem` within
+/// `STEP_CHECK_TIME_LIMIT`. Anything but a proof counts as no.
+pub fn proves_quickly(runner: &dyn ProcessRunner, config: &RunConfig, problem: &str) -> bool {
+    let time_limit = STEP_CHECK_TIME_LIMIT.as_secs().to_string();
+    let options = config.vampire_options();
+    let spec = ProcessSpec::new(&vampire_path(), STEP_CHECK_TIME_LIMIT * 2)
+        .args(&["--input_syntax", "tptp", "-t", time_limit.as_str()])
+        .args(options.as_slice())
+        .input(problem);
+    match runner.run(&spec) {
+        Ok(output) => output.stdout.contains("SZS status Theorem"),
+        Err(e) => {
+            eprintln!("[WARN] Step check skipped: {}", e);
+            false
         }
     }
+}
 
-    count
+/// egg-sc-tptp's run on the problem `input`, successful or not. Its proof is
+/// written to `output`.
+fn egg_run(runner: &dyn ProcessRunner, input: &str, output: &str) -> Option<ProcessOutput> {
+    prover_run(runner, &egg_path(), &[input, output], None)
 }
 
-fn proof_length_egg(proof: &str) -> usize {
-    proof
-        .lines()
-        .filter(|l| {
-            let line = l.trim_start();
-            line.starts_with("fof(") && line.contains(", plain") && line.contains("inference(")
-        })
-        .count()
+/// Keep the SZS status line and the `SZS output start ... end` block of a prover's output.
+/// Output without these markers is returned unchanged.
+pub fn extract_szs_output(output: &str) -> String {
+    let lines: Vec<&str> = output.lines().collect();
+    let start = lines.iter().position(|l| l.contains("SZS output start"));
+    let end = lines.iter().rposition(|l| l.contains("SZS output end"));
+
+    match (start, end) {
+        (Some(start), Some(end)) if start <= end => {
+            let mut kept: Vec<&str> = lines
+                .iter()
+                .find(|l| l.contains("SZS status"))
+                .into_iter()
+                .copied()
+                .collect();
+            kept.extend(&lines[start..=end]);
+            kept.join("\n") + "\n"
+        }
+        _ => output.to_string(),
+    }
 }
 
-pub fn proof_length_twee(proof: &str) -> usize {
-    let mut in_proof = false;
-    proof
-        .lines()
-        .map(str::trim_start)
-        .filter(|line| {
-            if line.starts_with("Proof:") {
-                in_proof = true;
-                return false;
+/// What the pipeline needs from a prover to compare its proofs with those of
+/// the others: how to read the steps of its proofs and count them
+pub trait ProverBackend: Sync {
+    /// name the prover is configured and its proofs are filed under, e.g. "twee"
+    fn name(&self) -> &'static str;
+
+    fn steps(&self, proof: &str) -> Result<Vec<ProofStep>, String>;
+
+    /// Number of core inferences of a proof, counted from its layout alone
+    /// without reading the formulas; `None` if it has no steps at all
+    fn inference_lines(&self, proof: &str) -> Option<usize>;
+
+    /// Number of core inferences of a proof. Proofs with formulas that cannot
+    /// be read are counted by their inference lines; text without any proof
+    /// steps has no length.
+    fn proof_length(&self, proof: &str) -> Result<usize, String> {
+        match self.steps(proof) {
+            Ok(steps) if steps.is_empty() => Err(format!("No {} proof steps found", self.name())),
+            Ok(steps) => Ok(steps.iter().filter(|s| s.is_inference()).count()),
+            Err(e) => {
+                eprintln!(
+                    "[WARN] Cannot read the steps of a {} proof ({}), counting its inference lines",
+                    self.name(),
+                    e
+                );
+                self.inference_lines(proof)
+                    .ok_or_else(|| format!("No {} proof steps found: {}", self.name(), e))
             }
-            in_proof && line.contains("= { by")
-        })
-        .count()
+        }
+    }
 }
 
-pub fn proof_length(prover: &str, proof: &str) -> usize {
-    match prover {
-        "vampire" => proof_length_vampire(proof),
-        "egg" => proof_length_egg(proof),
-        "twee" => proof_length_twee(proof),
-        _ => proof.lines().count(),
+pub struct Vampire;
+pub struct Twee;
+pub struct Egg;
+
+impl ProverBackend for Vampire {
+    fn name(&self) -> &'static str {
+        "vampire"
+    }
+
+    fn steps(&self, proof: &str) -> Result<Vec<ProofStep>, String> {
+        vampire_steps(proof)
+    }
+
+    /// numbered steps with an inference rule
+    fn inference_lines(&self, proof: &str) -> Option<usize> {
+        let steps = parse_vampire_steps(proof);
+        (!steps.is_empty()).then(|| steps.iter().filter(|s| s.is_inference()).count())
     }
 }
 
+impl ProverBackend for Twee {
+    fn name(&self) -> &'static str {
+        "twee"
+    }
+
+    fn steps(&self, proof: &str) -> Result<Vec<ProofStep>, String> {
+        twee_steps(proof)
+    }
+
+    /// `= { by ... }` rewrites under `Axiom`, `Lemma` or `Goal` statements
+    fn inference_lines(&self, proof: &str) -> Option<usize> {
+        let lines = || proof.lines().map(str::trim_start);
+        lines()
+            .any(|l| {
+                ["Axiom ", "Lemma ", "Goal "]
+                    .iter()
+                    .any(|kind| l.starts_with(kind) && l.contains(": "))
+            })
+            .then(|| lines().filter(|l| l.starts_with("= { by ")).count())
+    }
+}
+
+impl ProverBackend for Egg {
+    fn name(&self) -> &'static str {
+        "egg"
+    }
+
+    fn steps(&self, proof: &str) -> Result<Vec<ProofStep>, String> {
+        egg_steps(proof)
+    }
+
+    /// annotated formulas other than axioms and (negated) conjectures
+    fn inference_lines(&self, proof: &str) -> Option<usize> {
+        let file = TptpFile::parse(proof).ok()?;
+        let inferences = file
+            .formulas()
+            .filter(|f| {
+                !["axiom", "hypothesis", "conjecture", "negated_conjecture"]
+                    .contains(&f.role.as_str())
+            })
+            .count();
+        let readable = file.formulas().next().is_some();
+        readable.then_some(inferences)
+    }
+}
+
+/// The provers whose proofs the pipeline can read
+pub const BACKENDS: [&dyn ProverBackend; 3] = [&Vampire, &Twee, &Egg];
+
+/// The backend of prover `name`
+pub fn backend(name: &str) -> Result<&'static dyn ProverBackend, String> {
+    BACKENDS
+        .into_iter()
+        .find(|backend| backend.name() == name)
+        .ok_or_else(|| {
+            let known: Vec<&str> = BACKENDS.iter().map(|backend| backend.name()).collect();
+            format!(
+                "Unknown prover '{}', expected one of {}",
+                name,
+                known.join(", ")
+            )
+        })
+}
+
+/// Number of core inferences of a proof by `prover`, see
+/// `ProverBackend::proof_length`. There is no telling how long the proof of an
+/// unknown prover is.
+pub fn proof_length(prover: &str, proof: &str) -> Result<usize, String> {
+    backend(prover)?.proof_length(proof)
+}
+
+/// Number of the lemma a lemma problem is for, e.g. 3 for `single/single_lemma_0003.p`.
+/// The modes of a lemma share its number.
+pub fn lemma_number(lemma_file: &str) -> u32 {
+    let stem = Path::new(lemma_file).file_stem().unwrap().to_string_lossy();
+    let digits = stem.len() - stem.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    stem[stem.len() - digits..].parse().unwrap_or(0)
+}
+
+/// Prove the lemma problems `lemma_files` with `provers`, keeping the
+/// shortest proof of each lemma in `out_dir_path`. Up to `config.jobs`
+/// lemmas are proved at once (`--jobs`). Besides the summary, returns how
+/// often each prover was run on the problems of each mode, for `collect`'s report.
 pub fn prove_lemmas(
+    config: &RunConfig,
     lemma_files: &[String],
     provers: &[&str],
     out_dir_path: &str,
-) -> HashMap<u32, (String, String, String)> {
-    let mut results = HashMap::new();
+) -> (Summary, Vec<ProverRuns>) {
+    let runner = RecordingRunner::local(config);
+    prove_lemmas_with(&runner, config, lemma_files, provers, out_dir_path)
+}
+
+/// `prove_lemmas` with the provers started through `runner`
+pub fn prove_lemmas_with(
+    runner: &dyn ProcessRunner,
+    config: &RunConfig,
+    lemma_files: &[String],
+    provers: &[&str],
+    out_dir_path: &str,
+) -> (Summary, Vec<ProverRuns>) {
     let out_dir = Path::new(out_dir_path);
     if out_dir.exists() {
         fs::remove_dir_all(out_dir).unwrap();
@@ -173,182 +518,224 @@ pub fn prove_lemmas(
     // group by lemma index
     let mut groups: HashMap<u32, Vec<String>> = HashMap::new();
     for f in lemma_files {
-        let fname = Path::new(f).file_stem().unwrap().to_string_lossy();
-        let num: u32 = fname
-            .chars()
-            .rev()
-            .take_while(|c| c.is_ascii_digit())
-            .collect::<String>()
-            .chars()
-            .rev()
-            .collect::<String>()
-            .parse()
-            .unwrap_or(0);
-        groups.entry(num).or_default().push(f.clone());
+        groups.entry(lemma_number(f)).or_default().push(f.clone());
     }
 
     let mut sorted_nums: Vec<u32> = groups.keys().cloned().collect();
     sorted_nums.sort();
 
-    for n in sorted_nums {
-        println!("\n[INFO] Proving lemma {}", n);
-        let files = &groups[&n];
-
-        // collect all successful proofs for this group
-        let mut all_proofs: Vec<(String, String, usize, String)> = Vec::new(); // (prover, proof, len, filename)
-
-        for lemma_file in files {
-            let file_stem = Path::new(lemma_file).file_stem().unwrap().to_string_lossy();
-            let egg_file = egg_dir.join(format!("{}_egg.proof", file_stem));
-            let vampire_file = vampire_dir.join(format!("{}_vampire.proof", file_stem));
-            let twee_file = twee_dir.join(format!("{}_twee.proof", file_stem));
-
-            for (prover, proof) in
-                try_provers(lemma_file, provers, &egg_file, &vampire_file, &twee_file)
-            {
-                let szs_status = proof
-                    .lines()
-                    .find(|l| l.contains("RESULT:") || l.contains("SZS status"))
-                    .unwrap_or("")
-                    .to_lowercase(); // normalize to lowercase
-
-                let len = if szs_status.contains("countersatisfiable")
-                    || szs_status.contains("counter-satisfiable")
-                    || szs_status.contains("counter_satisfiable")
-                    || szs_status.contains("satisfiable") && !szs_status.contains("unsatisfiable")
-                    || szs_status.contains("unknown")
-                {
-                    1000 // sentinel for non-theorem / countersat / unknown
-                         // TODO we can use them. But for now we just want shortest
-                         // theorem proofs. Later we can see how we prove the
-                         // conjecture from the satisfiable ones.
-                } else {
-                    proof_length(&prover, &proof)
+    // groups are independent: every file a job writes is named after its own
+    // lemma problem, so jobs can share the tmp directories
+    let queue = Mutex::new(sorted_nums.into_iter());
+    let results = Mutex::new(Summary::new());
+    let runs = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        for _ in 0..config.jobs.min(groups.len()).max(1) {
+            scope.spawn(|| loop {
+                let Some(n) = queue.lock().unwrap().next() else {
+                    break;
                 };
+                let files = &groups[&n];
+                if let Some(best) = prove_group(runner, config, n, files, provers, out_dir, &runs) {
+                    results.lock().unwrap().insert(n, best);
+                }
+            });
+        }
+    });
+    (results.into_inner().unwrap(), runs.into_inner().unwrap())
+}
+
+/// Whether a prover's output says the lemma does not follow, or could not
+/// tell: a (counter-)satisfiable or unknown status. Output without a status
+/// line counts as a proof.
+pub fn is_non_theorem(output: &str) -> bool {
+    let szs_status = output
+        .lines()
+        .find(|l| l.contains("RESULT:") || l.contains("SZS status"))
+        .unwrap_or("")
+        .to_lowercase();
+    szs_status.contains("countersatisfiable")
+        || szs_status.contains("counter-satisfiable")
+        || szs_status.contains("counter_satisfiable")
+        || szs_status.contains("satisfiable") && !szs_status.contains("unsatisfiable")
+        || szs_status.contains("unknown")
+}
+
+/// Prove lemma `n` from each of its lemma problem `files` with every prover
+/// and keep the shortest proof as `<out_dir>/<file>_<prover>.proof`.
+/// The choice only depends on the proofs, not on the order they were found in.
+/// If no prover proved the lemma, the first non-theorem output is returned as
+/// `NotProved`; it is kept in `out_dir` only with `--assume-unproved`, so
+/// that later phases can use the lemma as a hypothesis. The prover runs are
+/// counted in `runs`.
+fn prove_group(
+    runner: &dyn ProcessRunner,
+    config: &RunConfig,
+    n: u32,
+    files: &[String],
+    provers: &[&str],
+    out_dir: &Path,
+    runs: &Mutex<Vec<ProverRuns>>,
+) -> Option<SummaryEntry> {
+    println!("\n[INFO] Proving lemma {}", n);
 
-                //let len = proof_length(&prover, &proof);
-                println!("[INFO] {} proof length: {} lines", prover, len);
-                all_proofs.push((prover, proof, len, file_stem.to_string()));
+    // collect all successful proofs for this group
+    let mut all_proofs: Vec<(String, String, usize, String)> = Vec::new(); // (prover, proof, len, filename)
+    let mut non_theorems: Vec<SummaryEntry> = Vec::new();
+
+    for lemma_file in files {
+        let file_stem = Path::new(lemma_file).file_stem().unwrap().to_string_lossy();
+        for (prover, proof) in try_provers(runner, config, lemma_file, provers, out_dir, runs) {
+            if is_non_theorem(&proof) {
+                non_theorems.push(SummaryEntry {
+                    file: file_stem.to_string(),
+                    prover,
+                    proof,
+                    status: LemmaStatus::NotProved,
+                    usefulness: None,
+                });
+                continue;
             }
+            let len = match proof_length(&prover, &proof) {
+                Ok(len) => len,
+                Err(e) => {
+                    eprintln!("[ERROR] {}", e);
+                    continue;
+                }
+            };
+            println!("[INFO] {} proof length: {} lines", prover, len);
+            all_proofs.push((prover, proof, len, file_stem.to_string()));
         }
+    }
 
-        // pick the shortest proof across all modes and provers
-        if let Some((best_prover, best_proof, best_len, best_file)) =
-            all_proofs.into_iter().min_by(|a, b| {
-                // Compare lengths first
-                if a.2 != b.2 {
-                    a.2.cmp(&b.2)
-                } else {
-                    // Tie-breaker: prefer "twee" over "vampire" over others
-                    let order = |p: &String| {
-                        if p == "twee" {
-                            0
-                        } else if p == "vampire" {
-                            1
-                        } else {
-                            2
-                        }
-                    };
-                    order(&a.0).cmp(&order(&b.0))
-                }
-            })
-        {
-            let final_path = out_dir.join(format!("{}_{}.proof", best_file, best_prover));
-            if let Err(e) = fs::write(&final_path, &best_proof) {
-                eprintln!("[ERROR] Failed to save shortest proof: {}", e);
+    // pick the shortest proof across all modes and provers
+    if let Some((best_prover, best_proof, best_len, best_file)) =
+        all_proofs.into_iter().min_by(|a, b| {
+            // Compare lengths first
+            if a.2 != b.2 {
+                a.2.cmp(&b.2)
             } else {
-                println!("[INFO] Saved shortest proof to '{}'", final_path.display());
+                // Tie-breaker: prefer "twee" over "vampire" over others
+                let order = |p: &String| {
+                    if p == "twee" {
+                        0
+                    } else if p == "vampire" {
+                        1
+                    } else {
+                        2
+                    }
+                };
+                order(&a.0).cmp(&order(&b.0))
             }
+        })
+    {
+        let final_path = out_dir.join(format!("{}_{}.proof", best_file, best_prover));
+        if let Err(e) = write_atomic(&final_path, &best_proof) {
+            eprintln!("[ERROR] Failed to save shortest proof: {}", e);
+        } else {
+            println!("[INFO] Saved shortest proof to '{}'", final_path.display());
+        }
 
-            println!(
-                "[INFO] Shortest proof for lemma {} found in '{}' by '{}' with {} lines",
-                n, best_file, best_prover, best_len
-            );
+        println!(
+            "[INFO] Shortest proof for lemma {} found in '{}' by '{}' with {} lines",
+            n, best_file, best_prover, best_len
+        );
 
-            results.insert(n, (best_file, best_prover, best_proof));
-        } else {
-            println!("[WARN] No successful proof for group {}", n);
+        Some(SummaryEntry {
+            file: best_file,
+            prover: best_prover,
+            proof: best_proof,
+            status: LemmaStatus::Proved,
+            usefulness: None,
+        })
+    } else if let Some(unproved) = non_theorems.into_iter().next() {
+        println!(
+            "[WARN] No proof for lemma {}, '{}' says: {}",
+            n,
+            unproved.prover,
+            output_status(&unproved.proof)
+        );
+        if config.assume_unproved {
+            let path = out_dir.join(format!("{}_{}.proof", unproved.file, unproved.prover));
+            match write_atomic(&path, &unproved.proof) {
+                Ok(()) => println!(
+                    "[INFO] Keeping lemma {} as a hypothesis in '{}'",
+                    n,
+                    path.display()
+                ),
+                Err(e) => eprintln!("[ERROR] Failed to save {}: {}", path.display(), e),
+            }
         }
+        Some(unproved)
+    } else {
+        println!("[WARN] No successful proof for group {}", n);
+        None
     }
-
-    results
 }
 
+/// Run each of `provers` on `lemma_file`, keeping its output in the tmp
+/// directory of the prover in `out_dir`, and count the runs in `runs`
 fn try_provers(
+    runner: &dyn ProcessRunner,
+    config: &RunConfig,
     lemma_file: &str,
     provers: &[&str],
-    egg_file: &Path,
-    vampire_file: &Path,
-    twee_file: &Path,
+    out_dir: &Path,
+    runs: &Mutex<Vec<ProverRuns>>,
 ) -> Vec<(String, String)> {
+    let file_stem = Path::new(lemma_file).file_stem().unwrap().to_string_lossy();
     let mut successes = Vec::new();
 
     for &prover in provers {
-        let output_file = match prover {
-            "egg" => egg_file,
-            "vampire" => vampire_file,
-            "twee" => twee_file,
-            _ => {
-                eprintln!("[ERROR] Unknown prover '{}'", prover);
-                continue;
-            }
-        };
+        if !matches!(prover, "egg" | "vampire" | "twee") {
+            eprintln!("[ERROR] Unknown prover '{}'", prover);
+            continue;
+        }
+        let output_file = out_dir
+            .join(format!("{}_tmp", prover))
+            .join(format!("{}_{}.proof", file_stem, prover));
 
         println!("[RUN] Trying prover '{}' on '{}'", prover, lemma_file);
 
-        let proof_content = match prover {
-            "egg" => {
-                if run_egg(lemma_file, &output_file.to_string_lossy()).is_none() {
-                    println!("[INFO] Egg failed for '{}'", lemma_file);
-                    continue;
-                }
-                match fs::read_to_string(output_file) {
-                    Ok(c) => c,
-                    Err(_) => {
-                        println!("[INFO] Egg failed to produce proof for '{}'", lemma_file);
-                        continue;
-                    }
-                }
-            }
-            "vampire" => match run_vampire(lemma_file) {
-                Some(c) => c,
-                None => {
-                    println!("[INFO] Vampire failed for '{}'", lemma_file);
-                    continue;
-                }
-            },
-            "twee" => match run_twee(lemma_file) {
-                Some(c) => c,
-                None => {
-                    println!("[INFO] Twee failed for '{}'", lemma_file);
-                    continue;
-                }
-            },
-            _ => continue,
+        let (failure, proof) = run_prover(runner, config, prover, lemma_file, &output_file);
+        count_run(&mut runs.lock().unwrap(), prover, lemma_file, failure);
+        let Some((proof_content, raw_output)) = proof else {
+            println!("[INFO] '{}' failed for '{}'", prover, lemma_file);
+            continue;
         };
 
-        if let Err(e) = fs::write(output_file, &proof_content) {
+        if let Err(e) = write_atomic(&output_file, &proof_content) {
             eprintln!(
                 "[ERROR] Failed to save proof for prover '{}': {}",
                 prover, e
             );
         }
+        // the proof file holds the converted text; keep what the prover printed next to it
+        if let Some(raw) = raw_output {
+            let raw_file = output_file.with_extension("proof.raw");
+            println!(
+                "[WARN] '{}' printed non-UTF-8 output, original bytes saved to {}",
+                prover,
+                raw_file.display()
+            );
+            if let Err(e) = write_atomic(&raw_file, raw) {
+                eprintln!("[ERROR] Failed to save {}: {}", raw_file.display(), e);
+            }
+        }
 
-        if prover != "egg" {
-            let szs = proof_content
-                .lines()
-                .find(|l| l.contains("SZS status") || l.contains("RESULT:"))
-                .unwrap_or("")
-                .to_lowercase();
+        let szs = proof_content
+            .lines()
+            .find(|l| l.contains("SZS status") || l.contains("RESULT:"))
+            .unwrap_or("")
+            .to_lowercase();
 
-            if szs.contains("theorem") || szs.contains("unsatisfiable") {
-                println!("[INFO] '{}' proved theorem for '{}'", prover, lemma_file);
-            } else {
-                println!(
-                    "[INFO] '{}' returned non-theorem status for '{}': {}",
-                    prover, lemma_file, szs
-                );
-            }
+        if szs.contains("theorem") || szs.contains("unsatisfiable") {
+            println!("[INFO] '{}' proved theorem for '{}'", prover, lemma_file);
+        } else {
+            println!(
+                "[INFO] '{}' returned non-theorem status for '{}': {}",
+                prover, lemma_file, szs
+            );
         }
 
         successes.push((prover.to_string(), proof_content));
@@ -356,3 +743,346 @@ fn try_provers(
 
     successes
 }
+
+/// The proof a prover printed, with its original bytes if they were not UTF-8
+type PrintedProof = (String, Option<Vec<u8>>);
+
+/// Run `prover` on `lemma_file`, writing its proof to `output_file`: why the
+/// run gave no proof, if it did not, and the proof it printed, if any, with the
+/// original bytes if they were not UTF-8. A non-theorem status still comes
+/// with its output.
+fn run_prover(
+    runner: &dyn ProcessRunner,
+    config: &RunConfig,
+    prover: &str,
+    lemma_file: &str,
+    output_file: &Path,
+) -> (Option<FailureKind>, Option<PrintedProof>) {
+    let (exe_path, output) = match prover {
+        "egg" => (
+            egg_path(),
+            egg_run(runner, lemma_file, &output_file.to_string_lossy()),
+        ),
+        "vampire" => (vampire_path(), vampire_run(runner, config, lemma_file)),
+        _ => (twee_path(), twee_run(runner, lemma_file)),
+    };
+    let Some(output) = output else {
+        return (Some(FailureKind::Error), None);
+    };
+    let failure = failure_kind(&output);
+    let Some(output) = successful(&exe_path, output) else {
+        return (failure.or(Some(FailureKind::Error)), None);
+    };
+    // egg-sc-tptp writes its proof to a file instead of printing it
+    let (proof, raw_output) = if prover == "egg" {
+        match fs::read_to_string(output_file) {
+            Ok(proof) => (proof, None),
+            Err(_) => {
+                println!("[INFO] Egg failed to produce proof for '{}'", lemma_file);
+                return (Some(FailureKind::Error), None);
+            }
+        }
+    } else {
+        (output.stdout, output.raw_stdout)
+    };
+    let unreadable = failure.is_none() && backend(prover).and_then(|b| b.steps(&proof)).is_err();
+    let failure = if unreadable {
+        Some(FailureKind::ParseError)
+    } else {
+        failure
+    };
+    (failure, Some((proof, raw_output)))
+}
+
+/// Why a prover run gave no proof, by its SZS status (or Twee's `RESULT:`).
+/// None for a proof, or for successful output without a status line, which
+/// counts as a proof. Whether a proof can be read is not checked here.
+fn failure_kind(output: &ProcessOutput) -> Option<FailureKind> {
+    if output.timed_out {
+        return Some(FailureKind::Timeout);
+    }
+    let has_status = output
+        .stdout
+        .lines()
+        .any(|l| l.contains("SZS status") || l.contains("RESULT:"));
+    let out_of_memory = || {
+        let stderr = output.stderr.to_lowercase();
+        stderr.contains("out of memory") || stderr.contains("heap overflow")
+    };
+    if !has_status && output.succeeded() {
+        return None;
+    }
+    if !has_status {
+        return Some(if out_of_memory() {
+            FailureKind::Memout
+        } else {
+            FailureKind::Error
+        });
+    }
+    match output_status(&output.stdout).to_lowercase().as_str() {
+        "theorem" | "unsatisfiable" if output.succeeded() => None,
+        "timeout" => Some(FailureKind::Timeout),
+        "memoryout" => Some(FailureKind::Memout),
+        "countersatisfiable" | "satisfiable" => Some(FailureKind::CounterSatisfiable),
+        "gaveup" | "unknown" | "incomplete" | "resourceout" => Some(FailureKind::GaveUp),
+        _ if out_of_memory() => Some(FailureKind::Memout),
+        _ => Some(FailureKind::Error),
+    }
+}
+
+/// Count a run of `prover` on `lemma_file` in `runs`, under the mode of the lemma problem
+fn count_run(
+    runs: &mut Vec<ProverRuns>,
+    prover: &str,
+    lemma_file: &str,
+    failure: Option<FailureKind>,
+) {
+    let stem = Path::new(lemma_file)
+        .file_stem()
+        .map_or(String::new(), |s| s.to_string_lossy().to_string());
+    // e.g. "abstract" for abstract_lemma_0002
+    let mode = stem.split("_lemma_").next().unwrap_or_default();
+    collect_report::add_run(runs, prover, mode, failure);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{DryRunRunner, ProcessOutput};
+    use crate::test_support::ScratchDir;
+
+    /// A lemma problem `<stem>.p` in `dir` for each stem, for a fake prover to answer
+    fn lemma_files(dir: &ScratchDir, stems: &[&str]) -> Vec<String> {
+        stems
+            .iter()
+            .map(|stem| dir.write(&format!("{}.p", stem), "fof(c, conjecture, a = b)."))
+            .collect()
+    }
+
+    #[test]
+    fn provers_are_looked_up_in_bin_then_on_the_path() {
+        // found nowhere: the bundled path, so that the error names it
+        let name = "krympa_no_such_prover";
+        assert_eq!(
+            prover_binary(name),
+            Path::new("..")
+                .join("bin")
+                .join(format!("{}{}", name, env::consts::EXE_SUFFIX))
+        );
+        #[cfg(unix)]
+        assert_eq!(prover_binary("sh"), which::which("sh").unwrap());
+    }
+
+    #[test]
+    fn screening_flags_refuted_axioms_once() {
+        let runner = DryRunRunner::replying(|spec| {
+            if spec.input.as_deref().unwrap_or("").contains("$false") {
+                ProcessOutput::success("% SZS status Unsatisfiable for stdin")
+            } else {
+                ProcessOutput::success("% SZS status Satisfiable for stdin")
+            }
+        });
+        let config = RunConfig::default();
+        let screen = ConsistencyScreen::new(&runner, &config);
+        let bad = "fof(screen_test_bad, axiom, $false).";
+        assert!(screen.inconsistent(bad));
+        assert!(screen.inconsistent(bad));
+        assert!(!screen.inconsistent("fof(screen_test_ok, axiom, a = b)."));
+        assert_eq!(runner.calls().len(), 2);
+        // another search screens again
+        assert!(ConsistencyScreen::new(&runner, &config).inconsistent(bad));
+        assert_eq!(runner.calls().len(), 3);
+    }
+
+    #[test]
+    fn twee_problems_are_piped_in() {
+        let runner = DryRunRunner::replying(|_| ProcessOutput::success("% SZS status Theorem"));
+        let problem = "fof(piped, conjecture, a = a).";
+        assert!(run_twee_problem(&runner, &["--quiet"], problem).is_some());
+        let calls = runner.calls();
+        assert_eq!(calls[0].args, ["--quiet", "-"]);
+        assert_eq!(calls[0].input.as_deref(), Some(problem));
+    }
+
+    #[test]
+    fn prover_runs_are_told_from_other_commands() {
+        let spec =
+            |program: &str, args: &[&str]| ProcessSpec::new(program, PROVER_TIMEOUT).args(args);
+        assert_eq!(
+            recorded_prover(&spec("/opt/bin/vampire", &["--input_syntax", "tptp"])),
+            Some("vampire".to_string())
+        );
+        assert_eq!(
+            recorded_prover(&spec("../bin/twee", &["--quiet", "-"])),
+            Some("twee".to_string())
+        );
+        assert_eq!(recorded_prover(&spec("vampire", &["--version"])), None);
+        assert_eq!(recorded_prover(&spec("ssh", &["node1", "ls"])), None);
+    }
+
+    #[test]
+    fn failed_runs_are_told_apart() {
+        let status = |line: &str| failure_kind(&ProcessOutput::success(line));
+        assert_eq!(status("% SZS status Theorem for lemma"), None);
+        assert_eq!(status("RESULT: Unsatisfiable"), None);
+        // output without a status counts as a proof
+        assert_eq!(status("1. a = b [input]"), None);
+        assert_eq!(
+            status("% SZS status Timeout for lemma"),
+            Some(FailureKind::Timeout)
+        );
+        assert_eq!(
+            status("% SZS status MemoryOut for lemma"),
+            Some(FailureKind::Memout)
+        );
+        assert_eq!(
+            status("% SZS status CounterSatisfiable for lemma"),
+            Some(FailureKind::CounterSatisfiable)
+        );
+        assert_eq!(status("RESULT: GaveUp"), Some(FailureKind::GaveUp));
+
+        let timed_out = ProcessOutput {
+            timed_out: true,
+            ..ProcessOutput::default()
+        };
+        assert_eq!(failure_kind(&timed_out), Some(FailureKind::Timeout));
+        let crashed = ProcessOutput {
+            exit_code: Some(251),
+            stderr: "twee: out of memory".to_string(),
+            ..ProcessOutput::default()
+        };
+        assert_eq!(failure_kind(&crashed), Some(FailureKind::Memout));
+        let crashed = ProcessOutput {
+            exit_code: Some(1),
+            stdout: "% SZS status Theorem for lemma".to_string(),
+            ..ProcessOutput::default()
+        };
+        assert_eq!(failure_kind(&crashed), Some(FailureKind::Error));
+    }
+
+    #[test]
+    fn parallel_jobs_pick_the_same_proofs() {
+        // the history problem of lemma 1 has the shorter proof
+        let runner = DryRunRunner::replying(|spec| {
+            let file = spec.args.last().unwrap();
+            let steps = if file.ends_with("single_lemma_0001.p") {
+                2
+            } else {
+                1
+            };
+            let mut proof = "% SZS status Theorem for lemma\n1. a = b [input]\n".to_string();
+            for i in 0..steps {
+                proof.push_str(&format!("{}. b = a [superposition 1,1]\n", i + 2));
+            }
+            ProcessOutput::success(&proof)
+        });
+        let dir = ScratchDir::new("prove_lemmas");
+        let files = lemma_files(&dir, &[
+            "single_lemma_0001",
+            "history_lemma_0001",
+            "single_lemma_0002",
+        ]);
+
+        let mut picked = Vec::new();
+        for jobs in [1, 3] {
+            let out_dir = dir.path().join(format!("proofs_{}", jobs));
+            let config = RunConfig {
+                jobs,
+                ..RunConfig::default()
+            };
+            let (results, runs) = prove_lemmas_with(
+                &runner,
+                &config,
+                &files,
+                &["vampire"],
+                &out_dir.to_string_lossy(),
+            );
+            // every problem is tried once, however many jobs there are
+            let attempts: usize = runs.iter().map(|r| r.attempts).sum();
+            assert_eq!(attempts, files.len());
+            let mut results: Vec<(u32, String)> = results
+                .into_iter()
+                .map(|(n, entry)| (n, entry.file))
+                .collect();
+            results.sort();
+            picked.push(results);
+        }
+
+        assert_eq!(
+            picked[0],
+            vec![
+                (1, "history_lemma_0001".to_string()),
+                (2, "single_lemma_0002".to_string())
+            ]
+        );
+        assert_eq!(picked[0], picked[1]);
+    }
+
+    #[test]
+    fn lemmas_without_a_proof_are_marked_not_proved() {
+        let runner = DryRunRunner::replying(|spec| {
+            let file = spec.args.last().unwrap();
+            if file.ends_with("history_lemma_0002.p") {
+                ProcessOutput::success(
+                    "% SZS status Theorem for lemma\n1. a = b [input]\n2. b = a [superposition 1,1]\n",
+                )
+            } else {
+                ProcessOutput::success("% SZS status CounterSatisfiable for lemma\n")
+            }
+        });
+        let dir = ScratchDir::new("unproved_lemmas");
+        let files = lemma_files(&dir, &[
+            "single_lemma_0001",
+            "single_lemma_0002",
+            "history_lemma_0002",
+        ]);
+        let out_dir = dir.path().join("proofs");
+        let (results, runs) = prove_lemmas_with(
+            &runner,
+            &RunConfig::default(),
+            &files,
+            &["vampire"],
+            &out_dir.to_string_lossy(),
+        );
+        let counted: Vec<(&str, usize, usize)> = runs
+            .iter()
+            .map(|r| (r.mode.as_str(), r.attempts, r.failed()))
+            .collect();
+        assert_eq!(counted, [("single", 2, 2), ("history", 1, 0)]);
+
+        assert_eq!(results[&1].status, LemmaStatus::NotProved);
+        assert!(!out_dir.join("single_lemma_0001_vampire.proof").exists());
+        // a counter-satisfiable problem of a lemma does not beat a proof from another one
+        assert_eq!(results[&2].status, LemmaStatus::Proved);
+        assert_eq!(results[&2].file, "history_lemma_0002");
+    }
+
+    #[test]
+    fn proofs_are_counted_by_their_backend() {
+        let twee = "Axiom 1 (a1): mult(e, X) = X.\n\nLemma 2: mult(e, e) = e.\nProof:\n  mult(e, e)\n= { by axiom 1 (a1) }\n  e\n";
+        let vampire = "1. mult(e,X0) = X0 [input]\n2. mult(e,e) = e [superposition 1,1]\n3. e = e [forward demodulation 2,1]\n";
+        assert_eq!(proof_length("twee", twee), Ok(1));
+        assert_eq!(proof_length("vampire", vampire), Ok(2));
+        assert_eq!(backend("egg").unwrap().name(), "egg");
+        // a proof of a prover without a backend has no length to compare
+        let err = proof_length("prover9", vampire).unwrap_err();
+        assert!(err.contains("prover9") && err.contains("vampire, twee, egg"));
+        assert!(crate::proof_step::proof_steps("prover9", vampire).is_err());
+    }
+
+    #[test]
+    fn unreadable_proofs_are_counted_by_their_inference_lines() {
+        // the formula of step 2 cannot be parsed
+        let vampire = "% SZS status Unsatisfiable for p\n% SZS output start Proof for p\n\
+                       1. mult(e,X0) = X0 [input]\n2. @@@ [superposition 1,1]\n\
+                       3. $false [forward demodulation 2,1]\n% SZS output end Proof for p\n";
+        assert_eq!(proof_length("vampire", vampire), Ok(2));
+        let twee = "Axiom 1 (a1): mult(e, X) = X.\n\nGoal 1 (g): @@@.\nProof:\n  @@@\n\
+                    = { by axiom 1 (a1) }\n  e\n";
+        assert_eq!(proof_length("twee", twee), Ok(1));
+        // comments and status lines are not steps
+        let err = proof_length("vampire", "% SZS status GaveUp for p\n% no proof\n").unwrap_err();
+        assert!(err.contains("No vampire proof steps"), "{}", err);
+        assert!(proof_length("twee", "RESULT: GaveUp.\n").is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use crate::alpha_match::canonical_form;
+use crate::lemma_store::LemmaStore;
+use crate::run_config::RunConfig;
+use crate::summary::{Summary, Usefulness};
+use crate::vampire_proof::parse_vampire_steps;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+
+// Ranking of the Phase 1 lemmas by how useful they look as the root of a
+// minimized proof, so `minimize` tries the promising ones first instead of
+// going down the lemma numbers. A lemma is useful if much of the Vampire
+// refutation is derived from it, appears in it several times, and is small.
+
+/// Symbols of a formula, leading quantifiers not counted
+fn size(formula: &str) -> usize {
+    canonical_form(formula)
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|token| !token.is_empty())
+        .count()
+}
+
+/// Usefulness of each lemma in `formulas` (lemma number -> formula) with
+/// respect to the refutation `vampire_proof`, ranked from 1
+pub fn rank(formulas: &BTreeMap<u32, String>, vampire_proof: &str) -> BTreeMap<u32, Usefulness> {
+    let steps = parse_vampire_steps(vampire_proof);
+    let mut children: HashMap<usize, usize> = HashMap::new();
+    for step in &steps {
+        for premise in &step.premises {
+            *children.entry(*premise).or_default() += 1;
+        }
+    }
+    // canonical form -> (steps stating it, steps derived from those)
+    let mut stated: HashMap<String, (usize, usize)> = HashMap::new();
+    for step in &steps {
+        let counts = stated.entry(canonical_form(&step.formula)).or_default();
+        counts.0 += 1;
+        counts.1 += children.get(&step.index).copied().unwrap_or(0);
+    }
+
+    let mut scored: Vec<(u32, Usefulness)> = formulas
+        .iter()
+        .map(|(&n, formula)| {
+            let (occurrences, children) = stated
+                .get(&canonical_form(formula))
+                .copied()
+                .unwrap_or((0, 0));
+            let usefulness = Usefulness {
+                rank: 0,
+                occurrences,
+                children,
+                size: size(formula),
+            };
+            (n, usefulness)
+        })
+        .collect();
+    // ties go to the higher number, as when going down the numbers
+    scored.sort_by_key(|(n, u)| {
+        (
+            Reverse(u.children),
+            Reverse(u.occurrences),
+            u.size,
+            Reverse(*n),
+        )
+    });
+    scored
+        .into_iter()
+        .enumerate()
+        .map(|(i, (n, usefulness))| {
+            (
+                n,
+                Usefulness {
+                    rank: i + 1,
+                    ..usefulness
+                },
+            )
+        })
+        .collect()
+}
+
+/// Rank the lemmas of `summary` that later phases build on against the
+/// refutation `vampire_proof`, and store the ranking in their entries.
+/// Lemmas whose formula cannot be read are left unranked.
+pub fn rank_summary(
+    config: &RunConfig,
+    summary: &mut Summary,
+    vampire_proof: &str,
+    lemmas: &LemmaStore,
+) {
+    let formulas: BTreeMap<u32, String> = summary
+        .iter()
+        .filter(|(_, entry)| entry.is_usable(config))
+        .filter_map(|(&n, entry)| Some((n, lemmas.formula(&entry.file).ok()?)))
+        .collect();
+    for (n, usefulness) in rank(&formulas, vampire_proof) {
+        if let Some(entry) = summary.get_mut(&n) {
+            entry.usefulness = Some(usefulness);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lemmas_the_refutation_builds_on_rank_first() {
+        let proof = "1. ! [X0] : mult(e,X0) = X0 [input]\n\
+            2. ! [X0] : e = mult(inv(X0),X0) [input]\n\
+            3. ! [X0,X1,X2] : mult(mult(X0,X1),X2) = mult(X0,mult(X1,X2)) [input]\n\
+            4. b = mult(inv(a),mult(a,b)) [input]\n\
+            5. b != mult(inv(a),mult(a,b)) [negated conjecture 4]\n\
+            6. ! [X0,X1] : mult(e,X1) = mult(inv(X0),mult(X0,X1)) [superposition 3,2]\n\
+            7. ! [X0,X1] : mult(inv(X0),mult(X0,X1)) = X1 [forward demodulation 6,1]\n\
+            8. b != b [superposition 5,7]\n";
+        let formulas = BTreeMap::from([
+            (
+                1,
+                "! [X, Y] : (mult(e, Y) = mult(inv(X), mult(X, Y)))".to_string(),
+            ),
+            (2, "! [X, Y] : (mult(inv(X), mult(X, Y)) = Y)".to_string()),
+            (3, "! [X] : (mult(X, inv(X)) = e)".to_string()),
+        ]);
+        let ranking = rank(&formulas, proof);
+
+        assert_eq!(
+            ranking[&1],
+            Usefulness {
+                rank: 2,
+                occurrences: 1,
+                children: 1,
+                size: 9,
+            }
+        );
+        // used as often as lemma 1, and smaller
+        assert_eq!(ranking[&2].rank, 1);
+        // not in the refutation
+        assert_eq!((ranking[&3].rank, ranking[&3].occurrences), (3, 0));
+    }
+}
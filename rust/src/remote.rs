@@ -0,0 +1,381 @@
+use crate::collect_report::CollectReport;
+use crate::process::{LocalRunner, ProcessOutput, ProcessRunner, ProcessSpec};
+use crate::run_config::RunConfig;
+use crate::{
+    config_flags, extract_suffix, inputs, kept_original_proof, minimized_hypotheses,
+    minimized_proof_steps, minimized_rule_steps, print_file_summary, print_global_summary,
+    record_outcome, vampire_proof_steps, vampire_rule_steps, vampire_szs_status, BenchmarkResult,
+    COMMANDS,
+};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Limit for one pipeline command, as for local runs
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(3600);
+/// Limit for copying a problem or its artifacts
+const COPY_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// What the pipeline writes to `output` for a problem, as `<kind>_<suffix>.<ext>`
+const ARTIFACT_KINDS: [&str; 7] = [
+    "vampire_proof",
+    "summary",
+    "collect",
+    "dag",
+    "lemmas",
+    "proof",
+    "trace",
+];
+
+/// A machine reachable with `ssh host` (key-based, no prompts) holding a Krympa
+/// checkout at `root`: the frankenstein binary runs in `root/rust`, with the
+/// provers in `root/bin` and the artifacts in `root/output`.
+pub struct SshWorker {
+    pub host: String,
+    pub root: String,
+    runner: Box<dyn ProcessRunner>,
+}
+
+impl SshWorker {
+    pub fn new(host: &str, root: &str) -> SshWorker {
+        SshWorker::with_runner(host, root, Box::new(LocalRunner))
+    }
+
+    /// A worker starting `ssh` and `scp` through `runner`
+    pub fn with_runner(host: &str, root: &str, runner: Box<dyn ProcessRunner>) -> SshWorker {
+        SshWorker {
+            host: host.to_string(),
+            root: root.trim_end_matches('/').to_string(),
+            runner,
+        }
+    }
+
+    /// Parse `host:root`; without a root the checkout is `~/Krympa`
+    pub fn parse(spec: &str) -> SshWorker {
+        match spec.split_once(':') {
+            Some((host, root)) => SshWorker::new(host, root),
+            None => SshWorker::new(spec, "Krympa"),
+        }
+    }
+
+    fn ssh(&self, command: &str, timeout: Duration) -> Result<ProcessOutput, String> {
+        let spec = ProcessSpec::new("ssh", timeout).args(&[
+            "-n",
+            "-o",
+            "BatchMode=yes",
+            self.host.as_str(),
+            command,
+        ]);
+        self.runner.run(&spec)
+    }
+
+    fn scp(&self, from: &[String], to: &str) -> Result<(), String> {
+        let spec = ProcessSpec::new("scp", COPY_TIMEOUT)
+            .args(&["-q", "-B"])
+            .args(from)
+            .args(&[to]);
+        let output = self.runner.run(&spec)?;
+        if output.succeeded() {
+            Ok(())
+        } else {
+            Err(format!(
+                "copying {} to {} failed: {}",
+                from.join(" "),
+                to,
+                output.stderr.trim()
+            ))
+        }
+    }
+
+    /// Copy `input` to `root/input` on the worker
+    fn stage(&self, input: &Path, name: &str) -> Result<(), String> {
+        let mkdir = format!(
+            "mkdir -p {} {}",
+            shell_quote(&format!("{}/input", self.root)),
+            shell_quote(&format!("{}/output", self.root))
+        );
+        let output = self.ssh(&mkdir, COPY_TIMEOUT)?;
+        if !output.succeeded() {
+            return Err(format!(
+                "cannot prepare {}: {}",
+                self.root,
+                output.stderr.trim()
+            ));
+        }
+        self.scp(
+            &[input.to_string_lossy().to_string()],
+            &format!("{}:{}/input/{}", self.host, self.root, name),
+        )
+    }
+
+    /// Copy the artifacts of the problem with `suffix` from `root/output` to `output_dir`
+    fn fetch(&self, suffix: &str, output_dir: &Path) -> Result<(), String> {
+        let remote_output = format!("{}/output", self.root);
+        let listing = self.ssh(
+            &format!("ls -1 {}", shell_quote(&remote_output)),
+            COPY_TIMEOUT,
+        )?;
+        if !listing.succeeded() {
+            return Err(format!(
+                "cannot list {}: {}",
+                remote_output,
+                listing.stderr.trim()
+            ));
+        }
+        let files: Vec<String> = listing
+            .stdout
+            .lines()
+            .filter(|name| is_artifact_of(name, suffix))
+            .map(|name| format!("{}:{}/{}", self.host, remote_output, name))
+            .collect();
+        if files.is_empty() {
+            return Err(format!("no artifacts of {} in {}", suffix, remote_output));
+        }
+        self.scp(&files, &output_dir.to_string_lossy())
+    }
+
+    /// Run the benchmark pipeline on `input` on this worker, with the
+    /// settings of `config`, and copy the artifacts it wrote for the problem
+    /// back to `output_dir`
+    pub fn run_problem(
+        &self,
+        config: &RunConfig,
+        frankenstein_bin: &str,
+        input: &Path,
+        output_dir: &Path,
+    ) -> BenchmarkResult {
+        let file = input.to_string_lossy().to_string();
+        let name = input.file_name().unwrap().to_string_lossy().to_string();
+        let failed = || BenchmarkResult::failed(file.clone());
+
+        if let Err(e) = self.stage(input, &name) {
+            eprintln!("[ERROR] {}: {}", self.host, e);
+            return failed();
+        }
+
+        let remote_input = format!("../input/{}", name);
+        let mut vampire_stdout = None;
+        let mut minimize_stdout = None;
+        for cmd in COMMANDS {
+            println!("[{}] Running '{} {}' ...", self.host, cmd, name);
+            let command = format!(
+                "cd {} && {} {} {} {}",
+                shell_quote(&format!("{}/rust", self.root)),
+                shell_quote(frankenstein_bin),
+                cmd,
+                shell_quote(&remote_input),
+                config_flags(config).join(" ")
+            );
+            let output = match self.ssh(&command, COMMAND_TIMEOUT) {
+                Ok(output) => output,
+                Err(e) => {
+                    eprintln!("Failed to run '{} {}' on {}: {}", cmd, name, self.host, e);
+                    continue;
+                }
+            };
+
+            if output.timed_out {
+                eprintln!(
+                    "[TIMEOUT] '{}' exceeded {:?} on {} ({}) — recording as failed",
+                    cmd, COMMAND_TIMEOUT, file, self.host
+                );
+                return failed();
+            }
+            if !output.succeeded() {
+                eprintln!(
+                    "Command '{}' failed on {} ({})\n{}",
+                    cmd, file, self.host, output.stderr
+                );
+            }
+            match cmd {
+                "run_vampire" => vampire_stdout = Some(output.stdout),
+                "minimize" => minimize_stdout = Some(output.stdout),
+                _ => {}
+            }
+        }
+
+        let suffix = extract_suffix(&file);
+        if let Err(e) = self.fetch(&suffix, output_dir) {
+            eprintln!("[WARN] {}: {}", self.host, e);
+        }
+
+        let vampire_steps = vampire_proof_steps(output_dir, &suffix);
+        BenchmarkResult {
+            file,
+            szs_status: vampire_stdout.as_deref().and_then(vampire_szs_status),
+            vampire_steps,
+            minimized_steps: minimize_stdout.as_deref().and_then(minimized_proof_steps),
+            kept_original: minimize_stdout.as_deref().is_some_and(kept_original_proof),
+            vampire_rules: vampire_rule_steps(output_dir, &suffix),
+            minimized_rules: minimize_stdout
+                .as_deref()
+                .map(minimized_rule_steps)
+                .unwrap_or_default(),
+            assumed: minimize_stdout
+                .as_deref()
+                .map(minimized_hypotheses)
+                .unwrap_or_default(),
+            collect: CollectReport::load(&CollectReport::path(output_dir, &suffix)).ok(),
+        }
+    }
+}
+
+/// Run the benchmark of `input_folder` on `workers` with the settings of
+/// `config`. Each worker takes the next problem as soon as it is free; the
+/// problems are those of a local run (see `inputs::problems`), a compressed
+/// problem or an archive member written to `../output/inputs` before it is
+/// copied. The artifacts are gathered in `../output` and the results merged
+/// into one global summary, which is returned.
+pub fn run_remote(
+    config: &RunConfig,
+    input_folder: &str,
+    frankenstein_bin: &str,
+    workers: &[SshWorker],
+) -> Vec<BenchmarkResult> {
+    let input_dir = Path::new(input_folder);
+    let problems = match inputs::problems(input_dir) {
+        Ok(problems) => problems,
+        Err(err) => {
+            eprintln!("{}", err);
+            return Vec::new();
+        }
+    };
+    if workers.is_empty() {
+        eprintln!("No workers given.");
+        return Vec::new();
+    }
+    let output_dir = Path::new("../output");
+    fs::create_dir_all(output_dir).expect("Failed to create output folder");
+
+    println!(
+        "Starting benchmarking of {} on {} worker(s): {}\n",
+        input_dir.display(),
+        workers.len(),
+        workers
+            .iter()
+            .map(|w| w.host.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let queue = Mutex::new(problems.into_iter());
+    let results = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        for worker in workers {
+            scope.spawn(|| loop {
+                let Some(problem) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                let result = match problem.materialize(&output_dir.join("inputs")) {
+                    Ok(input) => worker.run_problem(config, frankenstein_bin, &input, output_dir),
+                    Err(err) => {
+                        eprintln!("Skipping {}: {}", problem.label(), err);
+                        BenchmarkResult::failed(problem.label())
+                    }
+                };
+                // print under the lock so summaries of different workers do not interleave
+                let mut results = results.lock().unwrap();
+                print_file_summary(&result);
+                record_outcome(config, &result);
+                results.push(result);
+            });
+        }
+    });
+
+    let mut all_results = results.into_inner().unwrap();
+    all_results.sort_by(|a, b| a.file.cmp(&b.file));
+    print_global_summary(config, &all_results);
+    all_results
+}
+
+/// Whether the file `name` in `output` is an artifact of the problem with
+/// `suffix`: the whole name up to the extension is a kind and the suffix, so
+/// `dag_x_grp.txt` belongs to problem `x_grp`, not to `grp`. Kept versions
+/// (`proof_grp.out.orig`) count as well.
+fn is_artifact_of(name: &str, suffix: &str) -> bool {
+    ARTIFACT_KINDS
+        .iter()
+        .any(|kind| name.starts_with(&format!("{}_{}.", kind, suffix)))
+}
+
+/// Quote `s` for a POSIX shell
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::DryRunRunner;
+    use std::sync::Arc;
+
+    #[test]
+    fn artifacts_are_anchored_on_the_whole_problem_name() {
+        assert!(is_artifact_of("dag_grp.txt", "grp"));
+        assert!(is_artifact_of("vampire_proof_grp.out", "grp"));
+        assert!(is_artifact_of("proof_grp.out.orig", "grp"));
+        assert!(!is_artifact_of("dag_x_grp.txt", "grp"));
+        assert!(!is_artifact_of("dag_grp2.txt", "grp"));
+        assert!(!is_artifact_of("input_problem_grp.p", "grp"));
+    }
+
+    #[test]
+    fn run_problem_stages_runs_and_fetches() {
+        let fetched = Arc::new(Mutex::new(Vec::new()));
+        let copies = Arc::clone(&fetched);
+        let runner = DryRunRunner::replying(move |spec| {
+            let command = spec.args.last().map_or("", String::as_str);
+            if command.contains(" minimize ") {
+                ProcessOutput::success("[RESULT] Total steps: 7\n")
+            } else if command.starts_with("ls -1 ") {
+                ProcessOutput::success(
+                    "dag_grp.txt\ndag_x_grp.txt\nproof_grp.out\nproof_grp.out.orig\nvampire_proof_grp2.out\n",
+                )
+            } else {
+                if spec.args.iter().any(|a| a.contains(":/srv/krympa/output/")) {
+                    copies.lock().unwrap().extend(spec.args.clone());
+                }
+                ProcessOutput::success("")
+            }
+        });
+        let worker = SshWorker::with_runner("node1", "/srv/krympa/", Box::new(runner));
+        let output_dir = std::env::temp_dir().join("frankenstein-remote-test");
+
+        let result = worker.run_problem(
+            &RunConfig::default(),
+            "./frankenstein",
+            Path::new("problems/input_problem_grp.p"),
+            &output_dir,
+        );
+        assert_eq!(result.file, "problems/input_problem_grp.p");
+        assert_eq!(result.minimized_steps, Some(7));
+        assert_eq!(result.vampire_steps, None);
+        // only the artifacts of this problem are fetched, not those of x_grp or grp2
+        let fetched = fetched.lock().unwrap().clone();
+        assert_eq!(
+            fetched[2..fetched.len() - 1],
+            [
+                "node1:/srv/krympa/output/dag_grp.txt",
+                "node1:/srv/krympa/output/proof_grp.out",
+                "node1:/srv/krympa/output/proof_grp.out.orig",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_worker_spec() {
+        let worker = SshWorker::parse("node2:/data/krympa");
+        assert_eq!(worker.host, "node2");
+        assert_eq!(worker.root, "/data/krympa");
+
+        let worker = SshWorker::parse("node3");
+        assert_eq!(worker.root, "Krympa");
+    }
+
+    #[test]
+    fn quotes_for_the_remote_shell() {
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+}
@@ -0,0 +1,305 @@
+use crate::run_config::RunConfig;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Optional SQLite log of experiments (`--results-db <file>`): every prover
+// run, every candidate evaluated by minimize and the outcome of every
+// benchmark problem, for ad-hoc SQL over many runs. Needs the `sqlite` feature;
+// without a database every `record_*` call does nothing.
+
+/// The database of a run; the default one records nothing
+#[derive(Default)]
+pub struct ResultsDb {
+    db: Option<OpenDb>,
+}
+
+/// The open database, with the problem the prover runs belong to, and its path
+struct OpenDb {
+    recorder: Mutex<backend::Recorder>,
+    path: String,
+}
+
+impl fmt::Debug for ResultsDb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = self.db.as_ref().map(|db| db.path.as_str());
+        f.debug_struct("ResultsDb").field("path", &path).finish()
+    }
+}
+
+impl ResultsDb {
+    /// Log to the database at `path` (created if missing). `problem` names the
+    /// problem of the prover runs and candidates, `config` the settings they
+    /// were made with.
+    pub fn open(path: &str, problem: &str, config: &RunConfig) -> Result<ResultsDb, String> {
+        let recorder = backend::Recorder::open(path, problem, &config.fingerprint())?;
+        Ok(ResultsDb {
+            db: Some(OpenDb {
+                recorder: Mutex::new(recorder),
+                path: path.to_string(),
+            }),
+        })
+    }
+
+    /// `--results-db <file>` for the frankenstein commands if this run logs to a database
+    pub fn forwarded_flag(&self) -> Vec<String> {
+        match &self.db {
+            Some(db) => vec!["--results-db".to_string(), db.path.clone()],
+            None => Vec::new(),
+        }
+    }
+
+    fn with_db<F: FnOnce(&backend::Recorder) -> Result<(), String>>(&self, record: F) {
+        if let Some(db) = &self.db {
+            if let Err(e) = record(&db.recorder.lock().unwrap()) {
+                eprintln!("[WARN] Failed to record result: {}", e);
+            }
+        }
+    }
+
+    /// One prover invocation with its SZS status ("Timeout", "Error", ...)
+    pub fn record_prover_run(
+        &self,
+        prover: &str,
+        status: &str,
+        steps: Option<usize>,
+        elapsed: Duration,
+    ) {
+        self.with_db(|db| db.prover_run(prover, status, steps, elapsed));
+    }
+
+    /// One root/history combination evaluated by minimize
+    pub fn record_candidate(&self, root: &str, history: Option<&str>, steps: Option<usize>) {
+        self.with_db(|db| db.candidate(root, history, steps));
+    }
+
+    /// Final result of a benchmark problem
+    pub fn record_outcome(
+        &self,
+        problem: &str,
+        szs_status: Option<&str>,
+        vampire_steps: Option<usize>,
+        minimized_steps: Option<usize>,
+    ) {
+        self.with_db(|db| db.outcome(problem, szs_status, vampire_steps, minimized_steps));
+    }
+}
+
+#[cfg(feature = "sqlite")]
+mod backend {
+    use rusqlite::{params, Connection};
+    use std::time::Duration;
+
+    const SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS prover_runs (
+            id INTEGER PRIMARY KEY,
+            date TEXT NOT NULL DEFAULT (datetime('now')),
+            problem TEXT NOT NULL,
+            prover TEXT NOT NULL,
+            status TEXT NOT NULL,
+            steps INTEGER,
+            seconds REAL NOT NULL,
+            config TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS prover_runs_problem ON prover_runs (problem);
+        CREATE INDEX IF NOT EXISTS prover_runs_prover ON prover_runs (prover);
+        CREATE INDEX IF NOT EXISTS prover_runs_date ON prover_runs (date);
+
+        CREATE TABLE IF NOT EXISTS candidates (
+            id INTEGER PRIMARY KEY,
+            date TEXT NOT NULL DEFAULT (datetime('now')),
+            problem TEXT NOT NULL,
+            root TEXT NOT NULL,
+            history TEXT,
+            steps INTEGER,
+            config TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS candidates_problem ON candidates (problem);
+        CREATE INDEX IF NOT EXISTS candidates_date ON candidates (date);
+
+        CREATE TABLE IF NOT EXISTS outcomes (
+            id INTEGER PRIMARY KEY,
+            date TEXT NOT NULL DEFAULT (datetime('now')),
+            problem TEXT NOT NULL,
+            szs_status TEXT,
+            vampire_steps INTEGER,
+            minimized_steps INTEGER,
+            config TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS outcomes_problem ON outcomes (problem);
+        CREATE INDEX IF NOT EXISTS outcomes_date ON outcomes (date);
+    ";
+
+    pub struct Recorder {
+        conn: Connection,
+        problem: String,
+        /// `RunConfig::fingerprint`, so rows of different setups can be told apart
+        config: String,
+    }
+
+    fn sql_err(e: rusqlite::Error) -> String {
+        e.to_string()
+    }
+
+    impl Recorder {
+        pub fn open(path: &str, problem: &str, config: &str) -> Result<Recorder, String> {
+            let conn = Connection::open(path)
+                .map_err(|e| format!("Failed to open results database {}: {}", path, e))?;
+            // several frankenstein processes may write at the same time
+            conn.busy_timeout(Duration::from_secs(30))
+                .map_err(sql_err)?;
+            conn.execute_batch(SCHEMA).map_err(sql_err)?;
+            Ok(Recorder {
+                conn,
+                problem: problem.to_string(),
+                config: config.to_string(),
+            })
+        }
+
+        pub fn prover_run(
+            &self,
+            prover: &str,
+            status: &str,
+            steps: Option<usize>,
+            elapsed: Duration,
+        ) -> Result<(), String> {
+            self.conn
+                .execute(
+                    "INSERT INTO prover_runs (problem, prover, status, steps, seconds, config)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        self.problem,
+                        prover,
+                        status,
+                        steps.map(|s| s as i64),
+                        elapsed.as_secs_f64(),
+                        self.config
+                    ],
+                )
+                .map(|_| ())
+                .map_err(sql_err)
+        }
+
+        pub fn candidate(
+            &self,
+            root: &str,
+            history: Option<&str>,
+            steps: Option<usize>,
+        ) -> Result<(), String> {
+            self.conn
+                .execute(
+                    "INSERT INTO candidates (problem, root, history, steps, config)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        self.problem,
+                        root,
+                        history,
+                        steps.map(|s| s as i64),
+                        self.config
+                    ],
+                )
+                .map(|_| ())
+                .map_err(sql_err)
+        }
+
+        pub fn outcome(
+            &self,
+            problem: &str,
+            szs_status: Option<&str>,
+            vampire_steps: Option<usize>,
+            minimized_steps: Option<usize>,
+        ) -> Result<(), String> {
+            self.conn
+                .execute(
+                    "INSERT INTO outcomes (problem, szs_status, vampire_steps, minimized_steps, config)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        problem,
+                        szs_status,
+                        vampire_steps.map(|s| s as i64),
+                        minimized_steps.map(|s| s as i64),
+                        self.config
+                    ],
+                )
+                .map(|_| ())
+                .map_err(sql_err)
+        }
+
+        #[cfg(test)]
+        pub fn count(&self, table: &str) -> i64 {
+            self.conn
+                .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                    row.get(0)
+                })
+                .unwrap()
+        }
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+mod backend {
+    use std::time::Duration;
+
+    pub struct Recorder;
+
+    impl Recorder {
+        pub fn open(path: &str, _problem: &str, _config: &str) -> Result<Recorder, String> {
+            Err(format!(
+                "Cannot log to {}: built without the sqlite feature (cargo build --features sqlite)",
+                path
+            ))
+        }
+
+        pub fn prover_run(
+            &self,
+            _prover: &str,
+            _status: &str,
+            _steps: Option<usize>,
+            _elapsed: Duration,
+        ) -> Result<(), String> {
+            Ok(())
+        }
+
+        pub fn candidate(
+            &self,
+            _root: &str,
+            _history: Option<&str>,
+            _steps: Option<usize>,
+        ) -> Result<(), String> {
+            Ok(())
+        }
+
+        pub fn outcome(
+            &self,
+            _problem: &str,
+            _szs_status: Option<&str>,
+            _vampire_steps: Option<usize>,
+            _minimized_steps: Option<usize>,
+        ) -> Result<(), String> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::backend::Recorder;
+    use std::time::Duration;
+
+    #[test]
+    fn records_land_in_their_tables() {
+        let db = Recorder::open(":memory:", "toy", "id=0").unwrap();
+        db.prover_run("twee", "Theorem", Some(4), Duration::from_millis(20))
+            .unwrap();
+        db.prover_run("vampire", "Timeout", None, Duration::from_secs(10))
+            .unwrap();
+        db.candidate("history_lemma_0004", Some("history_lemma_0002"), Some(7))
+            .unwrap();
+        db.outcome("toy.p", Some("Theorem"), Some(12), Some(7))
+            .unwrap();
+
+        assert_eq!(db.count("prover_runs"), 2);
+        assert_eq!(db.count("candidates"), 1);
+        assert_eq!(db.count("outcomes"), 1);
+    }
+}
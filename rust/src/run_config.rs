@@ -0,0 +1,251 @@
+use crate::events::EventLog;
+use crate::prover_wrapper::prover_binary;
+use crate::results_db::ResultsDb;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// Seed passed to Vampire in deterministic mode
+pub const SEED: u32 = 0;
+
+/// Wall-clock limit for one prover run on a lemma
+pub const PROVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Vampire time limit for the consistency screening of the axioms given to Twee
+pub const SCREEN_TIME_LIMIT: Duration = Duration::from_secs(2);
+
+/// Vampire time limit for re-deriving a superposition step with `--recheck-steps`
+pub const STEP_CHECK_TIME_LIMIT: Duration = Duration::from_secs(1);
+
+/// Vampire activations allowed per step when the length of a candidate proof is capped
+pub const ACTIVATIONS_PER_STEP: usize = 200;
+
+/// Twee critical pairs allowed per step when the length of a candidate proof is capped
+pub const CRITICAL_PAIRS_PER_STEP: usize = 1000;
+
+/// The settings of a run, from the flags every command accepts. Built once
+/// from the command line and handed to whatever depends on them.
+#[derive(Debug)]
+pub struct RunConfig {
+    /// a fixed Vampire seed and no timestamps in provenance headers, so reruns
+    /// write the same files (`--deterministic`)
+    pub deterministic: bool,
+    /// keep only unit-equality steps when extracting superposition steps (`--equational-only`)
+    pub equational_only: bool,
+    /// let the step-chain minimization drop steps that a quick prover call can
+    /// re-derive from the remaining ones (`--recheck-steps`)
+    pub recheck_steps: bool,
+    /// give the lemmas of a Twee proof of a dependency to the following Twee
+    /// runs, as superposition steps are given (`--twee-steps`)
+    pub twee_steps: bool,
+    /// generalize root and history lemmas before building on them (`--generalize-lemmas`)
+    pub generalize_lemmas: bool,
+    /// pick the provers for a lemma from the Phase 1 winners (`--adaptive-provers`)
+    pub adaptive_provers: bool,
+    /// keep the Vampire proof as the output when minimizing makes it longer (`--keep-original`)
+    pub keep_original: bool,
+    /// use lemmas no prover proved as hypotheses instead of skipping them (`--assume-unproved`)
+    pub assume_unproved: bool,
+    /// pass step caps computed from the best candidate so far to the provers
+    /// (`--cap-steps`), see `ACTIVATIONS_PER_STEP` and `CRITICAL_PAIRS_PER_STEP`
+    pub cap_steps: bool,
+    /// what to do with root lemmas containing Skolem constants (`--skolems`)
+    pub skolems: SkolemPolicy,
+    /// always build on this backend instead of the shorter proof, for ablation
+    /// studies (`--force-backend`)
+    pub force_backend: Option<Backend>,
+    /// library file through which proved lemmas are shared with the other
+    /// problems of a benchmark (`--lemma-library`)
+    pub lemma_library: Option<String>,
+    /// number of lemma groups Phase 1 proves at the same time (`--jobs`). Not
+    /// part of the fingerprint: the proof picked for a lemma does not depend on it.
+    pub jobs: usize,
+    /// where progress events go (`--events`)
+    pub events: EventLog,
+    /// where prover runs, candidates and outcomes are logged (`--results-db`)
+    pub results_db: ResultsDb,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        RunConfig {
+            deterministic: false,
+            equational_only: false,
+            recheck_steps: false,
+            twee_steps: false,
+            generalize_lemmas: false,
+            adaptive_provers: false,
+            keep_original: false,
+            assume_unproved: false,
+            cap_steps: false,
+            skolems: SkolemPolicy::Skip,
+            force_backend: None,
+            lemma_library: None,
+            jobs: 1,
+            events: EventLog::default(),
+            results_db: ResultsDb::default(),
+        }
+    }
+}
+
+impl RunConfig {
+    /// `--lemma-library <file>` for the frankenstein commands if this run shares lemmas
+    pub fn forwarded_lemma_library(&self) -> Vec<String> {
+        match &self.lemma_library {
+            Some(path) => vec!["--lemma-library".to_string(), path.clone()],
+            None => Vec::new(),
+        }
+    }
+
+    /// Extra Vampire options: a fixed seed in deterministic mode
+    pub fn vampire_options(&self) -> Vec<String> {
+        if self.deterministic {
+            vec!["--random_seed".to_string(), SEED.to_string()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Everything that influences the results of a run, as `key=value` pairs,
+    /// followed by a short hash of them (the first 8 hex digits of their SHA-256,
+    /// the same for every build) to compare reports at a glance
+    pub fn fingerprint(&self) -> String {
+        let settings = [
+            ("frankenstein", env!("CARGO_PKG_VERSION").to_string()),
+            ("deterministic", self.deterministic.to_string()),
+            ("seed", SEED.to_string()),
+            ("equational_only", self.equational_only.to_string()),
+            ("recheck_steps", self.recheck_steps.to_string()),
+            ("twee_steps", self.twee_steps.to_string()),
+            ("skolems", self.skolems.name().to_string()),
+            (
+                "force_backend",
+                self.force_backend.map_or("none", Backend::name).to_string(),
+            ),
+            ("generalize_lemmas", self.generalize_lemmas.to_string()),
+            ("adaptive_provers", self.adaptive_provers.to_string()),
+            ("keep_original", self.keep_original.to_string()),
+            ("assume_unproved", self.assume_unproved.to_string()),
+            ("lemma_library", self.lemma_library.is_some().to_string()),
+            ("prover_timeout", format!("{}s", PROVER_TIMEOUT.as_secs())),
+            (
+                "step_caps",
+                if self.cap_steps {
+                    format!("{}al/{}cps", ACTIVATIONS_PER_STEP, CRITICAL_PAIRS_PER_STEP)
+                } else {
+                    "off".to_string()
+                },
+            ),
+            ("vampire", binary_hash(&prover_binary("vampire"))),
+            ("twee", binary_hash(&prover_binary("twee"))),
+        ];
+        let text = settings
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let hash = format!("{:x}", Sha256::digest(text.as_bytes()));
+        format!("{} id={}", text, &hash[..8])
+    }
+}
+
+/// What candidate selection does with root lemmas containing Skolem constants (`--skolems`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkolemPolicy {
+    /// leave them out, the default
+    Skip,
+    /// turn the constants into universally quantified variables, if the
+    /// generalized lemma still follows from the axioms
+    Generalize,
+    /// use them as they are, the constants standing for fresh constants
+    Keep,
+}
+
+impl SkolemPolicy {
+    pub fn parse(name: &str) -> Result<SkolemPolicy, String> {
+        match name {
+            "skip" => Ok(SkolemPolicy::Skip),
+            "generalize" => Ok(SkolemPolicy::Generalize),
+            "keep" => Ok(SkolemPolicy::Keep),
+            _ => Err(format!(
+                "Unknown Skolem policy '{}', expected skip, generalize or keep",
+                name
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SkolemPolicy::Skip => "skip",
+            SkolemPolicy::Generalize => "generalize",
+            SkolemPolicy::Keep => "keep",
+        }
+    }
+}
+
+/// Where `minimize` takes the start of a candidate proof and the proof of its
+/// history lemma from (`--force-backend`)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// superposition steps of the Vampire proof
+    Vampire,
+    /// proofs found by the provers, Twee unless `--adaptive-provers`
+    Twee,
+}
+
+impl Backend {
+    pub fn parse(name: &str) -> Result<Backend, String> {
+        match name {
+            "vampire" => Ok(Backend::Vampire),
+            "twee" => Ok(Backend::Twee),
+            _ => Err(format!(
+                "Unknown backend '{}', expected vampire or twee",
+                name
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Vampire => "vampire",
+            Backend::Twee => "twee",
+        }
+    }
+}
+
+/// Hex SHA-256 of a prover binary, so runs with different prover builds can be told apart
+fn binary_hash(path: &Path) -> String {
+    match fs::read(path) {
+        Ok(bytes) => format!("{:x}", Sha256::digest(&bytes)),
+        Err(_) => "missing".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic_mode_fixes_the_seed_and_shows_in_the_fingerprint() {
+        let id = |fingerprint: &str| fingerprint.rsplit("id=").next().unwrap().to_string();
+
+        let deterministic_config = || RunConfig {
+            deterministic: true,
+            ..RunConfig::default()
+        };
+        let config = deterministic_config();
+        assert_eq!(config.vampire_options(), ["--random_seed", "0"]);
+        let deterministic = config.fingerprint();
+        assert!(deterministic.contains(" deterministic=true seed=0 "));
+        // the same setup gets the same id
+        assert_eq!(deterministic_config().fingerprint(), deterministic);
+
+        let config = RunConfig::default();
+        assert!(config.vampire_options().is_empty());
+        assert_ne!(id(&config.fingerprint()), id(&deterministic));
+    }
+}
@@ -1,38 +1,412 @@
+use crate::process::{ProcessRunner, ProcessSpec};
+use crate::provenance::{self, write_with_provenance};
+use crate::prover_wrapper::prover_binary;
+use crate::run_config::RunConfig;
 use std::fs;
 use std::path::Path;
-use std::process::Command;
+use std::time::Duration;
+
+/// Default time limit for the initial Vampire run
+pub const DEFAULT_TIME_LIMIT: Duration = Duration::from_secs(60);
+
+/// Strategies tried in portfolio mode, in order: (name, extra Vampire options)
+pub const PORTFOLIO: &[(&str, &[&str])] = &[
+    ("default", &[]),
+    ("avatar_off", &["--avatar", "off"]),
+    (
+        "discount",
+        &["--saturation_algorithm", "discount", "--avatar", "off"],
+    ),
+    ("lrs", &["--saturation_algorithm", "lrs", "--avatar", "off"]),
+    (
+        "otter",
+        &["--saturation_algorithm", "otter", "--avatar", "off"],
+    ),
+];
+
+/// How a Vampire run ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VampireOutcome {
+    /// refutation found, proof written to the output file
+    Proved,
+    /// Vampire gave up without a proof, with its SZS status
+    NoProof(String),
+    /// the time limit was exceeded
+    Timeout,
+    /// Vampire rejected the input
+    ParseError(String),
+}
 
 /// Run Vampire on a given input file and save its proof.
-pub fn run_vampire_only(input: &str, output: &str) {
+/// With `portfolio`, several strategies share the time limit.
+pub fn run_vampire_only(
+    runner: &dyn ProcessRunner,
+    config: &RunConfig,
+    input: &str,
+    output: &str,
+    time_limit: Duration,
+    portfolio: bool,
+) -> Result<(), String> {
     let input_path = Path::new(input);
     if !input_path.exists() {
-        eprintln!(
-            "[ERROR] Input file does not exist: {}",
+        return Err(format!(
+            "Input file does not exist: {}",
             input_path.display()
-        );
-        return;
+        ));
     }
 
     let output_path = Path::new(output);
     if let Some(parent) = output_path.parent() {
-        fs::create_dir_all(parent).expect("Failed to create output directory");
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create output directory: {}", e))?;
+    }
+
+    if portfolio {
+        run_vampire_portfolio(runner, config, input, output, time_limit)?;
+        println!("[INFO] Vampire proof saved to {}", output_path.display());
+        return Ok(());
+    }
+
+    println!(
+        "[INFO] Running Vampire with a {}s time limit...",
+        time_limit.as_secs()
+    );
+    match run_vampire(runner, config, input, output, time_limit, &[])? {
+        VampireOutcome::Proved => {
+            println!("[INFO] Vampire proof saved to {}", output_path.display());
+            Ok(())
+        }
+        VampireOutcome::NoProof(status) => Err(format!("Vampire found no proof ({})", status)),
+        VampireOutcome::Timeout => {
+            Err(format!("Vampire exceeded {} seconds", time_limit.as_secs()))
+        }
+        VampireOutcome::ParseError(msg) => {
+            Err(format!("Vampire could not parse {}: {}", input, msg))
+        }
+    }
+}
+
+/// Use a refutation produced elsewhere (CASC runs, a cluster, ...) instead of
+/// running Vampire: check that it contains a proof and copy it to `output`
+pub fn import_existing_proof(
+    config: &RunConfig,
+    proof_file: &str,
+    output: &str,
+) -> Result<(), String> {
+    let content = fs::read_to_string(proof_file)
+        .map_err(|e| format!("Failed to read existing proof {}: {}", proof_file, e))?;
+    match classify_output(&content) {
+        VampireOutcome::Proved => {}
+        other => {
+            return Err(format!(
+                "{} does not contain a Vampire refutation ({:?})",
+                proof_file, other
+            ))
+        }
+    }
+
+    if let Some(parent) = Path::new(output).parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create output directory: {}", e))?;
+    }
+    if Path::new(proof_file) != Path::new(output) {
+        write_with_provenance(config, output, &content, proof_file)
+            .map_err(|e| format!("Failed to write {}: {}", output, e))?;
+    }
+    println!("[INFO] Using existing Vampire proof {}", proof_file);
+    println!(
+        "[RESULT] SZS status: {}",
+        szs_status(&content).unwrap_or("Unsatisfiable")
+    );
+    Ok(())
+}
+
+/// Run the strategies of `PORTFOLIO` one after another, splitting the time limit
+/// evenly between them, and keep the first proof. The winning strategy is
+/// recorded as a comment at the top of the proof file.
+pub fn run_vampire_portfolio(
+    runner: &dyn ProcessRunner,
+    config: &RunConfig,
+    input: &str,
+    output: &str,
+    time_limit: Duration,
+) -> Result<(), String> {
+    let slice = time_limit / PORTFOLIO.len() as u32;
+    let mut failures = Vec::new();
+
+    for (name, options) in PORTFOLIO {
+        println!(
+            "[INFO] Running Vampire strategy '{}' for {}s...",
+            name,
+            slice.as_secs().max(1)
+        );
+        match run_vampire(runner, config, input, output, slice, options)? {
+            VampireOutcome::Proved => {
+                let proof = fs::read_to_string(output)
+                    .map_err(|e| format!("Failed to read {}: {}", output, e))?;
+                let header = format!("% Strategy: {} [{}]\n", name, options.join(" "));
+                write_with_provenance(config, output, &(header + provenance::strip(&proof)), input)
+                    .map_err(|e| format!("Failed to write {}: {}", output, e))?;
+                println!("[RESULT] Strategy '{}' found a proof", name);
+                return Ok(());
+            }
+            // a parse error will not go away with another strategy
+            VampireOutcome::ParseError(msg) => {
+                return Err(format!("Vampire could not parse {}: {}", input, msg))
+            }
+            VampireOutcome::NoProof(status) => failures.push(format!("{}: {}", name, status)),
+            VampireOutcome::Timeout => failures.push(format!("{}: Timeout", name)),
+        }
+    }
+
+    Err(format!(
+        "No strategy found a proof ({})",
+        failures.join(", ")
+    ))
+}
+
+/// Helper: actually runs the Vampire binary, writes the proof only if one was found
+pub fn run_vampire(
+    runner: &dyn ProcessRunner,
+    config: &RunConfig,
+    input_file: &str,
+    output_file: &str,
+    time_limit: Duration,
+    options: &[&str],
+) -> Result<VampireOutcome, String> {
+    let time_arg = time_limit.as_secs().max(1).to_string();
+
+    // give Vampire a moment past its own limit before killing it
+    let wall_clock = time_limit + Duration::from_secs(5);
+    let vampire_bin = prover_binary("vampire");
+    let spec = ProcessSpec::new(&vampire_bin.to_string_lossy(), wall_clock)
+        .args(&[
+            "--input_syntax",
+            "tptp",
+            "--proof",
+            "on",
+            "--time_limit",
+            &time_arg,
+        ])
+        .args(&config.vampire_options())
+        .args(options)
+        .args(&[input_file]);
+
+    let output = runner
+        .run(&spec)
+        .map_err(|e| format!("Failed to run Vampire: {}", e))?;
+    if output.timed_out {
+        println!("[RESULT] SZS status: Timeout");
+        return Ok(VampireOutcome::Timeout);
+    }
+
+    let outcome = classify_output(&format!("{}\n{}", output.stdout, output.stderr));
+    println!(
+        "[RESULT] SZS status: {}",
+        reported_status(&outcome, &output.stdout)
+    );
+    if outcome == VampireOutcome::Proved {
+        write_with_provenance(config, output_file, &output.stdout, input_file)
+            .map_err(|e| format!("Failed to write Vampire output: {}", e))?;
+        println!("Vampire proof written to {}", output_file);
+    }
+    Ok(outcome)
+}
+
+/// Status from the "% SZS status <Status> for <problem>" line
+fn szs_status(output: &str) -> Option<&str> {
+    output
+        .lines()
+        .find_map(|l| l.split("SZS status ").nth(1))
+        .and_then(|rest| rest.split_whitespace().next())
+}
+
+/// The SZS status of a Vampire run for the benchmark: the one Vampire printed,
+/// or the one its `outcome` stands for
+fn reported_status<'a>(outcome: &'a VampireOutcome, stdout: &'a str) -> &'a str {
+    match outcome {
+        VampireOutcome::Proved => szs_status(stdout).unwrap_or("Unsatisfiable"),
+        VampireOutcome::NoProof(status) => status,
+        VampireOutcome::Timeout => "Timeout",
+        VampireOutcome::ParseError(_) => "InputError",
     }
+}
 
-    println!("[INFO] Running Vampire...");
-    run_vampire(input_path.to_str().unwrap(), output_path.to_str().unwrap());
+/// Classify Vampire's output into an outcome
+fn classify_output(output: &str) -> VampireOutcome {
+    if let Some(line) = output
+        .lines()
+        .find(|l| l.contains("Parsing Error") || l.contains("User error"))
+    {
+        return VampireOutcome::ParseError(line.trim().to_string());
+    }
 
-    println!("[INFO] Vampire proof saved to {}", output_path.display());
+    match szs_status(output) {
+        Some("Theorem") | Some("Unsatisfiable") | Some("ContradictoryAxioms") => {
+            VampireOutcome::Proved
+        }
+        Some("Timeout") => VampireOutcome::Timeout,
+        Some(status) => VampireOutcome::NoProof(status.to_string()),
+        None if output.contains("Refutation found") => VampireOutcome::Proved,
+        None if output.contains("Time limit reached") => VampireOutcome::Timeout,
+        None => VampireOutcome::NoProof("Unknown".to_string()),
+    }
 }
 
-/// Helper: actually runs the Vampire binary
-pub fn run_vampire(input_file: &str, output_file: &str) {
-    let vampire_bin = Path::new("../bin/vampire");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{DryRunRunner, ProcessOutput};
+    use crate::test_support::ScratchDir;
+
+    fn replying(stdout: &'static str) -> DryRunRunner {
+        DryRunRunner::replying(move |_| ProcessOutput::success(stdout))
+    }
+
+    #[test]
+    fn outputs_are_classified_by_their_szs_status() {
+        assert_eq!(
+            classify_output("% Refutation found.\n% SZS status Unsatisfiable for problem\n"),
+            VampireOutcome::Proved
+        );
+        assert_eq!(
+            classify_output("% SZS status CounterSatisfiable for problem\n"),
+            VampireOutcome::NoProof("CounterSatisfiable".to_string())
+        );
+        assert_eq!(
+            classify_output("% SZS status Timeout for problem\n"),
+            VampireOutcome::Timeout
+        );
+        // older Vampire versions print no status line
+        assert_eq!(
+            classify_output("% Time limit reached!\n"),
+            VampireOutcome::Timeout
+        );
+        assert_eq!(
+            classify_output("User error: Parsing Error on line 1\n"),
+            VampireOutcome::ParseError("User error: Parsing Error on line 1".to_string())
+        );
+        assert_eq!(
+            classify_output(""),
+            VampireOutcome::NoProof("Unknown".to_string())
+        );
+    }
+
+    #[test]
+    fn portfolio_strategies_are_distinct_and_start_with_the_default() {
+        assert_eq!(PORTFOLIO[0], ("default", &[][..]));
+        let mut names: Vec<&str> = PORTFOLIO.iter().map(|(name, _)| *name).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), PORTFOLIO.len());
+        for (name, options) in PORTFOLIO {
+            // Vampire options come as a flag and its value
+            let paired = options
+                .chunks(2)
+                .all(|o| o.len() == 2 && o[0].starts_with("--"));
+            assert!(paired, "{}", name);
+        }
+    }
+
+    #[test]
+    fn existing_proofs_are_imported_only_if_they_are_refutations() {
+        let dir = ScratchDir::new("existing_proof");
+        let config = RunConfig::default();
+        let output = dir.path().join("output").join("proof.out");
+        let output = output.to_string_lossy().to_string();
+
+        let refutation = "% Refutation found.\n% SZS status Theorem for problem\n";
+        let proof = dir.write("casc.out", refutation);
+        assert_eq!(import_existing_proof(&config, &proof, &output), Ok(()));
+        let imported = fs::read_to_string(&output).unwrap();
+        assert_eq!(provenance::strip(&imported), refutation);
+
+        let gave_up = dir.write("gave_up.out", "% SZS status GaveUp for problem\n");
+        let err = import_existing_proof(&config, &gave_up, &output).unwrap_err();
+        assert!(err.contains("does not contain a Vampire refutation"));
+        let missing = dir.path().join("missing.out").to_string_lossy().to_string();
+        assert!(import_existing_proof(&config, &missing, &output).is_err());
+    }
+
+    #[test]
+    fn runs_are_classified_and_only_proofs_are_written() {
+        let dir = ScratchDir::new("run_vampire");
+        let config = RunConfig::default();
+        let input = dir.write("problem.p", "fof(c, conjecture, a = a).");
+        let output = dir.path().join("proof.out").to_string_lossy().to_string();
+        let limit = Duration::from_secs(7);
+
+        let runner = replying("% SZS status CounterSatisfiable for problem\n");
+        assert_eq!(
+            run_vampire(&runner, &config, &input, &output, limit, &[]),
+            Ok(VampireOutcome::NoProof("CounterSatisfiable".to_string()))
+        );
+        assert!(!Path::new(&output).exists());
+        // Vampire gets the limit, the process a moment longer
+        let call = &runner.calls()[0];
+        assert!(call.args.windows(2).any(|w| w == ["--time_limit", "7"]));
+        assert_eq!(call.timeout, Duration::from_secs(12));
+
+        let runner = DryRunRunner::replying(|_| ProcessOutput {
+            timed_out: true,
+            ..ProcessOutput::default()
+        });
+        assert_eq!(
+            run_vampire(&runner, &config, &input, &output, limit, &[]),
+            Ok(VampireOutcome::Timeout)
+        );
+        let err = run_vampire_only(&runner, &config, &input, &output, limit, false).unwrap_err();
+        assert!(err.contains("exceeded 7 seconds"));
+
+        let runner = replying("% Refutation found.\n% SZS status Unsatisfiable for problem\n");
+        assert_eq!(
+            run_vampire_only(&runner, &config, &input, &output, limit, false),
+            Ok(())
+        );
+        assert!(fs::read_to_string(&output)
+            .unwrap()
+            .contains("% Refutation found."));
+        let missing = dir.path().join("missing.p").to_string_lossy().to_string();
+        assert!(run_vampire_only(&runner, &config, &missing, &output, limit, false).is_err());
+    }
 
-    let output = Command::new(vampire_bin)
-        .arg(input_file)
-        .output()
-        .expect("Failed to run Vampire");
+    #[test]
+    fn the_portfolio_keeps_the_first_proof_and_stops_on_parse_errors() {
+        let dir = ScratchDir::new("portfolio");
+        let config = RunConfig::default();
+        let input = dir.write("problem.p", "fof(c, conjecture, a = a).");
+        let output = dir.path().join("proof.out").to_string_lossy().to_string();
+        let limit = Duration::from_secs(10);
 
-    fs::write(output_file, &output.stdout).expect("Failed to write Vampire output");
-    println!("Vampire proof written to {}", output_file);
+        // only the second strategy finds a proof
+        let runner = DryRunRunner::replying(|spec| {
+            if spec.args.windows(2).any(|w| w == ["--avatar", "off"])
+                && !spec.args.contains(&"--saturation_algorithm".to_string())
+            {
+                ProcessOutput::success("% SZS status Unsatisfiable for problem\n")
+            } else {
+                ProcessOutput::success("% SZS status GaveUp for problem\n")
+            }
+        });
+        assert_eq!(
+            run_vampire_portfolio(&runner, &config, &input, &output, limit),
+            Ok(())
+        );
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 2);
+        // the strategies share the time limit
+        assert!(calls[0].args.windows(2).any(|w| w == ["--time_limit", "2"]));
+        let proof = fs::read_to_string(&output).unwrap();
+        assert!(provenance::strip(&proof).starts_with("% Strategy: avatar_off [--avatar off]\n"));
+
+        let runner = replying("User error: Parsing Error on line 1\n");
+        let err = run_vampire_portfolio(&runner, &config, &input, &output, limit).unwrap_err();
+        assert!(err.contains("could not parse"));
+        assert_eq!(runner.calls().len(), 1);
+
+        let runner = replying("% SZS status GaveUp for problem\n");
+        let err = run_vampire_portfolio(&runner, &config, &input, &output, limit).unwrap_err();
+        assert!(err.contains("default: GaveUp") && err.contains("otter: GaveUp"));
+        assert_eq!(runner.calls().len(), PORTFOLIO.len());
+    }
 }
@@ -0,0 +1,165 @@
+use crate::formula::{Formula, Term};
+use crate::proof_step::{detect_prover, format_breakdown, proof_steps, rule_breakdown};
+use crate::proof_step::{ProofStep, RuleKind};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// What a proof is made of beyond its length, for any prover: the rules of its
+// inferences, how deep its inferences nest, the symbols it talks about and
+// the size of its largest clause.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofStats {
+    /// prover the proof was read as, e.g. "twee"
+    pub prover: String,
+    /// core inferences, as counted in the proof length
+    pub steps: usize,
+    pub rules: BTreeMap<RuleKind, usize>,
+    /// most inferences on one path from an input to a step
+    pub depth: usize,
+    /// distinct function symbols, constants and predicates, not counting
+    /// TPTP's own such as `$false`
+    pub symbols: usize,
+    /// most literals in the formula of a step
+    pub max_clause_size: usize,
+}
+
+impl fmt::Display for ProofStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Prover: {}", self.prover)?;
+        writeln!(
+            f,
+            "Steps: {} ({})",
+            self.steps,
+            format_breakdown(&self.rules)
+        )?;
+        writeln!(f, "Depth: {}", self.depth)?;
+        writeln!(f, "Symbols: {}", self.symbols)?;
+        write!(f, "Max clause size: {}", self.max_clause_size)
+    }
+}
+
+/// Statistics of the proof in the file `path_or_text`, or of the proof text
+/// itself if it is no file. Without `prover` the prover is told by the format
+/// of the proof.
+pub fn proof_stats(path_or_text: &str, prover: Option<&str>) -> Result<ProofStats, String> {
+    let is_file = !path_or_text.contains('\n') && Path::new(path_or_text).is_file();
+    let proof = if is_file {
+        fs::read_to_string(path_or_text)
+            .map_err(|e| format!("Failed to read {}: {}", path_or_text, e))?
+    } else {
+        path_or_text.to_string()
+    };
+    let prover = prover.unwrap_or_else(|| detect_prover(&proof));
+    let steps = proof_steps(prover, &proof)?;
+    if steps.is_empty() {
+        return Err(format!("No {} proof steps found", prover));
+    }
+    Ok(step_stats(prover, &steps))
+}
+
+/// Statistics of the proof with `steps`, read as a proof by `prover`
+pub fn step_stats(prover: &str, steps: &[ProofStep]) -> ProofStats {
+    let symbols: BTreeSet<(String, usize)> = steps
+        .iter()
+        .flat_map(|s| s.formula.symbols())
+        .filter(|(name, _)| !name.starts_with('$'))
+        .collect();
+    ProofStats {
+        prover: prover.to_string(),
+        steps: steps.iter().filter(|s| s.is_inference()).count(),
+        rules: rule_breakdown(steps),
+        depth: depth(steps),
+        symbols: symbols.len(),
+        max_clause_size: steps
+            .iter()
+            .map(|s| literals(&s.formula))
+            .max()
+            .unwrap_or(0),
+    }
+}
+
+/// Most inferences on one path through the premises of `steps`. Premises
+/// that are no step of the proof start a path.
+fn depth(steps: &[ProofStep]) -> usize {
+    let by_name: HashMap<&str, &ProofStep> = steps.iter().map(|s| (s.name.as_str(), s)).collect();
+    let mut depths: HashMap<&str, usize> = HashMap::new();
+    steps
+        .iter()
+        .map(|step| step_depth(step, &by_name, &mut depths))
+        .max()
+        .unwrap_or(0)
+}
+
+fn step_depth<'a>(
+    step: &'a ProofStep,
+    by_name: &HashMap<&str, &'a ProofStep>,
+    depths: &mut HashMap<&'a str, usize>,
+) -> usize {
+    if let Some(&depth) = depths.get(step.name.as_str()) {
+        return depth;
+    }
+    // a step among its own premises would never finish
+    depths.insert(&step.name, 0);
+    let premises = step
+        .premises
+        .iter()
+        .filter_map(|p| by_name.get(p.as_str()).copied())
+        .map(|premise| step_depth(premise, by_name, depths))
+        .max()
+        .unwrap_or(0);
+    let depth = premises + usize::from(step.is_inference());
+    depths.insert(&step.name, depth);
+    depth
+}
+
+/// Literals of `formula` read as a clause: the disjuncts under its
+/// quantifiers, none for `$false`
+fn literals(formula: &Formula) -> usize {
+    match formula {
+        Formula::Forall(_, body) => literals(body),
+        Formula::Or(disjuncts) => disjuncts.iter().map(literals).sum(),
+        Formula::Atom(Term::Fun(name, args)) if name == "$false" && args.is_empty() => 0,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_of_a_vampire_proof() {
+        let proof = "\
+1. ! [X0] : mult(e,X0) = X0 [input]
+2. ! [X0] : mult(X0,e) = X0 [input]
+3. mult(a,b) != mult(b,a) [negated conjecture]
+4. a = b | mult(a,b) = e [input]
+5. mult(e,e) = e [superposition 1,2]
+6. e = mult(e,e) [forward demodulation 5,1]
+7. $false [trivial inequality removal 3,6]
+";
+        let stats = proof_stats(proof, Some("vampire")).unwrap();
+        assert_eq!(stats.prover, "vampire");
+        assert_eq!(stats.steps, 3);
+        assert_eq!(stats.rules[&RuleKind::Superposition], 1);
+        assert_eq!(stats.rules[&RuleKind::Demodulation], 1);
+        // 1 -> 5 -> 6 -> 7
+        assert_eq!(stats.depth, 3);
+        // mult, e, a, b
+        assert_eq!(stats.symbols, 4);
+        assert_eq!(stats.max_clause_size, 2);
+        // told by its format
+        assert_eq!(proof_stats(proof, None).unwrap(), stats);
+
+        let path = std::env::temp_dir().join(format!("proof_stats_{}.out", std::process::id()));
+        fs::write(&path, proof).unwrap();
+        assert_eq!(proof_stats(&path.to_string_lossy(), None).unwrap(), stats);
+        let _ = fs::remove_file(&path);
+
+        assert!(proof_stats("no proof here", Some("vampire")).is_err());
+        assert!(proof_stats(proof, Some("prover9")).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use crate::artifact::{self, write_atomic};
+use crate::run_config::RunConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+// The Phase 1 summary `summary_<suffix>.json`: for every lemma number, the
+// lemma problem, prover and output `collect` kept. Summaries written before
+// lemmas had a status are arrays `[file, prover, proof]` and read as proved.
+
+/// Whether the output kept for a lemma is a proof
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LemmaStatus {
+    #[default]
+    Proved,
+    /// no prover proved the lemma; the output is a saturation or an unknown result
+    NotProved,
+}
+
+/// How useful a lemma looks as the root of a minimized proof
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usefulness {
+    /// 1 for the lemma `minimize` tries first
+    pub rank: usize,
+    /// steps of the Vampire refutation that state the lemma
+    pub occurrences: usize,
+    /// steps of the refutation derived directly from those
+    pub children: usize,
+    /// symbols of the lemma
+    pub size: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SummaryEntry {
+    /// lemma problem, e.g. "single_lemma_0003"
+    pub file: String,
+    pub prover: String,
+    /// what the prover printed
+    pub proof: String,
+    #[serde(default)]
+    pub status: LemmaStatus,
+    /// missing for lemmas later phases do not build on, and in older summaries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usefulness: Option<Usefulness>,
+}
+
+impl SummaryEntry {
+    pub fn is_proved(&self) -> bool {
+        self.status == LemmaStatus::Proved
+    }
+
+    /// Whether later phases build on the lemma: if it is proved, or as a
+    /// hypothesis with `--assume-unproved`
+    pub fn is_usable(&self, config: &RunConfig) -> bool {
+        self.is_proved() || config.assume_unproved
+    }
+}
+
+pub type Summary = BTreeMap<u32, SummaryEntry>;
+
+pub fn load(summary_file: &str) -> Result<Summary, String> {
+    let text = fs::read_to_string(summary_file)
+        .map_err(|e| format!("Failed to read {}: {}", summary_file, e))?;
+    parse(&text).map_err(|e| format!("Failed to parse {}: {}", summary_file, e))
+}
+
+pub fn parse(text: &str) -> Result<Summary, String> {
+    artifact::parse_json(text)
+}
+
+pub fn write(summary_file: &str, summary: &Summary) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(summary)
+        .map_err(|e| format!("Failed to serialize the summary: {}", e))?;
+    write_atomic(summary_file, json).map_err(|e| format!("Failed to write {}: {}", summary_file, e))
+}
+
+/// Lemma numbers in the order their lemmas are tried as roots: ranked lemmas
+/// by rank, then the others from the highest number down
+pub fn by_usefulness(summary: &Summary) -> Vec<u32> {
+    let mut numbers: Vec<u32> = summary.keys().copied().collect();
+    numbers.sort_by_key(|n| {
+        let rank = summary[n].usefulness.map(|u| u.rank);
+        (rank.is_none(), rank, std::cmp::Reverse(*n))
+    });
+    numbers
+}
+
+/// The entries of proved lemmas
+pub fn proved(summary: &Summary) -> impl Iterator<Item = (&u32, &SummaryEntry)> {
+    summary.iter().filter(|(_, entry)| entry.is_proved())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_summaries_read_as_proved() {
+        let summary = parse(
+            r#"{"1": ["single_lemma_0001", "twee", "proof 1"],
+                "2": {"file": "history_lemma_0002", "prover": "vampire",
+                      "proof": "% SZS status CounterSatisfiable", "status": "NotProved"}}"#,
+        )
+        .unwrap();
+        assert_eq!(summary[&1].status, LemmaStatus::Proved);
+        assert_eq!(summary[&1].file, "single_lemma_0001");
+        assert_eq!(summary[&2].status, LemmaStatus::NotProved);
+        assert_eq!(
+            proved(&summary).map(|(n, _)| *n).collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert_eq!(
+            parse(&serde_json::to_string(&summary).unwrap()).unwrap(),
+            summary
+        );
+    }
+
+    #[test]
+    fn ranked_lemmas_come_first() {
+        let entry = |rank: Option<usize>| SummaryEntry {
+            file: String::new(),
+            prover: "twee".to_string(),
+            proof: String::new(),
+            status: LemmaStatus::Proved,
+            usefulness: rank.map(|rank| Usefulness {
+                rank,
+                occurrences: 1,
+                children: 1,
+                size: 3,
+            }),
+        };
+        let summary = Summary::from([
+            (1, entry(None)),
+            (2, entry(Some(2))),
+            (3, entry(None)),
+            (4, entry(Some(1))),
+        ]);
+        assert_eq!(by_usefulness(&summary), vec![4, 2, 3, 1]);
+    }
+}
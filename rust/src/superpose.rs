@@ -1,118 +1,121 @@
-use crate::alpha_match::formulas_match;
-use crate::dag::load_dag;
+use crate::alpha_match::{
+    is_renaming, match_with_substitution, normalize_for_matching, subsumes, Substitution,
+    SubsumptionIndex,
+};
+use crate::annotated_proof::{SUPERPOSITION_HEADER, SUPERPOSITION_RULE};
+use crate::formula::{format_fof, parse_formula};
+use crate::lemma_store::LemmaStore;
+use crate::naming::NameAllocator;
+use crate::provenance::write_with_provenance;
+use crate::prover_wrapper::{proves_quickly, RecordingRunner};
+use crate::run_config::RunConfig;
+use crate::tptp_file::TptpFile;
+use crate::twee_proof::{parse_twee_proof, Direction, TweeKind, TweeStatement};
 use crate::utils::*;
+use crate::vampire_proof::{
+    negated_conjecture_chain, parse_vampire_steps, read_vampire_steps, renumber_topologically,
+    StepRole, VampireStep,
+};
 use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
-
-/// Parse Vampire proof and extract superposition steps with dependencies
-#[derive(Debug, Clone)]
-pub struct SuperpositionStep {
-    pub formula: String,
-    /// (original Vampire number, sequential index)
-    pub deps: Vec<(usize, usize)>,
+use std::path::Path;
+
+/// Which Vampire steps `extract_superposition_steps` keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepFilter {
+    All,
+    /// Only unit equalities, the steps that can be used as axioms later on
+    EquationalOnly,
 }
 
-/// Parse Vampire proof and assign sequential indices starting from the first relevant inference step
-pub fn parse_vampire_proof(file_path: &str) -> Result<BTreeMap<usize, SuperpositionStep>, String> {
-    let content = fs::read_to_string(file_path).map_err(|e| e.to_string())?;
-    let mut steps = BTreeMap::new();
-    let mut seq_index: Option<usize> = None;
-    // map to look up seq_index from Vampire numbers
-    let mut vamp_to_seq: BTreeMap<usize, usize> = BTreeMap::new();
-
-    // keywords indicating relevant proof steps
-    let proof_keywords = ["demodulation", "superposition", "resolution", "inequality"];
-
-    for line in content.lines() {
-        let line_trimmed = line.trim();
-        if line_trimmed.is_empty() {
-            continue;
+impl StepFilter {
+    /// The filter `config` chose (`--equational-only`)
+    pub fn configured(config: &RunConfig) -> StepFilter {
+        if config.equational_only {
+            StepFilter::EquationalOnly
+        } else {
+            StepFilter::All
         }
+    }
+}
 
-        // extract Vampire number if present
-        let vamp_num: Option<usize> = line_trimmed
-            .split('.')
-            .next()
-            .and_then(|s| s.trim().parse::<usize>().ok());
-
-        // start indexing at first relevant step
-        if seq_index.is_none() {
-            if let Some(tag_part) = line_trimmed.split('[').nth(1) {
-                if proof_keywords.iter().any(|k| tag_part.contains(k)) {
-                    seq_index = Some(1);
-                } else {
-                    continue; // skip until first relevant step
-                }
-            } else {
-                continue;
-            }
-        }
+/// Vampire steps by sequential index, see `superposition_steps_from`
+pub type StepMap = BTreeMap<usize, VampireStep>;
+
+/// For the steps a lemma matched as a proper instance: (lemma name,
+/// instantiation of its variables), by sequential index
+pub type Instantiations = BTreeMap<usize, (String, Substitution)>;
+
+/// Index Vampire steps, as renumbered by `renumber_topologically` so that
+/// premises come first, sequentially from the first relevant inference step.
+/// Steps derived from the negated conjecture get `StepRole::NegatedConjecture`:
+/// they state the negation of a fact. Premises keep their Vampire numbers;
+/// those before the first step are the input clauses, a premise that is not a
+/// step of the proof at all is an error.
+pub fn superposition_steps_from(parsed: &[VampireStep]) -> Result<StepMap, String> {
+    // start indexing at first relevant step
+    let first = parsed
+        .iter()
+        .position(|s| s.is_inference())
+        .unwrap_or(parsed.len());
+
+    // premises may be printed after the step using them
+    let known: BTreeSet<usize> = parsed.iter().map(|s| s.index).collect();
+    let negated = negated_conjecture_chain(parsed);
 
-        let current_idx = seq_index.unwrap();
-        seq_index = Some(current_idx + 1);
-
-        // extract formula (everything before first '[')
-        let mut formula = line_trimmed
-            .split('[')
-            .next()
-            .unwrap_or("")
-            .trim()
-            .to_string();
-
-        // remove leading Vampire number + dot
-        if let Some(pos) = formula.find('.') {
-            if formula[..pos].trim().parse::<usize>().is_ok() {
-                formula = formula[pos + 1..].trim().to_string();
-            }
+    let mut steps = BTreeMap::new();
+    for (offset, step) in parsed[first..].iter().enumerate() {
+        if let Some(vnum) = step.premises.iter().find(|vnum| !known.contains(vnum)) {
+            return Err(format!(
+                "Step {} uses step {}, which is not in the proof",
+                step.index, vnum
+            ));
         }
-
-        // extract dependencies (numbers inside brackets)
-        let deps: Vec<(usize, usize)> = if let Some(tag_part) = line_trimmed.split('[').nth(1) {
-            tag_part
-                .trim_end_matches(']')
-                .split(|c| c == ',' || c == ' ')
-                .filter_map(|s| s.trim().parse::<usize>().ok())
-                .map(|vnum| {
-                    let seq = vamp_to_seq.get(&vnum).copied().unwrap_or(0);
-                    (vnum, seq)
-                })
-                .collect()
-        } else {
-            Vec::new()
-        };
-
-        // store the step
-        steps.insert(current_idx, SuperpositionStep { formula, deps });
-
-        // update lookup map for Vampire number
-        if let Some(vnum) = vamp_num {
-            vamp_to_seq.insert(vnum, current_idx);
+        let mut step = step.clone();
+        if negated.contains(&step.index) {
+            step.role = StepRole::NegatedConjecture;
         }
+        steps.insert(offset + 1, step);
     }
 
     Ok(steps)
 }
 
+/// Sequential index of each of `steps` by its Vampire number
+fn sequential_indices(steps: &StepMap) -> BTreeMap<usize, usize> {
+    steps
+        .iter()
+        .map(|(seq_idx, step)| (step.index, *seq_idx))
+        .collect()
+}
+
+/// Derived from the negated conjecture, see `superposition_steps_from`
+fn is_negated(step: &VampireStep) -> bool {
+    step.role == StepRole::NegatedConjecture
+}
+
 /// Extract nth history lemma and matching Vampire steps.
 ///
-/// This function takes a `dag`, a `vampire_file` (proof by Vampire),
-/// the directory containing lemmas, and a target lemma `n_history`.
+/// This function takes the `dag` of the root (each lemma to its children), a `vampire_file` (proof by Vampire),
+/// the lemmas of the problem, and a target lemma `n_history`.
 /// It returns:
 /// - a vector of dependency lemma names (from DAG)
-/// - a map of superposition steps from Vampire proof relevant to these dependencies.
+/// - a map of superposition steps from Vampire proof relevant to these dependencies,
+///   and how the lemmas matching them were instantiated
 ///
 /// If no relevant Vampire steps are found, it returns `None`.
 pub fn superposition_steps(
-    dag: &str,
+    config: &RunConfig,
+    dag: &BTreeMap<String, BTreeSet<String>>,
     vampire_file: &str,
-    lemmas_dir: &str,
+    lemmas: &LemmaStore,
     n_history: &str,
-) -> Option<(Vec<String>, BTreeMap<usize, SuperpositionStep>, bool)> {
-    // load the DAG from a file. This DAG maps each lemma to its children.
-    let dag = load_dag(&dag);
-
-    // parse Vampire proof into a map of step number -> SuperpositionStep
-    let steps_map = match parse_vampire_proof(vampire_file) {
+) -> Option<(Vec<String>, StepMap, Instantiations, bool)> {
+    // parse Vampire proof into a map of sequential index -> step
+    let steps_map = match read_vampire_steps(vampire_file)
+        .and_then(|s| renumber_topologically(&s))
+        .and_then(|s| superposition_steps_from(&s))
+    {
         Ok(m) => m,
         Err(err) => {
             eprintln!(
@@ -124,7 +127,8 @@ pub fn superposition_steps(
     };
 
     // store all Vampire steps that are relevant to the dependencies of `n_history`
-    let mut relevant_steps: BTreeMap<usize, SuperpositionStep> = BTreeMap::new();
+    let mut relevant_steps: StepMap = BTreeMap::new();
+    let mut instantiations = Instantiations::new();
     let mut proved_history = false;
     // TODO we might can do this a bit more elegantly but it works now:)
     let mut force_super = false;
@@ -189,13 +193,16 @@ pub fn superposition_steps(
         vec![n_history.to_string()]
     };
 
+    // index the Vampire steps by formula for fast lookup of matching steps
+    let step_index = step_formula_index(&steps_map);
+
     // flag to check if any Vampire steps match the dependencies
     let mut matched_any = false;
 
     // match dependencies to Vampire proof steps
     for dep in &deps {
         // load the formula of the dependency lemma
-        let dep_formula = match load_lemma(lemmas_dir, dep) {
+        let dep_formula = match lemmas.formula(dep) {
             Ok(f) => f,
             Err(err) => {
                 eprintln!("     [WARN] Cannot load {}: {}. Skipping.", dep, err);
@@ -203,28 +210,17 @@ pub fn superposition_steps(
             }
         };
 
-        // loop over all Vampire proof steps
-        for (step_num, step) in &steps_map {
-            let wrapped = format!("({})", step.formula);
-
-            // check if the dependency formula matches this step's formula
-            if formulas_match(&dep_formula, &wrapped) {
-                matched_any = true;
-
-                // recursively gather all dependencies of this Vampire step
-                let mut all_deps: BTreeSet<usize> = BTreeSet::new();
-                gather_all_dependencies(*step_num, &steps_map, &mut all_deps);
-
-                // collect the actual steps into the relevant steps map
-                for idx in &all_deps {
-                    if let Some(s) = steps_map.get(idx) {
-                        relevant_steps.insert(*idx, s.clone());
-                    }
-                }
-
-                // break the loop once a match is found for this dependency
-                break;
-            }
+        // collect the Vampire steps deriving the dependency formula
+        if let Some((step_num, chain, instantiation)) = extract_superposition_steps(
+            &steps_map,
+            &step_index,
+            &dep_formula,
+            dep,
+            StepFilter::configured(config),
+        ) {
+            matched_any = true;
+            relevant_steps.extend(shrink_chain(config, &chain, step_num, vampire_file));
+            instantiations.extend(instantiation);
         }
     }
 
@@ -235,88 +231,692 @@ pub fn superposition_steps(
             // we have no other dependencies
             deps = Vec::new();
         }
-        Some((deps, relevant_steps, proved_history))
+        Some((deps, relevant_steps, instantiations, proved_history))
     } else {
         None // no matching Vampire steps found
     }
 }
 
-/// Append all relevant superposition steps to a temporary file
+/// Index of the steps by normalized formula, for `extract_superposition_steps`
+pub fn step_formula_index(steps_map: &StepMap) -> SubsumptionIndex<usize> {
+    let mut step_index = SubsumptionIndex::new();
+    for (step_num, step) in steps_map {
+        step_index.insert(
+            &normalize_for_matching(&step.formula, is_negated(step)),
+            *step_num,
+        );
+    }
+    step_index
+}
+
+/// The first step deriving `formula` (up to variable renaming), or else the
+/// first deriving an instance of it, together with every step it depends on.
+/// Also returns how the variables of `lemma` were instantiated to obtain the
+/// matched step, if they were. With `StepFilter::EquationalOnly`
+/// the chain only goes through unit equalities: there is none if the matched
+/// step or any step it depends on is not one.
+pub fn extract_superposition_steps(
+    steps_map: &StepMap,
+    step_index: &SubsumptionIndex<usize>,
+    formula: &str,
+    lemma: &str,
+    filter: StepFilter,
+) -> Option<(usize, StepMap, Instantiations)> {
+    let formula = normalize_for_matching(formula, false);
+    let instances = step_index.instances(&formula);
+    let step_num = instances
+        .iter()
+        .copied()
+        .filter(|idx| {
+            let step = &steps_map[idx];
+            subsumes(
+                &normalize_for_matching(&step.formula, is_negated(step)),
+                &formula,
+            )
+        })
+        .min()
+        .or_else(|| instances.iter().copied().min())
+        .copied()?;
+    if filter == StepFilter::EquationalOnly && !is_unit_equality_step(&steps_map[&step_num]) {
+        println!(
+            "     [WARN] {} matches Vampire step {}, which is not a unit equality — not reachable through equational steps",
+            lemma, step_num
+        );
+        return None;
+    }
+
+    // recursively gather all dependencies of this Vampire step
+    let mut all_deps: BTreeSet<usize> = BTreeSet::new();
+    gather_all_dependencies(step_num, steps_map, &mut all_deps);
+    let chain: StepMap = all_deps
+        .iter()
+        .filter_map(|idx| steps_map.get(idx).map(|s| (*idx, s.clone())))
+        .collect();
+    // leaving a clause out would leave the steps derived from it without a premise
+    if filter == StepFilter::EquationalOnly {
+        if let Some(idx) = chain
+            .iter()
+            .find(|(_, step)| !is_unit_equality_step(step))
+            .map(|(idx, _)| *idx)
+        {
+            println!(
+                "     [WARN] {} matches Vampire step {}, which depends on step {}, not a unit equality — not reachable through equational steps",
+                lemma, step_num, idx
+            );
+            return None;
+        }
+    }
+
+    // record how the lemma variables were instantiated to obtain this step,
+    // unless the step is the lemma itself with its variables renamed
+    let step = &steps_map[&step_num];
+    let normalized = normalize_for_matching(&step.formula, is_negated(step));
+    let mut instantiations = Instantiations::new();
+    if let Some(subst) =
+        match_with_substitution(&formula, &normalized).filter(|subst| !is_renaming(subst))
+    {
+        println!(
+            "     [INFO] {} matches Vampire step {} with instantiation {:?}",
+            lemma, step_num, subst
+        );
+        instantiations.insert(step_num, (lemma.to_string(), subst));
+    }
+    Some((step_num, chain, instantiations))
+}
+
+fn is_unit_equality_step(step: &VampireStep) -> bool {
+    parse_formula(&step.formula).is_ok_and(|f| f.is_unit_equality())
+}
+
+/// Decides whether a step follows from the given formulas, see `minimize_chain`
+pub type StepCheck<'a> = &'a dyn Fn(&[&str], &str) -> bool;
+
+/// Greedily drop the steps of `chain` that `goal` does not need, latest first.
+/// A step no remaining step depends on goes right away. A step others depend
+/// on only goes if `check` re-derives each of them from the steps before it;
+/// they then take over its premises. Without `check` the pass is purely local.
+pub fn minimize_chain(chain: &StepMap, goal: usize, check: Option<StepCheck>) -> StepMap {
+    let mut chain = chain.clone();
+    let candidates: Vec<usize> = chain.keys().rev().copied().filter(|i| *i != goal).collect();
+    for idx in candidates {
+        let vnum = chain[&idx].index;
+        let dependents: Vec<usize> = chain
+            .iter()
+            .filter(|(_, step)| step.premises.contains(&vnum))
+            .map(|(i, _)| *i)
+            .collect();
+        if !dependents.is_empty() {
+            let Some(check) = check else { continue };
+            // negated steps are no facts to prove or to prove from
+            let rederived = dependents.iter().all(|d| {
+                let axioms: Vec<&str> = chain
+                    .range(..*d)
+                    .filter(|(i, step)| **i != idx && !is_negated(step))
+                    .map(|(_, step)| step.formula.as_str())
+                    .collect();
+                !is_negated(&chain[d]) && check(&axioms, &chain[d].formula)
+            });
+            if !rederived {
+                continue;
+            }
+        }
+
+        let removed = chain.remove(&idx).unwrap();
+        for d in dependents {
+            let step = chain.get_mut(&d).unwrap();
+            step.premises.retain(|premise| *premise != vnum);
+            for premise in &removed.premises {
+                if !step.premises.contains(premise) {
+                    step.premises.push(*premise);
+                }
+            }
+        }
+    }
+
+    // whatever the goal no longer reaches is not needed either
+    let mut reachable = BTreeSet::new();
+    gather_all_dependencies(goal, &chain, &mut reachable);
+    chain.retain(|idx, _| reachable.contains(idx));
+    chain
+}
+
+/// `minimize_chain` for a chain extracted from `vampire_file`, with Vampire
+/// re-deriving steps from the input clauses and earlier steps if `--recheck-steps` is on.
+/// The local pass always runs. The re-derivation is opt-in since it costs a
+/// Vampire call of up to `STEP_CHECK_TIME_LIMIT` for every dependent of every
+/// step tried, quadratic in the length of the chain, for each matched step,
+/// and its outcome depends on timing, so runs are no longer reproducible.
+fn shrink_chain(config: &RunConfig, chain: &StepMap, goal: usize, vampire_file: &str) -> StepMap {
+    let inputs = if config.recheck_steps {
+        match input_clauses(vampire_file) {
+            Ok(inputs) => Some(inputs),
+            Err(err) => {
+                eprintln!("     [WARN] Steps not rechecked: {}", err);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let check = |axioms: &[&str], conclusion: &str| {
+        let inputs = inputs.as_deref().unwrap_or_default();
+        let mut problem = String::new();
+        let premises = inputs
+            .iter()
+            .map(String::as_str)
+            .chain(axioms.iter().copied());
+        for (i, formula) in premises.enumerate() {
+            match format_fof(&format!("premise_{}", i), "axiom", formula) {
+                Ok(f) => problem.push_str(&f),
+                Err(_) => return false,
+            }
+        }
+        match format_fof("step", "conjecture", conclusion) {
+            Ok(f) => problem.push_str(&f),
+            Err(_) => return false,
+        }
+        proves_quickly(&RecordingRunner::local(config), config, &problem)
+    };
+
+    let shrunk = minimize_chain(chain, goal, inputs.as_ref().map(|_| &check as StepCheck));
+    if shrunk.len() < chain.len() {
+        println!(
+            "     [INFO] Step chain of step {} shrunk from {} to {} steps",
+            goal,
+            chain.len(),
+            shrunk.len()
+        );
+    }
+    shrunk
+}
+
+/// The input clauses of a Vampire proof, without the conjecture
+fn input_clauses(vampire_file: &str) -> Result<Vec<String>, String> {
+    let content = fs::read_to_string(vampire_file)
+        .map_err(|e| format!("Failed to read {}: {}", vampire_file, e))?;
+    let steps = parse_vampire_steps(&content);
+    let conjectures: BTreeSet<usize> = steps
+        .iter()
+        .filter(|s| s.role == StepRole::NegatedConjecture)
+        .flat_map(|s| s.premises.iter().copied())
+        .collect();
+    Ok(steps
+        .into_iter()
+        .filter(|s| s.role == StepRole::Input && !conjectures.contains(&s.index))
+        .map(|s| s.formula)
+        .collect())
+}
+
+/// `extract-steps <vampire_proof> <lemma>`: print the superposition steps
+/// deriving a lemma, given as a formula or as a TPTP file whose conjecture (or
+/// last formula) is the lemma, and optionally write them to `output` as axioms
+pub fn extract_steps(
+    config: &RunConfig,
+    vampire_file: &str,
+    lemma: &str,
+    output: Option<&str>,
+) -> Result<String, String> {
+    let (lemma_name, formula) = if Path::new(lemma).is_file() {
+        let content =
+            fs::read_to_string(lemma).map_err(|e| format!("Failed to read {}: {}", lemma, e))?;
+        let file = TptpFile::parse(&content)?;
+        let formula = file
+            .formulas()
+            .find(|f| f.is_conjecture())
+            .or_else(|| file.formulas().last())
+            .ok_or_else(|| format!("No formula in {}", lemma))?;
+        (formula.name.clone(), formula.body_line())
+    } else {
+        ("lemma".to_string(), lemma.to_string())
+    };
+
+    let steps = renumber_topologically(&read_vampire_steps(vampire_file)?)?;
+    let steps_map = superposition_steps_from(&steps)?;
+    let step_index = step_formula_index(&steps_map);
+    let (step_num, chain, instantiations) = extract_superposition_steps(
+        &steps_map,
+        &step_index,
+        &formula,
+        &lemma_name,
+        StepFilter::configured(config),
+    )
+    .ok_or_else(|| format!("No step of {} derives {}", vampire_file, formula))?;
+    let chain = shrink_chain(config, &chain, step_num, vampire_file);
+
+    let step_names = superposition_step_names(&chain, &mut NameAllocator::new());
+    print!(
+        "{}",
+        prepend_superposition_steps(&chain, &instantiations, &step_names)
+    );
+
+    if let Some(output) = output {
+        let mut axioms = String::new();
+        for (seq_idx, step) in &chain {
+            axioms.push_str(&format_fof(&step_names[seq_idx], "axiom", &step.formula)?);
+            axioms.push('\n');
+        }
+        write_with_provenance(config, output, &axioms, vampire_file)
+            .map_err(|e| format!("Failed to write {}: {}", output, e))?;
+    }
+    Ok(format!(
+        "{} derived by step {} from {} steps",
+        lemma_name,
+        step_num,
+        chain.len()
+    ))
+}
+
+/// Names for the superposition steps and the steps they depend on, in the
+/// problem given to Twee and in the annotated proof: `single_lemma_<index>`
+/// unless `names` already has that name in use
+pub fn superposition_step_names(
+    steps: &StepMap,
+    names: &mut NameAllocator,
+) -> BTreeMap<usize, String> {
+    let mut all_deps = BTreeSet::new();
+    for seq_idx in steps.keys() {
+        gather_all_dependencies(*seq_idx, steps, &mut all_deps);
+    }
+    all_deps
+        .into_iter()
+        .map(|idx| (idx, names.fresh(&format!("single_lemma_{:04}", idx))))
+        .collect()
+}
+
+/// Name of step `seq_idx`, index 0 standing for the input axiom
+fn step_name(step_names: &BTreeMap<usize, String>, seq_idx: usize) -> String {
+    if seq_idx == 0 {
+        return "a1".to_string();
+    }
+    step_names
+        .get(&seq_idx)
+        .cloned()
+        .unwrap_or_else(|| format!("single_lemma_{:04}", seq_idx))
+}
+
+/// Append all relevant superposition steps to an in-memory problem as axioms,
+/// under the names from `superposition_step_names`, and return those names.
+/// Steps derived from the negated conjecture are not facts and are left out.
 pub fn append_superposition_steps_as_lemmas(
-    tmp_file: &str,
-    steps: &BTreeMap<usize, SuperpositionStep>,
-    lemmas_dir: &str,
-) -> Result<(), String> {
-    for (seq_idx, _step) in steps {
-        let mut all_deps = BTreeSet::new();
+    problem: &mut String,
+    steps: &StepMap,
+    step_names: &BTreeMap<usize, String>,
+) -> Result<Vec<String>, String> {
+    // every step once, even if several steps depend on it
+    let mut all_deps = BTreeSet::new();
+    for seq_idx in steps.keys() {
         gather_all_dependencies(*seq_idx, steps, &mut all_deps);
+    }
 
-        for dep_idx in all_deps {
-            let lemma_name = format!("single_lemma_{:04}", dep_idx);
-            let formula = load_lemma(lemmas_dir, &lemma_name)?;
-            append_as_axiom(tmp_file, &formula, &lemma_name);
+    let mut names = Vec::new();
+    for dep_idx in all_deps {
+        let step = steps
+            .get(&dep_idx)
+            .ok_or_else(|| format!("Superposition step {} is missing", dep_idx))?;
+        if is_negated(step) {
+            continue;
         }
+        let name = step_name(step_names, dep_idx);
+        append_as_axiom(problem, &step.formula, &name)?;
+        names.push(name);
     }
-    Ok(())
+    Ok(names)
 }
 
-/// Recursively gather all sequential-indexed dependencies
+/// Gather the sequential indices of `n_history_step` and all steps it depends on
 pub fn gather_all_dependencies(
     n_history_step: usize,
-    steps_map: &BTreeMap<usize, SuperpositionStep>,
+    steps_map: &StepMap,
     collected: &mut BTreeSet<usize>,
 ) {
-    if collected.contains(&n_history_step) {
-        return;
-    }
-    collected.insert(n_history_step);
-
-    if let Some(step) = steps_map.get(&n_history_step) {
-        for (_vamp_num, seq_idx) in &step.deps {
-            if *seq_idx > 0 {
-                gather_all_dependencies(*seq_idx, steps_map, collected);
-            }
+    let seq_indices = sequential_indices(steps_map);
+    let mut pending = vec![n_history_step];
+    while let Some(seq_idx) = pending.pop() {
+        if !collected.insert(seq_idx) {
+            continue;
+        }
+        if let Some(step) = steps_map.get(&seq_idx) {
+            // input clauses are not in `steps_map`
+            pending.extend(
+                step.premises
+                    .iter()
+                    .filter_map(|vnum| seq_indices.get(vnum)),
+            );
         }
     }
 }
 
 /// Prepend superposition steps and dependency formulas to a proof
 pub fn prepend_superposition_steps(
-    superposition_steps: &BTreeMap<usize, SuperpositionStep>,
+    superposition_steps: &StepMap,
+    instantiations: &Instantiations,
+    step_names: &BTreeMap<usize, String>,
 ) -> String {
+    let seq_indices = sequential_indices(superposition_steps);
     let mut annotated_proof = String::new();
-    annotated_proof.push_str("% === Superposition Steps ===\n");
+    annotated_proof.push_str(SUPERPOSITION_HEADER);
+    annotated_proof.push('\n');
 
     for (seq_idx, step) in superposition_steps {
-        // handle the axiom
-        let lemma_name = if *seq_idx == 0 {
-            "a1".to_string()
-        } else {
-            format!("single_lemma_{:04}", seq_idx)
-        };
+        let lemma_name = step_name(step_names, *seq_idx);
 
         // format dependencies
         let dep_list: Vec<String> = step
-            .deps
+            .premises
             .iter()
-            .map(|(vnum, sidx)| {
-                let dep_name = if *sidx == 0 {
-                    "a1".to_string()
-                } else {
-                    format!("single_lemma_{:04}", sidx)
-                };
-                format!("{}->{}", dep_name, vnum)
+            .map(|vnum| {
+                let sidx = seq_indices.get(vnum).copied().unwrap_or(0);
+                format!("{}->{}", step_name(step_names, sidx), vnum)
             })
             .collect();
 
-        // write the step itself
+        // write the step itself and the rule that derived it
         annotated_proof.push_str(&format!(
             "% {}: {} | deps: {}\n",
             lemma_name,
             step.formula,
             dep_list.join(", ")
         ));
+        annotated_proof.push_str(&format!("{}{}\n", SUPERPOSITION_RULE, step.rule));
+
+        // write the instantiation if a lemma matched this step
+        if let Some((matched_lemma, subst)) = instantiations.get(seq_idx) {
+            let inst_list: Vec<String> = subst
+                .iter()
+                .map(|(var, term)| format!("{} := {}", var, term))
+                .collect();
+            annotated_proof.push_str(&format!(
+                "%   instance of {}: [{}]\n",
+                matched_lemma,
+                inst_list.join(", ")
+            ));
+        }
     }
     annotated_proof.push_str("\n");
     annotated_proof
 }
+
+/// The lemmas a Twee proof derived on its way, with names for them in a
+/// problem: `twee_lemma_<number>` unless `names` already has that name in use.
+/// Like superposition steps they are facts of the input problem and can be
+/// given to the next Twee run.
+pub fn twee_step_lemmas(
+    twee_proof: &str,
+    names: &mut NameAllocator,
+) -> Result<Vec<(String, TweeStatement)>, String> {
+    Ok(parse_twee_proof(twee_proof)?
+        .into_iter()
+        .filter(|statement| statement.kind == TweeKind::Lemma)
+        .map(|statement| {
+            let name = names.fresh(&format!("twee_lemma_{:02}", statement.number));
+            (name, statement)
+        })
+        .collect())
+}
+
+/// Prepend Twee step lemmas to a proof in the format of the superposition
+/// steps, the rewrites they came from as deps (`R->L` if used backwards)
+pub fn prepend_twee_steps(steps: &[(String, TweeStatement)]) -> String {
+    // Twee refers to its lemmas as lemma_<number>
+    let renamed: BTreeMap<String, &str> = steps
+        .iter()
+        .map(|(name, statement)| (statement.name.clone(), name.as_str()))
+        .collect();
+
+    let mut annotated_proof = String::from("% === Twee Steps ===\n");
+    for (name, statement) in steps {
+        let dep_list: Vec<String> = statement
+            .rewrites
+            .iter()
+            .map(|rewrite| {
+                let used = renamed.get(&rewrite.used).copied().unwrap_or(&rewrite.used);
+                match rewrite.direction {
+                    Direction::LeftToRight => used.to_string(),
+                    Direction::RightToLeft => format!("{} R->L", used),
+                }
+            })
+            .collect();
+        annotated_proof.push_str(&format!(
+            "% {}: {} | deps: {}\n",
+            name,
+            statement.formula,
+            dep_list.join(", ")
+        ));
+    }
+    annotated_proof.push('\n');
+    annotated_proof
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ScratchDir;
+
+    #[test]
+    fn premises_resolve_in_both_directions() {
+        let proof = "\
+1. mult(e,X0) = X0 [input]
+3. mult(X1,e) = X1 [superposition 1,4]
+4. mult(e,e) = e [superposition 1,1]
+";
+        let steps = superposition_steps_from(&parse_vampire_steps(proof)).unwrap();
+        assert_eq!(steps[&1].premises, vec![1, 4]);
+        let mut deps = BTreeSet::new();
+        gather_all_dependencies(1, &steps, &mut deps);
+        assert_eq!(deps, BTreeSet::from([1, 2]));
+    }
+
+    #[test]
+    fn unknown_premises_are_errors() {
+        let proof = "\
+1. mult(e,X0) = X0 [input]
+2. mult(X1,e) = X1 [superposition 1,7]
+";
+        let err = superposition_steps_from(&parse_vampire_steps(proof)).unwrap_err();
+        assert!(err.contains("step 7"));
+    }
+
+    #[test]
+    fn steps_are_extracted_for_a_formula_or_a_lemma_file() {
+        let dir = ScratchDir::new("extract_steps");
+        let config = RunConfig::default();
+        let proof = dir.write(
+            "vampire.out",
+            "\
+1. mult(e,X0) = X0 [input]
+2. mult(X0,e) = X0 [superposition 1,1]
+3. mult(e,e) = e [superposition 2,1]
+4. mult(mult(e,e),X0) = X0 [superposition 3,1]
+",
+        );
+        assert_eq!(
+            extract_steps(&config, &proof, "mult(e,e) = e", None),
+            Ok("lemma derived by step 2 from 2 steps".to_string())
+        );
+
+        let lemma = dir.write(
+            "lemma.p",
+            "fof(single_lemma_0001, conjecture, mult(e,e) = e).\n",
+        );
+        let axioms = dir.path().join("axioms.p").to_string_lossy().to_string();
+        let msg = extract_steps(&config, &proof, &lemma, Some(&axioms)).unwrap();
+        assert!(msg.starts_with("single_lemma_0001 derived by step 2"));
+        let written = fs::read_to_string(&axioms).unwrap();
+        assert_eq!(written.matches(", axiom,").count(), 2);
+
+        assert!(extract_steps(&config, &proof, "mult(a,b) = c", None).is_err());
+    }
+
+    #[test]
+    fn equational_chains_do_not_go_through_non_unit_steps() {
+        let proof = "\
+1. mult(e,X0) = X0 [input]
+2. p(X0) | mult(X0,e) = X0 [superposition 1,1]
+3. mult(X0,e) = X0 [resolution 2,1]
+4. mult(e,e) = e [superposition 3,1]
+";
+        let steps = superposition_steps_from(&parse_vampire_steps(proof)).unwrap();
+        let index = step_formula_index(&steps);
+        let extract = |filter| {
+            extract_superposition_steps(
+                &steps,
+                &index,
+                "mult(e,e) = e",
+                "single_lemma_0001",
+                filter,
+            )
+        };
+        // step 3 of the proof needs the clause of step 2
+        assert!(extract(StepFilter::EquationalOnly).is_none());
+        let (_, chain, _) = extract(StepFilter::All).unwrap();
+        assert_eq!(chain.keys().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn quantified_steps_match_lemmas() {
+        let proof = "\
+1. ! [X0] : mult(e,X0) = X0 [input]
+2. ! [X0] : e = mult(inv(X0),X0) [input]
+3. ! [X0,X1,X2] : mult(mult(X0,X1),X2) = mult(X0,mult(X1,X2)) [input]
+5. b != mult(inv(a),mult(a,b)) [negated conjecture 4]
+6. ! [X0,X1] : mult(e,X1) = mult(inv(X0),mult(X0,X1)) [superposition 3,2]
+7. ! [X0,X1] : mult(inv(X0),mult(X0,X1)) = X1 [forward demodulation 6,1]
+8. b != b [superposition 5,7]
+";
+        let steps = superposition_steps_from(&parse_vampire_steps(proof)).unwrap();
+        let index = step_formula_index(&steps);
+        let (step, chain, instantiations) = extract_superposition_steps(
+            &steps,
+            &index,
+            "! [X, Y] : (mult(inv(X), mult(X, Y)) = Y)",
+            "single_lemma_0002",
+            StepFilter::All,
+        )
+        .unwrap();
+        assert_eq!(step, 2);
+        assert_eq!(chain.keys().copied().collect::<Vec<_>>(), vec![1, 2]);
+        assert!(instantiations.is_empty());
+        assert!(is_negated(&steps[&3]) && !is_negated(&steps[&2]));
+    }
+
+    #[test]
+    fn instances_of_lemmas_record_their_instantiation() {
+        let proof = "\
+1. ! [X0] : mult(e,X0) = X0 [input]
+2. ! [X0] : e = mult(inv(X0),X0) [input]
+3. mult(inv(a),a) = e [superposition 2,1]
+4. mult(a,b) = mult(b,a) [superposition 2,1]
+5. ! [X0,X1] : mult(X0,X1) = mult(X1,X0) [superposition 2,1]
+";
+        let steps = superposition_steps_from(&parse_vampire_steps(proof)).unwrap();
+        let index = step_formula_index(&steps);
+        let extract = |formula| {
+            extract_superposition_steps(
+                &steps,
+                &index,
+                formula,
+                "single_lemma_0001",
+                StepFilter::All,
+            )
+        };
+
+        // only an instance of the lemma is derived
+        let (step, _, instantiations) = extract("! [X] : (mult(inv(X), X) = e)").unwrap();
+        assert_eq!(step, 1);
+        let (lemma, subst) = instantiations[&1].clone();
+        assert_eq!(lemma, "single_lemma_0001");
+        assert_eq!(subst["X"], "a");
+
+        // a step equal to the lemma comes before an earlier instance of it,
+        // and renaming its variables is no instantiation
+        let (step, _, instantiations) = extract("! [X, Y] : (mult(Y, X) = mult(X, Y))").unwrap();
+        assert_eq!(step, 3);
+        assert!(!instantiations.contains_key(&3));
+    }
+
+    #[test]
+    fn chain_drops_steps_the_check_rederives() {
+        let proof = "\
+1. mult(e,X0) = X0 [input]
+2. mult(X0,e) = X0 [superposition 1,1]
+3. mult(e,e) = e [superposition 2,1]
+4. mult(mult(e,e),X0) = X0 [superposition 3,1]
+";
+        let steps = superposition_steps_from(&parse_vampire_steps(proof)).unwrap();
+        // locally every step is needed
+        assert_eq!(minimize_chain(&steps, 3, None).len(), 3);
+
+        // mult(e,e) = e follows from the input alone, so step 1 of the chain goes
+        let check = |_: &[&str], conclusion: &str| conclusion == "mult(e,e) = e";
+        let chain = minimize_chain(&steps, 3, Some(&check));
+        assert_eq!(chain.keys().copied().collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(chain[&2].premises, vec![1]);
+    }
+
+    #[test]
+    fn written_steps_name_their_rule() {
+        let proof = "\
+1. mult(e,X0) = X0 [input]
+2. mult(X0,e) = X0 [superposition 1,1]
+3. mult(e,e) = e [forward demodulation 2,1]
+";
+        let steps = superposition_steps_from(&parse_vampire_steps(proof)).unwrap();
+        assert_eq!(steps[&1].rule, "superposition");
+        let step_names = superposition_step_names(&steps, &mut NameAllocator::new());
+        let text = prepend_superposition_steps(&steps, &Instantiations::new(), &step_names);
+        let rules: Vec<&str> = text
+            .lines()
+            .filter_map(|line| line.strip_prefix(SUPERPOSITION_RULE))
+            .collect();
+        assert_eq!(rules, vec!["superposition", "forward demodulation"]);
+    }
+
+    #[test]
+    fn steps_are_appended_with_their_own_formulas() {
+        let proof = "\
+2. ! [X0] : e = mult(inv(X0),X0) [input]
+3. ! [X0,X1,X2] : mult(mult(X0,X1),X2) = mult(X0,mult(X1,X2)) [input]
+5. b != mult(inv(a),mult(a,b)) [negated conjecture 4]
+6. ! [X0,X1] : mult(e,X1) = mult(inv(X0),mult(X0,X1)) [superposition 3,2]
+8. b != mult(e,b) [superposition 5,6]
+";
+        let steps = superposition_steps_from(&parse_vampire_steps(proof)).unwrap();
+        let step_names = superposition_step_names(&steps, &mut NameAllocator::new());
+        let mut problem = "fof(a1, axiom, mult(e, b) = b).".to_string();
+        let names =
+            append_superposition_steps_as_lemmas(&mut problem, &steps, &step_names).unwrap();
+
+        assert_eq!(names, vec!["single_lemma_0001"]);
+        assert!(problem.contains("fof(single_lemma_0001, axiom,"));
+        assert!(problem.contains("mult(inv(X0),mult(X0,X1))"));
+        assert!(!problem.contains("single_lemma_0002"));
+    }
+
+    #[test]
+    fn twee_lemmas_become_named_steps() {
+        let proof = "\
+Axiom 1 (a1): mult(e, X) = X.
+
+Lemma 3: mult(e, e) = e.
+Proof:
+  mult(e, e)
+= { by axiom 1 (a1) }
+  e
+
+Lemma 4: mult(X, e) = mult(e, X).
+Proof:
+  mult(X, e)
+= { by lemma 3 R->L }
+  mult(X, mult(e, e))
+";
+        let mut names = NameAllocator::new();
+        names.reserve(&["twee_lemma_03"]);
+        let steps = twee_step_lemmas(proof, &mut names).unwrap();
+        let step_names: Vec<_> = steps.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(step_names, vec!["twee_lemma_03_2", "twee_lemma_04"]);
+
+        let annotated = prepend_twee_steps(&steps);
+        assert!(annotated.contains("% twee_lemma_03_2: mult(e,e) = e | deps: a1\n"));
+        assert!(annotated.contains("| deps: twee_lemma_03_2 R->L\n"));
+    }
+}
@@ -0,0 +1,42 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Fixtures shared by the unit tests: a scratch directory for the files a
+// test hands to the code under test, usually together with a `DryRunRunner`
+// standing in for the provers.
+
+/// A fresh directory under the system temp directory, named after the test
+/// and this process, removed again when dropped
+pub struct ScratchDir {
+    path: PathBuf,
+}
+
+impl ScratchDir {
+    pub fn new(name: &str) -> ScratchDir {
+        let path =
+            std::env::temp_dir().join(format!("frankenstein-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        ScratchDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write `contents` to `relative`, creating its directory, and return its path
+    pub fn write<C: AsRef<[u8]>>(&self, relative: &str, contents: C) -> String {
+        let path = self.path.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().to_string()
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
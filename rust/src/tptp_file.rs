@@ -0,0 +1,340 @@
+use std::fmt;
+
+// A TPTP problem as a list of annotated formulas and the text between them
+// (comments, includes, blank lines), so formulas can be looked up, re-roled
+// and added without regexes over the raw text.
+
+/// `language(name, role, body[, annotations]).`, e.g. a `fof` axiom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnotatedFormula {
+    pub language: String,
+    pub name: String,
+    pub role: String,
+    pub body: String,
+    /// source and useful info, kept verbatim
+    pub annotations: Option<String>,
+}
+
+impl AnnotatedFormula {
+    pub fn new(language: &str, name: &str, role: &str, body: &str) -> AnnotatedFormula {
+        AnnotatedFormula {
+            language: language.to_string(),
+            name: name.to_string(),
+            role: role.to_string(),
+            body: body.trim().to_string(),
+            annotations: None,
+        }
+    }
+
+    /// The body on one line, as the rest of the pipeline expects formulas
+    pub fn body_line(&self) -> String {
+        self.body
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn is_conjecture(&self) -> bool {
+        self.role.contains("conjecture")
+    }
+}
+
+impl fmt::Display for AnnotatedFormula {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}({}, {},\n    {}",
+            self.language, self.name, self.role, self.body
+        )?;
+        if let Some(annotations) = &self.annotations {
+            write!(f, ",\n    {}", annotations)?;
+        }
+        write!(f, "\n).")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Item {
+    Formula(AnnotatedFormula),
+    /// comments, includes and whitespace, printed back unchanged
+    Text(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TptpFile {
+    items: Vec<Item>,
+}
+
+const LANGUAGES: [&str; 5] = ["fof", "cnf", "tff", "thf", "tcf"];
+
+impl TptpFile {
+    pub fn parse(text: &str) -> Result<TptpFile, String> {
+        let mut items = Vec::new();
+        let mut text_start = 0;
+        let mut pos = 0;
+        while pos < text.len() {
+            let rest = &text[pos..];
+            if rest.starts_with('%') {
+                pos += rest.find('\n').map_or(rest.len(), |i| i + 1);
+            } else if rest.starts_with("/*") {
+                let end = rest
+                    .find("*/")
+                    .ok_or_else(|| format!("Unterminated comment at byte {}", pos))?;
+                pos += end + 2;
+            } else if rest.starts_with(|c: char| c.is_ascii_lowercase()) {
+                let word_len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                let word = &rest[..word_len];
+                let open = rest.len() - rest[word_len..].trim_start().len();
+                if !rest[open..].starts_with('(') {
+                    return Err(format!("Expected '(' after '{}' at byte {}", word, pos));
+                }
+                let close = open
+                    + matching_paren(&rest[open..]).ok_or_else(|| {
+                        format!("Unbalanced parentheses in '{}' at byte {}", word, pos)
+                    })?;
+                let after = rest[close + 1..].trim_start();
+                if !after.starts_with('.') {
+                    return Err(format!("Missing '.' after '{}' at byte {}", word, pos));
+                }
+                let end = rest.len() - after.len() + 1;
+
+                if LANGUAGES.contains(&word) {
+                    if text_start < pos {
+                        items.push(Item::Text(text[text_start..pos].to_string()));
+                    }
+                    items.push(Item::Formula(parse_formula(word, &rest[open + 1..close])?));
+                    text_start = pos + end;
+                }
+                pos += end;
+            } else {
+                pos += rest.chars().next().map_or(1, char::len_utf8);
+            }
+        }
+        if text_start < text.len() {
+            items.push(Item::Text(text[text_start..].to_string()));
+        }
+        Ok(TptpFile { items })
+    }
+
+    pub fn formulas(&self) -> impl Iterator<Item = &AnnotatedFormula> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Formula(f) => Some(f),
+            Item::Text(_) => None,
+        })
+    }
+
+    pub fn formulas_mut(&mut self) -> impl Iterator<Item = &mut AnnotatedFormula> {
+        self.items.iter_mut().filter_map(|item| match item {
+            Item::Formula(f) => Some(f),
+            Item::Text(_) => None,
+        })
+    }
+
+    pub fn formula(&self, name: &str) -> Option<&AnnotatedFormula> {
+        self.formulas().find(|f| f.name == name)
+    }
+
+    /// Append `formula` at the end of the problem
+    pub fn push(&mut self, formula: AnnotatedFormula) {
+        self.items.push(Item::Text("\n\n".to_string()));
+        self.items.push(Item::Formula(formula));
+    }
+
+    /// Keep only the formulas for which `keep` holds
+    pub fn retain<F: Fn(&AnnotatedFormula) -> bool>(&mut self, keep: F) {
+        self.items.retain(|item| match item {
+            Item::Formula(f) => keep(f),
+            Item::Text(_) => true,
+        });
+    }
+
+    /// Give every formula named `name` with role `from` the role `to`.
+    /// Returns whether any formula changed.
+    pub fn change_role(&mut self, name: &str, from: &str, to: &str) -> bool {
+        let mut changed = false;
+        for item in &mut self.items {
+            if let Item::Formula(f) = item {
+                if f.name == name && f.role == from {
+                    f.role = to.to_string();
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+}
+
+impl fmt::Display for TptpFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for item in &self.items {
+            match item {
+                Item::Formula(formula) => write!(f, "{}", formula)?,
+                Item::Text(text) => write!(f, "{}", text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `name, role, body[, annotations]`, the inside of `language(...)`
+fn parse_formula(language: &str, inner: &str) -> Result<AnnotatedFormula, String> {
+    let parts = split_top_level_commas(inner);
+    if parts.len() < 3 {
+        return Err(format!(
+            "Expected name, role and formula in {}({})",
+            language,
+            inner.trim()
+        ));
+    }
+    let mut formula = AnnotatedFormula::new(language, parts[0].trim(), parts[1].trim(), &parts[2]);
+    if parts.len() > 3 {
+        formula.annotations = Some(parts[3..].join(",").trim().to_string());
+    }
+    Ok(formula)
+}
+
+/// Walk `s` outside of quoted names, strings and comments, calling `visit`
+/// with the byte offset and the bracket depth before each character.
+/// Stops early when `visit` returns false.
+fn scan<F: FnMut(usize, i32, char) -> bool>(s: &str, mut visit: F) {
+    let mut depth = 0;
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                // quoted until the matching unescaped quote
+                while let Some((_, q)) = chars.next() {
+                    if q == '\\' {
+                        chars.next();
+                    } else if q == c {
+                        break;
+                    }
+                }
+            }
+            '%' => {
+                for (_, q) in chars.by_ref() {
+                    if q == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek().map(|&(_, n)| n) == Some('*') => {
+                chars.next();
+                let mut last = ' ';
+                for (_, q) in chars.by_ref() {
+                    if last == '*' && q == '/' {
+                        break;
+                    }
+                    last = q;
+                }
+            }
+            _ => {
+                if !visit(i, depth, c) {
+                    return;
+                }
+                match c {
+                    '(' | '[' => depth += 1,
+                    ')' | ']' => depth -= 1,
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Offset of the parenthesis closing the one `s` starts with
+fn matching_paren(s: &str) -> Option<usize> {
+    let mut close = None;
+    scan(s, |i, depth, c| {
+        if c == ')' && depth == 1 {
+            close = Some(i);
+            return false;
+        }
+        true
+    });
+    close
+}
+
+fn split_top_level_commas(s: &str) -> Vec<String> {
+    let mut cuts = Vec::new();
+    scan(s, |i, depth, c| {
+        if c == ',' && depth == 0 {
+            cuts.push(i);
+        }
+        true
+    });
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for cut in cuts {
+        parts.push(s[start..cut].to_string());
+        start = cut + 1;
+    }
+    parts.push(s[start..].to_string());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROBLEM: &str = "\
+% group theory, with a comment mentioning fof(a, b, c).
+include('Axioms/GRP001-0.ax').
+fof(a1, axiom,
+    ! [X] :
+      (mult(e, X) = X)
+).
+/* block comment ). */
+fof(a2, axiom, p('a ). b'), [source('x, y')]).
+
+fof(goal, conjecture, mult(e, b) = b).
+";
+
+    #[test]
+    fn parses_formulas_around_comments_and_strings() {
+        let file = TptpFile::parse(PROBLEM).unwrap();
+        let names: Vec<_> = file.formulas().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["a1", "a2", "goal"]);
+
+        assert_eq!(
+            file.formula("a1").unwrap().body_line(),
+            "! [X] : (mult(e, X) = X)"
+        );
+        let a2 = file.formula("a2").unwrap();
+        assert_eq!(a2.body, "p('a ). b')");
+        assert_eq!(a2.annotations.as_deref(), Some("[source('x, y')]"));
+    }
+
+    #[test]
+    fn promote_and_print_keep_the_rest() {
+        let mut file = TptpFile::parse(PROBLEM).unwrap();
+        file.retain(|f| !f.is_conjecture());
+        assert!(file.change_role("a1", "axiom", "conjecture"));
+        file.push(AnnotatedFormula::new("fof", "l1", "axiom", "e = e"));
+
+        let printed = file.to_string();
+        assert!(printed.starts_with("% group theory"));
+        assert!(printed.contains("include('Axioms/GRP001-0.ax')."));
+        assert!(printed.contains("/* block comment ). */"));
+        assert!(!printed.contains("goal"));
+
+        let reparsed = TptpFile::parse(&printed).unwrap();
+        let roles: Vec<_> = reparsed
+            .formulas()
+            .map(|f| (f.name.as_str(), f.role.as_str()))
+            .collect();
+        assert_eq!(
+            roles,
+            vec![("a1", "conjecture"), ("a2", "axiom"), ("l1", "axiom")]
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_formulas() {
+        assert!(TptpFile::parse("fof(a, axiom, p(a).").is_err());
+    }
+}
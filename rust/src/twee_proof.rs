@@ -0,0 +1,381 @@
+use crate::artifact::write_atomic;
+use crate::formula::{parse_formula, Formula};
+use egg_sc_tptp::{
+    chain_to_steps, check_proof, fol, parse_tptp_formula, parse_tptp_problem, proof_to_tptp,
+    solution_header, ChainRewrite, Goal,
+};
+use std::fs;
+use std::path::Path;
+
+// Twee proofs list the axioms they use, then every lemma and goal with a
+// rewrite chain from its left-hand side to its right-hand side:
+//
+//   Lemma 3: mult(e, e) = e.
+//   Proof:
+//     mult(e, e)
+//   = { by axiom 1 (a1) R->L }
+//     e
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TweeKind {
+    Axiom,
+    Lemma,
+    Goal,
+}
+
+/// Which way a rewrite applied its equation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    LeftToRight,
+    /// marked `R->L` by Twee
+    RightToLeft,
+}
+
+/// One `= { by ... }` step: `equation` is the term before it equal to the term after it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TweeRewrite {
+    pub equation: Formula,
+    /// name of the axiom or lemma used, see `TweeStatement::name`
+    pub used: String,
+    pub direction: Direction,
+}
+
+/// An axiom, lemma or goal of a Twee proof with the rewrites proving it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TweeStatement {
+    pub kind: TweeKind,
+    /// Twee's number, counted per kind
+    pub number: usize,
+    /// the name from the problem (`a1`), otherwise `lemma_3`, `goal_1`, ...
+    pub name: String,
+    pub formula: Formula,
+    pub rewrites: Vec<TweeRewrite>,
+}
+
+/// Parse the axioms, lemmas and goals of a Twee proof in the order they appear.
+/// Anything else Twee prints, such as SZS lines and the RESULT line, is skipped.
+pub fn parse_twee_proof(proof: &str) -> Result<Vec<TweeStatement>, String> {
+    let mut reader = TweeReader::default();
+    for line in proof.lines() {
+        let trimmed = line.trim();
+        if let Some(header) = reader.header.as_mut() {
+            // a long statement is wrapped over several lines
+            header.push(' ');
+            header.push_str(trimmed);
+            if trimmed.ends_with('.') {
+                let header = reader.header.take().unwrap_or_default();
+                reader.start_statement(&header)?;
+            }
+        } else if statement_header(trimmed).is_some() {
+            reader.finish_term()?;
+            if trimmed.ends_with('.') {
+                reader.start_statement(trimmed)?;
+            } else {
+                reader.header = Some(trimmed.to_string());
+            }
+        } else if let Some(by) = trimmed
+            .strip_prefix("= { by")
+            .and_then(|rest| rest.strip_suffix('}'))
+        {
+            reader.finish_term()?;
+            reader.rule = Some(rewrite_rule(by));
+        } else if trimmed == "Proof:" || trimmed.is_empty() {
+            // a proof runs from "Proof:" to the next blank line
+            reader.finish_term()?;
+            reader.in_proof = !trimmed.is_empty();
+        } else if reader.in_proof && !reader.statements.is_empty() {
+            // long terms are wrapped over several indented lines too
+            if !reader.term.is_empty() {
+                reader.term.push(' ');
+            }
+            reader.term.push_str(trimmed);
+        }
+    }
+    reader.finish_term()?;
+    Ok(reader.statements)
+}
+
+#[derive(Default)]
+struct TweeReader {
+    statements: Vec<TweeStatement>,
+    /// start of a statement that continues on the next line
+    header: Option<String>,
+    in_proof: bool,
+    /// the term the proof of the last statement has reached
+    last_term: Option<String>,
+    /// text of the term being read
+    term: String,
+    /// what the rewrite into `term` used
+    rule: Option<(String, Direction)>,
+}
+
+impl TweeReader {
+    fn start_statement(&mut self, header: &str) -> Result<(), String> {
+        let (kind, number, label, formula) = statement_header(header)
+            .ok_or_else(|| format!("Cannot read Twee line '{}'", header))?;
+        let formula = parse_formula(formula.strip_suffix('.').unwrap_or(formula))
+            .map_err(|e| format!("Cannot parse {:?} {}: {}", kind, number, e))?;
+        self.statements.push(TweeStatement {
+            kind,
+            number,
+            name: label.unwrap_or_else(|| statement_name(kind, number)),
+            formula,
+            rewrites: Vec::new(),
+        });
+        self.last_term = None;
+        self.rule = None;
+        Ok(())
+    }
+
+    /// The term read so far is complete: the rewrite that led to it belongs to the last statement
+    fn finish_term(&mut self) -> Result<(), String> {
+        let term = std::mem::take(&mut self.term);
+        if term.is_empty() {
+            return Ok(());
+        }
+        if let (Some((used, direction)), Some(from), Some(statement)) = (
+            self.rule.take(),
+            self.last_term.as_ref(),
+            self.statements.last_mut(),
+        ) {
+            let equation = parse_formula(&format!("{} = {}", from, term)).map_err(|e| {
+                format!(
+                    "Cannot parse rewrite {} of {}: {}",
+                    statement.rewrites.len() + 1,
+                    statement.name,
+                    e
+                )
+            })?;
+            statement.rewrites.push(TweeRewrite {
+                equation,
+                used,
+                direction,
+            });
+        }
+        self.last_term = Some(term);
+        Ok(())
+    }
+}
+
+fn statement_name(kind: TweeKind, number: usize) -> String {
+    let kind = match kind {
+        TweeKind::Axiom => "axiom",
+        TweeKind::Lemma => "lemma",
+        TweeKind::Goal => "goal",
+    };
+    format!("{}_{}", kind, number)
+}
+
+/// `Lemma 2: f` or `Axiom 1 (a1): f` as (kind, number, name, formula)
+fn statement_header(line: &str) -> Option<(TweeKind, usize, Option<String>, &str)> {
+    let (head, formula) = line.split_once(": ")?;
+    let mut words = head.splitn(3, ' ');
+    let kind = match words.next()? {
+        "Axiom" => TweeKind::Axiom,
+        "Lemma" => TweeKind::Lemma,
+        "Goal" => TweeKind::Goal,
+        _ => return None,
+    };
+    let number = words.next()?.parse::<usize>().ok()?;
+    let label = words
+        .next()
+        .and_then(|rest| rest.strip_prefix('('))
+        .and_then(|rest| rest.strip_suffix(')'))
+        .map(str::to_string);
+    Some((kind, number, label, formula))
+}
+
+/// What a rewrite used: "axiom 1 (a1) R->L" is `a1` right to left, "lemma 3" is `lemma_3`
+fn rewrite_rule(by: &str) -> (String, Direction) {
+    let by = by.trim();
+    let (by, direction) = match by.strip_suffix("R->L") {
+        Some(rest) => (rest.trim(), Direction::RightToLeft),
+        None => (by, Direction::LeftToRight),
+    };
+    let used = match (by.find('('), by.rfind(')')) {
+        (Some(open), Some(close)) if open < close => by[open + 1..close].to_string(),
+        _ => by.split_whitespace().take(2).collect::<Vec<_>>().join("_"),
+    };
+    (used, direction)
+}
+
+/// SC-TPTP proofs of the lemmas and goals of a Twee proof of `problem`, as
+/// (statement name, proof) in proof order. Every rewrite becomes the
+/// rightSubst/leftForall/cut steps egg prints for its own explanations; a
+/// lemma is a rule of the proofs after it. Each proof is checked before it is
+/// returned.
+pub fn twee_to_sctptp(problem: &Path, proof: &str) -> Result<Vec<(String, String)>, String> {
+    let mut problem = parse_tptp_problem(&problem.to_path_buf())
+        .map_err(|e| format!("Cannot read {}: {}", problem.display(), e))?;
+    let mut proofs = Vec::new();
+    for statement in parse_twee_proof(proof)? {
+        if statement.kind == TweeKind::Axiom {
+            continue;
+        }
+        let formula = parse_tptp_formula(&statement.formula.to_string())
+            .map_err(|e| format!("Cannot read {}: {}", statement.name, e))?;
+        let (start, _) = equation_sides(&statement.formula)?;
+        let chain = statement
+            .rewrites
+            .iter()
+            .map(|rewrite| {
+                Ok(ChainRewrite {
+                    term: equation_sides(&rewrite.equation)?.1,
+                    rule: rewrite.used.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        // a goal keeps how the problem states it, e.g. as a negated conjecture
+        let negation = problem
+            .goals
+            .iter()
+            .find(|g| g.name == statement.name)
+            .and_then(|g| g.negation);
+        let goal = Goal {
+            name: statement.name.clone(),
+            formula: formula.clone(),
+            simplify: false,
+            negation,
+        };
+        let steps = chain_to_steps(&problem, &goal, &start, &chain, true)
+            .map_err(|e| format!("Cannot convert the proof of {}: {}", statement.name, e))?;
+        check_proof(&problem, &goal, &steps)
+            .map_err(|e| format!("Proof of {} does not check: {}", statement.name, e))?;
+        let header = solution_header(&problem.header, goal.szs_status()).to_string();
+        proofs.push((statement.name.clone(), proof_to_tptp(&header, &steps)));
+        if statement.kind == TweeKind::Lemma {
+            problem
+                .add_lemma(&statement.name, &formula)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(proofs)
+}
+
+/// Convert the Twee proof in `proof_file` into SC-TPTP proofs, one file
+/// `<statement>.p` per lemma and goal in `output_dir`
+pub fn write_sctptp_proofs(
+    problem_file: &str,
+    proof_file: &str,
+    output_dir: &str,
+) -> Result<String, String> {
+    let content = fs::read_to_string(proof_file)
+        .map_err(|e| format!("Failed to read {}: {}", proof_file, e))?;
+    let proofs = twee_to_sctptp(Path::new(problem_file), &content)?;
+    if proofs.is_empty() {
+        return Err(format!("No lemmas or goals found in {}", proof_file));
+    }
+    fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Failed to create {}: {}", output_dir, e))?;
+    for (name, proof) in &proofs {
+        let path = Path::new(output_dir).join(format!("{}.p", name));
+        write_atomic(&path, proof)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        println!(
+            "[INFO] Wrote SC-TPTP proof of {} to {}",
+            name,
+            path.display()
+        );
+    }
+    Ok(format!(
+        "[RESULT] {} Twee proof(s) converted to SC-TPTP in {}",
+        proofs.len(),
+        output_dir
+    ))
+}
+
+/// The two sides of an equation as egg-sc-tptp terms
+fn equation_sides(equation: &Formula) -> Result<(fol::Term, fol::Term), String> {
+    match parse_tptp_formula(&equation.to_string()).map_err(|e| e.to_string())? {
+        fol::Formula::Predicate(op, args) if op == "=" && args.len() == 2 => {
+            Ok((*args[0].clone(), *args[1].clone()))
+        }
+        formula => Err(format!("{} is not an equation", formula)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROOF: &str = "\
+% SZS status Theorem
+
+Axiom 1 (a1): mult(e, X) = X.
+Axiom 2 (a2): mult(X, e) = X.
+
+Lemma 3: e = mult(e, e).
+Proof:
+  e
+= { by axiom 1 (a1) R->L }
+  mult(e, e)
+
+Goal 1 (goal): mult(mult(e, e),
+  b) = b.
+Proof:
+  mult(mult(e, e),
+    b)
+= { by lemma 3 R->L }
+  mult(e, b)
+= { by axiom 1 (a1) }
+  b
+
+RESULT: Theorem (the conjecture is true).
+";
+
+    #[test]
+    fn reads_statements_with_their_rewrites() {
+        let statements = parse_twee_proof(PROOF).unwrap();
+        let names: Vec<_> = statements.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["a1", "a2", "lemma_3", "goal"]);
+        assert!(statements[1].rewrites.is_empty());
+
+        let lemma = &statements[2].rewrites;
+        assert_eq!(lemma.len(), 1);
+        assert_eq!(lemma[0].used, "a1");
+        assert_eq!(lemma[0].direction, Direction::RightToLeft);
+
+        let goal = &statements[3];
+        assert_eq!(goal.kind, TweeKind::Goal);
+        assert_eq!(goal.formula.to_string(), "mult(mult(e,e),b) = b");
+        let rewrites: Vec<_> = goal
+            .rewrites
+            .iter()
+            .map(|r| (r.equation.to_string(), r.used.as_str(), r.direction))
+            .collect();
+        assert_eq!(
+            rewrites,
+            vec![
+                (
+                    "mult(mult(e,e),b) = mult(e,b)".to_string(),
+                    "lemma_3",
+                    Direction::RightToLeft
+                ),
+                ("mult(e,b) = b".to_string(), "a1", Direction::LeftToRight),
+            ]
+        );
+    }
+
+    #[test]
+    fn converts_rewrite_chains_to_checked_sctptp() {
+        let problem =
+            std::env::temp_dir().join(format!("frankenstein-twee-{}.p", std::process::id()));
+        fs::write(
+            &problem,
+            "fof(a1, axiom, ! [X] : mult(e, X) = X).\nfof(a2, axiom, ! [X] : mult(X, e) = X).\nfof(goal, conjecture, mult(mult(e, e), b) = b).\n",
+        )
+        .unwrap();
+        let proofs = twee_to_sctptp(&problem, PROOF).unwrap();
+        let _ = fs::remove_file(&problem);
+
+        let names: Vec<_> = proofs.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["lemma_3", "goal"]);
+        assert!(proofs[0]
+            .1
+            .contains("inference(cut, [status(thm), 0], [a1, "));
+        // the goal rewrites with the lemma proved before it
+        assert!(proofs[1]
+            .1
+            .contains("inference(cut, [status(thm), 0], [lemma_3, "));
+    }
+}
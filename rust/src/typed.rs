@@ -0,0 +1,201 @@
+use crate::provenance::write_with_provenance;
+use crate::run_config::RunConfig;
+use crate::tptp_file::{AnnotatedFormula, TptpFile};
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+
+// Typed TPTP (TFF/TCF/THF) inputs. The OCaml tools and the lemma extraction
+// only read untyped first-order formulas, so a TFF problem that only uses the
+// individual type `$i` is translated to FOF by erasing the types (TCF clauses
+// to CNF), and anything else is rejected up front with the formulas that need
+// types.
+
+/// Builtins that mean the same in FOF
+const UNTYPED_BUILTINS: [&str; 2] = ["$true", "$false"];
+
+/// Why a typed formula cannot be translated
+fn unsupported(formula: &AnnotatedFormula) -> Option<String> {
+    let describe = |what: String| Some(format!("{} {}: {}", formula.language, formula.name, what));
+    if formula.language == "thf" {
+        return describe("higher-order formulas are not supported".to_string());
+    }
+    let builtin_re = Regex::new(r"\$\w+").unwrap();
+    if formula.role == "type" {
+        let Some((symbol, ty)) = formula.body.split_once(':') else {
+            return describe(format!(
+                "unreadable type declaration `{}`",
+                formula.body_line()
+            ));
+        };
+        if ty.contains("!>") {
+            return describe(format!("polymorphic type of {}", symbol.trim()));
+        }
+        let other = builtin_re
+            .find_iter(ty)
+            .map(|m| m.as_str())
+            .find(|t| *t != "$i" && *t != "$o");
+        let user_type = ty
+            .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+            .find(|t| !t.is_empty() && !t.starts_with('$'));
+        return match other.or(user_type) {
+            Some(t) => describe(format!("{} has type `{}`", symbol.trim(), t)),
+            None => None,
+        };
+    }
+    for binding in bound_variables(&formula.body) {
+        if let Some((var, ty)) = binding.split_once(':') {
+            if ty.trim() != "$i" {
+                return describe(format!("variable {} of type `{}`", var.trim(), ty.trim()));
+            }
+        }
+    }
+    // the variable types were checked above
+    let body = erase_types(&formula.body);
+    let builtin = builtin_re
+        .find_iter(&body)
+        .map(|m| m.as_str())
+        .find(|b| !UNTYPED_BUILTINS.contains(b));
+    builtin.and_then(|b| describe(format!("uses `{}`", b)))
+}
+
+/// `X: $i`, `Y`, ... for every variable bound by a quantifier in `body`
+fn bound_variables(body: &str) -> Vec<String> {
+    let quantifier_re = Regex::new(r"[!?]\s*\[([^\]]*)\]").unwrap();
+    quantifier_re
+        .captures_iter(body)
+        .flat_map(|c| {
+            c[1].split(',')
+                .map(|v| v.trim().to_string())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// The body without the types of its quantified variables
+fn erase_types(body: &str) -> String {
+    let typed_var_re = Regex::new(r"([A-Z]\w*)\s*:\s*\$i\b").unwrap();
+    typed_var_re.replace_all(body, "$1").to_string()
+}
+
+/// A TCF clause as CNF: without types and without the leading universal
+/// quantifier, since CNF variables are implicitly universal
+fn untyped_clause(body: &str) -> String {
+    let quantifier_re = Regex::new(r"^\s*!\s*\[[^\]]*\]\s*:\s*").unwrap();
+    quantifier_re
+        .replace(&erase_types(body), "")
+        .trim()
+        .to_string()
+}
+
+/// `file` as FOF (or CNF for TCF clauses) if it is typed; `None` if it has no typed formulas. Errors
+/// list every formula that cannot be translated.
+pub fn translate(file: &TptpFile) -> Result<Option<TptpFile>, String> {
+    let typed: Vec<&AnnotatedFormula> = file
+        .formulas()
+        .filter(|f| matches!(f.language.as_str(), "tff" | "thf" | "tcf"))
+        .collect();
+    if typed.is_empty() {
+        return Ok(None);
+    }
+    let problems: Vec<String> = typed.iter().filter_map(|f| unsupported(f)).collect();
+    if !problems.is_empty() {
+        return Err(format!(
+            "typed TPTP features that cannot be translated to FOF:\n  {}",
+            problems.join("\n  ")
+        ));
+    }
+
+    let mut untyped = file.clone();
+    untyped.retain(|f| f.role != "type");
+    for formula in untyped.formulas_mut() {
+        match formula.language.as_str() {
+            "tff" => {
+                formula.language = "fof".to_string();
+                formula.body = erase_types(&formula.body);
+            }
+            "tcf" => {
+                formula.language = "cnf".to_string();
+                formula.body = untyped_clause(&formula.body);
+            }
+            _ => {}
+        }
+    }
+    Ok(Some(untyped))
+}
+
+/// Check the problem a command is about to read. Untyped problems and
+/// unreadable files (reported by the command itself) give `None`; a typed
+/// problem is translated to FOF under `output_dir` with its file name, so its
+/// artifacts keep their names, and the path of the translation is returned.
+pub fn untyped_input(
+    config: &RunConfig,
+    input_file: &str,
+    output_dir: &str,
+) -> Result<Option<String>, String> {
+    let Ok(text) = fs::read_to_string(input_file) else {
+        return Ok(None);
+    };
+    let Ok(file) = TptpFile::parse(&text) else {
+        return Ok(None);
+    };
+    let Some(untyped) = translate(&file).map_err(|e| format!("{} uses {}", input_file, e))? else {
+        return Ok(None);
+    };
+
+    fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Failed to create {}: {}", output_dir, e))?;
+    let output_file = Path::new(output_dir)
+        .join(Path::new(input_file).file_name().unwrap())
+        .to_string_lossy()
+        .to_string();
+    write_with_provenance(config, &output_file, &untyped.to_string(), input_file)
+        .map_err(|e| format!("Failed to write {}: {}", output_file, e))?;
+    println!(
+        "[WARN] {} is a typed problem; using its FOF translation {}",
+        input_file, output_file
+    );
+    Ok(Some(output_file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monomorphic_problems_lose_their_types_and_others_are_rejected() {
+        let problem = "tff(mult_type, type, mult: ($i * $i) > $i).\n\
+            tff(e_type, type, e: $i).\n\
+            tff(a1, axiom, ! [X: $i] : mult(e, X) = X).\n\
+            tcf(a2, axiom, ! [X: $i, Y: $i] : (mult(X, Y) != e | mult(Y, X) = e)).\n\
+            tff(c, conjecture, ! [X : $i, Y: $i] : mult(X, Y) = mult(X, Y)).\n";
+        let file = TptpFile::parse(problem).unwrap();
+        let untyped = translate(&file).unwrap().unwrap();
+        assert_eq!(
+            untyped
+                .formulas()
+                .map(|f| f.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "fof(a1, axiom,\n    ! [X] : mult(e, X) = X\n).",
+                "cnf(a2, axiom,\n    (mult(X, Y) != e | mult(Y, X) = e)\n).",
+                "fof(c, conjecture,\n    ! [X, Y] : mult(X, Y) = mult(X, Y)\n).",
+            ]
+        );
+
+        let fof = TptpFile::parse("fof(a1, axiom, f(X) = X).\n").unwrap();
+        assert_eq!(translate(&fof).unwrap(), None);
+
+        let sorted = "tff(g_type, type, group: $tType).\n\
+            tff(a1, axiom, ! [N: $int] : $less(N, $sum(N, 1))).\n\
+            thf(c, conjecture, ! [F: $i > $i] : F = F).\n";
+        let err = translate(&TptpFile::parse(sorted).unwrap()).unwrap_err();
+        assert!(
+            err.contains("tff g_type: group has type `$tType`"),
+            "{}",
+            err
+        );
+        assert!(err.contains("tff a1: variable N of type `$int`"), "{}", err);
+        assert!(err.contains("thf c: higher-order"), "{}", err);
+    }
+}
@@ -1,14 +1,24 @@
-use crate::alpha_match::normalize_formula_alpha;
-use crate::prover_wrapper::proof_length;
+use crate::alpha_match::{normalize_formula_alpha, FormulaIndex, SubsumptionIndex};
+use crate::annotated_proof::hypothesis;
+use crate::formula::parse_formula;
+use crate::lemma_store::LemmaStore;
+use crate::provenance::is_version_copy;
+use crate::prover_wrapper::{is_non_theorem, proof_length};
+use crate::tptp_file::{AnnotatedFormula, TptpFile};
+use crate::twee_proof::{parse_twee_proof, TweeKind};
 use regex::Regex;
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 #[derive(Debug)]
 pub struct PrecomputedLemmas {
     pub all_lemmas: BTreeMap<String, LemmaInfo>,
     pub all_twee: Vec<TweeDependency>,
+    /// index into `all_twee` by formula
+    pub twee_index: FormulaIndex<usize>,
+    /// lemma -> a more general lemma it is a strict instance of, see `subsumed_lemmas`
+    pub subsumed_by: BTreeMap<String, String>,
     pub lemmas: BTreeMap<String, String>,
 }
 
@@ -36,20 +46,28 @@ pub struct TweeDependency {
 // or prove these children by superposition as already tried
 pub fn precompute_lemmas(
     proofs_dir: &str,
-    lemmas_dir: &str,
+    lemmas: &LemmaStore,
     twee_proofs_dir: &str,
 ) -> Result<PrecomputedLemmas, String> {
     let mut all_lemmas: BTreeMap<String, LemmaInfo> = BTreeMap::new();
     let mut existing_lemmas: BTreeMap<String, String> = BTreeMap::new();
-    let mut lemmas: BTreeMap<String, String> = BTreeMap::new();
+    let mut formulas: BTreeMap<String, String> = BTreeMap::new();
     let mut all_twee: Vec<TweeDependency> = Vec::new();
     let mut next_index = 2;
 
-    // precompute all lemmas
-    for entry in fs::read_dir(proofs_dir).map_err(|e| e.to_string())? {
-        let entry = entry.map_err(|e| e.to_string())?;
-        let path = entry.path();
-        if path.is_dir() {
+    // precompute all lemmas, in name order so twee lemmas are numbered the same on every run
+    let mut proof_paths = fs::read_dir(proofs_dir)
+        .map_err(|e| e.to_string())?
+        .map(|entry| entry.map(|e| e.path()).map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, String>>()?;
+    proof_paths.sort();
+    for path in proof_paths {
+        // `.raw` files hold the original bytes of non-UTF-8 prover output,
+        // `.orig`/`.vN` files the proofs `shorten` replaced
+        if path.is_dir()
+            || path.extension().is_some_and(|ext| ext == "raw")
+            || is_version_copy(&path)
+        {
             continue;
         }
 
@@ -67,13 +85,13 @@ pub fn precompute_lemmas(
         let proof_content = fs::read_to_string(&new_path).map_err(|e| e.to_string())?;
 
         // extract dependencies
-        let extracted = parse_used_lemmas(&proof_content, lemmas_dir, proofs_dir)?; // Vec<(name, formula)>
+        let extracted = parse_used_lemmas(&proof_content, lemmas, proofs_dir)?; // Vec<(name, formula)>
         let extracted_twee = extract_twee_lemmas(&proof_content); // Vec<(name, formula)>
 
         let mut dependencies: Vec<(String, String)> = Vec::new();
         for (dep_name, dep_formula) in extracted {
             dependencies.push((dep_name.clone(), dep_formula.clone()));
-            lemmas.insert(dep_name, dep_formula);
+            formulas.insert(dep_name, dep_formula);
         }
 
         // handle TWEE lemmas
@@ -100,11 +118,11 @@ pub fn precompute_lemmas(
                     parents: vec![lemma_name.clone()],
                 });
             }
-            lemmas.insert(canonical_name.clone(), twee_formula.clone());
+            formulas.insert(canonical_name.clone(), twee_formula.clone());
             dependencies.push((canonical_name, twee_formula));
         }
 
-        let formula = load_lemma(lemmas_dir, &lemma_name)?;
+        let formula = lemmas.formula(&lemma_name)?;
         all_lemmas.insert(
             lemma_name.clone(),
             LemmaInfo {
@@ -114,26 +132,80 @@ pub fn precompute_lemmas(
         );
     }
 
+    let mut twee_index = FormulaIndex::new();
+    for (i, twee_dep) in all_twee.iter().enumerate() {
+        twee_index.insert(&twee_dep.formula, i);
+    }
+
+    let subsumed_by = subsumed_lemmas(&all_lemmas);
+
     Ok(PrecomputedLemmas {
         all_lemmas,
         all_twee,
-        lemmas,
+        twee_index,
+        subsumed_by,
+        lemmas: formulas,
     })
 }
 
-/// Append a formula as an axiom to a file
-pub fn append_as_axiom(file_path: &str, formula: &str, lemma_name: &str) {
-    let indented_formula = formula
-        .lines()
-        .map(|line| format!("    {}", line))
-        .collect::<Vec<_>>()
-        .join("\n");
+/// The lemmas that are strict instances of another lemma, each with the
+/// first of its generalizations in `lemma_order`. Lemmas are only matched
+/// against the lemmas with the same head symbols.
+pub fn subsumed_lemmas(all_lemmas: &BTreeMap<String, LemmaInfo>) -> BTreeMap<String, String> {
+    let mut generality = SubsumptionIndex::new();
+    for (name, info) in all_lemmas {
+        generality.insert(&info.formula, name);
+    }
+    let mut subsumed_by = BTreeMap::new();
+    for (name, info) in all_lemmas {
+        let stronger = generality
+            .strict_generalizations(&info.formula)
+            .into_iter()
+            .filter(|other| **other != name)
+            .min_by_key(|other| lemma_order(other));
+        if let Some(other) = stronger {
+            subsumed_by.insert(name.clone(), other.to_string());
+        }
+    }
+    subsumed_by
+}
 
-    let axiom_text = format!("\nfof({}, axiom,\n{}\n).\n", lemma_name, indented_formula);
+/// Order of lemmas of all modes: by mode, e.g. "history_lemma", then by
+/// number, then by name. Each mode numbers its lemmas on its own, so numbers
+/// alone do not order lemmas of different modes.
+pub fn lemma_order(lemma_name: &str) -> (&str, u32, &str) {
+    let mode = lemma_name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '_');
+    (mode, lemma_index(lemma_name), lemma_name)
+}
 
-    let current_content = fs::read_to_string(file_path).expect("Failed to read tmp input file");
-    fs::write(file_path, format!("{}\n{}", current_content, axiom_text))
-        .expect("Failed to append axiom");
+/// Numeric index of a lemma name, e.g. 47 for "history_lemma_0047"
+pub fn lemma_index(lemma_name: &str) -> u32 {
+    lemma_name
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .collect::<String>()
+        .parse::<u32>()
+        .unwrap_or(u32::MAX)
+}
+
+/// Append a formula as an axiom to an in-memory problem, quantifying its free variables
+pub fn append_as_axiom(
+    problem: &mut String,
+    formula: &str,
+    lemma_name: &str,
+) -> Result<(), String> {
+    // parse and re-print so free variables get quantified and the output is valid TPTP
+    let parsed = parse_formula(formula)
+        .map_err(|e| format!("Cannot parse formula of {}: {}", lemma_name, e))?;
+    let mut file = TptpFile::parse(problem)?;
+    file.push(AnnotatedFormula::new(
+        "fof",
+        lemma_name,
+        "axiom",
+        &parsed.universal_closure().to_string(),
+    ));
+    *problem = file.to_string();
+    Ok(())
 }
 
 /// Determine the actual lemma variant (history, single, abstract) by checking the proofs folder
@@ -157,9 +229,7 @@ pub fn select_actual_lemma(proofs_dir: &str, lemma_name: &str) -> Option<String>
 
         for suf in &suffixes {
             let filename_with_ext = format!("{}{}", base_name, suf);
-            let proof_path = format!("{}/{}", proofs_dir, &filename_with_ext);
-
-            if Path::new(&proof_path).exists() {
+            if Path::new(proofs_dir).join(&filename_with_ext).exists() {
                 // strip the ".proof" extension for the returned value
                 return Some(
                     filename_with_ext
@@ -175,53 +245,34 @@ pub fn select_actual_lemma(proofs_dir: &str, lemma_name: &str) -> Option<String>
     None
 }
 
-/// Extract all Twee-generated lemmas from a proof output
+/// Extract all Twee-generated lemmas from a proof output, as
+/// (`twee_lemma_<n>`, formula with its variables quantified). The proof is
+/// read statement by statement, so a lemma wrapped over several lines comes
+/// out whole. A proof that cannot be read has no lemmas.
 pub fn extract_twee_lemmas(twee_output: &str) -> Vec<(String, String)> {
-    let lemma_re = Regex::new(r"(?s)Lemma\s+(\d+):\s*(.*?)Proof:").unwrap();
-    let mut result = Vec::new();
-
-    for cap in lemma_re.captures_iter(twee_output) {
-        let index: usize = cap[1].parse().unwrap();
-        let body = cap[2].trim();
-
-        // Take last line as the formula
-        let mut formula_line = body
-            .lines()
-            .last()
-            .map(|l| l.trim().to_string())
-            .unwrap_or_else(|| body.to_string());
-
-        if formula_line.ends_with('.') {
-            formula_line.pop();
+    let statements = match parse_twee_proof(twee_output) {
+        Ok(statements) => statements,
+        Err(err) => {
+            println!("[WARN] Cannot read the Twee lemmas: {}", err);
+            return Vec::new();
         }
-
-        // Detect variables (all uppercase words)
-        let var_re = Regex::new(r"\b([A-Z][0-9]*)\b").unwrap();
-        let mut vars: BTreeSet<String> = BTreeSet::new();
-        for cap_var in var_re.captures_iter(&formula_line) {
-            vars.insert(cap_var[1].to_string());
-        }
-        let var_list = vars.into_iter().collect::<Vec<_>>().join(", ");
-
-        let lemma_name = format!("twee_lemma_{:02}", index);
-
-        // Build only the body (wrap in universal quantifiers if variables exist)
-        let formula_body = if var_list.is_empty() {
-            formula_line
-        } else {
-            format!("! [{}] : ({})", var_list, formula_line)
-        };
-
-        result.push((lemma_name, formula_body));
-    }
-
-    result
+    };
+    statements
+        .into_iter()
+        .filter(|statement| statement.kind == TweeKind::Lemma)
+        .map(|statement| {
+            (
+                format!("twee_lemma_{:02}", statement.number),
+                statement.formula.universal_closure().to_string(),
+            )
+        })
+        .collect()
 }
 
 /// Parse used lemmas from twee output and return their formulas
 pub fn parse_used_lemmas(
     twee_output: &str,
-    lemmas_dir: &str,
+    lemmas: &LemmaStore,
     proofs_dir: &str,
 ) -> Result<Vec<(String, String)>, String> {
     let axiom_re = Regex::new(r"Axiom\s+\d+\s+\(([^)]+)\)\s*:\s*(.+)").unwrap();
@@ -233,7 +284,7 @@ pub fn parse_used_lemmas(
         // axioms
         if let Some(cap) = axiom_re.captures(line) {
             let name = cap[1].to_string();
-            let formula = cap[2].trim().to_string();
+            let formula = cap[2].trim().trim_end_matches('.').to_string();
 
             if name.starts_with('a') {
                 used.push((name.clone(), formula.clone()));
@@ -241,13 +292,7 @@ pub fn parse_used_lemmas(
             }
 
             if name.starts_with("lemma_") {
-                if let Some(actual) = select_actual_lemma(proofs_dir, &name) {
-                    let clean = strip_prover_suffix(&actual);
-                    let dep_formula = load_lemma(lemmas_dir, &clean)?;
-                    used.push((clean, dep_formula));
-                } else {
-                    println!("[WARN] No proof file found for {}", name);
-                }
+                used.extend(used_lemma(&name, &formula, lemmas, proofs_dir)?);
                 continue;
             }
 
@@ -263,15 +308,10 @@ pub fn parse_used_lemmas(
         // goals
         if let Some(cap) = goal_re.captures(line) {
             let name = cap[1].to_string();
+            let formula = cap[2].trim().trim_end_matches('.');
 
             if name.starts_with("lemma_") {
-                if let Some(actual) = select_actual_lemma(proofs_dir, &name) {
-                    let clean = strip_prover_suffix(&actual);
-                    let dep_formula = load_lemma(lemmas_dir, &clean)?;
-                    used.push((clean, dep_formula));
-                } else {
-                    println!("[WARN] No proof file found for {}", name);
-                }
+                used.extend(used_lemma(&name, formula, lemmas, proofs_dir)?);
             }
         }
     }
@@ -280,162 +320,52 @@ pub fn parse_used_lemmas(
     Ok(used)
 }
 
-/// Load a specific lemma (single, abstract, history) and extract its formula body
-pub fn load_lemma(lemmas_dir: &str, lemma_name: &str) -> Result<String, String> {
-    let subdir = if lemma_name.starts_with("single_lemma_") {
-        "single"
-    } else if lemma_name.starts_with("history_lemma_") {
-        "history"
-    } else if lemma_name.starts_with("abstract_lemma_") {
-        "abstract"
-    } else {
-        return Err(format!("[ERROR] Unknown lemma type for {}", lemma_name));
-    };
-
-    // strip prover suffix if present (_twee, _vampire, _egg)
-    let lemma_name = strip_prover_suffix(lemma_name);
-
-    let file_path = Path::new(lemmas_dir)
-        .join(subdir)
-        .join(format!("{}.p", lemma_name));
-    if !file_path.exists() {
-        return Err(format!(
-            "[ERROR] File not found for lemma {} at {:?}",
-            lemma_name, file_path
-        ));
+/// The lemma with a Phase 1 proof that the Twee axiom or goal `name` with
+/// `formula` stands for: the lemma of its number, otherwise one with the same
+/// formula up to renaming, such as the lemma it was dropped as an alias of
+fn used_lemma(
+    name: &str,
+    formula: &str,
+    lemmas: &LemmaStore,
+    proofs_dir: &str,
+) -> Result<Option<(String, String)>, String> {
+    if let Some(actual) = select_actual_lemma(proofs_dir, name) {
+        let clean = strip_prover_suffix(&actual);
+        let dep_formula = lemmas.formula(&clean)?;
+        return Ok(Some((clean, dep_formula)));
     }
-
-    let file_path_str = file_path
-        .to_str()
-        .ok_or_else(|| format!("[ERROR] Failed to convert path to string: {:?}", file_path))?;
-
-    // determine internal tptp name
-    let internal_name = if lemma_name.starts_with("single_lemma_")
-        || lemma_name.starts_with("history_lemma_")
-        || lemma_name.starts_with("abstract_lemma_")
-    {
-        lemma_name
-            .replace("single_lemma_", "conjecture_")
-            .replace("history_lemma_", "conjecture_")
-            .replace("abstract_lemma_", "conjecture_")
-    } else {
-        return Err(format!("[ERROR] Unknown lemma type for {}", lemma_name));
-    };
-
-    // pass internal name to extract function
-    extract_tptp_formula_body(file_path_str, &internal_name)
-        .map(|body| body.trim().to_string())
-        .ok_or_else(|| {
-            format!(
-                "[ERROR] Formula for {} not found inside file {:?}",
-                internal_name, file_path
-            )
-        })
-}
-
-/// Extract formula body for a given lemma from a TPTP file
-pub fn extract_tptp_formula_body(file_path: &str, lemma: &str) -> Option<String> {
-    let content = fs::read_to_string(file_path).ok()?;
-    let mut lines_iter = content.lines();
-
-    while let Some(line) = lines_iter.next() {
-        if line.contains(lemma) {
-            let mut formula_lines = Vec::new();
-            if line.contains(").") {
-                let start = line.find(',').unwrap_or(0);
-                let mut body = &line[start..];
-                // Remove the trailing ")."
-                if let Some(pos) = body.rfind(").") {
-                    body = &body[..pos];
-                }
-                formula_lines.push(body.trim().to_string());
-            } else {
-                while let Some(formula_line) = lines_iter.next() {
-                    let trimmed = formula_line.trim();
-                    if trimmed.ends_with(").") {
-                        let body = &trimmed[..trimmed.len() - 2]; // remove ")."
-                        formula_lines.push(body.to_string());
-                        break;
-                    } else {
-                        formula_lines.push(trimmed.to_string());
-                    }
-                }
-            }
-            let formula_body = formula_lines.join(" ");
-            return Some(formula_body);
+    let proved = lemmas
+        .find_alpha_equivalent(formula)
+        .into_iter()
+        .find(|known| {
+            select_actual_lemma(proofs_dir, known).is_some_and(|actual| actual != *known)
+        });
+    match proved {
+        Some(known) => Ok(Some((known.to_string(), lemmas.formula(known)?))),
+        None => {
+            println!("[WARN] No proof file found for {}", name);
+            Ok(None)
         }
     }
-    None
 }
 
-/// Promote a root lemma to conjecture in a TPTP file.
+/// Promote a root lemma to conjecture in an in-memory TPTP problem.
 ///
-/// - Removes any existing conjecture blocks.
-/// - Finds the `fof` block with name == `root_lemma` and role == `axiom`,
-///   and changes it to role `conjecture`.
+/// - Removes any existing conjectures.
+/// - Changes the formula named `root_lemma` from role `axiom` to `conjecture`.
 /// - Leaves all other axioms unchanged.
-pub fn promote_axiom_to_conjecture(path: &str, root_lemma: &str) -> Result<(), String> {
-    let content = fs::read_to_string(path).map_err(|e| format!("read error: {}", e))?;
-
-    // regex to match top-level fof/cnf blocks
-    let r_fof = Regex::new(r"(?is)^\s*fof\s*\(\s*([^,]+)\s*,\s*([^,]+)\s*,(.*?)\)\s*\.\s*$")
-        .map_err(|e| format!("regex error: {}", e))?;
-
-    let mut out_blocks = Vec::new();
-
-    for block in content.split_terminator(").\n") {
-        let block_trimmed = block.trim();
-        if block_trimmed.is_empty() {
-            continue;
-        }
-        let block_full = format!("{}).\n", block_trimmed);
-
-        if let Some(cap) = r_fof.captures(&block_full) {
-            let name = cap.get(1).map(|m| m.as_str()).unwrap_or_default();
-            let role = cap.get(2).map(|m| m.as_str()).unwrap_or_default();
-
-            // remove existing conjectures entirely
-            if role.to_lowercase().contains("conjecture") {
-                continue;
-            }
-
-            // if this is the root lemma, promote to conjecture
-            if name == root_lemma && role.to_lowercase() == "axiom" {
-                let formula = cap.get(3).map(|m| m.as_str()).unwrap_or_default();
-                let promoted = format!("fof({}, conjecture, {}).\n", name, formula);
-                out_blocks.push(promoted);
-                continue;
-            }
-
-            // otherwise, keep as-is
-            out_blocks.push(block_full);
-        } else {
-            // non-fof block, keep as-is
-            out_blocks.push(block_full);
-        }
-    }
-
-    // Write back
-    fs::write(path, out_blocks.join("\n")).map_err(|e| format!("write error: {}", e))?;
-
-    Ok(())
+pub fn promote_axiom_to_conjecture(content: &str, root_lemma: &str) -> Result<String, String> {
+    let mut file = TptpFile::parse(content)?;
+    file.retain(|f| !f.is_conjecture());
+    file.change_role(root_lemma, "axiom", "conjecture");
+    Ok(file.to_string())
 }
 
-pub fn create_tmp_copy(input_file: &str) -> Result<String, String> {
-    let tmp_dir = Path::new("../benchmarks/tmp");
-
-    // ensure temp directory exists
-    fs::create_dir_all(tmp_dir).map_err(|e| format!("Failed to create temp dir: {}", e))?;
-
-    let input_path = Path::new(input_file);
-
-    let file_name = input_path.file_name().ok_or("Invalid input filename")?;
-
-    let tmp_path: PathBuf = tmp_dir.join(file_name);
-
-    fs::copy(input_path, &tmp_path).map_err(|e| format!("Failed to copy temp input: {}", e))?;
-
-    Ok(tmp_path.to_str().ok_or("Bad temp filename")?.to_string())
+/// Drop every conjecture of an in-memory TPTP problem, leaving its axioms
+pub fn remove_conjectures(content: &str) -> Result<String, String> {
+    let mut file = TptpFile::parse(content)?;
+    file.retain(|f| !f.is_conjecture());
+    Ok(file.to_string())
 }
 
 /// For a list of dependency lemma names, load all existing proofs
@@ -451,7 +381,7 @@ pub fn load_all_dependency_proofs(
         // try to find a matching file: e.g. "single_lemma_0047_twee.proof"
         let actual_file = select_actual_lemma(proofs_dir, dep)
             .ok_or_else(|| format!("No proof file found for dependency {}", dep))?;
-        let path = format!("{}/{}.proof", proofs_dir, actual_file);
+        let path = Path::new(proofs_dir).join(format!("{}.proof", actual_file));
 
         // read file
         let text = std::fs::read_to_string(&path)
@@ -468,7 +398,7 @@ pub fn load_all_dependency_proofs(
             .to_string();
 
         // count steps
-        let steps = proof_length(&prover, &text);
+        let (text, steps) = lemma_proof(dep, &prover, text)?;
 
         result.push((dep.clone(), prover, steps, text));
     }
@@ -476,8 +406,21 @@ pub fn load_all_dependency_proofs(
     Ok(result)
 }
 
+/// The proof of `lemma` as it enters a minimized proof, with the steps it
+/// adds. Output that is not a proof, kept with `--assume-unproved`, makes the
+/// lemma a hypothesis: it is marked as one and counts one step, the assumption.
+pub fn lemma_proof(lemma: &str, prover: &str, proof: String) -> Result<(String, usize), String> {
+    if is_non_theorem(&proof) {
+        println!("   [WARN] {} is not proved, assuming it", lemma);
+        Ok((hypothesis(lemma, &proof), 1))
+    } else {
+        let steps = proof_length(prover, &proof)?;
+        Ok((proof, steps))
+    }
+}
+
 /// Strips the prover suffix (_twee, _vampire, _egg) from a lemma name if present
-fn strip_prover_suffix(lemma_name: &str) -> String {
+pub fn strip_prover_suffix(lemma_name: &str) -> String {
     let suffixes = ["_twee", "_vampire", "_egg"];
     for suf in &suffixes {
         if lemma_name.ends_with(suf) {
@@ -486,3 +429,146 @@ fn strip_prover_suffix(lemma_name: &str) -> String {
     }
     lemma_name.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // as Twee prints it: keyed axioms, and lemmas and goals wrapped over lines
+    const TWEE_PROOF: &str = "\
+Here is the input problem:
+  Axiom 1 (a1): mult(e, X) = X.
+  Goal 1 (conjecture_0004): mult(inv(a), mult(a, b)) = b.
+
+% SZS status Unsatisfiable
+
+% SZS output start CNFRefutation
+
+Axiom 1 (a1): mult(e, X) = X.
+Axiom 2 (left_inverse): mult(inv(X), X) = e.
+Axiom 3 (associativity): mult(mult(X, Y), Z) = mult(X, mult(Y, Z)).
+
+Lemma 4: mult(inv(X), mult(X, Y)) = Y.
+Proof:
+  mult(inv(X), mult(X, Y))
+= { by axiom 3 (associativity) R->L }
+  mult(mult(inv(X), X), Y)
+= { by axiom 2 (left_inverse) }
+  mult(e, Y)
+= { by axiom 1 (a1) }
+  Y
+
+Lemma 5: mult(inv(inv(X)), mult(inv(X),
+  mult(X, Y))) = mult(X, Y).
+Proof:
+  mult(inv(inv(X)), mult(inv(X),
+    mult(X, Y)))
+= { by lemma 4 }
+  mult(X, Y)
+
+Goal 1 (conjecture_0004): mult(inv(a), mult(a, b)) = b.
+Proof:
+  mult(inv(a), mult(a, b))
+= { by lemma 4 }
+  b
+
+% SZS output end CNFRefutation
+
+RESULT: Unsatisfiable (the axioms are contradictory).
+";
+
+    #[test]
+    fn twee_lemmas_are_read_whole() {
+        let lemmas = extract_twee_lemmas(TWEE_PROOF);
+        assert_eq!(
+            lemmas,
+            vec![
+                (
+                    "twee_lemma_04".to_string(),
+                    "! [X, Y] : (mult(inv(X),mult(X,Y)) = Y)".to_string()
+                ),
+                (
+                    "twee_lemma_05".to_string(),
+                    "! [X, Y] : (mult(inv(inv(X)),mult(inv(X),mult(X,Y))) = mult(X,Y))".to_string()
+                ),
+            ]
+        );
+        // the formulas parse back, e.g. as axioms of the next problem
+        for (_, formula) in &lemmas {
+            parse_formula(formula).unwrap();
+        }
+        assert!(extract_twee_lemmas("RESULT: GaveUp (couldn't find a proof).").is_empty());
+    }
+
+    #[test]
+    fn instances_are_subsumed_across_modes() {
+        let info = |formula: &str| LemmaInfo {
+            formula: formula.to_string(),
+            dependencies: Vec::new(),
+        };
+        let all_lemmas = BTreeMap::from([
+            ("single_lemma_0001".to_string(), info("(op(e, e) = e)")),
+            (
+                "single_lemma_0004".to_string(),
+                info("! [X, Y] : (op(X, Y) = X)"),
+            ),
+            (
+                "history_lemma_0009".to_string(),
+                info("! [A, B] : (op(A, B) = A)"),
+            ),
+            (
+                "abstract_lemma_0002".to_string(),
+                info("! [X] : (inv(X) = X)"),
+            ),
+        ]);
+        let subsumed_by = subsumed_lemmas(&all_lemmas);
+        // the equivalent lemmas of two modes do not replace each other, and
+        // the instance goes to the first of them, whatever their numbers
+        assert_eq!(
+            subsumed_by,
+            BTreeMap::from([(
+                "single_lemma_0001".to_string(),
+                "history_lemma_0009".to_string()
+            )])
+        );
+        assert!(lemma_order("history_lemma_0009") < lemma_order("single_lemma_0004"));
+        assert!(lemma_order("single_lemma_0004") < lemma_order("single_lemma_0010"));
+    }
+
+    #[test]
+    fn appended_axioms_quantify_variables_of_every_prover_style() {
+        let cases = [
+            // Vampire
+            (
+                "mult(X0,mult(X1,X0)) = X1",
+                "! [X0, X1] : (mult(X0,mult(X1,X0)) = X1)",
+            ),
+            // Twee
+            (
+                "mult(X, mult(Y, Z)) = Y",
+                "! [X, Y, Z] : (mult(X,mult(Y,Z)) = Y)",
+            ),
+            // renamed as in the alpha-equivalence checks
+            (
+                "op(V0, V1) = op(V1, V0)",
+                "! [V0, V1] : (op(V0,V1) = op(V1,V0))",
+            ),
+            // partly quantified: the leading quantifier takes the rest
+            ("! [X] : mult(X, Y) = Y", "! [X, Y] : (mult(X,Y) = Y)"),
+            // Skolem constants stay constants
+            ("mult(sK0, X) = X", "! [X] : (mult(sK0,X) = X)"),
+            ("mult(sK0, e) = sK0", "mult(sK0,e) = sK0"),
+        ];
+        for (formula, expected) in cases {
+            let mut problem = "fof(a1, axiom, mult(e, e) = e).\n".to_string();
+            append_as_axiom(&mut problem, formula, "lemma_0001").unwrap();
+            let file = TptpFile::parse(&problem).unwrap();
+            assert_eq!(
+                file.formula("lemma_0001").unwrap().body_line(),
+                expected,
+                "{}",
+                formula
+            );
+        }
+    }
+}
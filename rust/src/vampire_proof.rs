@@ -0,0 +1,662 @@
+use crate::artifact::write_atomic;
+use crate::formula::{fof, parse_formula, skolems_to_variables};
+use crate::process::ProcessRunner;
+use crate::proof_step::{print_tptp, ProofRole, ProofStep};
+use crate::prover_wrapper::{proves_quickly, RecordingRunner};
+use crate::run_config::RunConfig;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+
+/// Core inference rules counted as proof steps
+pub const INFERENCE_KEYWORDS: [&str; 4] = [
+    "demodulation",
+    "superposition",
+    "resolution",
+    "trivial inequality removal",
+];
+
+/// Role of a step in a Vampire refutation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepRole {
+    Input,
+    /// the negated conjecture, and in the superposition view of a proof
+    /// (`superpose::superposition_steps_from`) every step derived from it
+    NegatedConjecture,
+    Inference,
+    /// AVATAR splitting bookkeeping (`avatar split clause`, `sat splitting`, ...)
+    Splitting,
+}
+
+/// One line of a Vampire proof, e.g. "12. op(X0,X1) = X0 [superposition 3,5]"
+#[derive(Debug, Clone)]
+pub struct VampireStep {
+    /// Vampire's own step number
+    pub index: usize,
+    pub formula: String,
+    /// inference rule, e.g. "superposition" or "negated conjecture"
+    pub rule: String,
+    /// Vampire numbers of the premises
+    pub premises: Vec<usize>,
+    pub role: StepRole,
+}
+
+impl VampireStep {
+    /// Whether this step is one of the core inferences counted in the proof length
+    pub fn is_inference(&self) -> bool {
+        self.role == StepRole::Inference && INFERENCE_KEYWORDS.iter().any(|k| self.rule.contains(k))
+    }
+}
+
+/// Parse a single proof line, returns `None` for comments and non-step lines
+pub fn parse_vampire_line(line: &str) -> Option<VampireStep> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('%') {
+        return None;
+    }
+
+    // leading step number
+    let (num, rest) = line.split_once('.')?;
+    let index = num.trim().parse::<usize>().ok()?;
+    let rest = rest.trim();
+
+    // annotation is the last bracket group, the formula itself may contain "! [X0] :"
+    let (formula, annotation) = match rest.rfind('[') {
+        Some(pos) if rest.ends_with(']') => (rest[..pos].trim(), &rest[pos + 1..rest.len() - 1]),
+        _ => (rest, ""),
+    };
+
+    let mut rule_words = Vec::new();
+    let mut premises = Vec::new();
+    for token in annotation.split([',', ' ']).filter(|t| !t.is_empty()) {
+        match token.parse::<usize>() {
+            Ok(n) => premises.push(n),
+            Err(_) => rule_words.push(token),
+        }
+    }
+    let rule = rule_words.join(" ");
+
+    let role = match rule.as_str() {
+        "input" => StepRole::Input,
+        "negated conjecture" => StepRole::NegatedConjecture,
+        r if r.starts_with("avatar") || r.contains("splitting") => StepRole::Splitting,
+        _ => StepRole::Inference,
+    };
+
+    Some(VampireStep {
+        index,
+        formula: formula.to_string(),
+        rule,
+        premises,
+        role,
+    })
+}
+
+/// Parse all steps of a Vampire proof in the order they appear
+pub fn parse_vampire_steps(proof: &str) -> Vec<VampireStep> {
+    proof.lines().filter_map(parse_vampire_line).collect()
+}
+
+/// Read the steps of the Vampire proof in `proof_file`, splitting resolved
+pub fn read_vampire_steps(proof_file: &str) -> Result<Vec<VampireStep>, String> {
+    let content = fs::read_to_string(proof_file)
+        .map_err(|e| format!("Failed to read {}: {}", proof_file, e))?;
+    resolve_splitting(&parse_vampire_steps(&content))
+}
+
+/// Remove AVATAR splitting steps, pointing premises that went through them at
+/// the real clauses they were derived from. Clauses that only hold under a split
+/// assumption (`C <- (1)`) cannot be used on their own, so such proofs are rejected.
+pub fn resolve_splitting(steps: &[VampireStep]) -> Result<Vec<VampireStep>, String> {
+    let by_index: BTreeMap<usize, &VampireStep> = steps.iter().map(|s| (s.index, s)).collect();
+
+    // follow splitting steps back to the clauses they come from
+    fn real_premises(
+        idx: usize,
+        by_index: &BTreeMap<usize, &VampireStep>,
+        seen: &mut Vec<usize>,
+        out: &mut Vec<usize>,
+    ) {
+        if seen.contains(&idx) {
+            return;
+        }
+        seen.push(idx);
+        match by_index.get(&idx) {
+            Some(step) if step.role == StepRole::Splitting => {
+                for p in &step.premises {
+                    real_premises(*p, by_index, seen, out);
+                }
+            }
+            _ => {
+                if !out.contains(&idx) {
+                    out.push(idx);
+                }
+            }
+        }
+    }
+
+    let mut resolved = Vec::new();
+    for step in steps {
+        if step.role == StepRole::Splitting {
+            continue;
+        }
+        if step.formula.contains("<-") {
+            return Err(format!(
+                "Step {} depends on an AVATAR split assumption; rerun Vampire with --avatar off",
+                step.index
+            ));
+        }
+        let mut premises = Vec::new();
+        for p in &step.premises {
+            real_premises(*p, &by_index, &mut Vec::new(), &mut premises);
+        }
+        resolved.push(VampireStep {
+            premises,
+            ..step.clone()
+        });
+    }
+    Ok(resolved)
+}
+
+/// Vampire numbers of all steps derived from the negated conjecture
+pub fn negated_conjecture_chain(steps: &[VampireStep]) -> BTreeSet<usize> {
+    let mut chain = BTreeSet::new();
+    // Vampire lists premises before their conclusions
+    for step in steps {
+        if step.role == StepRole::NegatedConjecture
+            || step.premises.iter().any(|p| chain.contains(p))
+        {
+            chain.insert(step.index);
+        }
+    }
+    chain
+}
+
+/// A refutation needs to be turned around if any core inference works on the negated conjecture
+pub fn needs_turnaround(steps: &[VampireStep]) -> bool {
+    let chain = negated_conjecture_chain(steps);
+    steps
+        .iter()
+        .any(|s| s.is_inference() && chain.contains(&s.index))
+}
+
+/// The refutation `steps` (splitting resolved) turned into a forward proof of
+/// the conjecture. Steps that do not use the negated conjecture are kept as
+/// they are. Every step `s` derived from it is replaced by its negation
+/// `t<n>`, with its Skolem constants generalized to variables: if `c` was
+/// derived from `s` and the axiom side steps `a..`, then `not c` and `a..`
+/// give `not s` (the contrapositive), down to the negated conjecture, whose
+/// negation is the conjecture. A step with two premises from the negated
+/// conjecture only gives a disjunction this way and is rejected. Each turned
+/// step is re-proved from its premises through `runner`, and the
+/// transformation fails if one of them is not.
+pub fn turn_around(
+    runner: &dyn ProcessRunner,
+    config: &RunConfig,
+    steps: &[VampireStep],
+) -> Result<Vec<ProofStep>, String> {
+    let chain = negated_conjecture_chain(steps);
+    let is_false = |step: &VampireStep| step.formula.trim() == "$false";
+    // the conjecture itself, premise of the negated conjecture, is what the proof ends with
+    let conjecture: BTreeSet<usize> = steps
+        .iter()
+        .filter(|s| s.role == StepRole::NegatedConjecture)
+        .flat_map(|s| s.premises.iter().copied())
+        .filter(|p| !chain.contains(p))
+        .collect();
+
+    let mut forward = Vec::new();
+    for step in steps {
+        if !chain.contains(&step.index) && !conjecture.contains(&step.index) {
+            forward.push(ProofStep::from_vampire(step)?);
+        }
+    }
+
+    // the negations of the chain, with one Skolem mapping for all of them
+    let turned: Vec<&VampireStep> = steps
+        .iter()
+        .filter(|s| chain.contains(&s.index) && !is_false(s))
+        .collect();
+    let negations = turned
+        .iter()
+        .map(|s| {
+            let formula = s.formula.split(" <- ").next().unwrap_or_default();
+            parse_formula(formula)
+                .map(|f| f.negate())
+                .map_err(|e| format!("Cannot parse step {}: {}", s.index, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let (negations, _) = skolems_to_variables(&negations)
+        .map_err(|e| format!("Cannot turn the refutation around: {}", e))?;
+
+    // the first step derived from each chain step
+    let mut child: BTreeMap<usize, &VampireStep> = BTreeMap::new();
+    for step in steps.iter().filter(|s| chain.contains(&s.index)) {
+        let from_chain: Vec<usize> = step
+            .premises
+            .iter()
+            .copied()
+            .filter(|p| chain.contains(p))
+            .collect();
+        if from_chain.len() > 1 {
+            return Err(format!(
+                "Step {} uses the negated conjecture through several premises ({:?}), its contrapositive is a disjunction",
+                step.index, from_chain
+            ));
+        }
+        for p in from_chain {
+            child.entry(p).or_insert(step);
+        }
+    }
+
+    // conclusions before the steps they were derived from, so premises come first
+    for (step, negation) in turned.iter().zip(negations).rev() {
+        let (rule, premises) = match child.get(&step.index) {
+            Some(c) => {
+                let mut premises: Vec<String> = Vec::new();
+                if !is_false(c) {
+                    premises.push(format!("t{}", c.index));
+                }
+                premises.extend(
+                    c.premises
+                        .iter()
+                        .filter(|p| !chain.contains(p))
+                        .map(|p| format!("f{}", p)),
+                );
+                (format!("contrapositive of {}", c.rule), premises)
+            }
+            None => {
+                return Err(format!(
+                    "Nothing is derived from step {} of the negated conjecture, its negation has no premises",
+                    step.index
+                ))
+            }
+        };
+        forward.push(ProofStep {
+            name: format!("t{}", step.index),
+            formula: negation.universal_closure(),
+            rule,
+            premises,
+            role: ProofRole::Inference,
+        });
+    }
+    check_turned_steps(runner, config, &forward)?;
+    Ok(forward)
+}
+
+/// Re-prove every turned step `t<n>` of `forward` from its premises
+fn check_turned_steps(
+    runner: &dyn ProcessRunner,
+    config: &RunConfig,
+    forward: &[ProofStep],
+) -> Result<(), String> {
+    let by_name: BTreeMap<&str, &ProofStep> =
+        forward.iter().map(|s| (s.name.as_str(), s)).collect();
+    for step in forward.iter().filter(|s| s.name.starts_with('t')) {
+        let mut problem = String::new();
+        for premise in &step.premises {
+            let premise = by_name.get(premise.as_str()).ok_or_else(|| {
+                format!("Premise {} of {} is not in the proof", premise, step.name)
+            })?;
+            problem.push_str(&fof(&premise.name, "axiom", &premise.formula));
+        }
+        problem.push_str(&fof(&step.name, "conjecture", &step.formula));
+        if !proves_quickly(runner, config, &problem) {
+            return Err(format!(
+                "Turned step {} ({}) was not re-proved from {}",
+                step.name,
+                step.formula,
+                if step.premises.is_empty() {
+                    "no premises".to_string()
+                } else {
+                    step.premises.join(", ")
+                }
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Read a Vampire refutation and write it as a forward TPTP proof of the
+/// conjecture, see `turn_around`. A refutation that never reasons from the
+/// negated conjecture is written as it is, see `emit_forward_proof`.
+pub fn turnaround(
+    config: &RunConfig,
+    proof_file: &str,
+    output_file: &str,
+) -> Result<String, String> {
+    let steps = read_vampire_steps(proof_file)?;
+    if steps.is_empty() {
+        return Err(format!("No proof steps found in {}", proof_file));
+    }
+
+    if !needs_turnaround(&steps) {
+        write_atomic(output_file, emit_forward_proof(&steps)?)
+            .map_err(|e| format!("Failed to write {}: {}", output_file, e))?;
+        return Ok(format!(
+            "[RESULT] {} does not reason from the negated conjecture, written as it is to {}",
+            proof_file, output_file
+        ));
+    }
+
+    let forward = turn_around(&RecordingRunner::local(config), config, &steps)?;
+    write_atomic(output_file, print_tptp(&forward))
+        .map_err(|e| format!("Failed to write {}: {}", output_file, e))?;
+    Ok(format!(
+        "[RESULT] Forward proof with {} steps written to {}",
+        forward.len(),
+        output_file
+    ))
+}
+
+/// Render the refutation as a DOT graph (premise -> conclusion). The negated
+/// conjecture chain is filled, the first proof step is drawn in blue and the
+/// pivot (first inference on the negated conjecture) in red.
+pub fn refutation_dot(steps: &[VampireStep]) -> String {
+    let chain = negated_conjecture_chain(steps);
+    let first_step = steps.iter().find(|s| s.is_inference()).map(|s| s.index);
+    let pivot = steps
+        .iter()
+        .find(|s| s.is_inference() && chain.contains(&s.index))
+        .map(|s| s.index);
+
+    let mut dot = String::from("digraph refutation {\n    node [shape=box];\n");
+    for step in steps {
+        let mut attrs = vec![format!(
+            "label=\"{}. {}\\n[{}]\"",
+            step.index,
+            step.formula.replace('"', "\\\""),
+            step.rule
+        )];
+        if chain.contains(&step.index) {
+            attrs.push("style=filled".to_string());
+            attrs.push("fillcolor=lightyellow".to_string());
+        }
+        if Some(step.index) == pivot {
+            attrs.push("color=red".to_string());
+            attrs.push("penwidth=3".to_string());
+        } else if Some(step.index) == first_step {
+            attrs.push("color=blue".to_string());
+            attrs.push("penwidth=3".to_string());
+        }
+        dot.push_str(&format!("    s{} [{}];\n", step.index, attrs.join(", ")));
+        for p in &step.premises {
+            dot.push_str(&format!("    s{} -> s{};\n", p, step.index));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Read a Vampire proof and write its refutation graph in DOT format
+pub fn write_refutation_dot(proof_file: &str, output_file: &str) -> Result<String, String> {
+    let content = fs::read_to_string(proof_file)
+        .map_err(|e| format!("Failed to read {}: {}", proof_file, e))?;
+    let steps = parse_vampire_steps(&content);
+    if steps.is_empty() {
+        return Err(format!("No proof steps found in {}", proof_file));
+    }
+    write_atomic(output_file, refutation_dot(&steps))
+        .map_err(|e| format!("Failed to write {}: {}", output_file, e))?;
+    Ok(format!(
+        "[RESULT] Refutation graph written to {}",
+        output_file
+    ))
+}
+
+/// TPTP inference name of a Vampire rule, e.g. "trivial_inequality_removal"
+pub fn inference_name(rule: &str) -> String {
+    rule.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+/// Renumber steps from 1 so that every premise comes before its conclusion.
+/// Premise references are rewritten to the new numbers, a premise outside `steps` is an error.
+pub fn renumber_topologically(steps: &[VampireStep]) -> Result<Vec<VampireStep>, String> {
+    let by_index: BTreeMap<usize, &VampireStep> = steps.iter().map(|s| (s.index, s)).collect();
+    let mut renumbered: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut order: Vec<usize> = Vec::new();
+
+    // depth-first, premises before conclusions
+    fn visit(
+        idx: usize,
+        by_index: &BTreeMap<usize, &VampireStep>,
+        on_path: &mut Vec<usize>,
+        renumbered: &mut BTreeMap<usize, usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), String> {
+        if renumbered.contains_key(&idx) {
+            return Ok(());
+        }
+        if on_path.contains(&idx) {
+            return Err(format!("Cyclic dependency at step {}", idx));
+        }
+        on_path.push(idx);
+        for premise in &by_index[&idx].premises {
+            if !by_index.contains_key(premise) {
+                return Err(format!(
+                    "Step {} uses step {}, which is not in the proof",
+                    idx, premise
+                ));
+            }
+            visit(*premise, by_index, on_path, renumbered, order)?;
+        }
+        on_path.pop();
+        order.push(idx);
+        renumbered.insert(idx, order.len());
+        Ok(())
+    }
+
+    for step in steps {
+        visit(
+            step.index,
+            &by_index,
+            &mut Vec::new(),
+            &mut renumbered,
+            &mut order,
+        )?;
+    }
+
+    Ok(order
+        .iter()
+        .map(|idx| {
+            let step = by_index[idx];
+            VampireStep {
+                index: renumbered[idx],
+                premises: step.premises.iter().map(|p| renumbered[p]).collect(),
+                ..step.clone()
+            }
+        })
+        .collect())
+}
+
+/// Print steps as a forward TPTP derivation with closed formulas and
+/// `inference(...)` sources referring to the step names `f1`, `f2`, ...
+pub fn emit_forward_proof(steps: &[VampireStep]) -> Result<String, String> {
+    let steps = renumber_topologically(&resolve_splitting(steps)?)?
+        .iter()
+        .map(ProofStep::from_vampire)
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(print_tptp(&steps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{DryRunRunner, ProcessOutput};
+    use crate::test_support::ScratchDir;
+
+    #[test]
+    fn test_parse_input() {
+        let step = parse_vampire_line("1. ! [X0,X1] : op(X0,X1) = X0 [input]").unwrap();
+        assert_eq!(step.index, 1);
+        assert_eq!(step.formula, "! [X0,X1] : op(X0,X1) = X0");
+        assert_eq!(step.role, StepRole::Input);
+        assert!(step.premises.is_empty());
+    }
+
+    #[test]
+    fn test_parse_inference() {
+        let step = parse_vampire_line("14. sK0 != sK1 [trivial inequality removal 12,13]").unwrap();
+        assert_eq!(step.formula, "sK0 != sK1");
+        assert_eq!(step.rule, "trivial inequality removal");
+        assert_eq!(step.premises, vec![12, 13]);
+        assert!(step.is_inference());
+    }
+
+    #[test]
+    fn test_skip_comments() {
+        let proof = "% SZS output start Proof\n2. ~! [X0] : X0 = op(X0,X0) [negated conjecture 1]\n% SZS output end Proof";
+        let steps = parse_vampire_steps(proof);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].role, StepRole::NegatedConjecture);
+        assert!(!steps[0].is_inference());
+    }
+
+    #[test]
+    fn test_splitting() {
+        let proof = "5. op(X0,X1) = X0 | sK0 = sK1 [superposition 1,2]\n6. op(X0,X1) = X0 [avatar component clause 5]\n7. sK0 != op(sK0,sK0) [superposition 6,3]";
+        let steps = resolve_splitting(&parse_vampire_steps(proof)).unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[1].premises, vec![5, 3]);
+
+        let conditional = "8. sK0 != sK0 <- (1) [superposition 6,3]";
+        assert!(resolve_splitting(&parse_vampire_steps(conditional)).is_err());
+    }
+
+    #[test]
+    fn test_needs_turnaround() {
+        let proof = "1. ! [X0,X1] : op(X0,X1) = X0 [input]\n2. ~! [X0] : X0 = op(X0,X0) [negated conjecture 1]\n3. sK0 != op(sK0,sK0) [skolemisation 2]\n4. sK0 != sK0 [superposition 3,1]";
+        let steps = parse_vampire_steps(proof);
+        assert_eq!(negated_conjecture_chain(&steps), BTreeSet::from([2, 3, 4]));
+        assert!(needs_turnaround(&steps));
+        assert!(!needs_turnaround(&steps[..3]));
+    }
+
+    /// Answers every step check with a proof, except for the conjecture `rejected`
+    fn step_checker(rejected: &'static str) -> DryRunRunner {
+        DryRunRunner::replying(move |spec| {
+            let input = spec.input.as_deref().unwrap_or_default();
+            if input.contains(&format!("fof({}, conjecture,", rejected)) {
+                ProcessOutput::success("% SZS status Timeout")
+            } else {
+                ProcessOutput::success("% SZS status Theorem")
+            }
+        })
+    }
+
+    #[test]
+    fn test_turn_around() {
+        let proof = "1. ! [X0,X1] : op(X0,X1) = X0 [input]\n\
+                     2. ! [X0] : X0 = op(X0,X0) [input]\n\
+                     3. ~! [X0] : X0 = op(X0,X0) [negated conjecture 2]\n\
+                     4. sK0 != op(sK0,sK0) [skolemisation 3]\n\
+                     5. sK0 != sK0 [superposition 4,1]\n\
+                     6. $false [trivial inequality removal 5]";
+        let config = RunConfig::default();
+        let runner = step_checker("none");
+        let forward = turn_around(&runner, &config, &parse_vampire_steps(proof)).unwrap();
+        let summary: Vec<(&str, String, String)> = forward
+            .iter()
+            .map(|s| (s.name.as_str(), s.formula.to_string(), s.premises.join(",")))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "f1",
+                    "! [X0, X1] : (op(X0,X1) = X0)".to_string(),
+                    String::new()
+                ),
+                ("t5", "! [SK0] : (SK0 = SK0)".to_string(), String::new()),
+                (
+                    "t4",
+                    "! [SK0] : (SK0 = op(SK0,SK0))".to_string(),
+                    "t5,f1".to_string()
+                ),
+                (
+                    "t3",
+                    "! [X0] : (X0 = op(X0,X0))".to_string(),
+                    "t4".to_string()
+                ),
+            ]
+        );
+        assert_eq!(forward[2].rule, "contrapositive of superposition");
+        // the conjecture is proved, not assumed
+        assert!(print_tptp(&forward).contains("fof(t3, plain,"));
+        assert!(!print_tptp(&forward).contains("fof(f2,"));
+
+        let disjunction = "1. ~! [X0] : p(X0) [negated conjecture]\n\
+                           2. ~p(sK0) [skolemisation 1]\n3. ~q(sK0) [skolemisation 1]\n\
+                           4. r(sK0) [resolution 2,3]\n5. $false [resolution 4]";
+        assert!(turn_around(&runner, &config, &parse_vampire_steps(disjunction)).is_err());
+    }
+
+    #[test]
+    fn turned_steps_are_reproved() {
+        let proof = "1. ! [X0,X1] : op(X0,X1) = X0 [input]\n\
+                     2. ! [X0] : X0 = op(X0,X0) [input]\n\
+                     3. ~! [X0] : X0 = op(X0,X0) [negated conjecture 2]\n\
+                     4. sK0 != op(sK0,sK0) [skolemisation 3]\n\
+                     5. sK0 != sK0 [superposition 4,1]\n\
+                     6. $false [trivial inequality removal 5]";
+        let steps = parse_vampire_steps(proof);
+
+        // one check per turned step, each from the premises it names
+        let config = RunConfig::default();
+        let runner = step_checker("none");
+        turn_around(&runner, &config, &steps).unwrap();
+        let problems: Vec<String> = runner
+            .calls()
+            .into_iter()
+            .map(|spec| spec.input.unwrap_or_default())
+            .collect();
+        assert_eq!(problems.len(), 3);
+        assert!(problems[1].contains("fof(t5, axiom,") && problems[1].contains("fof(f1, axiom,"));
+        assert!(problems[1].contains("fof(t4, conjecture,"));
+
+        let err = turn_around(&step_checker("t4"), &config, &steps).unwrap_err();
+        assert!(err.contains("t4") && err.contains("t5, f1"), "{}", err);
+
+        // a chain step nothing is derived from cannot be turned around
+        let dead_end = "1. ~! [X0] : p(X0) [negated conjecture]\n\
+                        2. ~p(sK0) [skolemisation 1]\n3. q(a) [input]";
+        let err = turn_around(&runner, &config, &parse_vampire_steps(dead_end)).unwrap_err();
+        assert!(err.contains("step 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_refutation_dot() {
+        let proof = "1. ! [X0,X1] : op(X0,X1) = X0 [input]\n2. op(X0,X0) = X0 [superposition 1,1]\n3. sK0 != op(sK0,sK0) [negated conjecture 1]\n4. sK0 != sK0 [superposition 3,2]";
+        let dot = refutation_dot(&parse_vampire_steps(proof));
+        assert!(dot.contains("s2 [label=\"2. op(X0,X0) = X0\\n[superposition]\", color=blue"));
+        assert!(dot.contains("fillcolor=lightyellow, color=red"));
+        assert!(dot.contains("s3 -> s4;"));
+    }
+
+    #[test]
+    fn test_renumber_and_emit() {
+        let proof = "7. sK0 != op(sK0,sK0) [superposition 3,1]\n1. ! [X0,X1] : op(X0,X1) = X0 [input]\n3. sK0 != op(sK0,op(sK0,sK0)) [negated conjecture 1]";
+        let steps = renumber_topologically(&parse_vampire_steps(proof)).unwrap();
+        let order: Vec<(usize, Vec<usize>)> = steps
+            .iter()
+            .map(|s| (s.index, s.premises.clone()))
+            .collect();
+        assert_eq!(order, vec![(1, vec![]), (2, vec![1]), (3, vec![2, 1])]);
+
+        let emitted = emit_forward_proof(&parse_vampire_steps(proof)).unwrap();
+        assert!(emitted.contains("fof(f1, axiom,\n    ! [X0, X1] : (op(X0,X1) = X0))."));
+        assert!(emitted.contains("inference(superposition,[status(thm)],[f2,f1])"));
+
+        // a refutation that never uses the negated conjecture is written as it is
+        let dir = ScratchDir::new("turnaround");
+        let proof_file = dir.write("vampire.out", proof);
+        let output = dir.path().join("forward.p").to_string_lossy().to_string();
+        assert!(turnaround(&RunConfig::default(), &proof_file, &output)
+            .unwrap()
+            .contains("as it is"));
+        assert_eq!(fs::read_to_string(&output).unwrap(), emitted);
+
+        let dangling = "1. p(a) [input]\n2. q(a) [resolution 1,5]";
+        let err = renumber_topologically(&parse_vampire_steps(dangling)).unwrap_err();
+        assert!(err.contains("step 5"), "{}", err);
+    }
+}
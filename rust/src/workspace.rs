@@ -0,0 +1,262 @@
+use crate::artifact::write_atomic;
+use crate::process::{ProcessRunner, ProcessSpec};
+use crate::provenance::prover_version;
+use crate::prover_wrapper::backend;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// The workspace around the rust directory: the lemma, proof and output
+// directories the pipeline writes to, the prover binaries in `bin/` and the
+// configuration `krympa.toml`. `init` sets it up in a fresh checkout.
+
+/// Directories the pipeline expects, relative to the workspace root
+pub const LAYOUT: [&str; 12] = [
+    "lemmas/single",
+    "lemmas/history",
+    "lemmas/abstract",
+    "proofs/vampire_tmp",
+    "proofs/twee_tmp",
+    "proofs/egg_tmp",
+    "output",
+    "output/inputs",
+    "output/untyped",
+    "output/preprocessed",
+    "bin",
+    "tmp",
+];
+
+pub const CONFIG_FILE: &str = "krympa.toml";
+
+/// Time allowed for downloading one prover binary
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(600);
+
+const STARTER_CONFIG: &str = r#"# Krympa workspace configuration, written by `frankenstein init`.
+#
+# Provers are looked up as bin/<name>, then on the PATH. For each prover
+# below, `init` downloads a missing binary from `url`, and checks the binary
+# against `sha256` (hex) when given.
+
+[provers.vampire]
+# url = "https://example.org/vampire"
+# sha256 = "..."
+
+[provers.twee]
+# url = "https://example.org/twee"
+# sha256 = "..."
+"#;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub provers: BTreeMap<String, ProverConfig>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct ProverConfig {
+    /// where to download the binary from if it is missing
+    pub url: Option<String>,
+    /// expected SHA-256 of the binary
+    pub sha256: Option<String>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Config::parse(&text).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+    }
+}
+
+/// The OCaml lemma extractor of the workspace at `root`, where `build.rs`
+/// installs it
+pub fn ocaml_parser(root: &Path) -> PathBuf {
+    root.join("rust")
+        .join("ocaml_install")
+        .join(format!("tptp_parser{}", env::consts::EXE_SUFFIX))
+}
+
+/// Hex SHA-256 of a file
+pub fn sha256_file(path: &Path) -> Result<String, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(format!("{:x}", Sha256::digest(&bytes)))
+}
+
+/// Set up the workspace at `root`: create the missing directories, write a
+/// starter configuration if there is none, and check every configured prover,
+/// downloading it with `runner` if it is missing and has a `url`. Fails after
+/// the whole check if a prover is missing or does not match its `sha256`.
+pub fn init(root: &Path, runner: &dyn ProcessRunner) -> Result<String, String> {
+    for dir in LAYOUT {
+        let path = root.join(dir);
+        if !path.is_dir() {
+            fs::create_dir_all(&path)
+                .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+            println!("[INFO] Created {}", path.display());
+        }
+    }
+
+    let config_file = root.join(CONFIG_FILE);
+    if !config_file.exists() {
+        write_atomic(&config_file, STARTER_CONFIG)
+            .map_err(|e| format!("Failed to write {}: {}", config_file.display(), e))?;
+        println!("[INFO] Wrote {}", config_file.display());
+    }
+    let config = Config::load(&config_file)?;
+
+    let mut problems = Vec::new();
+    for (name, prover) in &config.provers {
+        match check_prover(root, name, prover, runner) {
+            Ok(path) => println!(
+                "[INFO] {}: {} ({})",
+                name,
+                path.display(),
+                prover_version(&path)
+            ),
+            Err(err) => {
+                println!("[ERROR] {}", err);
+                problems.push(name.clone());
+            }
+        }
+        if let Err(err) = backend(name) {
+            println!("[WARN] {}: the length of its proofs cannot be told", err);
+        }
+    }
+
+    let parser = ocaml_parser(root);
+    if !parser.is_file() {
+        println!(
+            "[WARN] {} is missing; `cargo build` builds it from ocaml/ with dune",
+            parser.display()
+        );
+    }
+
+    if !problems.is_empty() {
+        return Err(format!(
+            "Workspace {} is set up, but these provers are not usable: {}",
+            root.display(),
+            problems.join(", ")
+        ));
+    }
+    Ok(format!("Workspace {} is ready", root.display()))
+}
+
+/// Path of prover `name`, in `root/bin` or on the PATH, after downloading it
+/// if needed and checking its hash
+fn check_prover(
+    root: &Path,
+    name: &str,
+    prover: &ProverConfig,
+    runner: &dyn ProcessRunner,
+) -> Result<PathBuf, String> {
+    let bundled = root
+        .join("bin")
+        .join(format!("{}{}", name, env::consts::EXE_SUFFIX));
+    let path = if bundled.is_file() {
+        bundled
+    } else if let Ok(path) = which::which(name) {
+        path
+    } else if let Some(url) = &prover.url {
+        download(url, &bundled, runner)?;
+        bundled
+    } else {
+        return Err(format!(
+            "{} is neither in {} nor on the PATH; put it there or give its url in {}",
+            name,
+            root.join("bin").display(),
+            CONFIG_FILE
+        ));
+    };
+
+    if let Some(expected) = &prover.sha256 {
+        let actual = sha256_file(&path)?;
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            return Err(format!(
+                "{} has SHA-256 {}, but {} expects {}",
+                path.display(),
+                actual,
+                CONFIG_FILE,
+                expected
+            ));
+        }
+    }
+    Ok(path)
+}
+
+fn download(url: &str, target: &Path, runner: &dyn ProcessRunner) -> Result<(), String> {
+    println!("[INFO] Downloading {} to {}", url, target.display());
+    let target_str = target.to_string_lossy().to_string();
+    let spec =
+        ProcessSpec::new("curl", DOWNLOAD_TIMEOUT).args(&["-fsSL", "-o", target_str.as_str(), url]);
+    let output = runner.run(&spec)?;
+    if !output.succeeded() {
+        let _ = fs::remove_file(target);
+        return Err(format!(
+            "Failed to download {}: {}",
+            url,
+            output.stderr.trim()
+        ));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(target, fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("Failed to make {} executable: {}", target.display(), e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{DryRunRunner, ProcessOutput};
+    use crate::test_support::ScratchDir;
+
+    #[test]
+    fn init_creates_the_layout_and_checks_provers() {
+        let scratch = ScratchDir::new("workspace");
+        let root = scratch.path();
+
+        // a fresh workspace gets the layout and the starter configuration
+        let _ = init(root, &DryRunRunner::new());
+        assert!(LAYOUT.iter().all(|dir| root.join(dir).is_dir()));
+        let starter = Config::load(&root.join(CONFIG_FILE)).unwrap();
+        assert!(starter.provers.contains_key("vampire"));
+        // the parser is looked up in the workspace, not in the current directory
+        assert!(ocaml_parser(root).starts_with(root.join("rust").join("ocaml_install")));
+
+        fs::write(root.join("bin").join("krympa_test_prover"), "binary").unwrap();
+        let expected = sha256_file(&root.join("bin").join("krympa_test_prover")).unwrap();
+        let config = format!(
+            "[provers.krympa_test_prover]\nsha256 = \"{}\"\n\n[provers.krympa_missing_prover]\nurl = \"https://example.org/prover\"\n",
+            expected
+        );
+        fs::write(root.join(CONFIG_FILE), config).unwrap();
+        // stands in for curl, writing the file it is asked to download
+        let runner = DryRunRunner::replying(|spec| {
+            fs::write(&spec.args[2], "downloaded").unwrap();
+            ProcessOutput::success("")
+        });
+        assert!(init(root, &runner).is_ok());
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].program, "curl");
+        assert!(calls[0]
+            .args
+            .contains(&"https://example.org/prover".to_string()));
+        assert!(root.join("bin").join("krympa_missing_prover").is_file());
+
+        // a binary that does not match its hash is reported
+        fs::write(root.join("bin").join("krympa_test_prover"), "other").unwrap();
+        let err = init(root, &runner).unwrap_err();
+        assert!(err.contains("krympa_test_prover"));
+    }
+}
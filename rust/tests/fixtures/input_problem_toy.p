@@ -0,0 +1,4 @@
+fof(a1, axiom, ! [X] : mult(e, X) = X).
+fof(a2, axiom, ! [X] : mult(inv(X), X) = e).
+fof(a3, axiom, ! [X, Y, Z] : mult(mult(X, Y), Z) = mult(X, mult(Y, Z))).
+fof(goal, conjecture, mult(inv(a), mult(a, b)) = b).
@@ -0,0 +1,370 @@
+//! End-to-end run of `collect` and `minimize` on a toy problem. Vampire and Twee are replaced by
+//! the shell scripts in `tests/fixtures/bin`; the lemma extractor is the OCaml parser built by
+//! build.rs.
+#![cfg(unix)]
+
+use frankenstein::collect_report::CollectReport;
+use frankenstein::summary::{self, LemmaStatus};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+/// A throwaway copy of the layout the pipeline expects around its working directory:
+/// `bin/`, `lemmas/`, `proofs/` and `output/` next to `rust/`, which holds `ocaml_install/`
+struct Sandbox {
+    root: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Sandbox {
+        let root =
+            std::env::temp_dir().join(format!("frankenstein-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        for dir in ["bin", "lemmas", "proofs", "output", "rust/ocaml_install"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        // fs::copy keeps the executable bit of the mocks
+        for prover in ["vampire", "twee"] {
+            fs::copy(
+                Path::new(FIXTURES).join("bin").join(prover),
+                root.join("bin").join(prover),
+            )
+            .unwrap();
+        }
+        fs::copy(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("ocaml_install/tptp_parser"),
+            root.join("rust/ocaml_install/tptp_parser"),
+        )
+        .expect("the OCaml parser is built by build.rs");
+        for file in ["input_problem_toy.p", "vampire_proof_toy.out"] {
+            fs::copy(Path::new(FIXTURES).join(file), root.join(file)).unwrap();
+        }
+
+        Sandbox { root }
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        let output = Command::new(env!("CARGO_BIN_EXE_frankenstein"))
+            .args(args)
+            .current_dir(self.root.join("rust"))
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "frankenstein {:?} failed:\n{}\n{}",
+            args,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    }
+
+    fn collect(&self) -> Output {
+        self.run(&[
+            "collect",
+            "../input_problem_toy.p",
+            "--use-existing-proof",
+            "../vampire_proof_toy.out",
+        ])
+    }
+
+    fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.root.join(path))
+            .unwrap_or_else(|e| panic!("cannot read {}: {}", path, e))
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+#[test]
+fn collect_proves_every_lemma_with_twee() {
+    let sandbox = Sandbox::new("collect");
+    sandbox.collect();
+
+    let summary = summary::parse(&sandbox.read("output/summary_toy.json")).unwrap();
+
+    // the superposition, demodulation and final superposition steps of the refutation
+    let keys: Vec<u32> = summary.keys().copied().collect();
+    assert_eq!(keys, vec![1, 2, 3]);
+
+    for (key, entry) in &summary {
+        assert_eq!(entry.file, format!("single_lemma_{:04}", key));
+        assert_eq!(entry.prover, "twee");
+        assert_eq!(entry.status, LemmaStatus::Proved);
+        assert!(entry.proof.contains("SZS status Theorem"));
+    }
+
+    let report = CollectReport::load(&sandbox.root.join("output/collect_toy.json")).unwrap();
+    assert_eq!(report.extracted["single"], 3);
+    assert_eq!(report.proved.len(), 3);
+    assert!(report.proved.iter().all(|l| l.prover == "twee"));
+    assert!(report.failed.is_empty());
+}
+
+#[test]
+fn minimize_works_on_a_single_mode() {
+    let sandbox = Sandbox::new("modes");
+    sandbox.run(&[
+        "collect",
+        "../input_problem_toy.p",
+        "--use-existing-proof",
+        "../vampire_proof_toy.out",
+        "--modes",
+        "single",
+    ]);
+
+    let report = CollectReport::load(&sandbox.root.join("output/collect_toy.json")).unwrap();
+    assert_eq!(report.extracted.keys().collect::<Vec<_>>(), vec!["single"]);
+    assert!(!sandbox.root.join("lemmas/history").exists());
+    assert!(!sandbox.root.join("lemmas/abstract").exists());
+
+    sandbox.run(&["shorten", "../input_problem_toy.p"]);
+    let output = sandbox.run(&["minimize", "../input_problem_toy.p"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Minimization complete"));
+}
+
+#[test]
+fn minimize_writes_the_combined_proof() {
+    let sandbox = Sandbox::new("minimize");
+    sandbox.collect();
+    let output = sandbox.run(&["minimize", "../input_problem_toy.p"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("[RESULT] Root lemma: single_lemma_"));
+    assert!(stdout.contains("Minimization complete"));
+
+    let proof = sandbox.read("output/proof_toy.out");
+    assert!(proof.starts_with("% --- provenance ---"));
+    assert!(proof.contains("% input: ../input_problem_toy.p (hash "));
+    assert!(proof.contains("% === Input Problem ==="));
+    assert!(proof.contains("fof(goal, conjecture"));
+}
+
+#[test]
+fn minimize_replays_the_picked_candidate() {
+    let sandbox = Sandbox::new("replay");
+    sandbox.collect();
+    sandbox.run(&["minimize", "../input_problem_toy.p"]);
+
+    let trace: serde_json::Value =
+        serde_json::from_str(&sandbox.read("output/trace_toy.json")).unwrap();
+    let best = trace["best"]
+        .as_u64()
+        .expect("trace records the picked candidate") as usize;
+    let recorded = trace["candidates"][best]["steps"].as_u64().unwrap();
+
+    let output = sandbox.run(&[
+        "minimize",
+        "../input_problem_toy.p",
+        "--replay",
+        "../output/trace_toy.json",
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&format!("[RESULT] Replayed steps: {}", recorded)));
+    assert!(!stdout.contains("Replay differs"));
+
+    sandbox.read("output/replay_toy/proof.out");
+    sandbox.read("output/replay_toy/dag.txt");
+    // every Twee run is kept
+    let kept = fs::read_dir(sandbox.root.join("output/replay_toy"))
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .any(|e| e.file_name().to_string_lossy().starts_with("01_"));
+    assert!(kept);
+}
+
+#[test]
+fn minimize_reads_lemmas_and_proofs_from_given_dirs() {
+    let sandbox = Sandbox::new("dirs");
+    sandbox.collect();
+    // another workspace's directories, given by absolute path
+    let lemmas = sandbox.root.join("elsewhere/lemmas");
+    let proofs = sandbox.root.join("elsewhere/proofs");
+    fs::create_dir_all(sandbox.root.join("elsewhere")).unwrap();
+    fs::rename(sandbox.root.join("lemmas"), &lemmas).unwrap();
+    fs::rename(sandbox.root.join("proofs"), &proofs).unwrap();
+
+    let output = sandbox.run(&[
+        "minimize",
+        "../input_problem_toy.p",
+        "--lemmas-dir",
+        &lemmas.to_string_lossy(),
+        "--proofs-dir",
+        &proofs.to_string_lossy(),
+    ]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Minimization complete"));
+    sandbox.read("output/proof_toy.out");
+}
+
+#[test]
+fn collect_reads_single_lemmas_from_the_vampire_steps() {
+    let sandbox = Sandbox::new("steps");
+    let output = sandbox.run(&[
+        "collect",
+        "../input_problem_toy.p",
+        "--use-existing-proof",
+        "../vampire_proof_toy.out",
+        "--modes",
+        "vampire-steps",
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Extracted 2 single lemmas from the Vampire proof steps"));
+
+    // the unit equalities of steps 6 and 7; the disequality of step 8 is left out
+    let report = CollectReport::load(&sandbox.root.join("output/collect_toy.json")).unwrap();
+    assert_eq!(report.extracted.get("single"), Some(&2));
+    let lemma = sandbox.read("lemmas/single/single_lemma_0002.p");
+    assert!(lemma.contains("fof(conjecture_0002, conjecture"));
+}
+
+#[test]
+fn truncated_summaries_are_reported_as_such() {
+    let sandbox = Sandbox::new("truncated");
+    sandbox.collect();
+    // every artifact was renamed into place
+    for dir in ["output", "lemmas/single", "proofs"] {
+        for entry in fs::read_dir(sandbox.root.join(dir)).unwrap() {
+            let name = entry.unwrap().file_name().to_string_lossy().to_string();
+            assert!(!name.ends_with(".tmp"), "{}/{} left behind", dir, name);
+        }
+    }
+
+    let summary = sandbox.read("output/summary_toy.json");
+    fs::write(
+        sandbox.root.join("output/summary_toy.json"),
+        &summary[..summary.len() / 2],
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_frankenstein"))
+        .args(["lemmas", "../input_problem_toy.p", "list"])
+        .current_dir(sandbox.root.join("rust"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is truncated"));
+}
+
+#[test]
+fn problem_names_are_made_safe_for_file_names() {
+    let sandbox = Sandbox::new("names");
+    fs::copy(
+        sandbox.root.join("input_problem_toy.p"),
+        sandbox.root.join("input_problem_toy #1.p"),
+    )
+    .unwrap();
+    sandbox.run(&[
+        "collect",
+        "../input_problem_toy #1.p",
+        "--use-existing-proof",
+        "../vampire_proof_toy.out",
+    ]);
+
+    let outputs: Vec<String> = fs::read_dir(sandbox.root.join("output"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    let summary = outputs
+        .iter()
+        .find(|name| name.starts_with("summary_toy__1-") && name.ends_with(".json"))
+        .unwrap_or_else(|| panic!("no summary in {:?}", outputs));
+    assert!(!summary.contains(' ') && !summary.contains('#'));
+}
+
+#[test]
+fn minimize_asserts_the_length_of_the_final_proof() {
+    let sandbox = Sandbox::new("assert_steps");
+    sandbox.collect();
+    sandbox.run(&[
+        "minimize",
+        "../input_problem_toy.p",
+        "--assert-max-steps",
+        "1000",
+    ]);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_frankenstein"))
+        .args([
+            "minimize",
+            "../input_problem_toy.p",
+            "--assert-max-steps",
+            "0",
+        ])
+        .current_dir(sandbox.root.join("rust"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("more than the asserted 0"));
+    // the proof is written all the same, to look at what got longer
+    assert!(sandbox
+        .read("output/proof_toy.out")
+        .contains("% === Input Problem ==="));
+}
+
+#[test]
+fn vampire_runs_without_a_proof_report_their_status() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let sandbox = Sandbox::new("no_proof");
+    let vampire = sandbox.root.join("bin/vampire");
+    fs::write(
+        &vampire,
+        "#!/bin/sh\necho '% SZS status CounterSatisfiable for toy'\n",
+    )
+    .unwrap();
+    fs::set_permissions(&vampire, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_frankenstein"))
+        .args(["run_vampire", "../input_problem_toy.p", "1"])
+        .current_dir(sandbox.root.join("rust"))
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("[RESULT] SZS status: CounterSatisfiable"),
+        "{}",
+        stdout
+    );
+    assert!(!sandbox.root.join("output/vampire_proof_toy.out").exists());
+}
+
+#[test]
+fn long_vampire_proofs_are_not_timeouts() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let sandbox = Sandbox::new("long_proof");
+    // a proof of some 200KB, more than a pipe holds
+    let vampire = sandbox.root.join("bin/vampire");
+    fs::write(
+        &vampire,
+        "#!/bin/sh\n\
+         echo '% SZS status Theorem for toy'\n\
+         echo '% SZS output start Proof for toy'\n\
+         yes '1. mult(e,X0) = X0 [input]' | head -n 8000\n\
+         echo '% SZS output end Proof for toy'\n",
+    )
+    .unwrap();
+    fs::set_permissions(&vampire, fs::Permissions::from_mode(0o755)).unwrap();
+
+    sandbox.run(&["run_vampire", "../input_problem_toy.p", "1"]);
+    let proof = sandbox.read("output/vampire_proof_toy.out");
+    assert!(proof.len() > 200_000, "{} bytes", proof.len());
+    assert!(proof.contains("% SZS output end Proof for toy"));
+}
+
+#[test]
+fn preprocess_rejects_a_path_without_a_file_name() {
+    let sandbox = Sandbox::new("preprocess_dir");
+    let output = Command::new(env!("CARGO_BIN_EXE_frankenstein"))
+        .args(["preprocess", ".."])
+        .current_dir(sandbox.root.join("rust"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(".. does not name a problem file"));
+}